@@ -0,0 +1,77 @@
+//! Decoding of JSON messages as the sources do it. Compare the parsers with
+//! `cargo bench --bench json_decode` and `cargo bench --bench json_decode --features simd-json`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+
+use denormalized::datasource::kafka::{MessageDecoder, MessageFormat};
+use denormalized::utils::json_parse::parse_json;
+
+const MESSAGES: usize = 1_000;
+
+/// Flat messages with a few short fields, like sensor readings
+fn flat_messages() -> Vec<Vec<u8>> {
+    (0..MESSAGES)
+        .map(|i| {
+            format!(
+                r#"{{"occurred_at_ms": {}, "sensor_name": "sensor_{}", "reading": {}}}"#,
+                1_715_000_000_000_i64 + i as i64,
+                i % 10,
+                i as f64 * 0.37
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+/// Larger messages with nested objects, arrays and escaped strings
+fn nested_messages() -> Vec<Vec<u8>> {
+    (0..MESSAGES)
+        .map(|i| {
+            format!(
+                r#"{{"event_time": {}, "auction": {{"id": {}, "item_name": "item \"{}\"", "description": "{}", "initial_bid": {}, "reserve": {}, "category": {}}}, "bidders": [{}, {}, {}], "extra": {{"url": "https://example.com/auction/{}?ref=feed&page=2", "channel": "Google", "tags": ["new", "featured", "ending-soon"]}}}}"#,
+                1_715_000_000_000_i64 + i as i64,
+                1000 + i,
+                i,
+                "lorem ipsum dolor sit amet ".repeat(4),
+                i * 10,
+                i * 25,
+                i % 7,
+                i,
+                i + 1,
+                i + 2,
+                i
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+fn bench_json_decode(c: &mut Criterion) {
+    for (name, messages) in [("flat", flat_messages()), ("nested", nested_messages())] {
+        let bytes: usize = messages.iter().map(Vec::len).sum();
+        let mut group = c.benchmark_group(format!("json_decode/{name}"));
+        group.throughput(Throughput::Bytes(bytes as u64));
+
+        group.bench_function("parse_json", |b| {
+            b.iter(|| {
+                for message in &messages {
+                    black_box(parse_json(message).unwrap());
+                }
+            })
+        });
+
+        let mut decoder = MessageDecoder::new(MessageFormat::Json);
+        group.bench_function("message_decoder", |b| {
+            b.iter(|| {
+                for message in &messages {
+                    black_box(block_on(decoder.decode(message)).unwrap());
+                }
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_json_decode);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{Field, Schema};
+use datafusion::common::{DataFusionError, Result};
+use datafusion::logical_expr::{EmitTo, GroupsAccumulator};
+
+use crate::state_backend::backend::StateBackend;
+
+/// Point in time copy of a grouped aggregation: the group keys and, for every accumulator,
+/// its intermediate state. Row `i` of every array belongs to the same group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupsSnapshot {
+    pub group_keys: Vec<ArrayRef>,
+    pub states: Vec<Vec<ArrayRef>>,
+}
+
+impl GroupsSnapshot {
+    pub fn num_groups(&self) -> usize {
+        self.group_keys
+            .first()
+            .or_else(|| self.states.iter().flatten().next())
+            .map_or(0, |array| array.len())
+    }
+
+    /// All arrays as a single batch, keys first, in Arrow IPC stream format
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut fields = vec![];
+        let mut columns = vec![];
+        for (i, key) in self.group_keys.iter().enumerate() {
+            fields.push(Field::new(
+                format!("key_{i}"),
+                key.data_type().clone(),
+                true,
+            ));
+            columns.push(key.clone());
+        }
+        for (i, state) in self.states.iter().enumerate() {
+            for (j, array) in state.iter().enumerate() {
+                fields.push(Field::new(
+                    format!("state_{i}_{j}"),
+                    array.data_type().clone(),
+                    true,
+                ));
+                columns.push(array.clone());
+            }
+        }
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+        batch_to_ipc(&batch)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let batch = batch_from_ipc(bytes)?;
+        let mut snapshot = GroupsSnapshot {
+            group_keys: vec![],
+            states: vec![],
+        };
+        for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+            let name = field.name();
+            if name.starts_with("key_") {
+                snapshot.group_keys.push(column.clone());
+            } else if let Some(position) = name.strip_prefix("state_") {
+                let accumulator = position
+                    .split('_')
+                    .next()
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .ok_or_else(|| {
+                        DataFusionError::Internal(format!("Invalid snapshot column {name}"))
+                    })?;
+                if snapshot.states.len() <= accumulator {
+                    snapshot.states.resize(accumulator + 1, vec![]);
+                }
+                snapshot.states[accumulator].push(column.clone());
+            } else {
+                return Err(DataFusionError::Internal(format!(
+                    "Invalid snapshot column {name}"
+                )));
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Checkpoint the snapshot as `key` of `namespace`
+    pub fn save(&self, backend: &dyn StateBackend, namespace: &str, key: Vec<u8>) -> Result<()> {
+        backend.put_state(namespace, key, self.to_bytes()?)
+    }
+
+    /// The snapshot [`save`](Self::save)d as `key` of `namespace`, if any
+    pub fn load(backend: &dyn StateBackend, namespace: &str, key: Vec<u8>) -> Result<Option<Self>> {
+        backend
+            .get_state(namespace, key)?
+            .map(|bytes| Self::from_bytes(&bytes))
+            .transpose()
+    }
+}
+
+/// Intermediate state of every group of `acc`, one array per state field.
+///
+/// `GroupsAccumulator` can only hand out its state by emitting it, so the state is merged
+/// straight back afterwards and the accumulator carries on as if nothing happened.
+pub fn snapshot_groups_accumulator(
+    acc: &mut dyn GroupsAccumulator,
+    num_groups: usize,
+) -> Result<Vec<ArrayRef>> {
+    let state = acc.state(EmitTo::All)?;
+    restore_groups_accumulator(
+        acc,
+        &state,
+        &(0..num_groups).collect::<Vec<_>>(),
+        num_groups,
+    )?;
+    Ok(state)
+}
+
+/// Merge a state taken with [`snapshot_groups_accumulator`] into `acc`, row `i` of the state
+/// going to group `group_indices[i]`.
+pub fn restore_groups_accumulator(
+    acc: &mut dyn GroupsAccumulator,
+    state: &[ArrayRef],
+    group_indices: &[usize],
+    total_num_groups: usize,
+) -> Result<()> {
+    if group_indices.is_empty() {
+        return Ok(());
+    }
+    acc.merge_batch(state, group_indices, None, total_num_groups)
+}
+
+pub(crate) fn batch_to_ipc(batch: &RecordBatch) -> Result<Vec<u8>> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(writer.into_inner()?)
+}
+
+pub(crate) fn batch_from_ipc(bytes: &[u8]) -> Result<RecordBatch> {
+    let mut reader = StreamReader::try_new(Cursor::new(bytes), None)?;
+    match reader.next() {
+        Some(batch) => Ok(batch?),
+        None => Err(DataFusionError::Internal(
+            "Encoded state holds no batch".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::DataType;
+    use datafusion::functions_aggregate::sum::sum_udaf;
+    use datafusion::physical_expr::expressions::col;
+    use datafusion::physical_expr_common::aggregate::AggregateExprBuilder;
+
+    #[test]
+    fn snapshot_and_restore_sum() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, true)]));
+        let expr = AggregateExprBuilder::new(sum_udaf(), vec![col("v", &schema)?])
+            .schema(schema)
+            .alias("total")
+            .build()?;
+
+        let mut acc = expr.create_groups_accumulator()?;
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3]));
+        acc.update_batch(&[values], &[0, 1, 0], None, 2)?;
+
+        let snapshot = GroupsSnapshot {
+            group_keys: vec![],
+            states: vec![snapshot_groups_accumulator(acc.as_mut(), 2)?],
+        };
+        let snapshot = GroupsSnapshot::from_bytes(&snapshot.to_bytes()?)?;
+        assert_eq!(snapshot.num_groups(), 2);
+
+        let mut restored = expr.create_groups_accumulator()?;
+        restore_groups_accumulator(restored.as_mut(), &snapshot.states[0], &[0, 1], 2)?;
+
+        let more: ArrayRef = Arc::new(Int64Array::from(vec![10]));
+        acc.update_batch(&[more.clone()], &[1], None, 2)?;
+        restored.update_batch(&[more], &[1], None, 2)?;
+
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![4, 12]));
+        assert_eq!(&acc.evaluate(EmitTo::All)?, &expected);
+        assert_eq!(&restored.evaluate(EmitTo::All)?, &expected);
+        Ok(())
+    }
+}
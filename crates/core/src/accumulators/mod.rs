@@ -1,2 +1,9 @@
-pub(crate) mod serializable_accumulator;
+pub mod groups_snapshot;
+pub mod overflow;
+pub mod registry;
+pub mod serializable_accumulator;
 mod serialize;
+pub mod state_serde;
+
+#[cfg(test)]
+mod roundtrip_tests;
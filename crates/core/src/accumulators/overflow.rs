@@ -0,0 +1,399 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{i256, DataType, Decimal256Type, Field, DECIMAL256_MAX_PRECISION};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{exec_err, plan_err, DFSchema, Result, ScalarValue};
+use datafusion::logical_expr::expr::AggregateFunction;
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::utils::format_state_name;
+use datafusion::logical_expr::{
+    Accumulator, AggregateUDF, AggregateUDFImpl, Expr, ExprSchemable, Signature, Volatility,
+};
+
+/// What `SUM` and `COUNT` do once their result no longer fits its type. Without a policy
+/// integer sums wrap around silently, which long running aggregations eventually run into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Fail the aggregation
+    Error,
+    /// Stay at the largest or smallest value of the type
+    Saturate,
+    /// Return sums of integers and counts as `Decimal128(38, 0)` and sums of decimals as
+    /// `Decimal256(76, s)`, failing once those overflow
+    Promote,
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverflowPolicy::Error => write!(f, "checked"),
+            OverflowPolicy::Saturate => write!(f, "saturating"),
+            OverflowPolicy::Promote => write!(f, "promoting"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Aggregation {
+    Sum,
+    Count,
+}
+
+/// `SUM` or `COUNT` applying an [`OverflowPolicy`], named after it, e.g. `saturating_sum`.
+/// Values are accumulated as 256 bit integers and checked against the bounds of the result
+/// type as they are added.
+#[derive(Debug)]
+pub struct OverflowAggregate {
+    name: String,
+    aggregation: Aggregation,
+    policy: OverflowPolicy,
+    signature: Signature,
+}
+
+impl OverflowAggregate {
+    pub fn sum(policy: OverflowPolicy) -> Self {
+        Self {
+            name: format!("{policy}_sum"),
+            aggregation: Aggregation::Sum,
+            policy,
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+
+    pub fn count(policy: OverflowPolicy) -> Self {
+        Self {
+            name: format!("{policy}_count"),
+            aggregation: Aggregation::Count,
+            policy,
+            signature: Signature::any(1, Volatility::Immutable),
+        }
+    }
+
+    /// The sums and counts of every policy
+    pub fn all() -> Vec<Arc<AggregateUDF>> {
+        [
+            OverflowPolicy::Error,
+            OverflowPolicy::Saturate,
+            OverflowPolicy::Promote,
+        ]
+        .into_iter()
+        .flat_map(|policy| [Self::sum(policy), Self::count(policy)])
+        .map(|udaf| Arc::new(AggregateUDF::from(udaf)))
+        .collect()
+    }
+}
+
+impl AggregateUDFImpl for OverflowAggregate {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [arg_type] = arg_types else {
+            return plan_err!("{} expects a single argument", self.name);
+        };
+        let coerced = match arg_type {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => DataType::Int64,
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                DataType::UInt64
+            }
+            DataType::Decimal128(..) => arg_type.clone(),
+            other => return plan_err!("{} can't sum {other}", self.name),
+        };
+        Ok(vec![coerced])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(match (self.aggregation, self.policy, &arg_types[0]) {
+            (Aggregation::Count, OverflowPolicy::Promote, _) => DataType::Decimal128(38, 0),
+            (Aggregation::Count, ..) => DataType::Int64,
+            (_, OverflowPolicy::Promote, DataType::Decimal128(_, scale)) => {
+                DataType::Decimal256(DECIMAL256_MAX_PRECISION, *scale)
+            }
+            (_, OverflowPolicy::Promote, _) => DataType::Decimal128(38, 0),
+            (_, _, DataType::Decimal128(precision, scale)) => {
+                DataType::Decimal128((*precision + 10).min(38), *scale)
+            }
+            (_, _, other) => other.clone(),
+        })
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(OverflowAccumulator::try_new(
+            &self.name,
+            self.aggregation,
+            self.policy,
+            acc_args.return_type,
+        )?))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new(
+            format_state_name(args.name, "sum"),
+            state_type(args.return_type),
+            true,
+        )])
+    }
+}
+
+/// Sums are kept in the widest decimal of the result's scale, so partial states merge without
+/// overflowing before the bounds are checked
+fn state_type(return_type: &DataType) -> DataType {
+    let scale = match return_type {
+        DataType::Decimal128(_, scale) | DataType::Decimal256(_, scale) => *scale,
+        _ => 0,
+    };
+    DataType::Decimal256(DECIMAL256_MAX_PRECISION, scale)
+}
+
+#[derive(Debug)]
+struct OverflowAccumulator {
+    name: String,
+    aggregation: Aggregation,
+    policy: OverflowPolicy,
+    return_type: DataType,
+    min: i256,
+    max: i256,
+    sum: Option<i256>,
+}
+
+impl OverflowAccumulator {
+    fn try_new(
+        name: &str,
+        aggregation: Aggregation,
+        policy: OverflowPolicy,
+        return_type: &DataType,
+    ) -> Result<Self> {
+        let (min, max) = match return_type {
+            DataType::Int64 => (
+                i256::from_i128(i64::MIN.into()),
+                i256::from_i128(i64::MAX.into()),
+            ),
+            DataType::UInt64 => (i256::ZERO, i256::from_i128(u64::MAX.into())),
+            DataType::Decimal128(precision, _) | DataType::Decimal256(precision, _) => {
+                let max = i256::from_i128(10)
+                    .pow_wrapping(*precision as u32)
+                    .wrapping_sub(i256::ONE);
+                (max.wrapping_neg(), max)
+            }
+            other => return exec_err!("{name} can't return {other}"),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            aggregation,
+            policy,
+            return_type: return_type.clone(),
+            min,
+            max,
+            sum: None,
+        })
+    }
+
+    fn add(&mut self, value: i256) -> Result<()> {
+        let sum = self.sum.unwrap_or(i256::ZERO);
+        match sum.checked_add(value) {
+            Some(sum) if sum >= self.min && sum <= self.max => self.sum = Some(sum),
+            _ if self.policy == OverflowPolicy::Saturate => {
+                self.sum = Some(if value > i256::ZERO {
+                    self.max
+                } else {
+                    self.min
+                })
+            }
+            _ => return exec_err!("{} overflowed {}", self.name, self.return_type),
+        }
+        Ok(())
+    }
+
+    fn add_all(&mut self, values: &ArrayRef) -> Result<()> {
+        let values = cast(values, &state_type(&self.return_type))?;
+        for value in values.as_primitive::<Decimal256Type>().iter().flatten() {
+            self.add(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for OverflowAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        match self.aggregation {
+            Aggregation::Sum => self.add_all(&values[0]),
+            Aggregation::Count => {
+                let count = values[0].len() - values[0].null_count();
+                self.add(i256::from_i128(count as i128))
+            }
+        }
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.add_all(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let DataType::Decimal256(precision, scale) = state_type(&self.return_type) else {
+            unreachable!()
+        };
+        Ok(vec![ScalarValue::Decimal256(self.sum, precision, scale)])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let sum = match self.aggregation {
+            Aggregation::Count => Some(self.sum.unwrap_or(i256::ZERO)),
+            Aggregation::Sum => self.sum,
+        };
+        // The sum is within the bounds of the return type
+        let narrow = sum.and_then(|sum| sum.to_i128());
+        Ok(match &self.return_type {
+            DataType::Int64 => ScalarValue::Int64(narrow.map(|sum| sum as i64)),
+            DataType::UInt64 => ScalarValue::UInt64(narrow.map(|sum| sum as u64)),
+            DataType::Decimal128(precision, scale) => {
+                ScalarValue::Decimal128(narrow, *precision, *scale)
+            }
+            DataType::Decimal256(precision, scale) => {
+                ScalarValue::Decimal256(sum, *precision, *scale)
+            }
+            other => return exec_err!("{} can't return {other}", self.name),
+        })
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.name.capacity()
+    }
+}
+
+/// Replace the `SUM`s of integers and decimals and the `COUNT`s in `aggr_expr` with ones
+/// applying `policy`, keeping their output names. DISTINCT aggregates are left as they are.
+pub fn apply_overflow_policy(
+    aggr_expr: Vec<Expr>,
+    schema: &DFSchema,
+    policy: OverflowPolicy,
+) -> Result<Vec<Expr>> {
+    aggr_expr
+        .into_iter()
+        .map(|expr| {
+            let name = expr.display_name()?;
+            let rewritten = expr.transform(|expr| {
+                let Expr::AggregateFunction(aggregate) = &expr else {
+                    return Ok(Transformed::no(expr));
+                };
+                if aggregate.distinct || aggregate.args.len() != 1 {
+                    return Ok(Transformed::no(expr));
+                }
+                let udaf = match aggregate.func.name() {
+                    "sum" => match aggregate.args[0].get_type(schema)? {
+                        DataType::Float16 | DataType::Float32 | DataType::Float64 => None,
+                        _ => Some(OverflowAggregate::sum(policy)),
+                    },
+                    "count" => Some(OverflowAggregate::count(policy)),
+                    _ => None,
+                };
+                let Some(udaf) = udaf else {
+                    return Ok(Transformed::no(expr));
+                };
+                Ok(Transformed::yes(Expr::AggregateFunction(
+                    AggregateFunction {
+                        func: Arc::new(AggregateUDF::from(udaf)),
+                        ..aggregate.clone()
+                    },
+                )))
+            })?;
+            Ok(match rewritten {
+                Transformed {
+                    data: expr @ Expr::Alias(_),
+                    ..
+                } => expr,
+                Transformed {
+                    data: expr,
+                    transformed: true,
+                    ..
+                } => expr.alias(name),
+                Transformed { data: expr, .. } => expr,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::{Int64Array, UInt32Array};
+
+    fn accumulator(udaf: OverflowAggregate, input_type: DataType) -> Result<OverflowAccumulator> {
+        let return_type = udaf.return_type(&udaf.coerce_types(&[input_type])?)?;
+        OverflowAccumulator::try_new(&udaf.name, udaf.aggregation, udaf.policy, &return_type)
+    }
+
+    #[test]
+    fn sums_past_int64() -> Result<()> {
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![Some(i64::MAX - 1), None, Some(2)]));
+
+        let mut checked = accumulator(
+            OverflowAggregate::sum(OverflowPolicy::Error),
+            DataType::Int64,
+        )?;
+        let error = checked.update_batch(&[values.clone()]).unwrap_err();
+        assert!(error.to_string().contains("checked_sum overflowed Int64"));
+
+        let mut saturating = accumulator(
+            OverflowAggregate::sum(OverflowPolicy::Saturate),
+            DataType::Int64,
+        )?;
+        saturating.update_batch(&[values.clone()])?;
+        assert_eq!(saturating.evaluate()?, ScalarValue::Int64(Some(i64::MAX)));
+
+        let mut promoting = accumulator(
+            OverflowAggregate::sum(OverflowPolicy::Promote),
+            DataType::Int64,
+        )?;
+        promoting.update_batch(&[values.clone()])?;
+        let state = promoting
+            .state()?
+            .iter()
+            .map(ScalarValue::to_array)
+            .collect::<Result<Vec<_>>>()?;
+        promoting.merge_batch(&state)?;
+        assert_eq!(
+            promoting.evaluate()?,
+            ScalarValue::Decimal128(Some(2 * (i64::MAX as i128 + 1)), 38, 0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn counts_and_unsigned_sums() -> Result<()> {
+        let values: ArrayRef = Arc::new(UInt32Array::from(vec![Some(1), None, Some(u32::MAX)]));
+
+        let mut count = accumulator(
+            OverflowAggregate::count(OverflowPolicy::Error),
+            DataType::UInt32,
+        )?;
+        assert_eq!(count.evaluate()?, ScalarValue::Int64(Some(0)));
+        count.update_batch(&[values.clone()])?;
+        assert_eq!(count.evaluate()?, ScalarValue::Int64(Some(2)));
+
+        let mut sum = accumulator(
+            OverflowAggregate::sum(OverflowPolicy::Error),
+            DataType::UInt32,
+        )?;
+        assert_eq!(sum.evaluate()?, ScalarValue::UInt64(None));
+        sum.update_batch(&[values])?;
+        assert_eq!(
+            sum.evaluate()?,
+            ScalarValue::UInt64(Some(u32::MAX as u64 + 1))
+        );
+        Ok(())
+    }
+}
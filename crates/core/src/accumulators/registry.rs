@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema};
+use datafusion::common::{plan_err, Result};
+use datafusion::functions_aggregate::all_default_aggregate_functions;
+use datafusion::logical_expr::{Accumulator, AggregateUDF};
+use datafusion::physical_expr::expressions::col;
+use datafusion::physical_expr_common::aggregate::AggregateExprBuilder;
+
+use super::overflow::OverflowAggregate;
+use super::serializable_accumulator::merge_serialized_state;
+use super::state_serde::{encode_state, IpcStateSerde, StateSerde};
+use crate::state_backend::backend::StateBackend;
+
+/// Checkpointing for the accumulators of any aggregate function by its name, covering every
+/// built-in DataFusion aggregate, the overflow checked sums and counts, plus the ones [`register`](Self::register)ed later.
+///
+/// Nothing is specific to a function: the state an accumulator exposes through
+/// [`Accumulator::state`] is written out and merged into a fresh accumulator on restore, as if
+/// it came from a partial aggregate. DISTINCT aggregates expose their value sets as list state
+/// fields, written like any other.
+#[derive(Debug, Clone)]
+pub struct AccumulatorRegistry {
+    functions: HashMap<String, Arc<AggregateUDF>>,
+    serde: Arc<dyn StateSerde>,
+}
+
+impl Default for AccumulatorRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            functions: HashMap::new(),
+            serde: Arc::new(IpcStateSerde),
+        };
+        for udaf in all_default_aggregate_functions()
+            .into_iter()
+            .chain(OverflowAggregate::all())
+        {
+            registry.register(udaf);
+        }
+        registry
+    }
+}
+
+impl AccumulatorRegistry {
+    /// Encoding of the states written by [`serialize`](Self::serialize), Arrow IPC by default.
+    /// States are read back in whatever encoding they were written.
+    pub fn with_state_serde(mut self, serde: Arc<dyn StateSerde>) -> Self {
+        self.serde = serde;
+        self
+    }
+
+    /// Make `udaf` restorable by its name and aliases
+    pub fn register(&mut self, udaf: Arc<AggregateUDF>) {
+        for name in udaf
+            .aliases()
+            .iter()
+            .map(String::as_str)
+            .chain([udaf.name()])
+        {
+            self.functions.insert(name.to_lowercase(), udaf.clone());
+        }
+    }
+
+    pub fn function(&self, name: &str) -> Result<&Arc<AggregateUDF>> {
+        match self.functions.get(&name.to_lowercase()) {
+            Some(udaf) => Ok(udaf),
+            None => plan_err!("No aggregate function {name} to checkpoint"),
+        }
+    }
+
+    /// A fresh accumulator of `function` over columns of `input_types`. Functions taking
+    /// literal arguments, like the percentile of `approx_percentile_cont`, can't be built from
+    /// their types alone, restore those with [`restore_into`](Self::restore_into).
+    pub fn create(
+        &self,
+        function: &str,
+        input_types: &[DataType],
+        distinct: bool,
+    ) -> Result<Box<dyn Accumulator>> {
+        let udaf = self.function(function)?;
+        let schema = Arc::new(Schema::new(
+            input_types
+                .iter()
+                .enumerate()
+                .map(|(i, data_type)| Field::new(format!("c{i}"), data_type.clone(), true))
+                .collect::<Vec<_>>(),
+        ));
+        let args = (0..input_types.len())
+            .map(|i| col(&format!("c{i}"), &schema))
+            .collect::<Result<Vec<_>>>()?;
+        AggregateExprBuilder::new(udaf.clone(), args)
+            .schema(schema)
+            .alias(function)
+            .with_distinct(distinct)
+            .build()?
+            .create_accumulator()
+    }
+
+    pub fn serialize(&self, function: &str, acc: &mut dyn Accumulator) -> Result<Vec<u8>> {
+        self.function(function)?;
+        encode_state(self.serde.as_ref(), &acc.state()?)
+    }
+
+    /// The accumulator of `function` with the state written by [`serialize`](Self::serialize)
+    pub fn restore(
+        &self,
+        function: &str,
+        input_types: &[DataType],
+        distinct: bool,
+        bytes: &[u8],
+    ) -> Result<Box<dyn Accumulator>> {
+        let fresh = self.create(function, input_types, distinct)?;
+        self.restore_into(function, fresh, bytes)
+    }
+
+    /// Merge the state written by [`serialize`](Self::serialize) into `fresh`, an accumulator
+    /// of `function` that hasn't seen any rows
+    pub fn restore_into(
+        &self,
+        function: &str,
+        mut fresh: Box<dyn Accumulator>,
+        bytes: &[u8],
+    ) -> Result<Box<dyn Accumulator>> {
+        self.function(function)?;
+        merge_serialized_state(fresh.as_mut(), bytes)?;
+        Ok(fresh)
+    }
+
+    /// Checkpoint the state of `acc`, an accumulator of `function`, as `key` of `namespace`
+    pub fn save(
+        &self,
+        backend: &dyn StateBackend,
+        namespace: &str,
+        key: Vec<u8>,
+        function: &str,
+        acc: &mut dyn Accumulator,
+    ) -> Result<()> {
+        backend.put_state(namespace, key, self.serialize(function, acc)?)
+    }
+
+    /// The accumulator of `function` [`save`](Self::save)d as `key` of `namespace`, if any
+    pub fn load(
+        &self,
+        backend: &dyn StateBackend,
+        namespace: &str,
+        key: Vec<u8>,
+        function: &str,
+        input_types: &[DataType],
+        distinct: bool,
+    ) -> Result<Option<Box<dyn Accumulator>>> {
+        backend
+            .get_state(namespace, key)?
+            .map(|bytes| self.restore(function, input_types, distinct, &bytes))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray};
+
+    fn ints(values: &[Option<i64>]) -> ArrayRef {
+        Arc::new(Int64Array::from(values.to_vec()))
+    }
+
+    fn floats(values: &[f64]) -> ArrayRef {
+        Arc::new(Float64Array::from(values.to_vec()))
+    }
+
+    /// Checkpoint every aggregate after its first batch, then feed the second batch to the
+    /// original and the restored accumulator and compare the results
+    #[test]
+    fn roundtrip_builtin_aggregates() -> Result<()> {
+        let registry = AccumulatorRegistry::default();
+        let first_ints = ints(&[Some(4), None, Some(1), Some(4), Some(9)]);
+        let second_ints = ints(&[Some(2), Some(9)]);
+        // Four rows keep the means exact when merged into an empty accumulator
+        let first_floats = floats(&[1.0, 2.5, 4.0, 0.5]);
+        let second_floats = floats(&[-3.0, 8.0]);
+
+        let numeric = [
+            "sum",
+            "count",
+            "avg",
+            "min",
+            "max",
+            "median",
+            "approx_distinct",
+            "approx_median",
+            "first_value",
+            "last_value",
+            "array_agg",
+            "bit_and",
+            "bit_or",
+            "bit_xor",
+        ];
+        for function in numeric {
+            assert_roundtrip(
+                &registry,
+                function,
+                false,
+                &[DataType::Int64],
+                &[first_ints.clone()],
+                &[second_ints.clone()],
+            )?;
+        }
+        for function in ["sum", "count"] {
+            assert_roundtrip(
+                &registry,
+                function,
+                true,
+                &[DataType::Int64],
+                &[first_ints.clone()],
+                &[second_ints.clone()],
+            )?;
+        }
+
+        for function in ["stddev", "stddev_pop", "var_samp", "var_pop"] {
+            assert_roundtrip(
+                &registry,
+                function,
+                false,
+                &[DataType::Float64],
+                &[first_floats.clone()],
+                &[second_floats.clone()],
+            )?;
+        }
+        for function in [
+            "covar_samp",
+            "covar_pop",
+            "corr",
+            "regr_slope",
+            "regr_count",
+        ] {
+            assert_roundtrip(
+                &registry,
+                function,
+                false,
+                &[DataType::Float64, DataType::Float64],
+                &[first_floats.clone(), floats(&[2.0, 5.5, 7.0, 1.5])],
+                &[second_floats.clone(), floats(&[-5.0, 17.0])],
+            )?;
+        }
+        for function in ["bool_and", "bool_or"] {
+            assert_roundtrip(
+                &registry,
+                function,
+                false,
+                &[DataType::Boolean],
+                &[Arc::new(BooleanArray::from(vec![Some(true), None]))],
+                &[Arc::new(BooleanArray::from(vec![false]))],
+            )?;
+        }
+        assert_roundtrip(
+            &registry,
+            "min",
+            false,
+            &[DataType::Utf8],
+            &[Arc::new(StringArray::from(vec!["pear", "fig"]))],
+            &[Arc::new(StringArray::from(vec!["apple"]))],
+        )?;
+
+        assert!(registry.create("no_such_aggregate", &[], false).is_err());
+        Ok(())
+    }
+
+    fn assert_roundtrip(
+        registry: &AccumulatorRegistry,
+        function: &str,
+        distinct: bool,
+        input_types: &[DataType],
+        first: &[ArrayRef],
+        second: &[ArrayRef],
+    ) -> Result<()> {
+        let mut acc = registry.create(function, input_types, distinct)?;
+        acc.update_batch(first)?;
+        let bytes = registry.serialize(function, acc.as_mut())?;
+        let mut restored = registry.restore(function, input_types, distinct, &bytes)?;
+
+        acc.update_batch(second)?;
+        restored.update_batch(second)?;
+        assert_eq!(
+            restored.evaluate()?,
+            acc.evaluate()?,
+            "{function} distinct={distinct}"
+        );
+        Ok(())
+    }
+}
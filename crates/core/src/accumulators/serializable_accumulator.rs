@@ -1,46 +1,34 @@
-use arrow::array::{Array, ArrayRef};
-use datafusion::common::{Result, ScalarValue};
+use arrow::array::ArrayRef;
+use datafusion::common::{DataFusionError, Result, ScalarValue};
 use datafusion::functions_aggregate::array_agg::ArrayAggAccumulator;
 use datafusion::logical_expr::Accumulator;
-use serde::{Deserialize, Serialize};
 
-use super::serialize::SerializableScalarValue;
+use super::state_serde::{decode_state, encode_state, IpcStateSerde, StateSerde};
 
+/// Checkpointing of a single accumulator. States are written with a versioned header, so any
+/// state written before, including the unversioned JSON of older checkpoints, can be restored.
 #[allow(dead_code)]
 pub trait SerializableAccumulator {
-    fn serialize(&mut self) -> Result<String>;
-    fn deserialize(self, bytes: String) -> Result<Box<dyn Accumulator>>;
-}
+    /// The state in the compact Arrow IPC encoding
+    fn serialize(&mut self) -> Result<Vec<u8>> {
+        self.serialize_with(&IpcStateSerde)
+    }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SerializableArrayAggState {
-    state: Vec<SerializableScalarValue>,
+    fn serialize_with(&mut self, serde: &dyn StateSerde) -> Result<Vec<u8>>;
+    fn deserialize(self, bytes: Vec<u8>) -> Result<Box<dyn Accumulator>>;
 }
 
 impl SerializableAccumulator for ArrayAggAccumulator {
-    fn serialize(&mut self) -> Result<String> {
-        let state = self.state()?;
-        let serializable_state = SerializableArrayAggState {
-            state: state
-                .into_iter()
-                .map(SerializableScalarValue::from)
-                .collect(),
-        };
-        Ok(serde_json::to_string(&serializable_state).unwrap())
+    fn serialize_with(&mut self, serde: &dyn StateSerde) -> Result<Vec<u8>> {
+        encode_state(serde, &self.state()?)
     }
 
-    fn deserialize(self, bytes: String) -> Result<Box<dyn Accumulator>> {
-        let serializable_state: SerializableArrayAggState =
-            serde_json::from_str(bytes.as_str()).unwrap();
-        let state: Vec<ScalarValue> = serializable_state
-            .state
-            .into_iter()
-            .map(ScalarValue::from)
-            .collect();
+    fn deserialize(self, bytes: Vec<u8>) -> Result<Box<dyn Accumulator>> {
+        let state = decode_state(&bytes)?;
 
-        // Infer the datatype from the first element of the state
+        // Infer the element type from the first element of the state
         let datatype = if let Some(ScalarValue::List(list)) = state.first() {
-            list.data_type().clone()
+            list.value_type()
         } else {
             return Err(datafusion::common::DataFusionError::Internal(
                 "Invalid state for ArrayAggAccumulator".to_string(),
@@ -67,11 +55,99 @@ impl SerializableAccumulator for ArrayAggAccumulator {
     }
 }
 
+/// Checkpointing for order sensitive aggregates, e.g. `ARRAY_AGG(x ORDER BY ts)`.
+///
+/// The full accumulator state is kept, including the ordering keys stored next to the values.
+/// Restoring merges that state into a fresh accumulator, so rows added after the restore are
+/// placed by their keys exactly as they would have been without the checkpoint.
+pub struct OrderSensitiveAccumulator(pub Box<dyn Accumulator>);
+
+impl SerializableAccumulator for OrderSensitiveAccumulator {
+    fn serialize_with(&mut self, serde: &dyn StateSerde) -> Result<Vec<u8>> {
+        encode_state(serde, &self.0.state()?)
+    }
+
+    fn deserialize(mut self, bytes: Vec<u8>) -> Result<Box<dyn Accumulator>> {
+        merge_serialized_state(self.0.as_mut(), &bytes)?;
+        Ok(self.0)
+    }
+}
+
+/// Checkpointing for aggregates whose state is a fixed set of scalar fields, like the
+/// bitwise and boolean aggregates or the `(count, mean, m2)` kept by variance, stddev,
+/// covariance and correlation. The state fields are written as they are and merged into a
+/// fresh accumulator on restore.
+pub struct MergeableAccumulator(pub Box<dyn Accumulator>);
+
+impl SerializableAccumulator for MergeableAccumulator {
+    fn serialize_with(&mut self, serde: &dyn StateSerde) -> Result<Vec<u8>> {
+        encode_state(serde, &self.0.state()?)
+    }
+
+    fn deserialize(mut self, bytes: Vec<u8>) -> Result<Box<dyn Accumulator>> {
+        merge_serialized_state(self.0.as_mut(), &bytes)?;
+        Ok(self.0)
+    }
+}
+
+/// Merge a state written by [`encode_state`] as if it came from a partial aggregate
+pub(super) fn merge_serialized_state(acc: &mut dyn Accumulator, bytes: &[u8]) -> Result<()> {
+    let states = decode_state(bytes)?
+        .into_iter()
+        .map(|value| value.to_array())
+        .collect::<Result<Vec<_>>>()?;
+    acc.merge_batch(&states)
+}
+
+/// Checkpointing for DISTINCT aggregates such as `COUNT(DISTINCT x)` or `SUM(DISTINCT x)`.
+///
+/// Their accumulators keep a hash set of the values seen so far and expose it as a single list
+/// per state field. In the Arrow IPC encoding each set is written as one column of its value
+/// type rather than a list of individually tagged scalars, which keeps large sets small.
+pub struct DistinctAccumulator(pub Box<dyn Accumulator>);
+
+impl SerializableAccumulator for DistinctAccumulator {
+    fn serialize_with(&mut self, serde: &dyn StateSerde) -> Result<Vec<u8>> {
+        let state = self.0.state()?;
+        if let Some(other) = state
+            .iter()
+            .find(|set| !matches!(set, ScalarValue::List(_)))
+        {
+            return Err(DataFusionError::Internal(format!(
+                "Expected a list of distinct values as accumulator state, got {}",
+                other.data_type()
+            )));
+        }
+        encode_state(serde, &state)
+    }
+
+    fn deserialize(mut self, bytes: Vec<u8>) -> Result<Box<dyn Accumulator>> {
+        merge_serialized_state(self.0.as_mut(), &bytes)?;
+        Ok(self.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use arrow::array::{Int32Array, StringArray};
-    use arrow::datatypes::DataType;
+    use arrow::array::{BooleanArray, Float64Array, Int32Array, Int64Array, StringArray};
+    use arrow::compute::SortOptions;
+    use arrow::datatypes::{DataType, Int64Type};
+    use arrow::datatypes::{Field, Schema};
+    use datafusion::functions_aggregate::array_agg::OrderSensitiveArrayAggAccumulator;
+    use datafusion::functions_aggregate::{
+        bit_and_or_xor::{bit_and_udaf, bit_or_udaf, bit_xor_udaf},
+        bool_and_or::{bool_and_udaf, bool_or_udaf},
+        correlation::corr_udaf,
+        covariance::covar_samp_udaf,
+        stddev::stddev_udaf,
+        variance::var_samp_udaf,
+    };
+    use datafusion::logical_expr::AggregateUDF;
+    use datafusion::physical_expr::expressions::col;
+    use datafusion::physical_expr::PhysicalSortExpr;
+    use datafusion::physical_expr_common::aggregate::count_distinct::PrimitiveDistinctCountAccumulator;
+    use datafusion::physical_expr_common::aggregate::AggregateExprBuilder;
     use std::sync::Arc;
 
     fn create_int32_array(values: Vec<Option<i32>>) -> ArrayRef {
@@ -109,4 +185,166 @@ mod tests {
         assert_eq!(acc.evaluate()?, deserialized.evaluate()?);
         Ok(())
     }
+
+    #[test]
+    fn test_serialize_deserialize_count_distinct() -> Result<()> {
+        let mut acc = DistinctAccumulator(Box::new(
+            PrimitiveDistinctCountAccumulator::<Int64Type>::new(&DataType::Int64),
+        ));
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![Some(3), Some(1), None, Some(3)]));
+        acc.0.update_batch(&[values])?;
+
+        let serialized = acc.serialize()?;
+        let fresh = DistinctAccumulator(Box::new(
+            PrimitiveDistinctCountAccumulator::<Int64Type>::new(&DataType::Int64),
+        ));
+        let mut restored = fresh.deserialize(serialized)?;
+
+        assert_eq!(restored.evaluate()?, ScalarValue::Int64(Some(2)));
+        let more: ArrayRef = Arc::new(Int64Array::from(vec![1, 7]));
+        restored.update_batch(&[more])?;
+        assert_eq!(restored.evaluate()?, ScalarValue::Int64(Some(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_deserialize_ordered_array_agg() -> Result<()> {
+        let schema = Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("ts", DataType::Int64, true),
+        ]);
+        let new_acc = || -> Result<OrderSensitiveArrayAggAccumulator> {
+            OrderSensitiveArrayAggAccumulator::try_new(
+                &DataType::Utf8,
+                &[DataType::Int64],
+                vec![PhysicalSortExpr {
+                    expr: col("ts", &schema)?,
+                    options: SortOptions::default(),
+                }],
+                false,
+            )
+        };
+
+        let mut acc = OrderSensitiveAccumulator(Box::new(new_acc()?));
+        acc.0.update_batch(&[
+            create_string_array(vec![Some("b"), Some("a")]),
+            Arc::new(Int64Array::from(vec![2, 1])),
+        ])?;
+
+        let serialized = acc.serialize()?;
+        let mut restored =
+            OrderSensitiveAccumulator(Box::new(new_acc()?)).deserialize(serialized)?;
+        let late: [ArrayRef; 2] = [
+            create_string_array(vec![Some("c")]),
+            Arc::new(Int64Array::from(vec![0])),
+        ];
+        restored.update_batch(&late)?;
+        acc.0.update_batch(&late)?;
+
+        let expected = ScalarValue::List(ScalarValue::new_list_nullable(
+            &[
+                ScalarValue::from("c"),
+                ScalarValue::from("a"),
+                ScalarValue::from("b"),
+            ],
+            &DataType::Utf8,
+        ));
+        assert_eq!(restored.evaluate()?, expected);
+        assert_eq!(acc.0.evaluate()?, expected);
+        Ok(())
+    }
+
+    /// Checkpoint after `first`, then feed `second` to both the original and the restored
+    /// accumulator and compare the results.
+    fn assert_merge_roundtrip(
+        udaf: Arc<AggregateUDF>,
+        data_type: DataType,
+        first: Vec<ArrayRef>,
+        second: Vec<ArrayRef>,
+    ) -> Result<()> {
+        let fields = (0..first.len())
+            .map(|i| Field::new(format!("c{i}"), data_type.clone(), true))
+            .collect::<Vec<_>>();
+        let schema = Arc::new(Schema::new(fields));
+        let args = (0..first.len())
+            .map(|i| col(&format!("c{i}"), &schema))
+            .collect::<Result<Vec<_>>>()?;
+        let expr = AggregateExprBuilder::new(udaf, args)
+            .schema(schema)
+            .alias("agg")
+            .build()?;
+
+        let mut acc = MergeableAccumulator(expr.create_accumulator()?);
+        acc.0.update_batch(&first)?;
+        let serialized = acc.serialize()?;
+        let mut restored =
+            MergeableAccumulator(expr.create_accumulator()?).deserialize(serialized)?;
+
+        acc.0.update_batch(&second)?;
+        restored.update_batch(&second)?;
+        assert_eq!(restored.evaluate()?, acc.0.evaluate()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_deserialize_bitwise() -> Result<()> {
+        for udaf in [bit_and_udaf(), bit_or_udaf(), bit_xor_udaf()] {
+            assert_merge_roundtrip(
+                udaf,
+                DataType::Int64,
+                vec![Arc::new(Int64Array::from(vec![
+                    Some(0b1101),
+                    None,
+                    Some(0b0111),
+                ]))],
+                vec![Arc::new(Int64Array::from(vec![0b0101]))],
+            )?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_deserialize_boolean() -> Result<()> {
+        for udaf in [bool_and_udaf(), bool_or_udaf()] {
+            assert_merge_roundtrip(
+                udaf,
+                DataType::Boolean,
+                vec![Arc::new(BooleanArray::from(vec![Some(true), None]))],
+                vec![Arc::new(BooleanArray::from(vec![false]))],
+            )?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_deserialize_variance() -> Result<()> {
+        for udaf in [var_samp_udaf(), stddev_udaf()] {
+            assert_merge_roundtrip(
+                udaf,
+                DataType::Float64,
+                vec![Arc::new(Float64Array::from(vec![1.0, 2.5, 4.0]))],
+                vec![Arc::new(Float64Array::from(vec![Some(-3.0), None]))],
+            )?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_deserialize_covariance() -> Result<()> {
+        for udaf in [covar_samp_udaf(), corr_udaf()] {
+            assert_merge_roundtrip(
+                udaf,
+                DataType::Float64,
+                vec![
+                    Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])),
+                    Arc::new(Float64Array::from(vec![2.0, 4.0, 6.0])),
+                ],
+                vec![
+                    Arc::new(Float64Array::from(vec![4.0])),
+                    Arc::new(Float64Array::from(vec![9.0])),
+                ],
+            )?;
+        }
+        Ok(())
+    }
 }
@@ -1,13 +1,13 @@
 use std::sync::Arc;
 
-use arrow_array::GenericListArray;
+use arrow_array::{
+    new_empty_array, new_null_array, Array, ArrayRef, FixedSizeListArray, LargeListArray,
+    ListArray, MapArray, StructArray,
+};
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use datafusion::common::ScalarValue;
 
-use arrow::{
-    buffer::{OffsetBuffer, ScalarBuffer},
-    datatypes::*,
-};
+use arrow::{buffer::OffsetBuffer, datatypes::*};
 use half::f16;
 use serde_json::{json, Value};
 
@@ -15,6 +15,10 @@ use arrow::datatypes::{DataType, Field, IntervalUnit, TimeUnit};
 
 use serde::{Deserialize, Serialize};
 
+/// Layout of list and struct scalars, written with them. Version 1 objects carry no version
+/// and hold only the first element of a list and the type of each struct field.
+const NESTED_FORMAT_VERSION: u64 = 2;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SerializableScalarValue(#[serde(with = "scalar_value_serde")] ScalarValue);
 
@@ -73,7 +77,7 @@ pub fn string_to_data_type(s: &str) -> Result<DataType, Box<dyn std::error::Erro
         "Date32" => Ok(DataType::Date32),
         "Date64" => Ok(DataType::Date64),
         s if s.starts_with("Timestamp(") => {
-            let parts: Vec<&str> = s[10..s.len() - 1].split(',').collect();
+            let parts: Vec<&str> = s[10..s.len() - 1].splitn(2, ',').collect();
             if parts.len() != 2 {
                 return Err("Invalid Timestamp format".into());
             }
@@ -84,11 +88,16 @@ pub fn string_to_data_type(s: &str) -> Result<DataType, Box<dyn std::error::Erro
                 "Nanosecond" => TimeUnit::Nanosecond,
                 _ => return Err("Invalid TimeUnit".into()),
             };
-            let timezone = parts[1].trim().trim_matches('"');
+            // Timezones are printed as `Some("+00:00")`
+            let timezone = parts[1].trim();
             let timezone = if timezone == "None" {
                 None
             } else {
-                Some(timezone.into())
+                let timezone = timezone
+                    .strip_prefix("Some(")
+                    .and_then(|tz| tz.strip_suffix(')'))
+                    .unwrap_or(timezone);
+                Some(timezone.trim_matches('"').into())
             };
             Ok(DataType::Timestamp(time_unit, timezone))
         }
@@ -156,7 +165,7 @@ pub fn string_to_data_type(s: &str) -> Result<DataType, Box<dyn std::error::Erro
             ))
         }
         s if s.starts_with("Decimal128(") => {
-            let parts: Vec<&str> = s[10..s.len() - 1].split(',').collect();
+            let parts: Vec<&str> = s[11..s.len() - 1].split(',').collect();
             if parts.len() != 2 {
                 return Err("Invalid Decimal128 format".into());
             }
@@ -165,7 +174,7 @@ pub fn string_to_data_type(s: &str) -> Result<DataType, Box<dyn std::error::Erro
             Ok(DataType::Decimal128(precision, scale))
         }
         s if s.starts_with("Decimal256(") => {
-            let parts: Vec<&str> = s[10..s.len() - 1].split(',').collect();
+            let parts: Vec<&str> = s[11..s.len() - 1].split(',').collect();
             if parts.len() != 2 {
                 return Err("Invalid Decimal256 format".into());
             }
@@ -177,6 +186,180 @@ pub fn string_to_data_type(s: &str) -> Result<DataType, Box<dyn std::error::Erro
     }
 }
 
+fn time_unit_from_str(unit: &str) -> Result<TimeUnit, Box<dyn std::error::Error>> {
+    match unit {
+        "Second" => Ok(TimeUnit::Second),
+        "Millisecond" => Ok(TimeUnit::Millisecond),
+        "Microsecond" => Ok(TimeUnit::Microsecond),
+        "Nanosecond" => Ok(TimeUnit::Nanosecond),
+        _ => Err(format!("Invalid TimeUnit {unit}").into()),
+    }
+}
+
+fn interval_unit_from_str(unit: &str) -> Result<IntervalUnit, Box<dyn std::error::Error>> {
+    match unit {
+        "YearMonth" => Ok(IntervalUnit::YearMonth),
+        "DayTime" => Ok(IntervalUnit::DayTime),
+        "MonthDayNano" => Ok(IntervalUnit::MonthDayNano),
+        _ => Err(format!("Invalid IntervalUnit {unit}").into()),
+    }
+}
+
+pub fn field_to_json(field: &Field) -> Value {
+    json!({
+        "name": field.name(),
+        "data_type": data_type_to_json(field.data_type()),
+        "nullable": field.is_nullable(),
+    })
+}
+
+pub fn json_to_field(json: &Value) -> Result<Field, Box<dyn std::error::Error>> {
+    let name = json
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or("Missing or invalid field 'name'")?;
+    let data_type = json_to_data_type(json.get("data_type").ok_or("Missing 'data_type'")?)?;
+    let nullable = json
+        .get("nullable")
+        .and_then(Value::as_bool)
+        .unwrap_or(true);
+    Ok(Field::new(name, data_type, nullable))
+}
+
+/// Structured encoding of a [`DataType`]. Unlike the `Display` string parsed by
+/// [`string_to_data_type`] it keeps nested field names and nullability, timezones and decimal
+/// precision and scale.
+pub fn data_type_to_json(data_type: &DataType) -> Value {
+    match data_type {
+        DataType::Timestamp(unit, tz) => json!({
+            "type": "Timestamp",
+            "unit": format!("{unit:?}"),
+            "timezone": tz.as_ref().map(|tz| tz.to_string()),
+        }),
+        DataType::Time32(unit) => json!({"type": "Time32", "unit": format!("{unit:?}")}),
+        DataType::Time64(unit) => json!({"type": "Time64", "unit": format!("{unit:?}")}),
+        DataType::Duration(unit) => json!({"type": "Duration", "unit": format!("{unit:?}")}),
+        DataType::Interval(unit) => json!({"type": "Interval", "unit": format!("{unit:?}")}),
+        DataType::FixedSizeBinary(size) => json!({"type": "FixedSizeBinary", "size": size}),
+        DataType::Decimal128(precision, scale) => json!({
+            "type": "Decimal128",
+            "precision": precision,
+            "scale": scale,
+        }),
+        DataType::Decimal256(precision, scale) => json!({
+            "type": "Decimal256",
+            "precision": precision,
+            "scale": scale,
+        }),
+        DataType::List(field) => json!({"type": "List", "field": field_to_json(field)}),
+        DataType::LargeList(field) => json!({"type": "LargeList", "field": field_to_json(field)}),
+        DataType::FixedSizeList(field, size) => json!({
+            "type": "FixedSizeList",
+            "field": field_to_json(field),
+            "size": size,
+        }),
+        DataType::Struct(fields) => json!({
+            "type": "Struct",
+            "fields": fields.iter().map(|f| field_to_json(f)).collect::<Vec<_>>(),
+        }),
+        DataType::Map(field, sorted) => json!({
+            "type": "Map",
+            "field": field_to_json(field),
+            "sorted": sorted,
+        }),
+        DataType::Dictionary(key, value) => json!({
+            "type": "Dictionary",
+            "key": data_type_to_json(key),
+            "value": data_type_to_json(value),
+        }),
+        other => json!({"type": other.to_string()}),
+    }
+}
+
+pub fn json_to_data_type(json: &Value) -> Result<DataType, Box<dyn std::error::Error>> {
+    // Types written with the string encoding
+    if let Some(s) = json.as_str() {
+        return string_to_data_type(s);
+    }
+
+    let typ = json
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or("Missing or invalid 'type'")?;
+    let unit = || {
+        json.get("unit")
+            .and_then(Value::as_str)
+            .ok_or("Missing or invalid 'unit'")
+    };
+    let field = || json_to_field(json.get("field").ok_or("Missing 'field'")?);
+    let precision_and_scale = || -> Result<(u8, i8), Box<dyn std::error::Error>> {
+        let precision = json
+            .get("precision")
+            .and_then(Value::as_u64)
+            .ok_or("Missing or invalid 'precision'")?;
+        let scale = json
+            .get("scale")
+            .and_then(Value::as_i64)
+            .ok_or("Missing or invalid 'scale'")?;
+        Ok((precision as u8, scale as i8))
+    };
+
+    match typ {
+        "Timestamp" => Ok(DataType::Timestamp(
+            time_unit_from_str(unit()?)?,
+            json.get("timezone")
+                .and_then(Value::as_str)
+                .map(|tz| tz.into()),
+        )),
+        "Time32" => Ok(DataType::Time32(time_unit_from_str(unit()?)?)),
+        "Time64" => Ok(DataType::Time64(time_unit_from_str(unit()?)?)),
+        "Duration" => Ok(DataType::Duration(time_unit_from_str(unit()?)?)),
+        "Interval" => Ok(DataType::Interval(interval_unit_from_str(unit()?)?)),
+        "FixedSizeBinary" => Ok(DataType::FixedSizeBinary(
+            json.get("size")
+                .and_then(Value::as_i64)
+                .ok_or("Missing or invalid 'size'")? as i32,
+        )),
+        "Decimal128" => {
+            let (precision, scale) = precision_and_scale()?;
+            Ok(DataType::Decimal128(precision, scale))
+        }
+        "Decimal256" => {
+            let (precision, scale) = precision_and_scale()?;
+            Ok(DataType::Decimal256(precision, scale))
+        }
+        "List" => Ok(DataType::List(Arc::new(field()?))),
+        "LargeList" => Ok(DataType::LargeList(Arc::new(field()?))),
+        "FixedSizeList" => Ok(DataType::FixedSizeList(
+            Arc::new(field()?),
+            json.get("size")
+                .and_then(Value::as_i64)
+                .ok_or("Missing or invalid 'size'")? as i32,
+        )),
+        "Struct" => {
+            let fields = json
+                .get("fields")
+                .and_then(Value::as_array)
+                .ok_or("Missing or invalid 'fields'")?
+                .iter()
+                .map(json_to_field)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(DataType::Struct(fields.into()))
+        }
+        "Map" => Ok(DataType::Map(
+            Arc::new(field()?),
+            json.get("sorted").and_then(Value::as_bool).unwrap_or(false),
+        )),
+        "Dictionary" => Ok(DataType::Dictionary(
+            Box::new(json_to_data_type(json.get("key").ok_or("Missing 'key'")?)?),
+            Box::new(json_to_data_type(
+                json.get("value").ok_or("Missing 'value'")?,
+            )?),
+        )),
+        other => string_to_data_type(other),
+    }
+}
+
 pub fn scalar_to_json(value: &ScalarValue) -> serde_json::Value {
     match value {
         ScalarValue::Null => json!({"type": "Null"}),
@@ -219,14 +402,12 @@ pub fn scalar_to_json(value: &ScalarValue) -> serde_json::Value {
             "size": size,
             "value": v.as_ref().map(|b| STANDARD.encode(b))
         }),
-        ScalarValue::List(v) => {
-            let sv = ScalarValue::try_from_array(&v.value(0), 0).unwrap();
-            let dt = sv.data_type().to_string();
-            json!({
-                "type": "List",
-                "field_type": dt,
-                "value": scalar_to_json(&sv)
-            })
+        ScalarValue::List(v) => list_to_json("List", v.data_type(), v.is_null(0), &v.value(0)),
+        ScalarValue::LargeList(v) => {
+            list_to_json("LargeList", v.data_type(), v.is_null(0), &v.value(0))
+        }
+        ScalarValue::FixedSizeList(v) => {
+            list_to_json("FixedSizeList", v.data_type(), v.is_null(0), &v.value(0))
         }
         ScalarValue::Date32(v) => json!({"type": "Date32", "value": v}),
         ScalarValue::Date64(v) => json!({"type": "Date64", "value": v}),
@@ -263,31 +444,44 @@ pub fn scalar_to_json(value: &ScalarValue) -> serde_json::Value {
         ScalarValue::DurationMicrosecond(v) => json!({"type": "DurationMicrosecond", "value": v}),
         ScalarValue::DurationNanosecond(v) => json!({"type": "DurationNanosecond", "value": v}),
         ScalarValue::Struct(v) => {
-            let fields = v
-                .as_ref()
-                .fields()
-                .iter()
-                .map(|f| (f.name().to_string(), f.data_type().to_string()))
-                .collect::<Vec<_>>();
-
             let values = v
                 .columns()
-                .as_ref()
                 .iter()
-                .map(|c| {
-                    let sv = ScalarValue::try_from_array(c, 0).unwrap();
-                    scalar_to_json(&sv)
-                })
+                .map(|c| scalar_to_json(&ScalarValue::try_from_array(c, 0).unwrap()))
                 .collect::<Vec<_>>();
-            json!({"type" : "Struct", "fields": fields, "values": values})
+            json!({
+                "type": "Struct",
+                "fields": v.fields().iter().map(|f| field_to_json(f)).collect::<Vec<_>>(),
+                "values": values,
+                "null": v.is_null(0),
+                "version": NESTED_FORMAT_VERSION
+            })
         }
-        ScalarValue::Utf8View(_) => todo!(),
-        ScalarValue::BinaryView(_) => todo!(),
-        ScalarValue::FixedSizeList(_) => todo!(),
-        ScalarValue::LargeList(_) => todo!(),
-        ScalarValue::Map(_) => todo!(),
+        ScalarValue::Map(v) => {
+            let DataType::Map(entries_field, sorted) = v.data_type() else {
+                unreachable!()
+            };
+            let entries = v.value(0);
+            json!({
+                "type": "Map",
+                "entries": field_to_json(entries_field),
+                "sorted": sorted,
+                "keys": array_to_json(entries.column(0)),
+                "values": array_to_json(entries.column(1)),
+                "null": v.is_null(0)
+            })
+        }
+        ScalarValue::Utf8View(v) => json!({"type": "Utf8View", "value": v}),
+        ScalarValue::BinaryView(v) => json!({
+            "type": "BinaryView",
+            "value": v.as_ref().map(|b| STANDARD.encode(b))
+        }),
+        ScalarValue::Dictionary(key_type, v) => json!({
+            "type": "Dictionary",
+            "key_type": data_type_to_json(key_type),
+            "value": scalar_to_json(v)
+        }),
         ScalarValue::Union(_, _, _) => todo!(),
-        ScalarValue::Dictionary(_, _) => todo!(),
     }
 }
 
@@ -378,20 +572,10 @@ pub fn json_to_scalar(json: &Value) -> Result<ScalarValue, Box<dyn std::error::E
                 .map(|s| STANDARD.decode(s).unwrap());
             Ok(ScalarValue::FixedSizeBinary(size, value))
         }
-        "List" => {
-            let value = obj.get("value").ok_or("Missing 'value' for List")?;
-            let field_type = obj
-                .get("field_type")
-                .map(|ft| ft.as_str())
-                .ok_or("Missing 'field_type' for List")?;
-            let dt: DataType = string_to_data_type(field_type.unwrap())?;
-            let element: ScalarValue = json_to_scalar(value)?;
-            let array = element.to_array_of_size(1).unwrap();
-            let offsets = OffsetBuffer::new(ScalarBuffer::from(vec![0]));
-            let field = Field::new("item", dt, true);
-            let list = GenericListArray::try_new(Arc::new(field), offsets, array, None)?;
-            Ok(ScalarValue::List(Arc::new(list)))
-        }
+        "List" | "LargeList" | "FixedSizeList" => match nested_version(obj)? {
+            1 => json_to_legacy_list(obj),
+            _ => json_to_list(typ, obj),
+        },
         "Date32" => Ok(ScalarValue::Date32(
             obj.get("value").and_then(Value::as_i64).map(|i| i as i32),
         )),
@@ -431,41 +615,33 @@ pub fn json_to_scalar(json: &Value) -> Result<ScalarValue, Box<dyn std::error::E
         "IntervalYearMonth" => Ok(ScalarValue::IntervalYearMonth(
             obj.get("value").and_then(Value::as_i64).map(|i| i as i32),
         )),
-        // "IntervalDayTime" => {
-        //     let value = obj
-        //         .get("value")
-        //         .and_then(Value::as_array)
-        //         .map(|arr| {
-        //             if arr.len() == 2 {
-        //                 Some(arrow_buffer::IntervalDayTime::make_value(
-        //                     arr[0].as_i64().unwrap() as i32,
-        //                     arr[1].as_i64().unwrap() as i32,
-        //                 ))
-        //             } else {
-        //                 None
-        //             }
-        //         })
-        //         .flatten();
-        //     Ok(ScalarValue::IntervalDayTime(value))
-        // }
-        // "IntervalMonthDayNano" => {
-        //     let value = obj
-        //         .get("value")
-        //         .and_then(Value::as_array)
-        //         .map(|arr| {
-        //             if arr.len() == 3 {
-        //                 Some(arrow_buffer::IntervalMonthDayNano::make_value(
-        //                     arr[0].as_i64().unwrap() as i32,
-        //                     arr[1].as_i64().unwrap() as i32,
-        //                     arr[2].as_i64().unwrap(),
-        //                 ))
-        //             } else {
-        //                 None
-        //             }
-        //         })
-        //         .flatten();
-        //     Ok(ScalarValue::IntervalMonthDayNano(value))
-        // }
+        "IntervalDayTime" => {
+            let value = obj
+                .get("value")
+                .and_then(Value::as_array)
+                .map(|parts| {
+                    Ok::<_, Box<dyn std::error::Error>>(IntervalDayTime::new(
+                        interval_part(parts, 0)? as i32,
+                        interval_part(parts, 1)? as i32,
+                    ))
+                })
+                .transpose()?;
+            Ok(ScalarValue::IntervalDayTime(value))
+        }
+        "IntervalMonthDayNano" => {
+            let value = obj
+                .get("value")
+                .and_then(Value::as_array)
+                .map(|parts| {
+                    Ok::<_, Box<dyn std::error::Error>>(IntervalMonthDayNano::new(
+                        interval_part(parts, 0)? as i32,
+                        interval_part(parts, 1)? as i32,
+                        interval_part(parts, 2)?,
+                    ))
+                })
+                .transpose()?;
+            Ok(ScalarValue::IntervalMonthDayNano(value))
+        }
         "DurationSecond" => Ok(ScalarValue::DurationSecond(
             obj.get("value").and_then(Value::as_i64),
         )),
@@ -478,43 +654,232 @@ pub fn json_to_scalar(json: &Value) -> Result<ScalarValue, Box<dyn std::error::E
         "DurationNanosecond" => Ok(ScalarValue::DurationNanosecond(
             obj.get("value").and_then(Value::as_i64),
         )),
-        // "Struct" => {
-        //     let fields = obj
-        //         .get("fields")
-        //         .and_then(Value::as_array)
-        //         .ok_or("Missing or invalid 'fields'")?;
-        //     let values = obj
-        //         .get("values")
-        //         .and_then(Value::as_array)
-        //         .ok_or("Missing or invalid 'values'")?;
-
-        //     let field_vec: Vec<Field> = fields
-        //         .iter()
-        //         .map(|f| {
-        //             let name = f[0].as_str().unwrap().to_string();
-        //             let data_type = f[1].as_str().unwrap().parse().unwrap();
-        //             Field::new(name, data_type, true)
-        //         })
-        //         .collect();
-
-        //     let value_vec: Vec<ArrayRef> = values
-        //         .iter()
-        //         .map(|v| {
-        //             let scalar = json_to_scalar(v).unwrap();
-        //             scalar.to_array().unwrap()
-        //         })
-        //         .collect();
-
-        //     let struct_array = StructArray::from((field_vec, value_vec));
-        //     Ok(ScalarValue::Struct(Arc::new(struct_array)))
-        // }
+        "Struct" if nested_version(obj)? == 1 => json_to_legacy_struct(obj),
+        "Struct" => {
+            let fields = obj
+                .get("fields")
+                .and_then(Value::as_array)
+                .ok_or("Missing or invalid 'fields'")?
+                .iter()
+                .map(json_to_field)
+                .collect::<Result<Fields, _>>()?;
+            let values = obj
+                .get("values")
+                .and_then(Value::as_array)
+                .ok_or("Missing or invalid 'values'")?;
+            let columns = fields
+                .iter()
+                .zip(values)
+                .map(|(field, value)| json_to_array(std::slice::from_ref(value), field.data_type()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let array = StructArray::try_new(fields, columns, single_row_nulls(obj))?;
+            Ok(ScalarValue::Struct(Arc::new(array)))
+        }
+        "Map" => {
+            let entries_field =
+                json_to_field(obj.get("entries").ok_or("Missing 'entries' for Map")?)?;
+            let DataType::Struct(entry_fields) = entries_field.data_type() else {
+                return Err("Map entries must be a struct".into());
+            };
+            if entry_fields.len() != 2 {
+                return Err("Map entries must have a key and a value field".into());
+            }
+            let keys = json_to_array(
+                obj.get("keys")
+                    .and_then(Value::as_array)
+                    .ok_or("Missing or invalid 'keys'")?,
+                entry_fields[0].data_type(),
+            )?;
+            let values = json_to_array(
+                obj.get("values")
+                    .and_then(Value::as_array)
+                    .ok_or("Missing or invalid 'values'")?,
+                entry_fields[1].data_type(),
+            )?;
+            let sorted = obj.get("sorted").and_then(Value::as_bool).unwrap_or(false);
+            let offsets = OffsetBuffer::from_lengths([keys.len()]);
+            let entries = StructArray::try_new(entry_fields.clone(), vec![keys, values], None)?;
+            let array = MapArray::try_new(
+                Arc::new(entries_field),
+                offsets,
+                entries,
+                single_row_nulls(obj),
+                sorted,
+            )?;
+            Ok(ScalarValue::Map(Arc::new(array)))
+        }
+        "Utf8View" => Ok(ScalarValue::Utf8View(
+            obj.get("value").and_then(Value::as_str).map(String::from),
+        )),
+        "BinaryView" => Ok(ScalarValue::BinaryView(
+            obj.get("value")
+                .and_then(Value::as_str)
+                .map(|s| STANDARD.decode(s).unwrap()),
+        )),
+        "Dictionary" => {
+            let key_type = json_to_data_type(
+                obj.get("key_type")
+                    .ok_or("Missing 'key_type' for Dictionary")?,
+            )?;
+            let value = json_to_scalar(obj.get("value").ok_or("Missing 'value' for Dictionary")?)?;
+            Ok(ScalarValue::Dictionary(Box::new(key_type), Box::new(value)))
+        }
         _ => Err(format!("Unsupported type: {}", typ).into()),
     }
 }
 
+/// Nested scalars hold a single row array, its elements are written as a list of scalars.
+fn array_to_json(array: &ArrayRef) -> Vec<Value> {
+    (0..array.len())
+        .map(|i| scalar_to_json(&ScalarValue::try_from_array(array, i).unwrap()))
+        .collect()
+}
+
+fn json_to_array(
+    values: &[Value],
+    data_type: &DataType,
+) -> Result<ArrayRef, Box<dyn std::error::Error>> {
+    if values.is_empty() {
+        return Ok(new_empty_array(data_type));
+    }
+    let scalars = values
+        .iter()
+        .map(json_to_scalar)
+        .collect::<Result<Vec<_>, _>>()?;
+    let array = ScalarValue::iter_to_array(scalars)?;
+    // Field names and nullability of nested types are not carried by the element scalars
+    if array.data_type() != data_type {
+        return Ok(arrow::compute::cast(&array, data_type)?);
+    }
+    Ok(array)
+}
+
+fn list_to_json(typ: &str, data_type: &DataType, is_null: bool, values: &ArrayRef) -> Value {
+    let (field, size) = match data_type {
+        DataType::List(field) | DataType::LargeList(field) => (field, None),
+        DataType::FixedSizeList(field, size) => (field, Some(*size)),
+        _ => unreachable!(),
+    };
+    json!({
+        "type": typ,
+        "field": field_to_json(field),
+        "size": size,
+        "values": if is_null { vec![] } else { array_to_json(values) },
+        "null": is_null,
+        "version": NESTED_FORMAT_VERSION
+    })
+}
+
+fn json_to_list(
+    typ: &str,
+    obj: &serde_json::Map<String, Value>,
+) -> Result<ScalarValue, Box<dyn std::error::Error>> {
+    let field = Arc::new(json_to_field(
+        obj.get("field").ok_or("Missing 'field' for list")?,
+    )?);
+    let values = obj
+        .get("values")
+        .and_then(Value::as_array)
+        .ok_or("Missing or invalid 'values'")?;
+    let nulls = single_row_nulls(obj);
+
+    match typ {
+        "List" => {
+            let values = json_to_array(values, field.data_type())?;
+            let offsets = OffsetBuffer::from_lengths([values.len()]);
+            let list = ListArray::try_new(field, offsets, values, nulls)?;
+            Ok(ScalarValue::List(Arc::new(list)))
+        }
+        "LargeList" => {
+            let values = json_to_array(values, field.data_type())?;
+            let offsets = OffsetBuffer::from_lengths([values.len()]);
+            let list = LargeListArray::try_new(field, offsets, values, nulls)?;
+            Ok(ScalarValue::LargeList(Arc::new(list)))
+        }
+        _ => {
+            let size = obj
+                .get("size")
+                .and_then(Value::as_i64)
+                .ok_or("Missing 'size' for FixedSizeList")? as i32;
+            let values = if nulls.is_some() {
+                new_null_array(field.data_type(), size as usize)
+            } else {
+                json_to_array(values, field.data_type())?
+            };
+            let list = FixedSizeListArray::try_new(field, size, values, nulls)?;
+            Ok(ScalarValue::FixedSizeList(Arc::new(list)))
+        }
+    }
+}
+
+fn nested_version(obj: &serde_json::Map<String, Value>) -> Result<u64, Box<dyn std::error::Error>> {
+    match obj.get("version").map(Value::as_u64) {
+        None => Ok(1),
+        Some(Some(version)) if version <= NESTED_FORMAT_VERSION => Ok(version),
+        Some(version) => Err(format!("Unsupported nested scalar version {version:?}").into()),
+    }
+}
+
+/// A version 1 list, which held its first element and the element type
+fn json_to_legacy_list(
+    obj: &serde_json::Map<String, Value>,
+) -> Result<ScalarValue, Box<dyn std::error::Error>> {
+    let element_type = json_to_data_type(
+        obj.get("field_type")
+            .ok_or("Missing 'field_type' for List")?,
+    )?;
+    let element = json_to_scalar(obj.get("value").ok_or("Missing 'value' for List")?)?;
+    let field = Arc::new(Field::new("item", element_type, true));
+    let values = element.to_array_of_size(1)?;
+    let list = ListArray::try_new(field, OffsetBuffer::from_lengths([1]), values, None)?;
+    Ok(ScalarValue::List(Arc::new(list)))
+}
+
+/// A version 1 struct, which held `[name, type]` pairs for its fields
+fn json_to_legacy_struct(
+    obj: &serde_json::Map<String, Value>,
+) -> Result<ScalarValue, Box<dyn std::error::Error>> {
+    let fields = obj
+        .get("fields")
+        .and_then(Value::as_array)
+        .ok_or("Missing or invalid 'fields'")?;
+    let values = obj
+        .get("values")
+        .and_then(Value::as_array)
+        .ok_or("Missing or invalid 'values'")?;
+    let mut struct_fields = Vec::with_capacity(fields.len());
+    let mut columns = Vec::with_capacity(fields.len());
+    for (field, value) in fields.iter().zip(values) {
+        let name = field
+            .get(0)
+            .and_then(Value::as_str)
+            .ok_or("Invalid struct field")?;
+        let data_type = json_to_data_type(field.get(1).ok_or("Invalid struct field")?)?;
+        struct_fields.push(Field::new(name, data_type, true));
+        columns.push(json_to_scalar(value)?.to_array_of_size(1)?);
+    }
+    let array = StructArray::try_new(struct_fields.into(), columns, None)?;
+    Ok(ScalarValue::Struct(Arc::new(array)))
+}
+
+fn single_row_nulls(obj: &serde_json::Map<String, Value>) -> Option<NullBuffer> {
+    obj.get("null")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+        .then(|| NullBuffer::new_null(1))
+}
+
+fn interval_part(parts: &[Value], index: usize) -> Result<i64, Box<dyn std::error::Error>> {
+    parts
+        .get(index)
+        .and_then(Value::as_i64)
+        .ok_or_else(|| format!("Invalid interval component at {index}").into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use arrow_array::{Float64Array, Int32Array, Int64Array, StringArray};
     use datafusion::common::ScalarValue;
 
     fn test_roundtrip(scalar: ScalarValue) {
@@ -590,12 +955,137 @@ mod tests {
         test_roundtrip(ScalarValue::FixedSizeBinary(5, None));
     }
 
-    // #[test]
-    // fn test_list() {
-    //     let inner = ScalarValue::Int32(Some(42));
-    //     let list = ScalarValue::List(Arc::new(inner.to_array().unwrap()));
-    //     test_roundtrip(list);
-    // }
+    #[test]
+    fn test_interval_and_duration() {
+        test_roundtrip(ScalarValue::IntervalYearMonth(Some(14)));
+        test_roundtrip(ScalarValue::IntervalDayTime(Some(IntervalDayTime::new(
+            3, -500,
+        ))));
+        test_roundtrip(ScalarValue::IntervalDayTime(None));
+        test_roundtrip(ScalarValue::IntervalMonthDayNano(Some(
+            IntervalMonthDayNano::new(1, 2, 3_000_000_000),
+        )));
+        test_roundtrip(ScalarValue::IntervalMonthDayNano(None));
+        test_roundtrip(ScalarValue::DurationMillisecond(Some(1500)));
+    }
+
+    #[test]
+    fn test_list() {
+        let values = Int32Array::from(vec![Some(1), None, Some(3)]);
+        let list = ListArray::new(
+            Arc::new(Field::new("item", DataType::Int32, true)),
+            OffsetBuffer::from_lengths([3]),
+            Arc::new(values),
+            None,
+        );
+        test_roundtrip(ScalarValue::List(Arc::new(list)));
+        test_roundtrip(ScalarValue::new_null_list(DataType::Utf8, true, 1));
+        test_roundtrip(ScalarValue::LargeList(ScalarValue::new_large_list(
+            &[ScalarValue::from("a"), ScalarValue::from("b")],
+            &DataType::Utf8,
+        )));
+
+        let fixed = FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float64, true)),
+            2,
+            Arc::new(Float64Array::from(vec![1.5, -2.0])),
+            None,
+        );
+        test_roundtrip(ScalarValue::FixedSizeList(Arc::new(fixed)));
+    }
+
+    #[test]
+    fn test_version_1_nested_scalars() {
+        let list = json!({
+            "type": "List",
+            "field_type": data_type_to_json(&DataType::Int64),
+            "value": {"type": "Int64", "value": 7}
+        });
+        assert_eq!(
+            json_to_scalar(&list).unwrap(),
+            ScalarValue::List(ScalarValue::new_list_nullable(
+                &[ScalarValue::Int64(Some(7))],
+                &DataType::Int64
+            ))
+        );
+
+        let structure = json!({
+            "type": "Struct",
+            "fields": [["id", data_type_to_json(&DataType::Int64)]],
+            "values": [{"type": "Int64", "value": 7}]
+        });
+        let ScalarValue::Struct(array) = json_to_scalar(&structure).unwrap() else {
+            panic!("expected a struct");
+        };
+        assert_eq!(array.fields()[0].name(), "id");
+        assert_eq!(
+            ScalarValue::try_from_array(array.column(0), 0).unwrap(),
+            ScalarValue::Int64(Some(7))
+        );
+
+        let mut future = scalar_to_json(&ScalarValue::new_null_list(DataType::Utf8, true, 1));
+        future["version"] = json!(NESTED_FORMAT_VERSION + 1);
+        assert!(json_to_scalar(&future).is_err());
+    }
+
+    #[test]
+    fn test_list_of_structs() {
+        let fields = Fields::from(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+        let structs = StructArray::new(
+            fields.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("a"), None])),
+            ],
+            None,
+        );
+        let list = ListArray::new(
+            Arc::new(Field::new("item", DataType::Struct(fields), true)),
+            OffsetBuffer::from_lengths([2]),
+            Arc::new(structs),
+            None,
+        );
+        test_roundtrip(ScalarValue::List(Arc::new(list)));
+    }
+
+    #[test]
+    fn test_map() {
+        let entries_fields = Fields::from(vec![
+            Field::new("keys", DataType::Utf8, false),
+            Field::new("values", DataType::Int32, true),
+        ]);
+        let entries = StructArray::new(
+            entries_fields.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["x", "y"])),
+                Arc::new(Int32Array::from(vec![Some(1), None])),
+            ],
+            None,
+        );
+        let map = MapArray::new(
+            Arc::new(Field::new(
+                "entries",
+                DataType::Struct(entries_fields),
+                false,
+            )),
+            OffsetBuffer::from_lengths([2]),
+            entries,
+            None,
+            false,
+        );
+        test_roundtrip(ScalarValue::Map(Arc::new(map)));
+    }
+
+    #[test]
+    fn test_dictionary() {
+        test_roundtrip(ScalarValue::Dictionary(
+            Box::new(DataType::Int32),
+            Box::new(ScalarValue::from("value")),
+        ));
+    }
 
     #[test]
     fn test_timestamp() {
@@ -614,6 +1104,70 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_decimal() {
+        test_roundtrip(ScalarValue::Decimal128(Some(-123_456_789), 38, 10));
+        test_roundtrip(ScalarValue::Decimal128(None, 10, 2));
+        test_roundtrip(ScalarValue::Decimal256(Some(i256::from_i128(42)), 76, -3));
+    }
+
+    #[test]
+    fn test_data_type_roundtrip() {
+        let item = Arc::new(Field::new("element", DataType::Decimal128(20, 4), false));
+        let data_types = vec![
+            DataType::Null,
+            DataType::Boolean,
+            DataType::Int8,
+            DataType::UInt64,
+            DataType::Float16,
+            DataType::Utf8,
+            DataType::LargeBinary,
+            DataType::Date64,
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            DataType::Timestamp(TimeUnit::Millisecond, Some("+05:30".into())),
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("Europe/Berlin".into())),
+            DataType::Time32(TimeUnit::Second),
+            DataType::Time64(TimeUnit::Nanosecond),
+            DataType::Duration(TimeUnit::Millisecond),
+            DataType::Interval(IntervalUnit::MonthDayNano),
+            DataType::FixedSizeBinary(16),
+            DataType::Decimal128(38, 10),
+            DataType::Decimal256(76, -2),
+            DataType::List(item.clone()),
+            DataType::LargeList(item.clone()),
+            DataType::FixedSizeList(item.clone(), 3),
+            DataType::Struct(
+                vec![
+                    Field::new("a", DataType::Int32, false),
+                    Field::new(
+                        "b",
+                        DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+                        true,
+                    ),
+                ]
+                .into(),
+            ),
+            DataType::Dictionary(Box::new(DataType::Int16), Box::new(DataType::Utf8)),
+        ];
+        for data_type in data_types {
+            let json = data_type_to_json(&data_type);
+            assert_eq!(json_to_data_type(&json).unwrap(), data_type);
+        }
+    }
+
+    #[test]
+    fn test_data_type_from_string() {
+        for data_type in [
+            DataType::Decimal128(38, 10),
+            DataType::Decimal256(50, 5),
+            DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into())),
+            DataType::Timestamp(TimeUnit::Second, None),
+        ] {
+            let parsed = string_to_data_type(&data_type.to_string()).unwrap();
+            assert_eq!(parsed, data_type);
+        }
+    }
+
     #[test]
     fn test_serializable_scalar_value() {
         let original = ScalarValue::Int32(Some(42));
@@ -0,0 +1,211 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ListArray, RecordBatch, RecordBatchOptions};
+use arrow::buffer::OffsetBuffer;
+use arrow::datatypes::{Field, Schema};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use datafusion::common::{internal_err, DataFusionError, Result, ScalarValue};
+use serde::Deserialize;
+
+use super::groups_snapshot::{batch_from_ipc, batch_to_ipc};
+use super::serialize::SerializableScalarValue;
+
+/// Prefix of every accumulator state written since the format was versioned, followed by the
+/// version and the [`StateFormat`]. States without it are the JSON of earlier checkpoints.
+const STATE_MAGIC: &[u8; 4] = b"DNAS";
+const STATE_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StateFormat {
+    Json = 1,
+    ArrowIpc = 2,
+}
+
+impl StateFormat {
+    fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            1 => Ok(Self::Json),
+            2 => Ok(Self::ArrowIpc),
+            other => internal_err!("Unknown accumulator state format {other}"),
+        }
+    }
+
+    fn serde(self) -> &'static dyn StateSerde {
+        match self {
+            Self::Json => &JsonStateSerde,
+            Self::ArrowIpc => &IpcStateSerde,
+        }
+    }
+}
+
+/// Encoding of the state fields of an accumulator, as returned by
+/// [`Accumulator::state`](datafusion::logical_expr::Accumulator::state), in checkpoints.
+/// Whatever the encoding, [`decode_state`] reads it back from the header written with it.
+pub trait StateSerde: Debug + Send + Sync {
+    fn format(&self) -> StateFormat;
+    fn encode(&self, state: &[ScalarValue]) -> Result<Vec<u8>>;
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<ScalarValue>>;
+}
+
+/// Every state field as tagged JSON. Readable when debugging a checkpoint, but list states
+/// take many times the space of their values.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonStateSerde;
+
+impl StateSerde for JsonStateSerde {
+    fn format(&self) -> StateFormat {
+        StateFormat::Json
+    }
+
+    fn encode(&self, state: &[ScalarValue]) -> Result<Vec<u8>> {
+        let state: Vec<_> = state
+            .iter()
+            .cloned()
+            .map(SerializableScalarValue::from)
+            .collect();
+        serde_json::to_vec(&state).map_err(|e| DataFusionError::External(Box::new(e)))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<ScalarValue>> {
+        let state: Vec<SerializableScalarValue> = serde_json::from_slice(bytes)
+            .map_err(|e| DataFusionError::Internal(format!("Invalid accumulator state: {e}")))?;
+        Ok(state.into_iter().map(ScalarValue::from).collect())
+    }
+}
+
+/// Every state field as a column of a single row Arrow IPC batch. Values keep their exact
+/// type, decimals and timezones included, and list states are stored as plain buffers. The
+/// default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IpcStateSerde;
+
+impl StateSerde for IpcStateSerde {
+    fn format(&self) -> StateFormat {
+        StateFormat::ArrowIpc
+    }
+
+    fn encode(&self, state: &[ScalarValue]) -> Result<Vec<u8>> {
+        let fields = state
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Field::new(format!("state_{i}"), value.data_type(), true))
+            .collect::<Vec<_>>();
+        let columns = state
+            .iter()
+            .map(|value| value.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        let batch = RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields)),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(1)),
+        )?;
+        batch_to_ipc(&batch)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<ScalarValue>> {
+        batch_from_ipc(bytes)?
+            .columns()
+            .iter()
+            .map(|column| ScalarValue::try_from_array(column, 0))
+            .collect()
+    }
+}
+
+/// `state` in the format of `serde`, behind the versioned header
+pub fn encode_state(serde: &dyn StateSerde, state: &[ScalarValue]) -> Result<Vec<u8>> {
+    let mut bytes = STATE_MAGIC.to_vec();
+    bytes.push(STATE_VERSION);
+    bytes.push(serde.format() as u8);
+    bytes.extend(serde.encode(state)?);
+    Ok(bytes)
+}
+
+/// The state fields written by [`encode_state`] or, without a header, by the JSON encoding
+/// checkpoints used before
+pub fn decode_state(bytes: &[u8]) -> Result<Vec<ScalarValue>> {
+    let Some(versioned) = bytes.strip_prefix(STATE_MAGIC.as_slice()) else {
+        return decode_unversioned_state(bytes);
+    };
+    match versioned {
+        [STATE_VERSION, format, payload @ ..] => {
+            StateFormat::from_tag(*format)?.serde().decode(payload)
+        }
+        [version, ..] => internal_err!("Unsupported accumulator state version {version}"),
+        [] => internal_err!("Truncated accumulator state"),
+    }
+}
+
+/// Unversioned states are JSON objects holding either a list of tagged scalars or, for
+/// DISTINCT aggregates, one base64 Arrow IPC batch per value set
+#[derive(Debug, Deserialize)]
+struct UnversionedState {
+    #[serde(default)]
+    state: Vec<SerializableScalarValue>,
+    #[serde(default)]
+    sets: Vec<String>,
+}
+
+fn decode_unversioned_state(bytes: &[u8]) -> Result<Vec<ScalarValue>> {
+    let unversioned: UnversionedState = serde_json::from_slice(bytes)
+        .map_err(|e| DataFusionError::Internal(format!("Invalid accumulator state: {e}")))?;
+    let mut state: Vec<ScalarValue> = unversioned
+        .state
+        .into_iter()
+        .map(ScalarValue::from)
+        .collect();
+    for set in &unversioned.sets {
+        let encoded = STANDARD
+            .decode(set)
+            .map_err(|e| DataFusionError::Internal(format!("Invalid distinct state: {e}")))?;
+        let values = batch_from_ipc(&encoded)?.column(0).clone();
+        let field = Arc::new(Field::new("item", values.data_type().clone(), true));
+        let offsets = OffsetBuffer::from_lengths([values.len()]);
+        let list = ListArray::try_new(field, offsets, values, None)?;
+        state.push(ScalarValue::List(Arc::new(list)));
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::datatypes::Int64Type;
+
+    #[test]
+    fn roundtrip_versioned_states() -> Result<()> {
+        let values = (0..10_000).map(Some);
+        let state = vec![
+            ScalarValue::Decimal128(Some(123_456_789_012_345_678), 38, 10),
+            ScalarValue::TimestampNanosecond(
+                Some(1_700_000_000_123_456_789),
+                Some("+02:00".into()),
+            ),
+            ScalarValue::List(Arc::new(ListArray::from_iter_primitive::<Int64Type, _, _>(
+                [Some(values)],
+            ))),
+            ScalarValue::Null,
+        ];
+
+        let binary = encode_state(&IpcStateSerde, &state)?;
+        let json = encode_state(&JsonStateSerde, &state)?;
+        assert_eq!(decode_state(&binary)?, state);
+        assert_eq!(decode_state(&json)?, state);
+        assert!(binary.len() * 2 < json.len());
+
+        let unversioned = serde_json::json!({
+            "state": [SerializableScalarValue::from(ScalarValue::Int64(Some(7)))]
+        });
+        assert_eq!(
+            decode_state(unversioned.to_string().as_bytes())?,
+            [ScalarValue::Int64(Some(7))]
+        );
+
+        let mut future = binary.clone();
+        future[STATE_MAGIC.len()] = STATE_VERSION + 1;
+        assert!(decode_state(&future).is_err());
+        Ok(())
+    }
+}
@@ -3,7 +3,114 @@ use datafusion::config::ConfigExtension;
 
 extensions_options! {
     pub struct DenormalizedConfig {
+        /// Stable name of the pipeline, used in Kafka client ids, checkpoint paths and logs
+        pub job_id: String, default = String::new()
+        /// Identifies this run of the job, a random id is generated when empty
+        pub run_id: String, default = String::new()
+        /// How many times this run has been restarted, set by whatever supervises the job
+        pub attempt: u64, default = 0
         pub checkpoint: bool, default = false
+        /// Directory RocksDB checkpoints and savepoints are written to. When set, the state is
+        /// checkpointed every time a source closes an epoch
+        pub checkpoint_dir: String, default = String::new()
+        /// Number of completed checkpoints kept, older ones are deleted. Savepoints are kept
+        pub checkpoint_retain: usize, default = 3
+        /// How far event time may move back when restoring source watermarks, in milliseconds.
+        /// Rows older than the restored watermark less this allowance are skipped
+        pub watermark_rewind_ms: u64, default = 0
+        /// State entries deleted when the state is opened, as `namespace=key prefix,...`, e.g.
+        /// `accounts=tenant-7/` to drop a tenant being offboarded
+        pub restore_prune_prefixes: String, default = String::new()
+        /// zstd level for checkpointed state, 0 stores it uncompressed
+        pub checkpoint_compression_level: i32, default = 3
+        /// How to handle state that fails its checksum on restore: fail, skip or repair
+        pub checkpoint_corruption_policy: String, default = "fail".to_string()
+        /// Name of the secret holding the 32 byte AES-GCM key for checkpointed state, empty
+        /// leaves state unencrypted
+        pub checkpoint_encryption_key: String, default = String::new()
+        /// Plan and validate the pipeline without consuming or producing any data. Kafka topics
+        /// built with `KafkaTopicBuilder::with_dry_run` don't connect to their brokers either
+        pub dry_run: bool, default = false
+        /// Profile every operator and report after this many seconds, 0 disables profiling
+        pub profile_after_secs: u64, default = 0
+        /// File the folded profile stacks are written to, printed to stdout when empty
+        pub profile_output: String, default = String::new()
+        /// Memory the operators of the pipeline may reserve, in bytes, 0 leaves it unbounded
+        pub memory_limit_bytes: usize, default = 0
+        /// Size of the checkpointed state past which the pipeline fails, 0 leaves it unbounded
+        pub max_state_bytes: u64, default = 0
+        /// Rows per second sinks may write, 0 leaves the rate unbounded
+        pub max_output_rows_per_sec: u64, default = 0
+        /// What to do when sinks exceed their rate: backpressure or fail
+        pub quota_policy: String, default = "backpressure".to_string()
+        /// Per operator log levels as `operator=level,...`, e.g. `kafka_source=debug`. Only
+        /// applied when logging was set up with `LogLevels::init`
+        pub operator_log_levels: String, default = String::new()
+        /// Log a backpressure report when sources fall this far behind event time, in
+        /// milliseconds, 0 disables the check
+        pub diagnose_lag_ms: u64, default = 0
+        /// Least time between two backpressure reports logged because of lag
+        pub diagnose_interval_secs: u64, default = 60
+        /// Coalesce the inputs of joins into batches of this many rows, 0 disables coalescing
+        pub coalesce_target_rows: usize, default = 0
+        /// Longest time a coalescer holds on to buffered rows before emitting them
+        pub coalesce_max_wait_ms: u64, default = 100
+        /// Run chains of filters and projections as a single operator
+        pub fuse_operators: bool, default = true
+        /// Make runs over the same input produce identical output, for regression tests: one
+        /// partition per operator, a logical clock instead of the wall clock and seeded
+        /// randomness
+        pub deterministic: bool, default = false
+        /// Seed of the random numbers drawn in deterministic mode
+        pub deterministic_seed: u64, default = 0
+        /// Time the logical clock starts at in deterministic mode, in milliseconds since the
+        /// unix epoch
+        pub deterministic_clock_start_ms: i64, default = 0
+        /// Probability of a source batch being dropped, to test recovery
+        pub fault_drop_probability: f64, default = 0.0
+        /// Probability of a source batch being held back for `fault_delay_ms`
+        pub fault_delay_probability: f64, default = 0.0
+        pub fault_delay_ms: u64, default = 1000
+        /// Probability of a sink failing to write a batch
+        pub fault_send_failure_probability: f64, default = 0.0
+        /// Abort the process once a source closes this checkpoint epoch, 0 never does
+        pub fault_crash_at_epoch: u64, default = 0
+        /// Seed of the randomly injected faults
+        pub fault_seed: u64, default = 0
+        /// Read and process larger batches on more tasks while a source lags event time by more
+        /// than this, in milliseconds, 0 disables backfill mode
+        pub backfill_lag_ms: u64, default = 0
+        /// Lag below which a source no longer counts as backfilling, half of
+        /// `backfill_lag_ms` when 0
+        pub backfill_exit_lag_ms: u64, default = 0
+        /// How many times larger source reads and coalesced batches are while backfilling
+        pub backfill_batch_factor: usize, default = 10
+        /// Tasks sources decode on while backfilling, the number of cores when 0
+        pub backfill_parallelism: usize, default = 0
+        /// Percent of every read sources spend on records produced after the job started while
+        /// they catch up on a backlog, e.g. 80 to keep live results fresh during a
+        /// reprocessing. 0 reads the backlog first
+        pub fresh_data_percent: u64, default = 0
+        /// Compress the window state of keys that received no rows for this many seconds,
+        /// trading CPU for memory with long sessions over many idle keys. 0 keeps all state
+        /// uncompressed
+        pub cold_state_after_secs: u64, default = 0
+    }
+}
+
+impl DenormalizedConfig {
+    /// Whether operators are wrapped to collect profiles, for the profile report or for
+    /// backpressure diagnostics
+    pub fn profiling_enabled(&self) -> bool {
+        self.profile_after_secs > 0 || self.diagnose_lag_ms > 0
+    }
+
+    /// Whether any `fault_*` option asks for faults to be injected
+    pub fn fault_injection_enabled(&self) -> bool {
+        self.fault_drop_probability > 0.0
+            || self.fault_delay_probability > 0.0
+            || self.fault_send_failure_probability > 0.0
+            || self.fault_crash_at_epoch > 0
     }
 }
 
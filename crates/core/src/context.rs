@@ -1,27 +1,117 @@
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
-use datafusion::common::{DataFusionError, Result};
+use arrow_schema::SchemaRef;
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::dataframe::DataFrame;
+use datafusion::datasource::listing::ListingTableUrl;
 use datafusion::datasource::TableProvider;
 use datafusion::execution::{
-    config::SessionConfig, context::SessionContext, runtime_env::RuntimeEnv,
+    config::SessionConfig,
+    context::SessionContext,
+    runtime_env::{RuntimeConfig, RuntimeEnv},
     session_state::SessionStateBuilder,
 };
+use log::info;
 
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::catalog_sync::{CatalogSync, TableDefinition};
+use crate::datasource::delta::DeltaChangelog;
+use crate::datasource::epoch::EpochTracker;
 use crate::datasource::kafka::TopicReader;
+#[cfg(feature = "redis")]
+use crate::datasource::redis_reference::{RedisReference, RedisReferenceSource};
+use crate::datasource::side_output::{SideOutputReader, SideOutputRegistry};
 use crate::datastream::DataStream;
-use crate::physical_optimizer::CoaslesceBeforeStreamingAggregate;
+use crate::functions::register_denormalized_functions;
+use crate::logical_plan::interval_join::plan_interval_joins;
+use crate::logical_plan::window_table_functions::plan_window_table_functions;
+use crate::physical_optimizer::{
+    CheckStreamMetadata, CoalesceBeforeJoin, CoaslesceBeforeStreamingAggregate,
+    FuseStatelessOperators, InjectFaults, ProfileOperators, RegisterPlanMetrics,
+};
+use crate::physical_plan::lookup_join::LookupTable;
 use crate::query_planner::StreamingQueryPlanner;
+use crate::state_backend::rocksdb_backend::initialize_global_rocksdb_from_config;
+use crate::utils::backfill::BackfillController;
+use crate::utils::determinism::enable_logical_clock;
+use crate::utils::diagnostics::BackpressureReport;
+use crate::utils::fault_injection::FaultInjector;
 use crate::utils::get_default_optimizer_rules;
+use crate::utils::job::JobIdentity;
+use crate::utils::logging::LogLevels;
+#[cfg(feature = "prometheus")]
+use crate::utils::metrics_export::MetricsExporter;
+use crate::utils::metrics_export::MetricsRegistry;
+use crate::utils::profiling::Profiler;
+use crate::utils::quota::ResourceQuotas;
+use crate::utils::secrets::EnvSecretsProvider;
 
 #[derive(Clone)]
 pub struct Context {
     pub session_conext: Arc<RwLock<SessionContext>>,
+    pub job: Arc<JobIdentity>,
+    profiler: Option<Arc<Profiler>>,
+    metrics: Arc<MetricsRegistry>,
+    catalog_sync: Option<Arc<dyn CatalogSync>>,
 }
 
 impl Context {
     pub fn new() -> Result<Self, DataFusionError> {
-        let config = SessionConfig::new()
+        Self::with_config(DenormalizedConfig::default())
+    }
+
+    /// Create a context with the given denormalized specific configuration
+    pub fn with_config(denormalized_config: DenormalizedConfig) -> Result<Self, DataFusionError> {
+        let job = Arc::new(JobIdentity::from_config(&denormalized_config));
+        info!("Creating context for {job}");
+        if let Some(log_levels) = LogLevels::global() {
+            log_levels.apply_overrides(&denormalized_config.operator_log_levels)?;
+        }
+
+        let profiler = denormalized_config.profiling_enabled().then(|| {
+            let report_after = (denormalized_config.profile_after_secs > 0)
+                .then(|| Duration::from_secs(denormalized_config.profile_after_secs));
+            let output_path = (!denormalized_config.profile_output.is_empty())
+                .then(|| PathBuf::from(&denormalized_config.profile_output));
+            let mut profiler = Profiler::new(report_after, output_path).with_job(job.clone());
+            if denormalized_config.diagnose_lag_ms > 0 {
+                profiler = profiler.with_lag_alert(
+                    Duration::from_millis(denormalized_config.diagnose_lag_ms),
+                    Duration::from_secs(denormalized_config.diagnose_interval_secs),
+                );
+            }
+            Arc::new(profiler)
+        });
+
+        if denormalized_config.deterministic {
+            enable_logical_clock(denormalized_config.deterministic_clock_start_ms);
+        }
+        let target_partitions = match denormalized_config.deterministic {
+            true => 1,
+            false => SessionConfig::new().target_partitions(),
+        };
+
+        // A dry run reads no data, so there is no state to restore or keep
+        if denormalized_config.checkpoint && !denormalized_config.dry_run {
+            initialize_global_rocksdb_from_config(&denormalized_config, &job, &EnvSecretsProvider)?;
+        }
+
+        let metrics = Arc::new(MetricsRegistry::new(job.clone()));
+        let quotas = Arc::new(ResourceQuotas::from_config(&denormalized_config)?);
+        let fault_injector = FaultInjector::from_config(&denormalized_config)?;
+        let backfill = BackfillController::from_config(&denormalized_config)?;
+        let runtime = match denormalized_config.memory_limit_bytes {
+            0 => RuntimeEnv::default(),
+            limit => RuntimeEnv::new(RuntimeConfig::new().with_memory_limit(limit, 1.0))?,
+        };
+
+        // A single partition per operator keeps the order rows reach it in from depending on
+        // thread scheduling
+        let mut config = SessionConfig::new()
+            .with_target_partitions(target_partitions)
             .set(
                 "datafusion.execution.batch_size",
                 datafusion::common::ScalarValue::UInt64(Some(32)),
@@ -31,26 +121,70 @@ impl Context {
             .set(
                 "datafusion.execution.coalesce_batches",
                 datafusion::common::ScalarValue::Boolean(Some(false)),
-            );
-
-        let runtime = Arc::new(RuntimeEnv::default());
+            )
+            .with_option_extension(denormalized_config)
+            .with_extension(Arc::new(SideOutputRegistry::default()))
+            .with_extension(Arc::new(EpochTracker::default()))
+            .with_extension(job.clone())
+            .with_extension(quotas);
+        if let Some(profiler) = &profiler {
+            config = config.with_extension(profiler.clone());
+        }
+        if let Some(fault_injector) = fault_injector {
+            config = config.with_extension(Arc::new(fault_injector));
+        }
+        if let Some(backfill) = backfill {
+            config = config.with_extension(Arc::new(backfill));
+        }
 
         let state = SessionStateBuilder::new()
             .with_default_features()
             .with_config(config)
-            .with_runtime_env(runtime)
+            .with_runtime_env(Arc::new(runtime))
             .with_query_planner(Arc::new(StreamingQueryPlanner {}))
             .with_optimizer_rules(get_default_optimizer_rules())
             .with_physical_optimizer_rule(Arc::new(CoaslesceBeforeStreamingAggregate::new()))
+            .with_physical_optimizer_rule(Arc::new(CoalesceBeforeJoin::new()))
+            .with_physical_optimizer_rule(Arc::new(FuseStatelessOperators::new()))
+            .with_physical_optimizer_rule(Arc::new(CheckStreamMetadata::new()))
+            .with_physical_optimizer_rule(Arc::new(InjectFaults::new()))
+            .with_physical_optimizer_rule(Arc::new(ProfileOperators::new()))
+            .with_physical_optimizer_rule(Arc::new(RegisterPlanMetrics::new(metrics.clone())))
             .build();
 
+        let mut session_context = SessionContext::new_with_state(state);
+        register_denormalized_functions(&mut session_context);
+
         Ok(Self {
-            session_conext: Arc::new(RwLock::new(SessionContext::new_with_state(state))),
+            session_conext: Arc::new(RwLock::new(session_context)),
+            job,
+            profiler,
+            metrics,
+            catalog_sync: None,
         })
     }
 
+    /// Publish the definitions of the topics the pipeline reads and writes to an external
+    /// catalog when they are registered
+    pub fn with_catalog_sync(mut self, catalog_sync: Arc<dyn CatalogSync>) -> Self {
+        self.catalog_sync = Some(catalog_sync);
+        self
+    }
+
     pub async fn from_topic(&self, topic: TopicReader) -> Result<DataStream, DataFusionError> {
         let topic_name = topic.0.topic.clone();
+        if topic.0.dry_run && !self.dry_run().await {
+            return plan_err!(
+                "Topic {topic_name} was built for a dry run, set the dry_run option to plan it"
+            );
+        }
+        self.sync_table(TableDefinition::kafka(
+            &topic_name,
+            &topic.0.bootstrap_servers,
+            topic.0.original_schema.clone(),
+            topic.0.encoding,
+        ))
+        .await?;
 
         self.register_table(topic_name.clone(), Arc::new(topic))
             .await?;
@@ -69,6 +203,164 @@ impl Context {
         Ok(ds)
     }
 
+    /// Read `source` as a stream, registered as the table `name`. Sources attach the stream
+    /// metadata to their batches themselves.
+    pub async fn from_source(
+        &self,
+        name: &str,
+        source: Arc<dyn TableProvider>,
+    ) -> Result<DataStream> {
+        self.session_conext
+            .write()
+            .await
+            .register_table(name, source)?;
+        let df = self.session_conext.read().await.table(name).await?;
+
+        Ok(DataStream {
+            df: Arc::new(df),
+            context: Arc::new(self.clone()),
+        })
+    }
+
+    /// Read the Delta table at `table_uri` as a changelog, see [`DeltaChangelog`]. The URI is
+    /// a local path or the URL of an object store registered with the session.
+    pub async fn from_delta(
+        &self,
+        name: &str,
+        table_uri: &str,
+        poll_interval: Duration,
+    ) -> Result<DataStream> {
+        let url = ListingTableUrl::parse(table_uri)?;
+        let store = self
+            .session_conext
+            .read()
+            .await
+            .runtime_env()
+            .object_store(url.object_store())?;
+        let changelog = DeltaChangelog::try_new(store, url.prefix().clone(), poll_interval).await?;
+        self.from_source(name, Arc::new(changelog)).await
+    }
+
+    /// Read reference data from the Redis server at `url` as a changelog, see
+    /// [`RedisReferenceSource`]
+    #[cfg(feature = "redis")]
+    pub async fn from_redis(
+        &self,
+        name: &str,
+        url: &str,
+        reference: RedisReference,
+        rescan_interval: Option<Duration>,
+    ) -> Result<DataStream> {
+        let source = RedisReferenceSource::try_new(url, reference, rescan_interval)?;
+        self.from_source(name, Arc::new(source)).await
+    }
+
+    /// Register the reference data in `table` as `name`, for enriching streams with
+    /// [`DataStream::lookup_join`]. The table can be queried like any other, e.g. to check
+    /// its contents.
+    pub async fn register_lookup_table(
+        &self,
+        name: &str,
+        table: Arc<dyn TableProvider>,
+    ) -> Result<LookupTable> {
+        self.session_conext
+            .write()
+            .await
+            .register_table(name, table.clone())?;
+        Ok(LookupTable::new(name, table))
+    }
+
+    /// Where the operators of running pipelines spend their time, to find the one holding
+    /// back a backpressured pipeline. Needs `diagnose_lag_ms` or `profile_after_secs` set.
+    pub fn backpressure_report(&self) -> Option<BackpressureReport> {
+        self.profiler
+            .as_ref()
+            .map(|profiler| profiler.backpressure_report())
+    }
+
+    /// The metrics of the operators of running pipelines, e.g. rows and bytes written by sinks
+    /// and the consumer lag of Kafka sources, in the Prometheus text format
+    pub fn metrics(&self) -> Arc<MetricsRegistry> {
+        self.metrics.clone()
+    }
+
+    /// Serve [`Context::metrics`] on `GET /metrics` of `address` for Prometheus to scrape,
+    /// until the returned exporter is dropped
+    #[cfg(feature = "prometheus")]
+    pub async fn serve_metrics(&self, address: &str) -> Result<MetricsExporter> {
+        MetricsExporter::serve(self.metrics.clone(), address).await
+    }
+
+    /// Plan a query over the registered streams, e.g. for previewing it with
+    /// [`DataStream::print_table`]. Dropping the stream's execution cancels the query without
+    /// affecting the rest of the context.
+    ///
+    /// Streams are windowed with the `tumble`, `hop` and `session` table functions, see
+    /// [`register_denormalized_functions`]. Inner joins that bound the event times of both
+    /// sides with a `BETWEEN` are planned as interval joins, see [`plan_interval_joins`].
+    pub async fn sql(&self, query: &str) -> Result<DataStream> {
+        let (session_state, plan) = self
+            .session_conext
+            .read()
+            .await
+            .sql(query)
+            .await?
+            .into_parts();
+        let plan = plan_interval_joins(plan_window_table_functions(plan)?)?;
+        Ok(DataStream {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: Arc::new(self.clone()),
+        })
+    }
+
+    /// Read the rows operators emit onto the side output `tag` as a stream of their own
+    pub async fn side_output(&self, tag: &str, schema: SchemaRef) -> Result<DataStream> {
+        let registry = self
+            .session_conext
+            .read()
+            .await
+            .state()
+            .config()
+            .get_extension::<SideOutputRegistry>()
+            .ok_or_else(|| {
+                DataFusionError::Internal("No side output registry in the session".to_string())
+            })?;
+        let output = registry.output(tag, schema)?;
+
+        self.register_table(tag.to_string(), Arc::new(SideOutputReader::new(output)))
+            .await?;
+
+        let df = self.session_conext.read().await.table(tag).await?;
+
+        Ok(DataStream {
+            df: Arc::new(df),
+            context: Arc::new(self.clone()),
+        })
+    }
+
+    /// Hand `table` to the catalog sync, if any. Dry runs leave the catalog untouched.
+    pub(crate) async fn sync_table(&self, table: TableDefinition) -> Result<()> {
+        let Some(catalog_sync) = &self.catalog_sync else {
+            return Ok(());
+        };
+        if self.dry_run().await {
+            return Ok(());
+        }
+        catalog_sync.sync_table(&table).await
+    }
+
+    async fn dry_run(&self) -> bool {
+        self.session_conext
+            .read()
+            .await
+            .state()
+            .config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .is_some_and(|config| config.dry_run)
+    }
+
     pub async fn register_table(
         &self,
         name: String,
@@ -0,0 +1,368 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use arrow::compute::cast;
+use arrow_array::{Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, SchemaRef};
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan};
+use futures::StreamExt;
+use lapin::options::{
+    BasicAckOptions, BasicConsumeOptions, BasicPublishOptions, BasicQosOptions,
+    ConfirmSelectOptions,
+};
+use lapin::types::FieldTable;
+use lapin::{BasicProperties, Channel, Connection, ConnectionProperties};
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::message::{next_chunk, now_ms, JsonMessageDecoder, MAX_BATCH_MESSAGES};
+use crate::utils::json_format::JsonFormatOptions;
+use crate::utils::json_parse::parse_json;
+use crate::utils::quota::ResourceQuotas;
+use crate::utils::row_encoder::{JsonRowEncoder, RowEncoder};
+
+/// Column [`DataStream::sink_amqp`](crate::datastream::DataStream::sink_amqp) evaluates the
+/// routing key expression into
+pub const ROUTING_KEY_COLUMN: &str = "_routing_key";
+
+/// Consumes JSON messages from a RabbitMQ queue, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// With checkpointing enabled every batch closes an epoch and its deliveries are acknowledged
+/// once the batch was handed downstream, so messages of a job that fails before are delivered
+/// again. Without checkpointing the broker considers messages delivered as soon as it sent
+/// them.
+pub struct AmqpSource {
+    uri: String,
+    queue: String,
+    decoder: JsonMessageDecoder,
+}
+
+impl AmqpSource {
+    pub fn new(uri: &str, queue: &str, decoder: JsonMessageDecoder) -> Self {
+        Self {
+            uri: uri.to_string(),
+            queue: queue.to_string(),
+            decoder,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for AmqpSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = AmqpPartition {
+            uri: self.uri.clone(),
+            queue: self.queue.clone(),
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct AmqpPartition {
+    uri: String,
+    queue: String,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for AmqpPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let (uri, queue) = (self.uri.clone(), self.queue.clone());
+        let decoder = self.decoder.clone();
+
+        builder.spawn(async move {
+            let channel = open_channel(&uri).await?;
+            // Unacknowledged deliveries count against the prefetch, it has to hold more than
+            // a batch for batches to fill up
+            channel
+                .basic_qos((2 * MAX_BATCH_MESSAGES) as u16, BasicQosOptions::default())
+                .await
+                .map_err(amqp_error)?;
+            let mut consumer = channel
+                .basic_consume(
+                    &queue,
+                    "",
+                    BasicConsumeOptions {
+                        no_ack: !should_checkpoint,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await
+                .map_err(amqp_error)?;
+
+            let mut epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current());
+            while let Some(chunk) = next_chunk(&mut consumer).await {
+                let mut last_delivery_tag = 0;
+                let mut messages = Vec::with_capacity(chunk.len());
+                for delivery in chunk {
+                    let delivery = delivery.map_err(amqp_error)?;
+                    last_delivery_tag = delivery.delivery_tag;
+                    messages.push(parse_json(&delivery.data)?);
+                }
+
+                let batch = if should_checkpoint {
+                    epoch += 1;
+                    if let Some(tracker) = &epoch_tracker {
+                        tracker.advance(epoch)?;
+                    }
+                    decoder.decode_checkpointed(messages, now_ms(), epoch)?
+                } else {
+                    decoder.decode(messages, now_ms())?
+                };
+                if tx.send(Ok(batch)).await.is_err() {
+                    break;
+                }
+                if should_checkpoint {
+                    channel
+                        .basic_ack(last_delivery_tag, BasicAckOptions { multiple: true })
+                        .await
+                        .map_err(amqp_error)?;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+/// Where a published row is routed to within the exchange
+#[derive(Debug, Clone)]
+pub enum RoutingKey {
+    Fixed(String),
+    /// The value of a string column, which isn't part of the published message
+    Column(String),
+}
+
+/// Publishes every row as a JSON message to a RabbitMQ exchange. Publishes are confirmed by
+/// the broker batch by batch, a message the broker refuses fails the pipeline.
+pub struct AmqpSink {
+    uri: String,
+    exchange: String,
+    routing_key: RoutingKey,
+    json_format: JsonFormatOptions,
+}
+
+impl AmqpSink {
+    pub fn new(
+        uri: &str,
+        exchange: &str,
+        routing_key: RoutingKey,
+        json_format: JsonFormatOptions,
+    ) -> Self {
+        Self {
+            uri: uri.to_string(),
+            exchange: exchange.to_string(),
+            routing_key,
+            json_format,
+        }
+    }
+
+    /// The routing key of each row and the rows to publish
+    fn route(&self, batch: RecordBatch) -> Result<(Vec<String>, RecordBatch)> {
+        match &self.routing_key {
+            RoutingKey::Fixed(key) => Ok((vec![key.clone(); batch.num_rows()], batch)),
+            RoutingKey::Column(column) => {
+                let Ok(index) = batch.schema().index_of(column) else {
+                    return plan_err!("Routing key column {column} not found");
+                };
+                let keys = cast(batch.column(index), &DataType::Utf8)?;
+                let keys = keys.as_any().downcast_ref::<StringArray>().unwrap();
+                if keys.null_count() > 0 {
+                    return plan_err!("Routing key column {column} contains nulls");
+                }
+                let keys = keys.iter().flatten().map(str::to_string).collect();
+
+                let mut batch = batch;
+                batch.remove_column(index);
+                Ok((keys, batch))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataSink for AmqpSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let quotas = ResourceQuotas::from_task_context(context);
+        let channel = open_channel(&self.uri).await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await
+            .map_err(amqp_error)?;
+
+        let encoder = JsonRowEncoder::new(self.json_format);
+        let properties = BasicProperties::default().with_content_type("application/json".into());
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            let (keys, batch) = self.route(batch)?;
+            let mut confirms = Vec::with_capacity(keys.len());
+            for (key, payload) in keys.iter().zip(encoder.encode(&batch)?) {
+                let confirm = channel
+                    .basic_publish(
+                        &self.exchange,
+                        key,
+                        BasicPublishOptions::default(),
+                        &payload,
+                        properties.clone(),
+                    )
+                    .await
+                    .map_err(amqp_error)?;
+                confirms.push(confirm);
+            }
+            for confirm in confirms {
+                if confirm.await.map_err(amqp_error)?.is_nack() {
+                    return Err(DataFusionError::Execution(format!(
+                        "Exchange {} refused a message",
+                        self.exchange
+                    )));
+                }
+            }
+            row_count += batch.num_rows() as u64;
+        }
+        Ok(row_count)
+    }
+}
+
+impl Debug for AmqpSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AmqpSink")
+            .field("exchange", &self.exchange)
+            .field("routing_key", &self.routing_key)
+            .finish()
+    }
+}
+
+impl DisplayAs for AmqpSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "AmqpSink ({})", self.exchange)
+            }
+        }
+    }
+}
+
+async fn open_channel(uri: &str) -> Result<Channel> {
+    let connection = Connection::connect(uri, ConnectionProperties::default())
+        .await
+        .map_err(amqp_error)?;
+    connection.create_channel().await.map_err(amqp_error)
+}
+
+fn amqp_error(err: lapin::Error) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Int64Array;
+    use arrow_schema::{Field, Schema};
+
+    #[test]
+    fn route_rows_by_column() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(ROUTING_KEY_COLUMN, DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["orders.eu", "orders.us"])),
+            ],
+        )
+        .unwrap();
+        let sink = AmqpSink::new(
+            "amqp://localhost",
+            "orders",
+            RoutingKey::Column(ROUTING_KEY_COLUMN.to_string()),
+            JsonFormatOptions::default(),
+        );
+
+        let (keys, routed) = sink.route(batch.clone()).unwrap();
+        assert_eq!(keys, ["orders.eu", "orders.us"]);
+        assert_eq!(routed.schema().fields().len(), 1);
+
+        let null_keys = RecordBatch::try_new(
+            batch.schema(),
+            vec![
+                batch.column(0).clone(),
+                Arc::new(StringArray::from(vec![Some("orders.eu"), None])),
+            ],
+        )
+        .unwrap();
+        assert!(sink.route(null_keys).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use aws_sdk_glue::types::{Column, StorageDescriptor, TableInput};
+use aws_sdk_glue::Client;
+use datafusion::common::{DataFusionError, Result};
+
+use super::{CatalogSync, TableDefinition};
+
+/// Keeps stream tables in a database of the AWS Glue data catalog
+#[derive(Debug, Clone)]
+pub struct GlueCatalogSync {
+    client: Client,
+    database: String,
+}
+
+impl GlueCatalogSync {
+    /// Connect with the AWS configuration of the environment
+    pub async fn new(database: impl Into<String>) -> Self {
+        let config = aws_config::load_from_env().await;
+        Self::with_client(Client::new(&config), database)
+    }
+
+    pub fn with_client(client: Client, database: impl Into<String>) -> Self {
+        Self {
+            client,
+            database: database.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl CatalogSync for GlueCatalogSync {
+    async fn sync_table(&self, table: &TableDefinition) -> Result<()> {
+        let columns = table
+            .hive_columns()?
+            .into_iter()
+            .map(|(name, data_type)| Column::builder().name(name).r#type(data_type).build())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(glue_error)?;
+        let input = TableInput::builder()
+            .name(&table.name)
+            .table_type("EXTERNAL_TABLE")
+            .storage_descriptor(
+                StorageDescriptor::builder()
+                    .set_columns(Some(columns))
+                    .location(&table.location)
+                    .build(),
+            )
+            .set_parameters(Some(table.properties.clone()))
+            .build()
+            .map_err(glue_error)?;
+
+        let created = self
+            .client
+            .create_table()
+            .database_name(&self.database)
+            .table_input(input.clone())
+            .send()
+            .await;
+        match created {
+            Ok(_) => Ok(()),
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_already_exists_exception()) =>
+            {
+                self.client
+                    .update_table()
+                    .database_name(&self.database)
+                    .table_input(input)
+                    .send()
+                    .await
+                    .map_err(glue_error)?;
+                Ok(())
+            }
+            Err(err) => Err(glue_error(err)),
+        }
+    }
+}
+
+fn glue_error(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
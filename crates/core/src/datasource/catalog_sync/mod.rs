@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use arrow_schema::{DataType, Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::common::{not_impl_err, Result};
+
+use crate::datasource::kafka::StreamEncoding;
+use crate::physical_plan::utils::metadata::STREAMING_METADATA_COLUMN;
+
+#[cfg(feature = "glue")]
+pub mod glue;
+
+/// A stream table as an external catalog sees it
+#[derive(Debug, Clone)]
+pub struct TableDefinition {
+    pub name: String,
+    pub schema: SchemaRef,
+    pub location: String,
+    /// Table parameters, describing the connector and data format
+    pub properties: HashMap<String, String>,
+}
+
+impl TableDefinition {
+    pub fn kafka(
+        topic: &str,
+        bootstrap_servers: &str,
+        schema: SchemaRef,
+        encoding: StreamEncoding,
+    ) -> Self {
+        let format = match encoding {
+            StreamEncoding::Avro => "avro",
+            StreamEncoding::Json => "json",
+            StreamEncoding::Protobuf => "protobuf",
+        };
+        let properties = HashMap::from([
+            ("connector".to_string(), "kafka".to_string()),
+            ("topic".to_string(), topic.to_string()),
+            (
+                "bootstrap.servers".to_string(),
+                bootstrap_servers.to_string(),
+            ),
+            ("format".to_string(), format.to_string()),
+        ]);
+        Self {
+            name: topic.to_string(),
+            schema,
+            location: format!("kafka://{bootstrap_servers}/{topic}"),
+            properties,
+        }
+    }
+
+    /// The columns with their Hive types, which Glue uses as well. The stream metadata is
+    /// left out.
+    pub fn hive_columns(&self) -> Result<Vec<(String, String)>> {
+        hive_columns(&self.schema)
+    }
+}
+
+/// Publishes the definitions of the tables a pipeline reads and writes to an external
+/// catalog, so that batch engines querying the same data see the same schemas
+#[async_trait]
+pub trait CatalogSync: Debug + Send + Sync {
+    /// Create the table or replace the definition of an existing one
+    async fn sync_table(&self, table: &TableDefinition) -> Result<()>;
+}
+
+fn hive_columns(schema: &Schema) -> Result<Vec<(String, String)>> {
+    schema
+        .fields()
+        .iter()
+        .filter(|field| field.name() != STREAMING_METADATA_COLUMN)
+        .map(|field| Ok((field.name().clone(), hive_type(field.data_type())?)))
+        .collect()
+}
+
+/// The Hive type name of an Arrow type
+pub fn hive_type(data_type: &DataType) -> Result<String> {
+    Ok(match data_type {
+        DataType::Boolean => "boolean".to_string(),
+        DataType::Int8 => "tinyint".to_string(),
+        DataType::Int16 | DataType::UInt8 => "smallint".to_string(),
+        DataType::Int32 | DataType::UInt16 => "int".to_string(),
+        DataType::Int64 | DataType::UInt32 => "bigint".to_string(),
+        DataType::UInt64 => "decimal(20,0)".to_string(),
+        DataType::Float16 | DataType::Float32 => "float".to_string(),
+        DataType::Float64 => "double".to_string(),
+        DataType::Utf8 | DataType::LargeUtf8 => "string".to_string(),
+        DataType::Binary | DataType::LargeBinary => "binary".to_string(),
+        DataType::Dictionary(_, value_type) => hive_type(value_type)?,
+        DataType::Date32 | DataType::Date64 => "date".to_string(),
+        DataType::Timestamp(_, _) => "timestamp".to_string(),
+        DataType::Decimal128(precision, scale) => format!("decimal({precision},{scale})"),
+        DataType::List(item) | DataType::LargeList(item) => {
+            format!("array<{}>", hive_type(item.data_type())?)
+        }
+        DataType::Map(entries, _) => match entries.data_type() {
+            DataType::Struct(kv) => format!(
+                "map<{},{}>",
+                hive_type(kv[0].data_type())?,
+                hive_type(kv[1].data_type())?
+            ),
+            _ => unreachable!(),
+        },
+        DataType::Struct(fields) => {
+            let fields = fields
+                .iter()
+                .map(|field| {
+                    Ok(format!(
+                        "{}:{}",
+                        field.name(),
+                        hive_type(field.data_type())?
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            format!("struct<{}>", fields.join(","))
+        }
+        _ => return not_impl_err!("No Hive type for {data_type}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_schema::{Field, Fields, TimeUnit};
+
+    #[test]
+    fn hive_types_of_stream_columns() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new(
+                "readings",
+                DataType::List(Arc::new(Field::new(
+                    "item",
+                    DataType::Struct(Fields::from(vec![
+                        Field::new("value", DataType::Float64, true),
+                        Field::new("at", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+                    ])),
+                    true,
+                ))),
+                true,
+            ),
+            Field::new(STREAMING_METADATA_COLUMN, DataType::Utf8, true),
+        ]));
+        let table = TableDefinition::kafka(
+            "temperature",
+            "localhost:9092",
+            schema,
+            StreamEncoding::Json,
+        );
+        assert_eq!(
+            table.hive_columns()?,
+            [
+                ("sensor".to_string(), "string".to_string()),
+                (
+                    "readings".to_string(),
+                    "array<struct<value:double,at:timestamp>>".to_string()
+                ),
+            ]
+        );
+        assert_eq!(table.location, "kafka://localhost:9092/temperature");
+        Ok(())
+    }
+}
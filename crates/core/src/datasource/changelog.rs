@@ -0,0 +1,18 @@
+/// Column of a changelog telling whether the row was inserted into or deleted from the source.
+/// An update is a delete of the old row followed by an insert of the new one.
+pub const CHANGE_TYPE_COLUMN: &str = "_change_type";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeType {
+    Insert,
+    Delete,
+}
+
+impl ChangeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Insert => "insert",
+            Self::Delete => "delete",
+        }
+    }
+}
@@ -0,0 +1,871 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::compute::{can_cast_types, cast};
+use arrow::json::ArrayWriter;
+use arrow_array::{new_null_array, ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use datafusion::catalog::Session;
+use datafusion::common::{exec_err, not_impl_err, plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::parquet::arrow::async_reader::{
+    ParquetObjectReader, ParquetRecordBatchStream, ParquetRecordBatchStreamBuilder,
+};
+use datafusion::parquet::arrow::ProjectionMask;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::datasource::changelog::{ChangeType, CHANGE_TYPE_COLUMN};
+use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+/// Reads a Delta table as a changelog: the rows of the current snapshot as inserts, then the
+/// rows of every later commit as they are written. Files a commit removes come out as deletes
+/// and files it adds as inserts, so rewritten files repeat unchanged rows as a delete followed
+/// by an insert. Commits that don't change data, like compactions, are skipped.
+///
+/// Every row carries the time of its commit as event time. Nothing is checkpointed, a
+/// restarted pipeline reads the snapshot again, which suits dimension tables that are joined
+/// against rather than aggregated.
+///
+/// The snapshot starts at the latest Parquet checkpoint of the log, if there is one, so tables
+/// whose early commits were cleaned up can be read. V2 checkpoints aren't supported. Columns
+/// the table gains after the pipeline was planned are left out and dropped nullable columns
+/// read as null, other schema changes fail the stream. Iceberg tables aren't supported.
+pub struct DeltaChangelog {
+    log: DeltaLog,
+    table_schema: SchemaRef,
+    schema: SchemaRef,
+    poll_interval: Duration,
+}
+
+impl DeltaChangelog {
+    /// Open the table at `root` of `store`, checking for new commits every `poll_interval`
+    pub async fn try_new(
+        store: Arc<dyn ObjectStore>,
+        root: Path,
+        poll_interval: Duration,
+    ) -> Result<Self> {
+        let log = DeltaLog { store, root };
+        let table_schema = log.snapshot().await?.schema;
+
+        let mut fields = table_schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            CHANGE_TYPE_COLUMN,
+            DataType::Utf8,
+            false,
+        )));
+        fields.push(Arc::new(stream_metadata_field()));
+
+        Ok(Self {
+            log,
+            table_schema,
+            schema: Arc::new(Schema::new(fields)),
+            poll_interval,
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for DeltaChangelog {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = DeltaChangelogPartition {
+            log: self.log.clone(),
+            table_schema: self.table_schema.clone(),
+            schema: self.schema.clone(),
+            poll_interval: self.poll_interval,
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.schema.clone(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct DeltaChangelogPartition {
+    log: DeltaLog,
+    table_schema: SchemaRef,
+    schema: SchemaRef,
+    poll_interval: Duration,
+}
+
+impl PartitionStream for DeltaChangelogPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let reader = ChangelogReader {
+            log: self.log.clone(),
+            table_schema: self.table_schema.clone(),
+            schema: self.schema.clone(),
+            poll_interval: self.poll_interval,
+            next_version: None,
+            files: BTreeMap::new(),
+            pending: VecDeque::new(),
+            reading: None,
+        };
+        let stream = futures::stream::try_unfold(reader, |mut reader| async move {
+            let batch = reader.next_batch().await?;
+            Ok(Some((batch, reader)))
+        });
+        Box::pin(RecordBatchStreamAdapter::new(self.schema.clone(), stream))
+    }
+}
+
+struct ChangelogReader {
+    log: DeltaLog,
+    table_schema: SchemaRef,
+    schema: SchemaRef,
+    poll_interval: Duration,
+    /// The first commit not read yet, unknown until the snapshot was read
+    next_version: Option<u64>,
+    /// The files of the table as of the last commit read
+    files: BTreeMap<String, FileAction>,
+    pending: VecDeque<(FileAction, ChangeType, i64)>,
+    /// The data file whose changes are being read
+    reading: Option<OpenFile>,
+}
+
+struct OpenFile {
+    batches: ParquetRecordBatchStream<ParquetObjectReader>,
+    file: FileAction,
+    change: ChangeType,
+    timestamp_ms: i64,
+}
+
+impl ChangelogReader {
+    /// The changes of the next batch of a data file, waiting for the next commit if all have
+    /// been read
+    async fn next_batch(&mut self) -> Result<RecordBatch> {
+        loop {
+            if let Some(mut reading) = self.reading.take() {
+                let Some(batch) = reading.batches.try_next().await? else {
+                    continue;
+                };
+                let changes = self.changes(&batch, &reading)?;
+                self.reading = Some(reading);
+                if changes.num_rows() > 0 {
+                    return Ok(changes);
+                }
+                continue;
+            }
+
+            if let Some((file, change, timestamp_ms)) = self.pending.pop_front() {
+                if file.deletion_vector.is_some() {
+                    return not_impl_err!(
+                        "Delta deletion vectors are not supported, in {}",
+                        file.path
+                    );
+                }
+                self.reading = Some(OpenFile {
+                    batches: self.log.open_file(&file).await?,
+                    file,
+                    change,
+                    timestamp_ms,
+                });
+                continue;
+            }
+
+            match self.next_version {
+                None => {
+                    let snapshot = self.log.snapshot().await?;
+                    self.pending.extend(
+                        snapshot
+                            .files
+                            .values()
+                            .map(|file| (file.clone(), ChangeType::Insert, snapshot.timestamp_ms)),
+                    );
+                    self.files = snapshot.files;
+                    self.next_version = Some(snapshot.version + 1);
+                }
+                Some(version) => match self.log.commit(version).await? {
+                    Some(commit) => {
+                        if let Some(schema) = &commit.schema {
+                            check_schema_change(&self.table_schema, schema, version)?;
+                        }
+                        for removed in commit.removed {
+                            // Remove actions may leave out the partition values of the file
+                            let file = match self.files.remove(&removed.path) {
+                                Some(added) => FileAction {
+                                    data_change: removed.data_change,
+                                    ..added
+                                },
+                                None => removed,
+                            };
+                            if file.data_change {
+                                self.pending.push_back((
+                                    file,
+                                    ChangeType::Delete,
+                                    commit.timestamp_ms,
+                                ));
+                            }
+                        }
+                        for added in commit.added {
+                            self.files.insert(added.path.clone(), added.clone());
+                            if added.data_change {
+                                self.pending.push_back((
+                                    added,
+                                    ChangeType::Insert,
+                                    commit.timestamp_ms,
+                                ));
+                            }
+                        }
+                        self.next_version = Some(version + 1);
+                    }
+                    None => tokio::time::sleep(self.poll_interval).await,
+                },
+            }
+        }
+    }
+
+    fn changes(&self, batch: &RecordBatch, reading: &OpenFile) -> Result<RecordBatch> {
+        let rows = batch.num_rows();
+        let mut columns = conform_columns(batch, &reading.file, &self.table_schema)?;
+        columns.push(Arc::new(StringArray::from(vec![
+            reading.change.as_str();
+            rows
+        ])));
+        columns.push(Arc::new(stream_metadata_array(
+            TimestampMillisecondArray::from(vec![reading.timestamp_ms; rows]),
+        )));
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+}
+
+/// Check that data files written after the table schema changed to `changed` at `version`
+/// still conform to the `planned` schema of the stream
+fn check_schema_change(planned: &Schema, changed: &Schema, version: u64) -> Result<()> {
+    for field in planned.fields() {
+        match changed.field_with_name(field.name()) {
+            Ok(changed) if can_cast_types(changed.data_type(), field.data_type()) => {}
+            Ok(changed) => {
+                return exec_err!(
+                    "Column {} of the Delta table changed from {} to {} at version {version}, \
+                     which the stream can't read without planning it again",
+                    field.name(),
+                    field.data_type(),
+                    changed.data_type()
+                )
+            }
+            Err(_) if field.is_nullable() => {}
+            Err(_) => {
+                return exec_err!(
+                    "Non nullable column {} was dropped from the Delta table at version {version}",
+                    field.name()
+                )
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The columns of `batch` in the order and types of the table. Partition columns aren't part
+/// of the data files and are filled from the partition values of the file, columns added to
+/// the table after the file was written with nulls.
+fn conform_columns(
+    batch: &RecordBatch,
+    file: &FileAction,
+    table_schema: &Schema,
+) -> Result<Vec<ArrayRef>> {
+    table_schema
+        .fields()
+        .iter()
+        .map(|field| {
+            if let Some(column) = batch.column_by_name(field.name()) {
+                return Ok(cast(column, field.data_type())?);
+            }
+            match file.partition_values.get(field.name()) {
+                Some(Some(value)) => {
+                    let values = StringArray::from(vec![value.as_str(); batch.num_rows()]);
+                    Ok(cast(&values, field.data_type())?)
+                }
+                _ => Ok(new_null_array(field.data_type(), batch.num_rows())),
+            }
+        })
+        .collect()
+}
+
+/// The transaction log of a Delta table, read from its JSON commits and Parquet checkpoints
+#[derive(Debug, Clone)]
+struct DeltaLog {
+    store: Arc<dyn ObjectStore>,
+    root: Path,
+}
+
+#[derive(Debug)]
+struct Snapshot {
+    /// The last commit included
+    version: u64,
+    timestamp_ms: i64,
+    schema: SchemaRef,
+    files: BTreeMap<String, FileAction>,
+}
+
+#[derive(Debug, Default)]
+struct Commit {
+    timestamp_ms: i64,
+    schema: Option<SchemaRef>,
+    added: Vec<FileAction>,
+    removed: Vec<FileAction>,
+}
+
+/// The `_last_checkpoint` file pointing at the latest checkpoint
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LastCheckpoint {
+    version: u64,
+    /// Number of files a multi-part checkpoint is split into
+    parts: Option<u32>,
+    v2_checkpoint: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Action {
+    add: Option<FileAction>,
+    remove: Option<FileAction>,
+    meta_data: Option<MetaData>,
+    commit_info: Option<CommitInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileAction {
+    path: String,
+    #[serde(default)]
+    partition_values: HashMap<String, Option<String>>,
+    #[serde(default = "data_change_default")]
+    data_change: bool,
+    deletion_vector: Option<Value>,
+}
+
+fn data_change_default() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetaData {
+    schema_string: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitInfo {
+    timestamp: Option<i64>,
+}
+
+impl DeltaLog {
+    fn log_path(&self, file: &str) -> Path {
+        self.root.child("_delta_log").child(file)
+    }
+
+    /// The files of the table as of its latest commit, replaying the log from its latest
+    /// checkpoint or from the start
+    async fn snapshot(&self) -> Result<Snapshot> {
+        let (mut version, checkpoint) = match self.last_checkpoint().await? {
+            Some(last) => (last.version + 1, Some(self.checkpoint(&last).await?)),
+            None => (0, None),
+        };
+
+        let mut files = BTreeMap::new();
+        let mut schema = None;
+        let mut timestamp_ms = 0;
+        let mut apply = |commit: Commit| {
+            for file in commit.removed {
+                files.remove(&file.path);
+            }
+            for file in commit.added {
+                files.insert(file.path.clone(), file);
+            }
+            schema = commit.schema.or(schema.take());
+            timestamp_ms = commit.timestamp_ms;
+        };
+        if let Some(checkpoint) = checkpoint {
+            apply(checkpoint);
+        }
+        while let Some(commit) = self.commit(version).await? {
+            apply(commit);
+            version += 1;
+        }
+
+        if version == 0 {
+            return plan_err!("No Delta table found at {}", self.root);
+        }
+        let Some(schema) = schema else {
+            return plan_err!("The log of the Delta table at {} has no schema", self.root);
+        };
+        Ok(Snapshot {
+            version: version - 1,
+            timestamp_ms,
+            schema,
+            files,
+        })
+    }
+
+    /// The actions of commit `version`, none if it hasn't been written yet
+    async fn commit(&self, version: u64) -> Result<Option<Commit>> {
+        let path = self.log_path(&format!("{version:020}.json"));
+        let result = match self.store.get(&path).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let mut commit = Commit {
+            timestamp_ms: result.meta.last_modified.timestamp_millis(),
+            ..Default::default()
+        };
+
+        let bytes = result.bytes().await?;
+        for line in bytes.split(|byte| *byte == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let action: Action = serde_json::from_slice(line)
+                .map_err(|err| DataFusionError::External(Box::new(err)))?;
+            if let Some(add) = action.add {
+                commit.added.push(add);
+            }
+            if let Some(remove) = action.remove {
+                commit.removed.push(remove);
+            }
+            if let Some(meta_data) = action.meta_data {
+                commit.schema = Some(Arc::new(delta_schema(&meta_data.schema_string)?));
+            }
+            if let Some(timestamp) = action.commit_info.and_then(|info| info.timestamp) {
+                commit.timestamp_ms = timestamp;
+            }
+        }
+        Ok(Some(commit))
+    }
+
+    async fn last_checkpoint(&self) -> Result<Option<LastCheckpoint>> {
+        let result = match self.store.get(&self.log_path("_last_checkpoint")).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let last: LastCheckpoint = serde_json::from_slice(&result.bytes().await?)
+            .map_err(|err| DataFusionError::External(Box::new(err)))?;
+        if last.v2_checkpoint.is_some() {
+            return not_impl_err!(
+                "The log of the Delta table at {} has a v2 checkpoint, which is not supported",
+                self.root
+            );
+        }
+        Ok(Some(last))
+    }
+
+    /// The files and schema as of a checkpoint, as if it were a single commit adding them
+    async fn checkpoint(&self, last: &LastCheckpoint) -> Result<Commit> {
+        let version = last.version;
+        let paths: Vec<Path> = match last.parts {
+            Some(parts) if parts > 1 => (1..=parts)
+                .map(|part| {
+                    self.log_path(&format!(
+                        "{version:020}.checkpoint.{part:010}.{parts:010}.parquet"
+                    ))
+                })
+                .collect(),
+            _ => vec![self.log_path(&format!("{version:020}.checkpoint.parquet"))],
+        };
+
+        let mut commit = Commit::default();
+        for path in paths {
+            let meta = self.store.head(&path).await?;
+            commit.timestamp_ms = meta.last_modified.timestamp_millis();
+            let builder = ParquetRecordBatchStreamBuilder::new(ParquetObjectReader::new(
+                Arc::clone(&self.store),
+                meta,
+            ))
+            .await?;
+            // Removes in a checkpoint are tombstones of files that aren't part of the table
+            let roots: Vec<usize> = ["add", "metaData"]
+                .iter()
+                .filter_map(|name| builder.schema().index_of(name).ok())
+                .collect();
+            let mask = ProjectionMask::roots(builder.parquet_schema(), roots);
+            let mut batches = builder.with_projection(mask).build()?;
+            while let Some(batch) = batches.try_next().await? {
+                if batch.num_rows() == 0 {
+                    continue;
+                }
+                // The checkpoint columns hold the actions of the JSON commits as structs
+                let mut writer = ArrayWriter::new(Vec::new());
+                writer.write(&batch)?;
+                writer.finish()?;
+                let actions: Vec<Action> = serde_json::from_slice(&writer.into_inner())
+                    .map_err(|err| DataFusionError::External(Box::new(err)))?;
+                for action in actions {
+                    commit.added.extend(action.add);
+                    if let Some(meta_data) = action.meta_data {
+                        commit.schema = Some(Arc::new(delta_schema(&meta_data.schema_string)?));
+                    }
+                }
+            }
+        }
+        Ok(commit)
+    }
+
+    async fn open_file(
+        &self,
+        file: &FileAction,
+    ) -> Result<ParquetRecordBatchStream<ParquetObjectReader>> {
+        let relative = Path::from_url_path(&file.path)
+            .map_err(|err| DataFusionError::External(Box::new(err)))?;
+        let path: Path = self.root.parts().chain(relative.parts()).collect();
+        let meta = self.store.head(&path).await?;
+
+        let reader = ParquetObjectReader::new(Arc::clone(&self.store), meta);
+        Ok(ParquetRecordBatchStreamBuilder::new(reader)
+            .await?
+            .build()?)
+    }
+}
+
+/// The Arrow schema of a Delta schema string
+fn delta_schema(schema_string: &str) -> Result<Schema> {
+    let value: Value = serde_json::from_str(schema_string)
+        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+    match delta_type(&value)? {
+        DataType::Struct(fields) => Ok(Schema::new(fields)),
+        other => plan_err!("Expected a struct as Delta schema, got {other}"),
+    }
+}
+
+fn delta_type(value: &Value) -> Result<DataType> {
+    let nullable =
+        |value: &Value, key: &str| value.get(key).and_then(Value::as_bool) != Some(false);
+    match value {
+        Value::String(name) => Ok(match name.as_str() {
+            "string" => DataType::Utf8,
+            "long" => DataType::Int64,
+            "integer" => DataType::Int32,
+            "short" => DataType::Int16,
+            "byte" => DataType::Int8,
+            "float" => DataType::Float32,
+            "double" => DataType::Float64,
+            "boolean" => DataType::Boolean,
+            "binary" => DataType::Binary,
+            "date" => DataType::Date32,
+            "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            "timestamp_ntz" => DataType::Timestamp(TimeUnit::Microsecond, None),
+            decimal if decimal.starts_with("decimal(") => {
+                let precision_scale = decimal
+                    .trim_start_matches("decimal(")
+                    .trim_end_matches(')')
+                    .split_once(',')
+                    .and_then(|(precision, scale)| {
+                        Some((precision.trim().parse().ok()?, scale.trim().parse().ok()?))
+                    });
+                match precision_scale {
+                    Some((precision, scale)) => DataType::Decimal128(precision, scale),
+                    None => return plan_err!("Invalid Delta type {decimal}"),
+                }
+            }
+            other => return not_impl_err!("Unsupported Delta type {other}"),
+        }),
+        Value::Object(_) => match value.get("type").and_then(Value::as_str) {
+            Some("struct") => {
+                let fields = value
+                    .get("fields")
+                    .and_then(Value::as_array)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|field| {
+                        let Some(name) = field.get("name").and_then(Value::as_str) else {
+                            return plan_err!("Delta field without a name: {field}");
+                        };
+                        let data_type = delta_type(field.get("type").unwrap_or(&Value::Null))?;
+                        Ok(Field::new(name, data_type, nullable(field, "nullable")))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(DataType::Struct(Fields::from(fields)))
+            }
+            Some("array") => {
+                let element = delta_type(value.get("elementType").unwrap_or(&Value::Null))?;
+                Ok(DataType::List(Arc::new(Field::new(
+                    "element",
+                    element,
+                    nullable(value, "containsNull"),
+                ))))
+            }
+            Some("map") => {
+                let key = delta_type(value.get("keyType").unwrap_or(&Value::Null))?;
+                let map_value = delta_type(value.get("valueType").unwrap_or(&Value::Null))?;
+                let entries = Fields::from(vec![
+                    Field::new("key", key, false),
+                    Field::new("value", map_value, nullable(value, "valueContainsNull")),
+                ]);
+                Ok(DataType::Map(
+                    Arc::new(Field::new("key_value", DataType::Struct(entries), false)),
+                    false,
+                ))
+            }
+            _ => not_impl_err!("Unsupported Delta type {value}"),
+        },
+        _ => plan_err!("Invalid Delta type {value}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::AsArray;
+    use arrow::buffer::NullBuffer;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::{BooleanArray, StructArray};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use futures::StreamExt;
+    use object_store::local::LocalFileSystem;
+
+    const SCHEMA_STRING: &str = r#"{"type":"struct","fields":[{"name":"id","type":"long","nullable":false,"metadata":{}},{"name":"region","type":"string","nullable":true,"metadata":{}}]}"#;
+
+    fn write_data_file(dir: &std::path::Path, name: &str, ids: Vec<i64>) {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(arrow_array::Int64Array::from(ids))],
+        )
+        .unwrap();
+        let file = std::fs::File::create(dir.join(name)).unwrap();
+        let mut writer = ArrowWriter::try_new(file, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn write_commit(dir: &std::path::Path, version: u64, actions: &[Value]) {
+        let lines: Vec<String> = actions.iter().map(Value::to_string).collect();
+        let path = dir.join("_delta_log").join(format!("{version:020}.json"));
+        std::fs::write(path, lines.join("\n")).unwrap();
+    }
+
+    /// A checkpoint of a table with `SCHEMA_STRING` made of the files at `paths`
+    fn write_checkpoint(dir: &std::path::Path, version: u64, paths: &[&str]) {
+        let add_fields = Fields::from(vec![
+            Field::new("path", DataType::Utf8, true),
+            Field::new("dataChange", DataType::Boolean, true),
+        ]);
+        let meta_data_fields = Fields::from(vec![Field::new("schemaString", DataType::Utf8, true)]);
+        // The first row holds the metadata, the others an added file each
+        let rows = paths.len() + 1;
+        let add = StructArray::try_new(
+            add_fields.clone(),
+            vec![
+                Arc::new(StringArray::from_iter(
+                    std::iter::once(None).chain(paths.iter().map(Some)),
+                )),
+                Arc::new(BooleanArray::from(vec![false; rows])),
+            ],
+            Some(NullBuffer::from(
+                (0..rows).map(|row| row > 0).collect::<Vec<_>>(),
+            )),
+        )
+        .unwrap();
+        let meta_data = StructArray::try_new(
+            meta_data_fields.clone(),
+            vec![Arc::new(StringArray::from_iter(
+                (0..rows).map(|row| (row == 0).then_some(SCHEMA_STRING)),
+            ))],
+            Some(NullBuffer::from(
+                (0..rows).map(|row| row == 0).collect::<Vec<_>>(),
+            )),
+        )
+        .unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("add", DataType::Struct(add_fields), true),
+            Field::new("metaData", DataType::Struct(meta_data_fields), true),
+        ]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(add), Arc::new(meta_data)]).unwrap();
+
+        let log = dir.join("_delta_log");
+        let file =
+            std::fs::File::create(log.join(format!("{version:020}.checkpoint.parquet"))).unwrap();
+        let mut writer = ArrowWriter::try_new(file, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let last_checkpoint = serde_json::json!({"version": version, "size": rows});
+        std::fs::write(log.join("_last_checkpoint"), last_checkpoint.to_string()).unwrap();
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<i64> {
+        batch
+            .column(0)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec()
+    }
+
+    fn ids_and_changes(batch: &RecordBatch) -> Vec<(i64, String, String)> {
+        let ids = batch.column(0).as_primitive::<Int64Type>();
+        let regions = batch.column(1).as_string::<i32>();
+        let changes = batch.column(2).as_string::<i32>();
+        (0..batch.num_rows())
+            .map(|row| {
+                (
+                    ids.value(row),
+                    regions.value(row).to_string(),
+                    changes.value(row).to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn read_snapshot_then_commits() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("delta_changelog_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("_delta_log")).unwrap();
+        write_data_file(&dir, "part-0.parquet", vec![1, 2]);
+        write_commit(
+            &dir,
+            0,
+            &[
+                serde_json::json!({"commitInfo": {"timestamp": 1000}}),
+                serde_json::json!({"metaData": {"schemaString": SCHEMA_STRING, "partitionColumns": ["region"]}}),
+                serde_json::json!({"add": {"path": "part-0.parquet", "partitionValues": {"region": "eu"}, "dataChange": true}}),
+            ],
+        );
+
+        let root = Path::from_filesystem_path(&dir).unwrap();
+        let changelog = DeltaChangelog::try_new(
+            Arc::new(LocalFileSystem::new()),
+            root,
+            Duration::from_millis(10),
+        )
+        .await?;
+        let partition = DeltaChangelogPartition {
+            log: changelog.log.clone(),
+            table_schema: changelog.table_schema.clone(),
+            schema: changelog.schema.clone(),
+            poll_interval: changelog.poll_interval,
+        };
+        let mut stream = partition.execute(Arc::new(TaskContext::default()));
+
+        let snapshot = stream.next().await.unwrap()?;
+        assert_eq!(
+            ids_and_changes(&snapshot),
+            [
+                (1, "eu".to_string(), "insert".to_string()),
+                (2, "eu".to_string(), "insert".to_string()),
+            ]
+        );
+
+        write_data_file(&dir, "part-1.parquet", vec![1]);
+        write_commit(
+            &dir,
+            1,
+            &[
+                serde_json::json!({"commitInfo": {"timestamp": 2000}}),
+                serde_json::json!({"remove": {"path": "part-0.parquet", "dataChange": true}}),
+                serde_json::json!({"add": {"path": "part-1.parquet", "partitionValues": {"region": "us"}, "dataChange": true}}),
+            ],
+        );
+        let deleted = stream.next().await.unwrap()?;
+        let inserted = stream.next().await.unwrap()?;
+        assert_eq!(
+            ids_and_changes(&deleted),
+            [
+                (1, "eu".to_string(), "delete".to_string()),
+                (2, "eu".to_string(), "delete".to_string()),
+            ]
+        );
+        assert_eq!(
+            ids_and_changes(&inserted),
+            [(1, "us".to_string(), "insert".to_string())]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn start_at_checkpoint_and_check_schema_changes() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("delta_checkpoint_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("_delta_log")).unwrap();
+        write_data_file(&dir, "part-0.parquet", vec![1, 2]);
+        // The commits up to the checkpoint have been cleaned up
+        write_checkpoint(&dir, 3, &["part-0.parquet"]);
+
+        let root = Path::from_filesystem_path(&dir).unwrap();
+        let changelog = DeltaChangelog::try_new(
+            Arc::new(LocalFileSystem::new()),
+            root,
+            Duration::from_millis(10),
+        )
+        .await?;
+        let partition = DeltaChangelogPartition {
+            log: changelog.log.clone(),
+            table_schema: changelog.table_schema.clone(),
+            schema: changelog.schema.clone(),
+            poll_interval: changelog.poll_interval,
+        };
+        let mut stream = partition.execute(Arc::new(TaskContext::default()));
+        assert_eq!(ids(&stream.next().await.unwrap()?), [1, 2]);
+
+        // Columns the table gains are left out
+        let with_score = SCHEMA_STRING.replace(
+            "]}",
+            r#",{"name":"score","type":"double","nullable":true,"metadata":{}}]}"#,
+        );
+        write_data_file(&dir, "part-1.parquet", vec![3]);
+        write_commit(
+            &dir,
+            4,
+            &[
+                serde_json::json!({"metaData": {"schemaString": with_score}}),
+                serde_json::json!({"add": {"path": "part-1.parquet", "dataChange": true}}),
+            ],
+        );
+        assert_eq!(ids(&stream.next().await.unwrap()?), [3]);
+
+        let without_id = r#"{"type":"struct","fields":[{"name":"region","type":"string","nullable":true,"metadata":{}}]}"#;
+        write_commit(
+            &dir,
+            5,
+            &[serde_json::json!({"metaData": {"schemaString": without_id}})],
+        );
+        let err = stream.next().await.unwrap().unwrap_err().to_string();
+        assert!(
+            err.contains("Non nullable column id was dropped from the Delta table at version 5"),
+            "{err}"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+        Ok(())
+    }
+}
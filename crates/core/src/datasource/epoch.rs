@@ -0,0 +1,268 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use datafusion::common::{DataFusionError, Result};
+use datafusion::execution::TaskContext;
+use tokio::sync::watch;
+
+use crate::state_backend::backend::StateBackend;
+
+const EPOCH_NAMESPACE: &str = "epoch_tracker";
+
+/// Where a batch handed to a sink falls in the checkpoint sequence. The pair is unique per
+/// sink, so sinks that can't take part in a transaction can use it as an idempotency key.
+///
+/// The epoch is the latest one any source has started, batches still in flight from the
+/// previous epoch are numbered as part of the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SinkPosition {
+    pub epoch: u64,
+    /// Number of batches the sink received before this one in the same epoch
+    pub sequence: u64,
+}
+
+/// The checkpoint epoch the pipeline is in, shared by the sources that advance it and the
+/// sinks that read it. Every pipeline executed by a sink gets its own, see
+/// [`EpochTracker::from_task_context`].
+#[derive(Default)]
+pub struct EpochTracker {
+    epoch: AtomicU64,
+    /// Backend and key the latest epoch is kept under when checkpointing
+    state: Option<(Arc<dyn StateBackend>, Vec<u8>)>,
+}
+
+impl EpochTracker {
+    pub fn from_task_context(context: &TaskContext) -> Option<Arc<Self>> {
+        context.session_config().get_extension::<Self>()
+    }
+
+    /// Tracker of the pipeline writing to `pipeline`. It carries on after the latest epoch the
+    /// pipeline reached before a restart, so sinks don't hand out the same positions twice.
+    pub fn restore(backend: Arc<dyn StateBackend>, pipeline: &str) -> Result<Self> {
+        backend.ensure_namespace(EPOCH_NAMESPACE)?;
+        let key = pipeline.as_bytes().to_vec();
+        let epoch = match backend.get_state(EPOCH_NAMESPACE, key.clone())? {
+            Some(bytes) => {
+                let bytes: [u8; 8] = bytes.try_into().map_err(|_| {
+                    DataFusionError::Internal(format!("Invalid epoch stored for {pipeline}"))
+                })?;
+                u64::from_le_bytes(bytes) + 1
+            }
+            None => 0,
+        };
+        // Sinks number batches under the epoch before any source advances it
+        backend.put_state(EPOCH_NAMESPACE, key.clone(), epoch.to_le_bytes().to_vec())?;
+        Ok(Self {
+            epoch: AtomicU64::new(epoch),
+            state: Some((backend, key)),
+        })
+    }
+
+    /// Called by sources as they start emitting rows of `epoch`
+    pub fn advance(&self, epoch: u64) -> Result<()> {
+        let previous = self.epoch.fetch_max(epoch, Ordering::SeqCst);
+        match &self.state {
+            Some((backend, key)) if epoch > previous => {
+                backend.put_state(EPOCH_NAMESPACE, key.clone(), epoch.to_le_bytes().to_vec())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn current(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Numbering for the batches of a single `DataSink::write_all` call
+    pub fn sink_sequence(self: &Arc<Self>) -> SinkSequence {
+        SinkSequence {
+            tracker: Arc::clone(self),
+            epoch: self.current(),
+            next: 0,
+        }
+    }
+}
+
+pub struct SinkSequence {
+    tracker: Arc<EpochTracker>,
+    epoch: u64,
+    next: u64,
+}
+
+impl SinkSequence {
+    /// Position of the next batch, the sequence starts over whenever the epoch moves on
+    pub fn next_position(&mut self) -> SinkPosition {
+        let epoch = self.tracker.current();
+        if epoch != self.epoch {
+            self.epoch = epoch;
+            self.next = 0;
+        }
+        let position = SinkPosition {
+            epoch,
+            sequence: self.next,
+        };
+        self.next += 1;
+        position
+    }
+}
+
+/// Lines up the partitions of a source on checkpoint barriers. Each partition closes an
+/// epoch after persisting its offsets for it, and waits until every partition has closed it
+/// before reading on. The partition closing an epoch last takes the checkpoint, so it holds
+/// the offsets of every partition as of the same epoch.
+pub(crate) struct BarrierAlignment {
+    state: Mutex<AlignmentState>,
+    /// Latest epoch whose checkpoint was attempted, the partitions wait on it
+    aligned: watch::Sender<Option<u64>>,
+}
+
+struct AlignmentState {
+    /// Latest epoch each partition closed
+    closed: Vec<Option<u64>>,
+    /// Latest epoch every partition closed
+    aligned: Option<u64>,
+}
+
+impl BarrierAlignment {
+    pub fn new(partitions: usize) -> Self {
+        Self {
+            state: Mutex::new(AlignmentState {
+                closed: vec![None; partitions],
+                aligned: None,
+            }),
+            aligned: watch::channel(None).0,
+        }
+    }
+
+    /// Close `epoch` for `partition`, calling `checkpoint` if that lines up all partitions,
+    /// and wait for the other partitions to close it
+    pub async fn close<F, Fut>(&self, partition: usize, epoch: u64, checkpoint: F) -> Result<()>
+    where
+        F: FnOnce(u64) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let newly_aligned = {
+            let mut state = self.state.lock().unwrap();
+            state.closed[partition] = Some(epoch);
+            match state.closed.iter().copied().min().flatten() {
+                Some(lowest) if Some(lowest) > state.aligned => {
+                    state.aligned = Some(lowest);
+                    Some(lowest)
+                }
+                _ => None,
+            }
+        };
+        if let Some(aligned) = newly_aligned {
+            // Let the other partitions carry on even if the checkpoint failed, the error ends
+            // the stream of this one
+            let result = checkpoint(aligned).await;
+            self.aligned
+                .send_modify(|current| *current = (*current).max(Some(aligned)));
+            result?;
+        }
+
+        self.aligned
+            .subscribe()
+            .wait_for(|aligned| *aligned >= Some(epoch))
+            .await
+            .map_err(|e| DataFusionError::Internal(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    use crate::state_backend::rocksdb_backend::RocksDBBackend;
+
+    #[test]
+    fn sequence_restarts_with_each_epoch() {
+        let tracker = Arc::new(EpochTracker::default());
+        let mut sequence = tracker.sink_sequence();
+        assert_eq!(
+            sequence.next_position(),
+            SinkPosition {
+                epoch: 0,
+                sequence: 0
+            }
+        );
+        assert_eq!(
+            sequence.next_position(),
+            SinkPosition {
+                epoch: 0,
+                sequence: 1
+            }
+        );
+
+        tracker.advance(3).unwrap();
+        tracker.advance(2).unwrap();
+        assert_eq!(
+            sequence.next_position(),
+            SinkPosition {
+                epoch: 3,
+                sequence: 0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn checkpoint_once_all_partitions_closed_the_epoch() -> Result<()> {
+        let alignment = BarrierAlignment::new(2);
+        let taken = Mutex::new(vec![]);
+        let checkpoint = |epoch| {
+            taken.lock().unwrap().push(epoch);
+            async { Ok(()) }
+        };
+
+        // The first partition waits for the second one to close the epoch
+        let (first, second) = futures::join!(
+            alignment.close(0, 3, checkpoint),
+            alignment.close(1, 3, checkpoint)
+        );
+        first?;
+        second?;
+        // A partition ahead waits for the ones behind it to catch up
+        let (first, second) = futures::join!(alignment.close(1, 5, checkpoint), async {
+            alignment.close(0, 4, checkpoint).await?;
+            alignment.close(0, 5, checkpoint).await
+        });
+        first?;
+        second?;
+
+        assert_eq!(*taken.lock().unwrap(), [3, 4, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn positions_carry_on_after_a_restart() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("denormalized-epochs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let backend: Arc<dyn StateBackend> = Arc::new(RocksDBBackend::open(&path)?);
+
+        let mut positions = HashSet::new();
+        for _run in 0..2 {
+            let tracker = Arc::new(EpochTracker::restore(backend.clone(), "orders")?);
+            let mut sequence = tracker.sink_sequence();
+            let first = tracker.current();
+            for epoch in first..first + 3 {
+                tracker.advance(epoch)?;
+                for _ in 0..2 {
+                    assert!(positions.insert(sequence.next_position()));
+                }
+            }
+        }
+        assert_eq!(positions.len(), 12);
+        // Other pipelines keep their own epochs
+        assert_eq!(
+            EpochTracker::restore(backend.clone(), "payments")?.current(),
+            0
+        );
+
+        drop(backend);
+        Ok(std::fs::remove_dir_all(&path)?)
+    }
+}
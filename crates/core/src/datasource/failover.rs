@@ -0,0 +1,280 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_array::RecordBatch;
+use datafusion::common::{exec_datafusion_err, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+use futures::StreamExt;
+use log::{info, warn};
+use tokio::time::Instant;
+
+use crate::datasource::segment_queue::{list_segments, read_segment, SegmentQueueSink};
+
+/// Writes to a primary sink and spills to a local [`SegmentQueueSink`] while the primary is
+/// down, e.g. because the Kafka cluster or object store it writes to can't be reached.
+///
+/// Every batch is written to the primary on its own. A batch the primary fails to write, or
+/// takes longer than the latency threshold for, is spilled, as are the batches after it. Every
+/// retry interval the spilled segments are written to the primary again, oldest first, and once
+/// they all went through batches go to the primary directly again, so the primary sees the
+/// batches in order. Spilled segments left behind by a previous run are replayed before
+/// anything else. A batch that timed out may have been written in part, the primary should
+/// tolerate duplicates.
+pub struct FailoverSink {
+    primary: Arc<dyn DataSink>,
+    spill: SegmentQueueSink,
+    spill_dir: PathBuf,
+    latency_threshold: Duration,
+    retry_interval: Duration,
+}
+
+impl FailoverSink {
+    pub fn new(primary: Arc<dyn DataSink>, spill_dir: impl Into<PathBuf>) -> Self {
+        let spill_dir = spill_dir.into();
+        Self {
+            primary,
+            spill: SegmentQueueSink::new(&spill_dir),
+            spill_dir,
+            latency_threshold: Duration::from_secs(30),
+            retry_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Time the primary gets to write a batch before it counts as down, 30 seconds by default
+    pub fn with_latency_threshold(mut self, latency_threshold: Duration) -> Self {
+        self.latency_threshold = latency_threshold;
+        self
+    }
+
+    /// Time between attempts to replay spilled batches to the primary, 30 seconds by default
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// Size the spilled batches may take up on disk, 1 GiB by default. The oldest are dropped
+    /// beyond it.
+    pub fn with_spill_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.spill = self.spill.with_max_bytes(max_bytes);
+        self
+    }
+
+    async fn write_primary(
+        &self,
+        batches: Vec<RecordBatch>,
+        context: &Arc<TaskContext>,
+    ) -> Result<()> {
+        let schema = batches[0].schema();
+        let stream = futures::stream::iter(batches.into_iter().map(Ok));
+        let write = self.primary.write_all(
+            Box::pin(RecordBatchStreamAdapter::new(schema, stream)),
+            context,
+        );
+        tokio::time::timeout(self.latency_threshold, write)
+            .await
+            .map_err(|_| {
+                exec_datafusion_err!(
+                    "Primary sink took longer than {:?} to write a batch",
+                    self.latency_threshold
+                )
+            })??;
+        Ok(())
+    }
+
+    async fn write_spill(&self, batch: RecordBatch, context: &Arc<TaskContext>) -> Result<()> {
+        let schema = batch.schema();
+        let stream = futures::stream::iter([Ok(batch)]);
+        self.spill
+            .write_all(
+                Box::pin(RecordBatchStreamAdapter::new(schema, stream)),
+                context,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Write the spilled segments to the primary, oldest first. `false` if the primary failed
+    /// before all of them went through.
+    async fn replay(&self, context: &Arc<TaskContext>) -> Result<bool> {
+        if !self.spill_dir.exists() {
+            return Ok(true);
+        }
+        for segment in list_segments(&self.spill_dir)? {
+            let batches = read_segment(&segment.path)?;
+            if !batches.is_empty() {
+                if let Err(err) = self.write_primary(batches, context).await {
+                    warn!("Primary sink still failing, keeping spilled batches: {err}");
+                    return Ok(false);
+                }
+            }
+            fs::remove_file(&segment.path)?;
+        }
+        info!("Primary sink recovered, replayed the spilled batches");
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl DataSink for FailoverSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let mut spilling = self.spill_dir.exists() && !list_segments(&self.spill_dir)?.is_empty();
+        let mut next_attempt = Instant::now();
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            row_count += batch.num_rows() as u64;
+            if spilling && Instant::now() >= next_attempt {
+                spilling = !self.replay(context).await?;
+                next_attempt = Instant::now() + self.retry_interval;
+            }
+            if !spilling {
+                match self.write_primary(vec![batch.clone()], context).await {
+                    Ok(()) => continue,
+                    Err(err) => {
+                        warn!("Primary sink failed, spilling batches locally: {err}");
+                        spilling = true;
+                        next_attempt = Instant::now() + self.retry_interval;
+                    }
+                }
+            }
+            self.write_spill(batch, context).await?;
+        }
+        if spilling {
+            self.replay(context).await?;
+        }
+        Ok(row_count)
+    }
+}
+
+impl Debug for FailoverSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FailoverSink")
+            .field("primary", &self.primary)
+            .field("spill_dir", &self.spill_dir)
+            .finish()
+    }
+}
+
+impl DisplayAs for FailoverSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "FailoverSink (")?;
+                self.primary.fmt_as(t, f)?;
+                write!(f, ", spill to {})", self.spill_dir.display())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::common::exec_err;
+
+    #[derive(Debug, Default)]
+    struct FlakySink {
+        down: AtomicBool,
+        ids: Mutex<Vec<i64>>,
+    }
+
+    impl DisplayAs for FlakySink {
+        fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "FlakySink")
+        }
+    }
+
+    #[async_trait]
+    impl DataSink for FlakySink {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn metrics(&self) -> Option<MetricsSet> {
+            None
+        }
+
+        async fn write_all(
+            &self,
+            mut data: SendableRecordBatchStream,
+            _context: &Arc<TaskContext>,
+        ) -> Result<u64> {
+            if self.down.load(Ordering::SeqCst) {
+                return exec_err!("down");
+            }
+            while let Some(batch) = data.next().await.transpose()? {
+                let ids = batch.column(0).as_primitive::<Int64Type>();
+                self.ids.lock().unwrap().extend(ids.values().iter());
+            }
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn spill_and_replay_in_order() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("failover_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batches = (1..=4)
+            .map(|id| {
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![id]))])
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let primary = Arc::new(FlakySink::default());
+        primary.down.store(true, Ordering::SeqCst);
+        let sink = FailoverSink::new(primary.clone(), &dir).with_retry_interval(Duration::ZERO);
+        let context = Arc::new(TaskContext::default());
+        let stream = futures::stream::iter(batches[..2].to_vec().into_iter().map(Ok));
+        let rows = sink
+            .write_all(
+                Box::pin(RecordBatchStreamAdapter::new(schema.clone(), stream)),
+                &context,
+            )
+            .await?;
+        assert_eq!(rows, 2);
+        assert!(primary.ids.lock().unwrap().is_empty());
+        assert_eq!(list_segments(&dir)?.len(), 2);
+
+        primary.down.store(false, Ordering::SeqCst);
+        let stream = futures::stream::iter(batches[2..].to_vec().into_iter().map(Ok));
+        sink.write_all(
+            Box::pin(RecordBatchStreamAdapter::new(schema, stream)),
+            &context,
+        )
+        .await?;
+        assert_eq!(*primary.ids.lock().unwrap(), vec![1, 2, 3, 4]);
+        assert!(list_segments(&dir)?.is_empty());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
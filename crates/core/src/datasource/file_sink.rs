@@ -0,0 +1,420 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::compute::take_record_batch;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use arrow_array::{Array, RecordBatch, UInt32Array};
+use arrow_json::LineDelimitedWriter;
+use arrow_schema::SchemaRef;
+use datafusion::common::instant::Instant;
+use datafusion::common::{plan_err, Result};
+use datafusion::datasource::listing::ListingTableUrl;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::parquet::arrow::ArrowWriter;
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+use futures::StreamExt;
+use log::debug;
+use object_store::path::Path;
+use object_store::{ObjectStore, WriteMultipart};
+
+use crate::datasource::message::now_ms;
+use crate::utils::quota::ResourceQuotas;
+
+/// Directory name of rows whose partition column is null, as Hive names it
+pub const NULL_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+const IN_PROGRESS_EXTENSION: &str = "inprogress";
+
+/// Parts of a file uploaded at the same time
+const MAX_CONCURRENT_PARTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Parquet,
+    /// Newline delimited JSON, one object per row
+    Json,
+}
+
+impl FileFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Parquet => "parquet",
+            FileFormat::Json => "json",
+        }
+    }
+}
+
+/// Writes rows to rolling files under a directory on local disk or in an object store such
+/// as S3, for landing a stream in a data lake.
+///
+/// A file is rolled once it holds the target size or has been open for the maximum age,
+/// whichever comes first, and when the stream ends. Rows are laid out in Hive style
+/// directories by the partition columns, e.g. `dt=2024-06-01/country=NL/`, the partition
+/// columns themselves are left out of the files. A file is uploaded as
+/// `.part-<...>.inprogress` while it's open and renamed to its final name once complete, so
+/// readers that skip hidden files or go by extension never see a partial one. Files a
+/// stopped pipeline left in progress are not cleaned up.
+pub struct RollingFileSink {
+    table_url: ListingTableUrl,
+    format: FileFormat,
+    partition_by: Vec<String>,
+    target_size: usize,
+    max_age: Duration,
+}
+
+impl RollingFileSink {
+    /// Write to the directory at `table_uri`, a local path or the URL of an object store
+    /// registered with the session. Files roll at 128 MiB or after 5 minutes by default.
+    pub fn try_new(table_uri: &str, format: FileFormat) -> Result<Self> {
+        Ok(Self {
+            table_url: ListingTableUrl::parse(table_uri)?,
+            format,
+            partition_by: vec![],
+            target_size: 128 * 1024 * 1024,
+            max_age: Duration::from_secs(5 * 60),
+        })
+    }
+
+    pub fn with_partition_by(mut self, columns: &[&str]) -> Self {
+        self.partition_by = columns.iter().map(|column| column.to_string()).collect();
+        self
+    }
+
+    /// Bytes after which a file is rolled. Parquet files count the row group still being
+    /// buffered, so they can end up somewhat larger once compressed and closed.
+    pub fn with_target_size(mut self, target_size: usize) -> Self {
+        self.target_size = target_size;
+        self
+    }
+
+    /// Time after which a file is rolled even if it didn't reach the target size
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// The schema of the files, `schema` without the partition columns, and the indices of
+    /// the partition columns
+    fn split_schema(&self, schema: &SchemaRef) -> Result<(SchemaRef, Vec<usize>)> {
+        let mut partition_indices = vec![];
+        for column in &self.partition_by {
+            match schema.index_of(column) {
+                Ok(index) => partition_indices.push(index),
+                Err(_) => return plan_err!("Partition column {column} not found"),
+            }
+        }
+        let data_indices: Vec<usize> = (0..schema.fields().len())
+            .filter(|index| !partition_indices.contains(index))
+            .collect();
+        if data_indices.is_empty() {
+            return plan_err!("Files need at least one column besides the partition columns");
+        }
+        Ok((Arc::new(schema.project(&data_indices)?), partition_indices))
+    }
+
+    /// The rows of `batch` by the partition directory they go to
+    fn partition(
+        &self,
+        batch: &RecordBatch,
+        partition_indices: &[usize],
+    ) -> Result<Vec<(Path, RecordBatch)>> {
+        let prefix = self.table_url.prefix();
+        if partition_indices.is_empty() {
+            return Ok(vec![(prefix.clone(), batch.clone())]);
+        }
+        let options = FormatOptions::default();
+        let formatters = partition_indices
+            .iter()
+            .map(|&index| ArrayFormatter::try_new(batch.column(index), &options))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut rows: HashMap<Vec<String>, Vec<u32>> = HashMap::new();
+        for row in 0..batch.num_rows() {
+            let values = partition_indices
+                .iter()
+                .zip(&formatters)
+                .map(|(&index, formatter)| {
+                    if batch.column(index).is_null(row) {
+                        NULL_PARTITION.to_string()
+                    } else {
+                        formatter.value(row).to_string()
+                    }
+                })
+                .collect();
+            rows.entry(values).or_default().push(row as u32);
+        }
+        rows.into_iter()
+            .map(|(values, rows)| {
+                let dir = self
+                    .partition_by
+                    .iter()
+                    .zip(values)
+                    .fold(prefix.clone(), |dir, (column, value)| {
+                        dir.child(format!("{column}={value}"))
+                    });
+                Ok((dir, take_record_batch(batch, &UInt32Array::from(rows))?))
+            })
+            .collect()
+    }
+}
+
+enum FileWriter {
+    Parquet(ArrowWriter<Vec<u8>>),
+    Json { bytes_written: usize },
+}
+
+/// A file being uploaded under its in progress name
+struct OpenFile {
+    in_progress: Path,
+    path: Path,
+    writer: FileWriter,
+    upload: WriteMultipart,
+    opened: Instant,
+}
+
+impl OpenFile {
+    async fn create(
+        store: &Arc<dyn ObjectStore>,
+        dir: &Path,
+        name: &str,
+        format: FileFormat,
+        schema: &SchemaRef,
+    ) -> Result<Self> {
+        let in_progress = dir.child(format!(".{name}.{IN_PROGRESS_EXTENSION}"));
+        let writer = match format {
+            FileFormat::Parquet => {
+                FileWriter::Parquet(ArrowWriter::try_new(Vec::new(), schema.clone(), None)?)
+            }
+            FileFormat::Json => FileWriter::Json { bytes_written: 0 },
+        };
+        Ok(Self {
+            upload: WriteMultipart::new(store.put_multipart(&in_progress).await?),
+            in_progress,
+            path: dir.child(name),
+            writer,
+            opened: Instant::now(),
+        })
+    }
+
+    async fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        let bytes = match &mut self.writer {
+            FileWriter::Parquet(writer) => {
+                writer.write(batch)?;
+                std::mem::take(writer.inner_mut())
+            }
+            FileWriter::Json { bytes_written } => {
+                let mut writer = LineDelimitedWriter::new(Vec::new());
+                writer.write(batch)?;
+                writer.finish()?;
+                let bytes = writer.into_inner();
+                *bytes_written += bytes.len();
+                bytes
+            }
+        };
+        self.upload.wait_for_capacity(MAX_CONCURRENT_PARTS).await?;
+        self.upload.write(&bytes);
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        match &self.writer {
+            FileWriter::Parquet(writer) => writer.bytes_written() + writer.in_progress_size(),
+            FileWriter::Json { bytes_written } => *bytes_written,
+        }
+    }
+
+    /// Finish the upload and move the file to its final name
+    async fn close(mut self, store: &Arc<dyn ObjectStore>) -> Result<()> {
+        if let FileWriter::Parquet(writer) = self.writer {
+            self.upload.write(&writer.into_inner()?);
+        }
+        self.upload.finish().await?;
+        store.rename(&self.in_progress, &self.path).await?;
+        debug!("Rolled {}", self.path);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataSink for RollingFileSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let store = context.runtime_env().object_store(&self.table_url)?;
+        let quotas = ResourceQuotas::from_task_context(context);
+        let (file_schema, partition_indices) = self.split_schema(&data.schema())?;
+        let run = now_ms();
+        let mut sequence = 0;
+        let mut open: HashMap<Path, OpenFile> = HashMap::new();
+        let mut row_count = 0;
+
+        loop {
+            // Files of quiet partitions still roll on time
+            let next_roll = open
+                .values()
+                .map(|file| self.max_age.saturating_sub(file.opened.elapsed()))
+                .min();
+            let next = match next_roll {
+                Some(remaining) => tokio::time::timeout(remaining, data.next()).await.ok(),
+                None => Some(data.next().await),
+            };
+            let Some(next) = next else {
+                let expired: Vec<Path> = open
+                    .iter()
+                    .filter(|(_, file)| file.opened.elapsed() >= self.max_age)
+                    .map(|(dir, _)| dir.clone())
+                    .collect();
+                for dir in expired {
+                    open.remove(&dir).unwrap().close(&store).await?;
+                }
+                continue;
+            };
+            let Some(batch) = next.transpose()? else {
+                break;
+            };
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+
+            for (dir, rows) in self.partition(&batch, &partition_indices)? {
+                let rows = rows.project(
+                    &(0..rows.num_columns())
+                        .filter(|index| !partition_indices.contains(index))
+                        .collect::<Vec<_>>(),
+                )?;
+                if !open.contains_key(&dir) {
+                    sequence += 1;
+                    let name = format!("part-{run}-{sequence:05}.{}", self.format.extension());
+                    let file =
+                        OpenFile::create(&store, &dir, &name, self.format, &file_schema).await?;
+                    open.insert(dir.clone(), file);
+                }
+                let file = open.get_mut(&dir).unwrap();
+                file.write(&rows).await?;
+                if file.size() >= self.target_size || file.opened.elapsed() >= self.max_age {
+                    open.remove(&dir).unwrap().close(&store).await?;
+                }
+            }
+            row_count += batch.num_rows() as u64;
+        }
+        for (_, file) in open {
+            file.close(&store).await?;
+        }
+        Ok(row_count)
+    }
+}
+
+impl Debug for RollingFileSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RollingFileSink")
+            .field("table_url", &self.table_url.as_str())
+            .field("format", &self.format)
+            .field("partition_by", &self.partition_by)
+            .finish()
+    }
+}
+
+impl DisplayAs for RollingFileSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "RollingFileSink: url={}, format={:?}",
+                    self.table_url.as_str(),
+                    self.format
+                )?;
+                if !self.partition_by.is_empty() {
+                    write!(f, ", partition_by=[{}]", self.partition_by.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use arrow_array::{Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+
+    #[tokio::test]
+    async fn roll_partitioned_files() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("file_sink_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("dt", DataType::Utf8, true),
+            Field::new("amount", DataType::Int64, false),
+        ]));
+        let batches = [
+            (vec![Some("2024-06-01"), Some("2024-06-02")], vec![1, 2]),
+            (vec![Some("2024-06-01"), None], vec![3, 4]),
+        ]
+        .into_iter()
+        .map(|(dts, amounts)| {
+            Ok(RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(StringArray::from(dts)),
+                    Arc::new(Int64Array::from(amounts)),
+                ],
+            )?)
+        })
+        .collect::<Vec<Result<_>>>();
+
+        // Every batch fills a file
+        let sink = RollingFileSink::try_new(&format!("{}/", dir.display()), FileFormat::Json)?
+            .with_partition_by(&["dt"])
+            .with_target_size(1);
+        let stream = RecordBatchStreamAdapter::new(schema, futures::stream::iter(batches));
+        let rows = sink
+            .write_all(Box::pin(stream), &Arc::new(TaskContext::default()))
+            .await?;
+        assert_eq!(rows, 4);
+
+        let contents = |partition: &str| -> Result<Vec<String>> {
+            let mut files = fs::read_dir(dir.join(partition))?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<Vec<_>>>()?;
+            files.sort();
+            files
+                .iter()
+                .map(|file| Ok(fs::read_to_string(file)?))
+                .collect()
+        };
+        assert_eq!(
+            contents("dt=2024-06-01")?,
+            vec!["{\"amount\":1}\n", "{\"amount\":3}\n"]
+        );
+        assert_eq!(contents("dt=2024-06-02")?, vec!["{\"amount\":2}\n"]);
+        assert_eq!(
+            contents(&format!("dt={NULL_PARTITION}"))?,
+            vec!["{\"amount\":4}\n"]
+        );
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
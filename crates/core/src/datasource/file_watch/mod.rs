@@ -0,0 +1,398 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::compute::{cast, concat_batches};
+use arrow_array::{new_null_array, RecordBatch};
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::Result;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::parquet::arrow::async_reader::{
+    ParquetObjectReader, ParquetRecordBatchStreamBuilder,
+};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use futures::TryStreamExt;
+use log::debug;
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore};
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::message::JsonMessageDecoder;
+use crate::state_backend::backend::{get_global_state_backend, StateBackend};
+use crate::utils::json_parse::parse_json;
+
+#[cfg(feature = "sqs")]
+pub mod sqs;
+
+/// How the files of a watched prefix are encoded
+#[derive(Debug, Clone, Copy)]
+pub enum FileFormat {
+    /// Columns are matched to the message schema by name and cast to its types, columns a file
+    /// lacks are null
+    Parquet,
+    /// One JSON message per line
+    Json,
+}
+
+/// Watches a prefix of an object store for new files, e.g. an S3 drop zone, and emits the
+/// contents of every file once, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// The prefix is listed every `poll_interval`, files are read oldest first, each as one batch.
+/// Files whose name starts with `.` or `_`, like temporary uploads and `_SUCCESS` markers, are
+/// ignored. Rows take their event time from the decoder's timestamp column, or from the time
+/// the file was last modified. With checkpointing enabled every file closes an epoch and its
+/// name is recorded in the checkpoint, a restarted pipeline skips the files it already read.
+/// Files are expected to be complete once they are listed and not to change afterwards.
+pub struct FileWatchSource {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    format: FileFormat,
+    decoder: JsonMessageDecoder,
+    poll_interval: Duration,
+}
+
+impl FileWatchSource {
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        prefix: Path,
+        format: FileFormat,
+        decoder: JsonMessageDecoder,
+        poll_interval: Duration,
+    ) -> Self {
+        Self {
+            store,
+            prefix,
+            format,
+            decoder,
+            poll_interval,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for FileWatchSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = FileWatchPartition {
+            store: self.store.clone(),
+            prefix: self.prefix.clone(),
+            format: self.format,
+            decoder: self.decoder.clone(),
+            poll_interval: self.poll_interval,
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct FileWatchPartition {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    format: FileFormat,
+    decoder: JsonMessageDecoder,
+    poll_interval: Duration,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for FileWatchPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let reader = FileReader {
+            store: self.store.clone(),
+            format: self.format,
+            decoder: self.decoder.clone(),
+        };
+        let prefix = self.prefix.clone();
+        let poll_interval = self.poll_interval;
+
+        builder.spawn(async move {
+            let namespace = format!("file_watch_source_{prefix}");
+            let mut processed = ProcessedFiles::open(namespace, should_checkpoint)?;
+            let mut epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current());
+            loop {
+                let files = new_files(&reader.store, &prefix, &mut processed).await?;
+                if files.is_empty() {
+                    tokio::time::sleep(poll_interval).await;
+                    continue;
+                }
+                for file in files {
+                    let batch = reader.read(&file).await?;
+                    if batch.num_rows() > 0 {
+                        let checkpoint = if should_checkpoint {
+                            epoch += 1;
+                            if let Some(tracker) = &epoch_tracker {
+                                tracker.advance(epoch)?;
+                            }
+                            Some(epoch)
+                        } else {
+                            None
+                        };
+                        let batch = reader.with_metadata(batch, &file, checkpoint)?;
+                        if tx.send(Ok(batch)).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    processed.insert(file.location, epoch)?;
+                }
+            }
+        });
+        builder.build()
+    }
+}
+
+/// Files under `prefix` that weren't read yet, oldest first
+async fn new_files(
+    store: &Arc<dyn ObjectStore>,
+    prefix: &Path,
+    processed: &mut ProcessedFiles,
+) -> Result<Vec<ObjectMeta>> {
+    let listed: Vec<ObjectMeta> = store.list(Some(prefix)).try_collect().await?;
+    let mut files = vec![];
+    for file in listed {
+        if !is_hidden(&file.location) && !processed.contains(&file.location)? {
+            files.push(file);
+        }
+    }
+    files.sort_by(|a, b| (a.last_modified, &a.location).cmp(&(b.last_modified, &b.location)));
+    Ok(files)
+}
+
+/// Temporary uploads and markers like `_SUCCESS`
+fn is_hidden(location: &Path) -> bool {
+    location
+        .filename()
+        .map_or(true, |name| name.starts_with(['.', '_']))
+}
+
+/// The files a source has read, recorded in the checkpoint state when checkpointing so that
+/// a restored source doesn't read them again
+struct ProcessedFiles {
+    processed: HashSet<Path>,
+    checkpoint: Option<(Arc<dyn StateBackend>, String)>,
+}
+
+impl ProcessedFiles {
+    fn open(namespace: String, should_checkpoint: bool) -> Result<Self> {
+        let checkpoint = if should_checkpoint {
+            let backend = get_global_state_backend()?;
+            backend.ensure_namespace(&namespace)?;
+            Some((backend, namespace))
+        } else {
+            None
+        };
+        Ok(Self {
+            processed: HashSet::new(),
+            checkpoint,
+        })
+    }
+
+    fn contains(&mut self, location: &Path) -> Result<bool> {
+        if self.processed.contains(location) {
+            return Ok(true);
+        }
+        if let Some((backend, namespace)) = &self.checkpoint {
+            let key = location.to_string().into_bytes();
+            if backend.get_state(namespace, key)?.is_some() {
+                debug!("Skipping {location}, read before the restore");
+                self.processed.insert(location.clone());
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Record `location` as read by the batch closing `epoch`
+    fn insert(&mut self, location: Path, epoch: u64) -> Result<()> {
+        if let Some((backend, namespace)) = &self.checkpoint {
+            backend.put_state(
+                namespace,
+                location.to_string().into_bytes(),
+                epoch.to_be_bytes().to_vec(),
+            )?;
+        }
+        self.processed.insert(location);
+        Ok(())
+    }
+}
+
+struct FileReader {
+    store: Arc<dyn ObjectStore>,
+    format: FileFormat,
+    decoder: JsonMessageDecoder,
+}
+
+impl FileReader {
+    /// The rows of `file` in the message schema of the decoder
+    async fn read(&self, file: &ObjectMeta) -> Result<RecordBatch> {
+        let schema = self.decoder.message_schema();
+        match self.format {
+            FileFormat::Parquet => {
+                let reader = ParquetObjectReader::new(Arc::clone(&self.store), file.clone());
+                let batches: Vec<RecordBatch> = ParquetRecordBatchStreamBuilder::new(reader)
+                    .await?
+                    .build()?
+                    .try_collect()
+                    .await?;
+                let batches = batches
+                    .iter()
+                    .map(|batch| conform(batch, &schema))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(concat_batches(&schema, &batches)?)
+            }
+            FileFormat::Json => {
+                let bytes = self.store.get(&file.location).await?.bytes().await?;
+                let messages = bytes
+                    .split(|byte| *byte == b'\n')
+                    .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+                    .map(parse_json)
+                    .collect::<Result<Vec<_>>>()?;
+                // The metadata is attached by with_metadata
+                let batch = self.decoder.decode(messages, 0)?;
+                Ok(batch.project(&(0..schema.fields().len()).collect::<Vec<_>>())?)
+            }
+        }
+    }
+
+    /// Add the metadata column to the rows read from `file`, with the barrier of `epoch` for
+    /// a batch closing a checkpoint epoch
+    fn with_metadata(
+        &self,
+        batch: RecordBatch,
+        file: &ObjectMeta,
+        epoch: Option<u64>,
+    ) -> Result<RecordBatch> {
+        let modified_ms = file.last_modified.timestamp_millis();
+        match epoch {
+            Some(epoch) => self
+                .decoder
+                .decode_batch_checkpointed(batch, modified_ms, epoch),
+            None => self.decoder.decode_batch(batch, modified_ms),
+        }
+    }
+}
+
+/// `batch` with the columns of `schema`, matched by name
+pub(crate) fn conform(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(column) => Ok(cast(column, field.data_type())?),
+            None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use futures::StreamExt;
+    use object_store::local::LocalFileSystem;
+
+    use crate::utils::json_format::JsonFormatOptions;
+
+    #[tokio::test]
+    async fn read_new_files_once() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("file_watch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ids = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch =
+            RecordBatch::try_new(ids.clone(), vec![Arc::new(Int64Array::from(vec![1, 2]))])?;
+        let mut writer = ArrowWriter::try_new(
+            std::fs::File::create(dir.join("a.parquet")).unwrap(),
+            ids,
+            None,
+        )?;
+        writer.write(&batch)?;
+        writer.close()?;
+        std::fs::write(dir.join("_SUCCESS"), "").unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let decoder = JsonMessageDecoder::try_new(schema, JsonFormatOptions::default(), None)?;
+        let partition = FileWatchPartition {
+            store: Arc::new(LocalFileSystem::new()),
+            prefix: Path::from_filesystem_path(&dir).unwrap(),
+            format: FileFormat::Parquet,
+            schema: decoder.schema(),
+            decoder,
+            poll_interval: Duration::from_millis(10),
+        };
+        let mut stream = partition.execute(Arc::new(TaskContext::default()));
+
+        let first = stream.next().await.unwrap()?;
+        let first_ids = first
+            .column(0)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec();
+        assert_eq!(first_ids, vec![1, 2]);
+        assert_eq!(first.column(1).null_count(), 2);
+
+        std::fs::copy(dir.join("a.parquet"), dir.join("b.parquet")).unwrap();
+        let second = stream.next().await.unwrap()?;
+        assert_eq!(second.num_rows(), 2);
+        let third = tokio::time::timeout(Duration::from_millis(100), stream.next()).await;
+        assert!(third.is_err(), "files must be read only once");
+
+        std::fs::remove_dir_all(dir).unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use aws_sdk_sqs::types::DeleteMessageBatchRequestEntry;
+use aws_sdk_sqs::Client;
+use datafusion::catalog::Session;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::warn;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::{is_hidden, FileFormat, FileReader, ProcessedFiles};
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::message::JsonMessageDecoder;
+
+/// Most messages a single receive returns
+const MAX_RECEIVE_MESSAGES: i32 = 10;
+/// Long polling wait of a receive
+const WAIT_TIME_SECS: i32 = 20;
+/// How long a received notification stays hidden from other consumers, renewed while its
+/// files are read
+const VISIBILITY_TIMEOUT_SECS: i32 = 60;
+const VISIBILITY_RENEWAL: Duration = Duration::from_secs(20);
+
+/// Reads the files announced by S3 `ObjectCreated` notifications delivered to an SQS queue,
+/// directly or through SNS, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// A notification driven counterpart of [`FileWatchSource`](super::FileWatchSource) for
+/// buckets too large to list: `store` is the bucket the notifications are about, files are
+/// read as soon as their notification arrives and emitted once each, even if S3 notifies
+/// twice. With checkpointing enabled every file closes an epoch and notifications are deleted
+/// from the queue once their files have been handed downstream and recorded in the checkpoint,
+/// a notification that wasn't deleted becomes visible again and is retried. Without
+/// checkpointing notifications are deleted as they are received.
+pub struct S3NotificationSource {
+    client: Client,
+    queue_url: String,
+    store: Arc<dyn ObjectStore>,
+    format: FileFormat,
+    decoder: JsonMessageDecoder,
+}
+
+impl S3NotificationSource {
+    /// Connect with the AWS configuration of the environment
+    pub async fn new(
+        queue_url: impl Into<String>,
+        store: Arc<dyn ObjectStore>,
+        format: FileFormat,
+        decoder: JsonMessageDecoder,
+    ) -> Self {
+        let config = aws_config::load_from_env().await;
+        Self::with_client(Client::new(&config), queue_url, store, format, decoder)
+    }
+
+    pub fn with_client(
+        client: Client,
+        queue_url: impl Into<String>,
+        store: Arc<dyn ObjectStore>,
+        format: FileFormat,
+        decoder: JsonMessageDecoder,
+    ) -> Self {
+        Self {
+            client,
+            queue_url: queue_url.into(),
+            store,
+            format,
+            decoder,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for S3NotificationSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = S3NotificationPartition {
+            client: self.client.clone(),
+            queue_url: self.queue_url.clone(),
+            store: self.store.clone(),
+            format: self.format,
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct S3NotificationPartition {
+    client: Client,
+    queue_url: String,
+    store: Arc<dyn ObjectStore>,
+    format: FileFormat,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for S3NotificationPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let queue = Queue {
+            client: self.client.clone(),
+            url: self.queue_url.clone(),
+        };
+        let reader = FileReader {
+            store: self.store.clone(),
+            format: self.format,
+            decoder: self.decoder.clone(),
+        };
+
+        builder.spawn(async move {
+            let namespace = format!("s3_notification_source_{}", queue.url);
+            let mut ingest = Ingest {
+                reader,
+                processed: ProcessedFiles::open(namespace, should_checkpoint)?,
+                tx,
+                epoch: epoch_tracker
+                    .as_ref()
+                    .map_or(0, |tracker| tracker.current()),
+                epoch_tracker,
+                should_checkpoint,
+            };
+            loop {
+                let messages = queue.receive().await?;
+                if !should_checkpoint {
+                    let receipts = messages.iter().map(|(_, receipt)| receipt.as_str());
+                    queue.delete(receipts).await?;
+                }
+
+                for (body, receipt) in &messages {
+                    let locations = match created_objects(body) {
+                        Ok(locations) => locations,
+                        Err(err) => {
+                            warn!("Dropped notification that isn't an S3 event: {err}");
+                            if should_checkpoint {
+                                queue.delete([receipt.as_str()]).await?;
+                            }
+                            continue;
+                        }
+                    };
+
+                    let files = ingest.files(locations);
+                    tokio::pin!(files);
+                    let mut renewal = tokio::time::interval_at(
+                        tokio::time::Instant::now() + VISIBILITY_RENEWAL,
+                        VISIBILITY_RENEWAL,
+                    );
+                    let sent = loop {
+                        tokio::select! {
+                            sent = &mut files => break sent?,
+                            _ = renewal.tick(), if should_checkpoint => {
+                                queue.extend_visibility(receipt).await?
+                            }
+                        }
+                    };
+                    if !sent {
+                        return Ok(());
+                    }
+                    if should_checkpoint {
+                        queue.delete([receipt.as_str()]).await?;
+                    }
+                }
+            }
+        });
+        builder.build()
+    }
+}
+
+struct Queue {
+    client: Client,
+    url: String,
+}
+
+impl Queue {
+    /// The bodies and receipt handles of the next messages, waiting for them to arrive
+    async fn receive(&self) -> Result<Vec<(String, String)>> {
+        let received = self
+            .client
+            .receive_message()
+            .queue_url(&self.url)
+            .max_number_of_messages(MAX_RECEIVE_MESSAGES)
+            .wait_time_seconds(WAIT_TIME_SECS)
+            .visibility_timeout(VISIBILITY_TIMEOUT_SECS)
+            .send()
+            .await
+            .map_err(sqs_error)?;
+        Ok(received
+            .messages
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|message| Some((message.body?, message.receipt_handle?)))
+            .collect())
+    }
+
+    async fn delete<'a>(&self, receipts: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let entries = receipts
+            .into_iter()
+            .enumerate()
+            .map(|(id, receipt)| {
+                DeleteMessageBatchRequestEntry::builder()
+                    .id(id.to_string())
+                    .receipt_handle(receipt)
+                    .build()
+                    .map_err(sqs_error)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let deleted = self
+            .client
+            .delete_message_batch()
+            .queue_url(&self.url)
+            .set_entries(Some(entries))
+            .send()
+            .await
+            .map_err(sqs_error)?;
+        // Notifications that remain are delivered again and skipped as already read
+        for failure in deleted.failed() {
+            warn!(
+                "Failed to delete S3 notification from {}: {}",
+                self.url,
+                failure.message().unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+
+    async fn extend_visibility(&self, receipt: &str) -> Result<()> {
+        self.client
+            .change_message_visibility()
+            .queue_url(&self.url)
+            .receipt_handle(receipt)
+            .visibility_timeout(VISIBILITY_TIMEOUT_SECS)
+            .send()
+            .await
+            .map_err(sqs_error)?;
+        Ok(())
+    }
+}
+
+struct Ingest {
+    reader: FileReader,
+    processed: ProcessedFiles,
+    tx: mpsc::Sender<Result<RecordBatch>>,
+    epoch: u64,
+    epoch_tracker: Option<Arc<EpochTracker>>,
+    should_checkpoint: bool,
+}
+
+impl Ingest {
+    /// Read and send the files at `locations` that weren't read yet, false once the stream
+    /// reading them went away
+    async fn files(&mut self, locations: Vec<Path>) -> Result<bool> {
+        for location in locations {
+            if is_hidden(&location) || self.processed.contains(&location)? {
+                continue;
+            }
+            let file = match self.reader.store.head(&location).await {
+                Ok(file) => file,
+                Err(object_store::Error::NotFound { .. }) => {
+                    warn!("{location} was deleted before it could be read");
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            let batch = self.reader.read(&file).await?;
+            if batch.num_rows() > 0 {
+                let checkpoint = if self.should_checkpoint {
+                    self.epoch += 1;
+                    if let Some(tracker) = &self.epoch_tracker {
+                        tracker.advance(self.epoch)?;
+                    }
+                    Some(self.epoch)
+                } else {
+                    None
+                };
+                let batch = self.reader.with_metadata(batch, &file, checkpoint)?;
+                if self.tx.send(Ok(batch)).await.is_err() {
+                    return Ok(false);
+                }
+            }
+            self.processed.insert(location, self.epoch)?;
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Event {
+    #[serde(rename = "Records", default)]
+    records: Vec<S3EventRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3EventRecord {
+    #[serde(rename = "eventName")]
+    event_name: String,
+    s3: S3Entity,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Entity {
+    object: S3Object,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Object {
+    key: String,
+}
+
+/// The objects an S3 event notification announces as created. Test events announce none.
+fn created_objects(body: &str) -> Result<Vec<Path>> {
+    let json_error = |err| DataFusionError::External(Box::new(err));
+    let mut event: Value = serde_json::from_str(body).map_err(json_error)?;
+    // Notifications fanned out through SNS carry the S3 event as a string
+    if event.get("Type").and_then(Value::as_str) == Some("Notification") {
+        if let Some(message) = event.get("Message").and_then(Value::as_str) {
+            event = serde_json::from_str(message).map_err(json_error)?;
+        }
+    }
+    let event: S3Event = serde_json::from_value(event).map_err(json_error)?;
+
+    event
+        .records
+        .into_iter()
+        .filter(|record| record.event_name.starts_with("ObjectCreated:"))
+        .map(|record| {
+            // Keys are form encoded, spaces are sent as `+`
+            Path::from_url_path(record.s3.object.key.replace('+', " "))
+                .map_err(|err| DataFusionError::External(Box::new(err)))
+        })
+        .collect()
+}
+
+fn sqs_error(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_s3_notifications() {
+        let event = serde_json::json!({
+            "Records": [
+                {"eventName": "ObjectCreated:Put", "s3": {"object": {"key": "drop/day+1/a%2Bb.parquet"}}},
+                {"eventName": "ObjectRemoved:Delete", "s3": {"object": {"key": "drop/old.parquet"}}},
+            ]
+        });
+        let expected = vec![Path::from("drop/day 1/a+b.parquet")];
+        assert_eq!(created_objects(&event.to_string()).unwrap(), expected);
+
+        let through_sns = serde_json::json!({
+            "Type": "Notification",
+            "Message": event.to_string(),
+        });
+        assert_eq!(created_objects(&through_sns.to_string()).unwrap(), expected);
+
+        let test_event = r#"{"Service":"Amazon S3","Event":"s3:TestEvent"}"#;
+        assert!(created_objects(test_event).unwrap().is_empty());
+        assert!(created_objects("not json").is_err());
+    }
+}
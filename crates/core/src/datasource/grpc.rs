@@ -0,0 +1,409 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::{not_impl_err, plan_err, DataFusionError, Result};
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan};
+use futures::StreamExt;
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor, SerializeOptions,
+};
+use serde_json::Value;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+
+use crate::datasource::message::{next_chunk, now_ms, JsonMessageDecoder, MAX_BATCH_MESSAGES};
+use crate::utils::json_format::JsonFormatOptions;
+use crate::utils::quota::ResourceQuotas;
+use crate::utils::row_encoder::{JsonRowEncoder, RowEncoder};
+
+/// A method of a gRPC service, described by a protobuf descriptor set supplied at runtime
+/// rather than generated code
+#[derive(Debug, Clone)]
+pub struct GrpcMethod {
+    endpoint: String,
+    method: MethodDescriptor,
+}
+
+impl GrpcMethod {
+    /// `descriptor_set` is a serialized `FileDescriptorSet`, as written by
+    /// `protoc --include_imports --descriptor_set_out`, and `method` the full name of the
+    /// method, e.g. `sensors.Readings/Subscribe`
+    pub fn try_new(endpoint: &str, descriptor_set: &[u8], method: &str) -> Result<Self> {
+        let pool = DescriptorPool::decode(descriptor_set).map_err(grpc_error)?;
+        let Some((service_name, method_name)) = method.rsplit_once('/') else {
+            return plan_err!("Expected a gRPC method as package.Service/Method, got {method}");
+        };
+        let Some(service) = pool.get_service_by_name(service_name) else {
+            return plan_err!("Service {service_name} not found in the descriptor set");
+        };
+        let Some(method) = service.methods().find(|m| m.name() == method_name) else {
+            return plan_err!("Method {method_name} not found in service {service_name}");
+        };
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            method,
+        })
+    }
+
+    fn path(&self) -> Result<PathAndQuery> {
+        let path = format!(
+            "/{}/{}",
+            self.method.parent_service().full_name(),
+            self.method.name()
+        );
+        PathAndQuery::try_from(path).map_err(grpc_error)
+    }
+
+    fn codec(&self) -> DynamicCodec {
+        DynamicCodec(self.method.output())
+    }
+
+    async fn connect(&self) -> Result<tonic::client::Grpc<Channel>> {
+        let channel = Endpoint::from_shared(self.endpoint.clone())
+            .map_err(grpc_error)?
+            .connect()
+            .await
+            .map_err(grpc_error)?;
+        Ok(tonic::client::Grpc::new(channel))
+    }
+}
+
+/// Source reading the responses of a server streaming gRPC method, called once with a request
+/// given as JSON. Responses are converted to JSON with their protobuf field names and decoded
+/// like the messages of any other source.
+pub struct GrpcSource {
+    method: GrpcMethod,
+    request: Value,
+    decoder: JsonMessageDecoder,
+}
+
+impl GrpcSource {
+    pub fn try_new(
+        method: GrpcMethod,
+        request: Value,
+        decoder: JsonMessageDecoder,
+    ) -> Result<Self> {
+        if !method.method.is_server_streaming() {
+            return plan_err!(
+                "gRPC method {} doesn't stream its responses",
+                method.method.full_name()
+            );
+        }
+        Ok(Self {
+            method,
+            request,
+            decoder,
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for GrpcSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = GrpcPartition {
+            method: self.method.clone(),
+            request: self.request.clone(),
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct GrpcPartition {
+    method: GrpcMethod,
+    request: Value,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for GrpcPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let method = self.method.clone();
+        let request = self.request.clone();
+        let decoder = self.decoder.clone();
+
+        builder.spawn(async move {
+            let request = DynamicMessage::deserialize(method.method.input(), request)
+                .map_err(|err| DataFusionError::External(Box::new(err)))?;
+            let mut grpc = method.connect().await?;
+            grpc.ready().await.map_err(grpc_error)?;
+            let responses = grpc
+                .server_streaming(Request::new(request), method.path()?, method.codec())
+                .await
+                .map_err(grpc_error)?;
+
+            let mut responses = Box::pin(responses.into_inner());
+            while let Some(chunk) = next_chunk(&mut responses).await {
+                let messages = chunk
+                    .into_iter()
+                    .map(|response| message_to_json(&response.map_err(grpc_error)?))
+                    .collect::<Result<Vec<_>>>()?;
+                let batch = decoder.decode(messages, now_ms())?;
+                if tx.send(Ok(batch)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+/// Sink calling a gRPC method with every row, converted from JSON into its request message.
+/// Client streaming methods get all rows of the pipeline over a single call, other methods
+/// are called once per row.
+pub struct GrpcSink {
+    method: GrpcMethod,
+    json_format: JsonFormatOptions,
+}
+
+impl GrpcSink {
+    pub fn new(method: GrpcMethod, json_format: JsonFormatOptions) -> Self {
+        Self {
+            method,
+            json_format,
+        }
+    }
+
+    fn requests(
+        &self,
+        encoder: &JsonRowEncoder,
+        batch: &RecordBatch,
+    ) -> Result<Vec<DynamicMessage>> {
+        encoder
+            .encode(batch)?
+            .iter()
+            .map(|row| {
+                let mut deserializer = serde_json::Deserializer::from_slice(row);
+                DynamicMessage::deserialize(self.method.method.input(), &mut deserializer)
+                    .map_err(|err| DataFusionError::External(Box::new(err)))
+            })
+            .collect()
+    }
+
+    async fn write_unary(
+        &self,
+        mut data: SendableRecordBatchStream,
+        quotas: Option<Arc<ResourceQuotas>>,
+    ) -> Result<u64> {
+        let mut grpc = self.method.connect().await?;
+        let encoder = JsonRowEncoder::new(self.json_format);
+        let path = self.method.path()?;
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            for request in self.requests(&encoder, &batch)? {
+                grpc.ready().await.map_err(grpc_error)?;
+                grpc.unary(Request::new(request), path.clone(), self.method.codec())
+                    .await
+                    .map_err(grpc_error)?;
+            }
+            row_count += batch.num_rows() as u64;
+        }
+        Ok(row_count)
+    }
+
+    async fn write_client_streaming(
+        &self,
+        mut data: SendableRecordBatchStream,
+        quotas: Option<Arc<ResourceQuotas>>,
+    ) -> Result<u64> {
+        let mut grpc = self.method.connect().await?;
+        grpc.ready().await.map_err(grpc_error)?;
+        let (sender, receiver) = tokio::sync::mpsc::channel(MAX_BATCH_MESSAGES);
+        let requests = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|request| (request, receiver))
+        });
+        let (path, codec) = (self.method.path()?, self.method.codec());
+        let call = SpawnedTask::spawn(async move {
+            grpc.client_streaming(Request::new(requests), path, codec)
+                .await
+                .map(|_| ())
+        });
+
+        let encoder = JsonRowEncoder::new(self.json_format);
+        let mut row_count = 0;
+        'batches: while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            for request in self.requests(&encoder, &batch)? {
+                // The call ended early, its status tells why
+                if sender.send(request).await.is_err() {
+                    break 'batches;
+                }
+            }
+            row_count += batch.num_rows() as u64;
+        }
+        drop(sender);
+        call.join()
+            .await
+            .map_err(|err| DataFusionError::External(Box::new(err)))?
+            .map_err(grpc_error)?;
+        Ok(row_count)
+    }
+}
+
+#[async_trait]
+impl DataSink for GrpcSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let quotas = ResourceQuotas::from_task_context(context);
+        match (
+            self.method.method.is_client_streaming(),
+            self.method.method.is_server_streaming(),
+        ) {
+            (true, false) => self.write_client_streaming(data, quotas).await,
+            (false, false) => self.write_unary(data, quotas).await,
+            (_, true) => not_impl_err!(
+                "gRPC method {} streams responses, which a sink can't consume",
+                self.method.method.full_name()
+            ),
+        }
+    }
+}
+
+impl Debug for GrpcSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrpcSink")
+            .field("method", &self.method.method.full_name())
+            .finish()
+    }
+}
+
+impl DisplayAs for GrpcSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "GrpcSink ({})", self.method.method.full_name())
+            }
+        }
+    }
+}
+
+/// A message as JSON with protobuf field names and 64 bit integers as numbers, the way the
+/// arrow JSON reader expects them
+fn message_to_json(message: &DynamicMessage) -> Result<Value> {
+    let options = SerializeOptions::new()
+        .use_proto_field_name(true)
+        .stringify_64_bit_integers(false);
+    message
+        .serialize_with_options(serde_json::value::Serializer, &options)
+        .map_err(|err| DataFusionError::External(Box::new(err)))
+}
+
+/// Encodes and decodes messages of types only known from their descriptors
+#[derive(Debug, Clone)]
+struct DynamicCodec(MessageDescriptor);
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.0.clone())
+    }
+}
+
+struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(
+        &mut self,
+        item: Self::Item,
+        dst: &mut EncodeBuf<'_>,
+    ) -> std::result::Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(
+        &mut self,
+        src: &mut DecodeBuf<'_>,
+    ) -> std::result::Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+}
+
+fn grpc_error(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
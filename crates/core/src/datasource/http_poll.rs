@@ -0,0 +1,497 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::{exec_err, plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::warn;
+use reqwest::header::{HeaderMap, ETAG, IF_NONE_MATCH, LINK};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::message::{now_ms, JsonMessageDecoder};
+use crate::state_backend::backend::get_global_state_backend;
+
+/// Where a polling HTTP source finds the next page of a response
+#[derive(Debug, Clone)]
+pub enum Pagination {
+    /// Every poll reads a single response
+    None,
+    /// The URL of the next page, absolute or relative, at a JSON pointer into the body. Like
+    /// `Link` targets it is requested as is, without the query parameter of the cursor.
+    NextUrl(String),
+    /// The `rel="next"` target of the `Link` header
+    LinkHeader,
+    /// An opaque token at `pointer` into the body, sent back as the query parameter `param`
+    Token { pointer: String, param: String },
+}
+
+/// How a polling HTTP source tells new records from the ones it already emitted
+#[derive(Debug, Clone)]
+pub enum Cursor {
+    /// Every poll emits all records the endpoint returns
+    None,
+    /// Only records whose `field` is greater than the greatest value seen so far are emitted,
+    /// and that value is sent as the query parameter `param`, e.g. `updated_since`. Numbers
+    /// are compared as numbers and anything else as strings, which orders RFC 3339
+    /// timestamps of the same time zone. Records without the field are skipped.
+    Field { field: String, param: String },
+    /// The ETag of the last response is sent as `If-None-Match`, polls answered with
+    /// `304 Not Modified` emit nothing
+    ETag,
+}
+
+/// Polls a REST endpoint returning JSON every `interval` and emits the records of each poll
+/// as one batch, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// Records are the elements of the array at [`Self::with_records_pointer`], or of the body
+/// itself. A poll follows the [`Pagination`] of the endpoint to its last page and filters
+/// records by the [`Cursor`]. With checkpointing enabled every poll that emits records closes
+/// an epoch, and the cursor is kept in the checkpoint so a restored source continues where
+/// it stopped. Failed requests, `429 Too Many Requests` and server errors are logged and
+/// retried at the next poll, other client errors fail the source.
+pub struct HttpPollSource {
+    url: Url,
+    interval: Duration,
+    headers: Vec<(String, String)>,
+    records_pointer: Option<String>,
+    pagination: Pagination,
+    cursor: Cursor,
+    decoder: JsonMessageDecoder,
+}
+
+impl HttpPollSource {
+    pub fn try_new(url: &str, interval: Duration, decoder: JsonMessageDecoder) -> Result<Self> {
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(err) => return plan_err!("Invalid URL {url}: {err}"),
+        };
+        Ok(Self {
+            url,
+            interval,
+            headers: vec![],
+            records_pointer: None,
+            pagination: Pagination::None,
+            cursor: Cursor::None,
+            decoder,
+        })
+    }
+
+    /// Send `name: value` with every request, e.g. an `Authorization` header
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Read the records from the array at a JSON pointer into the body, e.g. `/data`
+    pub fn with_records_pointer(mut self, pointer: &str) -> Self {
+        self.records_pointer = Some(pointer.to_string());
+        self
+    }
+
+    pub fn with_pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = pagination;
+        self
+    }
+
+    pub fn with_cursor(mut self, cursor: Cursor) -> Self {
+        self.cursor = cursor;
+        self
+    }
+}
+
+#[async_trait]
+impl TableProvider for HttpPollSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = HttpPollPartition {
+            poller: Arc::new(Poller {
+                client: reqwest::Client::new(),
+                url: self.url.clone(),
+                headers: self.headers.clone(),
+                records_pointer: self.records_pointer.clone(),
+                pagination: self.pagination.clone(),
+                cursor: self.cursor.clone(),
+            }),
+            interval: self.interval,
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct HttpPollPartition {
+    poller: Arc<Poller>,
+    interval: Duration,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for HttpPollPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let poller = self.poller.clone();
+        let interval = self.interval;
+        let decoder = self.decoder.clone();
+
+        builder.spawn(async move {
+            let backend = if should_checkpoint {
+                let backend = get_global_state_backend()?;
+                let namespace = format!("http_poll_source_{}", poller.url);
+                backend.ensure_namespace(&namespace)?;
+                Some((backend, namespace))
+            } else {
+                None
+            };
+            let mut state = match &backend {
+                Some((backend, namespace)) => backend
+                    .get_state(namespace, CURSOR_KEY.to_vec())?
+                    .map(|bytes| CursorState::from_bytes(&bytes))
+                    .transpose()?
+                    .unwrap_or_default(),
+                None => CursorState::default(),
+            };
+
+            let mut epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current());
+            loop {
+                if let Some((records, polled)) = poller.poll(&state).await? {
+                    if !records.is_empty() {
+                        let batch = if should_checkpoint {
+                            epoch += 1;
+                            if let Some(tracker) = &epoch_tracker {
+                                tracker.advance(epoch)?;
+                            }
+                            decoder.decode_checkpointed(records, now_ms(), epoch)?
+                        } else {
+                            decoder.decode(records, now_ms())?
+                        };
+                        if tx.send(Ok(batch)).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    if let Some((backend, namespace)) = &backend {
+                        backend.put_state(namespace, CURSOR_KEY.to_vec(), polled.to_bytes()?)?;
+                    }
+                    state = polled;
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+        builder.build()
+    }
+}
+
+const CURSOR_KEY: &[u8] = b"cursor";
+
+/// What a source remembers between polls
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct CursorState {
+    /// The greatest value of the cursor field seen
+    position: Option<Value>,
+    etag: Option<String>,
+}
+
+impl CursorState {
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|err| DataFusionError::External(Box::new(err)))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).map_err(|err| DataFusionError::External(Box::new(err)))
+    }
+}
+
+struct Poller {
+    client: reqwest::Client,
+    url: Url,
+    headers: Vec<(String, String)>,
+    records_pointer: Option<String>,
+    pagination: Pagination,
+    cursor: Cursor,
+}
+
+impl Poller {
+    /// The new records on all pages and the state to continue from once they were emitted,
+    /// none if the poll failed and should be retried
+    async fn poll(&self, state: &CursorState) -> Result<Option<(Vec<Value>, CursorState)>> {
+        let mut polled = state.clone();
+        let mut records = vec![];
+        let mut url = self.url.clone();
+        let mut query = self.cursor_query(state);
+        let mut first_page = true;
+        loop {
+            let mut request = self.client.get(url.clone()).query(&query);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            if let (true, Cursor::ETag, Some(etag)) = (first_page, &self.cursor, &state.etag) {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    warn!("Polling {url} failed, retrying at the next poll: {err}");
+                    return Ok(None);
+                }
+            };
+            let status = response.status();
+            if status == StatusCode::NOT_MODIFIED {
+                return Ok(Some((vec![], polled)));
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                warn!("Polling {url} was answered with {status}, retrying at the next poll");
+                return Ok(None);
+            }
+            if !status.is_success() {
+                return exec_err!("Polling {url} was answered with {status}");
+            }
+            if first_page {
+                if let Cursor::ETag = self.cursor {
+                    polled.etag = response
+                        .headers()
+                        .get(ETAG)
+                        .and_then(|etag| etag.to_str().ok())
+                        .map(str::to_string);
+                }
+            }
+            let link_next = next_link(response.headers());
+            let body = match response.bytes().await {
+                Ok(body) => body,
+                Err(err) => {
+                    warn!("Reading the response of {url} failed, retrying at the next poll: {err}");
+                    return Ok(None);
+                }
+            };
+            let mut body: Value = serde_json::from_slice(&body)
+                .map_err(|err| DataFusionError::External(Box::new(err)))?;
+
+            let next = match &self.pagination {
+                Pagination::None => None,
+                Pagination::NextUrl(pointer) => body
+                    .pointer(pointer)
+                    .and_then(Value::as_str)
+                    .filter(|next| !next.is_empty())
+                    .and_then(|next| url.join(next).ok())
+                    .map(|next| (next, vec![])),
+                Pagination::LinkHeader => link_next
+                    .and_then(|next| url.join(&next).ok())
+                    .map(|next| (next, vec![])),
+                Pagination::Token { pointer, param } => body
+                    .pointer(pointer)
+                    .filter(|token| !token.is_null() && token.as_str() != Some(""))
+                    .map(|token| {
+                        let mut query = self.cursor_query(state);
+                        query.push((param.clone(), query_value(token)));
+                        (url.clone(), query)
+                    }),
+            };
+            records.extend(self.take_records(&mut body)?);
+            first_page = false;
+
+            match next {
+                // A page linking to itself would be read forever
+                Some((next_url, next_query)) if (&next_url, &next_query) != (&url, &query) => {
+                    url = next_url;
+                    query = next_query;
+                }
+                _ => break,
+            }
+        }
+
+        if let Cursor::Field { field, .. } = &self.cursor {
+            records.retain(|record| is_newer(record.get(field), state.position.as_ref()));
+            for record in &records {
+                if is_newer(record.get(field), polled.position.as_ref()) {
+                    polled.position = record.get(field).cloned();
+                }
+            }
+        }
+        Ok(Some((records, polled)))
+    }
+
+    /// The query parameter of the cursor field, sent with every page
+    fn cursor_query(&self, state: &CursorState) -> Vec<(String, String)> {
+        match (&self.cursor, &state.position) {
+            (Cursor::Field { param, .. }, Some(position)) => {
+                vec![(param.clone(), query_value(position))]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn take_records(&self, body: &mut Value) -> Result<Vec<Value>> {
+        let records = match &self.records_pointer {
+            Some(pointer) => body.pointer_mut(pointer).map(Value::take),
+            None => Some(body.take()),
+        };
+        match records {
+            Some(Value::Array(records)) => Ok(records),
+            Some(record @ Value::Object(_)) => Ok(vec![record]),
+            Some(Value::Null) | None => Ok(vec![]),
+            Some(other) => exec_err!(
+                "Expected records in the response of {}, got {other}",
+                self.url
+            ),
+        }
+    }
+}
+
+fn query_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Whether a cursor field `value` is past `position`
+fn is_newer(value: Option<&Value>, position: Option<&Value>) -> bool {
+    let Some(value) = value.filter(|value| !value.is_null()) else {
+        return false;
+    };
+    let Some(position) = position else {
+        return true;
+    };
+    let ordering = match (value, position) {
+        (Value::Number(value), Value::Number(position)) => value
+            .as_f64()
+            .partial_cmp(&position.as_f64())
+            .unwrap_or(Ordering::Equal),
+        _ => query_value(value).cmp(&query_value(position)),
+    };
+    ordering == Ordering::Greater
+}
+
+/// The target of the `rel="next"` link of a `Link` header
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(','))
+        .find_map(|link| {
+            let (target, params) = link.split_once(';')?;
+            let is_next = params.split(';').any(|param| {
+                let param = param.trim().replace(' ', "");
+                param == "rel=\"next\"" || param == "rel=next"
+            });
+            is_next.then(|| {
+                target
+                    .trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
+
+    #[test]
+    fn follow_links_and_cursor() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(
+                r#"<https://api.example.com/items?page=1>; rel="prev", <https://api.example.com/items?page=3>; rel="next""#,
+            ),
+        );
+        assert_eq!(
+            next_link(&headers).as_deref(),
+            Some("https://api.example.com/items?page=3")
+        );
+        assert_eq!(next_link(&HeaderMap::new()), None);
+
+        let position = json!("2024-05-01T10:00:00Z");
+        assert!(is_newer(
+            Some(&json!("2024-05-01T10:00:01Z")),
+            Some(&position)
+        ));
+        assert!(!is_newer(Some(&position), Some(&position)));
+        assert!(!is_newer(None, Some(&position)));
+        assert!(is_newer(Some(&json!(10)), Some(&json!(9.5))));
+
+        let poller = Poller {
+            client: reqwest::Client::new(),
+            url: Url::parse("https://api.example.com/items").unwrap(),
+            headers: vec![],
+            records_pointer: Some("/data".to_string()),
+            pagination: Pagination::None,
+            cursor: Cursor::Field {
+                field: "updated_at".to_string(),
+                param: "updated_since".to_string(),
+            },
+        };
+        let state = CursorState {
+            position: Some(position),
+            etag: None,
+        };
+        assert_eq!(
+            poller.cursor_query(&state),
+            [(
+                "updated_since".to_string(),
+                "2024-05-01T10:00:00Z".to_string()
+            )]
+        );
+        let mut body = json!({"data": [{"id": 1}, {"id": 2}], "next": null});
+        assert_eq!(poller.take_records(&mut body).unwrap().len(), 2);
+        assert!(poller.take_records(&mut json!({"data": 3})).is_err());
+    }
+}
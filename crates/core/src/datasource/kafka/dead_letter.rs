@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use datafusion::common::{DataFusionError, Result};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::ClientConfig;
+use tracing::warn;
+
+use super::fresh_lane::RawMessage;
+use super::KafkaReadConfig;
+use crate::utils::job::JobIdentity;
+
+/// Header of a dead lettered message with the reason it couldn't be decoded
+pub const DEAD_LETTER_ERROR_HEADER: &str = "denormalized.error";
+/// Headers of a dead lettered message with where it was read from
+pub const DEAD_LETTER_TOPIC_HEADER: &str = "denormalized.source.topic";
+pub const DEAD_LETTER_PARTITION_HEADER: &str = "denormalized.source.partition";
+pub const DEAD_LETTER_OFFSET_HEADER: &str = "denormalized.source.offset";
+pub const DEAD_LETTER_TIMESTAMP_HEADER: &str = "denormalized.source.timestamp";
+
+/// What a Kafka source does with messages whose payload can't be decoded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DecodeErrorPolicy {
+    /// Fail the stream
+    #[default]
+    Fail,
+    /// Drop the message with a warning
+    Skip,
+    /// Forward the message with its key and payload as they were to `topic`, with headers
+    /// telling where it was read from and why it couldn't be decoded. The message counts as
+    /// read once the forwarded copy was delivered.
+    DeadLetter { topic: String },
+}
+
+/// Handles the messages a source fails to decode according to its [`DecodeErrorPolicy`]
+pub(crate) struct DecodeFailures {
+    policy: DecodeErrorPolicy,
+    source_topic: String,
+    producer: Option<FutureProducer>,
+}
+
+impl DecodeFailures {
+    pub fn try_new(config: &KafkaReadConfig, job: Option<&JobIdentity>) -> Result<Self> {
+        let producer = match &config.decode_error_policy {
+            DecodeErrorPolicy::DeadLetter { topic } => {
+                let mut client_config = ClientConfig::new();
+                client_config.set("bootstrap.servers", config.bootstrap_servers.to_string());
+                if let Some(job) = job {
+                    client_config.set("client.id", job.client_id(&format!("dead-letter-{topic}")));
+                }
+                for (key, value) in &config.kafka_connection_opts {
+                    client_config.set(key, value);
+                }
+                Some(
+                    client_config
+                        .create()
+                        .map_err(|e| DataFusionError::External(Box::new(e)))?,
+                )
+            }
+            _ => None,
+        };
+        Ok(Self {
+            policy: config.decode_error_policy.clone(),
+            source_topic: config.topic.clone(),
+            producer,
+        })
+    }
+
+    /// Handle `message` that failed to decode with `error`, failing when the policy is to
+    /// fail or the message couldn't be dead lettered
+    pub async fn handle(&self, message: &RawMessage, error: DataFusionError) -> Result<()> {
+        let (partition, offset, ..) = message;
+        match (&self.policy, &self.producer) {
+            (DecodeErrorPolicy::DeadLetter { topic }, Some(producer)) => {
+                let headers = dead_letter_headers(&self.source_topic, message, &error);
+                let mut record = FutureRecord::<[u8], [u8]>::to(topic)
+                    .payload(&message.4)
+                    .headers(headers);
+                if let Some(key) = &message.3 {
+                    record = record.key(key);
+                }
+                producer
+                    .send(record, Duration::from_secs(30))
+                    .await
+                    .map_err(|(err, _)| {
+                        DataFusionError::External(
+                            format!(
+                                "Failed to dead letter offset {offset} of partition {partition} \
+                                 to {topic}: {err}"
+                            )
+                            .into(),
+                        )
+                    })?;
+                Ok(())
+            }
+            (DecodeErrorPolicy::Skip, _) => {
+                warn!(
+                    "Skipping offset {offset} of partition {partition} of {}: {error}",
+                    self.source_topic
+                );
+                Ok(())
+            }
+            _ => Err(error.context(format!(
+                "Failed to decode offset {offset} of partition {partition} of {}",
+                self.source_topic
+            ))),
+        }
+    }
+}
+
+fn dead_letter_headers(
+    source_topic: &str,
+    (partition, offset, timestamp, ..): &RawMessage,
+    error: &DataFusionError,
+) -> OwnedHeaders {
+    [
+        (DEAD_LETTER_ERROR_HEADER, error.to_string()),
+        (DEAD_LETTER_TOPIC_HEADER, source_topic.to_string()),
+        (DEAD_LETTER_PARTITION_HEADER, partition.to_string()),
+        (DEAD_LETTER_OFFSET_HEADER, offset.to_string()),
+        (DEAD_LETTER_TIMESTAMP_HEADER, timestamp.to_string()),
+    ]
+    .into_iter()
+    .fold(OwnedHeaders::new(), |headers, (key, value)| {
+        headers.insert(Header {
+            key,
+            value: Some(value.as_bytes()),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rdkafka::message::Headers;
+
+    fn failures(policy: DecodeErrorPolicy) -> DecodeFailures {
+        DecodeFailures {
+            policy,
+            source_topic: "orders".to_string(),
+            producer: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn handle_undecodable_messages() {
+        let message: RawMessage = (2, 41, 1_000, None, b"{\"id\": ".to_vec());
+        let error = || DataFusionError::Execution("EOF while parsing".to_string());
+
+        assert!(failures(DecodeErrorPolicy::Skip)
+            .handle(&message, error())
+            .await
+            .is_ok());
+        let failed = failures(DecodeErrorPolicy::Fail)
+            .handle(&message, error())
+            .await
+            .unwrap_err();
+        assert!(failed
+            .to_string()
+            .contains("Failed to decode offset 41 of partition 2 of orders"));
+
+        let headers = dead_letter_headers("orders", &message, &error());
+        let header = |key: &str| {
+            headers
+                .iter()
+                .find(|header| header.key == key)
+                .and_then(|header| header.value)
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+        assert_eq!(
+            header(DEAD_LETTER_ERROR_HEADER).as_deref(),
+            Some("Execution error: EOF while parsing")
+        );
+        assert_eq!(header(DEAD_LETTER_TOPIC_HEADER).as_deref(), Some("orders"));
+        assert_eq!(header(DEAD_LETTER_PARTITION_HEADER).as_deref(), Some("2"));
+        assert_eq!(header(DEAD_LETTER_OFFSET_HEADER).as_deref(), Some("41"));
+    }
+}
@@ -0,0 +1,114 @@
+use datafusion::common::{plan_err, Result};
+
+use super::{ConnectionOpts, KafkaTopicBuilder};
+
+/// Port of the Kafka endpoint of an Event Hubs namespace
+const KAFKA_PORT: u16 = 9093;
+
+/// Connection to an Azure Event Hubs namespace through its Kafka compatible endpoint,
+/// authenticated with a shared access connection string.
+///
+/// Event hubs are read and written like Kafka topics, one partition of the hub per Kafka
+/// partition. Read positions are kept in the engine's checkpoints along with those of any
+/// other Kafka source, Event Hubs checkpoint stores and consumer groups aren't involved.
+#[derive(Debug, Clone)]
+pub struct EventHubsConnection {
+    namespace_host: String,
+    connection_string: String,
+    event_hub: Option<String>,
+}
+
+impl EventHubsConnection {
+    /// Accepts the connection strings the Azure portal lists under shared access policies,
+    /// of the namespace or of a single event hub
+    pub fn from_connection_string(connection_string: &str) -> Result<Self> {
+        let mut endpoint = None;
+        let mut key_name = None;
+        let mut event_hub = None;
+        for part in connection_string.trim().split(';') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "Endpoint" => endpoint = Some(value.trim()),
+                "SharedAccessKeyName" => key_name = Some(value.trim()),
+                "EntityPath" => event_hub = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+
+        let Some(endpoint) = endpoint else {
+            return plan_err!("Event Hubs connection string has no Endpoint");
+        };
+        if key_name.is_none() {
+            return plan_err!("Event Hubs connection string has no SharedAccessKeyName");
+        }
+        let namespace_host = endpoint
+            .trim_start_matches("sb://")
+            .trim_end_matches('/')
+            .to_string();
+        if namespace_host.is_empty() {
+            return plan_err!("Event Hubs connection string has an empty Endpoint");
+        }
+        Ok(Self {
+            namespace_host,
+            connection_string: connection_string.trim().to_string(),
+            event_hub,
+        })
+    }
+
+    pub fn bootstrap_servers(&self) -> String {
+        format!("{}:{KAFKA_PORT}", self.namespace_host)
+    }
+
+    /// The event hub a hub level connection string is scoped to
+    pub fn event_hub(&self) -> Option<&str> {
+        self.event_hub.as_deref()
+    }
+
+    /// Options authenticating Kafka clients, to pass to
+    /// [`KafkaTopicBuilder::build_reader`] and [`KafkaTopicBuilder::build_writer`]
+    pub fn connection_opts(&self) -> ConnectionOpts {
+        ConnectionOpts::from([
+            ("security.protocol".to_string(), "SASL_SSL".to_string()),
+            ("sasl.mechanism".to_string(), "PLAIN".to_string()),
+            ("sasl.username".to_string(), "$ConnectionString".to_string()),
+            ("sasl.password".to_string(), self.connection_string.clone()),
+        ])
+    }
+
+    /// A topic builder for the namespace, with the topic set to the event hub of a hub level
+    /// connection string
+    pub fn topic_builder(&self) -> KafkaTopicBuilder {
+        let mut builder = KafkaTopicBuilder::new(self.bootstrap_servers());
+        if let Some(event_hub) = &self.event_hub {
+            builder.with_topic(event_hub.clone());
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_connection_strings() {
+        let connection = EventHubsConnection::from_connection_string(
+            "Endpoint=sb://telemetry.servicebus.windows.net/;SharedAccessKeyName=reader;\
+             SharedAccessKey=c2VjcmV0;EntityPath=readings",
+        )
+        .unwrap();
+        assert_eq!(
+            connection.bootstrap_servers(),
+            "telemetry.servicebus.windows.net:9093"
+        );
+        assert_eq!(connection.event_hub(), Some("readings"));
+        assert_eq!(
+            connection.connection_opts()["sasl.username"],
+            "$ConnectionString"
+        );
+
+        assert!(EventHubsConnection::from_connection_string("SharedAccessKeyName=reader").is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use datafusion::common::{DataFusionError, Result};
+use futures::StreamExt;
+use log::info;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::Message;
+use rdkafka::{Offset, Timestamp, TopicPartitionList};
+use tokio::time::{timeout_at, Instant};
+
+use crate::utils::job::JobIdentity;
+
+use super::KafkaReadConfig;
+
+/// Partition, offset, timestamp, key and payload of a message read from a topic
+pub(crate) type RawMessage = (i32, i64, i64, Option<Vec<u8>>, Vec<u8>);
+
+/// How long the fresh lane waits for a further message before the backlog gets its turn
+const FRESH_IDLE: Duration = Duration::from_millis(50);
+
+/// Read what `consumer` delivers within `window`, or until nothing arrived for `idle`
+pub(crate) async fn read_messages(
+    consumer: &StreamConsumer,
+    window: Duration,
+    idle: Option<Duration>,
+) -> Result<Vec<RawMessage>> {
+    let deadline = Instant::now() + window;
+    let mut stream = consumer.stream();
+    let mut messages = vec![];
+    loop {
+        let wait_until = match idle {
+            Some(idle) => deadline.min(Instant::now() + idle),
+            None => deadline,
+        };
+        let message = match timeout_at(wait_until, stream.next()).await {
+            Ok(Some(message)) => message.map_err(|e| DataFusionError::External(Box::new(e)))?,
+            Ok(None) | Err(_) => break,
+        };
+        let timestamp = match message.timestamp() {
+            Timestamp::NotAvailable => -1_i64,
+            Timestamp::CreateTime(ts) => ts,
+            Timestamp::LogAppendTime(ts) => ts,
+        };
+        let key = message.key().map(<[u8]>::to_vec);
+        let payload = message
+            .payload()
+            .ok_or_else(|| DataFusionError::Execution("Message payload is empty".to_string()))?;
+        messages.push((
+            message.partition(),
+            message.offset(),
+            timestamp,
+            key,
+            payload.to_vec(),
+        ));
+    }
+    Ok(messages)
+}
+
+/// Reads the records produced after a source started ahead of its backlog, so that results
+/// on recent data stay fresh while a long reprocessing catches up in the background.
+///
+/// A second consumer starts at the end of every partition. Each read gives it its share of
+/// the read window, less once it runs out of messages, and the backlog consumer the rest. A
+/// partition has caught up once its backlog reaches the offset the lane started at, after
+/// that the lane is all that's read.
+///
+/// Fresh and backlog rows go out in the same batches and the watermark keeps following the
+/// backlog, so windows on recent data stay open until it caught up. Their early results are
+/// available with [`DataStream::emit_on_schedule`](crate::datastream::DataStream::emit_on_schedule).
+pub(crate) struct FreshLane {
+    pub(crate) consumer: StreamConsumer,
+    topic: String,
+    share: f64,
+    progress: LaneProgress,
+}
+
+/// Which partitions of a [`FreshLane`] the backlog caught up on
+#[derive(Debug, Default)]
+struct LaneProgress {
+    /// First offset of each partition read by the lane, its backlog ends before
+    start_offsets: HashMap<i32, i64>,
+    caught_up: HashSet<i32>,
+}
+
+impl LaneProgress {
+    fn is_caught_up(&self) -> bool {
+        self.caught_up.len() == self.start_offsets.len()
+    }
+
+    fn is_backlog(&self, partition: i32, offset: i64) -> bool {
+        self.caught_up.contains(&partition)
+            || self
+                .start_offsets
+                .get(&partition)
+                .map_or(true, |start| offset < *start)
+    }
+
+    /// Whether the backlog consumer reading `offset` of `partition` read a record the lane
+    /// read already, which means the partition caught up
+    fn read_by_lane(&mut self, partition: i32, offset: i64) -> bool {
+        match self.start_offsets.get(&partition) {
+            Some(start) if offset >= *start => {
+                self.caught_up.insert(partition);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FreshLane {
+    /// Start reading `partitions` from their current end. `percent` is the share of every
+    /// read window the lane gets while the backlog is read.
+    pub(crate) fn try_new(
+        config: &KafkaReadConfig,
+        job: Option<&JobIdentity>,
+        partitions: &[i32],
+        percent: u64,
+    ) -> Result<Self> {
+        let consumer = config.make_consumer(job)?;
+        let mut assignment = TopicPartitionList::new();
+        let mut start_offsets = HashMap::new();
+        for &partition in partitions {
+            let (_, high) = consumer
+                .fetch_watermarks(&config.topic, partition, Duration::from_secs(5))
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            assignment
+                .add_partition_offset(&config.topic, partition, Offset::Offset(high))
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            start_offsets.insert(partition, high);
+        }
+        consumer
+            .assign(&assignment)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        Ok(Self {
+            consumer,
+            topic: config.topic.clone(),
+            share: percent.min(100) as f64 / 100.0,
+            progress: LaneProgress {
+                start_offsets,
+                caught_up: HashSet::new(),
+            },
+        })
+    }
+
+    pub(crate) fn is_caught_up(&self) -> bool {
+        self.progress.is_caught_up()
+    }
+
+    /// Whether the message at `offset` of `partition` belongs to the backlog, the part of the
+    /// topic event time progress is measured on
+    pub(crate) fn is_backlog(&self, partition: i32, offset: i64) -> bool {
+        self.progress.is_backlog(partition, offset)
+    }
+
+    /// Read the fresh records first and the backlog of `backlog` for the rest of `window`.
+    /// Records the backlog consumer reads past the start of the lane were read by the lane
+    /// already and are dropped.
+    pub(crate) async fn read(
+        &mut self,
+        backlog: &StreamConsumer,
+        window: Duration,
+    ) -> Result<Vec<RawMessage>> {
+        if self.is_caught_up() {
+            return read_messages(&self.consumer, window, None).await;
+        }
+
+        let started = Instant::now();
+        let mut messages =
+            read_messages(&self.consumer, window.mul_f64(self.share), Some(FRESH_IDLE)).await?;
+        let remaining = window.saturating_sub(started.elapsed());
+        for message in read_messages(backlog, remaining, None).await? {
+            let (partition, offset, ..) = message;
+            if !self.progress.read_by_lane(partition, offset) {
+                messages.push(message);
+            }
+        }
+
+        // Partitions without new records only show they caught up in their position
+        if let Ok(positions) = backlog.position() {
+            for element in positions.elements_for_topic(&self.topic) {
+                if let Offset::Offset(position) = element.offset() {
+                    self.progress.read_by_lane(element.partition(), position);
+                }
+            }
+        }
+        if self.is_caught_up() {
+            info!("Backlog of {} caught up with the fresh records", self.topic);
+            let _ = backlog.unassign();
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switch_to_the_lane_once_caught_up() {
+        let mut progress = LaneProgress {
+            start_offsets: HashMap::from([(0, 100), (1, 50)]),
+            caught_up: HashSet::new(),
+        };
+        assert!(progress.is_backlog(0, 99));
+        assert!(!progress.is_backlog(0, 100));
+        // Partitions the lane doesn't read are all backlog
+        assert!(progress.is_backlog(2, 1_000));
+
+        assert!(!progress.read_by_lane(0, 99));
+        assert!(progress.read_by_lane(0, 100));
+        assert!(!progress.is_caught_up());
+        // Records of a caught up partition count as backlog again
+        assert!(progress.is_backlog(0, 150));
+        assert!(!progress.is_backlog(1, 60));
+
+        // A backlog position past the start of the lane without reading a record
+        assert!(progress.read_by_lane(1, 50));
+        assert!(progress.is_caught_up());
+    }
+}
@@ -2,15 +2,28 @@ use std::collections::HashMap;
 use std::str::FromStr;
 use std::{sync::Arc, time::Duration};
 
-use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use arrow_schema::{DataType, Schema, SchemaRef};
 
 use datafusion::common::{plan_err, DataFusionError, Result};
 use datafusion::logical_expr::Expr;
 
+use crate::datasource::schema_registry::{
+    avro_schema_from_arrow, check_compatible, register_compatible, CompatibilityMode,
+    SchemaRegistry, SubjectSchema,
+};
+use crate::physical_plan::utils::metadata::{
+    stream_metadata_field, stream_metadata_field_with_watermark,
+};
 use crate::physical_plan::utils::time::TimestampUnit;
+use crate::physical_plan::utils::watermark::WatermarkStrategy;
 use crate::utils::arrow_helpers::infer_arrow_schema_from_json_value;
+use crate::utils::job::JobIdentity;
+use crate::utils::json_format::{BinaryFormat, JsonFormatOptions};
+use crate::utils::schema_drift::SchemaDriftMonitor;
 
-use super::{TopicReader, TopicWriter};
+use super::{
+    DecodeErrorPolicy, InvalidUtf8, MessageFormat, StartingOffsets, TopicReader, TopicWriter,
+};
 
 use rdkafka::consumer::Consumer;
 use rdkafka::consumer::StreamConsumer;
@@ -19,8 +32,13 @@ use rdkafka::ClientConfig;
 
 pub type ConnectionOpts = HashMap<String, String>;
 
+/// Messages a writer has waiting on delivery by default, librdkafka's default queue size
+pub const DEFAULT_MAX_IN_FLIGHT_REQUESTS: usize = 100_000;
+/// Bytes a writer has waiting on delivery by default
+pub const DEFAULT_MAX_BUFFER_BYTES: usize = 64 * 1024 * 1024;
+
 /// The configuration for a [`StreamTable`]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct KafkaReadConfig {
     pub topic: String,
     pub bootstrap_servers: String,
@@ -29,21 +47,46 @@ pub struct KafkaReadConfig {
     pub schema: SchemaRef,
 
     pub encoding: StreamEncoding,
+    pub message_format: MessageFormat,
+    pub json_format: JsonFormatOptions,
     pub order: Vec<Vec<Expr>>,
     pub partition_count: i32,
     pub timestamp_column: String,
     pub timestamp_unit: TimestampUnit,
+    pub watermark_strategy: WatermarkStrategy,
+    pub drift_monitor: Option<Arc<SchemaDriftMonitor>>,
+    /// Read just the messages at these `(partition, offset)` pairs and end the stream, see
+    /// [`TopicReader::replay`]
+    pub replay_offsets: Option<Vec<(i32, i64)>>,
+    /// What to do with messages whose payload can't be decoded
+    pub decode_error_policy: DecodeErrorPolicy,
+    /// How JSON messages that aren't valid UTF-8 are decoded
+    pub invalid_utf8: InvalidUtf8,
+    /// Where reading the partitions starts, replays start at the offsets they replay
+    pub starting_offsets: StartingOffsets,
+    /// Commit the offsets read to the consumer group after each checkpoint, or after each
+    /// batch without checkpointing
+    pub commit_offsets: bool,
+    /// Built for a dry run without asking the brokers, `partition_count` is a placeholder
+    pub dry_run: bool,
 
     pub kafka_connection_opts: ConnectionOpts,
 }
 
 impl KafkaReadConfig {
-    pub fn make_consumer(&self) -> Result<StreamConsumer> {
+    /// Options given with the topic take precedence over the client id derived from `job`
+    pub fn make_consumer(&self, job: Option<&JobIdentity>) -> Result<StreamConsumer> {
         let mut client_config = ClientConfig::new();
 
         client_config
             .set("bootstrap.servers", self.bootstrap_servers.to_string())
             .set("enable.auto.commit", "false");
+        if let Some(job) = job {
+            client_config.set(
+                "client.id",
+                job.client_id(&format!("source-{}", self.topic)),
+            );
+        }
 
         for (key, value) in self.kafka_connection_opts.clone().into_iter() {
             client_config.set(key, value);
@@ -52,6 +95,38 @@ impl KafkaReadConfig {
         let consumer: StreamConsumer = client_config.create().expect("Consumer creation failed");
         Ok(consumer)
     }
+
+    /// Check the configuration for mistakes that would otherwise only surface once the stream
+    /// starts consuming
+    pub fn validate(&self) -> Result<()> {
+        validate_connection(&self.topic, &self.bootstrap_servers)?;
+        if !self.dry_run {
+            validate_partition_count(&self.topic, self.partition_count)?;
+        }
+
+        let field = match self.original_schema.field_with_name(&self.timestamp_column) {
+            Ok(field) => field,
+            Err(_) => {
+                return plan_err!(
+                    "Timestamp column {} not found in the schema of topic {}",
+                    self.timestamp_column,
+                    self.topic
+                )
+            }
+        };
+
+        match (&self.timestamp_unit, field.data_type()) {
+            (TimestampUnit::StringIso8601(_), DataType::Utf8 | DataType::LargeUtf8) => Ok(()),
+            (TimestampUnit::Int64Millis | TimestampUnit::Int64Seconds, DataType::Int64) => Ok(()),
+            (unit, data_type) => plan_err!(
+                "Timestamp column {} of topic {} has type {} which cannot be read as {:?}",
+                self.timestamp_column,
+                self.topic,
+                data_type,
+                unit
+            ),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -62,27 +137,81 @@ pub struct KafkaWriteConfig {
     pub schema: SchemaRef,
 
     pub encoding: StreamEncoding,
+    pub json_format: JsonFormatOptions,
     pub partition_count: i32,
     pub timestamp_column: String,
     pub timestamp_unit: TimestampUnit,
 
+    /// Column whose value keys the message of every row
+    pub key_column: Option<String>,
+    /// Integer column with the partition of every row, otherwise the producer's partitioner
+    /// picks one by key
+    pub partition_column: Option<String>,
+    /// Columns whose values are sent as headers of the same name
+    pub header_columns: Vec<String>,
+    /// Headers sent with every message
+    pub headers: Vec<(String, String)>,
+    /// Messages that may wait on delivery before the sink waits for the oldest ones
+    pub max_in_flight_requests: usize,
+    /// Bytes of keys and payloads that may wait on delivery
+    pub max_buffer_bytes: usize,
+
     pub kafka_connection_opts: ConnectionOpts,
 }
 
 impl KafkaWriteConfig {
-    pub fn make_producer(&self) -> Result<FutureProducer> {
+    /// Options given with the topic take precedence over the client id derived from `job`
+    pub fn make_producer(&self, job: Option<&JobIdentity>) -> Result<FutureProducer> {
         let mut client_config = ClientConfig::new();
 
         client_config.set("bootstrap.servers", self.bootstrap_servers.to_string());
         client_config.set("message.timeout.ms", "60000");
+        if let Some(job) = job {
+            client_config.set("client.id", job.client_id(&format!("sink-{}", self.topic)));
+        }
 
         for (key, value) in self.kafka_connection_opts.clone().into_iter() {
             client_config.set(key, value);
         }
 
-        let producer: FutureProducer = client_config.create().expect("Consumer creation failed");
+        let producer: FutureProducer = client_config
+            .create()
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
         Ok(producer)
     }
+
+    /// Check the configuration for mistakes that would otherwise only surface once the stream
+    /// starts producing
+    pub fn validate(&self) -> Result<()> {
+        validate_connection(&self.topic, &self.bootstrap_servers)?;
+        validate_partition_count(&self.topic, self.partition_count)?;
+        for column in self.record_columns() {
+            if self.schema.field_with_name(column).is_err() {
+                return plan_err!(
+                    "Column {column} not found in the schema of topic {}",
+                    self.topic
+                );
+            }
+        }
+        if let Some(column) = &self.partition_column {
+            let data_type = self.schema.field_with_name(column)?.data_type();
+            if !data_type.is_integer() {
+                return plan_err!(
+                    "Partition column {column} of topic {} has non integer type {data_type}",
+                    self.topic
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The key, partition and header columns, which are left out of the payload
+    pub fn record_columns(&self) -> impl Iterator<Item = &String> {
+        self.key_column
+            .iter()
+            .chain(&self.partition_column)
+            .chain(&self.header_columns)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -92,11 +221,31 @@ pub struct KafkaTopicBuilder {
 
     schema: Option<SchemaRef>,
     infer_schema: bool,
+    dictionary_columns: Vec<String>,
 
     timestamp_column: Option<String>,
     timestamp_unit: Option<TimestampUnit>,
+    watermark_strategy: WatermarkStrategy,
 
     encoding: Option<StreamEncoding>,
+    message_format: Option<MessageFormat>,
+    json_format: JsonFormatOptions,
+
+    schema_registry: Option<(Arc<dyn SchemaRegistry>, CompatibilityMode)>,
+    drift_monitor: Option<Arc<SchemaDriftMonitor>>,
+    decode_error_policy: DecodeErrorPolicy,
+    invalid_utf8: InvalidUtf8,
+    starting_offsets: StartingOffsets,
+    commit_offsets: bool,
+
+    key_column: Option<String>,
+    partition_column: Option<String>,
+    header_columns: Vec<String>,
+    headers: Vec<(String, String)>,
+    max_in_flight_requests: usize,
+    max_buffer_bytes: usize,
+
+    dry_run: bool,
 }
 
 impl KafkaTopicBuilder {
@@ -107,11 +256,31 @@ impl KafkaTopicBuilder {
 
             schema: None,
             infer_schema: false,
+            dictionary_columns: vec![],
 
             timestamp_column: None,
             timestamp_unit: None,
+            watermark_strategy: WatermarkStrategy::default(),
 
             encoding: None,
+            message_format: None,
+            json_format: JsonFormatOptions::default(),
+
+            schema_registry: None,
+            drift_monitor: None,
+            decode_error_policy: DecodeErrorPolicy::default(),
+            invalid_utf8: InvalidUtf8::default(),
+            starting_offsets: StartingOffsets::default(),
+            commit_offsets: false,
+
+            key_column: None,
+            partition_column: None,
+            header_columns: vec![],
+            headers: vec![],
+            max_in_flight_requests: DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+            max_buffer_bytes: DEFAULT_MAX_BUFFER_BYTES,
+
+            dry_run: false,
         }
     }
 
@@ -145,6 +314,15 @@ impl KafkaTopicBuilder {
         Ok(self)
     }
 
+    /// Read the string `columns` dictionary encoded. Low cardinality columns such as an event
+    /// type or a country then hold every distinct value once per batch, and are cheaper to
+    /// group and join by.
+    pub fn with_dictionary_columns(&mut self, columns: &[&str]) -> &mut Self {
+        self.dictionary_columns
+            .extend(columns.iter().map(|column| column.to_string()));
+        self
+    }
+
     pub fn with_timestamp(
         &mut self,
         timestamp_column: String,
@@ -155,37 +333,185 @@ impl KafkaTopicBuilder {
         self
     }
 
+    /// How far the watermark of each partition trails the event times in the timestamp
+    /// column. By default every batch advances it to its oldest row.
+    pub fn with_watermark_strategy(&mut self, strategy: WatermarkStrategy) -> &mut Self {
+        self.watermark_strategy = strategy;
+        self
+    }
+
     pub fn with_encoding(&mut self, encoding: &str) -> Result<&mut Self> {
         self.encoding = Some(StreamEncoding::from_str(encoding)?);
         Ok(self)
     }
 
-    fn create_canonical_schema(&self) -> Result<SchemaRef> {
+    /// How the payloads of a read topic are decoded, also setting the encoding. JSON encoded
+    /// topics are read as JSON without one, others need it for their schemas. The protobuf
+    /// message of a written topic is registered with [`Self::with_schema_registry`].
+    pub fn with_message_format(&mut self, message_format: MessageFormat) -> &mut Self {
+        self.encoding = Some(message_format.encoding());
+        self.message_format = Some(message_format);
+        self
+    }
+
+    /// How timestamps, decimals and nulls are represented when the topic is JSON encoded
+    pub fn with_json_format(&mut self, json_format: JsonFormatOptions) -> &mut Self {
+        self.json_format = json_format;
+        self
+    }
+
+    /// Register the schema of a written topic under its `<topic>-value` subject. Building the
+    /// writer fails if the schema isn't compatible with the registered versions under `mode`.
+    /// Avro encoded topics register the schema of their rows and Protobuf encoded ones the
+    /// file of their protobuf message format. JSON encoded topics have no registered schema
+    /// and aren't checked.
+    pub fn with_schema_registry(
+        &mut self,
+        registry: Arc<dyn SchemaRegistry>,
+        mode: CompatibilityMode,
+    ) -> &mut Self {
+        self.schema_registry = Some((registry, mode));
+        self
+    }
+
+    /// Report fields appearing in, disappearing from or changing type in the messages of a
+    /// read topic, before they are decoded into its schema
+    pub fn with_drift_monitor(&mut self, monitor: Arc<SchemaDriftMonitor>) -> &mut Self {
+        self.drift_monitor = Some(monitor);
+        self
+    }
+
+    /// What the reader does with messages whose payload can't be decoded, by default the
+    /// stream fails
+    pub fn with_decode_error_policy(&mut self, policy: DecodeErrorPolicy) -> &mut Self {
+        self.decode_error_policy = policy;
+        self
+    }
+
+    /// How the reader decodes JSON messages that aren't valid UTF-8, by default they fail to
+    /// decode. A [`InvalidUtf8::Capture`] column must be a nullable binary column of the schema.
+    pub fn with_invalid_utf8(&mut self, invalid_utf8: InvalidUtf8) -> &mut Self {
+        self.invalid_utf8 = invalid_utf8;
+        self
+    }
+
+    /// Where the reader starts reading the partitions, by default at the offsets committed by
+    /// the consumer group set as `group.id`
+    pub fn with_starting_offsets(&mut self, starting_offsets: StartingOffsets) -> &mut Self {
+        self.starting_offsets = starting_offsets;
+        self
+    }
+
+    /// Commit the offsets the reader read to its consumer group once they are checkpointed,
+    /// so that a restarted pipeline resumes where it left off with
+    /// [`StartingOffsets::Committed`]. Needs a `group.id`.
+    pub fn with_commit_offsets(&mut self, commit_offsets: bool) -> &mut Self {
+        self.commit_offsets = commit_offsets;
+        self
+    }
+
+    /// Key the message of every written row by the value of `column`, as bytes for binary
+    /// columns and as a string otherwise. The column is left out of the payload.
+    pub fn with_key_column(&mut self, column: &str) -> &mut Self {
+        self.key_column = Some(column.to_string());
+        self
+    }
+
+    /// Write every row to the partition in the integer `column`, left out of the payload
+    pub fn with_partition_column(&mut self, column: &str) -> &mut Self {
+        self.partition_column = Some(column.to_string());
+        self
+    }
+
+    /// Send the value of `column` as a header of the same name with every written row, left
+    /// out of the payload
+    pub fn with_header_column(&mut self, column: &str) -> &mut Self {
+        self.header_columns.push(column.to_string());
+        self
+    }
+
+    /// Send a header with every written row
+    pub fn with_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Bound the messages the writer has waiting on delivery, by count and by the bytes of
+    /// their keys and payloads. Once either is reached the writer waits for the oldest
+    /// deliveries before sending more.
+    pub fn with_max_in_flight(&mut self, requests: usize, bytes: usize) -> &mut Self {
+        self.max_in_flight_requests = requests.max(1);
+        self.max_buffer_bytes = bytes;
+        self
+    }
+
+    /// Build without connecting to the brokers, for pipelines planned with the `dry_run`
+    /// option so they can be validated where the brokers aren't reachable. Readers are planned
+    /// with a single partition and writers only check their schema against the schema
+    /// registry instead of registering it. Streams built this way can't be run.
+    pub fn with_dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// The schema of the topic with the dictionary columns encoded
+    fn read_schema(&self) -> Result<SchemaRef> {
         let schema = self
             .schema
             .as_ref()
             .ok_or_else(|| create_error("Schema required"))?
             .clone();
+        if self.dictionary_columns.is_empty() {
+            return Ok(schema);
+        }
+
+        let mut fields = schema.fields().to_vec();
+        for column in &self.dictionary_columns {
+            let Some((index, field)) = schema.column_with_name(column) else {
+                return plan_err!("Dictionary column {column} not found in the schema");
+            };
+            let value_type = match field.data_type() {
+                DataType::Utf8 | DataType::LargeUtf8 => field.data_type().clone(),
+                DataType::Dictionary(_, _) => continue,
+                other => {
+                    return plan_err!(
+                        "Only string columns can be dictionary encoded, {column} is {other}"
+                    )
+                }
+            };
+            fields[index] = Arc::new(field.clone().with_data_type(DataType::Dictionary(
+                Box::new(DataType::Int32),
+                Box::new(value_type),
+            )));
+        }
+        Ok(Arc::new(Schema::new_with_metadata(
+            fields,
+            schema.metadata().clone(),
+        )))
+    }
+
+    /// The file of the protobuf message format, registered for Protobuf encoded topics
+    fn protobuf_schema(&self, topic: &str) -> Result<SubjectSchema> {
+        match &self.message_format {
+            #[cfg(feature = "protobuf")]
+            Some(MessageFormat::Protobuf { message, .. }) => Ok(SubjectSchema::protobuf(message)),
+            _ => plan_err!(
+                "Registering the schema of Protobuf encoded topic {topic} needs a protobuf \
+                 message format"
+            ),
+        }
+    }
+
+    fn create_canonical_schema(&self) -> Result<SchemaRef> {
+        let schema = self.read_schema()?;
 
         let mut fields = schema.fields().to_vec();
 
         // Add a new column to the dataset that should mirror the occurred_at_ms field
-        let struct_fields = vec![
-            Arc::new(Field::new("barrier_batch", DataType::Utf8, false)),
-            Arc::new(Field::new(
-                String::from("canonical_timestamp"),
-                DataType::Timestamp(TimeUnit::Millisecond, None),
-                true,
-            )),
-        ];
-        fields.insert(
-            fields.len(),
-            Arc::new(Field::new(
-                String::from("_streaming_internal_metadata"),
-                DataType::Struct(Fields::from(struct_fields)),
-                true,
-            )),
-        );
+        fields.push(Arc::new(match self.watermark_strategy {
+            WatermarkStrategy::BatchMinimum => stream_metadata_field(),
+            _ => stream_metadata_field_with_watermark(),
+        }));
 
         Ok(Arc::new(Schema::new(fields)))
     }
@@ -197,11 +523,7 @@ impl KafkaTopicBuilder {
             .ok_or_else(|| create_error("topic required"))?
             .clone();
 
-        let original_schema = self
-            .schema
-            .as_ref()
-            .ok_or_else(|| create_error("Schema required"))?
-            .clone();
+        let original_schema = self.read_schema()?;
 
         let canonical_schema = self.create_canonical_schema()?;
 
@@ -210,6 +532,30 @@ impl KafkaTopicBuilder {
             .as_ref()
             .ok_or_else(|| create_error("encoding required"))?;
 
+        let message_format = match (&self.message_format, encoding) {
+            (Some(message_format), _) => message_format.clone(),
+            (None, StreamEncoding::Json) => MessageFormat::Json,
+            (None, encoding) => {
+                return plan_err!(
+                "Reading {encoding:?} encoded topic {topic} needs a message format with its schema"
+            )
+            }
+        };
+
+        if let InvalidUtf8::Capture { column } = &self.invalid_utf8 {
+            let captured = original_schema.field_with_name(column).ok();
+            match captured.map(|field| (field.data_type(), field.is_nullable())) {
+                Some((DataType::Binary | DataType::LargeBinary, true))
+                    if self.json_format.binary != BinaryFormat::Skip => {}
+                _ => {
+                    return plan_err!(
+                        "Invalid UTF-8 of topic {topic} can only be captured in a nullable binary \
+                         column of its schema read with a binary format, got {column}"
+                    )
+                }
+            }
+        }
+
         let timestamp_column = self
             .timestamp_column
             .as_ref()
@@ -230,8 +576,19 @@ impl KafkaTopicBuilder {
         //@todo
         let order = vec![];
 
-        let partition_count =
-            get_topic_partition_count(self.bootstrap_servers.clone(), topic.clone())?;
+        if self.commit_offsets && !kafka_connection_opts.contains_key("group.id") {
+            return plan_err!("Committing the offsets read from topic {topic} needs a group.id");
+        }
+        validate_connection(&topic, &self.bootstrap_servers)?;
+
+        let partition_count = if self.dry_run {
+            1
+        } else {
+            let partition_count =
+                get_topic_partition_count(&self.bootstrap_servers, &topic, &kafka_connection_opts)?;
+            self.starting_offsets.validate(&topic, partition_count)?;
+            partition_count
+        };
 
         let config = KafkaReadConfig {
             topic,
@@ -240,11 +597,21 @@ impl KafkaTopicBuilder {
             original_schema,
             schema: canonical_schema,
             encoding,
+            message_format,
+            json_format: self.json_format,
             order,
             partition_count,
 
             timestamp_unit,
             timestamp_column,
+            watermark_strategy: self.watermark_strategy,
+            drift_monitor: self.drift_monitor.clone(),
+            replay_offsets: None,
+            decode_error_policy: self.decode_error_policy.clone(),
+            invalid_utf8: self.invalid_utf8.clone(),
+            starting_offsets: self.starting_offsets.clone(),
+            commit_offsets: self.commit_offsets,
+            dry_run: self.dry_run,
 
             kafka_connection_opts,
         };
@@ -287,8 +654,32 @@ impl KafkaTopicBuilder {
             kafka_connection_opts.insert(key.clone(), value.clone());
         }
 
-        let partition_count =
-            get_topic_partition_count(self.bootstrap_servers.clone(), topic.clone())?;
+        validate_connection(&topic, &self.bootstrap_servers)?;
+
+        if let Some((registry, mode)) = &self.schema_registry {
+            let subject_schema = match encoding {
+                StreamEncoding::Json => None,
+                StreamEncoding::Avro => Some(avro_schema_from_arrow(&schema, &topic)?.into()),
+                StreamEncoding::Protobuf => Some(self.protobuf_schema(&topic)?),
+            };
+            let subject = format!("{topic}-value");
+            match subject_schema {
+                Some(subject_schema) if self.dry_run => {
+                    check_compatible(registry.as_ref(), &subject, &subject_schema, *mode).await?
+                }
+                Some(subject_schema) => {
+                    register_compatible(registry.as_ref(), &subject, &subject_schema, *mode)
+                        .await?;
+                }
+                None => {}
+            }
+        }
+
+        let partition_count = if self.dry_run {
+            1
+        } else {
+            get_topic_partition_count(&self.bootstrap_servers, &topic, &kafka_connection_opts)?
+        };
 
         let config = KafkaWriteConfig {
             topic,
@@ -296,10 +687,19 @@ impl KafkaTopicBuilder {
 
             schema,
             encoding,
+            json_format: self.json_format,
             partition_count,
 
             timestamp_unit,
             timestamp_column,
+
+            key_column: self.key_column.clone(),
+            partition_column: self.partition_column.clone(),
+            header_columns: self.header_columns.clone(),
+            headers: self.headers.clone(),
+            max_in_flight_requests: self.max_in_flight_requests,
+            max_buffer_bytes: self.max_buffer_bytes,
+
             kafka_connection_opts,
         };
 
@@ -312,6 +712,7 @@ impl KafkaTopicBuilder {
 pub enum StreamEncoding {
     Avro,
     Json,
+    Protobuf,
 }
 
 impl FromStr for StreamEncoding {
@@ -321,6 +722,7 @@ impl FromStr for StreamEncoding {
         match s.to_ascii_lowercase().as_str() {
             "avro" => Ok(Self::Avro),
             "json" => Ok(Self::Json),
+            "protobuf" => Ok(Self::Protobuf),
             _ => plan_err!("Unrecognised StreamEncoding {}", s),
         }
     }
@@ -333,14 +735,39 @@ fn create_error(msg: &str) -> DataFusionError {
     )))
 }
 
-fn get_topic_partition_count(bootstrap_servers: String, topic: String) -> Result<i32> {
+fn validate_connection(topic: &str, bootstrap_servers: &str) -> Result<()> {
+    if topic.trim().is_empty() {
+        return plan_err!("Kafka topic name must not be empty");
+    }
+    if bootstrap_servers.trim().is_empty() {
+        return plan_err!("No bootstrap servers configured for topic {}", topic);
+    }
+    Ok(())
+}
+
+fn validate_partition_count(topic: &str, partition_count: i32) -> Result<()> {
+    if partition_count <= 0 {
+        return plan_err!("Topic {} does not exist or has no partitions", topic);
+    }
+    Ok(())
+}
+
+/// The metadata request authenticates with `opts`, like the consumers and producers later do
+fn get_topic_partition_count(
+    bootstrap_servers: &str,
+    topic: &str,
+    opts: &ConnectionOpts,
+) -> Result<i32> {
     let mut client_config = ClientConfig::new();
-    client_config.set("bootstrap.servers", bootstrap_servers.to_string());
+    client_config.set("bootstrap.servers", bootstrap_servers);
+    for (key, value) in opts {
+        client_config.set(key, value);
+    }
 
     let consumer: StreamConsumer = client_config.create().expect("Consumer creation failed");
 
     let data = consumer
-        .fetch_metadata(Some(topic.as_str()), Duration::from_millis(5_000))
+        .fetch_metadata(Some(topic), Duration::from_millis(5_000))
         .unwrap();
     let topic_metadata = data.topics();
     let md = &topic_metadata[0];
@@ -1,34 +1,63 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::slice;
 
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use arrow::datatypes::TimestampMillisecondType;
-use arrow_array::{Array, ArrayRef, PrimitiveArray, RecordBatch, StringArray, StructArray};
-use arrow_schema::{DataType, Field, SchemaRef, TimeUnit};
-use futures::StreamExt;
+use arrow_array::{Array, PrimitiveArray, RecordBatch};
+use arrow_schema::SchemaRef;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tracing::{debug, error};
+use tracing::{debug, error, warn, Instrument};
 
 use crate::config_extensions::denormalized_config::DenormalizedConfig;
-use crate::physical_plan::utils::time::array_to_timestamp_array;
-use crate::state_backend::rocksdb_backend::get_global_rocksdb;
+use crate::datasource::epoch::{BarrierAlignment, EpochTracker};
+use crate::physical_plan::utils::metadata::{
+    stream_metadata_array_with_barrier, stream_metadata_array_with_watermark,
+};
+use crate::physical_plan::utils::stream_message::{barrier_marker, control_batch, NO_BARRIER};
+use crate::physical_plan::utils::time::{array_to_timestamp_array, system_time_from_epoch};
+use crate::physical_plan::utils::watermark::{WatermarkGenerator, WatermarkStrategy};
+use crate::state_backend::backend::get_global_state_backend;
 use crate::utils::arrow_helpers::json_records_to_arrow_record_batch;
-
-use arrow::compute::{max, min};
+use crate::utils::backfill::BackfillController;
+use crate::utils::job::JobIdentity;
+use crate::utils::json_format::JsonFormatOptions;
+use crate::utils::logging::operator_span;
+use crate::utils::profiling::Profiler;
+use crate::utils::quota::ResourceQuotas;
+
+use arrow::array::AsArray;
+use arrow::compute::{concat_batches, filter, filter_record_batch, max, min};
+use arrow_array::TimestampMillisecondArray;
+use arrow_ord::cmp;
+use datafusion::common::DataFusionError;
+use datafusion::common_runtime::SpawnedTask;
 use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
 use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
 use datafusion::physical_plan::streaming::PartitionStream;
 
 use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::{ClientConfig, Message, Timestamp, TopicPartitionList};
+use rdkafka::{ClientConfig, Offset};
 
-use super::KafkaReadConfig;
+use super::dead_letter::DecodeFailures;
+use super::fresh_lane::{read_messages, FreshLane};
+use super::offsets::commit_positions;
+use super::replay::{PROVENANCE_OFFSET_COLUMN, PROVENANCE_PARTITION_COLUMN};
+use super::source_exec::SourceMetrics;
+use super::{KafkaReadConfig, MessageDecoder};
 
 pub struct KafkaStreamRead {
     pub config: Arc<KafkaReadConfig>,
     pub assigned_partitions: Vec<i32>,
+    /// Output partition of the source the stream is read as
+    pub partition: usize,
+    /// Metrics of the [`KafkaSourceExec`](super::KafkaSourceExec) reading the stream
+    pub metrics: ExecutionPlanMetricsSet,
+    /// Shared by the partitions of the source, which take checkpoints together
+    pub(crate) alignment: Arc<BarrierAlignment>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,12 +80,168 @@ impl BatchReadMetadata {
     }
 }
 
-fn create_consumer(config: Arc<KafkaReadConfig>) -> StreamConsumer {
+/// Per partition watermark of the source, the latest event time below which no further rows
+/// are expected. Persisted with every checkpoint so that a restarted job resumes from the
+/// event time it had reached instead of starting over.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PartitionWatermarks {
+    watermarks: Vec<(i32, i64)>,
+}
+
+impl PartitionWatermarks {
+    fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+
+    /// Advance each partition to the oldest row it contributed to the batch. Watermarks
+    /// never move backwards.
+    fn advance(&mut self, partitions: &[i32], timestamps: &TimestampMillisecondArray) {
+        let mut batch_minimums: HashMap<i32, i64> = HashMap::new();
+        for (partition, timestamp) in partitions.iter().zip(timestamps.iter()) {
+            if let Some(timestamp) = timestamp {
+                let minimum = batch_minimums.entry(*partition).or_insert(timestamp);
+                *minimum = (*minimum).min(timestamp);
+            }
+        }
+        for (partition, minimum) in batch_minimums {
+            match self.watermarks.iter_mut().find(|(p, _)| *p == partition) {
+                Some((_, watermark)) => *watermark = (*watermark).max(minimum),
+                None => self.watermarks.push((partition, minimum)),
+            }
+        }
+    }
+
+    /// Event time rows must reach after a restore, the slowest partition's watermark less the
+    /// allowed rewind
+    fn restore_floor(&self, rewind_ms: u64) -> Option<i64> {
+        self.watermarks
+            .iter()
+            .map(|(_, watermark)| *watermark)
+            .min()
+            .map(|watermark| watermark.saturating_sub(rewind_ms as i64))
+    }
+}
+
+/// The rows of `batch` at or after `floor`, with their timestamps and offsets, and the number
+/// of rows skipped. Rows without a timestamp are skipped too.
+fn skip_rows_before(
+    floor: i64,
+    batch: &RecordBatch,
+    timestamps: &TimestampMillisecondArray,
+    offsets: Vec<(i32, i64)>,
+) -> Result<
+    (
+        RecordBatch,
+        TimestampMillisecondArray,
+        Vec<(i32, i64)>,
+        usize,
+    ),
+    DataFusionError,
+> {
+    let keep = cmp::gt_eq(timestamps, &TimestampMillisecondArray::new_scalar(floor))?;
+    let offsets: Vec<(i32, i64)> = offsets
+        .into_iter()
+        .zip(keep.iter())
+        .filter(|(_, keep)| *keep == Some(true))
+        .map(|(offset, _)| offset)
+        .collect();
+    let skipped = batch.num_rows() - offsets.len();
+    let timestamps = filter(timestamps, &keep)?
+        .as_primitive::<TimestampMillisecondType>()
+        .clone();
+    Ok((
+        filter_record_batch(batch, &keep)?,
+        timestamps,
+        offsets,
+        skipped,
+    ))
+}
+
+/// Records of a batch that failed to decode, by their index in the batch
+type RecordErrors = Vec<(usize, DataFusionError)>;
+
+/// Decode `records` into a batch of `schema`. When the batch fails the records are decoded
+/// one by one, so only the ones that don't fit the schema fail.
+fn decode_records(
+    records: &[Value],
+    decode_schema: &SchemaRef,
+    json_format: JsonFormatOptions,
+    schema: &SchemaRef,
+) -> datafusion::common::Result<(RecordBatch, RecordErrors)> {
+    let decode = |records: &[Value]| {
+        let batch = json_records_to_arrow_record_batch(records, decode_schema.clone())?;
+        json_format.decode_batch(batch, schema)
+    };
+    if let Ok(batch) = decode(records) {
+        return Ok((batch, vec![]));
+    }
+
+    let mut batches = vec![];
+    let mut errors = vec![];
+    for (index, record) in records.iter().enumerate() {
+        match decode(slice::from_ref(record)) {
+            Ok(batch) => batches.push(batch),
+            Err(err) => errors.push((index, err)),
+        }
+    }
+    Ok((concat_batches(schema, &batches)?, errors))
+}
+
+/// Decode `records` in up to `parallelism` chunks, each on a blocking task of its own
+async fn decode_in_parallel(
+    records: Vec<Value>,
+    parallelism: usize,
+    decode_schema: SchemaRef,
+    json_format: JsonFormatOptions,
+    schema: SchemaRef,
+) -> datafusion::common::Result<(RecordBatch, RecordErrors)> {
+    let chunk_size = records.len().div_ceil(parallelism).max(1);
+    let mut records = records.into_iter();
+    let mut tasks = vec![];
+    loop {
+        let chunk: Vec<Value> = records.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        let (decode_schema, schema) = (decode_schema.clone(), schema.clone());
+        tasks.push(SpawnedTask::spawn_blocking(move || {
+            decode_records(&chunk, &decode_schema, json_format, &schema)
+        }));
+    }
+
+    let mut batches = Vec::with_capacity(tasks.len());
+    let mut errors = vec![];
+    for (chunk, task) in tasks.into_iter().enumerate() {
+        let (batch, chunk_errors) = task
+            .join()
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))??;
+        batches.push(batch);
+        errors.extend(
+            chunk_errors
+                .into_iter()
+                .map(|(index, err)| (chunk * chunk_size + index, err)),
+        );
+    }
+    Ok((concat_batches(&schema, &batches)?, errors))
+}
+
+fn create_consumer(config: Arc<KafkaReadConfig>, job: Option<&JobIdentity>) -> StreamConsumer {
     let mut client_config = ClientConfig::new();
 
     client_config
         .set("bootstrap.servers", config.bootstrap_servers.to_string())
         .set("enable.auto.commit", "false");
+    if let Some(job) = job {
+        client_config.set(
+            "client.id",
+            job.client_id(&format!("source-{}", config.topic)),
+        );
+    }
 
     for (key, value) in config.kafka_connection_opts.clone().into_iter() {
         client_config.set(key, value);
@@ -71,20 +256,32 @@ impl PartitionStream for KafkaStreamRead {
     }
 
     fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
-        let mut assigned_partitions = TopicPartitionList::new();
-
         let config_options = ctx
             .session_config()
             .options()
             .extensions
             .get::<DenormalizedConfig>();
 
-        let should_checkpoint = config_options.map_or_else(|| false, |c| c.checkpoint);
+        // A replay must not move the offsets and watermarks of the running pipeline
+        let should_checkpoint = config_options.map_or_else(|| false, |c| c.checkpoint)
+            && self.config.replay_offsets.is_none();
+        let take_checkpoints =
+            should_checkpoint && config_options.map_or(false, |c| !c.checkpoint_dir.is_empty());
+        let watermark_rewind_ms = config_options.map_or(0, |c| c.watermark_rewind_ms);
+        // A replay reads just the offsets it was given
+        let fresh_data_percent = match self.config.replay_offsets {
+            Some(_) => 0,
+            None => config_options.map_or(0, |c| c.fresh_data_percent),
+        };
 
         let topic = self.config.topic.clone();
-        for partition in self.assigned_partitions.clone() {
-            assigned_partitions.add_partition(self.config.topic.as_str(), partition);
-        }
+        let mut replay_remaining = self.config.replay_offsets.as_ref().map(|offsets| {
+            offsets
+                .iter()
+                .filter(|(partition, _)| self.assigned_partitions.contains(partition))
+                .copied()
+                .collect::<HashSet<_>>()
+        });
         let partition_tag = self
             .assigned_partitions
             .iter()
@@ -93,106 +290,262 @@ impl PartitionStream for KafkaStreamRead {
             .join("_");
 
         let state_backend = if should_checkpoint {
-            Some(get_global_rocksdb().unwrap())
+            Some(get_global_state_backend().unwrap())
         } else {
             None
         };
-        let consumer: StreamConsumer = create_consumer(self.config.clone());
-
-        consumer
-            .assign(&assigned_partitions)
-            .expect("Partition assignment failed.");
+        let job = JobIdentity::from_task_context(&ctx);
+        let consumer: StreamConsumer = create_consumer(self.config.clone(), job.as_deref());
+
+        let mut assigned_partitions = self
+            .config
+            .starting_offsets
+            .assignment(&consumer, &topic, &self.assigned_partitions)
+            .expect("Starting offsets lookup failed.");
+        for partition in self.assigned_partitions.iter() {
+            let first_replayed = replay_remaining.as_ref().and_then(|remaining| {
+                remaining
+                    .iter()
+                    .filter(|(p, _)| p == partition)
+                    .map(|(_, offset)| *offset)
+                    .min()
+            });
+            if let Some(offset) = first_replayed {
+                assigned_partitions
+                    .set_partition_offset(&topic, *partition, Offset::Offset(offset))
+                    .expect("Partition offset assignment failed.");
+            }
+        }
+        let lane_partitions = self.assigned_partitions.clone();
+        let output_partition = self.partition;
+        let alignment = Arc::clone(&self.alignment);
 
         let state_namespace = format!("kafka_source_{}", topic);
-
-        if state_backend.is_some() {
-            let backend = state_backend.as_ref().unwrap();
-            let _ = match backend.get_cf(&state_namespace) {
-                Ok(cf) => {
-                    debug!("cf for this already exists");
-                    Ok(cf)
-                }
-                Err(..) => {
-                    let _ = backend.create_cf(&state_namespace);
-                    backend.get_cf(&state_namespace)
-                }
-            };
-        };
         //let schema = self.config.schema.clone();
 
         let mut builder = RecordBatchReceiverStreamBuilder::new(self.config.schema.clone(), 1);
         let tx = builder.tx();
         let canonical_schema = self.config.schema.clone();
         let json_schema = self.config.original_schema.clone();
+        let json_format = self.config.json_format;
+        let mut decoder = MessageDecoder::new(self.config.message_format.clone())
+            .with_invalid_utf8(self.config.invalid_utf8.clone(), json_format);
+        let drift_monitor = self.config.drift_monitor.clone();
+        let decode_schema = json_format.decode_schema(&json_schema);
         let timestamp_column: String = self.config.timestamp_column.clone();
         let timestamp_unit = self.config.timestamp_unit.clone();
-
-        builder.spawn(async move {
-            let mut epoch = 0;
+        let watermark_strategy = self.config.watermark_strategy;
+        let mut watermark_generators: HashMap<i32, WatermarkGenerator> = self
+            .assigned_partitions
+            .iter()
+            .map(|&partition| (partition, WatermarkGenerator::new(watermark_strategy)))
+            .collect();
+        let profiler = Profiler::from_task_context(&ctx);
+        let profile_stack = format!("KafkaSource[{topic}];decode");
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+        let quotas = ResourceQuotas::from_task_context(&ctx);
+        let backfill = BackfillController::from_task_context(&ctx);
+        let backfill_source = format!("{topic}-{partition_tag}");
+        let mut metrics =
+            SourceMetrics::new(&self.metrics, self.partition, &self.assigned_partitions);
+
+        let watermark_key = format!("{partition_tag}_watermarks");
+        let offsets_key = format!("{partition_tag}_offsets");
+        let read_config = self.config.clone();
+        let commit_offsets = self.config.commit_offsets;
+        // Next offset of every partition read as part of the backlog, committed as the group's
+        // position and checkpointed to resume from
+        let mut committable: HashMap<i32, i64> = HashMap::new();
+
+        let span = operator_span("kafka_source", job.as_deref());
+        span.record("topic", topic.as_str());
+        span.record("partition", partition_tag.as_str());
+
+        let reader = async move {
+            if let Some(backend) = &state_backend {
+                backend.ensure_namespace(&state_namespace)?;
+                // Carry on after the rows the checkpoint covers, rather than at the starting
+                // offsets
+                let restored_offsets =
+                    backend.get_state(&state_namespace, offsets_key.clone().into_bytes())?;
+                if let Some(bytes) = restored_offsets {
+                    committable = bincode::deserialize(&bytes)
+                        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+                    debug!("Resuming at offsets {committable:?}");
+                    for (partition, offset) in &committable {
+                        assigned_partitions
+                            .set_partition_offset(&topic, *partition, Offset::Offset(*offset))
+                            .map_err(|err| DataFusionError::External(Box::new(err)))?;
+                    }
+                }
+            }
+            consumer
+                .assign(&assigned_partitions)
+                .map_err(|err| DataFusionError::External(Box::new(err)))?;
+            let decode_failures = DecodeFailures::try_new(&read_config, job.as_deref())?;
+            let mut fresh_lane = match fresh_data_percent {
+                0 => None,
+                percent => Some(FreshLane::try_new(
+                    &read_config,
+                    job.as_deref(),
+                    &lane_partitions,
+                    percent,
+                )?),
+            };
+            let restored_watermarks = match &state_backend {
+                Some(backend) => {
+                    backend.get_state(&state_namespace, watermark_key.clone().into_bytes())?
+                }
+                None => None,
+            };
+            let mut partition_watermarks = restored_watermarks
+                .map(|bytes| PartitionWatermarks::from_bytes(&bytes))
+                .transpose()
+                .map_err(|err| DataFusionError::External(Box::new(err)))?
+                .unwrap_or_default();
+            let watermark_floor = partition_watermarks.restore_floor(watermark_rewind_ms);
+            if let Some(floor) = watermark_floor {
+                debug!(
+                    "Restored watermarks {partition_watermarks:?}, skipping rows before {floor}"
+                );
+            }
+            let last_read_offsets = match (&state_backend, should_checkpoint) {
+                (Some(backend), true) => {
+                    backend.get_state(&state_namespace, partition_tag.clone().into_bytes())?
+                }
+                _ => None,
+            };
+            let last_batch_metadata = last_read_offsets
+                .map(|offsets| BatchReadMetadata::from_bytes(&offsets))
+                .transpose()
+                .map_err(|err| DataFusionError::External(Box::new(err)))?;
+            // Carry on after the epoch of the last checkpointed batch, and after any epoch the
+            // pipeline reached, so barriers and sink positions don't repeat after a restart
+            let mut epoch = last_batch_metadata
+                .as_ref()
+                .map_or(0, |metadata| metadata.epoch + 1)
+                .max(
+                    epoch_tracker
+                        .as_ref()
+                        .map_or(0, |tracker| tracker.current() as i32),
+                );
+            match &last_batch_metadata {
+                Some(metadata) => debug!(
+                    "epoch is {} and last read offsets are {:?}",
+                    epoch, metadata
+                ),
+                None => debug!("epoch is {} and no prior offsets were found.", epoch),
+            };
             loop {
-                let last_read_offsets = if should_checkpoint {
-                    state_backend.as_ref().and_then(|backend| {
-                        backend
-                            .get_state(&state_namespace, partition_tag.clone().into_bytes())
-                            .unwrap()
-                    })
-                } else {
-                    None
+                // Backfilling reads for longer, making for fewer and larger batches
+                let read_window = Duration::from_secs(
+                    backfill
+                        .as_ref()
+                        .map_or(1, |backfill| backfill.batch_factor() as u64),
+                );
+                let fetch_timer = metrics.fetch_time.timer();
+                let mut messages = match &mut fresh_lane {
+                    Some(lane) => lane.read(&consumer, read_window).await?,
+                    None => read_messages(&consumer, read_window, None).await?,
                 };
-
-                match last_read_offsets {
-                    Some(offsets) => {
-                        let last_batch_metadata = BatchReadMetadata::from_bytes(&offsets).unwrap();
-                        debug!(
-                            "epoch is {} and last read offsets are {:?}",
-                            epoch, last_batch_metadata
-                        );
+                fetch_timer.done();
+                metrics.record_fetch(
+                    messages.len(),
+                    messages
+                        .iter()
+                        .map(|(.., key, payload)| payload.len() + key.as_ref().map_or(0, Vec::len))
+                        .sum(),
+                );
+                for (partition, offset, ..) in &messages {
+                    let backlog = fresh_lane
+                        .as_ref()
+                        .map_or(true, |lane| lane.is_backlog(*partition, *offset));
+                    if backlog {
+                        metrics.advance(*partition, *offset);
+                        let next = committable.entry(*partition).or_insert(offset + 1);
+                        *next = (*next).max(offset + 1);
                     }
-                    None => debug!("epoch is {} and no prior offsets were found.", epoch),
-                };
-                let mut offsets_read: Vec<(i32, i64)> = vec![];
-                let batch: Vec<serde_json::Value> = consumer
-                    .stream()
-                    .take_until(tokio::time::sleep(Duration::from_secs(1)))
-                    .map(|message| match message {
-                        Ok(m) => {
-                            let timestamp = match m.timestamp() {
-                                Timestamp::NotAvailable => -1_i64,
-                                Timestamp::CreateTime(ts) => ts,
-                                Timestamp::LogAppendTime(ts) => ts,
-                            };
-                            let key = m.key();
-
-                            let payload = m.payload().expect("Message payload is empty");
-                            let mut deserialized_record: HashMap<String, Value> =
-                                serde_json::from_slice(payload).unwrap();
-                            deserialized_record
-                                .insert("kafka_timestamp".to_string(), Value::from(timestamp));
-                            if let Some(key) = key {
-                                deserialized_record.insert(
-                                    "kafka_key".to_string(),
-                                    Value::from(String::from_utf8_lossy(key)),
-                                );
-                            } else {
-                                deserialized_record
-                                    .insert("kafka_key".to_string(), Value::from(String::from("")));
-                            }
-                            let new_payload = serde_json::to_value(deserialized_record).unwrap();
-                            offsets_read.push((m.partition(), m.offset()));
-                            new_payload
-                        }
+                }
+                metrics.update_lags(&consumer, &topic);
+
+                let received = !messages.is_empty();
+                if let Some(remaining) = &mut replay_remaining {
+                    messages
+                        .retain(|(partition, offset, ..)| remaining.remove(&(*partition, *offset)));
+                }
+                let offsets_read: Vec<(i32, i64)> = messages
+                    .iter()
+                    .map(|(partition, offset, ..)| (*partition, *offset))
+                    .collect();
+
+                let mut batch: Vec<serde_json::Value> = Vec::with_capacity(messages.len());
+                // Index of the message of each record
+                let mut decoded = Vec::with_capacity(messages.len());
+                for (index, message) in messages.iter().enumerate() {
+                    let mut deserialized_record = match decoder.decode(&message.4).await {
+                        Ok(record) => record,
                         Err(err) => {
-                            error!("Error reading from Kafka {:?}", err);
-                            panic!("Error reading from Kafka {:?}", err)
+                            metrics.record_decode_error();
+                            decode_failures.handle(message, err).await?;
+                            continue;
                         }
-                    })
-                    .collect()
-                    .await;
-
-                debug!("Batch size {}", batch.len());
+                    };
+                    let (partition, offset, timestamp, key, _) = message;
+                    decoded.push(index);
+                    deserialized_record
+                        .insert("kafka_timestamp".to_string(), Value::from(*timestamp));
+                    let key = key
+                        .as_ref()
+                        .map(|key| String::from_utf8_lossy(key).into_owned())
+                        .unwrap_or_default();
+                    deserialized_record.insert("kafka_key".to_string(), Value::from(key));
+                    deserialized_record.insert(
+                        PROVENANCE_PARTITION_COLUMN.to_string(),
+                        Value::from(*partition),
+                    );
+                    deserialized_record
+                        .insert(PROVENANCE_OFFSET_COLUMN.to_string(), Value::from(*offset));
+                    batch.push(Value::Object(deserialized_record));
+                }
+                if let Some(monitor) = &drift_monitor {
+                    monitor.observe(&topic, &batch);
+                }
 
-                let record_batch: RecordBatch =
-                    json_records_to_arrow_record_batch(batch, json_schema.clone());
+                let key_sample = batch
+                    .first()
+                    .and_then(|record| record.get("kafka_key"))
+                    .map(|key| key.to_string());
+
+                let parallelism = backfill
+                    .as_ref()
+                    .map_or(1, |backfill| backfill.parallelism());
+                let (record_batch, record_errors) = if parallelism > 1 {
+                    decode_in_parallel(
+                        batch,
+                        parallelism,
+                        decode_schema.clone(),
+                        json_format,
+                        json_schema.clone(),
+                    )
+                    .await
+                } else {
+                    let decode =
+                        || decode_records(&batch, &decode_schema, json_format, &json_schema);
+                    match &profiler {
+                        Some(profiler) => profiler.measure(&profile_stack, decode),
+                        None => decode(),
+                    }
+                }?;
+
+                // Records that don't fit the schema fail like messages that can't be decoded
+                let mut failed = HashSet::with_capacity(record_errors.len());
+                for (record, err) in record_errors {
+                    metrics.record_decode_error();
+                    decode_failures
+                        .handle(&messages[decoded[record]], err)
+                        .await?;
+                    failed.insert(record);
+                }
 
                 let ts_column = record_batch
                     .column_by_name(timestamp_column.as_str())
@@ -201,11 +554,69 @@ impl PartitionStream for KafkaStreamRead {
                     })
                     .unwrap();
 
-                let binary_vec = Vec::from_iter(
-                    std::iter::repeat(String::from("no_barrier")).take(ts_column.len()),
-                );
-                let barrier_batch_column = StringArray::from(binary_vec);
-
+                // Messages that failed to decode have no rows
+                let mut row_offsets: Vec<(i32, i64)> = decoded
+                    .iter()
+                    .enumerate()
+                    .filter(|(record, _)| !failed.contains(record))
+                    .map(|(_, index)| (messages[*index].0, messages[*index].1))
+                    .collect();
+
+                // Rows older than the restored watermark belong to windows that fired before
+                // the restart, replaying them would fire those windows again
+                let (record_batch, ts_column) = match watermark_floor {
+                    Some(floor) => {
+                        let (record_batch, ts_column, offsets, skipped) =
+                            skip_rows_before(floor, &record_batch, &ts_column, row_offsets)?;
+                        if skipped > 0 {
+                            debug!(skipped, floor, "Skipped rows behind the restored watermark");
+                            metrics.record_rows_behind_watermark(skipped);
+                        }
+                        row_offsets = offsets;
+                        (record_batch, Arc::new(ts_column))
+                    }
+                    None => (record_batch, ts_column),
+                };
+                let row_partitions: Vec<i32> = row_offsets
+                    .iter()
+                    .map(|(partition, _)| *partition)
+                    .collect();
+                partition_watermarks.advance(&row_partitions, &ts_column);
+                // Rows read ahead of the backlog don't count as progress in event time
+                let backlog_timestamps: Vec<(i32, i64)> = row_offsets
+                    .iter()
+                    .zip(ts_column.iter())
+                    .filter(|((partition, offset), _)| {
+                        fresh_lane
+                            .as_ref()
+                            .map_or(true, |lane| lane.is_backlog(*partition, *offset))
+                    })
+                    .filter_map(|((partition, _), timestamp)| Some((*partition, timestamp?)))
+                    .collect();
+                for (partition, generator) in watermark_generators.iter_mut() {
+                    generator.advance(
+                        backlog_timestamps
+                            .iter()
+                            .filter(|(p, _)| p == partition)
+                            .map(|(_, timestamp)| *timestamp),
+                    );
+                }
+                // Partitions that haven't produced a row yet don't hold the watermark back
+                let watermark = watermark_generators
+                    .values()
+                    .filter_map(WatermarkGenerator::current)
+                    .min();
+
+                // Each checkpointed batch closes its epoch, downstream operators see it as
+                // a barrier once they have processed the batch's rows.
+                let barrier = if should_checkpoint {
+                    if let Some(tracker) = &epoch_tracker {
+                        tracker.advance(epoch as u64)?;
+                    }
+                    barrier_marker(epoch as u64)
+                } else {
+                    NO_BARRIER.to_string()
+                };
                 let ts_array = ts_column
                     .as_any()
                     .downcast_ref::<PrimitiveArray<TimestampMillisecondType>>()
@@ -213,52 +624,218 @@ impl PartitionStream for KafkaStreamRead {
 
                 let max_timestamp: Option<_> = max::<TimestampMillisecondType>(ts_array);
                 let min_timestamp: Option<_> = min::<TimestampMillisecondType>(ts_array);
-                debug!("min: {:?}, max: {:?}", min_timestamp, max_timestamp);
+                debug!(
+                    epoch,
+                    rows = ts_column.len(),
+                    ?min_timestamp,
+                    ?max_timestamp,
+                    key_sample,
+                    "Read batch"
+                );
+                let backlog_max_timestamp = backlog_timestamps
+                    .iter()
+                    .map(|(_, timestamp)| *timestamp)
+                    .max();
+                let lag = backlog_max_timestamp.map(|max_timestamp| {
+                    let now_ms = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |now| now.as_millis() as i64);
+                    Duration::from_millis(now_ms.saturating_sub(max_timestamp).max(0) as u64)
+                });
+                if let (Some(profiler), Some(lag)) = (&profiler, lag) {
+                    profiler.observe_lag(lag);
+                }
+                if let Some(backfill) = &backfill {
+                    // A read that found nothing means the partition is caught up
+                    let lag = lag.or((!received).then_some(Duration::ZERO));
+                    if let Some(lag) = lag {
+                        backfill.observe_lag(&backfill_source, lag);
+                    }
+                }
                 let mut columns: Vec<Arc<dyn Array>> = record_batch.columns().to_vec();
 
-                let metadata_column = StructArray::from(vec![
-                    (
-                        Arc::new(Field::new("barrier_batch", DataType::Utf8, false)),
-                        Arc::new(barrier_batch_column) as ArrayRef,
-                    ),
-                    (
-                        Arc::new(Field::new(
-                            "canonical_timestamp",
-                            DataType::Timestamp(TimeUnit::Millisecond, None),
-                            true,
-                        )),
-                        ts_column as ArrayRef,
+                let metadata_column = match watermark_strategy {
+                    WatermarkStrategy::BatchMinimum => {
+                        stream_metadata_array_with_barrier(ts_column.as_ref().clone(), &barrier)
+                    }
+                    _ => stream_metadata_array_with_watermark(
+                        ts_column.as_ref().clone(),
+                        &barrier,
+                        watermark,
                     ),
-                ]);
+                };
                 columns.push(Arc::new(metadata_column));
 
                 let timestamped_record_batch: RecordBatch =
                     RecordBatch::try_new(canonical_schema.clone(), columns).unwrap();
-                let tx_result = tx.send(Ok(timestamped_record_batch)).await;
+                // The watermark and barrier also travel on their own, operators may drop the
+                // rows carrying them and a read that found nothing has no rows at all
+                let control = control_batch(
+                    &canonical_schema,
+                    should_checkpoint.then_some(epoch as u64),
+                    match watermark_strategy {
+                        WatermarkStrategy::BatchMinimum => min_timestamp,
+                        _ => watermark,
+                    }
+                    .map(system_time_from_epoch),
+                );
+                let tx_result = match tx.send(Ok(timestamped_record_batch)).await {
+                    Ok(()) => tx.send(Ok(control)).await,
+                    Err(err) => Err(err),
+                };
                 match tx_result {
                     Ok(_) => {
-                        if should_checkpoint {
-                            let _ = state_backend.as_ref().map(|backend| {
-                                backend.put_state(
-                                    &state_namespace,
-                                    partition_tag.clone().into_bytes(),
-                                    BatchReadMetadata {
-                                        epoch,
-                                        min_timestamp,
-                                        max_timestamp,
-                                        offsets_read,
-                                    }
-                                    .to_bytes()
-                                    .unwrap(),
-                                )
-                            });
+                        if let (true, Some(backend), Some(quotas)) =
+                            (should_checkpoint, &state_backend, &quotas)
+                        {
+                            quotas.check_state_size(backend.state_size(&state_namespace)?)?;
+                        }
+                        if let (true, Some(backend)) = (should_checkpoint, &state_backend) {
+                            let to_state = |err| DataFusionError::External(Box::new(err));
+                            let metadata = BatchReadMetadata {
+                                epoch,
+                                min_timestamp,
+                                max_timestamp,
+                                offsets_read,
+                            };
+                            backend.put_state(
+                                &state_namespace,
+                                partition_tag.clone().into_bytes(),
+                                metadata.to_bytes().map_err(to_state)?,
+                            )?;
+                            backend.put_state(
+                                &state_namespace,
+                                watermark_key.clone().into_bytes(),
+                                partition_watermarks.to_bytes().map_err(to_state)?,
+                            )?;
+                            backend.put_state(
+                                &state_namespace,
+                                offsets_key.clone().into_bytes(),
+                                bincode::serialize(&committable).map_err(to_state)?,
+                            )?;
+                        }
+                        if let (true, Some(backend)) = (take_checkpoints, &state_backend) {
+                            alignment
+                                .close(output_partition, epoch as u64, |epoch| {
+                                    backend.checkpoint(epoch)
+                                })
+                                .await?;
+                        }
+                        // A failed commit is covered by the next one
+                        if commit_offsets {
+                            if let Err(err) = commit_positions(&consumer, &topic, &committable) {
+                                warn!(epoch, error = %err, "Failed to commit offsets");
+                            }
                         }
                     }
-                    Err(err) => error!("result err {:?}", err),
+                    Err(err) => error!(epoch, error = %err, "Failed to send batch downstream"),
                 }
                 epoch += 1;
+
+                if let Some(remaining) = &replay_remaining {
+                    if remaining.is_empty() {
+                        break;
+                    }
+                    if !received {
+                        warn!(
+                            ?remaining,
+                            "Replay found no further messages, the offsets may have expired"
+                        );
+                        break;
+                    }
+                }
             }
-        });
+            Ok(())
+        };
+        builder.spawn(reader.instrument(span));
         builder.build()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+
+    use crate::datasource::kafka::MessageFormat;
+
+    #[test]
+    fn skip_rows_behind_restored_watermarks() -> Result<(), DataFusionError> {
+        let mut watermarks = PartitionWatermarks::default();
+        watermarks.advance(
+            &[0, 1, 0],
+            &TimestampMillisecondArray::from(vec![5000, 3000, 4000]),
+        );
+        let bytes = watermarks.to_bytes().unwrap();
+        let restored = PartitionWatermarks::from_bytes(&bytes).unwrap();
+        let floor = restored.restore_floor(500).unwrap();
+        assert_eq!(floor, 2500);
+        assert!(PartitionWatermarks::from_bytes(&bytes[..3]).is_err());
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![1, 2, 3]))])?;
+        let timestamps = TimestampMillisecondArray::from(vec![Some(2000), Some(2500), None]);
+        let offsets = vec![(0, 10), (1, 11), (0, 12)];
+
+        let (batch, timestamps, offsets, skipped) =
+            skip_rows_before(floor, &batch, &timestamps, offsets)?;
+        assert_eq!(skipped, 2);
+        assert_eq!(offsets, [(1, 11)]);
+        assert_eq!(timestamps.values(), &[2500]);
+        assert_eq!(batch.num_rows(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fail_malformed_and_wrongly_typed_records() -> Result<(), DataFusionError> {
+        let payloads: [&[u8]; 4] = [
+            br#"{"id": 1, "name": "a"}"#,
+            br#"{"id": "#,
+            br#"{"id": 3, "name": "c"}"#,
+            br#"{"id": "four", "name": "d"}"#,
+        ];
+        let mut decoder = MessageDecoder::new(MessageFormat::Json);
+        let mut records = vec![];
+        for payload in payloads {
+            match decoder.decode(payload).await {
+                Ok(record) => records.push(Value::Object(record)),
+                Err(_) => assert_eq!(payload, payloads[1]),
+            }
+        }
+        assert_eq!(records.len(), 3);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let json_format = JsonFormatOptions::default();
+        let decode_schema = json_format.decode_schema(&schema);
+        let ids = |batch: &RecordBatch| {
+            batch
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec()
+        };
+
+        let (batch, errors) = decode_records(&records, &decode_schema, json_format, &schema)?;
+        assert_eq!(ids(&batch), [1, 3]);
+        assert_eq!(
+            errors.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [2]
+        );
+
+        // Chunks decoded in parallel tell the records that failed by their index in the batch
+        let (batch, errors) =
+            decode_in_parallel(records, 2, decode_schema, json_format, schema).await?;
+        assert_eq!(ids(&batch), [1, 3]);
+        assert_eq!(
+            errors.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [2]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use apache_avro::Schema as AvroSchema;
+use datafusion::common::{exec_err, DataFusionError, Result};
+use serde_json::{Map, Value};
+
+#[cfg(feature = "protobuf")]
+use prost_reflect::{DynamicMessage, MessageDescriptor, SerializeOptions};
+
+use crate::datasource::schema_registry::{SchemaRegistry, SubjectSchema};
+use crate::utils::json_format::JsonFormatOptions;
+use crate::utils::json_parse::parse_json;
+
+use super::StreamEncoding;
+
+/// The first byte of a message framed the Confluent way, followed by the big endian id of the
+/// schema it was written with
+const CONFLUENT_MAGIC_BYTE: u8 = 0;
+
+/// How the payload of every message of a topic is decoded into a row
+#[derive(Clone)]
+pub enum MessageFormat {
+    /// A JSON object
+    Json,
+    /// An Avro datum written with this schema, without any framing
+    Avro(AvroSchema),
+    /// An Avro datum framed the Confluent way, the writer schema is looked up in the registry
+    /// by the id in the frame
+    ConfluentAvro(Arc<dyn SchemaRegistry>),
+    /// A protobuf message of this type. With `confluent` the message is framed the Confluent
+    /// way, its message indexes pick the type among the messages of the file `message` is
+    /// declared in. The schema id in the frame isn't looked up.
+    #[cfg(feature = "protobuf")]
+    Protobuf {
+        message: MessageDescriptor,
+        confluent: bool,
+    },
+}
+
+impl MessageFormat {
+    pub fn encoding(&self) -> StreamEncoding {
+        match self {
+            Self::Json => StreamEncoding::Json,
+            Self::Avro(_) | Self::ConfluentAvro(_) => StreamEncoding::Avro,
+            #[cfg(feature = "protobuf")]
+            Self::Protobuf { .. } => StreamEncoding::Protobuf,
+        }
+    }
+}
+
+impl Debug for MessageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "Json"),
+            Self::Avro(schema) => write!(f, "Avro({})", schema.canonical_form()),
+            Self::ConfluentAvro(registry) => write!(f, "ConfluentAvro({registry:?})"),
+            #[cfg(feature = "protobuf")]
+            Self::Protobuf { message, confluent } => {
+                write!(
+                    f,
+                    "Protobuf({}, confluent: {confluent})",
+                    message.full_name()
+                )
+            }
+        }
+    }
+}
+
+/// How JSON messages that aren't valid UTF-8 are decoded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// Fail to decode the message
+    #[default]
+    Fail,
+    /// Replace invalid sequences with U+FFFD
+    Replace,
+    /// Replace invalid sequences with U+FFFD and keep the payload as it was in the binary
+    /// `column`, which is null for valid messages
+    Capture { column: String },
+}
+
+/// Decodes payloads in a [`MessageFormat`], keeping the writer schemas it looked up
+#[derive(Debug)]
+pub struct MessageDecoder {
+    format: MessageFormat,
+    writer_schemas: HashMap<u32, AvroSchema>,
+    invalid_utf8: InvalidUtf8,
+    json_format: JsonFormatOptions,
+}
+
+impl MessageDecoder {
+    pub fn new(format: MessageFormat) -> Self {
+        Self {
+            format,
+            writer_schemas: HashMap::new(),
+            invalid_utf8: InvalidUtf8::default(),
+            json_format: JsonFormatOptions::default(),
+        }
+    }
+
+    /// Decode JSON messages that aren't valid UTF-8 as `invalid_utf8` says, captured payloads
+    /// are encoded the way `json_format` reads binary values
+    pub fn with_invalid_utf8(
+        mut self,
+        invalid_utf8: InvalidUtf8,
+        json_format: JsonFormatOptions,
+    ) -> Self {
+        self.invalid_utf8 = invalid_utf8;
+        self.json_format = json_format;
+        self
+    }
+
+    /// The fields of the row in `payload`, as the arrow JSON reader expects them
+    pub async fn decode(&mut self, payload: &[u8]) -> Result<Map<String, Value>> {
+        let value = match &self.format {
+            MessageFormat::Json => self.decode_json(payload)?,
+            MessageFormat::Avro(schema) => avro_to_json(schema, payload)?,
+            MessageFormat::ConfluentAvro(registry) => {
+                let (id, datum) = confluent_frame(payload)?;
+                if !self.writer_schemas.contains_key(&id) {
+                    let schema = match registry.schema_by_id(id).await? {
+                        SubjectSchema::Avro(schema) => schema,
+                        #[cfg(feature = "protobuf")]
+                        SubjectSchema::Protobuf(_) => {
+                            return exec_err!(
+                                "Messages written with protobuf schema {id} aren't Avro"
+                            )
+                        }
+                    };
+                    self.writer_schemas.insert(id, schema);
+                }
+                avro_to_json(&self.writer_schemas[&id], datum)?
+            }
+            #[cfg(feature = "protobuf")]
+            MessageFormat::Protobuf { message, confluent } => {
+                let (descriptor, bytes) = match confluent {
+                    true => {
+                        let (_, framed) = confluent_frame(payload)?;
+                        let (indexes, bytes) = message_indexes(framed)?;
+                        (indexed_message(message, &indexes)?, bytes)
+                    }
+                    false => (message.clone(), payload),
+                };
+                protobuf_to_json(descriptor, bytes)?
+            }
+        };
+        match value {
+            Value::Object(fields) => Ok(fields),
+            other => exec_err!("Expected a message to decode into an object, got {other}"),
+        }
+    }
+
+    fn decode_json(&self, payload: &[u8]) -> Result<Value> {
+        if self.invalid_utf8 == InvalidUtf8::Fail || std::str::from_utf8(payload).is_ok() {
+            return parse_json(payload);
+        }
+        let mut value = parse_json(String::from_utf8_lossy(payload).as_bytes())?;
+        if let (InvalidUtf8::Capture { column }, Value::Object(fields)) =
+            (&self.invalid_utf8, &mut value)
+        {
+            let raw = self.json_format.encode_binary_value(payload);
+            fields.insert(column.clone(), Value::from(raw));
+        }
+        Ok(value)
+    }
+}
+
+fn decode_error(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+/// The schema id and the payload after it
+fn confluent_frame(payload: &[u8]) -> Result<(u32, &[u8])> {
+    match payload {
+        [CONFLUENT_MAGIC_BYTE, a, b, c, d, rest @ ..] => {
+            Ok((u32::from_be_bytes([*a, *b, *c, *d]), rest))
+        }
+        _ => exec_err!("Message is not framed with a Confluent schema id"),
+    }
+}
+
+fn avro_to_json(schema: &AvroSchema, mut datum: &[u8]) -> Result<Value> {
+    let value = apache_avro::from_avro_datum(schema, &mut datum, None).map_err(decode_error)?;
+    Value::try_from(value).map_err(decode_error)
+}
+
+/// The zigzag encoded message indexes Confluent puts before a protobuf message and the message
+/// after them. A single zero stands for the first message of the file.
+#[cfg(feature = "protobuf")]
+fn message_indexes(mut bytes: &[u8]) -> Result<(Vec<usize>, &[u8])> {
+    let mut next = || -> Result<i64> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let Some((byte, rest)) = bytes.split_first() else {
+                return exec_err!("Truncated protobuf message indexes");
+            };
+            bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        exec_err!("Malformed protobuf message indexes")
+    };
+    let indexes = match next()? {
+        0 => vec![0],
+        count => (0..count)
+            .map(|_| next().map(|index| index as usize))
+            .collect::<Result<_>>()?,
+    };
+    Ok((indexes, bytes))
+}
+
+/// The message type `indexes` point at, starting from the top level messages of the file
+/// `message` is declared in
+#[cfg(feature = "protobuf")]
+fn indexed_message(message: &MessageDescriptor, indexes: &[usize]) -> Result<MessageDescriptor> {
+    let mut candidates: Vec<_> = message.parent_file().messages().collect();
+    let mut found = None;
+    for index in indexes {
+        let Some(next) = candidates.get(*index).cloned() else {
+            return exec_err!(
+                "Message index {index} not found in {}",
+                message.parent_file().name()
+            );
+        };
+        candidates = next.child_messages().collect();
+        found = Some(next);
+    }
+    found.map_or_else(|| exec_err!("Protobuf message has no message indexes"), Ok)
+}
+
+/// Protobuf field names and 64 bit integers as numbers, like the gRPC source
+#[cfg(feature = "protobuf")]
+fn protobuf_to_json(descriptor: MessageDescriptor, bytes: &[u8]) -> Result<Value> {
+    let message = DynamicMessage::decode(descriptor, bytes).map_err(decode_error)?;
+    let options = SerializeOptions::new()
+        .use_proto_field_name(true)
+        .stringify_64_bit_integers(false);
+    message
+        .serialize_with_options(serde_json::value::Serializer, &options)
+        .map_err(decode_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use apache_avro::types::Record;
+    use serde_json::json;
+
+    use crate::datasource::schema_registry::MemorySchemaRegistry;
+
+    #[tokio::test]
+    async fn decode_confluent_avro() -> Result<()> {
+        let schema = AvroSchema::parse_str(
+            r#"{"type": "record", "name": "reading", "fields": [
+                {"name": "sensor", "type": "string"},
+                {"name": "reading", "type": ["null", "double"]}
+            ]}"#,
+        )
+        .unwrap();
+        let registry = Arc::new(MemorySchemaRegistry::default());
+        let subject_schema = SubjectSchema::Avro(schema.clone());
+        registry.register("readings-value", &subject_schema).await?;
+        let id = registry.id_of(&subject_schema).unwrap();
+
+        let mut record = Record::new(&schema).unwrap();
+        record.put("sensor", "a");
+        record.put("reading", Some(1.5));
+        let mut payload = vec![CONFLUENT_MAGIC_BYTE];
+        payload.extend(id.to_be_bytes());
+        payload.extend(apache_avro::to_avro_datum(&schema, record).unwrap());
+
+        let mut decoder = MessageDecoder::new(MessageFormat::ConfluentAvro(registry));
+        assert_eq!(
+            Value::Object(decoder.decode(&payload).await?),
+            json!({"sensor": "a", "reading": 1.5})
+        );
+        assert!(decoder.decode(&payload[1..]).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decode_invalid_utf8() -> Result<()> {
+        let payload = b"{\"name\": \"caf\xe9\", \"id\": 1}";
+        let mut decoder = MessageDecoder::new(MessageFormat::Json);
+        assert!(decoder.decode(payload).await.is_err());
+
+        let mut decoder = MessageDecoder::new(MessageFormat::Json)
+            .with_invalid_utf8(InvalidUtf8::Replace, JsonFormatOptions::default());
+        assert_eq!(
+            Value::Object(decoder.decode(payload).await?),
+            json!({"name": "caf\u{fffd}", "id": 1})
+        );
+
+        let mut decoder = MessageDecoder::new(MessageFormat::Json).with_invalid_utf8(
+            InvalidUtf8::Capture {
+                column: "raw".to_string(),
+            },
+            JsonFormatOptions::default(),
+        );
+        assert_eq!(
+            Value::Object(decoder.decode(payload).await?),
+            json!({"name": "caf\u{fffd}", "id": 1, "raw": hex::encode(payload)})
+        );
+        assert_eq!(
+            Value::Object(decoder.decode(b"{\"id\": 2}").await?),
+            json!({"id": 2})
+        );
+        Ok(())
+    }
+}
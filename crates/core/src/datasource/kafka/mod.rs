@@ -1,11 +1,26 @@
+pub mod dead_letter;
+#[cfg(feature = "event-hubs")]
+pub mod event_hubs;
+mod fresh_lane;
 pub mod kafka_config;
 pub mod kafka_stream_read;
+pub mod message_format;
+pub mod offsets;
+pub mod replay;
+pub mod source_exec;
 pub mod topic_reader;
 pub mod topic_writer;
 
+pub use dead_letter::DecodeErrorPolicy;
+#[cfg(feature = "event-hubs")]
+pub use event_hubs::EventHubsConnection;
 pub use kafka_config::{
     ConnectionOpts, KafkaReadConfig, KafkaTopicBuilder, KafkaWriteConfig, StreamEncoding,
 };
 pub use kafka_stream_read::KafkaStreamRead;
+pub use message_format::{InvalidUtf8, MessageDecoder, MessageFormat};
+pub use offsets::StartingOffsets;
+pub use replay::{provenance_offsets, PROVENANCE_OFFSET_COLUMN, PROVENANCE_PARTITION_COLUMN};
+pub use source_exec::KafkaSourceExec;
 pub use topic_reader::TopicReader;
-pub use topic_writer::TopicWriter;
+pub use topic_writer::{TopicWriter, KAFKA_KEY_COLUMN, KAFKA_PARTITION_COLUMN};
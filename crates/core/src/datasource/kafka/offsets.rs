@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use datafusion::common::{plan_err, DataFusionError, Result};
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::error::KafkaResult;
+use rdkafka::{Offset, TopicPartitionList};
+
+/// Where a Kafka source starts reading the partitions of its topic
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StartingOffsets {
+    /// The offsets committed by the consumer group set as `group.id`, partitions without one
+    /// start where `auto.offset.reset` says
+    #[default]
+    Committed,
+    Earliest,
+    Latest,
+    /// These `(partition, offset)` pairs, partitions left out start from their committed
+    /// offset
+    Offsets(Vec<(i32, i64)>),
+    /// The first message of every partition with a timestamp at or after this time, in
+    /// milliseconds since the unix epoch. Partitions without one start at their end.
+    Timestamp(i64),
+}
+
+impl StartingOffsets {
+    /// Check the offsets against the `partition_count` partitions of `topic`
+    pub(crate) fn validate(&self, topic: &str, partition_count: i32) -> Result<()> {
+        let Self::Offsets(offsets) = self else {
+            return Ok(());
+        };
+        match offsets
+            .iter()
+            .find(|(partition, offset)| !(0..partition_count).contains(partition) || *offset < 0)
+        {
+            Some((partition, offset)) => {
+                plan_err!("Topic {topic} can't start reading partition {partition} at {offset}")
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// The assignment of `partitions` of `topic` at these offsets, looking up the offsets of a
+    /// timestamp with `consumer`
+    pub(crate) fn assignment(
+        &self,
+        consumer: &StreamConsumer,
+        topic: &str,
+        partitions: &[i32],
+    ) -> Result<TopicPartitionList> {
+        let mut assignment = TopicPartitionList::new();
+        for &partition in partitions {
+            let offset = match self {
+                Self::Committed => Offset::Stored,
+                Self::Earliest => Offset::Beginning,
+                Self::Latest => Offset::End,
+                Self::Offsets(offsets) => offsets
+                    .iter()
+                    .find(|(p, _)| *p == partition)
+                    .map_or(Offset::Stored, |(_, offset)| Offset::Offset(*offset)),
+                Self::Timestamp(timestamp) => Offset::Offset(*timestamp),
+            };
+            assignment
+                .add_partition_offset(topic, partition, offset)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        }
+        if let Self::Timestamp(_) = self {
+            assignment = consumer
+                .offsets_for_times(assignment, Duration::from_secs(10))
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        }
+        Ok(assignment)
+    }
+}
+
+/// Commit the next offset to read of every partition in `positions` for the consumer group
+/// of `consumer`, without waiting for the brokers to acknowledge
+pub(crate) fn commit_positions(
+    consumer: &StreamConsumer,
+    topic: &str,
+    positions: &HashMap<i32, i64>,
+) -> KafkaResult<()> {
+    if positions.is_empty() {
+        return Ok(());
+    }
+    let mut offsets = TopicPartitionList::new();
+    for (&partition, &position) in positions {
+        offsets.add_partition_offset(topic, partition, Offset::Offset(position))?;
+    }
+    consumer.commit(&offsets, CommitMode::Async)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_starting_offsets() {
+        assert!(StartingOffsets::Latest.validate("orders", 0).is_ok());
+        assert!(StartingOffsets::Offsets(vec![(0, 10), (2, 0)])
+            .validate("orders", 3)
+            .is_ok());
+        let error = StartingOffsets::Offsets(vec![(0, 10), (3, 0)])
+            .validate("orders", 3)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Topic orders can't start reading partition 3 at 0"));
+        assert!(StartingOffsets::Offsets(vec![(1, -5)])
+            .validate("orders", 3)
+            .is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use arrow::array::AsArray;
+use arrow::compute::cast;
+use arrow::datatypes::{Int32Type, Int64Type};
+use arrow_array::RecordBatch;
+use arrow_schema::DataType;
+use datafusion::common::{plan_err, Result};
+
+use super::{DecodeErrorPolicy, KafkaReadConfig, TopicReader};
+
+/// Field every message of a Kafka source carries with the partition it was read from. Declare
+/// it in the topic's schema and keep it up to the sink to capture the provenance of outputs.
+pub const PROVENANCE_PARTITION_COLUMN: &str = "kafka_partition";
+/// Field every message of a Kafka source carries with its offset in its partition
+pub const PROVENANCE_OFFSET_COLUMN: &str = "kafka_offset";
+
+/// The distinct `(partition, offset)` pairs of the provenance columns of output rows, to
+/// [`TopicReader::replay`] the messages they came from. Rows missing either are skipped.
+pub fn provenance_offsets(batches: &[RecordBatch]) -> Result<Vec<(i32, i64)>> {
+    let mut offsets = BTreeSet::new();
+    for batch in batches {
+        let (Some(partitions), Some(positions)) = (
+            batch.column_by_name(PROVENANCE_PARTITION_COLUMN),
+            batch.column_by_name(PROVENANCE_OFFSET_COLUMN),
+        ) else {
+            return plan_err!(
+                "Rows need the {PROVENANCE_PARTITION_COLUMN} and {PROVENANCE_OFFSET_COLUMN} columns to be replayed"
+            );
+        };
+        let partitions = cast(partitions, &DataType::Int32)?;
+        let positions = cast(positions, &DataType::Int64)?;
+        let pairs = partitions
+            .as_primitive::<Int32Type>()
+            .iter()
+            .zip(positions.as_primitive::<Int64Type>().iter());
+        for pair in pairs {
+            if let (Some(partition), Some(offset)) = pair {
+                offsets.insert((partition, offset));
+            }
+        }
+    }
+    Ok(offsets.into_iter().collect())
+}
+
+impl TopicReader {
+    /// A bounded copy of the topic reading just the messages at `offsets`, as `(partition,
+    /// offset)`, e.g. from [`provenance_offsets`]. Running them through the pipeline again into
+    /// a debug sink or [`DataStream::print_stream`](crate::datastream::DataStream::print_stream)
+    /// shows how they turned into the outputs under investigation. The stream ends once every
+    /// message was read, or when none are left to read because they expired. The replay doesn't
+    /// checkpoint, the offsets and watermarks of the pipeline itself stay as they are.
+    pub fn replay(&self, offsets: Vec<(i32, i64)>) -> Result<TopicReader> {
+        if offsets.is_empty() {
+            return plan_err!("No offsets of topic {} to replay", self.0.topic);
+        }
+        if let Some((partition, _)) = offsets
+            .iter()
+            .find(|(partition, _)| !(0..self.0.partition_count).contains(partition))
+        {
+            return plan_err!(
+                "Topic {} has no partition {partition} to replay",
+                self.0.topic
+            );
+        }
+        // Messages the pipeline dead lettered already aren't forwarded again
+        let decode_error_policy = match &self.0.decode_error_policy {
+            DecodeErrorPolicy::DeadLetter { .. } => DecodeErrorPolicy::Skip,
+            policy => policy.clone(),
+        };
+        Ok(TopicReader(Arc::new(KafkaReadConfig {
+            replay_offsets: Some(offsets),
+            decode_error_policy,
+            commit_offsets: false,
+            ..self.0.as_ref().clone()
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, StringArray};
+    use arrow_schema::{Field, Schema};
+
+    #[test]
+    fn collect_provenance_offsets() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("total", DataType::Utf8, true),
+            Field::new(PROVENANCE_PARTITION_COLUMN, DataType::Int64, true),
+            Field::new(PROVENANCE_OFFSET_COLUMN, DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+                Arc::new(Int64Array::from(vec![Some(1), Some(0), Some(1), None])),
+                Arc::new(Int64Array::from(vec![Some(42), Some(7), Some(42), Some(3)])),
+            ],
+        )?;
+        assert_eq!(provenance_offsets(&[batch])?, vec![(0, 7), (1, 42)]);
+
+        let without = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("total", DataType::Utf8, true)])),
+            vec![Arc::new(StringArray::from(vec!["a"]))],
+        )?;
+        assert!(provenance_offsets(&[without]).is_err());
+        Ok(())
+    }
+}
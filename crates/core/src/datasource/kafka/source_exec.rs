@@ -0,0 +1,158 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use datafusion::common::Result;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, Gauge, MetricBuilder, MetricsSet, Time,
+};
+use datafusion::physical_plan::streaming::StreamingTableExec;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+use rdkafka::consumer::{Consumer, StreamConsumer};
+
+/// Reads the partitions of a topic, one [`KafkaStreamRead`](super::KafkaStreamRead) per
+/// output partition, reporting the metrics the streams record into `metrics`
+#[derive(Debug)]
+pub struct KafkaSourceExec {
+    input: StreamingTableExec,
+    topic: String,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl KafkaSourceExec {
+    pub fn new(input: StreamingTableExec, topic: &str, metrics: ExecutionPlanMetricsSet) -> Self {
+        Self {
+            input,
+            topic: topic.to_string(),
+            metrics,
+        }
+    }
+}
+
+impl DisplayAs for KafkaSourceExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "KafkaSourceExec: topic={}, ", self.topic)?;
+                self.input.fmt_as(t, f)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for KafkaSourceExec {
+    fn name(&self) -> &'static str {
+        "KafkaSourceExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+/// Metrics of the stream reading a partition of the source: the records and bytes read, the
+/// records that failed to decode, the number of fetches and the time spent in them, and the
+/// `consumer_lag` of every Kafka partition, the records between the last offset of the backlog
+/// read and the end of the partition as last reported by the broker
+pub(crate) struct SourceMetrics {
+    records_read: Count,
+    bytes_read: Count,
+    decode_errors: Count,
+    /// Rows older than the watermark restored from the checkpoint
+    rows_behind_watermark: Count,
+    fetches: Count,
+    pub fetch_time: Time,
+    lags: HashMap<i32, Gauge>,
+    /// The next offset of the backlog of every Kafka partition
+    positions: HashMap<i32, i64>,
+}
+
+impl SourceMetrics {
+    pub fn new(
+        metrics: &ExecutionPlanMetricsSet,
+        partition: usize,
+        kafka_partitions: &[i32],
+    ) -> Self {
+        let lags = kafka_partitions
+            .iter()
+            .map(|&kafka_partition| {
+                let lag = MetricBuilder::new(metrics)
+                    .with_new_label("kafka_partition", kafka_partition.to_string())
+                    .gauge("consumer_lag", partition);
+                (kafka_partition, lag)
+            })
+            .collect();
+        Self {
+            records_read: MetricBuilder::new(metrics).counter("records_read", partition),
+            bytes_read: MetricBuilder::new(metrics).counter("bytes_read", partition),
+            decode_errors: MetricBuilder::new(metrics).counter("decode_errors", partition),
+            rows_behind_watermark: MetricBuilder::new(metrics)
+                .counter("rows_behind_watermark", partition),
+            fetches: MetricBuilder::new(metrics).counter("fetches", partition),
+            fetch_time: MetricBuilder::new(metrics).subset_time("fetch_time", partition),
+            lags,
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn record_fetch(&self, records: usize, bytes: usize) {
+        self.fetches.add(1);
+        self.records_read.add(records);
+        self.bytes_read.add(bytes);
+    }
+
+    pub fn record_decode_error(&self) {
+        self.decode_errors.add(1);
+    }
+
+    pub fn record_rows_behind_watermark(&self, rows: usize) {
+        self.rows_behind_watermark.add(rows);
+    }
+
+    /// Advance the backlog of `partition` past `offset`
+    pub fn advance(&mut self, partition: i32, offset: i64) {
+        let position = self.positions.entry(partition).or_insert(offset + 1);
+        *position = (*position).max(offset + 1);
+    }
+
+    /// Update the lags from the high watermarks the consumer cached, without asking the brokers
+    pub fn update_lags(&self, consumer: &StreamConsumer, topic: &str) {
+        for (partition, lag) in &self.lags {
+            let Some(position) = self.positions.get(partition) else {
+                continue;
+            };
+            if let Ok((_, high)) = consumer.get_watermark_offsets(topic, *partition) {
+                lag.set(high.saturating_sub(*position).max(0) as usize);
+            }
+        }
+    }
+}
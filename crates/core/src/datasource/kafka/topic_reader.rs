@@ -7,9 +7,12 @@ use datafusion::common::{not_impl_err, plan_err, Result};
 use datafusion::datasource::TableProvider;
 use datafusion::logical_expr::{Expr, TableType};
 use datafusion::physical_expr::{expressions, LexOrdering, PhysicalSortExpr};
+use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
 use datafusion::physical_plan::{streaming::StreamingTableExec, ExecutionPlan};
 
-use super::{KafkaReadConfig, KafkaStreamRead};
+use crate::datasource::epoch::BarrierAlignment;
+
+use super::{KafkaReadConfig, KafkaSourceExec, KafkaStreamRead};
 
 // Used to createa kafka source
 pub struct TopicReader(pub Arc<KafkaReadConfig>);
@@ -31,24 +34,43 @@ impl TopicReader {
             }
             None => create_ordering(self.0.schema.as_ref(), &self.0.order)?,
         };
-        let mut partition_streams = Vec::with_capacity(self.0.partition_count as usize);
+        let partitions: Vec<i32> = match &self.0.replay_offsets {
+            Some(offsets) => {
+                let mut partitions: Vec<i32> = offsets.iter().map(|(p, _)| *p).collect();
+                partitions.sort_unstable();
+                partitions.dedup();
+                partitions
+            }
+            None => (0..self.0.partition_count).collect(),
+        };
+        let metrics = ExecutionPlanMetricsSet::new();
+        let alignment = Arc::new(BarrierAlignment::new(partitions.len()));
+        let mut partition_streams = Vec::with_capacity(partitions.len());
 
-        for part in 0..self.0.partition_count {
+        for (index, part) in partitions.into_iter().enumerate() {
             let read_stream = Arc::new(KafkaStreamRead {
                 config: self.0.clone(),
                 assigned_partitions: vec![part],
+                partition: index,
+                metrics: metrics.clone(),
+                alignment: Arc::clone(&alignment),
             });
             partition_streams.push(read_stream as _);
         }
 
-        Ok(Arc::new(StreamingTableExec::try_new(
+        let input = StreamingTableExec::try_new(
             self.0.schema.clone(),
             partition_streams,
             projection,
             projected_schema,
             true,
             None,
-        )?))
+        )?;
+        Ok(Arc::new(KafkaSourceExec::new(
+            input,
+            &self.0.topic,
+            metrics,
+        )))
     }
 }
 
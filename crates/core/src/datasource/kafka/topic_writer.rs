@@ -1,28 +1,50 @@
 use async_trait::async_trait;
+use futures::channel::oneshot::Canceled;
 use futures::StreamExt;
-use std::fmt::{self, Debug};
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Display};
 use std::time::Duration;
 use std::{any::Any, sync::Arc};
 
-use arrow_schema::SchemaRef;
+use arrow::array::AsArray;
+use arrow::compute::cast;
+use arrow::datatypes::Int32Type;
+use arrow_array::{Array, ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, SchemaRef};
 
 use datafusion::catalog::Session;
-use datafusion::common::{not_impl_err, Result};
+use datafusion::common::{exec_datafusion_err, not_impl_err, plan_err, DataFusionError, Result};
 use datafusion::datasource::TableProvider;
 use datafusion::execution::TaskContext;
 use datafusion::logical_expr::{Expr, TableType};
 use datafusion::physical_plan::{
     insert::{DataSink, DataSinkExec},
-    metrics::MetricsSet,
+    metrics::{Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet, Time},
     DisplayAs, DisplayFormatType, ExecutionPlan, SendableRecordBatchStream,
 };
 
-use rdkafka::producer::FutureProducer;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::future_producer::OwnedDeliveryResult;
 use rdkafka::producer::FutureRecord;
+use rdkafka::producer::{DeliveryFuture, FutureProducer};
+use tracing::{debug, Instrument};
 
 use super::KafkaWriteConfig;
+use crate::datasource::epoch::{EpochTracker, SinkPosition};
+use crate::utils::job::JobIdentity;
+use crate::utils::logging::operator_span;
+use crate::utils::profiling::Profiler;
+use crate::utils::quota::ResourceQuotas;
 use crate::utils::row_encoder::{JsonRowEncoder, RowEncoder};
 
+/// Column [`DataStream::sink_kafka_keyed`](crate::datastream::DataStream::sink_kafka_keyed)
+/// evaluates the message key into
+pub const KAFKA_KEY_COLUMN: &str = "_kafka_key";
+/// Column [`DataStream::sink_kafka_keyed`](crate::datastream::DataStream::sink_kafka_keyed)
+/// evaluates the partition into
+pub const KAFKA_PARTITION_COLUMN: &str = "_kafka_partition";
+
 // Used to createa kafka source
 pub struct TopicWriter(pub Arc<KafkaWriteConfig>);
 
@@ -59,14 +81,15 @@ impl TableProvider for TopicWriter {
 
     async fn insert_into(
         &self,
-        _state: &dyn Session,
+        state: &dyn Session,
         input: Arc<dyn ExecutionPlan>,
         overwrite: bool,
     ) -> Result<Arc<dyn ExecutionPlan>> {
         if overwrite {
             return not_impl_err!("Overwrite not implemented for TopicWriter");
         }
-        let sink = Arc::new(KafkaSink::new(self.0.clone()));
+        let job = state.config().get_extension::<JobIdentity>();
+        let sink = Arc::new(KafkaSink::try_new(self.0.clone(), job)?);
         Ok(Arc::new(DataSinkExec::new(
             input,
             sink,
@@ -79,13 +102,137 @@ impl TableProvider for TopicWriter {
 struct KafkaSink {
     producer: FutureProducer,
     config: Arc<KafkaWriteConfig>,
+    job: Option<Arc<JobIdentity>>,
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl KafkaSink {
-    fn new(config: Arc<KafkaWriteConfig>) -> Self {
-        let producer = config.make_producer().unwrap();
+    fn try_new(config: Arc<KafkaWriteConfig>, job: Option<Arc<JobIdentity>>) -> Result<Self> {
+        let producer = config.make_producer(job.as_deref())?;
 
-        Self { producer, config }
+        Ok(Self {
+            producer,
+            config,
+            job,
+            metrics: ExecutionPlanMetricsSet::new(),
+        })
+    }
+}
+
+/// Delivery metrics of the sink. `rows_written` and `bytes_written` count the messages the
+/// brokers acknowledged, `produce_time` the time the sink waited on acknowledgements before it
+/// could send more.
+struct SinkMetrics {
+    rows_written: Count,
+    bytes_written: Count,
+    delivery_failures: Count,
+    produce_time: Time,
+}
+
+impl SinkMetrics {
+    fn new(metrics: &ExecutionPlanMetricsSet, partition: usize) -> Self {
+        Self {
+            rows_written: MetricBuilder::new(metrics).counter("rows_written", partition),
+            bytes_written: MetricBuilder::new(metrics).counter("bytes_written", partition),
+            delivery_failures: MetricBuilder::new(metrics).counter("delivery_failures", partition),
+            produce_time: MetricBuilder::new(metrics).subset_time("produce_time", partition),
+        }
+    }
+
+    fn report_delivery(
+        &self,
+        delivery: std::result::Result<OwnedDeliveryResult, Canceled>,
+        bytes: usize,
+        position: Option<SinkPosition>,
+    ) -> Result<()> {
+        match delivery {
+            Ok(Ok(_)) => {
+                self.rows_written.add(1);
+                self.bytes_written.add(bytes);
+                Ok(())
+            }
+            Ok(Err((err, _))) => Err(self.failed_delivery(&err, position)),
+            Err(canceled) => Err(self.failed_delivery(&canceled, position)),
+        }
+    }
+
+    fn failed_delivery(
+        &self,
+        err: &dyn Display,
+        position: Option<SinkPosition>,
+    ) -> DataFusionError {
+        self.delivery_failures.add(1);
+        match position {
+            Some(position) => exec_datafusion_err!(
+                "Failed to deliver record of epoch {}, batch {}: {err}",
+                position.epoch,
+                position.sequence
+            ),
+            None => exec_datafusion_err!("Failed to deliver record: {err}"),
+        }
+    }
+}
+
+/// Bounds on the messages waiting on delivery
+#[derive(Debug, Clone, Copy)]
+struct InFlightLimits {
+    max_requests: usize,
+    max_bytes: usize,
+}
+
+impl InFlightLimits {
+    /// Whether another message of `bytes` may be sent while `requests` messages of `buffered`
+    /// bytes are in flight. A message larger than the buffer is sent once nothing else is.
+    fn admits(&self, requests: usize, buffered: usize, bytes: usize) -> bool {
+        requests == 0 || (requests < self.max_requests && buffered + bytes <= self.max_bytes)
+    }
+}
+
+/// Messages sent but not yet acknowledged, oldest first
+struct InFlight {
+    limits: InFlightLimits,
+    deliveries: VecDeque<(DeliveryFuture, usize, Option<SinkPosition>)>,
+    bytes: usize,
+}
+
+impl InFlight {
+    fn new(limits: InFlightLimits) -> Self {
+        Self {
+            limits,
+            deliveries: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    fn push(&mut self, delivery: DeliveryFuture, bytes: usize, position: Option<SinkPosition>) {
+        self.bytes += bytes;
+        self.deliveries.push_back((delivery, bytes, position));
+    }
+
+    /// Wait for deliveries until a message of `bytes` fits within the limits
+    async fn reserve(&mut self, bytes: usize, metrics: &SinkMetrics) -> Result<()> {
+        while !self.limits.admits(self.deliveries.len(), self.bytes, bytes) {
+            self.settle_oldest(metrics).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the oldest delivery, `false` if none is in flight
+    async fn settle_oldest(&mut self, metrics: &SinkMetrics) -> Result<bool> {
+        let Some((delivery, bytes, position)) = self.deliveries.pop_front() else {
+            return Ok(false);
+        };
+        self.bytes -= bytes;
+        let timer = metrics.produce_time.timer();
+        let delivery = delivery.await;
+        timer.done();
+        metrics.report_delivery(delivery, bytes, position)?;
+        Ok(true)
+    }
+
+    async fn flush(&mut self, metrics: &SinkMetrics) -> Result<()> {
+        while self.settle_oldest(metrics).await? {}
+        Ok(())
     }
 }
 
@@ -96,35 +243,238 @@ impl DataSink for KafkaSink {
     }
 
     fn metrics(&self) -> Option<MetricsSet> {
-        None
+        Some(self.metrics.clone_inner())
     }
 
     async fn write_all(
+        &self,
+        data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let span = operator_span("kafka_sink", self.job.as_deref());
+        span.record("topic", self.config.topic.as_str());
+        self.write_batches(data, context).instrument(span).await
+    }
+}
+
+impl KafkaSink {
+    /// Every row is sent as a message of its own. Sends are pipelined across batches, up to
+    /// `max_in_flight_requests` messages and `max_buffer_bytes` bytes wait on delivery at a
+    /// time, past that the oldest deliveries are awaited before sending more. All deliveries
+    /// are awaited whenever the epoch moves on, so that the rows of an epoch were delivered
+    /// before the rows of the next one are sent. A failed delivery fails the write.
+    async fn write_batches(
         &self,
         mut data: SendableRecordBatchStream,
-        _context: &Arc<TaskContext>,
+        context: &Arc<TaskContext>,
     ) -> Result<u64> {
         let mut row_count = 0;
         let topic = self.config.topic.as_str();
+        let profiler = Profiler::from_task_context(context);
+        let quotas = ResourceQuotas::from_task_context(context);
+        let profile_stack = format!("KafkaSink[{topic}];encode");
+        let encoder = JsonRowEncoder::new(self.config.json_format);
+        let mut sequence =
+            EpochTracker::from_task_context(context).map(|tracker| tracker.sink_sequence());
+        // DataSinkExec writes its input as a single partition
+        let metrics = SinkMetrics::new(&self.metrics, 0);
+        let mut in_flight = InFlight::new(InFlightLimits {
+            max_requests: self.config.max_in_flight_requests,
+            max_bytes: self.config.max_buffer_bytes,
+        });
+        let mut epoch = None;
 
         while let Some(batch) = data.next().await.transpose()? {
             row_count += batch.num_rows();
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            let position = sequence.as_mut().map(|sequence| sequence.next_position());
+            let batch_epoch = position.map(|position| position.epoch);
+            if batch_epoch != epoch {
+                in_flight.flush(&metrics).await?;
+                epoch = batch_epoch;
+            }
+            let headers = self.batch_headers(position);
+            debug!(
+                rows = batch.num_rows(),
+                epoch = batch_epoch,
+                sequence = position.map(|position| position.sequence),
+                in_flight = in_flight.deliveries.len(),
+                "Writing batch"
+            );
 
-            let encoder = JsonRowEncoder {};
-            let rows = encoder.encode(&batch)?;
+            let records = RecordColumns::try_new(&self.config, &batch)?;
+            let payload = records.payload(&batch)?;
+            let rows = match &profiler {
+                Some(profiler) => profiler.measure(&profile_stack, || encoder.encode(&payload))?,
+                None => encoder.encode(&payload)?,
+            };
 
-            for row in rows {
-                let record = FutureRecord::<[u8], _>::to(topic).payload(&row);
-                // .key(key.as_str()),
+            for (index, row) in rows.iter().enumerate() {
+                let mut bytes = row.len();
+                let mut record = FutureRecord::<[u8], _>::to(topic).payload(row);
+                if let Some(key) = records.key(index) {
+                    bytes += key.len();
+                    record = record.key(key);
+                }
+                if let Some(partition) = records.partition(index) {
+                    record = record.partition(partition);
+                }
+                if let Some(headers) = records.headers(index, &headers) {
+                    record = record.headers(headers);
+                }
 
-                if let Err(msg) = self.producer.send(record, Duration::from_secs(0)).await {
-                    tracing::error!("{}", msg.0);
+                in_flight.reserve(bytes, &metrics).await?;
+                loop {
+                    match self.producer.send_result(record) {
+                        Ok(delivery) => {
+                            in_flight.push(delivery, bytes, position);
+                            break;
+                        }
+                        Err((
+                            KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull),
+                            queued,
+                        )) => {
+                            record = queued;
+                            if !in_flight.settle_oldest(&metrics).await? {
+                                tokio::time::sleep(Duration::from_millis(10)).await;
+                            }
+                        }
+                        Err((err, _)) => return Err(metrics.failed_delivery(&err, position)),
+                    }
                 }
             }
         }
+        in_flight.flush(&metrics).await?;
 
         Ok(row_count as u64)
     }
+
+    /// The static headers and the position of the batch, sent with every message
+    fn batch_headers(&self, position: Option<SinkPosition>) -> Option<OwnedHeaders> {
+        if self.config.headers.is_empty() && position.is_none() {
+            return None;
+        }
+        let mut headers = position.map_or_else(OwnedHeaders::new, position_headers);
+        for (key, value) in &self.config.headers {
+            headers = headers.insert(Header {
+                key,
+                value: Some(value),
+            });
+        }
+        Some(headers)
+    }
+}
+
+/// The values of a batch's key, partition and header columns
+struct RecordColumns<'a> {
+    config: &'a KafkaWriteConfig,
+    keys: Option<ArrayRef>,
+    partitions: Option<Int32Array>,
+    headers: Vec<(&'a str, StringArray)>,
+}
+
+impl<'a> RecordColumns<'a> {
+    fn try_new(config: &'a KafkaWriteConfig, batch: &RecordBatch) -> Result<Self> {
+        let column = |name: &str| match batch.column_by_name(name) {
+            Some(column) => Ok(column.clone()),
+            None => plan_err!(
+                "Column {name} not found in the rows written to {}",
+                config.topic
+            ),
+        };
+        let keys = match &config.key_column {
+            Some(name) => {
+                let keys = column(name)?;
+                Some(match keys.data_type() {
+                    DataType::Binary | DataType::LargeBinary => {
+                        cast(&keys, &DataType::LargeBinary)?
+                    }
+                    _ => cast(&keys, &DataType::LargeUtf8)?,
+                })
+            }
+            None => None,
+        };
+        let partitions = match &config.partition_column {
+            Some(name) => Some(
+                cast(&column(name)?, &DataType::Int32)?
+                    .as_primitive::<Int32Type>()
+                    .clone(),
+            ),
+            None => None,
+        };
+        let headers = config
+            .header_columns
+            .iter()
+            .map(|name| {
+                let values = cast(&column(name)?, &DataType::Utf8)?;
+                Ok((name.as_str(), values.as_string::<i32>().clone()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            config,
+            keys,
+            partitions,
+            headers,
+        })
+    }
+
+    /// The batch without the key, partition and header columns
+    fn payload(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        let indices: Vec<usize> = (0..batch.num_columns())
+            .filter(|index| {
+                let name = schema.field(*index).name();
+                !self.config.record_columns().any(|column| column == name)
+            })
+            .collect();
+        Ok(batch.project(&indices)?)
+    }
+
+    fn key(&self, row: usize) -> Option<&[u8]> {
+        let keys = self.keys.as_ref()?;
+        if keys.is_null(row) {
+            return None;
+        }
+        match keys.data_type() {
+            DataType::LargeBinary => Some(keys.as_binary::<i64>().value(row)),
+            _ => Some(keys.as_string::<i64>().value(row).as_bytes()),
+        }
+    }
+
+    fn partition(&self, row: usize) -> Option<i32> {
+        let partitions = self.partitions.as_ref()?;
+        partitions.is_valid(row).then(|| partitions.value(row))
+    }
+
+    fn headers(&self, row: usize, batch_headers: &Option<OwnedHeaders>) -> Option<OwnedHeaders> {
+        if self.headers.is_empty() {
+            return batch_headers.clone();
+        }
+        let mut headers = batch_headers.clone().unwrap_or_else(OwnedHeaders::new);
+        for (key, values) in &self.headers {
+            headers = headers.insert(Header {
+                key,
+                value: values.is_valid(row).then(|| values.value(row)),
+            });
+        }
+        Some(headers)
+    }
+}
+
+/// Lets consumers drop records they have already seen when a batch is written again after
+/// recovery
+fn position_headers(position: SinkPosition) -> OwnedHeaders {
+    OwnedHeaders::new()
+        .insert(Header {
+            key: "denormalized.epoch",
+            value: Some(&position.epoch.to_string()),
+        })
+        .insert(Header {
+            key: "denormalized.sequence",
+            value: Some(&position.sequence.to_string()),
+        })
 }
 
 impl Debug for KafkaSink {
@@ -145,3 +495,80 @@ impl DisplayAs for KafkaSink {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::{Field, Schema};
+
+    use crate::datasource::kafka::kafka_config::{
+        DEFAULT_MAX_BUFFER_BYTES, DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+    };
+    use crate::datasource::kafka::StreamEncoding;
+    use crate::physical_plan::utils::time::TimestampUnit;
+    use crate::utils::json_format::JsonFormatOptions;
+
+    #[test]
+    fn in_flight_limits() {
+        let limits = InFlightLimits {
+            max_requests: 2,
+            max_bytes: 100,
+        };
+        assert!(limits.admits(0, 0, 500));
+        assert!(limits.admits(1, 60, 40));
+        assert!(!limits.admits(1, 60, 41));
+        assert!(!limits.admits(2, 10, 10));
+    }
+
+    #[test]
+    fn split_record_columns() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("customer", DataType::Utf8, true),
+            Field::new(KAFKA_PARTITION_COLUMN, DataType::Int64, true),
+            Field::new("trace", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("a"), None])),
+                Arc::new(Int64Array::from(vec![Some(3), None])),
+                Arc::new(StringArray::from(vec![Some("t1"), None])),
+            ],
+        )?;
+        let config = KafkaWriteConfig {
+            topic: "orders".to_string(),
+            bootstrap_servers: "localhost:9092".to_string(),
+            schema,
+            encoding: StreamEncoding::Json,
+            json_format: JsonFormatOptions::default(),
+            partition_count: 4,
+            timestamp_column: "id".to_string(),
+            timestamp_unit: TimestampUnit::Int64Millis,
+            key_column: Some("customer".to_string()),
+            partition_column: Some(KAFKA_PARTITION_COLUMN.to_string()),
+            header_columns: vec!["trace".to_string()],
+            headers: vec![],
+            max_in_flight_requests: DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+            max_buffer_bytes: DEFAULT_MAX_BUFFER_BYTES,
+            kafka_connection_opts: Default::default(),
+        };
+        config.validate()?;
+
+        let records = RecordColumns::try_new(&config, &batch)?;
+        let payload = records.payload(&batch)?;
+        assert_eq!(payload.schema().fields().len(), 1);
+        assert_eq!(payload.schema().field(0).name(), "id");
+        assert_eq!(records.key(0), Some("a".as_bytes()));
+        assert_eq!(records.key(1), None);
+        assert_eq!(records.partition(0), Some(3));
+        assert_eq!(records.partition(1), None);
+        let headers = records.headers(0, &None).unwrap();
+        assert_eq!(headers.get(0).key, "trace");
+        assert_eq!(headers.get(0).value, Some("t1".as_bytes()));
+        Ok(())
+    }
+}
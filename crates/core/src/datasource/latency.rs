@@ -0,0 +1,346 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::AsArray;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Int64Type};
+use arrow_array::RecordBatch;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use datafusion::common::{internal_datafusion_err, plan_datafusion_err, DataFusionError, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+use futures::StreamExt;
+use hdrhistogram::serialization::{Serializer, V2Serializer};
+use hdrhistogram::Histogram;
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::datasource::message::now_ms;
+
+/// Column [`DataStream::sink_latency_report`](crate::datastream::DataStream::sink_latency_report)
+/// puts the event time of every row into
+pub const EVENT_TIME_COLUMN: &str = "_event_time";
+
+/// Latencies above an hour are all counted as an hour
+const MAX_TRACKED_LATENCY_MS: u64 = 60 * 60 * 1000;
+
+/// Measures how long rows take from their event time to reaching the sink, and how many
+/// arrive, instead of writing them anywhere. Meant as the sink of benchmark runs, so that
+/// pipelines are measured the same way.
+///
+/// Latencies go into an HDR histogram with three significant digits. When the stream ends,
+/// fails or the pipeline is dropped a [`LatencyReport`] is written to the report path as JSON,
+/// with percentiles for the whole run and for every interval of it.
+pub struct LatencySink {
+    path: PathBuf,
+    event_time_column: String,
+    interval: Duration,
+}
+
+impl LatencySink {
+    /// Reads event times in milliseconds from [`EVENT_TIME_COLUMN`] and reports every second
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            event_time_column: EVENT_TIME_COLUMN.to_string(),
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Integer or timestamp column holding the event time of a row in milliseconds
+    pub fn with_event_time_column(mut self, column: &str) -> Self {
+        self.event_time_column = column.to_string();
+        self
+    }
+
+    /// Length of the intervals throughput and latency are reported for
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// What a [`LatencySink`] measured, latencies in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyReport {
+    pub started_ms: i64,
+    pub finished_ms: i64,
+    pub rows: u64,
+    pub rows_per_second: f64,
+    pub latency_ms: LatencySummary,
+    /// The histogram of all latencies, base64 of its HdrHistogram V2 encoding, for merging
+    /// runs or plotting the full distribution
+    pub histogram: String,
+    /// Intervals the sink received nothing in have no latencies
+    pub intervals: Vec<IntervalReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntervalReport {
+    pub start_ms: i64,
+    pub rows: u64,
+    pub rows_per_second: f64,
+    pub latency_ms: LatencySummary,
+}
+
+/// Percentiles of the latencies of rows that had an event time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub min: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+impl LatencySummary {
+    fn of(histogram: &Histogram<u64>) -> Self {
+        if histogram.is_empty() {
+            return Self::default();
+        }
+        Self {
+            count: histogram.len(),
+            min: histogram.min(),
+            mean: histogram.mean(),
+            p50: histogram.value_at_quantile(0.5),
+            p90: histogram.value_at_quantile(0.9),
+            p99: histogram.value_at_quantile(0.99),
+            p999: histogram.value_at_quantile(0.999),
+            max: histogram.max(),
+        }
+    }
+}
+
+fn new_histogram() -> Result<Histogram<u64>> {
+    Histogram::new_with_bounds(1, MAX_TRACKED_LATENCY_MS, 3)
+        .map_err(|e| internal_datafusion_err!("Invalid latency histogram: {e:?}"))
+}
+
+/// Measurements of a single `write_all` call. Writes the report when dropped unless
+/// [`Self::write_report`] did.
+struct LatencyRecorder {
+    path: PathBuf,
+    interval_ms: i64,
+    started_ms: i64,
+    total: Histogram<u64>,
+    rows: u64,
+    current_start_ms: i64,
+    current_rows: u64,
+    current: Histogram<u64>,
+    intervals: Vec<IntervalReport>,
+    written: bool,
+}
+
+impl LatencyRecorder {
+    fn try_new(sink: &LatencySink, started_ms: i64) -> Result<Self> {
+        Ok(Self {
+            path: sink.path.clone(),
+            interval_ms: (sink.interval.as_millis() as i64).max(1),
+            started_ms,
+            total: new_histogram()?,
+            rows: 0,
+            current_start_ms: started_ms,
+            current_rows: 0,
+            current: new_histogram()?,
+            intervals: vec![],
+            written: false,
+        })
+    }
+
+    fn record(&mut self, batch: &RecordBatch, column: &str, now_ms: i64) -> Result<()> {
+        let event_times = batch
+            .column_by_name(column)
+            .ok_or_else(|| plan_datafusion_err!("No event time column {column}"))?;
+        let event_times = cast(event_times, &DataType::Int64)?;
+
+        self.roll_intervals(now_ms)?;
+        self.current_rows += batch.num_rows() as u64;
+        for event_time in event_times.as_primitive::<Int64Type>().iter().flatten() {
+            // Event times ahead of the clock count as no latency
+            self.current
+                .saturating_record((now_ms - event_time).max(0) as u64);
+        }
+        Ok(())
+    }
+
+    /// Close the intervals that ended before `now_ms`, quiet ones included
+    fn roll_intervals(&mut self, now_ms: i64) -> Result<()> {
+        while now_ms >= self.current_start_ms + self.interval_ms {
+            self.close_interval()?;
+            self.current_start_ms += self.interval_ms;
+        }
+        Ok(())
+    }
+
+    fn close_interval(&mut self) -> Result<()> {
+        self.total
+            .add(&self.current)
+            .map_err(|e| internal_datafusion_err!("Invalid latency histogram: {e:?}"))?;
+        self.rows += self.current_rows;
+        self.intervals.push(IntervalReport {
+            start_ms: self.current_start_ms,
+            rows: self.current_rows,
+            rows_per_second: self.current_rows as f64 * 1000.0 / self.interval_ms as f64,
+            latency_ms: LatencySummary::of(&self.current),
+        });
+        self.current_rows = 0;
+        self.current.reset();
+        Ok(())
+    }
+
+    fn report(&mut self, finished_ms: i64) -> Result<LatencyReport> {
+        self.roll_intervals(finished_ms)?;
+        if self.current_rows > 0 || !self.current.is_empty() {
+            self.close_interval()?;
+        }
+
+        let mut histogram = vec![];
+        V2Serializer::new()
+            .serialize(&self.total, &mut histogram)
+            .map_err(|e| internal_datafusion_err!("Failed to encode latencies: {e:?}"))?;
+        let seconds = ((finished_ms - self.started_ms).max(1)) as f64 / 1000.0;
+        Ok(LatencyReport {
+            started_ms: self.started_ms,
+            finished_ms,
+            rows: self.rows,
+            rows_per_second: self.rows as f64 / seconds,
+            latency_ms: LatencySummary::of(&self.total),
+            histogram: STANDARD.encode(histogram),
+            intervals: self.intervals.clone(),
+        })
+    }
+
+    fn write_report(&mut self) -> Result<LatencyReport> {
+        self.written = true;
+        let report = self.report(now_ms())?;
+        let json = serde_json::to_vec_pretty(&report)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        std::fs::write(&self.path, json)?;
+        Ok(report)
+    }
+}
+
+impl Drop for LatencyRecorder {
+    fn drop(&mut self) {
+        if self.written {
+            return;
+        }
+        if let Err(err) = self.write_report() {
+            error!("Failed to write latency report {:?}: {err}", self.path);
+        }
+    }
+}
+
+#[async_trait]
+impl DataSink for LatencySink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        _context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let mut recorder = LatencyRecorder::try_new(self, now_ms())?;
+        while let Some(batch) = data.next().await.transpose()? {
+            recorder.record(&batch, &self.event_time_column, now_ms())?;
+        }
+        Ok(recorder.write_report()?.rows)
+    }
+}
+
+impl Debug for LatencySink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LatencySink")
+            .field("path", &self.path)
+            .field("event_time_column", &self.event_time_column)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl DisplayAs for LatencySink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "LatencySink ({})", self.path.display())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::TimestampMillisecondArray;
+    use arrow_schema::{Field, Schema, TimeUnit};
+    use hdrhistogram::serialization::Deserializer;
+
+    #[test]
+    fn report_latencies_per_interval() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("latency_{}.json", std::process::id()));
+        let sink = LatencySink::new(&path).with_interval(Duration::from_millis(100));
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            EVENT_TIME_COLUMN,
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        )]));
+        let batch = |event_times: Vec<Option<i64>>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(TimestampMillisecondArray::from(event_times))],
+            )
+        };
+
+        let mut recorder = LatencyRecorder::try_new(&sink, 1_000)?;
+        recorder.record(
+            &batch(vec![Some(990), Some(900), None])?,
+            EVENT_TIME_COLUMN,
+            1_010,
+        )?;
+        // A quiet interval, then event times ahead of the clock
+        recorder.record(
+            &batch(vec![Some(1_250), Some(1_300)])?,
+            EVENT_TIME_COLUMN,
+            1_250,
+        )?;
+        let report = recorder.report(1_400)?;
+        recorder.written = true;
+
+        assert_eq!(report.rows, 5);
+        assert_eq!(report.rows_per_second, 12.5);
+        assert_eq!(report.latency_ms.count, 4);
+        assert_eq!((report.latency_ms.min, report.latency_ms.max), (0, 110));
+        let rows = report.intervals.iter().map(|i| i.rows).collect::<Vec<_>>();
+        assert_eq!(rows, [3, 0, 2, 0]);
+        assert_eq!(report.intervals[0].latency_ms.p50, 20);
+        assert_eq!(report.intervals[1].latency_ms, LatencySummary::default());
+
+        let encoded = STANDARD.decode(&report.histogram).unwrap();
+        let histogram: Histogram<u64> = Deserializer::new()
+            .deserialize(&mut encoded.as_slice())
+            .unwrap();
+        assert_eq!(histogram.len(), 4);
+
+        drop(LatencyRecorder::try_new(&sink, now_ms())?);
+        let written: LatencyReport = serde_json::from_slice(&std::fs::read(&path)?).unwrap();
+        assert_eq!(written.rows, 0);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use arrow_array::{RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{Schema, SchemaRef};
+use datafusion::common::{plan_err, Result};
+use futures::{Stream, StreamExt};
+use serde_json::Value;
+
+use crate::physical_plan::utils::metadata::{
+    stream_metadata_array_with_barrier, stream_metadata_field,
+};
+use crate::physical_plan::utils::stream_message::{barrier_marker, NO_BARRIER};
+use crate::physical_plan::utils::time::{array_to_timestamp_array, TimestampUnit};
+use crate::utils::arrow_helpers::json_records_to_arrow_record_batch;
+use crate::utils::determinism::logical_now_ms;
+use crate::utils::json_format::JsonFormatOptions;
+
+/// Rows a message source collects into a batch at most
+pub const MAX_BATCH_MESSAGES: usize = 1024;
+/// Time a message source waits for a batch to fill once its first message arrived
+pub const MAX_BATCH_WAIT: Duration = Duration::from_millis(100);
+
+/// Turns the JSON messages of a source into batches carrying the stream metadata. Sources
+/// other than Kafka share it, adding fields of their own, such as message attributes, to the
+/// payloads before decoding.
+#[derive(Debug, Clone)]
+pub struct JsonMessageDecoder {
+    schema: SchemaRef,
+    canonical_schema: SchemaRef,
+    json_format: JsonFormatOptions,
+    timestamp: Option<(String, TimestampUnit)>,
+}
+
+impl JsonMessageDecoder {
+    /// Decode messages into `schema`. Event times are read from the timestamp column if given,
+    /// otherwise the arrival time of a message is its event time.
+    pub fn try_new(
+        schema: SchemaRef,
+        json_format: JsonFormatOptions,
+        timestamp: Option<(String, TimestampUnit)>,
+    ) -> Result<Self> {
+        if let Some((column, _)) = &timestamp {
+            if schema.field_with_name(column).is_err() {
+                return plan_err!("Timestamp column {column} not found in the message schema");
+            }
+        }
+        let mut fields = schema.fields().to_vec();
+        fields.push(Arc::new(stream_metadata_field()));
+        Ok(Self {
+            canonical_schema: Arc::new(Schema::new(fields)),
+            schema,
+            json_format,
+            timestamp,
+        })
+    }
+
+    /// The schema of the decoded batches, including the metadata column
+    pub fn schema(&self) -> SchemaRef {
+        self.canonical_schema.clone()
+    }
+
+    pub fn decode(&self, messages: Vec<Value>, arrival_ms: i64) -> Result<RecordBatch> {
+        self.decode_with_barrier(messages, arrival_ms, NO_BARRIER)
+    }
+
+    /// Decode messages whose batch closes the checkpoint `epoch`
+    pub fn decode_checkpointed(
+        &self,
+        messages: Vec<Value>,
+        arrival_ms: i64,
+        epoch: u64,
+    ) -> Result<RecordBatch> {
+        self.decode_with_barrier(messages, arrival_ms, &barrier_marker(epoch))
+    }
+
+    fn decode_with_barrier(
+        &self,
+        messages: Vec<Value>,
+        arrival_ms: i64,
+        barrier: &str,
+    ) -> Result<RecordBatch> {
+        let batch = json_records_to_arrow_record_batch(
+            &messages,
+            self.json_format.decode_schema(&self.schema),
+        )?;
+        let batch = self.json_format.decode_batch(batch, &self.schema)?;
+        self.attach_metadata(batch, arrival_ms, barrier)
+    }
+
+    /// The schema of the messages, without the metadata column
+    pub fn message_schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// How values without a native JSON representation are read from the messages
+    pub fn json_format(&self) -> JsonFormatOptions {
+        self.json_format
+    }
+
+    /// Add the metadata column to messages a source decoded itself into
+    /// [`Self::message_schema`]
+    pub fn decode_batch(&self, batch: RecordBatch, arrival_ms: i64) -> Result<RecordBatch> {
+        self.attach_metadata(batch, arrival_ms, NO_BARRIER)
+    }
+
+    /// Like [`Self::decode_batch`], for a batch that closes the checkpoint `epoch`
+    pub fn decode_batch_checkpointed(
+        &self,
+        batch: RecordBatch,
+        arrival_ms: i64,
+        epoch: u64,
+    ) -> Result<RecordBatch> {
+        self.attach_metadata(batch, arrival_ms, &barrier_marker(epoch))
+    }
+
+    fn attach_metadata(
+        &self,
+        batch: RecordBatch,
+        arrival_ms: i64,
+        barrier: &str,
+    ) -> Result<RecordBatch> {
+        let rows = batch.num_rows();
+        let timestamps = match &self.timestamp {
+            Some((column, unit)) => {
+                array_to_timestamp_array(batch.column_by_name(column).unwrap(), unit.clone())
+            }
+            None => TimestampMillisecondArray::from(vec![arrival_ms; rows]),
+        };
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(stream_metadata_array_with_barrier(
+            timestamps, barrier,
+        )));
+        Ok(RecordBatch::try_new(
+            self.canonical_schema.clone(),
+            columns,
+        )?)
+    }
+}
+
+/// Milliseconds since the unix epoch, on the logical clock in deterministic mode
+pub fn now_ms() -> i64 {
+    if let Some(now) = logical_now_ms() {
+        return now;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as i64)
+}
+
+/// Wait for the next item of `stream`, then collect whatever else arrives within
+/// [`MAX_BATCH_WAIT`], up to [`MAX_BATCH_MESSAGES`] items. `None` once the stream ended.
+pub async fn next_chunk<S: Stream + Unpin>(stream: &mut S) -> Option<Vec<S::Item>> {
+    let mut chunk = vec![stream.next().await?];
+    let deadline = tokio::time::Instant::now() + MAX_BATCH_WAIT;
+    while chunk.len() < MAX_BATCH_MESSAGES {
+        match tokio::time::timeout_at(deadline, stream.next()).await {
+            Ok(Some(item)) => chunk.push(item),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    Some(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, StringArray};
+    use arrow_schema::{DataType, Field};
+
+    use crate::physical_plan::utils::stream_message::StreamMessage;
+    use crate::utils::row_encoder::{JsonRowEncoder, RowEncoder};
+
+    #[test]
+    fn decode_encoded_rows() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("occurred_at_ms", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(Int64Array::from(vec![2000, 1000])),
+            ],
+        )?;
+        let messages: Vec<Value> = JsonRowEncoder::default()
+            .encode(&batch)?
+            .iter()
+            .map(|row| serde_json::from_slice(row).unwrap())
+            .collect();
+
+        let decoder = JsonMessageDecoder::try_new(
+            schema,
+            JsonFormatOptions::default(),
+            Some(("occurred_at_ms".to_string(), TimestampUnit::Int64Millis)),
+        )?;
+        let decoded = decoder.decode_checkpointed(messages, 5000, 7)?;
+        assert_eq!(decoded.schema(), decoder.schema());
+        assert_eq!(decoded.project(&[0, 1])?, batch);
+
+        // Event times come from the timestamp column rather than the arrival time
+        let messages = StreamMessage::from_batch(decoded)?;
+        assert!(matches!(
+            messages[1],
+            StreamMessage::Watermark(watermark)
+                if watermark == UNIX_EPOCH + Duration::from_millis(1000)
+        ));
+        assert!(matches!(messages[2], StreamMessage::Barrier(7)));
+        Ok(())
+    }
+}
@@ -1 +1,44 @@
+#[cfg(feature = "amqp")]
+pub mod amqp;
+pub mod catalog_sync;
+pub mod changelog;
+pub mod delta;
+pub mod epoch;
+pub mod failover;
+pub mod file_sink;
+pub mod file_watch;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "http")]
+pub mod http_poll;
 pub mod kafka;
+pub mod latency;
+pub mod message;
+#[cfg(feature = "mongodb")]
+pub mod mongodb;
+#[cfg(feature = "mysql")]
+pub mod mysql_cdc;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "pubsub")]
+pub mod pubsub;
+#[cfg(any(feature = "websocket", feature = "sse"))]
+pub mod push;
+#[cfg(feature = "redis")]
+pub mod redis_reference;
+pub mod router;
+pub mod schema_registry;
+pub mod segment_queue;
+pub mod side_output;
+pub mod sink;
+pub mod socket;
+#[cfg(feature = "sse")]
+pub mod sse;
+pub mod statsd;
+pub mod syslog;
+pub mod tee;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+pub mod write_audit_publish;
@@ -0,0 +1,323 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use base64::{engine::general_purpose, Engine as _};
+use datafusion::catalog::Session;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::debug;
+use mongodb::bson::{Bson, Document};
+use mongodb::change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken};
+use mongodb::options::FullDocumentType;
+use mongodb::{Client, Collection};
+use serde_json::{Map, Value};
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::changelog::{ChangeType, CHANGE_TYPE_COLUMN};
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::message::{next_chunk, now_ms, JsonMessageDecoder};
+use crate::state_backend::backend::get_global_state_backend;
+use crate::utils::json_format::JsonFormatOptions;
+
+const RESUME_TOKEN_KEY: &[u8] = b"resume_token";
+
+/// Follows a MongoDB collection through a change stream, as a changelog of the fields of
+/// `schema` and [`CHANGE_TYPE_COLUMN`], registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// Documents are decoded by field name, embedded documents into struct columns and arrays
+/// into list columns. Object ids come out as hex strings, dates as milliseconds since the
+/// epoch, decimals as strings and binary data base64 encoded. Inserts are inserts of the
+/// document, updates and replacements a delete of the document key followed by an insert of
+/// the current document, and deletes a delete of the document key. Delete rows only carry
+/// `_id`, change streams don't have the old document. Rows take the time they were received
+/// as event time.
+///
+/// The stream needs a replica set or sharded cluster. With checkpointing enabled every batch
+/// closes an epoch and the resume token after it is kept in the checkpoint, a restored source
+/// resumes the stream after the last change it emitted. The source ends when the stream is
+/// invalidated, e.g. because the collection was dropped.
+pub struct MongoChangeStreamSource {
+    collection: Collection<Document>,
+    decoder: JsonMessageDecoder,
+}
+
+impl MongoChangeStreamSource {
+    pub async fn try_new(
+        uri: &str,
+        database: &str,
+        collection: &str,
+        schema: SchemaRef,
+    ) -> Result<Self> {
+        let client = Client::with_uri_str(uri).await.map_err(mongo_error)?;
+        let mut fields = schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            CHANGE_TYPE_COLUMN,
+            DataType::Utf8,
+            false,
+        )));
+        let decoder = JsonMessageDecoder::try_new(
+            Arc::new(Schema::new(fields)),
+            JsonFormatOptions::default(),
+            None,
+        )?;
+        Ok(Self {
+            collection: client.database(database).collection(collection),
+            decoder,
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for MongoChangeStreamSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = MongoChangeStreamPartition {
+            collection: self.collection.clone(),
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct MongoChangeStreamPartition {
+    collection: Collection<Document>,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for MongoChangeStreamPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let collection = self.collection.clone();
+        let decoder = self.decoder.clone();
+
+        builder.spawn(async move {
+            let backend = if should_checkpoint {
+                let backend = get_global_state_backend()?;
+                let namespace = format!("mongodb_source_{}", collection.namespace());
+                backend.ensure_namespace(&namespace)?;
+                Some((backend, namespace))
+            } else {
+                None
+            };
+            let resume_token: Option<ResumeToken> = match &backend {
+                Some((backend, namespace)) => backend
+                    .get_state(namespace, RESUME_TOKEN_KEY.to_vec())?
+                    .map(|bytes| serde_json::from_slice(&bytes))
+                    .transpose()
+                    .map_err(|err| DataFusionError::External(Box::new(err)))?,
+                None => None,
+            };
+
+            let mut watch = collection
+                .watch()
+                .full_document(FullDocumentType::UpdateLookup);
+            if let Some(resume_token) = resume_token {
+                debug!("Resuming the change stream of {}", collection.namespace());
+                watch = watch.resume_after(resume_token);
+            }
+            let mut changes = watch.await.map_err(mongo_error)?;
+
+            let mut epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current());
+            while let Some(chunk) = next_chunk(&mut changes).await {
+                let mut messages = vec![];
+                let mut invalidated = false;
+                for event in chunk {
+                    let event = event.map_err(mongo_error)?;
+                    invalidated |= event.operation_type == OperationType::Invalidate;
+                    messages.extend(change_messages(event));
+                }
+
+                if !messages.is_empty() {
+                    let batch = if should_checkpoint {
+                        epoch += 1;
+                        if let Some(tracker) = &epoch_tracker {
+                            tracker.advance(epoch)?;
+                        }
+                        decoder.decode_checkpointed(messages, now_ms(), epoch)?
+                    } else {
+                        decoder.decode(messages, now_ms())?
+                    };
+                    if tx.send(Ok(batch)).await.is_err() {
+                        break;
+                    }
+                }
+                if let (Some((backend, namespace)), Some(token)) =
+                    (&backend, changes.resume_token())
+                {
+                    let token = serde_json::to_vec(&token)
+                        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+                    backend.put_state(namespace, RESUME_TOKEN_KEY.to_vec(), token)?;
+                }
+                if invalidated {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+/// The changelog rows of a change event, none for events that don't change documents
+fn change_messages(event: ChangeStreamEvent<Document>) -> Vec<Value> {
+    let change_row = |document: &Document, change: ChangeType| {
+        let mut row = document_to_json(document);
+        row.insert(CHANGE_TYPE_COLUMN.to_string(), Value::from(change.as_str()));
+        Value::Object(row)
+    };
+
+    let mut messages = vec![];
+    match event.operation_type {
+        OperationType::Insert => {
+            if let Some(document) = &event.full_document {
+                messages.push(change_row(document, ChangeType::Insert));
+            }
+        }
+        OperationType::Update | OperationType::Replace => {
+            if let Some(key) = &event.document_key {
+                messages.push(change_row(key, ChangeType::Delete));
+            }
+            // Missing if the document was deleted before the update was looked up
+            if let Some(document) = &event.full_document {
+                messages.push(change_row(document, ChangeType::Insert));
+            }
+        }
+        OperationType::Delete => {
+            if let Some(key) = &event.document_key {
+                messages.push(change_row(key, ChangeType::Delete));
+            }
+        }
+        _ => {}
+    }
+    messages
+}
+
+fn document_to_json(document: &Document) -> Map<String, Value> {
+    document
+        .iter()
+        .map(|(key, value)| (key.clone(), bson_to_json(value)))
+        .collect()
+}
+
+/// JSON the Arrow decoder reads into plain columns, unlike MongoDB extended JSON
+fn bson_to_json(value: &Bson) -> Value {
+    match value {
+        Bson::Null | Bson::Undefined => Value::Null,
+        Bson::Boolean(value) => Value::from(*value),
+        Bson::Int32(value) => Value::from(*value),
+        Bson::Int64(value) => Value::from(*value),
+        Bson::Double(value) => Value::from(*value),
+        Bson::String(value) | Bson::Symbol(value) | Bson::JavaScriptCode(value) => {
+            Value::from(value.as_str())
+        }
+        Bson::ObjectId(id) => Value::from(id.to_hex()),
+        Bson::DateTime(date) => Value::from(date.timestamp_millis()),
+        Bson::Timestamp(timestamp) => Value::from(timestamp.time as i64 * 1000),
+        Bson::Decimal128(decimal) => Value::from(decimal.to_string()),
+        Bson::Binary(binary) => Value::from(general_purpose::STANDARD.encode(&binary.bytes)),
+        Bson::Array(values) => Value::Array(values.iter().map(bson_to_json).collect()),
+        Bson::Document(document) => Value::Object(document_to_json(document)),
+        other => other.clone().into_relaxed_extjson(),
+    }
+}
+
+fn mongo_error(err: mongodb::error::Error) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::oid::ObjectId;
+    use mongodb::bson::{doc, DateTime};
+
+    #[test]
+    fn convert_documents() {
+        let id = ObjectId::parse_str("65f1c0ffee0000000000abcd").unwrap();
+        let document = doc! {
+            "_id": id,
+            "placed_at": DateTime::from_millis(1_700_000_000_000),
+            "customer": {"name": "Ada", "tags": ["vip"]},
+            "total": 12.5,
+        };
+        let json = Value::Object(document_to_json(&document));
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "_id": "65f1c0ffee0000000000abcd",
+                "placed_at": 1_700_000_000_000_i64,
+                "customer": {"name": "Ada", "tags": ["vip"]},
+                "total": 12.5,
+            })
+        );
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("_id", DataType::Utf8, false),
+            Field::new(
+                "customer",
+                DataType::Struct(vec![Field::new("name", DataType::Utf8, true)].into()),
+                true,
+            ),
+            Field::new(CHANGE_TYPE_COLUMN, DataType::Utf8, false),
+        ]));
+        let decoder =
+            JsonMessageDecoder::try_new(schema, JsonFormatOptions::default(), None).unwrap();
+        let mut row = document_to_json(&document);
+        row.insert(CHANGE_TYPE_COLUMN.to_string(), Value::from("insert"));
+        let batch = decoder.decode(vec![Value::Object(row)], 0).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(
+            batch.column(1).data_type(),
+            &DataType::Struct(vec![Field::new("name", DataType::Utf8, true)].into())
+        );
+    }
+}
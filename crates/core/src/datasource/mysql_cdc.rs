@@ -0,0 +1,517 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use datafusion::catalog::Session;
+use datafusion::common::{exec_err, plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use futures::StreamExt;
+use log::debug;
+use mysql_async::binlog::events::EventData;
+use mysql_async::binlog::row::BinlogRow;
+use mysql_async::binlog::value::BinlogValue;
+use mysql_async::prelude::Queryable;
+use mysql_async::{BinlogStreamRequest, Conn, GnoInterval, Opts, Sid};
+use serde_json::{Map, Value};
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::changelog::{ChangeType, CHANGE_TYPE_COLUMN};
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::message::JsonMessageDecoder;
+use crate::state_backend::backend::get_global_state_backend;
+use crate::utils::json_format::JsonFormatOptions;
+
+const GTID_SET_KEY: &[u8] = b"gtid_executed";
+const EPOCH_KEY: &[u8] = b"epoch";
+
+/// Reads the row changes of a MySQL table from the binary log as a changelog of the columns
+/// of `schema` and [`CHANGE_TYPE_COLUMN`], registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// The server needs `binlog_format=ROW`, `binlog_row_metadata=FULL`, so that rows carry their
+/// column names, and GTIDs enabled. The source connects as a replica with `server_id`, which
+/// has to be unique among the replicas of the server. Every committed transaction becomes one
+/// batch with the commit time as event time, updates come out as a delete of the old row
+/// followed by an insert of the new one. Columns are matched by name, columns of the table
+/// missing from `schema` are ignored. String and binary columns of the table are read as
+/// they are declared in `schema`.
+///
+/// The first run starts at the transactions executed by then, without a snapshot of the
+/// table. With checkpointing enabled every transaction closes an epoch and the set of
+/// transactions read is kept in the checkpoint, a restored source continues after the last
+/// of them.
+pub struct MySqlCdcSource {
+    opts: Opts,
+    server_id: u32,
+    database: String,
+    table: String,
+    decoder: JsonMessageDecoder,
+}
+
+impl MySqlCdcSource {
+    /// Follow `table`, given as `database.table`, of the server at `url`
+    pub fn try_new(url: &str, server_id: u32, table: &str, schema: SchemaRef) -> Result<Self> {
+        let opts = Opts::from_url(url).map_err(mysql_error)?;
+        let Some((database, table)) = table.split_once('.') else {
+            return plan_err!("Expected a table as database.table, got {table}");
+        };
+        let mut fields = schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            CHANGE_TYPE_COLUMN,
+            DataType::Utf8,
+            false,
+        )));
+        let decoder = JsonMessageDecoder::try_new(
+            Arc::new(Schema::new(fields)),
+            JsonFormatOptions::default(),
+            None,
+        )?;
+        Ok(Self {
+            opts,
+            server_id,
+            database: database.to_string(),
+            table: table.to_string(),
+            decoder,
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for MySqlCdcSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = MySqlCdcPartition {
+            opts: self.opts.clone(),
+            server_id: self.server_id,
+            database: self.database.clone(),
+            table: self.table.clone(),
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct MySqlCdcPartition {
+    opts: Opts,
+    server_id: u32,
+    database: String,
+    table: String,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for MySqlCdcPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let opts = self.opts.clone();
+        let server_id = self.server_id;
+        let (database, table) = (self.database.clone(), self.table.clone());
+        let decoder = self.decoder.clone();
+
+        builder.spawn(async move {
+            let backend = if should_checkpoint {
+                let backend = get_global_state_backend()?;
+                let namespace = format!("mysql_cdc_source_{database}.{table}");
+                backend.ensure_namespace(&namespace)?;
+                Some((backend, namespace))
+            } else {
+                None
+            };
+            let restored = match &backend {
+                Some((backend, namespace)) => backend
+                    .get_state(namespace, GTID_SET_KEY.to_vec())?
+                    .map(|bytes| GtidSet::parse(&String::from_utf8_lossy(&bytes)))
+                    .transpose()?,
+                None => None,
+            };
+            let restored_epoch = match &backend {
+                Some((backend, namespace)) => {
+                    match backend.get_state(namespace, EPOCH_KEY.to_vec())? {
+                        Some(bytes) => {
+                            let bytes: [u8; 8] = bytes.try_into().map_err(|_| {
+                                DataFusionError::Internal(format!(
+                                    "Invalid epoch stored for {database}.{table}"
+                                ))
+                            })?;
+                            u64::from_le_bytes(bytes)
+                        }
+                        None => 0,
+                    }
+                }
+                None => 0,
+            };
+
+            let mut conn = Conn::new(opts).await.map_err(mysql_error)?;
+            let mut executed = match restored {
+                Some(executed) => executed,
+                None => {
+                    let executed: Option<String> = conn
+                        .query_first("SELECT @@GLOBAL.gtid_executed")
+                        .await
+                        .map_err(mysql_error)?;
+                    GtidSet::parse(&executed.unwrap_or_default())?
+                }
+            };
+            debug!("Reading the binlog of {database}.{table} after {executed}");
+            let request = BinlogStreamRequest::new(server_id)
+                .with_gtid()
+                .with_gtid_set(executed.sids()?);
+            let mut binlog = conn.get_binlog_stream(request).await.map_err(mysql_error)?;
+
+            // Carry on after the epochs of earlier runs, whether or not the pipeline tracks them
+            let mut epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current())
+                .max(restored_epoch);
+            let message_schema = decoder.message_schema();
+            let json_format = decoder.json_format();
+            let mut transaction: Option<(String, u64)> = None;
+            let mut changes: Vec<Value> = vec![];
+            while let Some(event) = binlog.next().await {
+                let event = event.map_err(mysql_error)?;
+                let commit_ms = event.header().timestamp() as i64 * 1000;
+                let committed = match event.read_data()? {
+                    Some(EventData::GtidEvent(gtid)) => {
+                        transaction = Some((format_uuid(&gtid.sid()), gtid.gno()));
+                        false
+                    }
+                    Some(EventData::RowsEvent(rows)) => {
+                        let Some(tme) = binlog.get_tme(rows.table_id()) else {
+                            return exec_err!("Rows event without a table map");
+                        };
+                        if tme.database_name() == database && tme.table_name() == table {
+                            for row in rows.rows(tme) {
+                                let (before, after) = row?;
+                                for (image, change) in
+                                    [(before, ChangeType::Delete), (after, ChangeType::Insert)]
+                                {
+                                    if let Some(image) = image {
+                                        changes.push(row_change(
+                                            &image,
+                                            change,
+                                            &message_schema,
+                                            &json_format,
+                                        )?);
+                                    }
+                                }
+                            }
+                        }
+                        false
+                    }
+                    Some(EventData::XidEvent(_)) => true,
+                    // DDL and transactions of non transactional tables end with a query
+                    Some(EventData::QueryEvent(query)) => query.query() != "BEGIN",
+                    _ => false,
+                };
+                if !committed {
+                    continue;
+                }
+
+                if !changes.is_empty() {
+                    let messages = std::mem::take(&mut changes);
+                    let batch = if should_checkpoint {
+                        epoch += 1;
+                        if let Some(tracker) = &epoch_tracker {
+                            tracker.advance(epoch)?;
+                        }
+                        decoder.decode_checkpointed(messages, commit_ms, epoch)?
+                    } else {
+                        decoder.decode(messages, commit_ms)?
+                    };
+                    if tx.send(Ok(batch)).await.is_err() {
+                        break;
+                    }
+                }
+                if let Some((sid, gno)) = transaction.take() {
+                    executed.add(sid, gno);
+                    if let Some((backend, namespace)) = &backend {
+                        backend.put_state(
+                            namespace,
+                            GTID_SET_KEY.to_vec(),
+                            executed.to_string().into_bytes(),
+                        )?;
+                        backend.put_state(
+                            namespace,
+                            EPOCH_KEY.to_vec(),
+                            epoch.to_le_bytes().to_vec(),
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+/// The columns of a row image as a JSON message, with its change type
+fn row_change(
+    row: &BinlogRow,
+    change: ChangeType,
+    schema: &Schema,
+    json_format: &JsonFormatOptions,
+) -> Result<Value> {
+    let mut message = Map::new();
+    for (index, column) in row.columns_ref().iter().enumerate() {
+        let name = column.name_str();
+        if name.is_empty() {
+            return exec_err!(
+                "Binlog rows carry no column names, binlog_row_metadata must be FULL"
+            );
+        }
+        let data_type = schema
+            .field_with_name(&name)
+            .ok()
+            .map(|field| field.data_type());
+        let value = match row.as_ref(index) {
+            Some(value) => binlog_value(value, data_type, json_format)?,
+            None => Value::Null,
+        };
+        message.insert(name.to_string(), value);
+    }
+    message.insert(CHANGE_TYPE_COLUMN.to_string(), Value::from(change.as_str()));
+    Ok(Value::Object(message))
+}
+
+/// `value` as the JSON the decoder reads into `data_type`, the type of its column in the schema
+fn binlog_value(
+    value: &BinlogValue,
+    data_type: Option<&DataType>,
+    json_format: &JsonFormatOptions,
+) -> Result<Value> {
+    use mysql_async::Value as MySqlValue;
+    Ok(match value {
+        BinlogValue::Value(value) => match value {
+            MySqlValue::NULL => Value::Null,
+            MySqlValue::Int(value) => Value::from(*value),
+            MySqlValue::UInt(value) => Value::from(*value),
+            MySqlValue::Float(value) => Value::from(*value),
+            MySqlValue::Double(value) => Value::from(*value),
+            // Strings, decimals and binary data
+            MySqlValue::Bytes(bytes) => match data_type {
+                Some(DataType::Binary | DataType::LargeBinary) => {
+                    Value::from(json_format.encode_binary_value(bytes))
+                }
+                _ => Value::from(String::from_utf8_lossy(bytes)),
+            },
+            MySqlValue::Date(year, month, day, hour, minute, second, micros) => {
+                Value::from(format!(
+                    "{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}.{micros:06}"
+                ))
+            }
+            MySqlValue::Time(negative, days, hours, minutes, seconds, micros) => {
+                let sign = if *negative { "-" } else { "" };
+                let hours = *days * 24 + *hours as u32;
+                Value::from(format!(
+                    "{sign}{hours:02}:{minutes:02}:{seconds:02}.{micros:06}"
+                ))
+            }
+        },
+        BinlogValue::Jsonb(json) => {
+            Value::try_from(json.clone()).map_err(|err| DataFusionError::External(Box::new(err)))?
+        }
+        BinlogValue::JsonDiff(_) => {
+            return exec_err!(
+                "Partial JSON updates are not supported, binlog_row_value_options must be empty"
+            )
+        }
+    })
+}
+
+/// The 16 bytes of a source id as the UUID MySQL prints
+fn format_uuid(sid: &[u8; 16]) -> String {
+    let hex = hex::encode(sid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Transaction numbers executed per source id, as inclusive intervals
+#[derive(Debug, Default, PartialEq)]
+struct GtidSet {
+    intervals: BTreeMap<String, Vec<(u64, u64)>>,
+}
+
+impl GtidSet {
+    /// Parse the text form, e.g. `3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:7`
+    fn parse(text: &str) -> Result<Self> {
+        let mut set = Self::default();
+        for sid_set in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut parts = sid_set.split(':');
+            let sid = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let intervals = set.intervals.entry(sid).or_default();
+            for interval in parts {
+                let (start, end) = interval.split_once('-').unwrap_or((interval, interval));
+                match (start.parse(), end.parse()) {
+                    (Ok(start), Ok(end)) => intervals.push((start, end)),
+                    _ => return plan_err!("Invalid GTID set {text}"),
+                }
+            }
+        }
+        Ok(set)
+    }
+
+    fn add(&mut self, sid: String, gno: u64) {
+        let intervals = self.intervals.entry(sid).or_default();
+        intervals.push((gno, gno));
+        intervals.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+        for (start, end) in intervals.drain(..) {
+            match merged.last_mut() {
+                Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        *intervals = merged;
+    }
+
+    /// The set as sent with a binlog dump request
+    fn sids(&self) -> Result<Vec<Sid<'static>>> {
+        self.intervals
+            .iter()
+            .map(|(sid, intervals)| {
+                let bytes: [u8; 16] = hex::decode(sid.replace('-', ""))
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| {
+                        DataFusionError::Plan(format!("Invalid GTID source id {sid}"))
+                    })?;
+                Ok(intervals.iter().fold(Sid::new(bytes), |sid, (start, end)| {
+                    // Intervals of the protocol exclude their end
+                    sid.with_interval(GnoInterval::new(*start, end + 1))
+                }))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for GtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sid_sets: Vec<String> = self
+            .intervals
+            .iter()
+            .map(|(sid, intervals)| {
+                let mut text = sid.clone();
+                for (start, end) in intervals {
+                    if start == end {
+                        text.push_str(&format!(":{start}"));
+                    } else {
+                        text.push_str(&format!(":{start}-{end}"));
+                    }
+                }
+                text
+            })
+            .collect();
+        write!(f, "{}", sid_sets.join(","))
+    }
+}
+
+fn mysql_error(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::AsArray;
+
+    #[test]
+    fn track_gtid_sets() {
+        let sid = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+        let mut set = GtidSet::parse(&format!("{sid}:1-5:7, ")).unwrap();
+        set.add(sid.to_string(), 6);
+        set.add(sid.to_string(), 9);
+        set.add(sid.to_string(), 3);
+        assert_eq!(set.to_string(), format!("{sid}:1-7:9"));
+        assert_eq!(GtidSet::parse(&set.to_string()).unwrap(), set);
+        assert_eq!(set.sids().unwrap().len(), 1);
+
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hex::decode(sid.replace('-', "")).unwrap());
+        assert_eq!(format_uuid(&bytes), sid);
+
+        assert!(GtidSet::parse(&format!("{sid}:x-2")).is_err());
+        assert!(GtidSet::parse("").unwrap().sids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn keep_binary_columns() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("payload", DataType::Binary, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let decoder = JsonMessageDecoder::try_new(schema, JsonFormatOptions::default(), None)?;
+        let bytes = vec![0xff, 0x00, 0xfe];
+        let value = |data_type: &DataType| {
+            binlog_value(
+                &BinlogValue::Value(mysql_async::Value::Bytes(bytes.clone())),
+                Some(data_type),
+                &decoder.json_format(),
+            )
+        };
+        let message = serde_json::json!({
+            "payload": value(&DataType::Binary)?,
+            "name": value(&DataType::Utf8)?,
+        });
+
+        let batch = decoder.decode(vec![message], 0)?;
+        assert_eq!(batch.column(0).as_binary::<i32>().value(0), bytes);
+        assert_eq!(
+            batch.column(1).as_string::<i32>().value(0),
+            "\u{fffd}\0\u{fffd}"
+        );
+        Ok(())
+    }
+}
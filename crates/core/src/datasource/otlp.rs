@@ -0,0 +1,976 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, Weak};
+
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, Int32Builder, ListBuilder, MapBuilder, StringBuilder,
+    TimestampNanosecondBuilder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use datafusion::catalog::Session;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::error;
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{
+    LogsService, LogsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{
+    MetricsService, MetricsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::{
+    TraceService, TraceServiceServer,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::metrics::v1::{metric, number_data_point};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use opentelemetry_proto::tonic::trace::v1::{span, status};
+use prost::Message;
+use tokio::sync::mpsc;
+
+use crate::datasource::message::now_ms;
+use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+use crate::utils::arrow_helpers::string_map_type;
+
+/// Requests buffered per signal before exporters are slowed down
+const REQUEST_BUFFER: usize = 16;
+
+/// The kind of telemetry a table of an [`OtlpReceiver`] holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OtlpSignal {
+    /// One row per span
+    Traces,
+    /// One row per data point
+    Metrics,
+    /// One row per log record
+    Logs,
+}
+
+/// Receives telemetry from OpenTelemetry exporters and collectors over OTLP, on gRPC and on
+/// HTTP with protobuf payloads, and exposes each signal as a stream.
+///
+/// The receiver listens while any of its sources is being read. Exports of signals no source
+/// reads are refused, so that exporters keep them for another receiver rather than losing
+/// them. Every export request becomes one batch, the rows of which take their event time from
+/// the span start, data point or log record time.
+#[derive(Debug, Clone)]
+pub struct OtlpReceiver {
+    state: Arc<ReceiverState>,
+}
+
+#[derive(Debug)]
+struct ReceiverState {
+    grpc_address: Option<String>,
+    http_address: Option<String>,
+    readers: Readers,
+    servers: tokio::sync::Mutex<Weak<Servers>>,
+}
+
+type Readers = Arc<Mutex<HashMap<OtlpSignal, mpsc::Sender<Result<RecordBatch>>>>>;
+
+struct Servers {
+    _tasks: Vec<SpawnedTask<()>>,
+}
+
+impl OtlpReceiver {
+    /// Listen for gRPC exports on `grpc_address` and HTTP exports on `http_address`, usually
+    /// port 4317 and 4318
+    pub fn new(grpc_address: Option<&str>, http_address: Option<&str>) -> Self {
+        Self {
+            state: Arc::new(ReceiverState {
+                grpc_address: grpc_address.map(str::to_string),
+                http_address: http_address.map(str::to_string),
+                readers: Readers::default(),
+                servers: tokio::sync::Mutex::new(Weak::new()),
+            }),
+        }
+    }
+
+    /// A source streaming the telemetry of `signal`, to register with
+    /// [`Context::from_source`](crate::context::Context::from_source)
+    pub fn source(&self, signal: OtlpSignal) -> OtlpSource {
+        OtlpSource {
+            receiver: self.clone(),
+            signal,
+            schema: signal_schema(signal),
+        }
+    }
+
+    /// Start listening unless a source of the receiver already did. The servers stop once the
+    /// last source holding them ends.
+    async fn start(&self) -> Result<Arc<Servers>> {
+        let mut servers = self.state.servers.lock().await;
+        if let Some(running) = servers.upgrade() {
+            return Ok(running);
+        }
+
+        let handler = ExportHandler {
+            readers: self.state.readers.clone(),
+        };
+        let mut tasks = vec![];
+        if let Some(address) = &self.state.grpc_address {
+            let address = resolve(address).await?;
+            let handler = handler.clone();
+            tasks.push(SpawnedTask::spawn(async move {
+                let served = tonic::transport::Server::builder()
+                    .add_service(TraceServiceServer::new(handler.clone()))
+                    .add_service(MetricsServiceServer::new(handler.clone()))
+                    .add_service(LogsServiceServer::new(handler))
+                    .serve(address)
+                    .await;
+                if let Err(err) = served {
+                    error!("OTLP gRPC receiver on {address} failed: {err}");
+                }
+            }));
+        }
+        if let Some(address) = &self.state.http_address {
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            let router = Router::new()
+                .route("/v1/traces", post(export_http::<ExportTraceServiceRequest>))
+                .route(
+                    "/v1/metrics",
+                    post(export_http::<ExportMetricsServiceRequest>),
+                )
+                .route("/v1/logs", post(export_http::<ExportLogsServiceRequest>))
+                .with_state(handler);
+            let address = address.clone();
+            tasks.push(SpawnedTask::spawn(async move {
+                if let Err(err) = axum::serve(listener, router).await {
+                    error!("OTLP HTTP receiver on {address} failed: {err}");
+                }
+            }));
+        }
+
+        let running = Arc::new(Servers { _tasks: tasks });
+        *servers = Arc::downgrade(&running);
+        Ok(running)
+    }
+}
+
+/// One signal of an [`OtlpReceiver`]
+pub struct OtlpSource {
+    receiver: OtlpReceiver,
+    signal: OtlpSignal,
+    schema: SchemaRef,
+}
+
+#[async_trait]
+impl TableProvider for OtlpSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = OtlpPartition {
+            receiver: self.receiver.clone(),
+            signal: self.signal,
+            schema: self.schema.clone(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.schema.clone(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct OtlpPartition {
+    receiver: OtlpReceiver,
+    signal: OtlpSignal,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for OtlpPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let receiver = self.receiver.clone();
+        let signal = self.signal;
+
+        builder.spawn(async move {
+            let (sender, mut batches) = mpsc::channel(REQUEST_BUFFER);
+            receiver
+                .state
+                .readers
+                .lock()
+                .unwrap()
+                .insert(signal, sender);
+            let _servers = receiver.start().await?;
+
+            while let Some(batch) = batches.recv().await {
+                if tx.send(batch).await.is_err() {
+                    break;
+                }
+            }
+            receiver.state.readers.lock().unwrap().remove(&signal);
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+async fn resolve(address: &str) -> Result<SocketAddr> {
+    tokio::net::lookup_host(address)
+        .await?
+        .next()
+        .ok_or_else(|| DataFusionError::Plan(format!("Can't resolve OTLP address {address}")))
+}
+
+#[derive(Debug, Clone)]
+struct ExportHandler {
+    readers: Readers,
+}
+
+/// Why an export was turned down
+enum Refusal {
+    NoReader(OtlpSignal),
+    Invalid(DataFusionError),
+}
+
+impl ExportHandler {
+    async fn deliver(&self, signal: OtlpSignal, batch: Result<RecordBatch>) -> Result<(), Refusal> {
+        let batch = batch.map_err(Refusal::Invalid)?;
+        let sender = self.readers.lock().unwrap().get(&signal).cloned();
+        let Some(sender) = sender else {
+            return Err(Refusal::NoReader(signal));
+        };
+        if batch.num_rows() > 0 {
+            sender
+                .send(Ok(batch))
+                .await
+                .map_err(|_| Refusal::NoReader(signal))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Refusal> for tonic::Status {
+    fn from(refusal: Refusal) -> Self {
+        match refusal {
+            Refusal::NoReader(signal) => {
+                tonic::Status::unavailable(format!("No stream reads {signal:?}"))
+            }
+            Refusal::Invalid(err) => tonic::Status::invalid_argument(err.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for Refusal {
+    fn into_response(self) -> Response {
+        match self {
+            Refusal::NoReader(signal) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("No stream reads {signal:?}"),
+            )
+                .into_response(),
+            Refusal::Invalid(err) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl TraceService for ExportHandler {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportTraceServiceRequest>,
+    ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+        self.deliver(OtlpSignal::Traces, spans_batch(&request.into_inner()))
+            .await?;
+        Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+    }
+}
+
+#[tonic::async_trait]
+impl MetricsService for ExportHandler {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportMetricsServiceRequest>,
+    ) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+        self.deliver(
+            OtlpSignal::Metrics,
+            data_points_batch(&request.into_inner()),
+        )
+        .await?;
+        Ok(tonic::Response::new(ExportMetricsServiceResponse::default()))
+    }
+}
+
+#[tonic::async_trait]
+impl LogsService for ExportHandler {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportLogsServiceRequest>,
+    ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+        self.deliver(OtlpSignal::Logs, log_records_batch(&request.into_inner()))
+            .await?;
+        Ok(tonic::Response::new(ExportLogsServiceResponse::default()))
+    }
+}
+
+/// An export request of one of the signals
+trait ExportRequest: Message + Default + 'static {
+    const SIGNAL: OtlpSignal;
+    /// The encoded response acknowledging the request, all signals' are empty messages
+    const RESPONSE: &'static [u8] = &[];
+
+    fn to_batch(&self) -> Result<RecordBatch>;
+}
+
+impl ExportRequest for ExportTraceServiceRequest {
+    const SIGNAL: OtlpSignal = OtlpSignal::Traces;
+
+    fn to_batch(&self) -> Result<RecordBatch> {
+        spans_batch(self)
+    }
+}
+
+impl ExportRequest for ExportMetricsServiceRequest {
+    const SIGNAL: OtlpSignal = OtlpSignal::Metrics;
+
+    fn to_batch(&self) -> Result<RecordBatch> {
+        data_points_batch(self)
+    }
+}
+
+impl ExportRequest for ExportLogsServiceRequest {
+    const SIGNAL: OtlpSignal = OtlpSignal::Logs;
+
+    fn to_batch(&self) -> Result<RecordBatch> {
+        log_records_batch(self)
+    }
+}
+
+/// OTLP/HTTP with protobuf payloads, JSON payloads are refused
+async fn export_http<R: ExportRequest>(
+    State(handler): State<ExportHandler>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let protobuf = headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == "application/x-protobuf");
+    if !protobuf {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Only application/x-protobuf payloads are supported",
+        )
+            .into_response();
+    }
+    let batch = R::decode(body)
+        .map_err(|err| DataFusionError::External(Box::new(err)))
+        .and_then(|request| request.to_batch());
+    match handler.deliver(R::SIGNAL, batch).await {
+        Ok(()) => (
+            [(header::CONTENT_TYPE, "application/x-protobuf")],
+            R::RESPONSE,
+        )
+            .into_response(),
+        Err(refusal) => refusal.into_response(),
+    }
+}
+
+fn signal_schema(signal: OtlpSignal) -> SchemaRef {
+    let timestamp = |name| Field::new(name, DataType::Timestamp(TimeUnit::Nanosecond, None), true);
+    let utf8 = |name, nullable| Field::new(name, DataType::Utf8, nullable);
+    let mut fields = ResourceColumns::fields();
+    match signal {
+        OtlpSignal::Traces => fields.extend([
+            utf8("trace_id", false),
+            utf8("span_id", false),
+            utf8("parent_span_id", true),
+            utf8("name", false),
+            utf8("kind", true),
+            timestamp("start_time"),
+            timestamp("end_time"),
+            utf8("status_code", true),
+            utf8("status_message", true),
+            attributes_field("attributes"),
+        ]),
+        OtlpSignal::Metrics => fields.extend([
+            utf8("metric", false),
+            utf8("description", true),
+            utf8("unit", true),
+            utf8("type", false),
+            attributes_field("attributes"),
+            timestamp("start_time"),
+            timestamp("time"),
+            Field::new("value", DataType::Float64, true),
+            Field::new("count", DataType::UInt64, true),
+            Field::new("sum", DataType::Float64, true),
+            Field::new(
+                "bucket_counts",
+                DataType::List(Arc::new(Field::new("item", DataType::UInt64, true))),
+                true,
+            ),
+            Field::new(
+                "explicit_bounds",
+                DataType::List(Arc::new(Field::new("item", DataType::Float64, true))),
+                true,
+            ),
+            Field::new("is_monotonic", DataType::Boolean, true),
+        ]),
+        OtlpSignal::Logs => fields.extend([
+            timestamp("time"),
+            Field::new("severity_number", DataType::Int32, true),
+            utf8("severity_text", true),
+            utf8("body", true),
+            attributes_field("attributes"),
+            utf8("trace_id", true),
+            utf8("span_id", true),
+        ]),
+    }
+    fields.push(stream_metadata_field());
+    Arc::new(Schema::new(fields))
+}
+
+/// Attributes as a map of strings, values that aren't strings are formatted as JSON
+fn attributes_field(name: &str) -> Field {
+    Field::new(name, string_map_type(), true)
+}
+
+type AttributesBuilder = MapBuilder<StringBuilder, StringBuilder>;
+
+fn attributes_builder() -> AttributesBuilder {
+    MapBuilder::new(None, StringBuilder::new(), StringBuilder::new())
+}
+
+fn append_attributes(builder: &mut AttributesBuilder, attributes: &[KeyValue]) -> Result<()> {
+    for attribute in attributes {
+        builder.keys().append_value(&attribute.key);
+        builder
+            .values()
+            .append_option(attribute.value.as_ref().and_then(any_value_string));
+    }
+    Ok(builder.append(true)?)
+}
+
+fn any_value_string(value: &AnyValue) -> Option<String> {
+    match value.value.as_ref()? {
+        any_value::Value::StringValue(value) => Some(value.clone()),
+        _ => Some(any_value_json(value).to_string()),
+    }
+}
+
+fn any_value_json(value: &AnyValue) -> serde_json::Value {
+    use serde_json::Value as Json;
+    match &value.value {
+        None => Json::Null,
+        Some(any_value::Value::StringValue(value)) => Json::from(value.as_str()),
+        Some(any_value::Value::BoolValue(value)) => Json::from(*value),
+        Some(any_value::Value::IntValue(value)) => Json::from(*value),
+        Some(any_value::Value::DoubleValue(value)) => Json::from(*value),
+        Some(any_value::Value::BytesValue(value)) => Json::from(hex::encode(value)),
+        Some(any_value::Value::ArrayValue(array)) => {
+            Json::Array(array.values.iter().map(any_value_json).collect())
+        }
+        Some(any_value::Value::KvlistValue(list)) => Json::Object(
+            list.values
+                .iter()
+                .map(|kv| {
+                    let value = kv.value.as_ref().map_or(Json::Null, any_value_json);
+                    (kv.key.clone(), value)
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Unset OTLP times are zero
+fn nanos(time_unix_nano: u64) -> Option<i64> {
+    (time_unix_nano != 0).then_some(time_unix_nano as i64)
+}
+
+fn hex_id(id: &[u8]) -> Option<String> {
+    (!id.is_empty()).then(|| hex::encode(id))
+}
+
+/// Lower case name of an OTLP enum value, without the prefix its protobuf name repeats
+fn enum_name(name: &str, prefix: &str) -> String {
+    name.trim_start_matches(prefix).to_lowercase()
+}
+
+/// The columns describing where telemetry came from, leading every signal's rows
+struct ResourceColumns {
+    service_name: StringBuilder,
+    resource_attributes: AttributesBuilder,
+    scope_name: StringBuilder,
+}
+
+impl ResourceColumns {
+    fn fields() -> Vec<Field> {
+        vec![
+            Field::new("service_name", DataType::Utf8, true),
+            attributes_field("resource_attributes"),
+            Field::new("scope_name", DataType::Utf8, true),
+        ]
+    }
+
+    fn new() -> Self {
+        Self {
+            service_name: StringBuilder::new(),
+            resource_attributes: attributes_builder(),
+            scope_name: StringBuilder::new(),
+        }
+    }
+
+    fn append(
+        &mut self,
+        resource: Option<&Resource>,
+        scope: Option<&InstrumentationScope>,
+    ) -> Result<()> {
+        let attributes = resource.map_or(&[][..], |resource| &resource.attributes);
+        let service_name = attributes
+            .iter()
+            .find(|attribute| attribute.key == "service.name")
+            .and_then(|attribute| attribute.value.as_ref())
+            .and_then(any_value_string);
+        self.service_name.append_option(service_name);
+        append_attributes(&mut self.resource_attributes, attributes)?;
+        self.scope_name.append_option(
+            scope
+                .map(|scope| scope.name.as_str())
+                .filter(|name| !name.is_empty()),
+        );
+        Ok(())
+    }
+
+    fn finish(mut self) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(self.service_name.finish()),
+            Arc::new(self.resource_attributes.finish()),
+            Arc::new(self.scope_name.finish()),
+        ]
+    }
+}
+
+/// Complete the batch with the metadata column, rows without an event time get the arrival
+/// time
+fn signal_batch(
+    signal: OtlpSignal,
+    mut columns: Vec<ArrayRef>,
+    event_times_ns: Vec<Option<i64>>,
+) -> Result<RecordBatch> {
+    let arrival_ms = now_ms();
+    let event_times = TimestampMillisecondArray::from_iter_values(
+        event_times_ns
+            .into_iter()
+            .map(|time| time.map_or(arrival_ms, |time| time / 1_000_000)),
+    );
+    columns.push(Arc::new(stream_metadata_array(event_times)));
+    Ok(RecordBatch::try_new(signal_schema(signal), columns)?)
+}
+
+fn spans_batch(request: &ExportTraceServiceRequest) -> Result<RecordBatch> {
+    let mut resources = ResourceColumns::new();
+    let mut trace_ids = StringBuilder::new();
+    let mut span_ids = StringBuilder::new();
+    let mut parent_span_ids = StringBuilder::new();
+    let mut names = StringBuilder::new();
+    let mut kinds = StringBuilder::new();
+    let mut start_times = TimestampNanosecondBuilder::new();
+    let mut end_times = TimestampNanosecondBuilder::new();
+    let mut status_codes = StringBuilder::new();
+    let mut status_messages = StringBuilder::new();
+    let mut attributes = attributes_builder();
+    let mut event_times = vec![];
+
+    for resource_spans in &request.resource_spans {
+        for scope_spans in &resource_spans.scope_spans {
+            for span in &scope_spans.spans {
+                resources.append(resource_spans.resource.as_ref(), scope_spans.scope.as_ref())?;
+                trace_ids.append_value(hex::encode(&span.trace_id));
+                span_ids.append_value(hex::encode(&span.span_id));
+                parent_span_ids.append_option(hex_id(&span.parent_span_id));
+                names.append_value(&span.name);
+                kinds.append_option(
+                    span::SpanKind::try_from(span.kind)
+                        .ok()
+                        .filter(|kind| *kind != span::SpanKind::Unspecified)
+                        .map(|kind| enum_name(kind.as_str_name(), "SPAN_KIND_")),
+                );
+                start_times.append_option(nanos(span.start_time_unix_nano));
+                end_times.append_option(nanos(span.end_time_unix_nano));
+                let status = span.status.as_ref();
+                status_codes.append_option(
+                    status
+                        .and_then(|status| status::StatusCode::try_from(status.code).ok())
+                        .map(|code| enum_name(code.as_str_name(), "STATUS_CODE_")),
+                );
+                status_messages.append_option(
+                    status
+                        .map(|status| status.message.as_str())
+                        .filter(|message| !message.is_empty()),
+                );
+                append_attributes(&mut attributes, &span.attributes)?;
+                event_times.push(nanos(span.start_time_unix_nano));
+            }
+        }
+    }
+
+    let mut columns = resources.finish();
+    columns.extend([
+        Arc::new(trace_ids.finish()) as ArrayRef,
+        Arc::new(span_ids.finish()),
+        Arc::new(parent_span_ids.finish()),
+        Arc::new(names.finish()),
+        Arc::new(kinds.finish()),
+        Arc::new(start_times.finish()),
+        Arc::new(end_times.finish()),
+        Arc::new(status_codes.finish()),
+        Arc::new(status_messages.finish()),
+        Arc::new(attributes.finish()),
+    ]);
+    signal_batch(OtlpSignal::Traces, columns, event_times)
+}
+
+/// The columns of a data point, all metric types share them
+#[derive(Default)]
+struct DataPoint<'a> {
+    attributes: &'a [KeyValue],
+    start_time_unix_nano: u64,
+    time_unix_nano: u64,
+    value: Option<f64>,
+    count: Option<u64>,
+    sum: Option<f64>,
+    bucket_counts: Option<&'a [u64]>,
+    explicit_bounds: Option<&'a [f64]>,
+    is_monotonic: Option<bool>,
+}
+
+fn data_points(data: &metric::Data) -> (&'static str, Vec<DataPoint<'_>>) {
+    let number = |point: &number_data_point::Value| match point {
+        number_data_point::Value::AsDouble(value) => *value,
+        number_data_point::Value::AsInt(value) => *value as f64,
+    };
+    match data {
+        metric::Data::Gauge(gauge) => (
+            "gauge",
+            gauge
+                .data_points
+                .iter()
+                .map(|point| DataPoint {
+                    attributes: &point.attributes,
+                    start_time_unix_nano: point.start_time_unix_nano,
+                    time_unix_nano: point.time_unix_nano,
+                    value: point.value.as_ref().map(number),
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+        metric::Data::Sum(sum) => (
+            "sum",
+            sum.data_points
+                .iter()
+                .map(|point| DataPoint {
+                    attributes: &point.attributes,
+                    start_time_unix_nano: point.start_time_unix_nano,
+                    time_unix_nano: point.time_unix_nano,
+                    value: point.value.as_ref().map(number),
+                    is_monotonic: Some(sum.is_monotonic),
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+        metric::Data::Histogram(histogram) => (
+            "histogram",
+            histogram
+                .data_points
+                .iter()
+                .map(|point| DataPoint {
+                    attributes: &point.attributes,
+                    start_time_unix_nano: point.start_time_unix_nano,
+                    time_unix_nano: point.time_unix_nano,
+                    count: Some(point.count),
+                    sum: point.sum,
+                    bucket_counts: Some(&point.bucket_counts),
+                    explicit_bounds: Some(&point.explicit_bounds),
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+        metric::Data::ExponentialHistogram(histogram) => (
+            "exponential_histogram",
+            histogram
+                .data_points
+                .iter()
+                .map(|point| DataPoint {
+                    attributes: &point.attributes,
+                    start_time_unix_nano: point.start_time_unix_nano,
+                    time_unix_nano: point.time_unix_nano,
+                    count: Some(point.count),
+                    sum: point.sum,
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+        metric::Data::Summary(summary) => (
+            "summary",
+            summary
+                .data_points
+                .iter()
+                .map(|point| DataPoint {
+                    attributes: &point.attributes,
+                    start_time_unix_nano: point.start_time_unix_nano,
+                    time_unix_nano: point.time_unix_nano,
+                    count: Some(point.count),
+                    sum: Some(point.sum),
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn data_points_batch(request: &ExportMetricsServiceRequest) -> Result<RecordBatch> {
+    let mut resources = ResourceColumns::new();
+    let mut metrics = StringBuilder::new();
+    let mut descriptions = StringBuilder::new();
+    let mut units = StringBuilder::new();
+    let mut types = StringBuilder::new();
+    let mut attributes = attributes_builder();
+    let mut start_times = TimestampNanosecondBuilder::new();
+    let mut times = TimestampNanosecondBuilder::new();
+    let mut values = Float64Builder::new();
+    let mut counts = UInt64Builder::new();
+    let mut sums = Float64Builder::new();
+    let mut bucket_counts = ListBuilder::new(UInt64Builder::new());
+    let mut explicit_bounds = ListBuilder::new(Float64Builder::new());
+    let mut is_monotonic = BooleanBuilder::new();
+    let mut event_times = vec![];
+
+    for resource_metrics in &request.resource_metrics {
+        for scope_metrics in &resource_metrics.scope_metrics {
+            for metric in &scope_metrics.metrics {
+                let Some(data) = &metric.data else {
+                    continue;
+                };
+                let (metric_type, points) = data_points(data);
+                for point in points {
+                    resources.append(
+                        resource_metrics.resource.as_ref(),
+                        scope_metrics.scope.as_ref(),
+                    )?;
+                    metrics.append_value(&metric.name);
+                    descriptions
+                        .append_option(Some(metric.description.as_str()).filter(|d| !d.is_empty()));
+                    units.append_option(Some(metric.unit.as_str()).filter(|u| !u.is_empty()));
+                    types.append_value(metric_type);
+                    append_attributes(&mut attributes, point.attributes)?;
+                    start_times.append_option(nanos(point.start_time_unix_nano));
+                    times.append_option(nanos(point.time_unix_nano));
+                    values.append_option(point.value);
+                    counts.append_option(point.count);
+                    sums.append_option(point.sum);
+                    match point.bucket_counts {
+                        Some(counts) => bucket_counts.append_value(counts.iter().map(|c| Some(*c))),
+                        None => bucket_counts.append_null(),
+                    }
+                    match point.explicit_bounds {
+                        Some(bounds) => {
+                            explicit_bounds.append_value(bounds.iter().map(|b| Some(*b)))
+                        }
+                        None => explicit_bounds.append_null(),
+                    }
+                    is_monotonic.append_option(point.is_monotonic);
+                    event_times.push(nanos(point.time_unix_nano));
+                }
+            }
+        }
+    }
+
+    let mut columns = resources.finish();
+    columns.extend([
+        Arc::new(metrics.finish()) as ArrayRef,
+        Arc::new(descriptions.finish()),
+        Arc::new(units.finish()),
+        Arc::new(types.finish()),
+        Arc::new(attributes.finish()),
+        Arc::new(start_times.finish()),
+        Arc::new(times.finish()),
+        Arc::new(values.finish()),
+        Arc::new(counts.finish()),
+        Arc::new(sums.finish()),
+        Arc::new(bucket_counts.finish()),
+        Arc::new(explicit_bounds.finish()),
+        Arc::new(is_monotonic.finish()),
+    ]);
+    signal_batch(OtlpSignal::Metrics, columns, event_times)
+}
+
+fn log_records_batch(request: &ExportLogsServiceRequest) -> Result<RecordBatch> {
+    let mut resources = ResourceColumns::new();
+    let mut times = TimestampNanosecondBuilder::new();
+    let mut severity_numbers = Int32Builder::new();
+    let mut severity_texts = StringBuilder::new();
+    let mut bodies = StringBuilder::new();
+    let mut attributes = attributes_builder();
+    let mut trace_ids = StringBuilder::new();
+    let mut span_ids = StringBuilder::new();
+    let mut event_times = vec![];
+
+    for resource_logs in &request.resource_logs {
+        for scope_logs in &resource_logs.scope_logs {
+            for record in &scope_logs.log_records {
+                resources.append(resource_logs.resource.as_ref(), scope_logs.scope.as_ref())?;
+                // Records without a time of their own are timed by when the collector saw them
+                let time =
+                    nanos(record.time_unix_nano).or_else(|| nanos(record.observed_time_unix_nano));
+                times.append_option(time);
+                severity_numbers
+                    .append_option(Some(record.severity_number).filter(|number| *number != 0));
+                severity_texts.append_option(
+                    Some(record.severity_text.as_str()).filter(|text| !text.is_empty()),
+                );
+                bodies.append_option(record.body.as_ref().and_then(any_value_string));
+                append_attributes(&mut attributes, &record.attributes)?;
+                trace_ids.append_option(hex_id(&record.trace_id));
+                span_ids.append_option(hex_id(&record.span_id));
+                event_times.push(time);
+            }
+        }
+    }
+
+    let mut columns = resources.finish();
+    columns.extend([
+        Arc::new(times.finish()) as ArrayRef,
+        Arc::new(severity_numbers.finish()),
+        Arc::new(severity_texts.finish()),
+        Arc::new(bodies.finish()),
+        Arc::new(attributes.finish()),
+        Arc::new(trace_ids.finish()),
+        Arc::new(span_ids.finish()),
+    ]);
+    signal_batch(OtlpSignal::Logs, columns, event_times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, MapArray, StringArray};
+    use opentelemetry_proto::tonic::common::v1::ArrayValue;
+    use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+
+    fn attribute(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn convert_spans() {
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![attribute(
+                        "service.name",
+                        any_value::Value::StringValue("checkout".to_string()),
+                    )],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        trace_id: vec![0xab; 16],
+                        span_id: vec![0x01; 8],
+                        name: "POST /orders".to_string(),
+                        kind: span::SpanKind::Server as i32,
+                        start_time_unix_nano: 1_700_000_000_000_000_000,
+                        end_time_unix_nano: 1_700_000_000_250_000_000,
+                        attributes: vec![
+                            attribute("http.status_code", any_value::Value::IntValue(201)),
+                            attribute(
+                                "tags",
+                                any_value::Value::ArrayValue(ArrayValue {
+                                    values: vec![AnyValue {
+                                        value: Some(any_value::Value::BoolValue(true)),
+                                    }],
+                                }),
+                            ),
+                        ],
+                        status: Some(Status {
+                            code: status::StatusCode::Ok as i32,
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let batch = spans_batch(&request).unwrap();
+        let column = |index| {
+            batch
+                .column(index)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+        };
+        assert_eq!(column(0).value(0), "checkout");
+        assert_eq!(column(4).value(0), "0101010101010101");
+        assert!(column(5).is_null(0));
+        assert_eq!(column(7).value(0), "server");
+        assert_eq!(column(10).value(0), "ok");
+
+        let attributes = batch
+            .column(12)
+            .as_any()
+            .downcast_ref::<MapArray>()
+            .unwrap();
+        let values = attributes.value(0);
+        let values = values
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            values.iter().collect::<Vec<_>>(),
+            [Some("201"), Some("[true]")]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    Float64Builder, MapBuilder, StringBuilder, TimestampMillisecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use datafusion::catalog::Session;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::error;
+use prost::Message;
+use tokio::sync::mpsc;
+
+use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+use crate::utils::arrow_helpers::string_map_type;
+
+/// Write requests buffered before senders are slowed down
+const REQUEST_BUFFER: usize = 16;
+
+/// Label holding the metric name
+const METRIC_NAME_LABEL: &str = "__name__";
+
+/// Receives samples over the Prometheus remote write protocol (version 1) on
+/// `POST /api/v1/write`, as rows of `metric`, `labels`, `timestamp` and `value`, registered
+/// with [`Context::from_source`](crate::context::Context::from_source).
+///
+/// `labels` is a map of all labels but the metric name. Rows take their event time from the
+/// sample timestamp. Every write request becomes one batch, requests are acknowledged once
+/// their batch was handed downstream, so that Prometheus retries writes the pipeline couldn't
+/// take. Native histograms and metric metadata are ignored.
+pub struct PrometheusRemoteWriteSource {
+    address: String,
+    schema: SchemaRef,
+}
+
+impl PrometheusRemoteWriteSource {
+    pub fn new(address: &str) -> Self {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("metric", DataType::Utf8, false),
+            Field::new("labels", string_map_type(), false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("value", DataType::Float64, false),
+            stream_metadata_field(),
+        ]));
+        Self {
+            address: address.to_string(),
+            schema,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for PrometheusRemoteWriteSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = RemoteWritePartition {
+            address: self.address.clone(),
+            schema: self.schema.clone(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.schema.clone(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct RemoteWritePartition {
+    address: String,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for RemoteWritePartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let address = self.address.clone();
+        let schema = self.schema.clone();
+
+        builder.spawn(async move {
+            let (sender, mut batches) = mpsc::channel(REQUEST_BUFFER);
+            let listener = tokio::net::TcpListener::bind(&address).await?;
+            let router = Router::new()
+                .route("/api/v1/write", post(write))
+                .with_state(WriteState { schema, sender });
+            let _server = SpawnedTask::spawn(async move {
+                if let Err(err) = axum::serve(listener, router).await {
+                    error!("Prometheus remote write receiver on {address} failed: {err}");
+                }
+            });
+
+            while let Some(batch) = batches.recv().await {
+                if tx.send(Ok(batch)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+#[derive(Clone)]
+struct WriteState {
+    schema: SchemaRef,
+    sender: mpsc::Sender<RecordBatch>,
+}
+
+/// Bad requests aren't retried by Prometheus, unavailable receivers are
+async fn write(State(state): State<WriteState>, body: Bytes) -> (StatusCode, String) {
+    let batch = match samples_batch(&state.schema, &body) {
+        Ok(batch) => batch,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()),
+    };
+    if batch.num_rows() > 0 && state.sender.send(batch).await.is_err() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The stream reading the samples stopped".to_string(),
+        );
+    }
+    (StatusCode::NO_CONTENT, String::new())
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Sample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+/// Decode a snappy compressed `WriteRequest`, one row per sample
+fn samples_batch(schema: &SchemaRef, body: &[u8]) -> Result<RecordBatch> {
+    let body = snap::raw::Decoder::new()
+        .decompress_vec(body)
+        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+    let request = WriteRequest::decode(body.as_slice())
+        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+
+    let mut metrics = StringBuilder::new();
+    let mut labels = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+    let mut timestamps = TimestampMillisecondBuilder::new();
+    let mut values = Float64Builder::new();
+    for series in &request.timeseries {
+        let metric = series
+            .labels
+            .iter()
+            .find(|label| label.name == METRIC_NAME_LABEL)
+            .map_or("", |label| label.value.as_str());
+        for sample in &series.samples {
+            metrics.append_value(metric);
+            for label in &series.labels {
+                if label.name != METRIC_NAME_LABEL {
+                    labels.keys().append_value(&label.name);
+                    labels.values().append_value(&label.value);
+                }
+            }
+            labels.append(true)?;
+            timestamps.append_value(sample.timestamp);
+            values.append_value(sample.value);
+        }
+    }
+
+    let timestamps = timestamps.finish();
+    let event_times = TimestampMillisecondArray::from(timestamps.values().to_vec());
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(metrics.finish()),
+        Arc::new(labels.finish()),
+        Arc::new(timestamps),
+        Arc::new(values.finish()),
+        Arc::new(stream_metadata_array(event_times)),
+    ];
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, Float64Array, MapArray, StringArray};
+
+    #[test]
+    fn decode_write_requests() {
+        let label = |name: &str, value: &str| Label {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+        let request = WriteRequest {
+            timeseries: vec![TimeSeries {
+                labels: vec![
+                    label(METRIC_NAME_LABEL, "http_requests_total"),
+                    label("job", "api"),
+                ],
+                samples: vec![
+                    Sample {
+                        value: 10.0,
+                        timestamp: 1_700_000_000_000,
+                    },
+                    Sample {
+                        value: 12.0,
+                        timestamp: 1_700_000_015_000,
+                    },
+                ],
+            }],
+        };
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .unwrap();
+
+        let source = PrometheusRemoteWriteSource::new("127.0.0.1:9201");
+        let batch = samples_batch(&source.schema, &body).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let metrics = batch.column(0).as_any().downcast_ref::<StringArray>();
+        assert_eq!(metrics.unwrap().value(1), "http_requests_total");
+        let labels = batch.column(1).as_any().downcast_ref::<MapArray>().unwrap();
+        assert_eq!(labels.value(0).len(), 1);
+        let values = batch.column(3).as_any().downcast_ref::<Float64Array>();
+        assert_eq!(values.unwrap().value(1), 12.0);
+
+        assert!(samples_batch(&source.schema, b"not snappy").is_err());
+    }
+}
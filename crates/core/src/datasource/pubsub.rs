@@ -0,0 +1,443 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::compute::cast;
+use arrow_array::{Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, SchemaRef};
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan};
+use futures::StreamExt;
+use google_cloud_googleapis::pubsub::v1::PubsubMessage;
+use google_cloud_pubsub::client::{Client, ClientConfig};
+use google_cloud_pubsub::subscriber::ReceivedMessage;
+use serde_json::Value;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::message::{next_chunk, now_ms, JsonMessageDecoder};
+use crate::utils::json_format::JsonFormatOptions;
+use crate::utils::quota::ResourceQuotas;
+use crate::utils::row_encoder::{JsonRowEncoder, RowEncoder};
+
+/// Ack deadline requested for messages held until their batch was handed downstream
+const ACK_DEADLINE_SECS: i32 = 60;
+/// How often held messages get their deadline extended, well within [`ACK_DEADLINE_SECS`]
+const ACK_DEADLINE_RENEWAL: Duration = Duration::from_secs(20);
+
+/// Reads JSON messages from a Pub/Sub subscription over streaming pull, registered with
+/// [`Context::from_source`](crate::context::Context::from_source). Credentials are found the
+/// way the Google Cloud SDKs find them.
+///
+/// With checkpointing enabled every batch closes an epoch and its messages are acknowledged
+/// after the batch was handed downstream, their ack deadline is extended for as long as that
+/// takes. Otherwise messages are acknowledged as they arrive. Messages are read in the order
+/// the subscription delivers them, so subscriptions with message ordering keep the order of
+/// each ordering key.
+pub struct PubSubSource {
+    subscription: String,
+    decoder: JsonMessageDecoder,
+    attributes_column: Option<String>,
+    ordering_key_column: Option<String>,
+}
+
+impl PubSubSource {
+    /// `subscription` is the full name, `projects/{project}/subscriptions/{subscription}`, or
+    /// the subscription ID within the project of the credentials
+    pub fn new(subscription: &str, decoder: JsonMessageDecoder) -> Self {
+        Self {
+            subscription: subscription.to_string(),
+            decoder,
+            attributes_column: None,
+            ordering_key_column: None,
+        }
+    }
+
+    /// Add the attributes of each message to its payload as the object `column`. Declare the
+    /// column as a struct of the expected attributes or a map in the decoder's schema.
+    pub fn with_attributes_column(mut self, column: &str) -> Self {
+        self.attributes_column = Some(column.to_string());
+        self
+    }
+
+    /// Add the ordering key of each message to its payload as `column`
+    pub fn with_ordering_key_column(mut self, column: &str) -> Self {
+        self.ordering_key_column = Some(column.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl TableProvider for PubSubSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = PubSubPartition {
+            subscription: self.subscription.clone(),
+            decoder: self.decoder.clone(),
+            attributes_column: self.attributes_column.clone(),
+            ordering_key_column: self.ordering_key_column.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct PubSubPartition {
+    subscription: String,
+    decoder: JsonMessageDecoder,
+    attributes_column: Option<String>,
+    ordering_key_column: Option<String>,
+    schema: SchemaRef,
+}
+
+impl PubSubPartition {
+    fn payload(&self, message: &PubsubMessage) -> Result<Value> {
+        let mut payload: Value = serde_json::from_slice(&message.data)
+            .map_err(|err| DataFusionError::External(Box::new(err)))?;
+        let Value::Object(fields) = &mut payload else {
+            return plan_err!("Expected Pub/Sub messages holding JSON objects");
+        };
+        if let Some(column) = &self.attributes_column {
+            let attributes = message
+                .attributes
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                .collect();
+            fields.insert(column.clone(), Value::Object(attributes));
+        }
+        if let Some(column) = &self.ordering_key_column {
+            let key = (!message.ordering_key.is_empty())
+                .then(|| Value::String(message.ordering_key.clone()));
+            fields.insert(column.clone(), key.unwrap_or(Value::Null));
+        }
+        Ok(payload)
+    }
+}
+
+impl PartitionStream for PubSubPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let partition = PubSubPartition {
+            subscription: self.subscription.clone(),
+            decoder: self.decoder.clone(),
+            attributes_column: self.attributes_column.clone(),
+            ordering_key_column: self.ordering_key_column.clone(),
+            schema: self.schema.clone(),
+        };
+
+        builder.spawn(async move {
+            let client = connect().await?;
+            let subscription = client.subscription(&partition.subscription);
+            let mut messages = Box::pin(subscription.subscribe(None).await.map_err(pubsub_error)?);
+
+            let mut epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current());
+            while let Some(chunk) = next_chunk(&mut messages).await {
+                let payloads = chunk
+                    .iter()
+                    .map(|received| partition.payload(&received.message))
+                    .collect::<Result<Vec<_>>>()?;
+
+                if !should_checkpoint {
+                    acknowledge(&chunk).await?;
+                    let batch = partition.decoder.decode(payloads, now_ms())?;
+                    if tx.send(Ok(batch)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+
+                epoch += 1;
+                if let Some(tracker) = &epoch_tracker {
+                    tracker.advance(epoch)?;
+                }
+                let batch = partition
+                    .decoder
+                    .decode_checkpointed(payloads, now_ms(), epoch)?;
+                extend_deadlines(&chunk).await?;
+                let send = tx.send(Ok(batch));
+                tokio::pin!(send);
+                let mut renewal = tokio::time::interval_at(
+                    tokio::time::Instant::now() + ACK_DEADLINE_RENEWAL,
+                    ACK_DEADLINE_RENEWAL,
+                );
+                let sent = loop {
+                    tokio::select! {
+                        sent = &mut send => break sent.is_ok(),
+                        _ = renewal.tick() => extend_deadlines(&chunk).await?,
+                    }
+                };
+                if !sent {
+                    break;
+                }
+                acknowledge(&chunk).await?;
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+async fn acknowledge(messages: &[ReceivedMessage]) -> Result<()> {
+    for result in futures::future::join_all(messages.iter().map(|message| message.ack())).await {
+        result.map_err(pubsub_error)?;
+    }
+    Ok(())
+}
+
+async fn extend_deadlines(messages: &[ReceivedMessage]) -> Result<()> {
+    let extensions = messages
+        .iter()
+        .map(|message| message.modify_ack_deadline(ACK_DEADLINE_SECS));
+    for result in futures::future::join_all(extensions).await {
+        result.map_err(pubsub_error)?;
+    }
+    Ok(())
+}
+
+/// Publishes every row as a JSON message to a Pub/Sub topic, waiting for the messages of a
+/// batch to be published before taking the next one.
+pub struct PubSubSink {
+    topic: String,
+    json_format: JsonFormatOptions,
+    attribute_columns: Vec<String>,
+    ordering_key_column: Option<String>,
+}
+
+impl PubSubSink {
+    /// `topic` is the full name, `projects/{project}/topics/{topic}`, or the topic ID within
+    /// the project of the credentials
+    pub fn new(topic: &str, json_format: JsonFormatOptions) -> Self {
+        Self {
+            topic: topic.to_string(),
+            json_format,
+            attribute_columns: vec![],
+            ordering_key_column: None,
+        }
+    }
+
+    /// Publish the values of `columns` as message attributes instead of in the payload.
+    /// Null values leave the attribute out.
+    pub fn with_attribute_columns(mut self, columns: &[&str]) -> Self {
+        self.attribute_columns = columns.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    /// Publish the values of `column` as ordering keys instead of in the payload. The topic's
+    /// subscriptions need message ordering enabled to receive the messages in order.
+    pub fn with_ordering_key_column(mut self, column: &str) -> Self {
+        self.ordering_key_column = Some(column.to_string());
+        self
+    }
+
+    fn messages(
+        &self,
+        encoder: &JsonRowEncoder,
+        batch: &RecordBatch,
+    ) -> Result<Vec<PubsubMessage>> {
+        let mut payload_columns: Vec<usize> = (0..batch.num_columns()).collect();
+        let mut string_column = |name: &str| -> Result<StringArray> {
+            let Ok(index) = batch.schema().index_of(name) else {
+                return plan_err!("Column {name} not found in the stream sunk to Pub/Sub");
+            };
+            payload_columns.retain(|&i| i != index);
+            let values = cast(batch.column(index), &DataType::Utf8)?;
+            Ok(values
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone())
+        };
+        let attributes = self
+            .attribute_columns
+            .iter()
+            .map(|name| Ok((name.clone(), string_column(name)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let ordering_keys = self
+            .ordering_key_column
+            .as_deref()
+            .map(&mut string_column)
+            .transpose()?;
+
+        let payloads = encoder.encode(&batch.project(&payload_columns)?)?;
+        Ok(payloads
+            .into_iter()
+            .enumerate()
+            .map(|(row, data)| PubsubMessage {
+                data,
+                attributes: attributes
+                    .iter()
+                    .filter(|(_, values)| values.is_valid(row))
+                    .map(|(name, values)| (name.clone(), values.value(row).to_string()))
+                    .collect::<HashMap<_, _>>(),
+                ordering_key: ordering_keys
+                    .as_ref()
+                    .filter(|keys| keys.is_valid(row))
+                    .map_or_else(String::new, |keys| keys.value(row).to_string()),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl DataSink for PubSubSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let quotas = ResourceQuotas::from_task_context(context);
+        let client = connect().await?;
+        let mut publisher = client.topic(&self.topic).new_publisher(None);
+
+        let encoder = JsonRowEncoder::new(self.json_format);
+        let mut row_count = 0;
+        let mut result = Ok(());
+        while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            let awaiters = publisher
+                .publish_bulk(self.messages(&encoder, &batch)?)
+                .await;
+            for awaiter in awaiters {
+                if let Err(err) = awaiter.get().await {
+                    result = Err(pubsub_error(err));
+                }
+            }
+            if result.is_err() {
+                break;
+            }
+            row_count += batch.num_rows() as u64;
+        }
+        publisher.shutdown().await;
+        result.map(|_| row_count)
+    }
+}
+
+impl Debug for PubSubSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PubSubSink")
+            .field("topic", &self.topic)
+            .field("attribute_columns", &self.attribute_columns)
+            .field("ordering_key_column", &self.ordering_key_column)
+            .finish()
+    }
+}
+
+impl DisplayAs for PubSubSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "PubSubSink ({})", self.topic)
+            }
+        }
+    }
+}
+
+async fn connect() -> Result<Client> {
+    let config = ClientConfig::default()
+        .with_auth()
+        .await
+        .map_err(pubsub_error)?;
+    Client::new(config).await.map_err(pubsub_error)
+}
+
+fn pubsub_error(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Int64Array;
+    use arrow_schema::{Field, Schema};
+
+    #[test]
+    fn publish_columns_as_attributes() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("region", DataType::Utf8, true),
+            Field::new("customer", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("eu"), None])),
+                Arc::new(StringArray::from(vec!["c-1", "c-2"])),
+            ],
+        )
+        .unwrap();
+        let sink = PubSubSink::new("orders", JsonFormatOptions::default())
+            .with_attribute_columns(&["region"])
+            .with_ordering_key_column("customer");
+
+        let messages = sink
+            .messages(&JsonRowEncoder::new(JsonFormatOptions::default()), &batch)
+            .unwrap();
+        assert_eq!(messages[0].data, br#"{"id":1}"#);
+        assert_eq!(messages[0].attributes["region"], "eu");
+        assert_eq!(messages[0].ordering_key, "c-1");
+        assert!(messages[1].attributes.is_empty());
+        assert_eq!(messages[1].ordering_key, "c-2");
+    }
+}
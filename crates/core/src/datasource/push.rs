@@ -0,0 +1,163 @@
+//! Machinery shared by the sources that receive messages pushed over a long lived connection,
+//! the WebSocket and Server-Sent Events sources.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_schema::SchemaRef;
+use datafusion::common::Result;
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::PartitionStream;
+use log::{info, warn};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::datasource::message::{next_chunk, now_ms, JsonMessageDecoder, MAX_BATCH_MESSAGES};
+use crate::datasource::socket::Lines;
+use crate::utils::json_parse::parse_json;
+
+/// How long a push source waits before connecting again after its connection failed or was
+/// closed. The wait doubles with every attempt that received nothing, from `initial` up to
+/// `max`, and starts over once a connection delivered a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reconnect {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Reconnect {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Reconnect {
+    /// The wait before the next attempt after `failed` attempts in a row
+    fn delay(&self, failed: u32) -> Duration {
+        self.initial
+            .saturating_mul(2u32.saturating_pow(failed.saturating_sub(1)))
+            .min(self.max)
+    }
+}
+
+/// A connection messages are pushed over
+#[async_trait]
+pub(crate) trait Feed: Send + Sync + 'static {
+    /// Where the feed connects to, for logging
+    fn address(&self) -> &str;
+
+    /// Connect and send every message received to `sender` until the connection ends or the
+    /// receiver goes away, counting the messages sent in `received`
+    async fn read(&self, sender: &mpsc::Sender<Result<String>>, received: &mut usize)
+        -> Result<()>;
+}
+
+/// The messages `feed` receives, reconnecting whenever its connection ends
+fn feed_messages(feed: Arc<dyn Feed>, reconnect: Reconnect) -> Lines {
+    let (sender, receiver) = mpsc::channel(MAX_BATCH_MESSAGES);
+    let reader = SpawnedTask::spawn(async move {
+        let mut failed = 0;
+        loop {
+            let mut received = 0;
+            if let Err(err) = feed.read(&sender, &mut received).await {
+                warn!("Connection to {} failed: {err}", feed.address());
+            }
+            failed = if received > 0 { 1 } else { failed + 1 };
+            if sender.is_closed() {
+                return;
+            }
+            let delay = reconnect.delay(failed);
+            info!("Reconnecting to {} in {delay:?}", feed.address());
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = sender.closed() => return,
+            }
+        }
+    });
+    Lines::new(receiver, reader)
+}
+
+/// The records of a JSON message, the elements of an array or the message itself
+fn message_records(message: Value) -> Vec<Value> {
+    match message {
+        Value::Array(records) => records,
+        Value::Null => vec![],
+        record => vec![record],
+    }
+}
+
+/// The single partition of a push source, decoding the JSON messages of its feed in batches
+pub(crate) struct PushPartition {
+    pub feed: Arc<dyn Feed>,
+    pub reconnect: Reconnect,
+    pub decoder: JsonMessageDecoder,
+    pub schema: SchemaRef,
+}
+
+impl PartitionStream for PushPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let feed = self.feed.clone();
+        let reconnect = self.reconnect;
+        let decoder = self.decoder.clone();
+
+        builder.spawn(async move {
+            let mut messages = feed_messages(feed, reconnect);
+            while let Some(chunk) = next_chunk(&mut messages).await {
+                let mut records = vec![];
+                for message in chunk {
+                    records.extend(message_records(parse_json(message?.as_bytes())?));
+                }
+                if records.is_empty() {
+                    continue;
+                }
+                if tx
+                    .send(Ok(decoder.decode(records, now_ms())?))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn back_off_and_split_messages() {
+        let reconnect = Reconnect {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+        };
+        let delays: Vec<u64> = (1..=5)
+            .map(|failed| reconnect.delay(failed).as_secs())
+            .collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+
+        assert_eq!(
+            message_records(json!([{"bid": 1.5}, {"bid": 1.6}])).len(),
+            2
+        );
+        assert_eq!(message_records(json!({"bid": 1.5})).len(), 1);
+        assert!(message_records(Value::Null).is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use arrow_array::{RecordBatch, StringArray, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use datafusion::catalog::Session;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use redis::aio::MultiplexedConnection;
+
+use crate::datasource::changelog::{ChangeType, CHANGE_TYPE_COLUMN};
+use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+/// Keys fetched per `MGET` when loading a keyspace
+const MGET_CHUNK: usize = 500;
+
+/// The reference data to load from Redis
+#[derive(Debug, Clone)]
+pub enum RedisReference {
+    /// The fields and values of a hash
+    Hash(String),
+    /// The string keys matching a pattern and their values
+    Keys(String),
+}
+
+/// Reads reference data held in Redis as a changelog of `key` and `value` columns: the entries
+/// once loaded as inserts, and when `rescan_interval` is set, the entries that changed since
+/// the previous scan. Changed values come out as a delete of the old value followed by an
+/// insert of the new one.
+///
+/// The source is a single partition, every row carries the time of the scan that found it as
+/// event time.
+pub struct RedisReferenceSource {
+    client: redis::Client,
+    reference: RedisReference,
+    rescan_interval: Option<Duration>,
+    schema: SchemaRef,
+}
+
+impl RedisReferenceSource {
+    pub fn try_new(
+        url: &str,
+        reference: RedisReference,
+        rescan_interval: Option<Duration>,
+    ) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, false),
+            Field::new(CHANGE_TYPE_COLUMN, DataType::Utf8, false),
+            stream_metadata_field(),
+        ]));
+        Ok(Self {
+            client,
+            reference,
+            rescan_interval,
+            schema,
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for RedisReferenceSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = RedisReferencePartition {
+            client: self.client.clone(),
+            reference: self.reference.clone(),
+            rescan_interval: self.rescan_interval,
+            schema: self.schema.clone(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.schema.clone(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct RedisReferencePartition {
+    client: redis::Client,
+    reference: RedisReference,
+    rescan_interval: Option<Duration>,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for RedisReferencePartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let client = self.client.clone();
+        let reference = self.reference.clone();
+        let rescan_interval = self.rescan_interval;
+        let schema = self.schema.clone();
+
+        // The loaded entries, none before the first scan
+        let state: Option<(MultiplexedConnection, BTreeMap<String, String>)> = None;
+        let stream = futures::stream::try_unfold(state, move |state| {
+            let (client, reference, schema) = (client.clone(), reference.clone(), schema.clone());
+            async move {
+                let (mut connection, previous) = match state {
+                    Some((connection, previous)) => match rescan_interval {
+                        Some(interval) => {
+                            tokio::time::sleep(interval).await;
+                            (connection, previous)
+                        }
+                        None => return Ok(None),
+                    },
+                    None => {
+                        let connection = client
+                            .get_multiplexed_async_connection()
+                            .await
+                            .map_err(redis_error)?;
+                        (connection, BTreeMap::new())
+                    }
+                };
+                let current = load(&mut connection, &reference).await?;
+                let batch = changes_batch(&schema, diff(&previous, &current))?;
+                Ok(Some((batch, Some((connection, current)))))
+            }
+        });
+        Box::pin(RecordBatchStreamAdapter::new(self.schema.clone(), stream))
+    }
+}
+
+async fn load(
+    connection: &mut MultiplexedConnection,
+    reference: &RedisReference,
+) -> Result<BTreeMap<String, String>> {
+    match reference {
+        RedisReference::Hash(key) => redis::cmd("HGETALL")
+            .arg(key)
+            .query_async(connection)
+            .await
+            .map_err(redis_error),
+        RedisReference::Keys(pattern) => {
+            let mut keys = vec![];
+            let mut cursor = 0_u64;
+            loop {
+                let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                    .arg(cursor)
+                    .arg("MATCH")
+                    .arg(pattern)
+                    .arg("COUNT")
+                    .arg(MGET_CHUNK)
+                    .query_async(connection)
+                    .await
+                    .map_err(redis_error)?;
+                keys.extend(batch);
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+            keys.sort_unstable();
+            keys.dedup();
+
+            let mut entries = BTreeMap::new();
+            for chunk in keys.chunks(MGET_CHUNK) {
+                let values: Vec<Option<String>> = redis::cmd("MGET")
+                    .arg(chunk)
+                    .query_async(connection)
+                    .await
+                    .map_err(redis_error)?;
+                // Keys deleted since the scan, or holding something other than a string, have
+                // no value
+                for (key, value) in chunk.iter().zip(values) {
+                    if let Some(value) = value {
+                        entries.insert(key.clone(), value);
+                    }
+                }
+            }
+            Ok(entries)
+        }
+    }
+}
+
+/// The changes turning `previous` into `current`
+fn diff<'a>(
+    previous: &'a BTreeMap<String, String>,
+    current: &'a BTreeMap<String, String>,
+) -> Vec<(&'a str, &'a str, ChangeType)> {
+    let mut changes = vec![];
+    for (key, value) in previous {
+        if current.get(key) != Some(value) {
+            changes.push((key.as_str(), value.as_str(), ChangeType::Delete));
+        }
+    }
+    for (key, value) in current {
+        if previous.get(key) != Some(value) {
+            changes.push((key.as_str(), value.as_str(), ChangeType::Insert));
+        }
+    }
+    changes
+}
+
+fn changes_batch(
+    schema: &SchemaRef,
+    changes: Vec<(&str, &str, ChangeType)>,
+) -> Result<RecordBatch> {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as i64);
+    let rows = changes.len();
+    let keys = StringArray::from_iter_values(changes.iter().map(|(key, _, _)| *key));
+    let values = StringArray::from_iter_values(changes.iter().map(|(_, value, _)| *value));
+    let change_types =
+        StringArray::from_iter_values(changes.iter().map(|(_, _, change)| change.as_str()));
+    Ok(RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(keys),
+            Arc::new(values),
+            Arc::new(change_types),
+            Arc::new(stream_metadata_array(TimestampMillisecondArray::from(
+                vec![now_ms; rows],
+            ))),
+        ],
+    )?)
+}
+
+fn redis_error(err: redis::RedisError) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_rescanned_entries() {
+        let previous = BTreeMap::from([
+            ("de".to_string(), "Germany".to_string()),
+            ("fr".to_string(), "France".to_string()),
+            ("uk".to_string(), "Great Britain".to_string()),
+        ]);
+        let current = BTreeMap::from([
+            ("de".to_string(), "Germany".to_string()),
+            ("nl".to_string(), "Netherlands".to_string()),
+            ("uk".to_string(), "United Kingdom".to_string()),
+        ]);
+        assert_eq!(
+            diff(&previous, &current),
+            [
+                ("fr", "France", ChangeType::Delete),
+                ("uk", "Great Britain", ChangeType::Delete),
+                ("nl", "Netherlands", ChangeType::Insert),
+                ("uk", "United Kingdom", ChangeType::Insert),
+            ]
+        );
+    }
+}
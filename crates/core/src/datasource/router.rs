@@ -0,0 +1,223 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use arrow::compute::kernels::{boolean, cmp};
+use arrow::compute::{cast, filter_record_batch, prep_null_mask_filter};
+use arrow_array::{BooleanArray, RecordBatch, StringArray};
+use arrow_schema::{DataType, Schema};
+use datafusion::common::{exec_err, plan_err, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+use futures::StreamExt;
+
+use crate::datasource::tee::{Branch, FailurePolicy};
+use crate::utils::quota::ResourceQuotas;
+
+/// Column [`DataStream::sink_routed`](crate::datastream::DataStream::sink_routed) evaluates the
+/// route of every row into
+pub const ROUTE_COLUMN: &str = "_route";
+
+/// Splits a stream across sinks by the value of a routing column, e.g. failed rows to a dead
+/// letter topic and the rest to the main one. The column is dropped before the rows are
+/// written.
+///
+/// Rows whose value has no route of its own, null included, go to the default sink. Without
+/// one they fail the pipeline. Every sink writes in a task of its own, a sink failing fails
+/// the pipeline.
+pub struct RouterSink {
+    column: String,
+    routes: Vec<(String, Arc<dyn DataSink>)>,
+    default: Option<Arc<dyn DataSink>>,
+}
+
+impl RouterSink {
+    pub fn new(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            routes: vec![],
+            default: None,
+        }
+    }
+
+    /// Send the rows whose routing column is `value`, cast to a string, to `sink`
+    pub fn with_route(mut self, value: &str, sink: Arc<dyn DataSink>) -> Self {
+        self.routes.push((value.to_string(), sink));
+        self
+    }
+
+    pub fn with_default(mut self, sink: Arc<dyn DataSink>) -> Self {
+        self.default = Some(sink);
+        self
+    }
+
+    /// The rows of every route, and the unrouted rows last, without the routing column
+    fn split(&self, batch: RecordBatch) -> Result<Vec<RecordBatch>> {
+        let Ok(index) = batch.schema().index_of(&self.column) else {
+            return plan_err!("Routing column {} not found", self.column);
+        };
+        let values = cast(batch.column(index), &DataType::Utf8)?;
+        let values = values.as_any().downcast_ref::<StringArray>().unwrap();
+        let mut batch = batch;
+        batch.remove_column(index);
+
+        let mut unrouted = BooleanArray::from(vec![true; batch.num_rows()]);
+        let mut split = vec![];
+        for (value, _) in &self.routes {
+            let rows = prep_null_mask_filter(&cmp::eq(values, &StringArray::new_scalar(value))?);
+            unrouted = boolean::and(&unrouted, &boolean::not(&rows)?)?;
+            split.push(filter_record_batch(&batch, &rows)?);
+        }
+        split.push(filter_record_batch(&batch, &unrouted)?);
+        Ok(split)
+    }
+}
+
+#[async_trait]
+impl DataSink for RouterSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let quotas = ResourceQuotas::from_task_context(context);
+        let schema = data.schema();
+        if schema.column_with_name(&self.column).is_none() {
+            return plan_err!("Routing column {} not found", self.column);
+        }
+        let schema = Arc::new(Schema::new(
+            schema
+                .fields()
+                .iter()
+                .filter(|field| field.name() != &self.column)
+                .cloned()
+                .collect::<Vec<_>>(),
+        ));
+
+        let mut branches = self
+            .routes
+            .iter()
+            .map(|(_, sink)| Some(sink))
+            .chain([self.default.as_ref()])
+            .map(|sink| {
+                sink.map(|sink| Branch::start(sink, FailurePolicy::Fail, schema.clone(), context))
+            })
+            .collect::<Vec<_>>();
+
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            row_count += batch.num_rows() as u64;
+            for (rows, branch) in self.split(batch)?.into_iter().zip(&mut branches) {
+                if rows.num_rows() == 0 {
+                    continue;
+                }
+                let Some(branch) = branch else {
+                    return exec_err!(
+                        "{} rows have no route and the router has no default sink",
+                        rows.num_rows()
+                    );
+                };
+                branch.send(&rows).await?;
+            }
+        }
+        for branch in branches.iter_mut().flatten() {
+            branch.finish().await?;
+        }
+        Ok(row_count)
+    }
+}
+
+impl Debug for RouterSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RouterSink")
+            .field("column", &self.column)
+            .field("routes", &self.routes)
+            .field("default", &self.default)
+            .finish()
+    }
+}
+
+impl DisplayAs for RouterSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "RouterSink ({}: ", self.column)?;
+                for (value, sink) in &self.routes {
+                    write!(f, "{value} => ")?;
+                    sink.fmt_as(t, f)?;
+                    write!(f, ", ")?;
+                }
+                match &self.default {
+                    Some(sink) => {
+                        write!(f, "_ => ")?;
+                        sink.fmt_as(t, f)?;
+                    }
+                    None => write!(f, "_ => error")?,
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::Field;
+
+    use crate::datasource::tee::TeeSink;
+
+    #[test]
+    fn split_rows_by_route() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(ROUTE_COLUMN, DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                Arc::new(StringArray::from(vec![
+                    Some("dlq"),
+                    None,
+                    Some("vip"),
+                    Some("dlq"),
+                ])),
+            ],
+        )
+        .unwrap();
+        let sink = || -> Arc<dyn DataSink> { Arc::new(TeeSink::new()) };
+        let router = RouterSink::new(ROUTE_COLUMN)
+            .with_route("dlq", sink())
+            .with_route("vip", sink());
+
+        let ids = router
+            .split(batch)
+            .unwrap()
+            .iter()
+            .map(|rows| {
+                assert_eq!(rows.num_columns(), 1);
+                rows.column(0).as_primitive::<Int64Type>().values().to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![vec![1, 4], vec![3], vec![2]]);
+    }
+}
@@ -0,0 +1,909 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use apache_avro::schema::{RecordSchema, SchemaKind};
+use apache_avro::Schema as AvroSchema;
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use async_trait::async_trait;
+use datafusion::common::{not_impl_err, plan_err, DataFusionError, Result};
+use serde_json::{json, Value};
+
+#[cfg(feature = "protobuf")]
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+#[cfg(feature = "protobuf")]
+use prost::Message;
+#[cfg(feature = "protobuf")]
+use prost_reflect::prost_types::FileDescriptorProto;
+#[cfg(feature = "protobuf")]
+use prost_reflect::{Cardinality, DescriptorPool, FileDescriptor, Kind, MessageDescriptor};
+
+/// Which earlier versions of a subject a new schema has to stay compatible with, following
+/// the modes of the Confluent schema registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompatibilityMode {
+    None,
+    /// Consumers on the new schema can read data written with the latest one
+    Backward,
+    BackwardTransitive,
+    /// Consumers on the latest schema can read data written with the new one
+    Forward,
+    ForwardTransitive,
+    Full,
+    FullTransitive,
+}
+
+impl CompatibilityMode {
+    fn is_transitive(&self) -> bool {
+        matches!(
+            self,
+            Self::BackwardTransitive | Self::ForwardTransitive | Self::FullTransitive
+        )
+    }
+
+    fn checks_backward(&self) -> bool {
+        matches!(
+            self,
+            Self::Backward | Self::BackwardTransitive | Self::Full | Self::FullTransitive
+        )
+    }
+
+    fn checks_forward(&self) -> bool {
+        matches!(
+            self,
+            Self::Forward | Self::ForwardTransitive | Self::Full | Self::FullTransitive
+        )
+    }
+}
+
+impl FromStr for CompatibilityMode {
+    type Err = DataFusionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "NONE" => Ok(Self::None),
+            "BACKWARD" => Ok(Self::Backward),
+            "BACKWARD_TRANSITIVE" => Ok(Self::BackwardTransitive),
+            "FORWARD" => Ok(Self::Forward),
+            "FORWARD_TRANSITIVE" => Ok(Self::ForwardTransitive),
+            "FULL" => Ok(Self::Full),
+            "FULL_TRANSITIVE" => Ok(Self::FullTransitive),
+            _ => plan_err!("Unrecognised CompatibilityMode {}", s),
+        }
+    }
+}
+
+/// A schema registered under a subject
+#[derive(Debug, Clone)]
+pub enum SubjectSchema {
+    Avro(AvroSchema),
+    /// A protobuf file, its messages are compared with the ones of the same full name. Files
+    /// it imports aren't registered along with it.
+    #[cfg(feature = "protobuf")]
+    Protobuf(FileDescriptor),
+}
+
+impl SubjectSchema {
+    /// The file `message` is declared in
+    #[cfg(feature = "protobuf")]
+    pub fn protobuf(message: &MessageDescriptor) -> Self {
+        Self::Protobuf(message.parent_file())
+    }
+
+    /// A file as the Confluent registry serializes it, a base64 encoded `FileDescriptorProto`
+    #[cfg(feature = "protobuf")]
+    fn from_serialized_protobuf(serialized: &str) -> Result<Self> {
+        let bytes = STANDARD.decode(serialized).map_err(registry_error)?;
+        let file = FileDescriptorProto::decode(bytes.as_slice()).map_err(registry_error)?;
+        let name = file.name().to_string();
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_proto(file)
+            .map_err(registry_error)?;
+        match pool.get_file_by_name(&name) {
+            Some(file) => Ok(Self::Protobuf(file)),
+            None => plan_err!("Protobuf file {name} not found in its own descriptor"),
+        }
+    }
+
+    #[cfg(feature = "protobuf")]
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Avro(_) => "Avro",
+            #[cfg(feature = "protobuf")]
+            Self::Protobuf(_) => "Protobuf",
+        }
+    }
+}
+
+impl From<AvroSchema> for SubjectSchema {
+    fn from(schema: AvroSchema) -> Self {
+        Self::Avro(schema)
+    }
+}
+
+impl PartialEq for SubjectSchema {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Avro(schema), Self::Avro(other)) => schema == other,
+            // Files parsed into different pools only compare equal by their contents
+            #[cfg(feature = "protobuf")]
+            (Self::Protobuf(file), Self::Protobuf(other)) => {
+                file.file_descriptor_proto() == other.file_descriptor_proto()
+            }
+            #[cfg(feature = "protobuf")]
+            _ => false,
+        }
+    }
+}
+
+/// The versions of the schemas registered under a subject
+#[async_trait]
+pub trait SchemaRegistry: Debug + Send + Sync {
+    /// All versions of `subject`, oldest first
+    async fn versions(&self, subject: &str) -> Result<Vec<SubjectSchema>>;
+
+    /// Register `schema` as the latest version of `subject` and return its version number.
+    /// Registering the latest schema again doesn't create a version.
+    async fn register(&self, subject: &str, schema: &SubjectSchema) -> Result<u32>;
+
+    /// The schema with the registry wide `id`, as embedded in messages framed the Confluent way
+    async fn schema_by_id(&self, id: u32) -> Result<SubjectSchema> {
+        not_impl_err!("Schema registry {self:?} can't look up schema {id}")
+    }
+}
+
+/// A registry kept in memory, for tests and local development. Schemas are numbered from 1 in
+/// the order they were first registered.
+#[derive(Debug, Default)]
+pub struct MemorySchemaRegistry {
+    subjects: Mutex<HashMap<String, Vec<SubjectSchema>>>,
+    ids: Mutex<Vec<SubjectSchema>>,
+}
+
+impl MemorySchemaRegistry {
+    /// The id `schema` was registered under, if it was
+    pub fn id_of(&self, schema: &SubjectSchema) -> Option<u32> {
+        let ids = self.ids.lock().unwrap();
+        ids.iter()
+            .position(|registered| registered == schema)
+            .map(|index| index as u32 + 1)
+    }
+}
+
+#[async_trait]
+impl SchemaRegistry for MemorySchemaRegistry {
+    async fn versions(&self, subject: &str) -> Result<Vec<SubjectSchema>> {
+        let subjects = self.subjects.lock().unwrap();
+        Ok(subjects.get(subject).cloned().unwrap_or_default())
+    }
+
+    async fn register(&self, subject: &str, schema: &SubjectSchema) -> Result<u32> {
+        let mut subjects = self.subjects.lock().unwrap();
+        let versions = subjects.entry(subject.to_string()).or_default();
+        if versions.last() != Some(schema) {
+            versions.push(schema.clone());
+            let mut ids = self.ids.lock().unwrap();
+            if !ids.contains(schema) {
+                ids.push(schema.clone());
+            }
+        }
+        Ok(versions.len() as u32)
+    }
+
+    async fn schema_by_id(&self, id: u32) -> Result<SubjectSchema> {
+        let ids = self.ids.lock().unwrap();
+        match ids.get((id as usize).wrapping_sub(1)) {
+            Some(schema) => Ok(schema.clone()),
+            None => plan_err!("Schema {id} is not registered"),
+        }
+    }
+}
+
+/// The REST API of a Confluent schema registry, or one compatible with it
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct ConfluentSchemaRegistry {
+    client: reqwest::Client,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "http")]
+impl ConfluentSchemaRegistry {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            headers: vec![],
+        }
+    }
+
+    /// Send a header with every request, e.g. `Authorization`
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// The response to a request, none if the registry doesn't know what `path` names
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Option<Value>> {
+        let url = format!("{}{path}", self.url);
+        let mut request = self
+            .client
+            .request(method, &url)
+            .header("Accept", "application/vnd.schemaregistry.v1+json");
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/vnd.schemaregistry.v1+json")
+                .body(body.to_string());
+        }
+        let response = request.send().await.map_err(registry_error)?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response.bytes().await.map_err(registry_error)?;
+        if !status.is_success() {
+            return plan_err!(
+                "Schema registry answered {url} with {status}: {}",
+                String::from_utf8_lossy(&body)
+            );
+        }
+        serde_json::from_slice(&body)
+            .map(Some)
+            .map_err(registry_error)
+    }
+
+    async fn schema_at(&self, path: &str) -> Result<SubjectSchema> {
+        let response = self.request(reqwest::Method::GET, path, None).await?;
+        let field = |name: &str| {
+            response
+                .as_ref()
+                .and_then(|response| response.get(name))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let Some(schema) = field("schema") else {
+            return plan_err!("Schema registry returned no schema for {path}");
+        };
+        // Avro schemas are registered without a type
+        match field("schemaType").as_deref().unwrap_or("AVRO") {
+            "AVRO" => Ok(SubjectSchema::Avro(
+                AvroSchema::parse_str(&schema).map_err(registry_error)?,
+            )),
+            // Without a parser for .proto files, ask for the descriptor instead
+            #[cfg(feature = "protobuf")]
+            "PROTOBUF" => {
+                let path = format!("{path}?format=serialized");
+                let response = self.request(reqwest::Method::GET, &path, None).await?;
+                match response
+                    .as_ref()
+                    .and_then(|response| response.get("schema"))
+                    .and_then(Value::as_str)
+                {
+                    Some(serialized) => SubjectSchema::from_serialized_protobuf(serialized),
+                    None => plan_err!("Schema registry returned no schema for {path}"),
+                }
+            }
+            other => not_impl_err!("Schema registry returned a {other} schema for {path}"),
+        }
+    }
+}
+
+fn registry_error(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[cfg(feature = "http")]
+#[async_trait]
+impl SchemaRegistry for ConfluentSchemaRegistry {
+    async fn versions(&self, subject: &str) -> Result<Vec<SubjectSchema>> {
+        let path = format!("/subjects/{subject}/versions");
+        // Subjects nothing was registered under yet are unknown to the registry
+        let versions = self
+            .request(reqwest::Method::GET, &path, None)
+            .await?
+            .unwrap_or_default();
+        let mut schemas = vec![];
+        for version in versions.as_array().into_iter().flatten() {
+            schemas.push(self.schema_at(&format!("{path}/{version}")).await?);
+        }
+        Ok(schemas)
+    }
+
+    async fn register(&self, subject: &str, schema: &SubjectSchema) -> Result<u32> {
+        let body = match schema {
+            SubjectSchema::Avro(schema) => json!({ "schema": schema.canonical_form() }),
+            // The registry accepts a base64 encoded descriptor in place of a .proto file
+            #[cfg(feature = "protobuf")]
+            SubjectSchema::Protobuf(file) => json!({
+                "schemaType": "PROTOBUF",
+                "schema": STANDARD.encode(file.file_descriptor_proto().encode_to_vec()),
+            }),
+        };
+        self.request(
+            reqwest::Method::POST,
+            &format!("/subjects/{subject}/versions"),
+            Some(body),
+        )
+        .await?;
+        let latest = self
+            .request(
+                reqwest::Method::GET,
+                &format!("/subjects/{subject}/versions/latest"),
+                None,
+            )
+            .await?;
+        match latest
+            .as_ref()
+            .and_then(|latest| latest.get("version"))
+            .and_then(Value::as_u64)
+        {
+            Some(version) => Ok(version as u32),
+            None => plan_err!("Schema registry returned no version for subject {subject}"),
+        }
+    }
+
+    async fn schema_by_id(&self, id: u32) -> Result<SubjectSchema> {
+        self.schema_at(&format!("/schemas/ids/{id}")).await
+    }
+}
+
+/// Register `schema` under `subject` once it is compatible with the versions `mode` asks for.
+/// Otherwise nothing is registered and the error lists every incompatibility per version.
+pub async fn register_compatible(
+    registry: &dyn SchemaRegistry,
+    subject: &str,
+    schema: &SubjectSchema,
+    mode: CompatibilityMode,
+) -> Result<u32> {
+    check_compatible(registry, subject, schema, mode).await?;
+    registry.register(subject, schema).await
+}
+
+/// Check that `schema` could be registered under `subject` without registering it
+pub async fn check_compatible(
+    registry: &dyn SchemaRegistry,
+    subject: &str,
+    schema: &SubjectSchema,
+    mode: CompatibilityMode,
+) -> Result<()> {
+    let versions = registry.versions(subject).await?;
+    let checked = match mode.is_transitive() {
+        true => 0,
+        false => versions.len().saturating_sub(1),
+    };
+
+    let mut diff = vec![];
+    for (index, previous) in versions.iter().enumerate().skip(checked) {
+        for problem in compatibility_problems(mode, previous, schema) {
+            diff.push(format!("version {}: {problem}", index + 1));
+        }
+    }
+    if !diff.is_empty() {
+        return plan_err!(
+            "New schema for subject {subject} is not {mode:?} compatible:\n  {}",
+            diff.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// The reasons `proposed` can't replace `previous` under `mode`, empty if it can
+pub fn compatibility_problems(
+    mode: CompatibilityMode,
+    previous: &SubjectSchema,
+    proposed: &SubjectSchema,
+) -> Vec<String> {
+    let mut problems = vec![];
+    if mode.checks_backward() {
+        problems.extend(
+            schema_read_problems(previous, proposed)
+                .into_iter()
+                .map(|problem| format!("new schema can't read old data, {problem}")),
+        );
+    }
+    if mode.checks_forward() {
+        problems.extend(
+            schema_read_problems(proposed, previous)
+                .into_iter()
+                .map(|problem| format!("old schema can't read new data, {problem}")),
+        );
+    }
+    problems
+}
+
+fn schema_read_problems(writer: &SubjectSchema, reader: &SubjectSchema) -> Vec<String> {
+    let mut problems = vec![];
+    match (writer, reader) {
+        (SubjectSchema::Avro(writer), SubjectSchema::Avro(reader)) => {
+            read_problems(writer, reader, "", &mut problems)
+        }
+        #[cfg(feature = "protobuf")]
+        (SubjectSchema::Protobuf(writer), SubjectSchema::Protobuf(reader)) => {
+            protobuf_read_problems(writer, reader, &mut problems)
+        }
+        #[cfg(feature = "protobuf")]
+        (writer, reader) => problems.push(format!(
+            "{} schema can't be read as {}",
+            writer.kind(),
+            reader.kind()
+        )),
+    }
+    problems
+}
+
+/// Collect why data written with `writer` can't be decoded with `reader`, following the
+/// schema resolution rules of the Avro specification
+fn read_problems(writer: &AvroSchema, reader: &AvroSchema, path: &str, problems: &mut Vec<String>) {
+    let location = if path.is_empty() { "<root>" } else { path };
+    match (writer, reader) {
+        // Named types are compared where they are defined
+        (AvroSchema::Ref { .. }, _) | (_, AvroSchema::Ref { .. }) => {}
+        (AvroSchema::Union(writer), _) => {
+            for variant in writer.variants() {
+                read_problems(variant, reader, path, problems);
+            }
+        }
+        (_, AvroSchema::Union(reader)) => {
+            let readable = reader.variants().iter().any(|variant| {
+                let mut variant_problems = vec![];
+                read_problems(writer, variant, path, &mut variant_problems);
+                variant_problems.is_empty()
+            });
+            if !readable {
+                problems.push(format!(
+                    "{location}: {:?} matches none of the union variants",
+                    SchemaKind::from(writer)
+                ));
+            }
+        }
+        (
+            AvroSchema::Record(RecordSchema {
+                fields: writer_fields,
+                ..
+            }),
+            AvroSchema::Record(RecordSchema {
+                fields: reader_fields,
+                ..
+            }),
+        ) => {
+            for field in reader_fields {
+                let field_path = match path.is_empty() {
+                    true => field.name.clone(),
+                    false => format!("{path}.{}", field.name),
+                };
+                let aliases = field.aliases.as_deref().unwrap_or_default();
+                let written = writer_fields
+                    .iter()
+                    .find(|written| written.name == field.name || aliases.contains(&written.name));
+                match written {
+                    Some(written) => {
+                        read_problems(&written.schema, &field.schema, &field_path, problems)
+                    }
+                    None if field.default.is_none() => {
+                        problems.push(format!("{field_path}: missing and has no default"))
+                    }
+                    None => {}
+                }
+            }
+        }
+        (AvroSchema::Array(writer), AvroSchema::Array(reader))
+        | (AvroSchema::Map(writer), AvroSchema::Map(reader)) => {
+            read_problems(writer, reader, &format!("{path}[]"), problems)
+        }
+        (AvroSchema::Enum(writer), AvroSchema::Enum(reader)) => {
+            for symbol in &writer.symbols {
+                if !reader.symbols.contains(symbol) {
+                    problems.push(format!("{location}: enum symbol {symbol} is unknown"));
+                }
+            }
+        }
+        (AvroSchema::Fixed(writer), AvroSchema::Fixed(reader)) if writer.size != reader.size => {
+            problems.push(format!(
+                "{location}: fixed size {} can't be read as {}",
+                writer.size, reader.size
+            ));
+        }
+        (AvroSchema::Decimal(writer), AvroSchema::Decimal(reader))
+            if (writer.precision, writer.scale) != (reader.precision, reader.scale) =>
+        {
+            problems.push(format!(
+                "{location}: decimal({}, {}) can't be read as decimal({}, {})",
+                writer.precision, writer.scale, reader.precision, reader.scale
+            ));
+        }
+        _ if is_promotable(writer, reader) => {}
+        _ => {
+            let (written, read) = (SchemaKind::from(writer), SchemaKind::from(reader));
+            if written != read {
+                problems.push(format!("{location}: {written:?} can't be read as {read:?}"));
+            }
+        }
+    }
+}
+
+/// The type promotions allowed by the Avro specification
+fn is_promotable(writer: &AvroSchema, reader: &AvroSchema) -> bool {
+    matches!(
+        (writer, reader),
+        (
+            AvroSchema::Int,
+            AvroSchema::Long | AvroSchema::Float | AvroSchema::Double
+        ) | (AvroSchema::Long, AvroSchema::Float | AvroSchema::Double)
+            | (AvroSchema::Float, AvroSchema::Double)
+            | (AvroSchema::String, AvroSchema::Bytes)
+            | (AvroSchema::Bytes, AvroSchema::String)
+    )
+}
+
+/// Collect why messages written with the `writer` file can't be decoded with the `reader` file,
+/// following the wire compatibility rules the Confluent registry checks protobuf schemas by
+#[cfg(feature = "protobuf")]
+fn protobuf_read_problems(
+    writer: &FileDescriptor,
+    reader: &FileDescriptor,
+    problems: &mut Vec<String>,
+) {
+    if writer.package_name() != reader.package_name() {
+        problems.push(format!(
+            "package {} can't be read as {}",
+            writer.package_name(),
+            reader.package_name()
+        ));
+        return;
+    }
+    let mut messages: Vec<MessageDescriptor> = writer.messages().collect();
+    while let Some(written) = messages.pop() {
+        messages.extend(
+            written
+                .child_messages()
+                .filter(|child| !child.is_map_entry()),
+        );
+        match reader
+            .parent_pool()
+            .get_message_by_name(written.full_name())
+        {
+            Some(read) => protobuf_message_problems(&written, &read, problems),
+            None => problems.push(format!("{}: message is missing", written.full_name())),
+        }
+    }
+}
+
+#[cfg(feature = "protobuf")]
+fn protobuf_message_problems(
+    writer: &MessageDescriptor,
+    reader: &MessageDescriptor,
+    problems: &mut Vec<String>,
+) {
+    let name = reader.full_name();
+    for field in reader.fields() {
+        let path = format!("{name}.{}", field.name());
+        match writer.get_field(field.number()) {
+            Some(written) if !wire_compatible(&written.kind(), &field.kind()) => {
+                problems.push(format!(
+                    "{path}: {} can't be read as {}",
+                    kind_name(&written.kind()),
+                    kind_name(&field.kind())
+                ))
+            }
+            Some(_) => {}
+            None if field.cardinality() == Cardinality::Required => {
+                problems.push(format!("{path}: required and missing"))
+            }
+            None => {}
+        }
+    }
+
+    for oneof in writer.oneofs().filter(|oneof| !oneof.is_synthetic()) {
+        for field in oneof.fields() {
+            if reader.get_field(field.number()).is_none() {
+                problems.push(format!(
+                    "{name}.{}: removed from oneof {}",
+                    field.name(),
+                    oneof.name()
+                ));
+            }
+        }
+    }
+    // A oneof keeps only one of the fields that were all set before
+    for oneof in reader.oneofs().filter(|oneof| !oneof.is_synthetic()) {
+        let moved = oneof
+            .fields()
+            .filter(|field| {
+                writer
+                    .get_field(field.number())
+                    .map_or(false, |written| written.containing_oneof().is_none())
+            })
+            .count();
+        if moved > 1 {
+            problems.push(format!(
+                "{name}: {moved} fields moved into oneof {}",
+                oneof.name()
+            ));
+        }
+    }
+}
+
+/// Whether values of kind `writer` decode as `reader`, for kinds sharing a wire encoding
+#[cfg(feature = "protobuf")]
+fn wire_compatible(writer: &Kind, reader: &Kind) -> bool {
+    fn encoding(kind: &Kind) -> u8 {
+        match kind {
+            Kind::Int32
+            | Kind::Int64
+            | Kind::Uint32
+            | Kind::Uint64
+            | Kind::Bool
+            | Kind::Enum(_) => 0,
+            Kind::Sint32 | Kind::Sint64 => 1,
+            Kind::Fixed32 | Kind::Sfixed32 => 2,
+            Kind::Fixed64 | Kind::Sfixed64 => 3,
+            Kind::String | Kind::Bytes => 4,
+            Kind::Float => 5,
+            Kind::Double => 6,
+            Kind::Message(_) => 7,
+        }
+    }
+    match (writer, reader) {
+        (Kind::Message(writer), Kind::Message(reader)) => writer.full_name() == reader.full_name(),
+        (Kind::Enum(writer), Kind::Enum(reader)) => writer.full_name() == reader.full_name(),
+        _ => encoding(writer) == encoding(reader),
+    }
+}
+
+#[cfg(feature = "protobuf")]
+fn kind_name(kind: &Kind) -> String {
+    match kind {
+        Kind::Message(message) => message.full_name().to_string(),
+        Kind::Enum(enumeration) => enumeration.full_name().to_string(),
+        scalar => format!("{scalar:?}").to_lowercase(),
+    }
+}
+
+/// The Avro record schema `name` for rows of `schema`. Nullable columns become unions with
+/// null that default to null, so that adding them stays backward compatible.
+pub fn avro_schema_from_arrow(schema: &Schema, name: &str) -> Result<AvroSchema> {
+    let record = avro_record(schema.fields().iter().map(|f| f.as_ref()), name)?;
+    AvroSchema::parse(&record).map_err(|err| DataFusionError::External(err.into()))
+}
+
+fn avro_record<'a>(fields: impl Iterator<Item = &'a Field>, name: &str) -> Result<Value> {
+    let fields = fields
+        .map(|field| {
+            let name_path = format!("{name}_{}", field.name());
+            let data_type = avro_type(field.data_type(), &name_path)?;
+            Ok(match field.is_nullable() {
+                true => json!({
+                    "name": field.name(),
+                    "type": ["null", data_type],
+                    "default": null,
+                }),
+                false => json!({"name": field.name(), "type": data_type}),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(json!({"type": "record", "name": avro_name(name), "fields": fields}))
+}
+
+fn avro_type(data_type: &DataType, name: &str) -> Result<Value> {
+    Ok(match data_type {
+        DataType::Boolean => json!("boolean"),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            json!("int")
+        }
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => json!("long"),
+        DataType::Float16 | DataType::Float32 => json!("float"),
+        DataType::Float64 => json!("double"),
+        DataType::Utf8 | DataType::LargeUtf8 => json!("string"),
+        DataType::Binary | DataType::LargeBinary => json!("bytes"),
+        DataType::Dictionary(_, value_type) => avro_type(value_type, name)?,
+        DataType::Date32 => json!({"type": "int", "logicalType": "date"}),
+        DataType::Timestamp(TimeUnit::Second | TimeUnit::Millisecond, _) => {
+            json!({"type": "long", "logicalType": "timestamp-millis"})
+        }
+        DataType::Timestamp(TimeUnit::Microsecond | TimeUnit::Nanosecond, _) => {
+            json!({"type": "long", "logicalType": "timestamp-micros"})
+        }
+        DataType::Decimal128(precision, scale) => json!({
+            "type": "bytes",
+            "logicalType": "decimal",
+            "precision": precision,
+            "scale": scale,
+        }),
+        DataType::List(item) | DataType::LargeList(item) => {
+            json!({"type": "array", "items": avro_field_type(item, name)?})
+        }
+        DataType::Map(entries, _) => match entries.data_type() {
+            DataType::Struct(kv) if kv[0].data_type() == &DataType::Utf8 => {
+                json!({"type": "map", "values": avro_field_type(&kv[1], name)?})
+            }
+            _ => return not_impl_err!("Avro maps need string keys, got {data_type}"),
+        },
+        DataType::Struct(fields) => avro_record(fields.iter().map(|f| f.as_ref()), name)?,
+        _ => return not_impl_err!("No Avro type for {data_type}"),
+    })
+}
+
+fn avro_field_type(field: &Field, name: &str) -> Result<Value> {
+    let data_type = avro_type(field.data_type(), name)?;
+    Ok(match field.is_nullable() {
+        true => json!(["null", data_type]),
+        false => data_type,
+    })
+}
+
+/// Avro names may only hold letters, digits and underscores
+fn avro_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.starts_with(|c: char| c.is_ascii_digit()) {
+        true => format!("_{name}"),
+        false => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "protobuf")]
+    use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+    #[cfg(feature = "protobuf")]
+    use prost_reflect::prost_types::{DescriptorProto, FieldDescriptorProto};
+
+    fn record(fields: &str) -> SubjectSchema {
+        AvroSchema::parse_str(&format!(
+            r#"{{"type": "record", "name": "reading", "fields": [{fields}]}}"#
+        ))
+        .unwrap()
+        .into()
+    }
+
+    #[cfg(feature = "protobuf")]
+    fn proto_file(fields: &[(&str, i32, Type)]) -> SubjectSchema {
+        let field = fields
+            .iter()
+            .map(|(name, number, kind)| FieldDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(*number),
+                label: Some(Label::Optional as i32),
+                r#type: Some(*kind as i32),
+                ..Default::default()
+            })
+            .collect();
+        let file = FileDescriptorProto {
+            name: Some("reading.proto".to_string()),
+            package: Some("sensors".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Reading".to_string()),
+                field,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_proto(file).unwrap();
+        SubjectSchema::Protobuf(pool.get_file_by_name("reading.proto").unwrap())
+    }
+
+    #[test]
+    fn check_compatibility_modes() {
+        let v1 =
+            record(r#"{"name": "sensor", "type": "string"}, {"name": "count", "type": "int"}"#);
+        let with_default = record(
+            r#"{"name": "sensor", "type": "string"}, {"name": "count", "type": "long"},
+               {"name": "region", "type": ["null", "string"], "default": null}"#,
+        );
+        let without_default = record(
+            r#"{"name": "sensor", "type": "string"}, {"name": "count", "type": "int"},
+               {"name": "region", "type": "string"}"#,
+        );
+
+        assert!(compatibility_problems(CompatibilityMode::Backward, &v1, &with_default).is_empty());
+        assert_eq!(
+            compatibility_problems(CompatibilityMode::Backward, &v1, &without_default),
+            ["new schema can't read old data, region: missing and has no default"]
+        );
+        // Old readers can't narrow the long back to an int
+        assert_eq!(
+            compatibility_problems(CompatibilityMode::Full, &v1, &with_default),
+            ["old schema can't read new data, count: Long can't be read as Int"]
+        );
+        assert!(compatibility_problems(CompatibilityMode::None, &v1, &without_default).is_empty());
+    }
+
+    #[tokio::test]
+    async fn register_checked_versions() -> Result<()> {
+        let registry = MemorySchemaRegistry::default();
+        let readings = Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("count", DataType::Int32, true),
+        ]);
+        let v1 = avro_schema_from_arrow(&readings, "sensor-readings")?.into();
+        assert_eq!(
+            register_compatible(
+                &registry,
+                "readings-value",
+                &v1,
+                CompatibilityMode::Backward
+            )
+            .await?,
+            1
+        );
+
+        let dropped_sensor = Schema::new(vec![
+            Field::new("count", DataType::Int32, true),
+            Field::new("sensor_id", DataType::Int64, false),
+        ]);
+        let v2 = avro_schema_from_arrow(&dropped_sensor, "sensor-readings")?.into();
+        let err = register_compatible(
+            &registry,
+            "readings-value",
+            &v2,
+            CompatibilityMode::Backward,
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains(
+                "version 1: new schema can't read old data, sensor_id: missing and has no default"
+            ),
+            "{err}"
+        );
+        assert_eq!(registry.versions("readings-value").await?.len(), 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[tokio::test]
+    async fn register_checked_protobuf_versions() -> Result<()> {
+        let v1 = proto_file(&[("sensor", 1, Type::String), ("count", 2, Type::Int32)]);
+        // Strings read as bytes and int32 as int64 on the wire
+        let v2 = proto_file(&[
+            ("sensor", 1, Type::Bytes),
+            ("count", 2, Type::Int64),
+            ("region", 3, Type::String),
+        ]);
+        let v3 = proto_file(&[("sensor", 1, Type::String), ("count", 2, Type::Double)]);
+        assert!(compatibility_problems(CompatibilityMode::Full, &v1, &v2).is_empty());
+        assert_eq!(
+            compatibility_problems(CompatibilityMode::Backward, &v1, &v3),
+            ["new schema can't read old data, sensors.Reading.count: int32 can't be read as double"]
+        );
+        assert_eq!(
+            compatibility_problems(CompatibilityMode::Backward, &record(""), &v1),
+            ["new schema can't read old data, Avro schema can't be read as Protobuf"]
+        );
+
+        // The registry serializes files as base64 encoded descriptors
+        let SubjectSchema::Protobuf(file) = &v1 else {
+            unreachable!()
+        };
+        let serialized = STANDARD.encode(file.file_descriptor_proto().encode_to_vec());
+        assert_eq!(SubjectSchema::from_serialized_protobuf(&serialized)?, v1);
+
+        let registry = MemorySchemaRegistry::default();
+        let subject = "readings-value";
+        let mode = CompatibilityMode::Backward;
+        assert_eq!(register_compatible(&registry, subject, &v1, mode).await?, 1);
+        assert_eq!(register_compatible(&registry, subject, &v1, mode).await?, 1);
+        assert!(register_compatible(&registry, subject, &v3, mode)
+            .await
+            .is_err());
+        assert_eq!(register_compatible(&registry, subject, &v2, mode).await?, 2);
+        Ok(())
+    }
+}
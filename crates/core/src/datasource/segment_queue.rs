@@ -0,0 +1,465 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::instant::Instant;
+use datafusion::common::Result;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan};
+use futures::StreamExt;
+use log::{debug, warn};
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::file_watch::conform;
+use crate::datasource::message::JsonMessageDecoder;
+use crate::utils::quota::ResourceQuotas;
+
+const SEGMENT_EXTENSION: &str = "arrows";
+const OPEN_SUFFIX: &str = ".open";
+
+/// Buffers a stream on local disk, for edge devices whose uplink to Kafka or S3 comes and
+/// goes. A pipeline sinks into the queue, a second one drains it with a
+/// [`SegmentQueueSource`] and forwards the rows once the uplink is back.
+///
+/// Batches are appended to segment files in Arrow IPC stream format and synced to disk as they
+/// are written. A segment is sealed, and visible to the source, once it reaches the segment
+/// size or age, or when the stream ends. When the queue outgrows its size limit the oldest
+/// sealed segments are deleted, dropping their rows, so a long outage loses the oldest data
+/// rather than filling the disk.
+pub struct SegmentQueueSink {
+    dir: PathBuf,
+    max_bytes: u64,
+    segment_bytes: u64,
+    segment_age: Duration,
+}
+
+impl SegmentQueueSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: 1 << 30,
+            segment_bytes: 16 << 20,
+            segment_age: Duration::from_secs(10),
+        }
+    }
+
+    /// Size the segments of the queue may take up together, 1 GiB by default
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Size at which a segment is sealed, 16 MiB by default
+    pub fn with_segment_bytes(mut self, segment_bytes: u64) -> Self {
+        self.segment_bytes = segment_bytes;
+        self
+    }
+
+    /// Time after which a segment is sealed, and so becomes available to drain, even if it
+    /// didn't fill up. 10 seconds by default.
+    pub fn with_segment_age(mut self, segment_age: Duration) -> Self {
+        self.segment_age = segment_age;
+        self
+    }
+
+    /// Delete the oldest sealed segments until the queue fits its size limit
+    fn enforce_retention(&self) -> Result<()> {
+        let mut segments = list_segments(&self.dir)?;
+        let mut total: u64 = segments.iter().map(|segment| segment.bytes).sum();
+        segments.retain(|segment| segment.sealed);
+        for segment in segments {
+            if total <= self.max_bytes {
+                break;
+            }
+            warn!(
+                "Segment queue {} is over its size limit, dropping {}",
+                self.dir.display(),
+                segment.path.display()
+            );
+            fs::remove_file(&segment.path)?;
+            total -= segment.bytes;
+        }
+        Ok(())
+    }
+}
+
+/// The segment a sink is appending to
+struct OpenSegment {
+    path: PathBuf,
+    sealed_path: PathBuf,
+    writer: StreamWriter<File>,
+    opened: Instant,
+}
+
+impl OpenSegment {
+    fn create(dir: &Path, sequence: u64, schema: &SchemaRef) -> Result<Self> {
+        let sealed_path = dir.join(segment_name(sequence));
+        let path = dir.join(format!("{}{OPEN_SUFFIX}", segment_name(sequence)));
+        let writer = StreamWriter::try_new(File::create(&path)?, schema)?;
+        Ok(Self {
+            path,
+            sealed_path,
+            writer,
+            opened: Instant::now(),
+        })
+    }
+
+    fn append(&mut self, batch: &RecordBatch) -> Result<u64> {
+        self.writer.write(batch)?;
+        let file = self.writer.get_ref();
+        file.sync_data()?;
+        Ok(file.metadata()?.len())
+    }
+
+    fn seal(mut self) -> Result<()> {
+        self.writer.finish()?;
+        self.writer.get_ref().sync_data()?;
+        fs::rename(&self.path, &self.sealed_path)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataSink for SegmentQueueSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let quotas = ResourceQuotas::from_task_context(context);
+        fs::create_dir_all(&self.dir)?;
+        // Segments left open by a previous run hold whatever made it to disk before it stopped
+        for segment in list_segments(&self.dir)? {
+            if !segment.sealed {
+                fs::rename(&segment.path, self.dir.join(segment_name(segment.sequence)))?;
+            }
+        }
+        let mut sequence = list_segments(&self.dir)?
+            .last()
+            .map_or(0, |segment| segment.sequence);
+
+        let schema = data.schema();
+        let mut segment: Option<OpenSegment> = None;
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            if segment
+                .as_ref()
+                .is_some_and(|open| open.opened.elapsed() >= self.segment_age)
+            {
+                segment.take().unwrap().seal()?;
+            }
+            if segment.is_none() {
+                sequence += 1;
+                segment = Some(OpenSegment::create(&self.dir, sequence, &schema)?);
+            }
+            if segment.as_mut().unwrap().append(&batch)? >= self.segment_bytes {
+                segment.take().unwrap().seal()?;
+            }
+            self.enforce_retention()?;
+            row_count += batch.num_rows() as u64;
+        }
+        if let Some(open) = segment {
+            open.seal()?;
+        }
+        Ok(row_count)
+    }
+}
+
+impl Debug for SegmentQueueSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SegmentQueueSink")
+            .field("dir", &self.dir)
+            .field("max_bytes", &self.max_bytes)
+            .finish()
+    }
+}
+
+impl DisplayAs for SegmentQueueSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "SegmentQueueSink ({})", self.dir.display())
+            }
+        }
+    }
+}
+
+/// Drains the queue a [`SegmentQueueSink`] writes to, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// Sealed segments are replayed oldest first and deleted once their batches are sent
+/// downstream, the directory is checked for new ones every `poll_interval`. Columns are
+/// matched to the decoder's message schema by name. Rows take their event time from the
+/// decoder's timestamp column, or from the time their segment was sealed. With checkpointing
+/// enabled every batch closes an epoch.
+pub struct SegmentQueueSource {
+    dir: PathBuf,
+    decoder: JsonMessageDecoder,
+    poll_interval: Duration,
+}
+
+impl SegmentQueueSource {
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        decoder: JsonMessageDecoder,
+        poll_interval: Duration,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            decoder,
+            poll_interval,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for SegmentQueueSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = SegmentQueuePartition {
+            dir: self.dir.clone(),
+            decoder: self.decoder.clone(),
+            poll_interval: self.poll_interval,
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct SegmentQueuePartition {
+    dir: PathBuf,
+    decoder: JsonMessageDecoder,
+    poll_interval: Duration,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for SegmentQueuePartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let should_checkpoint = ctx
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let epoch_tracker = EpochTracker::from_task_context(&ctx);
+
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let dir = self.dir.clone();
+        let decoder = self.decoder.clone();
+        let poll_interval = self.poll_interval;
+
+        builder.spawn(async move {
+            let mut epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current());
+            let mut poll = tokio::time::interval(poll_interval);
+            loop {
+                poll.tick().await;
+                let segments = if dir.exists() {
+                    list_segments(&dir)?
+                } else {
+                    vec![]
+                };
+                for segment in segments.into_iter().filter(|segment| segment.sealed) {
+                    debug!("Draining segment {}", segment.path.display());
+                    let sealed_ms = fs::metadata(&segment.path)?
+                        .modified()?
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |since| since.as_millis() as i64);
+                    for batch in read_segment(&segment.path)? {
+                        let batch = conform(&batch, &decoder.message_schema())?;
+                        let batch = if should_checkpoint {
+                            epoch += 1;
+                            if let Some(tracker) = &epoch_tracker {
+                                tracker.advance(epoch)?;
+                            }
+                            decoder.decode_batch_checkpointed(batch, sealed_ms, epoch)?
+                        } else {
+                            decoder.decode_batch(batch, sealed_ms)?
+                        };
+                        if tx.send(Ok(batch)).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    fs::remove_file(&segment.path)?;
+                }
+            }
+        });
+        builder.build()
+    }
+}
+
+/// A segment file of a queue directory
+#[derive(Debug)]
+pub(crate) struct Segment {
+    pub(crate) sequence: u64,
+    pub(crate) path: PathBuf,
+    pub(crate) bytes: u64,
+    pub(crate) sealed: bool,
+}
+
+fn segment_name(sequence: u64) -> String {
+    format!("{sequence:020}.{SEGMENT_EXTENSION}")
+}
+
+/// The segments in `dir`, oldest first
+pub(crate) fn list_segments(dir: &Path) -> Result<Vec<Segment>> {
+    let mut segments = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let (stem, sealed) = match name.strip_suffix(OPEN_SUFFIX) {
+            Some(stem) => (stem, false),
+            None => (name.as_str(), true),
+        };
+        let Some(sequence) = stem
+            .strip_suffix(&format!(".{SEGMENT_EXTENSION}"))
+            .and_then(|sequence| sequence.parse().ok())
+        else {
+            continue;
+        };
+        segments.push(Segment {
+            sequence,
+            path: entry.path(),
+            bytes: entry.metadata()?.len(),
+            sealed,
+        });
+    }
+    segments.sort_by_key(|segment| segment.sequence);
+    Ok(segments)
+}
+
+/// The batches of a segment. A segment cut short by a crash yields the batches before the cut.
+pub(crate) fn read_segment(path: &Path) -> Result<Vec<RecordBatch>> {
+    let reader = match StreamReader::try_new(File::open(path)?, None) {
+        Ok(reader) => reader,
+        Err(err) => {
+            warn!("Skipping unreadable segment {}: {err}", path.display());
+            return Ok(vec![]);
+        }
+    };
+    let mut batches = vec![];
+    for batch in reader {
+        match batch {
+            Ok(batch) => batches.push(batch),
+            Err(err) => {
+                warn!("Segment {} is truncated: {err}", path.display());
+                break;
+            }
+        }
+    }
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+
+    async fn write(sink: &SegmentQueueSink, batches: Vec<RecordBatch>) -> Result<u64> {
+        let schema = batches[0].schema();
+        let stream = futures::stream::iter(batches.into_iter().map(Ok));
+        sink.write_all(
+            Box::pin(RecordBatchStreamAdapter::new(schema, stream)),
+            &Arc::new(TaskContext::default()),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn append_and_drop_oldest_segments() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("segment_queue_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = |id: i64| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![id]))])
+                .unwrap()
+        };
+
+        let sink = SegmentQueueSink::new(&dir).with_segment_bytes(1);
+        assert_eq!(write(&sink, vec![batch(1), batch(2), batch(3)]).await?, 3);
+        let segments = list_segments(&dir)?;
+        assert_eq!(
+            segments.iter().map(|s| s.sequence).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(segments.iter().all(|s| s.sealed));
+        let ids = read_segment(&segments[1].path)?;
+        assert_eq!(ids[0].column(0).as_primitive::<Int64Type>().value(0), 2);
+
+        // Room for three segments, the fourth pushes out the oldest
+        let sink = sink.with_max_bytes(segments.iter().map(|s| s.bytes).sum());
+        write(&sink, vec![batch(4)]).await?;
+        assert_eq!(
+            list_segments(&dir)?
+                .iter()
+                .map(|s| s.sequence)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
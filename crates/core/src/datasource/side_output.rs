@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::warn;
+use tokio::sync::broadcast;
+
+/// Number of batches buffered per side output before slow readers start missing batches.
+const SIDE_OUTPUT_CAPACITY: usize = 64;
+
+/// Named secondary streams that operators can emit rows onto, e.g. rows arriving after their
+/// window was closed. Every side output has a fixed schema and any number of readers.
+///
+/// The registry is shared by the whole session, operators get hold of it through
+/// [`SideOutputRegistry::from_task_context`].
+#[derive(Debug, Default)]
+pub struct SideOutputRegistry {
+    outputs: Mutex<HashMap<String, SideOutput>>,
+}
+
+impl SideOutputRegistry {
+    pub fn from_task_context(context: &TaskContext) -> Result<Arc<Self>> {
+        context
+            .session_config()
+            .get_extension::<Self>()
+            .ok_or_else(|| {
+                DataFusionError::Internal("No side output registry in the session".to_string())
+            })
+    }
+
+    /// The side output registered under `tag`, creating it if needed. Fails when the tag is
+    /// already in use with a different schema.
+    pub fn output(&self, tag: &str, schema: SchemaRef) -> Result<SideOutput> {
+        let mut outputs = self.outputs.lock().unwrap();
+        if let Some(output) = outputs.get(tag) {
+            if output.schema != schema {
+                return plan_err!(
+                    "Side output '{tag}' is already registered with schema {:?}",
+                    output.schema
+                );
+            }
+            return Ok(output.clone());
+        }
+
+        let (sender, _) = broadcast::channel(SIDE_OUTPUT_CAPACITY);
+        let output = SideOutput {
+            tag: tag.to_string(),
+            schema,
+            sender,
+        };
+        outputs.insert(tag.to_string(), output.clone());
+        Ok(output)
+    }
+}
+
+/// Handle used by operators to emit rows onto a side output
+#[derive(Debug, Clone)]
+pub struct SideOutput {
+    tag: String,
+    schema: SchemaRef,
+    sender: broadcast::Sender<RecordBatch>,
+}
+
+impl SideOutput {
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Never blocks. Rows are dropped when nobody is reading the side output.
+    pub fn emit(&self, batch: RecordBatch) {
+        if batch.num_rows() > 0 {
+            let _ = self.sender.send(batch);
+        }
+    }
+}
+
+/// Table provider that reads a side output as an unbounded stream
+pub struct SideOutputReader(SideOutput);
+
+impl SideOutputReader {
+    pub fn new(output: SideOutput) -> Self {
+        Self(output)
+    }
+}
+
+#[async_trait]
+impl TableProvider for SideOutputReader {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.0.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.0.schema.clone(),
+            vec![Arc::new(SideOutputPartition(self.0.clone()))],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct SideOutputPartition(SideOutput);
+
+impl PartitionStream for SideOutputPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.0.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let receiver = self.0.sender.subscribe();
+        let tag = self.0.tag.clone();
+        let stream = futures::stream::unfold(receiver, move |mut receiver| {
+            let tag = tag.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(batch) => return Some((Ok(batch), receiver)),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("Reader of side output '{tag}' fell behind, skipped {skipped} batches")
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        Box::pin(RecordBatchStreamAdapter::new(self.0.schema.clone(), stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn read_rows_emitted_onto_a_side_output() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let registry = SideOutputRegistry::default();
+        let output = registry.output("late", schema.clone())?;
+        let other = Arc::new(Schema::new(vec![Field::new("id", DataType::Utf8, false)]));
+        assert!(registry.output("late", other).is_err());
+
+        let mut reader = SideOutputPartition(registry.output("late", schema.clone())?)
+            .execute(Arc::new(TaskContext::default()));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![7]))])?;
+        output.emit(RecordBatch::new_empty(schema));
+        output.emit(batch.clone());
+        assert_eq!(reader.next().await.transpose()?, Some(batch));
+
+        // The stream ends once every handle to the side output is gone
+        drop(output);
+        drop(registry);
+        assert!(reader.next().await.is_none());
+        Ok(())
+    }
+}
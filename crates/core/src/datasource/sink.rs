@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::{not_impl_err, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::insert::{DataSink, DataSinkExec};
+use datafusion::physical_plan::ExecutionPlan;
+
+/// Table that writes whatever is inserted into it to a [`DataSink`], so that any sink can be
+/// the target of [`DataStream::sink`](crate::datastream::DataStream::sink)
+#[derive(Debug)]
+pub struct SinkTable {
+    schema: SchemaRef,
+    sink: Arc<dyn DataSink>,
+}
+
+impl SinkTable {
+    pub fn new(schema: SchemaRef, sink: Arc<dyn DataSink>) -> Self {
+        Self { schema, sink }
+    }
+}
+
+#[async_trait]
+impl TableProvider for SinkTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        _projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        not_impl_err!("Sinks can't be read from")
+    }
+
+    async fn insert_into(
+        &self,
+        _state: &dyn Session,
+        input: Arc<dyn ExecutionPlan>,
+        overwrite: bool,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if overwrite {
+            return not_impl_err!("Overwrite not implemented for sinks");
+        }
+        Ok(Arc::new(DataSinkExec::new(
+            input,
+            self.sink.clone(),
+            self.schema.clone(),
+            None,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fmt;
+    use std::sync::Mutex;
+
+    use arrow_array::{Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::datasource::MemTable;
+    use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+    use datafusion::logical_expr::{col, lit};
+    use datafusion::physical_plan::metrics::MetricsSet;
+    use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+    use futures::StreamExt;
+
+    use crate::context::Context;
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+    #[derive(Debug, Default)]
+    struct CollectSink {
+        batches: Mutex<Vec<RecordBatch>>,
+    }
+
+    impl DisplayAs for CollectSink {
+        fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "CollectSink")
+        }
+    }
+
+    #[async_trait]
+    impl DataSink for CollectSink {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn metrics(&self) -> Option<MetricsSet> {
+            None
+        }
+
+        async fn write_all(
+            &self,
+            mut data: SendableRecordBatchStream,
+            _context: &Arc<TaskContext>,
+        ) -> Result<u64> {
+            let mut rows = 0;
+            while let Some(batch) = data.next().await.transpose()? {
+                rows += batch.num_rows() as u64;
+                self.batches.lock().unwrap().push(batch);
+            }
+            Ok(rows)
+        }
+    }
+
+    #[tokio::test]
+    async fn sink_rows_without_stream_metadata() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(stream_metadata_array(
+                    vec![Some(1_000), Some(2_000), Some(3_000)].into(),
+                )),
+            ],
+        )?;
+        let table = MemTable::try_new(schema, vec![vec![batch]])?;
+
+        let sink = Arc::new(CollectSink::default());
+        Context::new()?
+            .from_source("events", Arc::new(table))
+            .await?
+            .filter(col("id").gt(lit(1_i64)))?
+            .sink("collected", sink.clone())
+            .await?;
+
+        let batches = sink.batches.lock().unwrap();
+        let ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|batch| {
+                assert_eq!(batch.num_columns(), 1);
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(ids, [2, 3]);
+        Ok(())
+    }
+}
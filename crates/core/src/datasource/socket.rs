@@ -0,0 +1,336 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::future::Future;
+use std::io::Cursor;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use arrow::compute::concat_batches;
+use arrow::csv::ReaderBuilder;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::Result;
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use futures::Stream;
+use log::warn;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::datasource::message::{next_chunk, now_ms, JsonMessageDecoder, MAX_BATCH_MESSAGES};
+use crate::utils::json_parse::parse_json;
+
+/// The maximum size of a UDP datagram
+const MAX_DATAGRAM_BYTES: usize = 65_535;
+
+/// Where a socket source listens for connections
+#[derive(Debug, Clone)]
+pub enum SocketAddress {
+    /// A `host:port` to bind
+    Tcp(String),
+    /// The path of a unix domain socket, replacing a stale socket left at the path
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// How the lines received by a socket source are encoded
+#[derive(Debug, Clone, Copy)]
+pub enum LineFormat {
+    Json,
+    /// Fields in the order of the schema, without a header
+    Csv {
+        delimiter: u8,
+    },
+}
+
+/// Accepts connections on a TCP or unix domain socket and reads newline delimited records
+/// from all of them, e.g. from the `tcp` output of fluent-bit, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// Blank lines are skipped, a line that can't be decoded fails the source. The connection of
+/// a client that goes away is dropped without affecting the others.
+pub struct SocketSource {
+    address: SocketAddress,
+    format: LineFormat,
+    decoder: JsonMessageDecoder,
+}
+
+impl SocketSource {
+    pub fn new(address: SocketAddress, format: LineFormat, decoder: JsonMessageDecoder) -> Self {
+        Self {
+            address,
+            format,
+            decoder,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for SocketSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = SocketPartition {
+            address: self.address.clone(),
+            format: self.format,
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct SocketPartition {
+    address: SocketAddress,
+    format: LineFormat,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for SocketPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let address = self.address.clone();
+        let format = self.format;
+        let decoder = self.decoder.clone();
+
+        builder.spawn(async move {
+            let mut lines = listen_lines(&address).await?;
+            while let Some(chunk) = next_chunk(&mut lines).await {
+                let lines = chunk.into_iter().collect::<Result<Vec<_>>>()?;
+                let batch = match format {
+                    LineFormat::Json => {
+                        let messages = lines
+                            .iter()
+                            .map(|line| parse_json(line.as_bytes()))
+                            .collect::<Result<Vec<_>>>()?;
+                        decoder.decode(messages, now_ms())?
+                    }
+                    LineFormat::Csv { delimiter } => {
+                        let batch = read_csv(decoder.message_schema(), delimiter, &lines)?;
+                        decoder.decode_batch(batch, now_ms())?
+                    }
+                };
+                if tx.send(Ok(batch)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+/// The non blank lines received on all connections to a listening socket, ending with an
+/// error if the socket stops accepting connections
+pub(crate) struct Lines {
+    receiver: mpsc::Receiver<Result<String>>,
+    _acceptor: SpawnedTask<()>,
+}
+
+impl Lines {
+    /// Lines `acceptor` sends to `receiver`, the acceptor is stopped along with the stream
+    pub(crate) fn new(receiver: mpsc::Receiver<Result<String>>, acceptor: SpawnedTask<()>) -> Self {
+        Self {
+            receiver,
+            _acceptor: acceptor,
+        }
+    }
+}
+
+impl Stream for Lines {
+    type Item = Result<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+pub(crate) async fn listen_lines(address: &SocketAddress) -> Result<Lines> {
+    let (sender, receiver) = mpsc::channel(MAX_BATCH_MESSAGES);
+    let acceptor = match address {
+        SocketAddress::Tcp(address) => serve(TcpListener::bind(address).await?, sender, read_lines),
+        #[cfg(unix)]
+        SocketAddress::Unix(path) => {
+            use std::os::unix::fs::FileTypeExt;
+            if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                std::fs::remove_file(path)?;
+            }
+            serve(tokio::net::UnixListener::bind(path)?, sender, read_lines)
+        }
+    };
+    Ok(Lines::new(receiver, acceptor))
+}
+
+#[async_trait]
+pub(crate) trait Listener: Send + Sync + 'static {
+    type Connection: AsyncRead + Unpin + Send + 'static;
+
+    async fn accept_connection(&self) -> std::io::Result<Self::Connection>;
+}
+
+#[async_trait]
+impl Listener for TcpListener {
+    type Connection = TcpStream;
+
+    async fn accept_connection(&self) -> std::io::Result<TcpStream> {
+        Ok(self.accept().await?.0)
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl Listener for tokio::net::UnixListener {
+    type Connection = tokio::net::UnixStream;
+
+    async fn accept_connection(&self) -> std::io::Result<Self::Connection> {
+        Ok(self.accept().await?.0)
+    }
+}
+
+/// Hand every connection `listener` accepts to `read`, which sends what it reads from the
+/// connection, until the receiver goes away
+pub(crate) fn serve<L, F, Fut>(
+    listener: L,
+    sender: mpsc::Sender<Result<String>>,
+    read: F,
+) -> SpawnedTask<()>
+where
+    L: Listener,
+    F: Fn(L::Connection, mpsc::Sender<Result<String>>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    SpawnedTask::spawn(async move {
+        let mut connections = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept_connection() => match accepted {
+                    Ok(connection) => {
+                        connections.spawn(read(connection, sender.clone()));
+                    }
+                    Err(err) => {
+                        let _ = sender.send(Err(err.into())).await;
+                        return;
+                    }
+                },
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = sender.closed() => return,
+            }
+        }
+    })
+}
+
+/// Send every datagram `socket` receives as one string, until the receiver goes away
+pub(crate) fn receive_datagrams(
+    socket: UdpSocket,
+    sender: mpsc::Sender<Result<String>>,
+) -> SpawnedTask<()> {
+    SpawnedTask::spawn(async move {
+        let mut buf = vec![0; MAX_DATAGRAM_BYTES];
+        loop {
+            tokio::select! {
+                received = socket.recv_from(&mut buf) => {
+                    let datagram = match received {
+                        Ok((len, _)) => Ok(String::from_utf8_lossy(&buf[..len]).into_owned()),
+                        Err(err) => Err(err.into()),
+                    };
+                    let failed = datagram.is_err();
+                    if sender.send(datagram).await.is_err() || failed {
+                        return;
+                    }
+                }
+                _ = sender.closed() => return,
+            }
+        }
+    })
+}
+
+async fn read_lines(stream: impl AsyncRead + Unpin, sender: mpsc::Sender<Result<String>>) {
+    let mut lines = BufReader::new(stream).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) if line.trim().is_empty() => {}
+            Ok(Some(line)) => {
+                if sender.send(Ok(line)).await.is_err() {
+                    return;
+                }
+            }
+            Ok(None) => return,
+            Err(err) => {
+                warn!("Dropped socket connection: {err}");
+                return;
+            }
+        }
+    }
+}
+
+fn read_csv(schema: SchemaRef, delimiter: u8, lines: &[String]) -> Result<RecordBatch> {
+    let reader = ReaderBuilder::new(schema.clone())
+        .with_delimiter(delimiter)
+        .with_batch_size(lines.len().max(1))
+        .build(Cursor::new(lines.join("\n")))?;
+    let batches = reader.collect::<Result<Vec<_>, _>>()?;
+    Ok(concat_batches(&schema, &batches)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, Float64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    #[test]
+    fn read_csv_lines() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("reading", DataType::Float64, true),
+        ]));
+        let lines = vec!["s-1;20.5".to_string(), "s-2;".to_string()];
+
+        let batch = read_csv(schema, b';', &lines).unwrap();
+        let sensors = batch.column(0).as_any().downcast_ref::<StringArray>();
+        assert_eq!(sensors.unwrap().value(1), "s-2");
+        let readings = batch.column(1).as_any().downcast_ref::<Float64Array>();
+        assert_eq!(readings.unwrap().value(0), 20.5);
+        assert!(readings.unwrap().is_null(1));
+    }
+}
@@ -0,0 +1,273 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::{exec_err, plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::streaming::StreamingTableExec;
+use datafusion::physical_plan::ExecutionPlan;
+use futures::StreamExt;
+use log::info;
+use reqwest::header::{ACCEPT, CACHE_CONTROL};
+use reqwest::Url;
+use tokio::sync::mpsc;
+
+use crate::datasource::message::JsonMessageDecoder;
+use crate::datasource::push::{Feed, PushPartition, Reconnect};
+
+const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+/// Reads the JSON data of Server-Sent Events from an HTTP endpoint, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// The data of an event holds a record or an array of them, data that isn't JSON fails the
+/// source. Without event types every event is read, otherwise only events of those types.
+/// When the stream fails or the server ends it the source connects again as the
+/// [`Reconnect`] policy says, sending the id of the last event it received as
+/// `Last-Event-ID` so servers that support it resume the stream there. The `retry` field of
+/// the stream is ignored.
+pub struct SseSource {
+    url: Url,
+    headers: Vec<(String, String)>,
+    event_types: Vec<String>,
+    reconnect: Reconnect,
+    decoder: JsonMessageDecoder,
+}
+
+impl SseSource {
+    pub fn try_new(url: &str, decoder: JsonMessageDecoder) -> Result<Self> {
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(err) => return plan_err!("Invalid URL {url}: {err}"),
+        };
+        Ok(Self {
+            url,
+            headers: vec![],
+            event_types: vec![],
+            reconnect: Reconnect::default(),
+            decoder,
+        })
+    }
+
+    /// Send `name: value` with every request, e.g. an `Authorization` header
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Read only events of `event_type`, events without a type are of type `message`
+    pub fn with_event_type(mut self, event_type: &str) -> Self {
+        self.event_types.push(event_type.to_string());
+        self
+    }
+
+    pub fn with_reconnect(mut self, reconnect: Reconnect) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+}
+
+#[async_trait]
+impl TableProvider for SseSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = PushPartition {
+            feed: Arc::new(SseFeed {
+                client: reqwest::Client::new(),
+                url: self.url.clone(),
+                headers: self.headers.clone(),
+                event_types: self.event_types.clone(),
+                last_event_id: Mutex::new(None),
+            }),
+            reconnect: self.reconnect,
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct SseFeed {
+    client: reqwest::Client,
+    url: Url,
+    headers: Vec<(String, String)>,
+    event_types: Vec<String>,
+    last_event_id: Mutex<Option<String>>,
+}
+
+#[async_trait]
+impl Feed for SseFeed {
+    fn address(&self) -> &str {
+        self.url.as_str()
+    }
+
+    async fn read(
+        &self,
+        sender: &mpsc::Sender<Result<String>>,
+        received: &mut usize,
+    ) -> Result<()> {
+        let mut request = self
+            .client
+            .get(self.url.clone())
+            .header(ACCEPT, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache");
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let last_event_id = self.last_event_id.lock().unwrap().clone();
+        if let Some(id) = last_event_id {
+            request = request.header(LAST_EVENT_ID, id);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|err| DataFusionError::External(Box::new(err)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return exec_err!("{} was answered with {status}", self.url);
+        }
+        info!("Connected to {}", self.url);
+
+        let mut parser = EventParser::default();
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|err| DataFusionError::External(Box::new(err)))?;
+            for event in parser.push(&chunk) {
+                if let Some(id) = event.id {
+                    *self.last_event_id.lock().unwrap() = Some(id);
+                }
+                if !self.event_types.is_empty() && !self.event_types.contains(&event.event_type) {
+                    continue;
+                }
+                if sender.send(Ok(event.data)).await.is_err() {
+                    return Ok(());
+                }
+                *received += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Event {
+    event_type: String,
+    data: String,
+    /// The id the event set, which stays the last event id until another event sets one
+    id: Option<String>,
+}
+
+/// Splits an event stream into events as its bytes arrive, see the
+/// [spec](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation)
+#[derive(Default)]
+struct EventParser {
+    /// The incomplete line at the end of what arrived so far
+    line: Vec<u8>,
+    event_type: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl EventParser {
+    /// The events completed by `bytes`
+    fn push(&mut self, bytes: &[u8]) -> Vec<Event> {
+        let mut events = vec![];
+        for &byte in bytes {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            let mut line = std::mem::take(&mut self.line);
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            events.extend(self.process_line(&String::from_utf8_lossy(&line)));
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            let event_type = self.event_type.take();
+            // An id without data is sent along with the next event
+            if self.data.is_empty() {
+                return None;
+            }
+            let id = self.id.take();
+            return Some(Event {
+                event_type: event_type.unwrap_or_else(|| "message".to_string()),
+                data: std::mem::take(&mut self.data).join("\n"),
+                id,
+            });
+        }
+        let (field, value) = match line.split_once(':') {
+            // A comment
+            Some(("", _)) => return None,
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event_type = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_event_stream() {
+        let mut parser = EventParser::default();
+        assert!(parser
+            .push(b": keep-alive\n\nevent: quote\nid: 7\nda")
+            .is_empty());
+        let events = parser.push(b"ta: {\"bid\": 1.5,\r\ndata: \"ask\": 1.6}\n\ndata: [1]\n\n");
+        assert_eq!(
+            events,
+            [
+                Event {
+                    event_type: "quote".to_string(),
+                    data: "{\"bid\": 1.5,\n\"ask\": 1.6}".to_string(),
+                    id: Some("7".to_string()),
+                },
+                Event {
+                    event_type: "message".to_string(),
+                    data: "[1]".to_string(),
+                    id: None,
+                }
+            ]
+        );
+    }
+}
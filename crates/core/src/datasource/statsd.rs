@@ -0,0 +1,336 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, MapBuilder, StringBuilder, TimestampMillisecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::catalog::Session;
+use datafusion::common::Result;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::warn;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+
+use crate::datasource::message::{next_chunk, now_ms, MAX_BATCH_MESSAGES};
+use crate::datasource::socket::{receive_datagrams, Lines};
+use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+use crate::utils::arrow_helpers::string_map_type;
+
+/// Listens for StatsD metrics on a UDP socket, as rows of `metric`, `type`, `value`,
+/// `set_member`, `sample_rate`, `delta`, `tags` and `timestamp`, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// Every line of a datagram is one metric, `name:value|type`, optionally followed by
+/// `|@sample_rate` and DogStatsD `|#tag:value,...`. `type` is one of `counter`, `gauge`,
+/// `timer`, `histogram`, `distribution` and `set`. Counters aren't scaled by their sample rate,
+/// so that queries can weigh them with `value / sample_rate`. Gauges sent as `+n` or `-n` are
+/// adjustments of the previous value and have `delta` set. Sets carry their member in
+/// `set_member` and have no `value`.
+///
+/// Graphite plaintext lines, `path value timestamp` with optional `;tag=value` tags on the
+/// path, are accepted on the same socket and read as gauges. Only they have a `timestamp`,
+/// the event time of the other rows is the time they arrived. Lines that are neither are
+/// dropped with a warning, like StatsD servers do.
+pub struct StatsdSource {
+    address: String,
+    schema: SchemaRef,
+}
+
+impl StatsdSource {
+    pub fn new(address: &str) -> Self {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("metric", DataType::Utf8, false),
+            Field::new("type", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, true),
+            Field::new("set_member", DataType::Utf8, true),
+            Field::new("sample_rate", DataType::Float64, false),
+            Field::new("delta", DataType::Boolean, false),
+            Field::new("tags", string_map_type(), false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            stream_metadata_field(),
+        ]));
+        Self {
+            address: address.to_string(),
+            schema,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for StatsdSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = StatsdPartition {
+            address: self.address.clone(),
+            schema: self.schema.clone(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.schema.clone(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct StatsdPartition {
+    address: String,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for StatsdPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let address = self.address.clone();
+        let schema = self.schema.clone();
+
+        builder.spawn(async move {
+            let (sender, receiver) = mpsc::channel(MAX_BATCH_MESSAGES);
+            let socket = UdpSocket::bind(&address).await?;
+            let mut datagrams = Lines::new(receiver, receive_datagrams(socket, sender));
+            while let Some(chunk) = next_chunk(&mut datagrams).await {
+                let datagrams = chunk.into_iter().collect::<Result<Vec<_>>>()?;
+                let metrics: Vec<_> = datagrams
+                    .iter()
+                    .flat_map(|datagram| datagram.lines())
+                    .filter(|line| !line.trim().is_empty())
+                    .filter_map(|line| {
+                        let metric = parse(line.trim());
+                        if metric.is_none() {
+                            warn!("Dropped malformed metric {line:?}");
+                        }
+                        metric
+                    })
+                    .collect();
+                if metrics.is_empty() {
+                    continue;
+                }
+                if tx
+                    .send(metrics_batch(&schema, &metrics, now_ms()))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Metric<'a> {
+    name: &'a str,
+    kind: &'static str,
+    value: Option<f64>,
+    set_member: Option<&'a str>,
+    sample_rate: f64,
+    delta: bool,
+    tags: Vec<(&'a str, Option<&'a str>)>,
+    /// Milliseconds since the epoch, given by Graphite lines only
+    timestamp: Option<i64>,
+}
+
+fn parse(line: &str) -> Option<Metric<'_>> {
+    if line.contains('|') {
+        parse_statsd(line)
+    } else {
+        parse_graphite(line)
+    }
+}
+
+fn parse_statsd(line: &str) -> Option<Metric<'_>> {
+    let (name, rest) = line.split_once(':')?;
+    let mut parts = rest.split('|');
+    let raw_value = parts.next()?;
+    let kind = match parts.next()? {
+        "c" => "counter",
+        "g" => "gauge",
+        "ms" => "timer",
+        "h" => "histogram",
+        "d" => "distribution",
+        "s" => "set",
+        _ => return None,
+    };
+
+    let mut sample_rate = 1.0;
+    let mut tags = vec![];
+    for part in parts {
+        if let Some(rate) = part.strip_prefix('@') {
+            sample_rate = rate
+                .parse::<f64>()
+                .ok()
+                .filter(|rate| *rate > 0.0 && *rate <= 1.0)?;
+        } else if let Some(tag_list) = part.strip_prefix('#') {
+            tags = tag_list
+                .split(',')
+                .filter(|tag| !tag.is_empty())
+                .map(|tag| match tag.split_once(':') {
+                    Some((key, value)) => (key, Some(value)),
+                    None => (tag, None),
+                })
+                .collect();
+        }
+    }
+
+    let (value, set_member) = if kind == "set" {
+        (None, Some(raw_value))
+    } else {
+        (Some(raw_value.parse().ok()?), None)
+    };
+    Some(Metric {
+        name: (!name.is_empty()).then_some(name)?,
+        kind,
+        value,
+        set_member,
+        sample_rate,
+        delta: kind == "gauge" && raw_value.starts_with(['+', '-']),
+        tags,
+        timestamp: None,
+    })
+}
+
+/// `path value timestamp`, a timestamp of -1 meaning the time of arrival
+fn parse_graphite(line: &str) -> Option<Metric<'_>> {
+    let mut fields = line.split_whitespace();
+    let (path, value, timestamp) = (fields.next()?, fields.next()?, fields.next()?);
+    if fields.next().is_some() {
+        return None;
+    }
+    let timestamp = match timestamp.parse::<f64>().ok()? {
+        seconds if seconds < 0.0 => None,
+        seconds => Some((seconds * 1000.0) as i64),
+    };
+
+    let mut segments = path.split(';');
+    let name = segments.next().filter(|name| !name.is_empty())?;
+    let tags = segments
+        .map(|tag| tag.split_once('=').map(|(key, value)| (key, Some(value))))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Metric {
+        name,
+        kind: "gauge",
+        value: Some(value.parse().ok()?),
+        set_member: None,
+        sample_rate: 1.0,
+        delta: false,
+        tags,
+        timestamp,
+    })
+}
+
+fn metrics_batch(schema: &SchemaRef, metrics: &[Metric], arrival_ms: i64) -> Result<RecordBatch> {
+    let mut names = StringBuilder::new();
+    let mut kinds = StringBuilder::new();
+    let mut values = Float64Builder::new();
+    let mut set_members = StringBuilder::new();
+    let mut sample_rates = Float64Builder::new();
+    let mut deltas = BooleanBuilder::new();
+    let mut tags = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+    let mut timestamps = TimestampMillisecondBuilder::new();
+    let mut event_times = Vec::with_capacity(metrics.len());
+    for metric in metrics {
+        names.append_value(metric.name);
+        kinds.append_value(metric.kind);
+        values.append_option(metric.value);
+        set_members.append_option(metric.set_member);
+        sample_rates.append_value(metric.sample_rate);
+        deltas.append_value(metric.delta);
+        for (key, value) in &metric.tags {
+            tags.keys().append_value(key);
+            tags.values().append_option(*value);
+        }
+        tags.append(true)?;
+        timestamps.append_option(metric.timestamp);
+        event_times.push(metric.timestamp.unwrap_or(arrival_ms));
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(names.finish()),
+        Arc::new(kinds.finish()),
+        Arc::new(values.finish()),
+        Arc::new(set_members.finish()),
+        Arc::new(sample_rates.finish()),
+        Arc::new(deltas.finish()),
+        Arc::new(tags.finish()),
+        Arc::new(timestamps.finish()),
+        Arc::new(stream_metadata_array(TimestampMillisecondArray::from(
+            event_times,
+        ))),
+    ];
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_metric_lines() {
+        let counter = parse("api.requests:3|c|@0.1|#route:/users,canary").unwrap();
+        assert_eq!(counter.kind, "counter");
+        assert_eq!(counter.value, Some(3.0));
+        assert_eq!(counter.sample_rate, 0.1);
+        assert_eq!(
+            counter.tags,
+            vec![("route", Some("/users")), ("canary", None)]
+        );
+
+        let gauge = parse("queue.depth:-4|g").unwrap();
+        assert!(gauge.delta);
+        assert_eq!(gauge.value, Some(-4.0));
+
+        let set = parse("users.unique:alice|s").unwrap();
+        assert_eq!((set.value, set.set_member), (None, Some("alice")));
+
+        let graphite = parse("cpu.load;host=web-1 0.75 1700000000").unwrap();
+        assert_eq!(graphite.timestamp, Some(1_700_000_000_000));
+        assert_eq!(graphite.tags, vec![("host", Some("web-1"))]);
+
+        assert_eq!(parse("api.requests:3|x"), None);
+        assert_eq!(parse("api.requests:three|c"), None);
+        assert_eq!(parse("cpu.load 0.75"), None);
+
+        let source = StatsdSource::new("127.0.0.1:8125");
+        let batch = metrics_batch(&source.schema, &[counter, set, graphite], 42).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.column(2).null_count(), 1);
+    }
+}
@@ -0,0 +1,501 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::io;
+#[cfg(feature = "syslog-tls")]
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow_array::builder::{StringBuilder, TimestampMillisecondBuilder};
+use arrow_array::{ArrayRef, RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Utc};
+use datafusion::catalog::Session;
+use datafusion::common::Result;
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+use log::warn;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::datasource::message::{next_chunk, now_ms, MAX_BATCH_MESSAGES};
+use crate::datasource::socket::{receive_datagrams, serve, Lines};
+use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+/// Largest frame accepted over TCP, the same as the maximum size of a UDP datagram
+const MAX_FRAME_BYTES: usize = 65_535;
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+const SEVERITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// How a syslog source receives messages
+#[derive(Debug, Clone)]
+pub enum SyslogTransport {
+    /// One message per datagram
+    Udp(String),
+    /// Messages framed by octet counting or by newlines, as described in RFC 6587
+    Tcp(String),
+    /// Framed like [`SyslogTransport::Tcp`], with the server certificate chain and private key
+    /// read from PEM files
+    #[cfg(feature = "syslog-tls")]
+    Tls {
+        address: String,
+        cert_chain: PathBuf,
+        private_key: PathBuf,
+    },
+}
+
+/// Listens for syslog messages and parses RFC 5424 and RFC 3164 frames into the columns
+/// `timestamp`, `facility`, `severity`, `host`, `app`, `proc_id`, `msg_id`, `structured_data`
+/// and `message`, registered with
+/// [`Context::from_source`](crate::context::Context::from_source).
+///
+/// Facility and severity are given by their keywords, e.g. `local0` and `warning`. Parts a
+/// frame leaves out are null, a frame that isn't syslog at all is kept as `message` only.
+/// Rows take their event time from the message timestamp, or from the time they arrived for
+/// messages without one. RFC 3164 timestamps have no year and time zone and are taken as UTC
+/// in the past twelve months.
+pub struct SyslogSource {
+    transport: SyslogTransport,
+    schema: SchemaRef,
+}
+
+impl SyslogSource {
+    pub fn new(transport: SyslogTransport) -> Self {
+        let optional = |name| Field::new(name, DataType::Utf8, true);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            optional("facility"),
+            optional("severity"),
+            optional("host"),
+            optional("app"),
+            optional("proc_id"),
+            optional("msg_id"),
+            optional("structured_data"),
+            Field::new("message", DataType::Utf8, false),
+            stream_metadata_field(),
+        ]));
+        Self { transport, schema }
+    }
+}
+
+#[async_trait]
+impl TableProvider for SyslogSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = SyslogPartition {
+            transport: self.transport.clone(),
+            schema: self.schema.clone(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.schema.clone(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct SyslogPartition {
+    transport: SyslogTransport,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for SyslogPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let transport = self.transport.clone();
+        let schema = self.schema.clone();
+
+        builder.spawn(async move {
+            let mut frames = listen(&transport).await?;
+            while let Some(chunk) = next_chunk(&mut frames).await {
+                let frames = chunk.into_iter().collect::<Result<Vec<_>>>()?;
+                let now = Utc::now();
+                let messages = frames.iter().map(|frame| parse(frame, now)).collect();
+                if tx.send(messages_batch(&schema, messages)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}
+
+async fn listen(transport: &SyslogTransport) -> Result<Lines> {
+    let (sender, receiver) = mpsc::channel(MAX_BATCH_MESSAGES);
+    let acceptor = match transport {
+        SyslogTransport::Udp(address) => receive_datagrams(UdpSocket::bind(address).await?, sender),
+        SyslogTransport::Tcp(address) => {
+            serve(TcpListener::bind(address).await?, sender, read_frames)
+        }
+        #[cfg(feature = "syslog-tls")]
+        SyslogTransport::Tls {
+            address,
+            cert_chain,
+            private_key,
+        } => {
+            let acceptor = tls_acceptor(cert_chain, private_key)?;
+            serve(
+                TcpListener::bind(address).await?,
+                sender,
+                move |stream, sender| {
+                    let acceptor = acceptor.clone();
+                    async move {
+                        match acceptor.accept(stream).await {
+                            Ok(stream) => read_frames(stream, sender).await,
+                            Err(err) => warn!("TLS handshake with syslog client failed: {err}"),
+                        }
+                    }
+                },
+            )
+        }
+    };
+    Ok(Lines::new(receiver, acceptor))
+}
+
+#[cfg(feature = "syslog-tls")]
+fn tls_acceptor(
+    cert_chain: &std::path::Path,
+    private_key: &std::path::Path,
+) -> Result<tokio_rustls::TlsAcceptor> {
+    use datafusion::common::DataFusionError;
+    use std::fs::File;
+
+    let certs = rustls_pemfile::certs(&mut io::BufReader::new(File::open(cert_chain)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut io::BufReader::new(File::open(private_key)?))?
+        .ok_or_else(|| {
+            DataFusionError::Plan(format!("No private key found in {}", private_key.display()))
+        })?;
+    let config = tokio_rustls::rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+}
+
+async fn read_frames(stream: impl AsyncRead + Unpin, sender: mpsc::Sender<Result<String>>) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        match read_frame(&mut reader).await {
+            Ok(Some(frame)) if frame.trim().is_empty() => {}
+            Ok(Some(frame)) => {
+                if sender.send(Ok(frame)).await.is_err() {
+                    return;
+                }
+            }
+            Ok(None) => return,
+            Err(err) => {
+                warn!("Dropped syslog connection: {err}");
+                return;
+            }
+        }
+    }
+}
+
+/// Frames start with their length when sent with octet counting, otherwise they end with a
+/// newline
+async fn read_frame(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<String>> {
+    let first = match reader.fill_buf().await? {
+        [] => return Ok(None),
+        buf => buf[0],
+    };
+    let mut frame = vec![];
+    if first.is_ascii_digit() {
+        let mut length = vec![];
+        reader.read_until(b' ', &mut length).await?;
+        let length = std::str::from_utf8(&length)
+            .ok()
+            .and_then(|length| length.trim_end().parse::<usize>().ok())
+            .filter(|&length| length <= MAX_FRAME_BYTES)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid frame length"))?;
+        frame.resize(length, 0);
+        reader.read_exact(&mut frame).await?;
+    } else {
+        reader.read_until(b'\n', &mut frame).await?;
+    }
+    Ok(Some(String::from_utf8_lossy(&frame).into_owned()))
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct SyslogMessage {
+    timestamp_ms: Option<i64>,
+    facility: Option<&'static str>,
+    severity: Option<&'static str>,
+    host: Option<String>,
+    app: Option<String>,
+    proc_id: Option<String>,
+    msg_id: Option<String>,
+    structured_data: Option<String>,
+    message: String,
+}
+
+fn parse(frame: &str, now: DateTime<Utc>) -> SyslogMessage {
+    let frame = frame.trim_end_matches(['\r', '\n']);
+    let Some((priority, rest)) = parse_priority(frame) else {
+        return SyslogMessage {
+            message: frame.to_string(),
+            ..Default::default()
+        };
+    };
+    let mut message = match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(rest),
+        None => parse_rfc3164(rest, now),
+    };
+    message.facility = FACILITIES.get(priority as usize / 8).copied();
+    message.severity = Some(SEVERITIES[priority as usize % 8]);
+    message
+}
+
+fn parse_priority(frame: &str) -> Option<(u8, &str)> {
+    let rest = frame.strip_prefix('<')?;
+    let end = rest.find('>').filter(|&end| (1..=3).contains(&end))?;
+    let priority = rest[..end].parse::<u8>().ok().filter(|&p| p < 192)?;
+    Some((priority, &rest[end + 1..]))
+}
+
+/// `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]`, `-` standing for a
+/// missing part
+fn parse_rfc5424(rest: &str) -> SyslogMessage {
+    let mut parts = rest.splitn(6, ' ');
+    let mut next = || parts.next().filter(|part| *part != "-").map(str::to_string);
+    let timestamp = next();
+    let (host, app, proc_id, msg_id) = (next(), next(), next(), next());
+    let (structured_data, message) = split_structured_data(parts.next().unwrap_or_default());
+    SyslogMessage {
+        timestamp_ms: timestamp
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(&timestamp).ok())
+            .map(|timestamp| timestamp.timestamp_millis()),
+        host,
+        app,
+        proc_id,
+        msg_id,
+        structured_data,
+        message: message.trim_start_matches('\u{feff}').to_string(),
+        ..Default::default()
+    }
+}
+
+/// The structured data elements at the start of `rest` and the message following them.
+/// Elements are bracketed, within their quoted parameter values `]` is escaped.
+fn split_structured_data(rest: &str) -> (Option<String>, &str) {
+    if let Some(message) = rest.strip_prefix('-') {
+        return (None, message.strip_prefix(' ').unwrap_or(message));
+    }
+    let bytes = rest.as_bytes();
+    let mut end = 0;
+    while bytes.get(end) == Some(&b'[') {
+        let mut quoted = false;
+        end += 1;
+        while end < bytes.len() {
+            match bytes[end] {
+                b'\\' if quoted => end += 1,
+                b'"' => quoted = !quoted,
+                b']' if !quoted => break,
+                _ => {}
+            }
+            end += 1;
+        }
+        end = (end + 1).min(bytes.len());
+    }
+    if end == 0 {
+        return (None, rest);
+    }
+    let message = &rest[end..];
+    (
+        Some(rest[..end].to_string()),
+        message.strip_prefix(' ').unwrap_or(message),
+    )
+}
+
+/// `Mmm dd hh:mm:ss HOSTNAME TAG: MSG`, where the tag is the app name optionally followed by
+/// the process ID in brackets. Many senders leave out the timestamp or the host name.
+fn parse_rfc3164(rest: &str, now: DateTime<Utc>) -> SyslogMessage {
+    let (timestamp_ms, rest) = match rest
+        .get(..15)
+        .and_then(|ts| parse_rfc3164_timestamp(ts, now))
+    {
+        Some(timestamp_ms) => (Some(timestamp_ms), rest[15..].trim_start()),
+        None => (None, rest),
+    };
+    let is_tag = |word: &str| word.ends_with(':') || word.contains('[');
+    let (host, rest) = match rest.split_once(' ') {
+        Some((word, remainder)) if !is_tag(word) => (Some(word.to_string()), remainder),
+        _ => (None, rest),
+    };
+    let (tag, message) = match rest.split_once(' ') {
+        Some((word, message)) if word.ends_with(':') => (Some(word.trim_end_matches(':')), message),
+        _ => (None, rest),
+    };
+    let (app, proc_id) = match tag.map(|tag| tag.split_once('[')) {
+        Some(Some((app, proc_id))) => (Some(app), Some(proc_id.trim_end_matches(']'))),
+        Some(None) => (tag, None),
+        None => (None, None),
+    };
+    SyslogMessage {
+        timestamp_ms,
+        host,
+        app: app.map(str::to_string),
+        proc_id: proc_id.map(str::to_string),
+        message: message.to_string(),
+        ..Default::default()
+    }
+}
+
+fn parse_rfc3164_timestamp(timestamp: &str, now: DateTime<Utc>) -> Option<i64> {
+    let parse = |year: i32| {
+        NaiveDateTime::parse_from_str(&format!("{year} {timestamp}"), "%Y %b %e %H:%M:%S")
+            .ok()
+            .map(|timestamp| timestamp.and_utc())
+    };
+    // A timestamp ahead of now by more than clock skew was sent last year
+    let timestamp = parse(now.year())?;
+    let timestamp = if timestamp > now + Duration::days(1) {
+        parse(now.year() - 1)?
+    } else {
+        timestamp
+    };
+    Some(timestamp.timestamp_millis())
+}
+
+fn messages_batch(schema: &SchemaRef, messages: Vec<SyslogMessage>) -> Result<RecordBatch> {
+    let arrival_ms = now_ms();
+    let mut timestamps = TimestampMillisecondBuilder::with_capacity(messages.len());
+    let mut columns: [StringBuilder; 8] = Default::default();
+    for message in &messages {
+        timestamps.append_option(message.timestamp_ms);
+        columns[0].append_option(message.facility);
+        columns[1].append_option(message.severity);
+        columns[2].append_option(message.host.as_deref());
+        columns[3].append_option(message.app.as_deref());
+        columns[4].append_option(message.proc_id.as_deref());
+        columns[5].append_option(message.msg_id.as_deref());
+        columns[6].append_option(message.structured_data.as_deref());
+        columns[7].append_value(&message.message);
+    }
+    let event_times = TimestampMillisecondArray::from_iter_values(
+        messages
+            .iter()
+            .map(|message| message.timestamp_ms.unwrap_or(arrival_ms)),
+    );
+
+    let mut arrays: Vec<ArrayRef> = vec![Arc::new(timestamps.finish())];
+    arrays.extend(
+        columns
+            .iter_mut()
+            .map(|column| Arc::new(column.finish()) as _),
+    );
+    arrays.push(Arc::new(stream_metadata_array(event_times)));
+    Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_syslog_frames() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 5, 12, 0, 0).unwrap();
+
+        let message = parse(
+            "<165>1 2024-01-05T10:20:30.123Z web-1 nginx 4123 ACCESS [meta seq=\"7\" note=\"a\\]b\"] \u{feff}GET /",
+            now,
+        );
+        assert_eq!(
+            message,
+            SyslogMessage {
+                timestamp_ms: Some(1_704_450_030_123),
+                facility: Some("local4"),
+                severity: Some("notice"),
+                host: Some("web-1".to_string()),
+                app: Some("nginx".to_string()),
+                proc_id: Some("4123".to_string()),
+                msg_id: Some("ACCESS".to_string()),
+                structured_data: Some("[meta seq=\"7\" note=\"a\\]b\"]".to_string()),
+                message: "GET /".to_string(),
+            }
+        );
+
+        // Sent before the turn of the year
+        let message = parse("<34>Dec 31 23:59:59 mail-2 su[88]: auth failure\n", now);
+        assert_eq!(
+            message,
+            SyslogMessage {
+                timestamp_ms: Some(1_704_067_199_000),
+                facility: Some("auth"),
+                severity: Some("crit"),
+                host: Some("mail-2".to_string()),
+                app: Some("su".to_string()),
+                proc_id: Some("88".to_string()),
+                message: "auth failure".to_string(),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(parse("plain text", now).message, "plain text");
+        assert_eq!(parse("plain text", now).severity, None);
+    }
+}
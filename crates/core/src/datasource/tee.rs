@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+use futures::StreamExt;
+use log::warn;
+use tokio::sync::mpsc;
+
+use crate::utils::quota::ResourceQuotas;
+
+/// What a [`TeeSink`] does when one of its sinks fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Fail the pipeline
+    Fail,
+    /// Log the error and stop writing to the sink, the other sinks carry on
+    Detach,
+}
+
+/// Writes the same rows to several sinks, e.g. Kafka for consumers and Parquet for an audit
+/// trail, while the query producing them runs once.
+///
+/// Every sink consumes its own copy of the stream concurrently, a batch is handed to the next
+/// sink once the previous one took it, so the slowest sink sets the pace. What a failing sink
+/// does to the others is up to its [`FailurePolicy`].
+#[derive(Default)]
+pub struct TeeSink {
+    sinks: Vec<(Arc<dyn DataSink>, FailurePolicy)>,
+}
+
+impl TeeSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sink(mut self, sink: Arc<dyn DataSink>, policy: FailurePolicy) -> Self {
+        self.sinks.push((sink, policy));
+        self
+    }
+}
+
+/// A sink writing whatever is sent to it in a task of its own, one per sink of a tee or route
+/// of a [`RouterSink`](crate::datasource::router::RouterSink)
+pub(crate) struct Branch {
+    sink: Arc<dyn DataSink>,
+    policy: FailurePolicy,
+    sender: Option<mpsc::Sender<RecordBatch>>,
+    write: Option<SpawnedTask<Result<u64>>>,
+}
+
+impl Branch {
+    pub(crate) fn start(
+        sink: &Arc<dyn DataSink>,
+        policy: FailurePolicy,
+        schema: SchemaRef,
+        context: &Arc<TaskContext>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(1);
+        let batches = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|batch| (Ok(batch), receiver))
+        });
+        let stream = Box::pin(RecordBatchStreamAdapter::new(schema, batches));
+        let (branch_sink, context) = (sink.clone(), context.clone());
+        let write =
+            SpawnedTask::spawn(async move { branch_sink.write_all(stream, &context).await });
+        Self {
+            sink: sink.clone(),
+            policy,
+            sender: Some(sender),
+            write: Some(write),
+        }
+    }
+
+    /// `false` once the sink stopped taking batches
+    pub(crate) async fn send(&mut self, batch: &RecordBatch) -> Result<bool> {
+        let Some(sender) = &self.sender else {
+            return Ok(false);
+        };
+        if sender.send(batch.clone()).await.is_ok() {
+            return Ok(true);
+        }
+        // The sink returned early, its result tells why
+        self.finish().await?;
+        Ok(false)
+    }
+
+    /// Close the sink's stream and wait for it to finish writing
+    pub(crate) async fn finish(&mut self) -> Result<()> {
+        self.sender = None;
+        let Some(write) = self.write.take() else {
+            return Ok(());
+        };
+        let result = write
+            .join()
+            .await
+            .map_err(|err| DataFusionError::External(Box::new(err)))
+            .and_then(|result| result);
+        match (result, self.policy) {
+            (Ok(_), _) => Ok(()),
+            (Err(err), FailurePolicy::Fail) => Err(err),
+            (Err(err), FailurePolicy::Detach) => {
+                warn!("Detaching failed sink {:?} from the tee: {err}", self.sink);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataSink for TeeSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let quotas = ResourceQuotas::from_task_context(context);
+        let mut branches = self
+            .sinks
+            .iter()
+            .map(|(sink, policy)| Branch::start(sink, *policy, data.schema(), context))
+            .collect::<Vec<_>>();
+
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            for branch in &mut branches {
+                branch.send(&batch).await?;
+            }
+            row_count += batch.num_rows() as u64;
+        }
+        for branch in &mut branches {
+            branch.finish().await?;
+        }
+        Ok(row_count)
+    }
+}
+
+impl Debug for TeeSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TeeSink")
+            .field("sinks", &self.sinks)
+            .finish()
+    }
+}
+
+impl DisplayAs for TeeSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "TeeSink (")?;
+                for (i, (sink, _)) in self.sinks.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    sink.fmt_as(t, f)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::common::exec_err;
+
+    #[derive(Debug, Default)]
+    struct TestSink {
+        failing: bool,
+        rows: Mutex<usize>,
+    }
+
+    impl DisplayAs for TestSink {
+        fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "TestSink")
+        }
+    }
+
+    #[async_trait]
+    impl DataSink for TestSink {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn metrics(&self) -> Option<MetricsSet> {
+            None
+        }
+
+        async fn write_all(
+            &self,
+            mut data: SendableRecordBatchStream,
+            _context: &Arc<TaskContext>,
+        ) -> Result<u64> {
+            while let Some(batch) = data.next().await.transpose()? {
+                if self.failing {
+                    return exec_err!("broken");
+                }
+                *self.rows.lock().unwrap() += batch.num_rows();
+            }
+            Ok(0)
+        }
+    }
+
+    async fn write(tee: &TeeSink) -> Result<u64> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batches = (0..3)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from(vec![i, i + 10]))],
+                )
+                .map_err(Into::into)
+            })
+            .collect::<Vec<Result<_>>>();
+        let stream = RecordBatchStreamAdapter::new(schema, futures::stream::iter(batches));
+        tee.write_all(Box::pin(stream), &Arc::new(TaskContext::default()))
+            .await
+    }
+
+    #[tokio::test]
+    async fn apply_failure_policies() -> Result<()> {
+        let healthy = Arc::new(TestSink::default());
+        let broken = Arc::new(TestSink {
+            failing: true,
+            ..Default::default()
+        });
+
+        let tee = TeeSink::new()
+            .with_sink(healthy.clone(), FailurePolicy::Fail)
+            .with_sink(broken.clone(), FailurePolicy::Detach);
+        assert_eq!(write(&tee).await?, 6);
+        assert_eq!(*healthy.rows.lock().unwrap(), 6);
+
+        let tee = TeeSink::new()
+            .with_sink(healthy.clone(), FailurePolicy::Detach)
+            .with_sink(broken, FailurePolicy::Fail);
+        assert!(write(&tee).await.is_err());
+        Ok(())
+    }
+}
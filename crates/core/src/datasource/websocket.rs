@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow_schema::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::datasource::TableProvider;
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::streaming::StreamingTableExec;
+use datafusion::physical_plan::ExecutionPlan;
+use futures::{SinkExt, StreamExt};
+use log::info;
+use tokio::sync::mpsc;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::datasource::message::JsonMessageDecoder;
+use crate::datasource::push::{Feed, PushPartition, Reconnect};
+
+/// Reads JSON frames from a WebSocket feed, such as the market data feeds of exchanges,
+/// registered with [`Context::from_source`](crate::context::Context::from_source).
+///
+/// A frame holds a record or an array of them, a frame that isn't JSON fails the source.
+/// Binary frames are read as UTF-8. The subscribe messages are sent every time the source
+/// connects, which it does again as the [`Reconnect`] policy says whenever the connection
+/// fails or the server closes it. Records the server pushed while the source was
+/// disconnected are missed.
+pub struct WebSocketSource {
+    url: String,
+    headers: Vec<(String, String)>,
+    subscribe: Vec<String>,
+    reconnect: Reconnect,
+    decoder: JsonMessageDecoder,
+}
+
+impl WebSocketSource {
+    /// Connect to a `ws://` or `wss://` URL
+    pub fn try_new(url: &str, decoder: JsonMessageDecoder) -> Result<Self> {
+        if !url.starts_with("ws://") && !url.starts_with("wss://") {
+            return plan_err!("Invalid WebSocket URL {url}");
+        }
+        Ok(Self {
+            url: url.to_string(),
+            headers: vec![],
+            subscribe: vec![],
+            reconnect: Reconnect::default(),
+            decoder,
+        })
+    }
+
+    /// Send `name: value` with the handshake, e.g. an `Authorization` header
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Send `message` as a text frame after connecting, e.g.
+    /// `{"op": "subscribe", "args": ["trades.BTCUSD"]}`
+    pub fn with_subscribe_message(mut self, message: &str) -> Self {
+        self.subscribe.push(message.to_string());
+        self
+    }
+
+    pub fn with_reconnect(mut self, reconnect: Reconnect) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+}
+
+#[async_trait]
+impl TableProvider for WebSocketSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = PushPartition {
+            feed: Arc::new(WebSocketFeed {
+                url: self.url.clone(),
+                headers: self.headers.clone(),
+                subscribe: self.subscribe.clone(),
+            }),
+            reconnect: self.reconnect,
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct WebSocketFeed {
+    url: String,
+    headers: Vec<(String, String)>,
+    subscribe: Vec<String>,
+}
+
+fn external(err: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+#[async_trait]
+impl Feed for WebSocketFeed {
+    fn address(&self) -> &str {
+        &self.url
+    }
+
+    async fn read(
+        &self,
+        sender: &mpsc::Sender<Result<String>>,
+        received: &mut usize,
+    ) -> Result<()> {
+        let mut request = self.url.as_str().into_client_request().map_err(external)?;
+        for (name, value) in &self.headers {
+            request.headers_mut().insert(
+                HeaderName::try_from(name.as_str()).map_err(external)?,
+                HeaderValue::from_str(value).map_err(external)?,
+            );
+        }
+        let (mut socket, _) = connect_async(request).await.map_err(external)?;
+        info!("Connected to {}", self.url);
+        for message in &self.subscribe {
+            socket
+                .send(Message::Text(message.clone()))
+                .await
+                .map_err(external)?;
+        }
+
+        while let Some(frame) = socket.next().await {
+            let text = match frame.map_err(external)? {
+                Message::Text(text) => text,
+                Message::Binary(bytes) => String::from_utf8(bytes).map_err(external)?,
+                Message::Close(_) => break,
+                // Pings are answered by the socket itself
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            if sender.send(Ok(text)).await.is_err() {
+                break;
+            }
+            *received += 1;
+        }
+        Ok(())
+    }
+}
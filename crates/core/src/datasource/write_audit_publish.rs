@@ -0,0 +1,362 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow::ipc::writer::StreamWriter;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::common::{exec_err, Result};
+use datafusion::datasource::MemTable;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::insert::DataSink;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+use datafusion::prelude::SessionContext;
+use futures::StreamExt;
+use log::{debug, warn};
+
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::segment_queue::read_segment;
+use crate::utils::quota::ResourceQuotas;
+
+/// Table the audit query of a [`WriteAuditPublishSink`] reads a stage from
+pub const STAGED_TABLE: &str = "staged";
+
+const OPEN_EXTENSION: &str = "open";
+const STAGED_EXTENSION: &str = "staged";
+const REJECTED_EXTENSION: &str = "rejected";
+
+/// What a [`WriteAuditPublishSink`] does with a stage that fails its audit. Either way the
+/// stage is kept in the staging directory with a `.rejected` extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectPolicy {
+    /// Fail the pipeline
+    Fail,
+    /// Log the rejection and carry on with the next stage
+    Quarantine,
+}
+
+/// Publishes rows to the target sink only once they passed an audit, for pipelines whose
+/// output has to meet data quality checks before anyone reads it.
+///
+/// Rows are first written to a stage file in the staging directory. A stage closes when the
+/// checkpoint epoch moves on, when it holds the stage row limit, or when the stream ends. The
+/// audit query then runs against the stage, registered as the table [`STAGED_TABLE`], and
+/// selects the rows that violate the checks, e.g.
+/// `SELECT * FROM staged WHERE amount < 0 OR customer_id IS NULL`. A stage the query returns
+/// no rows for is forwarded to the target in one write and deleted, any other stage is
+/// rejected as the [`RejectPolicy`] says. Stages left in the staging directory by a previous
+/// run are audited and published first. A stage whose publishing failed half way is published
+/// again in full, the target should tolerate duplicates.
+pub struct WriteAuditPublishSink {
+    target: Arc<dyn DataSink>,
+    staging_dir: PathBuf,
+    audit_sql: String,
+    stage_rows: usize,
+    reject_policy: RejectPolicy,
+}
+
+impl WriteAuditPublishSink {
+    pub fn new(
+        target: Arc<dyn DataSink>,
+        staging_dir: impl Into<PathBuf>,
+        audit_sql: &str,
+    ) -> Self {
+        Self {
+            target,
+            staging_dir: staging_dir.into(),
+            audit_sql: audit_sql.to_string(),
+            stage_rows: 100_000,
+            reject_policy: RejectPolicy::Fail,
+        }
+    }
+
+    /// Rows after which a stage closes even if the epoch didn't move on, 100,000 by default
+    pub fn with_stage_rows(mut self, stage_rows: usize) -> Self {
+        self.stage_rows = stage_rows;
+        self
+    }
+
+    pub fn with_reject_policy(mut self, reject_policy: RejectPolicy) -> Self {
+        self.reject_policy = reject_policy;
+        self
+    }
+
+    /// Number of rows the audit query flags in `batches`
+    async fn audit(&self, schema: SchemaRef, batches: Vec<RecordBatch>) -> Result<usize> {
+        let ctx = SessionContext::new();
+        ctx.register_table(
+            STAGED_TABLE,
+            Arc::new(MemTable::try_new(schema, vec![batches])?),
+        )?;
+        let violations = ctx.sql(&self.audit_sql).await?.collect().await?;
+        Ok(violations.iter().map(|batch| batch.num_rows()).sum())
+    }
+
+    /// Audit the stage at `path` and publish it if it passes
+    async fn publish(&self, path: &Path, context: &Arc<TaskContext>) -> Result<()> {
+        let batches = read_segment(path)?;
+        let Some(schema) = batches.first().map(|batch| batch.schema()) else {
+            fs::remove_file(path)?;
+            return Ok(());
+        };
+
+        let violations = self.audit(schema.clone(), batches.clone()).await?;
+        if violations > 0 {
+            let rejected = path.with_extension(REJECTED_EXTENSION);
+            fs::rename(path, &rejected)?;
+            match self.reject_policy {
+                RejectPolicy::Fail => {
+                    return exec_err!(
+                        "{violations} rows failed the audit, the stage is kept at {}",
+                        rejected.display()
+                    );
+                }
+                RejectPolicy::Quarantine => {
+                    warn!(
+                        "{violations} rows failed the audit, quarantined the stage at {}",
+                        rejected.display()
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        debug!("Publishing stage {}", path.display());
+        let stream = futures::stream::iter(batches.into_iter().map(Ok));
+        self.target
+            .write_all(
+                Box::pin(RecordBatchStreamAdapter::new(schema, stream)),
+                context,
+            )
+            .await?;
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+/// The stage a sink is writing to
+struct Stage {
+    path: PathBuf,
+    writer: StreamWriter<File>,
+    rows: usize,
+    epoch: u64,
+}
+
+impl Stage {
+    fn create(dir: &Path, sequence: u64, schema: &SchemaRef, epoch: u64) -> Result<Self> {
+        let path = dir.join(format!("{sequence:020}.{OPEN_EXTENSION}"));
+        Ok(Self {
+            writer: StreamWriter::try_new(File::create(&path)?, schema)?,
+            path,
+            rows: 0,
+            epoch,
+        })
+    }
+
+    /// Finish writing the stage, the path of the staged file
+    fn close(mut self) -> Result<PathBuf> {
+        self.writer.finish()?;
+        self.writer.get_ref().sync_data()?;
+        let staged = self.path.with_extension(STAGED_EXTENSION);
+        fs::rename(&self.path, &staged)?;
+        Ok(staged)
+    }
+}
+
+/// Stage files in `dir` with one of `extensions`, with their sequence numbers, oldest first
+fn list_stages(dir: &Path, extensions: &[&str]) -> Result<Vec<(u64, PathBuf)>> {
+    let mut stages = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        if !extension.is_some_and(|extension| extensions.contains(&extension)) {
+            continue;
+        }
+        let sequence = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok());
+        if let Some(sequence) = sequence {
+            stages.push((sequence, path));
+        }
+    }
+    stages.sort();
+    Ok(stages)
+}
+
+#[async_trait]
+impl DataSink for WriteAuditPublishSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let quotas = ResourceQuotas::from_task_context(context);
+        fs::create_dir_all(&self.staging_dir)?;
+        let mut sequence = list_stages(
+            &self.staging_dir,
+            &[OPEN_EXTENSION, STAGED_EXTENSION, REJECTED_EXTENSION],
+        )?
+        .last()
+        .map_or(0, |(sequence, _)| *sequence);
+        // A stage left open holds what a previous run wrote of it before it stopped
+        for (_, path) in list_stages(&self.staging_dir, &[OPEN_EXTENSION, STAGED_EXTENSION])? {
+            let staged = path.with_extension(STAGED_EXTENSION);
+            fs::rename(&path, &staged)?;
+            self.publish(&staged, context).await?;
+        }
+
+        let epoch_tracker = EpochTracker::from_task_context(context);
+        let schema = data.schema();
+        let mut stage: Option<Stage> = None;
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            if let Some(quotas) = &quotas {
+                quotas.acquire_output(batch.num_rows()).await?;
+            }
+            let epoch = epoch_tracker
+                .as_ref()
+                .map_or(0, |tracker| tracker.current());
+            if stage.as_ref().is_some_and(|open| open.epoch != epoch) {
+                self.publish(&stage.take().unwrap().close()?, context)
+                    .await?;
+            }
+            if stage.is_none() {
+                sequence += 1;
+                stage = Some(Stage::create(&self.staging_dir, sequence, &schema, epoch)?);
+            }
+            let open = stage.as_mut().unwrap();
+            open.writer.write(&batch)?;
+            open.rows += batch.num_rows();
+            if open.rows >= self.stage_rows {
+                self.publish(&stage.take().unwrap().close()?, context)
+                    .await?;
+            }
+            row_count += batch.num_rows() as u64;
+        }
+        if let Some(open) = stage {
+            self.publish(&open.close()?, context).await?;
+        }
+        Ok(row_count)
+    }
+}
+
+impl Debug for WriteAuditPublishSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteAuditPublishSink")
+            .field("target", &self.target)
+            .field("staging_dir", &self.staging_dir)
+            .field("audit_sql", &self.audit_sql)
+            .finish()
+    }
+}
+
+impl DisplayAs for WriteAuditPublishSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "WriteAuditPublishSink (")?;
+                self.target.fmt_as(t, f)?;
+                write!(f, ", audit: {})", self.audit_sql)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+
+    #[derive(Debug, Default)]
+    struct CollectSink(Mutex<Vec<i64>>);
+
+    impl DisplayAs for CollectSink {
+        fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "CollectSink")
+        }
+    }
+
+    #[async_trait]
+    impl DataSink for CollectSink {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn metrics(&self) -> Option<MetricsSet> {
+            None
+        }
+
+        async fn write_all(
+            &self,
+            mut data: SendableRecordBatchStream,
+            _context: &Arc<TaskContext>,
+        ) -> Result<u64> {
+            while let Some(batch) = data.next().await.transpose()? {
+                let amounts = batch.column(0).as_primitive::<Int64Type>();
+                self.0.lock().unwrap().extend(amounts.values().iter());
+            }
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn publish_audited_stages_only() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("wap_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "amount",
+            DataType::Int64,
+            false,
+        )]));
+        let batches = [vec![1, 2], vec![3, -1], vec![4]]
+            .into_iter()
+            .map(|amounts| {
+                Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from(amounts))],
+                )?)
+            })
+            .collect::<Vec<Result<_>>>();
+
+        let target = Arc::new(CollectSink::default());
+        let sink = WriteAuditPublishSink::new(
+            target.clone(),
+            &dir,
+            "SELECT * FROM staged WHERE amount < 0",
+        )
+        .with_stage_rows(2)
+        .with_reject_policy(RejectPolicy::Quarantine);
+        let stream = RecordBatchStreamAdapter::new(schema, futures::stream::iter(batches));
+        let rows = sink
+            .write_all(Box::pin(stream), &Arc::new(TaskContext::default()))
+            .await?;
+        assert_eq!(rows, 5);
+        assert_eq!(*target.0.lock().unwrap(), vec![1, 2, 4]);
+        let rejected = list_stages(&dir, &[REJECTED_EXTENSION])?;
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].0, 2);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,20 +1,59 @@
+use arrow_schema::{DataType, SchemaRef};
 use datafusion::logical_expr::LogicalPlan;
 use futures::StreamExt;
-use std::{sync::Arc, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use datafusion::common::{DFSchema, DataFusionError, Result};
+use datafusion::common::{plan_err, Column, DFSchema, DataFusionError, Result, ScalarValue};
 pub use datafusion::dataframe::DataFrame;
 use datafusion::dataframe::DataFrameWriteOptions;
 use datafusion::execution::SendableRecordBatchStream;
+use datafusion::functions::core::expr_fn::{get_field, named_struct};
 use datafusion::logical_expr::{
-    logical_plan::LogicalPlanBuilder, utils::find_window_exprs, Expr, JoinType,
+    col, lit, logical_plan::LogicalPlanBuilder, utils::find_window_exprs, when, Aggregate, Expr,
+    Extension, JoinType,
 };
 use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::insert::DataSink;
 
+use crate::accumulators::overflow::{apply_overflow_policy, OverflowPolicy};
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
 use crate::context::Context;
-use crate::datasource::kafka::{ConnectionOpts, KafkaTopicBuilder};
+#[cfg(feature = "amqp")]
+use crate::datasource::amqp::{AmqpSink, RoutingKey, ROUTING_KEY_COLUMN};
+use crate::datasource::catalog_sync::TableDefinition;
+use crate::datasource::epoch::EpochTracker;
+use crate::datasource::file_sink::{FileFormat, RollingFileSink};
+use crate::datasource::kafka::{
+    ConnectionOpts, KafkaTopicBuilder, KAFKA_KEY_COLUMN, KAFKA_PARTITION_COLUMN,
+};
+use crate::datasource::latency::{LatencySink, EVENT_TIME_COLUMN};
+use crate::datasource::router::{RouterSink, ROUTE_COLUMN};
+use crate::datasource::sink::SinkTable;
+use crate::logical_plan::enforce_schema::SchemaEnforcement;
+use crate::logical_plan::interval_join::JoinTimeBound;
+use crate::logical_plan::streaming_window::{
+    StreamingWindowPlanNode, StreamingWindowSchema, StreamingWindowType,
+};
 use crate::logical_plan::StreamingLogicalPlanBuilder;
-use crate::physical_plan::utils::time::TimestampUnit;
+use crate::physical_plan::continuous::global_window::{Evictor, Trigger};
+use crate::physical_plan::continuous::window_assigner::WindowAssigner;
+use crate::physical_plan::continuous::{LateDataPolicy, WindowColumns};
+use crate::physical_plan::deduplicate::Deduplication;
+use crate::physical_plan::lookup_join::LookupTable;
+use crate::physical_plan::quality::QualityCheck;
+use crate::physical_plan::sample::SampleMethod;
+use crate::physical_plan::tap::TapSink;
+use crate::physical_plan::utils::cron::CronSchedule;
+use crate::physical_plan::utils::metadata::{
+    BARRIER_FIELD, CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN, WATERMARK_FIELD,
+};
+use crate::physical_plan::utils::time::{CalendarInterval, TimestampUnit};
+use crate::state_backend::backend::get_global_state_backend;
+use crate::utils::dry_run::DryRunReport;
+#[cfg(feature = "amqp")]
+use crate::utils::json_format::JsonFormatOptions;
+use crate::utils::live_table::LiveTable;
+use crate::utils::preview::preview_stream;
 
 /// The primary interface for building a streaming job
 ///
@@ -27,7 +66,12 @@ pub struct DataStream {
 }
 
 impl DataStream {
-    // Select columns in the output stream
+    /// Select columns in the output stream.
+    ///
+    /// When the stream carries event times the internal `_streaming_internal_metadata` column
+    /// is kept after the selected columns even if `expr_list` leaves it out, so windows and
+    /// joins further down still see them. Sinks and [`Self::print_table`] receive the rows
+    /// without it.
     pub fn select(self, expr_list: Vec<Expr>) -> Result<Self, DataFusionError> {
         let (session_state, plan) = self.df.as_ref().clone().into_parts();
 
@@ -37,6 +81,20 @@ impl DataStream {
         } else {
             LogicalPlanBuilder::window_plan(plan, window_func_exprs)?
         };
+
+        // Carry the stream metadata through the projection so downstream operators still
+        // see the event time of each row
+        let mut expr_list = expr_list;
+        let keeps_metadata = expr_list.iter().any(
+            |expr| matches!(expr, Expr::Column(column) if column.name == STREAMING_METADATA_COLUMN),
+        );
+        if !keeps_metadata
+            && plan
+                .schema()
+                .has_column_with_unqualified_name(STREAMING_METADATA_COLUMN)
+        {
+            expr_list.push(col(STREAMING_METADATA_COLUMN));
+        }
         let project_plan = LogicalPlanBuilder::from(plan).project(expr_list)?.build()?;
 
         Ok(Self {
@@ -70,6 +128,7 @@ impl DataStream {
         let plan = LogicalPlanBuilder::from(plan)
             .join_on(right_plan, join_type, on_exprs)?
             .build()?;
+        let plan = merge_stream_metadata(plan)?;
 
         Ok(Self {
             df: Arc::new(DataFrame::new(session_state, plan)),
@@ -98,6 +157,67 @@ impl DataStream {
                 filter,
             )?
             .build()?;
+        let plan = merge_stream_metadata(plan)?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Join with the rows of `right` that have equal keys and event times at most `within`
+    /// apart, e.g. orders with the payments made within an hour of them. Rows are kept until
+    /// the watermark shows nothing can match them anymore.
+    pub fn join_within(
+        self,
+        right: DataStream,
+        left_cols: &[&str],
+        right_cols: &[&str],
+        within: Duration,
+    ) -> Result<Self> {
+        if left_cols.len() != right_cols.len() {
+            return plan_err!("join_within needs as many left as right columns");
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+        let right_plan = right.get_plan();
+
+        let event_time = |plan: &LogicalPlan| -> Result<Expr> {
+            let (qualifier, _) = plan
+                .schema()
+                .qualified_field_with_unqualified_name(STREAMING_METADATA_COLUMN)?;
+            Ok(get_field(
+                Expr::Column(Column::new(qualifier.cloned(), STREAMING_METADATA_COLUMN)),
+                CANONICAL_TIMESTAMP_FIELD,
+            ))
+        };
+        let time_bound =
+            JoinTimeBound::within(event_time(&plan)?, event_time(&right_plan)?, within);
+        let on = left_cols
+            .iter()
+            .zip(right_cols)
+            .map(|(left, right)| (col(*left), col(*right)))
+            .collect();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .interval_join(right_plan, on, time_bound)?
+            .build()?;
+        let plan = merge_stream_metadata(plan)?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Enrich every row with the rows of `table` matching it on `on`, equalities of the
+    /// stream's columns and the table's, e.g. `col("customer_id").eq(col("customers.id"))`.
+    /// Rows without a match are kept with nulls for the table's columns.
+    pub fn lookup_join(self, table: LookupTable, on: Expr) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .lookup_join(table, on)?
+            .build()?;
 
         Ok(Self {
             df: Arc::new(DataFrame::new(session_state, plan)),
@@ -124,9 +244,478 @@ impl DataStream {
         })
     }
 
+    /// Create a tumbling window of calendar days, weeks or months, aligned to the session
+    /// time zone
+    pub fn calendar_window(
+        self,
+        group_expr: Vec<Expr>,
+        aggr_expr: Vec<Expr>,
+        interval: CalendarInterval,
+    ) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .calendar_window(group_expr, aggr_expr, interval)?
+            .build()?;
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Aggregate over windows laid out by `assigner`, for windows the built in types don't
+    /// cover
+    pub fn custom_window(
+        self,
+        group_expr: Vec<Expr>,
+        aggr_expr: Vec<Expr>,
+        assigner: Arc<dyn WindowAssigner>,
+    ) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .custom_window(group_expr, aggr_expr, assigner)?
+            .build()?;
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Aggregate over sessions of each group that end once no row arrived for the gap. The
+    /// gap is evaluated for every row, in milliseconds or as a duration, so it can depend on
+    /// the record, e.g. shorter gaps for bot traffic.
+    pub fn session_window(
+        self,
+        group_expr: Vec<Expr>,
+        aggr_expr: Vec<Expr>,
+        gap: Expr,
+    ) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .session_window(group_expr, aggr_expr, gap)?
+            .build()?;
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Aggregate every `size` rows per group, e.g. to micro-batch feature vectors. With a
+    /// `timeout`, a window that has been open for that long fires before it is full.
+    pub fn count_window(
+        self,
+        group_expr: Vec<Expr>,
+        aggr_expr: Vec<Expr>,
+        size: usize,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .count_window(group_expr, aggr_expr, size, timeout)?
+            .build()?;
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Aggregate all rows of each group, emitting whenever `trigger` fires, e.g. on a control
+    /// message. The `evictor` bounds what is kept, such as the last 1000 rows per group.
+    pub fn global_window(
+        self,
+        group_expr: Vec<Expr>,
+        aggr_expr: Vec<Expr>,
+        trigger: Arc<dyn Trigger>,
+        evictor: Option<Arc<dyn Evictor>>,
+    ) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .global_window(group_expr, aggr_expr, trigger, evictor)?
+            .build()?;
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Route rows that arrive after their window was already emitted to the side output `tag`
+    /// instead of dropping them into a new, partial window. Must directly follow
+    /// [`Self::window`]. Returns the windowed stream along with a stream of the late rows.
+    pub async fn late_data(self, tag: &str) -> Result<(Self, DataStream)> {
+        let windowed = self.update_window("late_data", |window| {
+            window.late_data = LateDataPolicy::SideOutput(tag.to_string());
+            Ok(())
+        })?;
+
+        let late_schema = windowed.df.logical_plan().inputs()[0]
+            .schema()
+            .inner()
+            .clone();
+        let late = self.context.side_output(tag, late_schema).await?;
+        Ok((windowed, late))
+    }
+
+    /// Discard rows that arrive after their window was already emitted, instead of dropping
+    /// them into a new, partial window. Must directly follow [`Self::window`].
+    pub fn drop_late_data(self) -> Result<Self> {
+        self.update_window("drop_late_data", |window| {
+            window.late_data = LateDataPolicy::Drop;
+            Ok(())
+        })
+    }
+
+    /// Keep windows for `lateness` after the watermark passed their end. Rows arriving in that
+    /// time update their window and its result is emitted again with the `late` firing reason,
+    /// rows arriving later are discarded. Not supported by session windows. Must directly
+    /// follow [`Self::window`].
+    pub fn allowed_lateness(self, lateness: Duration) -> Result<Self> {
+        self.update_window("allowed_lateness", |window| {
+            if let StreamingWindowType::Session(_) = window.window_type {
+                return plan_err!("Allowed lateness is not supported in session windows");
+            }
+            window.late_data = LateDataPolicy::UpdateAndEmit(lateness);
+            Ok(())
+        })
+    }
+
+    /// Additionally emit the current results of all open windows whenever the cron
+    /// expression `schedule` fires, e.g. `0 * * * *` for every hour on the hour. Windows keep
+    /// accumulating and are emitted once more when the watermark closes them. Must directly
+    /// follow [`Self::window`].
+    pub fn emit_on_schedule(self, schedule: &str) -> Result<Self> {
+        let schedule: CronSchedule = schedule.parse()?;
+        self.update_window("emit_on_schedule", |window| {
+            window.emit_schedule = Some(schedule);
+            Ok(())
+        })
+    }
+
+    /// Choose which metadata columns windows append to their results and what they are
+    /// called, e.g. to match the schema a sink expects. Must directly follow
+    /// [`Self::window`].
+    pub fn window_columns(self, columns: WindowColumns) -> Result<Self> {
+        self.update_window("window_columns", |window| {
+            window.window_schema =
+                StreamingWindowSchema::try_new(window.aggregrate.clone(), &columns)?;
+            window.window_columns = columns;
+            Ok(())
+        })
+    }
+
+    /// Apply `policy` to the integer and decimal `SUM`s and the `COUNT`s of the window, for
+    /// aggregations that run long enough for their results to overflow. Must directly follow
+    /// [`Self::window`].
+    pub fn overflow_policy(self, policy: OverflowPolicy) -> Result<Self> {
+        self.update_window("overflow_policy", |window| {
+            let aggr_expr = apply_overflow_policy(
+                window.aggregrate.aggr_expr.clone(),
+                window.input.schema(),
+                policy,
+            )?;
+            window.aggregrate = Aggregate::try_new(
+                Arc::new(window.input.clone()),
+                window.aggregrate.group_expr.clone(),
+                aggr_expr,
+            )?;
+            window.window_schema =
+                StreamingWindowSchema::try_new(window.aggregrate.clone(), &window.window_columns)?;
+            Ok(())
+        })
+    }
+
+    fn update_window(
+        &self,
+        operation: &str,
+        update: impl FnOnce(&mut StreamingWindowPlanNode) -> Result<()>,
+    ) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+        let window = match &plan {
+            LogicalPlan::Extension(Extension { node }) => {
+                node.as_any().downcast_ref::<StreamingWindowPlanNode>()
+            }
+            _ => None,
+        };
+        let Some(window) = window else {
+            return plan_err!("{operation} can only be applied directly after a window");
+        };
+
+        let mut window = window.clone();
+        update(&mut window)?;
+        let plan = LogicalPlan::Extension(Extension {
+            node: Arc::new(window),
+        });
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Merge small batches into batches of about `target_rows` rows before passing them on.
+    /// Rows are never held back for longer than `max_wait`, which bounds the added latency.
+    pub fn coalesce(self, target_rows: usize, max_wait: Duration) -> Result<Self> {
+        if target_rows == 0 {
+            return plan_err!("Coalesce target_rows must be greater than 0");
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .coalesce(target_rows, max_wait)?
+            .build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Flatten list and map columns into a row per element, e.g. the sub-events of a JSON
+    /// event, before aggregating them. Every element keeps the event time of its row, map
+    /// entries become structs of their key and value.
+    pub fn unnest(self, columns: &[&str]) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan).unnest(columns)?.build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Make the stream match the `target` schema of a sink: columns are put in its order,
+    /// missing ones filled from the defaults of `enforcement` or with nulls, and extra ones
+    /// or mismatched types rejected unless `enforcement` allows dropping or casting them.
+    /// Every mismatch is reported when the plan is built rather than by the sink at runtime.
+    pub fn enforce_schema(self, target: SchemaRef, enforcement: SchemaEnforcement) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .enforce_schema(&target, &enforcement)?
+            .build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Same as [`DataStream::unnest`] for a single column, adding the position of every
+    /// element within its list as `position_column`
+    pub fn posexplode(self, column: &str, position_column: &str) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .posexplode(column, position_column)?
+            .build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Reshape wide rows into narrow ones: every column in `columns` becomes a row holding
+    /// its name in `name_column` and its value in `value_column`, the other columns are
+    /// repeated. Null values are dropped. For the reverse within a window, see
+    /// [`pivot_aggregates`](crate::logical_plan::pivot::pivot_aggregates).
+    pub fn unpivot(self, columns: &[&str], name_column: &str, value_column: &str) -> Result<Self> {
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .unpivot(columns, name_column, value_column)?
+            .build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Pass on a representative subset of the rows, so that expensive sinks downstream don't
+    /// have to keep up with the full stream. See [`SampleMethod`] for the available methods.
+    pub fn sample(self, method: SampleMethod) -> Result<Self> {
+        match method {
+            SampleMethod::Bernoulli(probability) if !(0.0..=1.0).contains(&probability) => {
+                return plan_err!("Sample probability must be between 0 and 1, got {probability}");
+            }
+            SampleMethod::Reservoir { size: 0, .. } => {
+                return plan_err!("Reservoir samples must hold at least one row");
+            }
+            SampleMethod::Rate(rows_per_second) if rows_per_second <= 0.0 => {
+                return plan_err!("Sample rate must be positive, got {rows_per_second}");
+            }
+            _ => {}
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan).sample(method)?.build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Check every row against `checks`, declarative rules like non null columns, value
+    /// ranges, uniqueness within a window and freshness, each with the action to take on the
+    /// rows that violate it. Violations are counted per check in the operator metrics. Rows
+    /// sent to a dead letter side output are read with
+    /// [`Context::side_output`](crate::context::Context::side_output), with the schema of this
+    /// stream.
+    pub fn assert_quality(self, checks: Vec<QualityCheck>) -> Result<Self> {
+        let schema = self.df.schema();
+        for check in &checks {
+            for column in check.rule.columns() {
+                if !schema.has_column_with_unqualified_name(column) {
+                    return plan_err!("Column {column} of quality check {} not found", check.name);
+                }
+            }
+            if check.rule.needs_event_time()
+                && !schema.has_column_with_unqualified_name(STREAMING_METADATA_COLUMN)
+            {
+                return plan_err!(
+                    "Quality check {} needs the event times of a streaming source",
+                    check.name
+                );
+            }
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .assert_quality(checks)?
+            .build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Drop the rows whose `key_column` repeats the idempotency key of a row seen within
+    /// `ttl` of event time, absorbing the retries of at least once producers before they
+    /// reach stateful operators. Best applied right after the source. At most `max_keys` keys
+    /// are remembered, past that the oldest are forgotten and counted as `evicted_keys` in the
+    /// operator metrics. Rows without a key are passed on.
+    pub fn deduplicate(self, key_column: &str, ttl: Duration, max_keys: usize) -> Result<Self> {
+        let schema = self.df.schema();
+        if !schema.has_column_with_unqualified_name(key_column) {
+            return plan_err!("Idempotency key column {key_column} not found");
+        }
+        if !schema.has_column_with_unqualified_name(STREAMING_METADATA_COLUMN) {
+            return plan_err!("Deduplication needs the event times of a streaming source");
+        }
+        if ttl.is_zero() || max_keys == 0 {
+            return plan_err!("Deduplication needs a ttl and room for at least one key");
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .deduplicate(Deduplication {
+                key_column: key_column.to_string(),
+                ttl,
+                max_keys,
+            })?
+            .build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Sort the rows of every partition by event time, holding each back until a row `delay`
+    /// of event time later arrived. Meant for sources that deliver rows only slightly out of
+    /// order, ahead of operators that depend on the order rows arrive in, such as
+    /// `first_value` and `last_value`. Rows arriving after rows later than them were passed
+    /// on can't be put in order anymore, they are passed on right away and counted as
+    /// `late_rows` in the operator metrics.
+    pub fn reorder(self, delay: Duration) -> Result<Self> {
+        if !self
+            .df
+            .schema()
+            .has_column_with_unqualified_name(STREAMING_METADATA_COLUMN)
+        {
+            return plan_err!("Reordering needs the event times of a streaming source");
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan).reorder(delay)?.build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Print a random sample of the rows flowing through this point of the pipeline.
+    /// `sample_rate` is the fraction of rows to print, between 0 and 1.
+    pub fn tap(self, name: &str, sample_rate: f64) -> Result<Self> {
+        self.tap_with_sink(name, sample_rate, TapSink::Console)
+    }
+
+    /// Same as [`DataStream::tap`] but sends the sampled rows to the given sink
+    pub fn tap_with_sink(self, name: &str, sample_rate: f64, sink: TapSink) -> Result<Self> {
+        if !(0.0..=1.0).contains(&sample_rate) {
+            return plan_err!("Tap sample rate must be between 0 and 1, got {sample_rate}");
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+
+        let plan = LogicalPlanBuilder::from(plan)
+            .tap(name.to_string(), sample_rate, sink)?
+            .build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Plan the full pipeline and validate its sources without consuming or producing any data
+    pub async fn dry_run(&self) -> Result<DryRunReport> {
+        let physical_plan = self.df.as_ref().clone().create_physical_plan().await?;
+        DryRunReport::try_new(self.df.logical_plan(), &physical_plan)
+    }
+
+    /// Give the pipeline writing to `sink` an [`EpochTracker`] of its own, resumed from its
+    /// previous run when checkpointing
+    fn with_epoch_tracker(self, sink: &str) -> Result<Self> {
+        let tracker = if self.config().checkpoint {
+            EpochTracker::restore(get_global_state_backend()?, sink)?
+        } else {
+            EpochTracker::default()
+        };
+        let (mut session_state, plan) = self.df.as_ref().clone().into_parts();
+        session_state.config_mut().set_extension(Arc::new(tracker));
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context,
+        })
+    }
+
+    fn config(&self) -> DenormalizedConfig {
+        self.df
+            .task_ctx()
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// execute the stream and print the results to stdout.
     /// Mainly used for development and debugging
     pub async fn print_stream(self) -> Result<(), DataFusionError> {
+        if self.config().dry_run {
+            println!("{}", self.dry_run().await?);
+            return Ok(());
+        }
+
         let mut stream: SendableRecordBatchStream =
             self.df.as_ref().clone().execute_stream().await?;
         loop {
@@ -149,6 +738,49 @@ impl DataStream {
         }
     }
 
+    /// Show the latest row per key as a table redrawn in place as results come in,
+    /// highlighting the cells that changed. Meant for iterating on queries in a terminal,
+    /// e.g. with the group by columns and `window_start_time` as `key_columns`.
+    pub async fn print_table(self, key_columns: &[&str], max_rows: usize) -> Result<()> {
+        if self.config().dry_run {
+            println!("{}", self.dry_run().await?);
+            return Ok(());
+        }
+
+        let ds = self.drop_stream_metadata()?;
+        let columns = ds
+            .df
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+        let mut table = LiveTable::new(columns, key_columns, max_rows)?;
+
+        let mut stream = ds.df.as_ref().clone().execute_stream().await?;
+        while let Some(batch) = stream.next().await.transpose()? {
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            table.update(&batch)?;
+            print!("{}", table.render());
+        }
+        Ok(())
+    }
+
+    /// Run the stream without a sink and get snapshots of its latest `n_rows` rows, at most
+    /// one every `refresh`. Handy in notebooks to watch an aggregation converge, dropping the
+    /// returned stream stops the pipeline.
+    pub async fn stream_preview(
+        self,
+        n_rows: usize,
+        refresh: Duration,
+    ) -> Result<SendableRecordBatchStream> {
+        let ds = self.drop_stream_metadata()?;
+        let stream = ds.df.as_ref().clone().execute_stream().await?;
+        Ok(preview_stream(stream, n_rows, refresh))
+    }
+
     /// Return the schema of DataFrame that backs the DataStream
     pub fn schema(&self) -> &DFSchema {
         self.df.schema()
@@ -183,17 +815,182 @@ impl DataStream {
         })
     }
 
+    /// Remove the internal stream metadata column, used before handing rows to a sink
+    fn drop_stream_metadata(self) -> Result<Self> {
+        if !self
+            .df
+            .schema()
+            .has_column_with_unqualified_name(STREAMING_METADATA_COLUMN)
+        {
+            return Ok(self);
+        }
+        let (session_state, plan) = self.df.as_ref().clone().into_parts();
+        let exprs: Vec<Expr> = plan
+            .schema()
+            .iter()
+            .filter(|(_, field)| field.name() != STREAMING_METADATA_COLUMN)
+            .map(|(qualifier, field)| Expr::Column(Column::new(qualifier.cloned(), field.name())))
+            .collect();
+        let plan = LogicalPlanBuilder::from(plan).project(exprs)?.build()?;
+
+        Ok(Self {
+            df: Arc::new(DataFrame::new(session_state, plan)),
+            context: self.context.clone(),
+        })
+    }
+
+    /// Execute the stream and write the results to `sink`, registered as the table `name`.
+    /// The sink receives the rows without the stream metadata.
+    pub async fn sink(self, name: &str, sink: Arc<dyn DataSink>) -> Result<()> {
+        let ds = self.drop_stream_metadata()?;
+        if ds.config().dry_run {
+            println!("{}", ds.dry_run().await?);
+            return Ok(());
+        }
+
+        let schema = Arc::new(datafusion::common::arrow::datatypes::Schema::from(
+            ds.df.schema(),
+        ));
+        ds.context
+            .register_table(name.to_string(), Arc::new(SinkTable::new(schema, sink)))
+            .await?;
+        ds.with_epoch_tracker(name)?
+            .df
+            .as_ref()
+            .clone()
+            .write_table(name, DataFrameWriteOptions::default())
+            .await?;
+        Ok(())
+    }
+
+    /// Execute the stream and publish every row to the RabbitMQ `exchange`, routed by the
+    /// value `routing_key` evaluates to for the row
+    #[cfg(feature = "amqp")]
+    pub async fn sink_amqp(self, uri: &str, exchange: &str, routing_key: Expr) -> Result<()> {
+        let df = self
+            .df
+            .as_ref()
+            .clone()
+            .with_column(ROUTING_KEY_COLUMN, routing_key)?;
+        let ds = Self {
+            df: Arc::new(df),
+            context: self.context.clone(),
+        };
+        let sink = AmqpSink::new(
+            uri,
+            exchange,
+            RoutingKey::Column(ROUTING_KEY_COLUMN.to_string()),
+            JsonFormatOptions::default(),
+        );
+        ds.sink(exchange, Arc::new(sink)).await
+    }
+
+    /// Execute the stream and write every row to the sink of the first of `routes` whose
+    /// predicate holds for it, or to `default` if none does. Without a default, rows that match
+    /// no predicate fail the pipeline.
+    pub async fn sink_routed(
+        self,
+        name: &str,
+        routes: Vec<(Expr, Arc<dyn DataSink>)>,
+        default: Option<Arc<dyn DataSink>>,
+    ) -> Result<()> {
+        let mut router = RouterSink::new(ROUTE_COLUMN);
+        let mut route = lit(ScalarValue::Utf8(None));
+        for (i, (predicate, _)) in routes.iter().enumerate().rev() {
+            route = when(predicate.clone(), lit(i.to_string())).otherwise(route)?;
+        }
+        for (i, (_, sink)) in routes.into_iter().enumerate() {
+            router = router.with_route(&i.to_string(), sink);
+        }
+        if let Some(sink) = default {
+            router = router.with_default(sink);
+        }
+
+        let df = self.df.as_ref().clone().with_column(ROUTE_COLUMN, route)?;
+        let ds = Self {
+            df: Arc::new(df),
+            context: self.context.clone(),
+        };
+        ds.sink(name, Arc::new(router)).await
+    }
+
+    /// Execute the stream and write it to rolling `format` files in the directory at
+    /// `table_uri`, laid out in directories by the `partition_by` columns, see
+    /// [`RollingFileSink`] for how files are rolled
+    pub async fn sink_files(
+        self,
+        table_uri: &str,
+        format: FileFormat,
+        partition_by: &[&str],
+    ) -> Result<()> {
+        for column in partition_by {
+            if !self.df.schema().has_column_with_unqualified_name(column) {
+                return plan_err!("Partition column {column} not found");
+            }
+        }
+        let sink = RollingFileSink::try_new(table_uri, format)?.with_partition_by(partition_by);
+        self.sink("files", Arc::new(sink)).await
+    }
+
+    /// Execute the stream and measure it instead of writing the results anywhere: the latency
+    /// from the event time of every row to it reaching the sink, and the throughput. The
+    /// [`LatencyReport`](crate::datasource::latency::LatencyReport) is written to
+    /// `report_path` as JSON once the stream ends or the pipeline is stopped.
+    pub async fn sink_latency_report(self, report_path: impl Into<PathBuf>) -> Result<()> {
+        let event_time = get_field(col(STREAMING_METADATA_COLUMN), CANONICAL_TIMESTAMP_FIELD);
+        let df = self
+            .df
+            .as_ref()
+            .clone()
+            .with_column(EVENT_TIME_COLUMN, event_time)?;
+        let ds = Self {
+            df: Arc::new(df),
+            context: self.context.clone(),
+        };
+        ds.sink("latency_report", Arc::new(LatencySink::new(report_path)))
+            .await
+    }
+
     /// execute the stream and write the results to a give kafka topic
     pub async fn sink_kafka(
         self,
         bootstrap_servers: String,
         topic: String,
     ) -> Result<(), DataFusionError> {
+        self.sink_kafka_keyed(bootstrap_servers, topic, None, None)
+            .await
+    }
+
+    /// Write every row to a Kafka topic as a message keyed by what `key` evaluates to, and to
+    /// the partition `partition` evaluates to. Without a partition the producer picks one by
+    /// key, so rows of a key stay in order and compacted topics keep the latest row per key.
+    pub async fn sink_kafka_keyed(
+        self,
+        bootstrap_servers: String,
+        topic: String,
+        key: Option<Expr>,
+        partition: Option<Expr>,
+    ) -> Result<(), DataFusionError> {
+        let ds = self.drop_stream_metadata()?;
+        let mut builder = KafkaTopicBuilder::new(bootstrap_servers.clone());
+        let mut df = ds.df.as_ref().clone();
+        if let Some(key) = key {
+            df = df.with_column(KAFKA_KEY_COLUMN, key)?;
+            builder.with_key_column(KAFKA_KEY_COLUMN);
+        }
+        if let Some(partition) = partition {
+            df = df.with_column(KAFKA_PARTITION_COLUMN, partition)?;
+            builder.with_partition_column(KAFKA_PARTITION_COLUMN);
+        }
+        let ds = Self {
+            df: Arc::new(df),
+            context: ds.context,
+        };
         let processed_schema = Arc::new(datafusion::common::arrow::datatypes::Schema::from(
-            self.df.schema(),
+            ds.df.schema(),
         ));
 
-        let sink_topic = KafkaTopicBuilder::new(bootstrap_servers.clone())
+        let sink_topic = builder
             .with_timestamp(String::from("occurred_at_ms"), TimestampUnit::Int64Millis)
             .with_encoding("json")?
             .with_topic(topic.clone())
@@ -201,11 +998,26 @@ impl DataStream {
             .build_writer(ConnectionOpts::new())
             .await?;
 
-        self.context
+        if ds.config().dry_run {
+            sink_topic.0.validate()?;
+            println!("{}", ds.dry_run().await?);
+            return Ok(());
+        }
+
+        ds.context
+            .sync_table(TableDefinition::kafka(
+                &topic,
+                &bootstrap_servers,
+                sink_topic.0.schema.clone(),
+                sink_topic.0.encoding,
+            ))
+            .await?;
+        ds.context
             .register_table(topic.clone(), Arc::new(sink_topic))
             .await?;
 
-        self.df
+        ds.with_epoch_tracker(&topic)?
+            .df
             .as_ref()
             .clone()
             .write_table(topic.as_str(), DataFrameWriteOptions::default())
@@ -215,6 +1027,80 @@ impl DataStream {
     }
 }
 
+/// Joining two streams yields two metadata columns. Replace them with a single one whose
+/// event time is the later of the two input rows, so the joined stream can be windowed. If
+/// either input carries the watermarks of its source the earlier of the two is kept, an input
+/// without them counts as having reached the event time of its row.
+fn merge_stream_metadata(plan: LogicalPlan) -> Result<LogicalPlan> {
+    let metadata_columns: Vec<(Expr, bool)> = plan
+        .schema()
+        .iter()
+        .filter(|(_, field)| field.name() == STREAMING_METADATA_COLUMN)
+        .map(|(qualifier, field)| {
+            let has_watermark = match field.data_type() {
+                DataType::Struct(fields) => fields.find(WATERMARK_FIELD).is_some(),
+                _ => false,
+            };
+            (
+                Expr::Column(Column::new(qualifier.cloned(), field.name())),
+                has_watermark,
+            )
+        })
+        .collect();
+    let [(left, left_has_watermark), (right, right_has_watermark)] = metadata_columns.as_slice()
+    else {
+        return Ok(plan);
+    };
+
+    let left_ts = get_field(left.clone(), CANONICAL_TIMESTAMP_FIELD);
+    let right_ts = get_field(right.clone(), CANONICAL_TIMESTAMP_FIELD);
+    let canonical_timestamp = when(
+        left_ts
+            .clone()
+            .gt_eq(right_ts.clone())
+            .or(right_ts.clone().is_null()),
+        left_ts.clone(),
+    )
+    .otherwise(right_ts.clone())?;
+    let mut fields = vec![
+        lit(BARRIER_FIELD),
+        get_field(left.clone(), BARRIER_FIELD),
+        lit(CANONICAL_TIMESTAMP_FIELD),
+        canonical_timestamp,
+    ];
+    if *left_has_watermark || *right_has_watermark {
+        let watermark = |metadata: &Expr, has_watermark: bool, event_time: Expr| match has_watermark
+        {
+            true => get_field(metadata.clone(), WATERMARK_FIELD),
+            false => event_time,
+        };
+        let left_watermark = watermark(left, *left_has_watermark, left_ts);
+        let right_watermark = watermark(right, *right_has_watermark, right_ts);
+        fields.push(lit(WATERMARK_FIELD));
+        fields.push(
+            when(
+                left_watermark
+                    .clone()
+                    .lt_eq(right_watermark.clone())
+                    .or(right_watermark.clone().is_null()),
+                left_watermark,
+            )
+            .otherwise(right_watermark)?,
+        );
+    }
+    let merged = named_struct(fields).alias(STREAMING_METADATA_COLUMN);
+
+    let mut exprs: Vec<Expr> = plan
+        .schema()
+        .iter()
+        .filter(|(_, field)| field.name() != STREAMING_METADATA_COLUMN)
+        .map(|(qualifier, field)| Expr::Column(Column::new(qualifier.cloned(), field.name())))
+        .collect();
+    exprs.push(merged);
+
+    LogicalPlanBuilder::from(plan).project(exprs)?.build()
+}
+
 /// Trait that allows both DataStream and DataFrame objects to be joined to
 /// the current DataStream
 pub trait Joinable {
@@ -232,3 +1118,56 @@ impl Joinable for DataStream {
         plan
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::datasource::MemTable;
+
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+    async fn events() -> Result<DataStream> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("reading", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(Int64Array::from(vec![10, 20])),
+                Arc::new(stream_metadata_array(vec![Some(1_000), Some(2_000)].into())),
+            ],
+        )?;
+        let table = MemTable::try_new(schema, vec![vec![batch]])?;
+        Context::new()?.from_source("events", Arc::new(table)).await
+    }
+
+    fn columns(ds: &DataStream) -> Vec<String> {
+        ds.df
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn select_keeps_stream_metadata() -> Result<()> {
+        let selected = events().await?.select(vec![col("id")])?;
+        assert_eq!(columns(&selected), ["id", STREAMING_METADATA_COLUMN]);
+
+        let explicit = events()
+            .await?
+            .select(vec![col(STREAMING_METADATA_COLUMN), col("reading")])?;
+        assert_eq!(columns(&explicit), [STREAMING_METADATA_COLUMN, "reading"]);
+
+        let dropped = selected.drop_stream_metadata()?;
+        assert_eq!(columns(&dropped), ["id"]);
+        Ok(())
+    }
+}
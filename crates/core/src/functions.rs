@@ -0,0 +1,40 @@
+use datafusion::execution::context::SessionContext;
+
+use crate::accumulators::overflow::OverflowAggregate;
+use crate::logical_plan::window_table_functions::register_window_table_functions;
+
+/// Register the SQL functions denormalized adds with `context`, for sessions set up without a
+/// [`Context`](crate::context::Context), which registers them with its own session:
+///
+/// - the window table functions `tumble`, `hop` and `session`, see
+///   [`register_window_table_functions`]
+/// - the sums and counts applying an overflow policy, such as `checked_sum` and
+///   `saturating_count`, see [`OverflowAggregate`]
+///
+/// The window table functions look streams up in the catalogs of `context` as it is
+/// configured when they are registered.
+pub fn register_denormalized_functions(context: &mut SessionContext) {
+    register_window_table_functions(context);
+    for udaf in OverflowAggregate::all() {
+        context.register_udaf(udaf.as_ref().clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_all_functions() {
+        let mut context = SessionContext::new();
+        register_denormalized_functions(&mut context);
+
+        let state = context.state();
+        for function in ["tumble", "hop", "session"] {
+            assert!(state.table_functions().contains_key(function));
+        }
+        for function in ["checked_sum", "saturating_count", "promoting_sum"] {
+            assert!(state.aggregate_functions().contains_key(function));
+        }
+    }
+}
@@ -3,6 +3,7 @@ pub mod config_extensions;
 pub mod context;
 pub mod datasource;
 pub mod datastream;
+pub mod functions;
 pub mod logical_plan;
 pub mod physical_optimizer;
 pub mod physical_plan;
@@ -0,0 +1,56 @@
+use std::fmt::{self, Debug};
+use std::time::Duration;
+
+use datafusion::common::{DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+
+#[derive(PartialEq, Eq, Hash)]
+pub struct CoalescePlanNode {
+    pub target_rows: usize,
+    pub max_wait: Duration,
+    pub input: LogicalPlan,
+}
+
+impl Debug for CoalescePlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        UserDefinedLogicalNodeCore::fmt_for_explain(self, f)
+    }
+}
+
+impl UserDefinedLogicalNodeCore for CoalescePlanNode {
+    fn name(&self) -> &str {
+        "Coalesce"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.input.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Coalesce: target_rows={}, max_wait={:?}",
+            self.target_rows, self.max_wait
+        )
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        _exprs: Vec<Expr>,
+        mut inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        Ok(Self {
+            target_rows: self.target_rows,
+            max_wait: self.max_wait,
+            input: inputs.swap_remove(0),
+        })
+    }
+}
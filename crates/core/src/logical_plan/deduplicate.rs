@@ -0,0 +1,51 @@
+use std::fmt::{self, Debug};
+
+use datafusion::common::{DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+
+use crate::physical_plan::deduplicate::Deduplication;
+
+#[derive(PartialEq, Eq, Hash)]
+pub struct DeduplicatePlanNode {
+    pub deduplication: Deduplication,
+    pub input: LogicalPlan,
+}
+
+impl Debug for DeduplicatePlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        UserDefinedLogicalNodeCore::fmt_for_explain(self, f)
+    }
+}
+
+impl UserDefinedLogicalNodeCore for DeduplicatePlanNode {
+    fn name(&self) -> &str {
+        "Deduplicate"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.input.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Deduplicate: {}", self.deduplication)
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        _exprs: Vec<Expr>,
+        mut inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        Ok(Self {
+            deduplication: self.deduplication.clone(),
+            input: inputs.swap_remove(0),
+        })
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use arrow::compute::can_cast_types;
+use arrow_schema::Schema;
+use datafusion::common::{plan_err, Column, Result, ScalarValue};
+use datafusion::logical_expr::{cast, lit, Expr, LogicalPlanBuilder};
+
+use crate::physical_plan::utils::metadata::STREAMING_METADATA_COLUMN;
+
+/// What to do with columns the target schema doesn't have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtraColumns {
+    #[default]
+    Reject,
+    Drop,
+}
+
+/// How a stream is made to match the schema a sink declares
+#[derive(Debug, Clone, Default)]
+pub struct SchemaEnforcement {
+    /// Values for target columns the stream lacks. Nullable columns without a default are
+    /// filled with nulls.
+    pub defaults: HashMap<String, ScalarValue>,
+    pub extra_columns: ExtraColumns,
+    /// Cast columns to the target type rather than rejecting them
+    pub coerce_types: bool,
+}
+
+impl SchemaEnforcement {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_default(mut self, column: &str, value: ScalarValue) -> Self {
+        self.defaults.insert(column.to_string(), value);
+        self
+    }
+
+    pub fn with_extra_columns(mut self, extra_columns: ExtraColumns) -> Self {
+        self.extra_columns = extra_columns;
+        self
+    }
+
+    pub fn with_coerce_types(mut self, coerce_types: bool) -> Self {
+        self.coerce_types = coerce_types;
+        self
+    }
+}
+
+/// Project the stream onto `target`: its columns in order, defaults for missing ones and
+/// casts where allowed. All mismatches are reported at once when planning.
+pub(crate) fn enforce_schema_plan(
+    builder: LogicalPlanBuilder,
+    target: &Schema,
+    enforcement: &SchemaEnforcement,
+) -> Result<LogicalPlanBuilder> {
+    let schema = builder.schema().clone();
+    let mut problems = vec![];
+    let mut exprs = vec![];
+    for field in target.fields() {
+        let expr = match schema.qualified_field_with_unqualified_name(field.name()) {
+            Ok((qualifier, source)) => {
+                let column = Expr::Column(Column::new(qualifier.cloned(), source.name()));
+                if source.data_type() == field.data_type() {
+                    column
+                } else if enforcement.coerce_types
+                    && can_cast_types(source.data_type(), field.data_type())
+                {
+                    cast(column, field.data_type().clone())
+                } else {
+                    problems.push(format!(
+                        "{}: expected {}, found {}",
+                        field.name(),
+                        field.data_type(),
+                        source.data_type()
+                    ));
+                    continue;
+                }
+            }
+            Err(_) => match enforcement.defaults.get(field.name()) {
+                Some(default) => cast(lit(default.clone()), field.data_type().clone()),
+                None if field.is_nullable() => lit(ScalarValue::try_from(field.data_type())?),
+                None => {
+                    problems.push(format!("{}: missing and has no default", field.name()));
+                    continue;
+                }
+            },
+        };
+        exprs.push(expr.alias(field.name()));
+    }
+
+    let extra: Vec<&str> = schema
+        .fields()
+        .iter()
+        .map(|field| field.name().as_str())
+        .filter(|name| *name != STREAMING_METADATA_COLUMN && target.field_with_name(name).is_err())
+        .collect();
+    if enforcement.extra_columns == ExtraColumns::Reject && !extra.is_empty() {
+        problems.push(format!("unexpected columns {}", extra.join(", ")));
+    }
+    if !problems.is_empty() {
+        return plan_err!(
+            "Stream doesn't match the sink schema:\n  {}",
+            problems.join("\n  ")
+        );
+    }
+
+    if schema.has_column_with_unqualified_name(STREAMING_METADATA_COLUMN) {
+        exprs.push(Expr::Column(Column::from_name(STREAMING_METADATA_COLUMN)));
+    }
+    builder.project(exprs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_schema::{DataType, Field};
+    use datafusion::logical_expr::table_scan;
+
+    #[test]
+    fn enforce_target_schema() -> Result<()> {
+        let stream = Schema::new(vec![
+            Field::new("count", DataType::Int32, false),
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("debug", DataType::Utf8, true),
+        ]);
+        let target = Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, false),
+            Field::new("region", DataType::Utf8, false),
+            Field::new("note", DataType::Utf8, true),
+        ]);
+
+        let err = enforce_schema_plan(
+            table_scan(Some("readings"), &stream, None)?,
+            &target,
+            &SchemaEnforcement::new(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("count: expected Int64, found Int32"), "{err}");
+        assert!(err.contains("region: missing and has no default"), "{err}");
+        assert!(err.contains("unexpected columns debug"), "{err}");
+
+        let enforcement = SchemaEnforcement::new()
+            .with_default("region", ScalarValue::from("eu-west-1"))
+            .with_extra_columns(ExtraColumns::Drop)
+            .with_coerce_types(true);
+        let plan = enforce_schema_plan(
+            table_scan(Some("readings"), &stream, None)?,
+            &target,
+            &enforcement,
+        )?
+        .build()?;
+        let fields: Vec<(&str, &DataType)> = plan
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| (field.name().as_str(), field.data_type()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("sensor", &DataType::Utf8),
+                ("count", &DataType::Int64),
+                ("region", &DataType::Utf8),
+                ("note", &DataType::Utf8),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::Duration;
+
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{plan_err, Column, DFSchemaRef, Result};
+use datafusion::logical_expr::builder::build_join_schema;
+use datafusion::logical_expr::utils::{conjunction, split_conjunction};
+use datafusion::logical_expr::{
+    Between, BinaryExpr, Expr, ExprSchemable, Extension, Filter, JoinType, LogicalPlan, Operator,
+    UserDefinedLogicalNodeCore,
+};
+
+use crate::physical_plan::utils::time::interval_millis;
+
+/// How far apart in event time the rows of an interval join may be: the left event time minus
+/// the right one lies within `lower_ms..=upper_ms`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JoinTimeBound {
+    /// Event time of the rows of the left input, in milliseconds or as a timestamp
+    pub left_time: Expr,
+    pub right_time: Expr,
+    pub lower_ms: i64,
+    pub upper_ms: i64,
+}
+
+impl JoinTimeBound {
+    /// Event times at most `within` apart in either direction
+    pub fn within(left_time: Expr, right_time: Expr, within: Duration) -> Self {
+        let within_ms = within.as_millis() as i64;
+        Self {
+            left_time,
+            right_time,
+            lower_ms: -within_ms,
+            upper_ms: within_ms,
+        }
+    }
+}
+
+/// Inner join of two streams on equal keys and event times within a [`JoinTimeBound`]
+#[derive(PartialEq, Eq, Hash)]
+pub struct IntervalJoinPlanNode {
+    pub left: LogicalPlan,
+    pub right: LogicalPlan,
+    /// Pairs of left and right key expressions
+    pub on: Vec<(Expr, Expr)>,
+    pub time_bound: JoinTimeBound,
+    pub schema: DFSchemaRef,
+}
+
+impl IntervalJoinPlanNode {
+    pub fn try_new(
+        left: LogicalPlan,
+        right: LogicalPlan,
+        on: Vec<(Expr, Expr)>,
+        time_bound: JoinTimeBound,
+    ) -> Result<Self> {
+        if on.is_empty() {
+            return plan_err!("Interval joins need at least one pair of keys");
+        }
+        if time_bound.lower_ms > time_bound.upper_ms {
+            return plan_err!(
+                "Interval join bound from {}ms to {}ms is empty",
+                time_bound.lower_ms,
+                time_bound.upper_ms
+            );
+        }
+        for (left_key, right_key) in &on {
+            let left_type = left_key.get_type(left.schema())?;
+            let right_type = right_key.get_type(right.schema())?;
+            if left_type != right_type {
+                return plan_err!(
+                    "Interval join keys {left_key} and {right_key} differ in type, {left_type} and {right_type}"
+                );
+            }
+        }
+        let schema = build_join_schema(left.schema(), right.schema(), &JoinType::Inner)?;
+
+        Ok(Self {
+            left,
+            right,
+            on,
+            time_bound,
+            schema: Arc::new(schema),
+        })
+    }
+}
+
+impl Debug for IntervalJoinPlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        UserDefinedLogicalNodeCore::fmt_for_explain(self, f)
+    }
+}
+
+impl UserDefinedLogicalNodeCore for IntervalJoinPlanNode {
+    fn name(&self) -> &str {
+        "IntervalJoin"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.left, &self.right]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        let mut exprs = self
+            .on
+            .iter()
+            .flat_map(|(left, right)| [left.clone(), right.clone()])
+            .collect::<Vec<_>>();
+        exprs.push(self.time_bound.left_time.clone());
+        exprs.push(self.time_bound.right_time.clone());
+        exprs
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on = self
+            .on
+            .iter()
+            .map(|(left, right)| format!("{left} = {right}"))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "IntervalJoin: on=[{}], {} - {} in {}ms..={}ms",
+            on.join(", "),
+            self.time_bound.left_time,
+            self.time_bound.right_time,
+            self.time_bound.lower_ms,
+            self.time_bound.upper_ms
+        )
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        mut exprs: Vec<Expr>,
+        mut inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        let right_time = exprs.pop().unwrap();
+        let left_time = exprs.pop().unwrap();
+        let on = exprs
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        let right = inputs.swap_remove(1);
+        let left = inputs.swap_remove(0);
+        Self::try_new(
+            left,
+            right,
+            on,
+            JoinTimeBound {
+                left_time,
+                right_time,
+                lower_ms: self.time_bound.lower_ms,
+                upper_ms: self.time_bound.upper_ms,
+            },
+        )
+    }
+}
+
+/// Plan inner joins in `plan` that bound the event times of the two sides, e.g.
+/// `ON a.key = b.key AND a.ts BETWEEN b.ts - INTERVAL '5 minutes' AND b.ts`, as interval joins.
+/// The rest of the join condition is applied as a filter on the joined rows.
+pub fn plan_interval_joins(plan: LogicalPlan) -> Result<LogicalPlan> {
+    plan.transform_up(|plan| {
+        let LogicalPlan::Join(join) = &plan else {
+            return Ok(Transformed::no(plan));
+        };
+        if join.join_type != JoinType::Inner {
+            return Ok(Transformed::no(plan));
+        }
+
+        let side = |expr: &Expr| match expr {
+            Expr::Column(column) if join.left.schema().has_column(column) => Some(true),
+            Expr::Column(column) if join.right.schema().has_column(column) => Some(false),
+            _ => None,
+        };
+        let mut on = join.on.clone();
+        let mut time_bound = None;
+        let mut rest = vec![];
+        for predicate in join.filter.iter().flat_map(split_conjunction) {
+            match predicate {
+                Expr::BinaryExpr(BinaryExpr {
+                    left,
+                    op: Operator::Eq,
+                    right,
+                }) => match (side(left), side(right)) {
+                    (Some(true), Some(false)) => on.push((*left.clone(), *right.clone())),
+                    (Some(false), Some(true)) => on.push((*right.clone(), *left.clone())),
+                    _ => rest.push(predicate.clone()),
+                },
+                Expr::Between(between) if time_bound.is_none() => {
+                    match between_bound(between, &side) {
+                        Some(bound) => time_bound = Some(bound),
+                        None => rest.push(predicate.clone()),
+                    }
+                }
+                _ => rest.push(predicate.clone()),
+            }
+        }
+        let Some(time_bound) = time_bound else {
+            return Ok(Transformed::no(plan));
+        };
+        if on.is_empty() {
+            return Ok(Transformed::no(plan));
+        }
+
+        let joined = LogicalPlan::Extension(Extension {
+            node: Arc::new(IntervalJoinPlanNode::try_new(
+                join.left.as_ref().clone(),
+                join.right.as_ref().clone(),
+                on,
+                time_bound,
+            )?),
+        });
+        match conjunction(rest) {
+            Some(predicate) => Ok(Transformed::yes(LogicalPlan::Filter(Filter::try_new(
+                predicate,
+                Arc::new(joined),
+            )?))),
+            None => Ok(Transformed::yes(joined)),
+        }
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// The bound of `time BETWEEN other_time + low AND other_time + high`, with the times taken
+/// from opposite sides of the join and the offsets given as intervals
+fn between_bound(
+    between: &Between,
+    side: &impl Fn(&Expr) -> Option<bool>,
+) -> Option<JoinTimeBound> {
+    if between.negated {
+        return None;
+    }
+    let time_is_left = side(&between.expr)?;
+    let (low_time, low_ms) = offset_column(&between.low)?;
+    let (high_time, high_ms) = offset_column(&between.high)?;
+    let other_time = Expr::Column(low_time.clone());
+    if low_time != high_time || side(&other_time)? == time_is_left {
+        return None;
+    }
+
+    let time = between.expr.as_ref().clone();
+    Some(match time_is_left {
+        true => JoinTimeBound {
+            left_time: time,
+            right_time: other_time,
+            lower_ms: low_ms,
+            upper_ms: high_ms,
+        },
+        false => JoinTimeBound {
+            left_time: other_time,
+            right_time: time,
+            lower_ms: -high_ms,
+            upper_ms: -low_ms,
+        },
+    })
+}
+
+/// A column plus or minus an interval, or on its own
+fn offset_column(expr: &Expr) -> Option<(Column, i64)> {
+    match expr {
+        Expr::Column(column) => Some((column.clone(), 0)),
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+            let (Expr::Column(column), Expr::Literal(interval)) = (left.as_ref(), right.as_ref())
+            else {
+                return None;
+            };
+            let millis = interval_millis(interval)?;
+            match op {
+                Operator::Plus => Some((column.clone(), millis)),
+                Operator::Minus => Some((column.clone(), -millis)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use arrow::compute::can_cast_types;
+use datafusion::common::{plan_err, Column, DFSchema, DFSchemaRef, Result};
+use datafusion::logical_expr::builder::build_join_schema;
+use datafusion::logical_expr::expr_rewriter::normalize_col;
+use datafusion::logical_expr::utils::split_conjunction;
+use datafusion::logical_expr::{
+    BinaryExpr, Expr, ExprSchemable, JoinType, LogicalPlan, Operator, UserDefinedLogicalNodeCore,
+};
+
+use crate::physical_plan::lookup_join::LookupTable;
+
+/// Left join of a stream with a [`LookupTable`], looking up the rows of the table whose key
+/// columns equal the keys of each row of the stream
+#[derive(PartialEq, Eq, Hash)]
+pub struct LookupJoinPlanNode {
+    pub input: LogicalPlan,
+    pub table: LookupTable,
+    /// Pairs of keys of the stream and the names of the table columns they look up
+    pub on: Vec<(Expr, String)>,
+    pub schema: DFSchemaRef,
+}
+
+impl LookupJoinPlanNode {
+    /// Join `input` with `table` on `on`, equalities of expressions of the stream and columns
+    /// of the table combined with `AND`, e.g. `col("customer_id").eq(col("customers.id"))`
+    pub fn try_new(input: LogicalPlan, table: LookupTable, on: Expr) -> Result<Self> {
+        let table_schema = DFSchema::try_from_qualified_schema(
+            table.name().to_string(),
+            &table.provider().schema(),
+        )?;
+        let table_column = |expr: &Expr| match expr {
+            Expr::Column(column) if table_schema.has_column(column) => Some(column.name.clone()),
+            Expr::Column(Column {
+                relation: None,
+                name,
+            }) if !input.schema().has_column_with_unqualified_name(name) => table_schema
+                .has_column_with_unqualified_name(name)
+                .then(|| name.clone()),
+            _ => None,
+        };
+
+        let mut keys = vec![];
+        for predicate in split_conjunction(&on) {
+            let Expr::BinaryExpr(BinaryExpr {
+                left,
+                op: Operator::Eq,
+                right,
+            }) = predicate
+            else {
+                return plan_err!("Lookup joins only support equalities, got {predicate}");
+            };
+            let key = match (table_column(left), table_column(right)) {
+                (None, Some(column)) => (normalize_col(left.as_ref().clone(), &input)?, column),
+                (Some(column), None) => (normalize_col(right.as_ref().clone(), &input)?, column),
+                _ => {
+                    return plan_err!(
+                        "{predicate} has to compare the stream with a column of {}",
+                        table.name()
+                    )
+                }
+            };
+            keys.push(key);
+        }
+        Self::with_keys(input, table, keys, table_schema)
+    }
+
+    fn with_keys(
+        input: LogicalPlan,
+        table: LookupTable,
+        on: Vec<(Expr, String)>,
+        table_schema: DFSchema,
+    ) -> Result<Self> {
+        if on.is_empty() {
+            return plan_err!("Lookup joins need at least one key");
+        }
+        for (key, column) in &on {
+            let key_type = key.get_type(input.schema())?;
+            let (_, field) = table_schema.qualified_field_with_unqualified_name(column)?;
+            // The table column is cast to the type of the key when looking it up
+            if !can_cast_types(field.data_type(), &key_type) {
+                return plan_err!(
+                    "Lookup key {key} of type {key_type} can't look up {column} of type {}",
+                    field.data_type()
+                );
+            }
+        }
+        let schema = build_join_schema(input.schema(), &table_schema, &JoinType::Left)?;
+
+        Ok(Self {
+            input,
+            table,
+            on,
+            schema: Arc::new(schema),
+        })
+    }
+}
+
+impl Debug for LookupJoinPlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        UserDefinedLogicalNodeCore::fmt_for_explain(self, f)
+    }
+}
+
+impl UserDefinedLogicalNodeCore for LookupJoinPlanNode {
+    fn name(&self) -> &str {
+        "LookupJoin"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        self.on.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on = self
+            .on
+            .iter()
+            .map(|(key, column)| format!("{key} = {}.{column}", self.table.name()))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "LookupJoin: table={}, on=[{}]",
+            self.table,
+            on.join(", ")
+        )
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        exprs: Vec<Expr>,
+        mut inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        let on = exprs
+            .into_iter()
+            .zip(&self.on)
+            .map(|(key, (_, column))| (key, column.clone()))
+            .collect();
+        let table_schema = DFSchema::try_from_qualified_schema(
+            self.table.name().to_string(),
+            &self.table.provider().schema(),
+        )?;
+        Self::with_keys(inputs.swap_remove(0), self.table.clone(), on, table_schema)
+    }
+}
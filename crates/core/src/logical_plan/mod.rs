@@ -1,16 +1,48 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use datafusion::common::Result;
+use arrow_schema::Schema;
+use datafusion::common::{plan_err, Result};
 
 use datafusion::logical_expr::builder::add_group_by_exprs_from_dependencies;
-use datafusion::logical_expr::expr_rewriter::normalize_cols;
+use datafusion::logical_expr::expr_rewriter::{normalize_col, normalize_cols};
 use datafusion::logical_expr::logical_plan::{Extension, LogicalPlan};
 use datafusion::logical_expr::LogicalPlanBuilder;
 use datafusion::logical_expr::{Aggregate, Expr};
 
+pub mod coalesce;
+pub mod deduplicate;
+pub mod enforce_schema;
+pub mod interval_join;
+pub mod lookup_join;
+pub mod pivot;
+pub mod quality;
+pub mod reorder;
+pub mod sample;
 pub mod streaming_window;
+pub mod tap;
+pub mod unnest;
+pub mod window_table_functions;
+use coalesce::CoalescePlanNode;
+use deduplicate::DeduplicatePlanNode;
+use enforce_schema::SchemaEnforcement;
+use interval_join::{IntervalJoinPlanNode, JoinTimeBound};
+use lookup_join::LookupJoinPlanNode;
+use quality::QualityPlanNode;
+use reorder::ReorderPlanNode;
+use sample::SamplePlanNode;
 use streaming_window::{StreamingWindowPlanNode, StreamingWindowSchema, StreamingWindowType};
+use tap::TapPlanNode;
+
+use crate::physical_plan::continuous::global_window::{Evictor, GlobalWindow, Trigger};
+use crate::physical_plan::continuous::window_assigner::{CustomWindow, WindowAssigner};
+use crate::physical_plan::continuous::{LateDataPolicy, WindowColumns};
+use crate::physical_plan::deduplicate::Deduplication;
+use crate::physical_plan::lookup_join::LookupTable;
+use crate::physical_plan::quality::QualityCheck;
+use crate::physical_plan::sample::SampleMethod;
+use crate::physical_plan::tap::TapSink;
+use crate::physical_plan::utils::time::CalendarInterval;
 
 /// Extend the DataFusion logical plan builder with streaming specific functionality
 pub trait StreamingLogicalPlanBuilder {
@@ -21,6 +53,92 @@ pub trait StreamingLogicalPlanBuilder {
         window_length: Duration,
         slide: Option<Duration>,
     ) -> Result<LogicalPlanBuilder>;
+
+    /// Tumbling windows of calendar days, weeks or months
+    fn calendar_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        interval: CalendarInterval,
+    ) -> Result<LogicalPlanBuilder>;
+
+    /// Windows laid out by a user provided [`WindowAssigner`]
+    fn custom_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        assigner: Arc<dyn WindowAssigner>,
+    ) -> Result<LogicalPlanBuilder>;
+
+    /// Session windows per key, with a gap evaluated for every row in milliseconds or as a
+    /// duration
+    fn session_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        gap: Expr,
+    ) -> Result<LogicalPlanBuilder>;
+
+    /// Windows of every `size` rows per key, firing early once a window has been open for
+    /// `timeout` if given
+    fn count_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        size: usize,
+        timeout: Option<Duration>,
+    ) -> Result<LogicalPlanBuilder>;
+
+    /// A window per key holding all of its rows, emitted whenever `trigger` fires. The
+    /// `evictor` removes rows to bound the window.
+    fn global_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        trigger: Arc<dyn Trigger>,
+        evictor: Option<Arc<dyn Evictor>>,
+    ) -> Result<LogicalPlanBuilder>;
+
+    /// Inner join with `right` on pairs of left and right keys, of rows whose event times are
+    /// within `time_bound`
+    fn interval_join(
+        self,
+        right: LogicalPlan,
+        on: Vec<(Expr, Expr)>,
+        time_bound: JoinTimeBound,
+    ) -> Result<LogicalPlanBuilder>;
+
+    /// Left join with the rows of `table` looked up by the equalities in `on`
+    fn lookup_join(self, table: LookupTable, on: Expr) -> Result<LogicalPlanBuilder>;
+
+    fn tap(self, name: String, sample_rate: f64, sink: TapSink) -> Result<LogicalPlanBuilder>;
+
+    fn coalesce(self, target_rows: usize, max_wait: Duration) -> Result<LogicalPlanBuilder>;
+
+    fn sample(self, method: SampleMethod) -> Result<LogicalPlanBuilder>;
+
+    fn assert_quality(self, checks: Vec<QualityCheck>) -> Result<LogicalPlanBuilder>;
+
+    fn deduplicate(self, deduplication: Deduplication) -> Result<LogicalPlanBuilder>;
+
+    fn reorder(self, delay: Duration) -> Result<LogicalPlanBuilder>;
+
+    fn unpivot(
+        self,
+        columns: &[&str],
+        name_column: &str,
+        value_column: &str,
+    ) -> Result<LogicalPlanBuilder>;
+
+    fn unnest(self, columns: &[&str]) -> Result<LogicalPlanBuilder>;
+
+    fn posexplode(self, column: &str, position_column: &str) -> Result<LogicalPlanBuilder>;
+
+    fn enforce_schema(
+        self,
+        target: &Schema,
+        enforcement: &SchemaEnforcement,
+    ) -> Result<LogicalPlanBuilder>;
 }
 
 // Extend the LogicalPlanBuilder with functions to add streaming operators to the plan
@@ -33,28 +151,238 @@ impl StreamingLogicalPlanBuilder for LogicalPlanBuilder {
         window_length: Duration,
         slide: Option<Duration>,
     ) -> Result<Self> {
-        let group_expr = normalize_cols(group_expr, &self.plan)?;
-        let aggr_expr = normalize_cols(aggr_expr, &self.plan)?;
-
-        let group_expr = add_group_by_exprs_from_dependencies(group_expr, self.plan.schema())?;
         let window: StreamingWindowType = slide.map_or_else(
             || StreamingWindowType::Tumbling(window_length),
             |_slide| StreamingWindowType::Sliding(window_length, _slide),
         );
+        window_plan(self, group_expr, aggr_expr, window)
+    }
+
+    fn calendar_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        interval: CalendarInterval,
+    ) -> Result<Self> {
+        window_plan(
+            self,
+            group_expr,
+            aggr_expr,
+            StreamingWindowType::Calendar(interval),
+        )
+    }
+
+    fn custom_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        assigner: Arc<dyn WindowAssigner>,
+    ) -> Result<Self> {
+        window_plan(
+            self,
+            group_expr,
+            aggr_expr,
+            StreamingWindowType::Custom(CustomWindow(assigner)),
+        )
+    }
+
+    fn session_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        gap: Expr,
+    ) -> Result<Self> {
+        let gap = normalize_col(gap, &self.plan)?;
+        window_plan(
+            self,
+            group_expr,
+            aggr_expr,
+            StreamingWindowType::Session(gap),
+        )
+    }
 
-        let plan = self.plan.clone();
-
-        Aggregate::try_new(Arc::new(self.plan), group_expr, aggr_expr)
-            .map(|new_aggr| {
-                LogicalPlan::Extension(Extension {
-                    node: Arc::new(StreamingWindowPlanNode {
-                        window_type: window,
-                        window_schema: StreamingWindowSchema::try_new(new_aggr.clone()).unwrap(),
-                        aggregrate: new_aggr.clone(),
-                        input: plan,
-                    }),
-                })
+    fn count_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        size: usize,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        if size == 0 {
+            return plan_err!("Count windows need at least one row");
+        }
+        window_plan(
+            self,
+            group_expr,
+            aggr_expr,
+            StreamingWindowType::Count(size, timeout),
+        )
+    }
+
+    fn global_window(
+        self,
+        group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+        trigger: Arc<dyn Trigger>,
+        evictor: Option<Arc<dyn Evictor>>,
+    ) -> Result<Self> {
+        window_plan(
+            self,
+            group_expr,
+            aggr_expr,
+            StreamingWindowType::Global(GlobalWindow { trigger, evictor }),
+        )
+    }
+
+    fn interval_join(
+        self,
+        right: LogicalPlan,
+        on: Vec<(Expr, Expr)>,
+        time_bound: JoinTimeBound,
+    ) -> Result<Self> {
+        let on = on
+            .into_iter()
+            .map(|(left, right_key)| {
+                Ok((
+                    normalize_col(left, &self.plan)?,
+                    normalize_col(right_key, &right)?,
+                ))
             })
-            .map(Self::from)
+            .collect::<Result<Vec<_>>>()?;
+        let time_bound = JoinTimeBound {
+            left_time: normalize_col(time_bound.left_time, &self.plan)?,
+            right_time: normalize_col(time_bound.right_time, &right)?,
+            ..time_bound
+        };
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(IntervalJoinPlanNode::try_new(
+                self.build()?,
+                right,
+                on,
+                time_bound,
+            )?),
+        })))
+    }
+
+    fn lookup_join(self, table: LookupTable, on: Expr) -> Result<Self> {
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(LookupJoinPlanNode::try_new(self.build()?, table, on)?),
+        })))
     }
+
+    /// Sample rows passing through this point of the plan into a debug sink
+    fn tap(self, name: String, sample_rate: f64, sink: TapSink) -> Result<Self> {
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(TapPlanNode {
+                name,
+                sample_rate,
+                sink,
+                input: self.build()?,
+            }),
+        })))
+    }
+
+    /// Merge small batches up to `target_rows`, waiting at most `max_wait` for more rows
+    fn coalesce(self, target_rows: usize, max_wait: Duration) -> Result<Self> {
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(CoalescePlanNode {
+                target_rows,
+                max_wait,
+                input: self.build()?,
+            }),
+        })))
+    }
+
+    /// Pass on a subset of the rows picked by `method`
+    fn sample(self, method: SampleMethod) -> Result<Self> {
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(SamplePlanNode {
+                method,
+                input: self.build()?,
+            }),
+        })))
+    }
+
+    /// Check every row against `checks`, acting on the rows that violate them
+    fn assert_quality(self, checks: Vec<QualityCheck>) -> Result<Self> {
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(QualityPlanNode {
+                checks,
+                input: self.build()?,
+            }),
+        })))
+    }
+
+    /// Drop rows repeating an idempotency key seen before
+    fn deduplicate(self, deduplication: Deduplication) -> Result<Self> {
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(DeduplicatePlanNode {
+                deduplication,
+                input: self.build()?,
+            }),
+        })))
+    }
+
+    /// Sort rows by event time, holding them back for up to `delay`
+    fn reorder(self, delay: Duration) -> Result<Self> {
+        Ok(Self::from(LogicalPlan::Extension(Extension {
+            node: Arc::new(ReorderPlanNode {
+                delay,
+                input: self.build()?,
+            }),
+        })))
+    }
+
+    /// Turn `columns` into one row each, see [`pivot::unpivot_plan`]
+    fn unpivot(self, columns: &[&str], name_column: &str, value_column: &str) -> Result<Self> {
+        pivot::unpivot_plan(self, columns, name_column, value_column)
+    }
+
+    /// Flatten list and map columns, see [`unnest::unnest_plan`]
+    fn unnest(self, columns: &[&str]) -> Result<Self> {
+        unnest::unnest_plan(self, columns)
+    }
+
+    fn posexplode(self, column: &str, position_column: &str) -> Result<Self> {
+        unnest::posexplode_plan(self, column, position_column)
+    }
+
+    /// Match the schema of a sink, see [`enforce_schema::enforce_schema_plan`]
+    fn enforce_schema(self, target: &Schema, enforcement: &SchemaEnforcement) -> Result<Self> {
+        enforce_schema::enforce_schema_plan(self, target, enforcement)
+    }
+}
+
+fn window_plan(
+    builder: LogicalPlanBuilder,
+    group_expr: impl IntoIterator<Item = impl Into<Expr>>,
+    aggr_expr: impl IntoIterator<Item = impl Into<Expr>>,
+    window: StreamingWindowType,
+) -> Result<LogicalPlanBuilder> {
+    let group_expr = normalize_cols(group_expr, &builder.plan)?;
+    let aggr_expr = normalize_cols(aggr_expr, &builder.plan)?;
+
+    let group_expr = add_group_by_exprs_from_dependencies(group_expr, builder.plan.schema())?;
+
+    let plan = builder.plan.clone();
+
+    Aggregate::try_new(Arc::new(builder.plan), group_expr, aggr_expr)
+        .map(|new_aggr| {
+            LogicalPlan::Extension(Extension {
+                node: Arc::new(StreamingWindowPlanNode {
+                    window_type: window,
+                    window_schema: StreamingWindowSchema::try_new(
+                        new_aggr.clone(),
+                        &WindowColumns::default(),
+                    )
+                    .unwrap(),
+                    aggregrate: new_aggr.clone(),
+                    input: plan,
+                    late_data: LateDataPolicy::default(),
+                    emit_schedule: None,
+                    window_columns: WindowColumns::default(),
+                }),
+            })
+        })
+        .map(LogicalPlanBuilder::from)
 }
@@ -0,0 +1,127 @@
+use datafusion::common::{plan_err, Column, Result};
+use datafusion::functions::core::expr_fn::{get_field, named_struct};
+use datafusion::functions_nested::expr_fn::make_array;
+use datafusion::logical_expr::{lit, when, Expr, LogicalPlanBuilder};
+
+const UNPIVOTED_COLUMN: &str = "__unpivoted";
+
+/// Aggregates of a window that pivot rows into columns: one column per entry of
+/// `pivot_values`, holding `aggregate(value)` over the rows whose `pivot_column` equals it.
+///
+/// With rows like `(host, metric, value)`, `pivot_aggregates("metric", &["cpu", "memory"],
+/// col("value"), max)` yields a `cpu` and a `memory` column per host and window. Rows with
+/// other entries are ignored.
+pub fn pivot_aggregates(
+    pivot_column: &str,
+    pivot_values: &[&str],
+    value: Expr,
+    aggregate: impl Fn(Expr) -> Expr,
+) -> Result<Vec<Expr>> {
+    pivot_values
+        .iter()
+        .map(|pivot_value| {
+            let matching = Expr::Column(Column::from_name(pivot_column)).eq(lit(*pivot_value));
+            let value = when(matching, value.clone()).end()?;
+            Ok(aggregate(value).alias(*pivot_value))
+        })
+        .collect()
+}
+
+/// Turn `columns` into rows of `(name_column, value_column)`, keeping every other column.
+/// Like SQL's `UNPIVOT`, null values produce no row.
+pub(crate) fn unpivot_plan(
+    builder: LogicalPlanBuilder,
+    columns: &[&str],
+    name_column: &str,
+    value_column: &str,
+) -> Result<LogicalPlanBuilder> {
+    if columns.is_empty() {
+        return plan_err!("Unpivot needs at least one column");
+    }
+    let kept: Vec<Expr> = builder
+        .schema()
+        .columns()
+        .into_iter()
+        .filter(|column| !columns.contains(&column.name.as_str()))
+        .map(Expr::Column)
+        .collect();
+
+    let entries = columns
+        .iter()
+        .map(|column| {
+            named_struct(vec![
+                lit("name"),
+                lit(*column),
+                lit("value"),
+                Expr::Column(
+                    builder
+                        .schema()
+                        .qualified_field_with_unqualified_name(column)?
+                        .into(),
+                ),
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut unpivoted = kept.clone();
+    unpivoted.push(make_array(entries).alias(UNPIVOTED_COLUMN));
+
+    let unpivoted_column = || Expr::Column(Column::from_name(UNPIVOTED_COLUMN));
+    let mut output = kept;
+    output.push(get_field(unpivoted_column(), "name").alias(name_column));
+    output.push(get_field(unpivoted_column(), "value").alias(value_column));
+
+    builder
+        .project(unpivoted)?
+        .unnest_column(UNPIVOTED_COLUMN)?
+        .project(output)?
+        .filter(Expr::Column(Column::from_name(value_column)).is_not_null())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::functions_aggregate::expr_fn::sum;
+    use datafusion::logical_expr::{col, table_scan};
+
+    #[test]
+    fn pivot_and_unpivot_columns() -> Result<()> {
+        let metrics = Schema::new(vec![
+            Field::new("host", DataType::Utf8, false),
+            Field::new("metric", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, true),
+        ]);
+        let pivoted = table_scan(Some("metrics"), &metrics, None)?
+            .aggregate(
+                vec![col("host")],
+                pivot_aggregates("metric", &["cpu", "memory"], col("value"), sum)?,
+            )?
+            .build()?;
+        let names: Vec<&String> = pivoted.schema().fields().iter().map(|f| f.name()).collect();
+        assert_eq!(names, ["host", "cpu", "memory"]);
+
+        let unpivoted = unpivot_plan(
+            LogicalPlanBuilder::from(pivoted),
+            &["cpu", "memory"],
+            "metric",
+            "value",
+        )?
+        .build()?;
+        let fields: Vec<(&String, &DataType)> = unpivoted
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| (f.name(), f.data_type()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (&"host".to_string(), &DataType::Utf8),
+                (&"metric".to_string(), &DataType::Utf8),
+                (&"value".to_string(), &DataType::Float64),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt::{self, Debug};
+
+use datafusion::common::{DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+
+use crate::physical_plan::quality::QualityCheck;
+
+#[derive(PartialEq, Eq, Hash)]
+pub struct QualityPlanNode {
+    pub checks: Vec<QualityCheck>,
+    pub input: LogicalPlan,
+}
+
+impl Debug for QualityPlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        UserDefinedLogicalNodeCore::fmt_for_explain(self, f)
+    }
+}
+
+impl UserDefinedLogicalNodeCore for QualityPlanNode {
+    fn name(&self) -> &str {
+        "AssertQuality"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.input.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let checks: Vec<String> = self
+            .checks
+            .iter()
+            .map(|check| format!("{}={}", check.name, check.rule))
+            .collect();
+        write!(f, "AssertQuality: checks=[{}]", checks.join(", "))
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        _exprs: Vec<Expr>,
+        mut inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        Ok(Self {
+            checks: self.checks.clone(),
+            input: inputs.swap_remove(0),
+        })
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt::{self, Debug};
+use std::time::Duration;
+
+use datafusion::common::{DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+
+#[derive(PartialEq, Eq, Hash)]
+pub struct ReorderPlanNode {
+    pub delay: Duration,
+    pub input: LogicalPlan,
+}
+
+impl Debug for ReorderPlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        UserDefinedLogicalNodeCore::fmt_for_explain(self, f)
+    }
+}
+
+impl UserDefinedLogicalNodeCore for ReorderPlanNode {
+    fn name(&self) -> &str {
+        "Reorder"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.input.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Reorder: delay={:?}", self.delay)
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        _exprs: Vec<Expr>,
+        mut inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        Ok(Self {
+            delay: self.delay,
+            input: inputs.swap_remove(0),
+        })
+    }
+}
@@ -4,19 +4,31 @@ use std::fmt;
 use std::sync::Arc;
 use std::time::Duration;
 
-use arrow::datatypes::{DataType, Field, SchemaBuilder, TimeUnit};
+use arrow::datatypes::SchemaBuilder;
 
 use datafusion::common::{DFSchema, DFSchemaRef, Result};
 use datafusion::logical_expr::{Aggregate, Expr};
 use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNodeCore};
 
+use crate::physical_plan::continuous::global_window::GlobalWindow;
+use crate::physical_plan::continuous::window_assigner::CustomWindow;
+use crate::physical_plan::continuous::{LateDataPolicy, WindowColumns};
+use crate::physical_plan::utils::cron::CronSchedule;
+use crate::physical_plan::utils::time::CalendarInterval;
+
 //TODO: Avoid use of Aggregate here as we need to clone the internal expressions back and forth.
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct StreamingWindowPlanNode {
     pub window_type: StreamingWindowType,
     pub window_schema: StreamingWindowSchema,
     pub aggregrate: Aggregate,
     pub input: LogicalPlan,
+    /// What happens to rows arriving after their window closed
+    pub late_data: LateDataPolicy,
+    /// Emit the current results of open windows whenever the schedule fires
+    pub emit_schedule: Option<CronSchedule>,
+    /// Metadata columns appended to the results, part of `window_schema`
+    pub window_columns: WindowColumns,
 }
 
 impl Debug for StreamingWindowPlanNode {
@@ -39,7 +51,11 @@ impl UserDefinedLogicalNodeCore for StreamingWindowPlanNode {
     }
 
     fn expressions(&self) -> Vec<Expr> {
-        self.aggregrate.aggr_expr.clone()
+        let mut exprs = self.aggregrate.aggr_expr.clone();
+        if let StreamingWindowType::Session(gap) = &self.window_type {
+            exprs.push(gap.clone());
+        }
+        exprs
     }
 
     fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -48,9 +64,13 @@ impl UserDefinedLogicalNodeCore for StreamingWindowPlanNode {
 
     fn with_exprs_and_inputs(
         &self,
-        exprs: Vec<Expr>,
+        mut exprs: Vec<Expr>,
         mut inputs: Vec<LogicalPlan>,
     ) -> Result<Self> {
+        let window_type = match &self.window_type {
+            StreamingWindowType::Session(_) => StreamingWindowType::Session(exprs.pop().unwrap()),
+            _ => self.window_type.clone(),
+        };
         let input = inputs.swap_remove(0);
         let new_aggregation = Aggregate::try_new(
             Arc::new(input.clone()),
@@ -58,10 +78,13 @@ impl UserDefinedLogicalNodeCore for StreamingWindowPlanNode {
             exprs,
         )?;
         Ok(Self {
-            window_type: self.window_type.clone(),
+            window_type,
             window_schema: self.window_schema.clone(),
             aggregrate: new_aggregation,
             input,
+            late_data: self.late_data.clone(),
+            emit_schedule: self.emit_schedule.clone(),
+            window_columns: self.window_columns.clone(),
         })
     }
 }
@@ -70,7 +93,17 @@ impl UserDefinedLogicalNodeCore for StreamingWindowPlanNode {
 pub enum StreamingWindowType {
     Tumbling(Duration),
     Sliding(Duration, Duration),
-    Session(Duration, String),
+    /// Sessions of each key end once no row arrived for the gap, which is evaluated for
+    /// every row and given in milliseconds or as a duration
+    Session(Expr),
+    /// Tumbling windows of calendar days, weeks or months
+    Calendar(CalendarInterval),
+    /// Windows of a user provided [`WindowAssigner`](crate::physical_plan::continuous::window_assigner::WindowAssigner)
+    Custom(CustomWindow),
+    /// Every so many rows per key, or once the window has been open for the timeout
+    Count(usize, Option<Duration>),
+    /// One window per key, emitted when its trigger fires
+    Global(GlobalWindow),
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -81,7 +114,7 @@ pub struct StreamingWindowSchema {
 }
 
 impl StreamingWindowSchema {
-    pub fn try_new(aggr_expr: Aggregate) -> Result<Self> {
+    pub fn try_new(aggr_expr: Aggregate, window_columns: &WindowColumns) -> Result<Self> {
         let inner_schema = aggr_expr.schema.inner().clone();
         let fields = inner_schema.flattened_fields().to_owned();
 
@@ -90,16 +123,9 @@ impl StreamingWindowSchema {
         for field in fields {
             builder.push(field.clone());
         }
-        builder.push(Field::new(
-            "window_start_time",
-            DataType::Timestamp(TimeUnit::Millisecond, None),
-            false,
-        ));
-        builder.push(Field::new(
-            "window_end_time",
-            DataType::Timestamp(TimeUnit::Millisecond, None),
-            false,
-        ));
+        for field in window_columns.fields() {
+            builder.push(field);
+        }
         let schema_with_window_columns = DFSchema::try_from(builder.finish())?;
         Ok(StreamingWindowSchema {
             schema: Arc::new(schema_with_window_columns),
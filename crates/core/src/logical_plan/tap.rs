@@ -0,0 +1,79 @@
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+
+use datafusion::common::{DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+
+use crate::physical_plan::tap::TapSink;
+
+pub struct TapPlanNode {
+    pub name: String,
+    pub sample_rate: f64,
+    pub sink: TapSink,
+    pub input: LogicalPlan,
+}
+
+impl PartialEq for TapPlanNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.sample_rate.to_bits() == other.sample_rate.to_bits()
+            && self.sink == other.sink
+            && self.input == other.input
+    }
+}
+
+impl Eq for TapPlanNode {}
+
+impl Hash for TapPlanNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.sample_rate.to_bits().hash(state);
+        self.sink.hash(state);
+        self.input.hash(state);
+    }
+}
+
+impl Debug for TapPlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        UserDefinedLogicalNodeCore::fmt_for_explain(self, f)
+    }
+}
+
+impl UserDefinedLogicalNodeCore for TapPlanNode {
+    fn name(&self) -> &str {
+        "Tap"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.input.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Tap: name={}, sample_rate={}",
+            self.name, self.sample_rate
+        )
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        _exprs: Vec<Expr>,
+        mut inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        Ok(Self {
+            name: self.name.clone(),
+            sample_rate: self.sample_rate,
+            sink: self.sink.clone(),
+            input: inputs.swap_remove(0),
+        })
+    }
+}
@@ -0,0 +1,217 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow::array::AsArray;
+use arrow_array::ListArray;
+use arrow_schema::DataType;
+use datafusion::common::{plan_err, Column, Result, UnnestOptions};
+use datafusion::functions::core::expr_fn::coalesce;
+use datafusion::functions_nested::expr_fn::{array_length, range};
+use datafusion::logical_expr::{
+    cast, lit, ColumnarValue, Expr, LogicalPlanBuilder, ScalarUDF, ScalarUDFImpl, Signature,
+    Volatility,
+};
+
+use crate::physical_plan::utils::metadata::STREAMING_METADATA_COLUMN;
+
+/// Flatten list and map columns into a row per element, repeating the other columns.
+/// Columns unnested together are zipped, the shorter ones padded with nulls. Map entries
+/// become structs of their key and value.
+///
+/// The stream metadata is repeated along with the other columns, so every element keeps the
+/// event time of the row it came from.
+pub(crate) fn unnest_plan(
+    builder: LogicalPlanBuilder,
+    columns: &[&str],
+) -> Result<LogicalPlanBuilder> {
+    if columns.contains(&STREAMING_METADATA_COLUMN) {
+        return plan_err!("{STREAMING_METADATA_COLUMN} can't be unnested");
+    }
+    let columns: Vec<Column> = columns
+        .iter()
+        .map(|column| Column::from_name(*column))
+        .collect();
+    map_entries_as_lists(builder, &columns)?
+        .unnest_columns_with_options(columns, UnnestOptions::default())
+}
+
+/// Like [`unnest_plan`] for a single column, adding the position of every element within its
+/// list, starting at 0
+pub(crate) fn posexplode_plan(
+    builder: LogicalPlanBuilder,
+    column: &str,
+    position_column: &str,
+) -> Result<LogicalPlanBuilder> {
+    if column == STREAMING_METADATA_COLUMN {
+        return plan_err!("{STREAMING_METADATA_COLUMN} can't be unnested");
+    }
+    let builder = map_entries_as_lists(builder, &[Column::from_name(column)])?;
+
+    let length = cast(
+        array_length(Expr::Column(Column::from_name(column))),
+        DataType::Int64,
+    );
+    let positions = range(lit(0_i64), coalesce(vec![length, lit(0_i64)]), lit(1_i64));
+    let mut projection: Vec<Expr> = builder
+        .schema()
+        .columns()
+        .into_iter()
+        .map(Expr::Column)
+        .collect();
+    projection.push(positions.alias(position_column));
+
+    builder.project(projection)?.unnest_columns_with_options(
+        vec![
+            Column::from_name(column),
+            Column::from_name(position_column),
+        ],
+        UnnestOptions::default(),
+    )
+}
+
+/// Replace the map columns among `columns` with lists of their entries, which DataFusion
+/// can unnest
+fn map_entries_as_lists(
+    builder: LogicalPlanBuilder,
+    columns: &[Column],
+) -> Result<LogicalPlanBuilder> {
+    let schema = Arc::clone(builder.schema());
+    let is_map = |column: &Column| {
+        columns.iter().any(|unnested| unnested.name == column.name)
+            && matches!(
+                schema
+                    .field_from_column(column)
+                    .map(|field| field.data_type()),
+                Ok(DataType::Map(..))
+            )
+    };
+    if !schema.columns().iter().any(is_map) {
+        return Ok(builder);
+    }
+
+    let map_entries = ScalarUDF::from(MapEntries::new());
+    let projection: Vec<Expr> = schema
+        .columns()
+        .into_iter()
+        .map(|column| match is_map(&column) {
+            true => {
+                let name = column.name.clone();
+                map_entries.call(vec![Expr::Column(column)]).alias(name)
+            }
+            false => Expr::Column(column),
+        })
+        .collect();
+    builder.project(projection)
+}
+
+/// The entries of a map as a list of key and value structs
+#[derive(Debug)]
+struct MapEntries {
+    signature: Signature,
+}
+
+impl MapEntries {
+    fn new() -> Self {
+        Self {
+            signature: Signature::any(1, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for MapEntries {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "map_entries"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        match &arg_types[0] {
+            DataType::Map(entries, _) => Ok(DataType::List(Arc::clone(entries))),
+            other => plan_err!("map_entries expects a map, got {other}"),
+        }
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let map = arrays[0].as_map();
+        let DataType::Map(entries, _) = map.data_type() else {
+            unreachable!()
+        };
+        let list = ListArray::new(
+            Arc::clone(entries),
+            map.offsets().clone(),
+            Arc::new(map.entries().clone()),
+            map.nulls().cloned(),
+        );
+        Ok(ColumnarValue::Array(Arc::new(list)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_schema::{Field, Fields, Schema};
+    use datafusion::logical_expr::table_scan;
+
+    #[test]
+    fn unnest_lists_and_maps() -> Result<()> {
+        let entries = Field::new(
+            "entries",
+            DataType::Struct(Fields::from(vec![
+                Field::new("key", DataType::Utf8, false),
+                Field::new("value", DataType::Int64, true),
+            ])),
+            false,
+        );
+        let events = Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new_list("items", Field::new("item", DataType::Int64, true), true),
+            Field::new(
+                "tags",
+                DataType::Map(Arc::new(entries.clone()), false),
+                true,
+            ),
+        ]);
+        let field_types = |builder: LogicalPlanBuilder| -> Result<Vec<(String, DataType)>> {
+            Ok(builder
+                .build()?
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| (field.name().clone(), field.data_type().clone()))
+                .collect())
+        };
+
+        let unnested = unnest_plan(table_scan(Some("events"), &events, None)?, &["tags"])?;
+        assert_eq!(
+            field_types(unnested)?[2],
+            ("tags".to_string(), entries.data_type().clone())
+        );
+
+        let exploded = posexplode_plan(table_scan(Some("events"), &events, None)?, "items", "pos")?;
+        assert_eq!(
+            field_types(exploded)?,
+            [
+                ("id".to_string(), DataType::Utf8),
+                ("items".to_string(), DataType::Int64),
+                ("tags".to_string(), events.field(2).data_type().clone()),
+                ("pos".to_string(), DataType::Int64),
+            ]
+        );
+
+        assert!(unnest_plan(
+            table_scan(Some("events"), &events, None)?,
+            &[STREAMING_METADATA_COLUMN]
+        )
+        .is_err());
+        Ok(())
+    }
+}
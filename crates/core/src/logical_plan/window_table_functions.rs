@@ -0,0 +1,355 @@
+use std::any::Any;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_schema::{Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::catalog::{CatalogProviderList, Session};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{plan_err, Column, Result, ScalarValue, TableReference};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::{provider_as_source, DefaultTableSource, TableProvider};
+use datafusion::execution::context::SessionContext;
+use datafusion::logical_expr::{
+    lit, Expr, Filter, LogicalPlan, LogicalPlanBuilder, Projection, SubqueryAlias, TableScan,
+    TableType,
+};
+use datafusion::physical_plan::ExecutionPlan;
+
+use super::streaming_window::StreamingWindowType;
+use super::window_plan;
+use crate::physical_plan::continuous::WindowColumns;
+use crate::physical_plan::utils::time::interval_millis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WindowFunction {
+    Tumble,
+    Hop,
+    Session,
+}
+
+impl WindowFunction {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Tumble => "tumble",
+            Self::Hop => "hop",
+            Self::Session => "session",
+        }
+    }
+}
+
+struct WindowTableFunction {
+    function: WindowFunction,
+    catalogs: Arc<dyn CatalogProviderList>,
+    default_catalog: String,
+    default_schema: String,
+}
+
+/// Register the window table functions with `context`. They take the name of a registered stream
+/// and lay its rows out in windows of their event time:
+///
+/// - `tumble('orders', INTERVAL '1 minute')`
+/// - `hop('orders', INTERVAL '10 seconds', INTERVAL '1 minute')`, a window of a minute every
+///   ten seconds, slide before size as in Flink
+/// - `session('orders', INTERVAL '30 seconds')`, sessions per group that end after thirty
+///   seconds without rows
+///
+/// The result adds `window_start_time` and `window_end_time` to the columns of the stream and
+/// has to be aggregated with a `GROUP BY`, which is planned as a streaming window.
+pub fn register_window_table_functions(context: &SessionContext) {
+    let state = context.state();
+    let options = &state.config_options().catalog;
+    for function in [
+        WindowFunction::Tumble,
+        WindowFunction::Hop,
+        WindowFunction::Session,
+    ] {
+        context.register_udtf(
+            function.name(),
+            Arc::new(WindowTableFunction {
+                function,
+                catalogs: state.catalog_list().clone(),
+                default_catalog: options.default_catalog.clone(),
+                default_schema: options.default_schema.clone(),
+            }),
+        );
+    }
+}
+
+impl WindowTableFunction {
+    fn source(&self, table: &str) -> Result<Arc<dyn TableProvider>> {
+        let table =
+            TableReference::from(table).resolve(&self.default_catalog, &self.default_schema);
+        let schema = self
+            .catalogs
+            .catalog(&table.catalog)
+            .and_then(|catalog| catalog.schema(&table.schema));
+        // Registered streams live in memory, looking them up never has to wait
+        let source = match schema {
+            Some(schema) => futures::executor::block_on(schema.table(&table.table))?,
+            None => None,
+        };
+        match source {
+            Some(source) => Ok(source),
+            None => plan_err!("{}: no stream named {table}", self.function.name()),
+        }
+    }
+
+    fn duration(&self, args: &[Expr], index: usize, name: &str) -> Result<Duration> {
+        let millis = match args.get(index) {
+            Some(Expr::Literal(value)) => interval_millis(value),
+            _ => None,
+        };
+        match millis {
+            Some(millis) if millis > 0 => Ok(Duration::from_millis(millis as u64)),
+            Some(_) => plan_err!("{} needs a positive {name}", self.function.name()),
+            None => plan_err!(
+                "{} expects its {name} as an interval of days or less, e.g. INTERVAL '10 seconds'",
+                self.function.name()
+            ),
+        }
+    }
+}
+
+impl TableFunctionImpl for WindowTableFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let (table, arity) = match (args.first(), self.function) {
+            (Some(Expr::Literal(ScalarValue::Utf8(Some(table)))), WindowFunction::Hop) => {
+                (table, 3)
+            }
+            (Some(Expr::Literal(ScalarValue::Utf8(Some(table)))), _) => (table, 2),
+            _ => {
+                return plan_err!(
+                    "{} expects the name of a stream as its first argument",
+                    self.function.name()
+                )
+            }
+        };
+        if args.len() != arity {
+            return plan_err!(
+                "{} takes {arity} arguments but got {}",
+                self.function.name(),
+                args.len()
+            );
+        }
+
+        let window = match self.function {
+            WindowFunction::Tumble => {
+                StreamingWindowType::Tumbling(self.duration(args, 1, "size")?)
+            }
+            WindowFunction::Hop => StreamingWindowType::Sliding(
+                self.duration(args, 2, "size")?,
+                self.duration(args, 1, "slide")?,
+            ),
+            WindowFunction::Session => {
+                let gap = self.duration(args, 1, "gap")?;
+                StreamingWindowType::Session(lit(ScalarValue::DurationMillisecond(Some(
+                    gap.as_millis() as i64,
+                ))))
+            }
+        };
+        let source = self.source(table)?;
+        let mut fields = source.schema().fields().to_vec();
+        fields.extend(WindowColumns::default().fields().into_iter().map(Arc::new));
+
+        Ok(Arc::new(WindowedTable {
+            source,
+            window,
+            schema: Arc::new(Schema::new(fields)),
+        }))
+    }
+}
+
+/// A stream laid out in windows by a window table function. It only stands in for the
+/// stream until [`plan_window_table_functions`] replaces the aggregation over it with a
+/// streaming window.
+struct WindowedTable {
+    source: Arc<dyn TableProvider>,
+    window: StreamingWindowType,
+    schema: SchemaRef,
+}
+
+#[async_trait]
+impl TableProvider for WindowedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.source.table_type()
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        _projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        plan_err!("Window table functions have to be aggregated with a GROUP BY")
+    }
+}
+
+/// Plan the aggregations over window table functions in `plan` as streaming windows. The
+/// window columns are dropped from the grouping, the window adds them back to its results.
+pub fn plan_window_table_functions(plan: LogicalPlan) -> Result<LogicalPlan> {
+    let window_columns = WindowColumns::default();
+    let is_window_column = |expr: &Expr| match expr {
+        Expr::Column(column) => [&window_columns.start, &window_columns.end]
+            .into_iter()
+            .any(|name| name.as_deref() == Some(column.name.as_str())),
+        _ => false,
+    };
+
+    plan.transform_up(|plan| {
+        let LogicalPlan::Aggregate(aggregate) = &plan else {
+            return Ok(Transformed::no(plan));
+        };
+        let Some((input, window)) = unwrap_windowed_table(&aggregate.input)? else {
+            return Ok(Transformed::no(plan));
+        };
+
+        let group_expr = aggregate
+            .group_expr
+            .iter()
+            .filter(|expr| !is_window_column(expr))
+            .cloned()
+            .collect::<Vec<_>>();
+        let windowed = window_plan(
+            LogicalPlanBuilder::from(input),
+            group_expr,
+            aggregate.aggr_expr.clone(),
+            window,
+        )?
+        .build()?;
+
+        // Restore the columns of the aggregation as the plan above it refers to them
+        let columns = aggregate
+            .schema
+            .iter()
+            .map(|(qualifier, field)| {
+                let column = Expr::Column(Column::new_unqualified(field.name()));
+                match qualifier {
+                    Some(qualifier) => {
+                        column.alias_qualified(Some(qualifier.clone()), field.name())
+                    }
+                    None => column,
+                }
+            })
+            .collect();
+        let projection = Projection::try_new(columns, Arc::new(windowed))?;
+        Ok(Transformed::yes(LogicalPlan::Projection(projection)))
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// The input of an aggregation with the window table function in it replaced by the stream
+/// it windows, and the window
+fn unwrap_windowed_table(plan: &LogicalPlan) -> Result<Option<(LogicalPlan, StreamingWindowType)>> {
+    let unwrapped = match plan {
+        LogicalPlan::TableScan(scan) => {
+            let Some(windowed) = scan
+                .source
+                .as_any()
+                .downcast_ref::<DefaultTableSource>()
+                .and_then(|source| {
+                    source
+                        .table_provider
+                        .as_any()
+                        .downcast_ref::<WindowedTable>()
+                })
+            else {
+                return Ok(None);
+            };
+            let source_fields = windowed.source.schema().fields().len();
+            let projection = scan.projection.as_ref().map(|projection| {
+                projection
+                    .iter()
+                    .copied()
+                    .filter(|index| *index < source_fields)
+                    .collect()
+            });
+            let scan = TableScan::try_new(
+                scan.table_name.clone(),
+                provider_as_source(windowed.source.clone()),
+                projection,
+                scan.filters.clone(),
+                scan.fetch,
+            )?;
+            (LogicalPlan::TableScan(scan), windowed.window.clone())
+        }
+        LogicalPlan::Filter(filter) => match unwrap_windowed_table(&filter.input)? {
+            Some((input, window)) => (
+                LogicalPlan::Filter(Filter::try_new(filter.predicate.clone(), Arc::new(input))?),
+                window,
+            ),
+            None => return Ok(None),
+        },
+        LogicalPlan::SubqueryAlias(alias) => match unwrap_windowed_table(&alias.input)? {
+            Some((input, window)) => (
+                LogicalPlan::SubqueryAlias(SubqueryAlias::try_new(
+                    Arc::new(input),
+                    alias.alias.clone(),
+                )?),
+                window,
+            ),
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(unwrapped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_schema::{DataType, Field};
+    use datafusion::datasource::MemTable;
+    use datafusion::logical_expr::Extension;
+
+    use crate::logical_plan::streaming_window::StreamingWindowPlanNode;
+
+    #[tokio::test]
+    async fn plan_hop_as_sliding_window() -> Result<()> {
+        let context = SessionContext::new();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("customer", DataType::Utf8, false),
+            Field::new("amount", DataType::Int64, false),
+        ]));
+        context.register_table("orders", Arc::new(MemTable::try_new(schema, vec![vec![]])?))?;
+        register_window_table_functions(&context);
+
+        let plan = context
+            .state()
+            .create_logical_plan(
+                "SELECT customer, window_start_time, sum(amount) AS total \
+                 FROM hop('orders', INTERVAL '10 seconds', INTERVAL '1 minute') \
+                 GROUP BY customer, window_start_time",
+            )
+            .await?;
+        let plan = plan_window_table_functions(plan)?;
+
+        let mut windows = vec![];
+        plan.apply(|plan| {
+            if let LogicalPlan::Extension(Extension { node }) = plan {
+                if let Some(window) = node.as_any().downcast_ref::<StreamingWindowPlanNode>() {
+                    windows.push(window.window_type.clone());
+                }
+            }
+            Ok(datafusion::common::tree_node::TreeNodeRecursion::Continue)
+        })?;
+        assert_eq!(
+            windows,
+            [StreamingWindowType::Sliding(
+                Duration::from_secs(60),
+                Duration::from_secs(10)
+            )]
+        );
+        Ok(())
+    }
+}
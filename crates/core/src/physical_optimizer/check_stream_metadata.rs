@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use datafusion::common::config::ConfigOptions;
+use datafusion::common::plan_err;
+use datafusion::common::tree_node::{Transformed, TransformedResult, TreeNode};
+use datafusion::error::Result;
+use datafusion::physical_expr::Partitioning;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::repartition::RepartitionExec;
+use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
+use datafusion::physical_plan::ExecutionPlan;
+
+use super::fuse_stateless_operators::as_step;
+use crate::physical_plan::continuous::streaming_window::FranzStreamingWindowExec;
+use crate::physical_plan::fused::FusedStatelessExec;
+use crate::physical_plan::streaming_repartition::StreamingRepartitionExec;
+use crate::physical_plan::utils::metadata::{has_stream_metadata, STREAMING_METADATA_COLUMN};
+
+/// Fails planning when an operator that relies on event time is fed by an operator that
+/// dropped the stream metadata column, instead of failing at runtime on the first batch.
+///
+/// Watermarks and barriers also travel in control batches without rows, see
+/// [`control_batch`](crate::physical_plan::utils::stream_message::control_batch). DataFusion
+/// operators on a stream that would drop or reorder them are replaced with ones that pass
+/// them on: filters and projections with a [`FusedStatelessExec`], repartitions and merges
+/// with a [`StreamingRepartitionExec`]. Planning fails for the operators that can't be
+/// replaced.
+#[derive(Default)]
+pub struct CheckStreamMetadata {}
+
+impl CheckStreamMetadata {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+/// Operators that read event time from the stream metadata column of their input
+fn requires_stream_metadata(plan: &Arc<dyn ExecutionPlan>) -> bool {
+    plan.as_any().is::<FranzStreamingWindowExec>()
+}
+
+/// Find the operator that dropped the metadata column, i.e. the first operator without it
+/// whose input still had it.
+fn find_swallowing_operator(plan: &Arc<dyn ExecutionPlan>) -> Option<String> {
+    let children = plan.children();
+    if !has_stream_metadata(&plan.schema())
+        && children
+            .iter()
+            .any(|child| has_stream_metadata(&child.schema()))
+    {
+        return Some(plan.name().to_string());
+    }
+    children.into_iter().find_map(find_swallowing_operator)
+}
+
+/// The operator passing control batches on in place of `plan`, if `plan` reads a stream
+fn forward_control(plan: Arc<dyn ExecutionPlan>) -> Result<Transformed<Arc<dyn ExecutionPlan>>> {
+    let streaming = plan
+        .children()
+        .iter()
+        .any(|child| has_stream_metadata(&child.schema()));
+    if !streaming {
+        return Ok(Transformed::no(plan));
+    }
+
+    let any = plan.as_any();
+    if any.is::<FilterExec>() || any.is::<ProjectionExec>() {
+        let Some((step, input)) = as_step(&plan) else {
+            return plan_err!(
+                "{} with an embedded projection would drop the watermarks and barriers of the stream",
+                plan.name()
+            );
+        };
+        let properties = plan.properties().clone();
+        return Ok(Transformed::yes(Arc::new(FusedStatelessExec::new(
+            input,
+            vec![step],
+            properties,
+        ))));
+    }
+    if let Some(repartition) = any.downcast_ref::<RepartitionExec>() {
+        return Ok(Transformed::yes(Arc::new(
+            StreamingRepartitionExec::try_new(
+                repartition.input().clone(),
+                repartition.partitioning().clone(),
+            )?,
+        )));
+    }
+    if let Some(coalesce) = any.downcast_ref::<CoalescePartitionsExec>() {
+        return Ok(Transformed::yes(Arc::new(
+            StreamingRepartitionExec::try_new(
+                coalesce.input().clone(),
+                Partitioning::RoundRobinBatch(1),
+            )?,
+        )));
+    }
+    if any.is::<CoalesceBatchesExec>() || any.is::<SortPreservingMergeExec>() {
+        return plan_err!(
+            "{} would drop the watermarks and barriers of the stream",
+            plan.name()
+        );
+    }
+    Ok(Transformed::no(plan))
+}
+
+fn check(plan: &Arc<dyn ExecutionPlan>) -> Result<()> {
+    if requires_stream_metadata(plan) {
+        for child in plan.children() {
+            if has_stream_metadata(&child.schema()) {
+                continue;
+            }
+            return match find_swallowing_operator(child) {
+                Some(operator) => plan_err!(
+                    "{operator} drops the {STREAMING_METADATA_COLUMN} column required by {}; keep the column in the operator's output",
+                    plan.name()
+                ),
+                None => plan_err!(
+                    "{} requires a streaming source but its input has no {STREAMING_METADATA_COLUMN} column",
+                    plan.name()
+                ),
+            };
+        }
+    }
+    plan.children().into_iter().try_for_each(check)
+}
+
+impl PhysicalOptimizerRule for CheckStreamMetadata {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        check(&plan)?;
+        plan.transform_up(forward_control).data()
+    }
+
+    fn name(&self) -> &str {
+        "check_stream_metadata"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use datafusion::common::config::ConfigOptions;
+use datafusion::common::tree_node::{Transformed, TransformedResult, TreeNode};
+use datafusion::error::Result;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::joins::{
+    CrossJoinExec, HashJoinExec, NestedLoopJoinExec, SortMergeJoinExec, SymmetricHashJoinExec,
+};
+use datafusion::physical_plan::ExecutionPlan;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::physical_plan::coalesce::StreamingCoalesceExec;
+use crate::physical_plan::interval_join::IntervalJoinExec;
+
+/// Inserts a [`StreamingCoalesceExec`] in front of both inputs of every join when
+/// `coalesce_target_rows` is configured. Joins pay a fixed cost per probe batch, so feeding
+/// them a trickle of tiny batches is much slower than feeding them a few larger ones.
+#[derive(Default)]
+pub struct CoalesceBeforeJoin {}
+
+impl CoalesceBeforeJoin {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+fn is_join(plan: &Arc<dyn ExecutionPlan>) -> bool {
+    let any = plan.as_any();
+    any.is::<HashJoinExec>()
+        || any.is::<SymmetricHashJoinExec>()
+        || any.is::<NestedLoopJoinExec>()
+        || any.is::<CrossJoinExec>()
+        || any.is::<SortMergeJoinExec>()
+        || any.is::<IntervalJoinExec>()
+}
+
+impl PhysicalOptimizerRule for CoalesceBeforeJoin {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let Some(denormalized_config) = config.extensions.get::<DenormalizedConfig>() else {
+            return Ok(plan);
+        };
+        let target_rows = denormalized_config.coalesce_target_rows;
+        if target_rows == 0 {
+            return Ok(plan);
+        }
+        let max_wait = Duration::from_millis(denormalized_config.coalesce_max_wait_ms);
+
+        plan.transform_up(|plan| {
+            if !is_join(&plan) {
+                return Ok(Transformed::no(plan));
+            }
+            let children = plan
+                .children()
+                .into_iter()
+                .map(|child| {
+                    if child.as_any().is::<StreamingCoalesceExec>() {
+                        child.clone()
+                    } else {
+                        Arc::new(StreamingCoalesceExec::new(
+                            child.clone(),
+                            target_rows,
+                            max_wait,
+                        )) as Arc<dyn ExecutionPlan>
+                    }
+                })
+                .collect();
+            Ok(Transformed::yes(plan.with_new_children(children)?))
+        })
+        .data()
+    }
+
+    fn name(&self) -> &str {
+        "coalesce_before_join"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
@@ -52,17 +52,10 @@ impl PhysicalOptimizerRule for CoaslesceBeforeStreamingAggregate {
                     input.clone(),
                     Partitioning::RoundRobinBatch(1),
                 )?);
-                Ok(Transformed::yes(Arc::new(
-                    FranzStreamingWindowExec::try_new(
-                        streaming_aggr_exec.mode,
-                        streaming_aggr_exec.group_by.clone(),
-                        streaming_aggr_exec.aggregate_expressions.clone(),
-                        streaming_aggr_exec.filter_expressions.clone(),
-                        coalesce_exec.clone(),
-                        input.schema(),
-                        streaming_aggr_exec.window_type,
-                    )?,
-                )))
+                // Rebuilding through the plan keeps the window's settings beyond its inputs
+                Ok(Transformed::yes(
+                    Arc::clone(&original).with_new_children(vec![coalesce_exec])?,
+                ))
             } else {
                 Ok(Transformed::no(original))
             }
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use datafusion::common::config::ConfigOptions;
+use datafusion::error::Result;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::ExecutionPlan;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::physical_plan::fused::{FusedStatelessExec, FusedStep};
+
+/// Collapses chains of two or more filters and projections into a single
+/// [`FusedStatelessExec`].
+#[derive(Default)]
+pub struct FuseStatelessOperators {}
+
+impl FuseStatelessOperators {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PhysicalOptimizerRule for FuseStatelessOperators {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let enabled = config
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(true, |c| c.fuse_operators);
+        if !enabled {
+            return Ok(plan);
+        }
+        fuse(plan)
+    }
+
+    fn name(&self) -> &str {
+        "fuse_stateless_operators"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+/// The step `plan` is as part of a [`FusedStatelessExec`], and its input
+pub(crate) fn as_step(
+    plan: &Arc<dyn ExecutionPlan>,
+) -> Option<(FusedStep, Arc<dyn ExecutionPlan>)> {
+    if let Some(filter) = plan.as_any().downcast_ref::<FilterExec>() {
+        // Filters with an embedded projection change the schema, leave those alone
+        if filter.schema() != filter.input().schema() {
+            return None;
+        }
+        return Some((
+            FusedStep::Filter(filter.predicate().clone()),
+            filter.input().clone(),
+        ));
+    }
+    if let Some(projection) = plan.as_any().downcast_ref::<ProjectionExec>() {
+        return Some((
+            FusedStep::Projection {
+                exprs: projection.expr().to_vec(),
+                schema: projection.schema(),
+            },
+            projection.input().clone(),
+        ));
+    }
+    None
+}
+
+fn fuse(plan: Arc<dyn ExecutionPlan>) -> Result<Arc<dyn ExecutionPlan>> {
+    let mut steps = vec![];
+    let mut current = plan.clone();
+    while let Some((step, input)) = as_step(&current) {
+        steps.push(step);
+        current = input;
+    }
+
+    if steps.len() >= 2 {
+        steps.reverse();
+        let input = fuse(current)?;
+        return Ok(Arc::new(FusedStatelessExec::new(
+            input,
+            steps,
+            plan.properties().clone(),
+        )));
+    }
+
+    let children = plan
+        .children()
+        .into_iter()
+        .map(|child| fuse(child.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    if children.is_empty() {
+        Ok(plan)
+    } else {
+        plan.with_new_children(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::common::ScalarValue;
+    use datafusion::execution::TaskContext;
+    use datafusion::logical_expr::Operator;
+    use datafusion::physical_expr::expressions::{binary, col, lit};
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    #[tokio::test]
+    async fn fuse_filters_and_projections() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, false),
+            Field::new("b", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                Arc::new(Int64Array::from(vec![10, 20, 30, 40])),
+            ],
+        )?;
+        let input: Arc<dyn ExecutionPlan> =
+            Arc::new(MemoryExec::try_new(&[vec![batch]], schema.clone(), None)?);
+
+        let a_gt_1 = binary(
+            col("a", &schema)?,
+            Operator::Gt,
+            lit(ScalarValue::Int64(Some(1))),
+            &schema,
+        )?;
+        let filter: Arc<dyn ExecutionPlan> = Arc::new(FilterExec::try_new(a_gt_1, input)?);
+        let projection: Arc<dyn ExecutionPlan> = Arc::new(ProjectionExec::try_new(
+            vec![(col("b", &schema)?, "b".to_string())],
+            filter,
+        )?);
+        let projected = projection.schema();
+        let b_lt_40 = binary(
+            col("b", &projected)?,
+            Operator::Lt,
+            lit(ScalarValue::Int64(Some(40))),
+            &projected,
+        )?;
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(FilterExec::try_new(b_lt_40, projection)?);
+
+        let fused = fuse(plan.clone())?;
+        let steps = fused
+            .as_any()
+            .downcast_ref::<FusedStatelessExec>()
+            .expect("the chain should be fused")
+            .steps()
+            .len();
+        assert_eq!(steps, 3);
+        assert_eq!(fused.schema(), plan.schema());
+
+        let context = Arc::new(TaskContext::default());
+        let expected = collect(plan.execute(0, context.clone())?).await?;
+        let output = collect(fused.execute(0, context)?).await?;
+        assert_eq!(output, expected);
+        assert_eq!(output[0].num_rows(), 2);
+        Ok(())
+    }
+}
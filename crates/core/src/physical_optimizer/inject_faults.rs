@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use datafusion::common::config::ConfigOptions;
+use datafusion::error::Result;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::insert::DataSinkExec;
+use datafusion::physical_plan::ExecutionPlan;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::physical_plan::fault_injection::{FaultInjectionExec, FaultPoint};
+
+/// Puts a [`FaultInjectionExec`] after every source and before every sink when fault
+/// injection is enabled. Runs after the rules rewriting the plan, like [`ProfileOperators`]
+/// which then profiles the injection too.
+///
+/// [`ProfileOperators`]: super::ProfileOperators
+#[derive(Default)]
+pub struct InjectFaults {}
+
+impl InjectFaults {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PhysicalOptimizerRule for InjectFaults {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let enabled = config
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.fault_injection_enabled());
+        if !enabled {
+            return Ok(plan);
+        }
+        inject_faults(plan)
+    }
+
+    fn name(&self) -> &str {
+        "inject_faults"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+fn inject_faults(plan: Arc<dyn ExecutionPlan>) -> Result<Arc<dyn ExecutionPlan>> {
+    if plan.children().is_empty() {
+        return Ok(Arc::new(FaultInjectionExec::new(plan, FaultPoint::Source)));
+    }
+    let sink = plan.as_any().is::<DataSinkExec>();
+    let children = plan
+        .children()
+        .into_iter()
+        .map(|child| {
+            let child = inject_faults(child.clone())?;
+            Ok(match sink {
+                true => Arc::new(FaultInjectionExec::new(child, FaultPoint::Sink)) as _,
+                false => child,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    plan.with_new_children(children)
+}
@@ -1,3 +1,15 @@
+pub mod check_stream_metadata;
+pub mod coalesce_before_join;
 pub mod coalesce_before_streaming_window_aggregate;
+pub mod fuse_stateless_operators;
+pub mod inject_faults;
+pub mod profile_operators;
+pub mod register_plan_metrics;
 
+pub use check_stream_metadata::CheckStreamMetadata;
+pub use coalesce_before_join::CoalesceBeforeJoin;
 pub use coalesce_before_streaming_window_aggregate::CoaslesceBeforeStreamingAggregate;
+pub use fuse_stateless_operators::FuseStatelessOperators;
+pub use inject_faults::InjectFaults;
+pub use profile_operators::ProfileOperators;
+pub use register_plan_metrics::RegisterPlanMetrics;
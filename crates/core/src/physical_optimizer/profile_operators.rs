@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use datafusion::common::config::ConfigOptions;
+use datafusion::error::Result;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::ExecutionPlan;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::physical_plan::profile::ProfileExec;
+
+/// Wraps every operator in a [`ProfileExec`] when profiling is enabled. Must run after all
+/// other physical optimizer rules so the wrappers don't get in the way of their matching.
+#[derive(Default)]
+pub struct ProfileOperators {}
+
+impl ProfileOperators {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PhysicalOptimizerRule for ProfileOperators {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let enabled = config
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.profiling_enabled());
+        if !enabled {
+            return Ok(plan);
+        }
+        wrap_with_profile(plan, "")
+    }
+
+    fn name(&self) -> &str {
+        "profile_operators"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+fn wrap_with_profile(plan: Arc<dyn ExecutionPlan>, parent: &str) -> Result<Arc<dyn ExecutionPlan>> {
+    let stack = if parent.is_empty() {
+        plan.name().to_string()
+    } else {
+        format!("{parent};{}", plan.name())
+    };
+
+    let children = plan
+        .children()
+        .into_iter()
+        .map(|child| wrap_with_profile(child.clone(), &stack))
+        .collect::<Result<Vec<_>>>()?;
+
+    let plan = if children.is_empty() {
+        plan
+    } else {
+        plan.with_new_children(children)?
+    };
+    Ok(Arc::new(ProfileExec::new(plan, stack)))
+}
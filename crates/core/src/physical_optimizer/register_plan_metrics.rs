@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use datafusion::common::config::ConfigOptions;
+use datafusion::error::Result;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::ExecutionPlan;
+
+use crate::utils::metrics_export::MetricsRegistry;
+
+/// Registers the final plan with the [`MetricsRegistry`] its operator metrics are exported
+/// from. Must run last so the registered plan is the one that gets executed.
+pub struct RegisterPlanMetrics {
+    registry: Arc<MetricsRegistry>,
+}
+
+impl RegisterPlanMetrics {
+    pub fn new(registry: Arc<MetricsRegistry>) -> Self {
+        Self { registry }
+    }
+}
+
+impl PhysicalOptimizerRule for RegisterPlanMetrics {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        self.registry.register(&plan);
+        Ok(plan)
+    }
+
+    fn name(&self) -> &str {
+        "register_plan_metrics"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,270 @@
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use arrow::compute::concat_batches;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use futures::{Stream, StreamExt};
+use tokio::time::Sleep;
+
+use datafusion::common::Result;
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+
+use crate::physical_plan::utils::stream_message::is_control_batch;
+use crate::utils::backfill::BackfillController;
+
+/// Merges small batches until `target_rows` rows are buffered or `max_wait` has passed since
+/// the first buffered batch arrived, whichever comes first.
+///
+/// Unlike DataFusion's `CoalesceBatchesExec` the wait is bounded, so a quiet stream never
+/// holds rows back for longer than `max_wait`. Control batches flush the buffered rows and
+/// are passed on after them. While the pipeline backfills the target and
+/// the wait grow by the batch factor of its [`BackfillController`].
+#[derive(Debug)]
+pub struct StreamingCoalesceExec {
+    input: Arc<dyn ExecutionPlan>,
+    target_rows: usize,
+    max_wait: Duration,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl StreamingCoalesceExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, target_rows: usize, max_wait: Duration) -> Self {
+        let cache = input.properties().clone();
+        Self {
+            input,
+            target_rows,
+            max_wait,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        }
+    }
+
+    pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.input
+    }
+}
+
+impl DisplayAs for StreamingCoalesceExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => write!(
+                f,
+                "StreamingCoalesceExec: target_rows={}, max_wait={:?}",
+                self.target_rows, self.max_wait
+            ),
+        }
+    }
+}
+
+impl ExecutionPlan for StreamingCoalesceExec {
+    fn name(&self) -> &'static str {
+        "StreamingCoalesceExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(StreamingCoalesceExec::new(
+            children[0].clone(),
+            self.target_rows,
+            self.max_wait,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        Ok(Box::pin(CoalesceStream {
+            input: self.input.execute(partition, context.clone())?,
+            target_rows: self.target_rows,
+            max_wait: self.max_wait,
+            backfill: BackfillController::from_task_context(&context),
+            buffer: vec![],
+            buffered_rows: 0,
+            deadline: None,
+            control: None,
+            input_done: false,
+            baseline_metrics: BaselineMetrics::new(&self.metrics, partition),
+        }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+struct CoalesceStream {
+    input: SendableRecordBatchStream,
+    target_rows: usize,
+    max_wait: Duration,
+    backfill: Option<Arc<BackfillController>>,
+    buffer: Vec<RecordBatch>,
+    buffered_rows: usize,
+    deadline: Option<Pin<Box<Sleep>>>,
+    /// Control batch waiting for the rows before it to be flushed
+    control: Option<RecordBatch>,
+    input_done: bool,
+    baseline_metrics: BaselineMetrics,
+}
+
+impl CoalesceStream {
+    fn batch_factor(&self) -> usize {
+        self.backfill
+            .as_ref()
+            .map_or(1, |backfill| backfill.batch_factor())
+    }
+    fn flush(&mut self) -> Result<RecordBatch> {
+        let _timer = self.baseline_metrics.elapsed_compute().timer();
+        let batch = concat_batches(&self.input.schema(), &self.buffer)?;
+        self.buffer.clear();
+        self.buffered_rows = 0;
+        self.deadline = None;
+        Ok(batch)
+    }
+
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            if let Some(control) = self.control.take() {
+                return Poll::Ready(Some(Ok(control)));
+            }
+            if self.input_done {
+                return if self.buffer.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(self.flush()))
+                };
+            }
+
+            match self.input.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(batch))) => {
+                    if is_control_batch(&batch) {
+                        if self.buffer.is_empty() {
+                            return Poll::Ready(Some(Ok(batch)));
+                        }
+                        self.control = Some(batch);
+                        return Poll::Ready(Some(self.flush()));
+                    }
+                    if batch.num_rows() == 0 {
+                        continue;
+                    }
+                    if self.buffer.is_empty() {
+                        let max_wait = self.max_wait * self.batch_factor() as u32;
+                        self.deadline = Some(Box::pin(tokio::time::sleep(max_wait)));
+                    }
+                    self.buffered_rows += batch.num_rows();
+                    self.buffer.push(batch);
+                    if self.buffered_rows >= self.target_rows * self.batch_factor() {
+                        return Poll::Ready(Some(self.flush()));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => self.input_done = true,
+                Poll::Pending => {
+                    let expired = self
+                        .deadline
+                        .as_mut()
+                        .map_or(false, |deadline| deadline.as_mut().poll(cx).is_ready());
+                    return if expired {
+                        Poll::Ready(Some(self.flush()))
+                    } else {
+                        Poll::Pending
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Stream for CoalesceStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+}
+
+impl RecordBatchStream for CoalesceStream {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn coalesce_to_target_or_until_max_wait() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batches = (1..=4)
+            .map(|id| {
+                Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from(vec![id]))],
+                )?)
+            })
+            .collect::<Vec<Result<RecordBatch>>>();
+        // The input goes quiet after the fourth row
+        let input = stream::iter(batches).chain(stream::pending());
+
+        let mut coalesced = CoalesceStream {
+            input: Box::pin(RecordBatchStreamAdapter::new(schema, input)),
+            target_rows: 3,
+            max_wait: Duration::from_millis(50),
+            backfill: None,
+            buffer: vec![],
+            buffered_rows: 0,
+            deadline: None,
+            control: None,
+            input_done: false,
+            baseline_metrics: BaselineMetrics::new(&ExecutionPlanMetricsSet::new(), 0),
+        };
+        let ids = |batch: RecordBatch| {
+            batch
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec()
+        };
+
+        assert_eq!(ids(coalesced.next().await.unwrap()?), vec![1, 2, 3]);
+        assert_eq!(ids(coalesced.next().await.unwrap()?), vec![4]);
+        Ok(())
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use arrow::{
+    array::AsArray,
+    compute::{concat_batches, kernels::aggregate, take_record_batch},
+    datatypes::TimestampMillisecondType,
+};
+use arrow_array::{RecordBatch, UInt32Array};
+use arrow_schema::SchemaRef;
+use datafusion::{
+    common::{not_impl_err, plan_err, Result, ScalarValue},
+    execution::{RecordBatchStream, TaskContext},
+    physical_plan::{
+        aggregates::{aggregate_expressions, AggregateMode, PhysicalGroupBy},
+        metrics::BaselineMetrics,
+        AggregateExpr, PhysicalExpr,
+    },
+};
+use futures::{ready, Stream, StreamExt};
+use tokio::time::{Instant, Sleep};
+
+use crate::physical_plan::utils::{
+    accumulators::{create_accumulators, AccumulatorItem},
+    cron::ScheduledEmission,
+    metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN},
+    stream_message::{control_batch, MessageStream, StreamMessage},
+    time::WindowTimezone,
+};
+
+use super::{
+    add_window_columns_to_schema,
+    grouped_window_agg_stream::evaluate_group_by,
+    session_window_stream::keyed_window_result,
+    streaming_window::{aggregate_batch, FranzStreamingWindowExec},
+    FiringReason, WindowColumns,
+};
+
+/// The open count window of one key
+struct CountWindow {
+    rows: usize,
+    /// Event times of the first and last row, the window covers `[start_ms, end_ms]`
+    start_ms: i64,
+    end_ms: i64,
+    /// When the window times out, on the processing time clock
+    deadline: Option<Instant>,
+    accumulators: Vec<AccumulatorItem>,
+}
+
+/// Windows of every `size` rows per key. With a timeout a window also fires once it has been
+/// open for that long, whichever comes first.
+///
+/// Rows are counted in arrival order and the timeout runs on processing time, watermarks
+/// don't affect these windows.
+pub struct CountWindowStream {
+    schema: SchemaRef,
+    input: MessageStream,
+    baseline_metrics: BaselineMetrics,
+    exec_aggregate_expressions: Vec<Arc<dyn AggregateExpr>>,
+    aggregate_expressions: Vec<Vec<Arc<dyn PhysicalExpr>>>,
+    filter_expressions: Vec<Option<Arc<dyn PhysicalExpr>>>,
+    group_by: PhysicalGroupBy,
+    size: usize,
+    timeout: Option<Duration>,
+    windows: HashMap<Vec<ScalarValue>, CountWindow>,
+    timer: Option<Pin<Box<Sleep>>>,
+    scheduled_emission: Option<ScheduledEmission>,
+    window_columns: WindowColumns,
+    aggregation_mode: AggregateMode,
+}
+
+impl CountWindowStream {
+    pub fn new(
+        exec_operator: &FranzStreamingWindowExec,
+        context: Arc<TaskContext>,
+        partition: usize,
+        size: usize,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        if exec_operator.group_by.groups.len() > 1 {
+            return not_impl_err!("Grouping sets are not supported in count windows");
+        }
+        if size == 0 {
+            return plan_err!("Count windows need at least one row");
+        }
+
+        let input = MessageStream::new(
+            exec_operator
+                .input
+                .execute(partition, Arc::clone(&context))?,
+        );
+        let aggregate_expressions =
+            aggregate_expressions(&exec_operator.aggregate_expressions, &exec_operator.mode, 0)?;
+        let filter_expressions = match exec_operator.mode {
+            AggregateMode::Partial | AggregateMode::Single | AggregateMode::SinglePartitioned => {
+                exec_operator.filter_expressions.clone()
+            }
+            AggregateMode::Final | AggregateMode::FinalPartitioned => {
+                vec![None; exec_operator.aggregate_expressions.len()]
+            }
+        };
+        let timezone = WindowTimezone::from_task_context(&context)?;
+
+        Ok(Self {
+            schema: Arc::clone(&exec_operator.schema),
+            input,
+            baseline_metrics: BaselineMetrics::new(&exec_operator.metrics, partition),
+            exec_aggregate_expressions: exec_operator.aggregate_expressions.clone(),
+            aggregate_expressions,
+            filter_expressions,
+            group_by: exec_operator.group_by.clone(),
+            size,
+            timeout,
+            windows: HashMap::new(),
+            timer: None,
+            scheduled_emission: exec_operator
+                .emit_schedule
+                .clone()
+                .map(|schedule| ScheduledEmission::new(schedule, timezone)),
+            window_columns: exec_operator.window_columns.clone(),
+            aggregation_mode: exec_operator.mode,
+        })
+    }
+
+    pub fn output_schema_with_window(&self) -> SchemaRef {
+        Arc::new(add_window_columns_to_schema(
+            self.schema.clone(),
+            &self.window_columns,
+        ))
+    }
+
+    /// Aggregate a batch and return the results of the windows it filled
+    fn process_batch(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let keys = evaluate_group_by(&self.group_by, batch)?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+
+        let mut pending: HashMap<Vec<ScalarValue>, Vec<u32>> = HashMap::new();
+        let mut results = vec![];
+        for row in 0..batch.num_rows() {
+            let key = keys
+                .iter()
+                .map(|column| ScalarValue::try_from_array(column, row))
+                .collect::<Result<Vec<_>>>()?;
+            let rows = pending.entry(key.clone()).or_default();
+            rows.push(row as u32);
+            let open_rows = self.windows.get(&key).map_or(0, |window| window.rows);
+            if open_rows + rows.len() < self.size {
+                continue;
+            }
+
+            let rows = pending.remove(&key).unwrap();
+            let mut window = match self.windows.remove(&key) {
+                Some(window) => window,
+                None => self.open_window()?,
+            };
+            self.add_rows(&mut window, batch, rows)?;
+            results.push(self.window_result(&key, &mut window, FiringReason::Count)?);
+        }
+
+        for (key, rows) in pending {
+            let mut window = match self.windows.remove(&key) {
+                Some(window) => window,
+                None => self.open_window()?,
+            };
+            self.add_rows(&mut window, batch, rows)?;
+            self.windows.insert(key, window);
+        }
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    fn open_window(&self) -> Result<CountWindow> {
+        Ok(CountWindow {
+            rows: 0,
+            start_ms: i64::MAX,
+            end_ms: i64::MIN,
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+            accumulators: create_accumulators(&self.exec_aggregate_expressions)?,
+        })
+    }
+
+    fn add_rows(
+        &self,
+        window: &mut CountWindow,
+        batch: &RecordBatch,
+        rows: Vec<u32>,
+    ) -> Result<()> {
+        let rows = take_record_batch(batch, &UInt32Array::from(rows))?;
+        let timestamps = rows
+            .column_by_name(STREAMING_METADATA_COLUMN)
+            .unwrap()
+            .as_struct()
+            .column_by_name(CANONICAL_TIMESTAMP_FIELD)
+            .unwrap()
+            .as_primitive::<TimestampMillisecondType>();
+        if let (Some(min), Some(max)) = (aggregate::min(timestamps), aggregate::max(timestamps)) {
+            window.start_ms = window.start_ms.min(min);
+            window.end_ms = window.end_ms.max(max);
+        }
+        window.rows += rows.num_rows();
+        aggregate_batch(
+            &self.aggregation_mode,
+            rows,
+            &mut window.accumulators,
+            &self.aggregate_expressions,
+            &self.filter_expressions,
+        )?;
+        Ok(())
+    }
+
+    fn window_result(
+        &self,
+        key: &[ScalarValue],
+        window: &mut CountWindow,
+        reason: FiringReason,
+    ) -> Result<RecordBatch> {
+        // Windows of rows without event times span nothing
+        let (start_ms, end_ms) = match window.start_ms <= window.end_ms {
+            true => (window.start_ms, window.end_ms + 1),
+            false => (0, 0),
+        };
+        keyed_window_result(
+            &self.schema,
+            key,
+            &mut window.accumulators,
+            &self.aggregation_mode,
+            (start_ms, end_ms),
+            &self.window_columns,
+            reason,
+        )
+    }
+
+    /// Results of the windows that timed out, which are removed, or of every open window for
+    /// scheduled emissions
+    fn emit_windows(&mut self, reason: FiringReason) -> Result<RecordBatch> {
+        let now = Instant::now();
+        let fires = |window: &CountWindow| match reason {
+            FiringReason::Timeout => window.deadline.is_some_and(|deadline| deadline <= now),
+            _ => true,
+        };
+        let keys: Vec<_> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| fires(window))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let mut results = vec![];
+        for key in keys {
+            let mut window = self.windows.remove(&key).unwrap();
+            results.push(self.window_result(&key, &mut window, reason)?);
+            if reason == FiringReason::Schedule {
+                self.windows.insert(key, window);
+            }
+        }
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    /// Ready once the earliest open window times out
+    fn poll_timeout(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(deadline) = self.windows.values().filter_map(|w| w.deadline).min() else {
+            return Poll::Pending;
+        };
+        let timer = self
+            .timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+        if timer.deadline() != deadline {
+            timer.as_mut().reset(deadline);
+        }
+        timer.as_mut().poll(cx)
+    }
+
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            let fired = self
+                .scheduled_emission
+                .as_mut()
+                .is_some_and(|emission| emission.poll_fire(cx).is_ready());
+            if fired {
+                let output = self.emit_windows(FiringReason::Schedule)?;
+                if output.num_rows() > 0 {
+                    return Poll::Ready(Some(Ok(output)));
+                }
+            }
+            if self.poll_timeout(cx).is_ready() {
+                let output = self.emit_windows(FiringReason::Timeout)?;
+                if output.num_rows() > 0 {
+                    return Poll::Ready(Some(Ok(output)));
+                }
+            }
+
+            let message = match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            match message {
+                StreamMessage::Data(batch) => {
+                    let output = self.process_batch(&batch)?;
+                    if output.num_rows() > 0 {
+                        return Poll::Ready(Some(Ok(output)));
+                    }
+                }
+                StreamMessage::Watermark(_) => {}
+                // Window state isn't checkpointed, the barrier is passed on to the operators and
+                // sinks after the window
+                StreamMessage::Barrier(epoch) => {
+                    let control =
+                        control_batch(&self.output_schema_with_window(), Some(epoch), None);
+                    return Poll::Ready(Some(Ok(control)));
+                }
+                StreamMessage::EndOfPartition => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl RecordBatchStream for CountWindowStream {
+    fn schema(&self) -> SchemaRef {
+        self.output_schema_with_window()
+    }
+}
+
+impl Stream for CountWindowStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::datatypes::Int64Type;
+    use arrow_array::{StringArray, TimestampMillisecondArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::datasource::streaming::StreamingTable;
+    use datafusion::execution::SendableRecordBatchStream;
+    use datafusion::functions_aggregate::count::count;
+    use datafusion::logical_expr::col;
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use datafusion::physical_plan::streaming::PartitionStream;
+    use datafusion::physical_plan::ExecutionPlan;
+
+    use crate::context::Context;
+    use crate::datastream::DataStream;
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+    /// Hands out its rows and then goes quiet without ending
+    struct QuietAfter(RecordBatch);
+
+    impl PartitionStream for QuietAfter {
+        fn schema(&self) -> &SchemaRef {
+            self.0.schema_ref()
+        }
+
+        fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+            Box::pin(RecordBatchStreamAdapter::new(
+                self.0.schema(),
+                futures::stream::iter([Ok(self.0.clone())]).chain(futures::stream::pending()),
+            ))
+        }
+    }
+
+    async fn readings() -> Result<DataStream> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            stream_metadata_field(),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "a"])),
+                Arc::new(stream_metadata_array(TimestampMillisecondArray::from(
+                    vec![1_000, 1_100, 1_200],
+                ))),
+            ],
+        )?;
+        let table = StreamingTable::try_new(schema, vec![Arc::new(QuietAfter(batch))])?;
+        Context::new()?
+            .from_source("readings", Arc::new(table))
+            .await
+    }
+
+    fn window_exec(plan: &Arc<dyn ExecutionPlan>) -> Option<&FranzStreamingWindowExec> {
+        plan.as_any()
+            .downcast_ref()
+            .or_else(|| plan.children().into_iter().find_map(window_exec))
+    }
+
+    /// `(sensor, window start, window end, count)` of every result row
+    fn results(batch: &RecordBatch) -> Vec<(String, i64, i64, i64)> {
+        let column = |name: &str| batch.column_by_name(name).unwrap();
+        (0..batch.num_rows())
+            .map(|row| {
+                (
+                    column("sensor").as_string::<i32>().value(row).to_string(),
+                    column("window_start_time")
+                        .as_primitive::<TimestampMillisecondType>()
+                        .value(row),
+                    column("window_end_time")
+                        .as_primitive::<TimestampMillisecondType>()
+                        .value(row),
+                    column("count").as_primitive::<Int64Type>().value(row),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fire_on_count_schedule_and_timeout() -> Result<()> {
+        let ds = readings().await?.count_window(
+            vec![col("sensor")],
+            vec![count(col("sensor")).alias("count")],
+            2,
+            Some(Duration::from_millis(50)),
+        )?;
+        let context = Arc::new(ds.df.task_ctx());
+        let plan = ds.df.as_ref().clone().create_physical_plan().await?;
+        let exec = window_exec(&plan).expect("a window operator");
+        let mut windows =
+            CountWindowStream::new(exec, context, 0, 2, Some(Duration::from_millis(50)))?;
+
+        // The second row of `a` fills its window
+        let filled = windows.next().await.unwrap()?;
+        assert_eq!(results(&filled), [("a".to_string(), 1_000, 1_201, 2)]);
+
+        // A scheduled emission reports the open window of `b` and keeps it open
+        let scheduled = windows.emit_windows(FiringReason::Schedule)?;
+        assert_eq!(results(&scheduled), [("b".to_string(), 1_100, 1_101, 1)]);
+        assert_eq!(windows.windows.len(), 1);
+
+        // Without further rows it times out
+        let timed_out = windows.next().await.unwrap()?;
+        assert_eq!(results(&timed_out), [("b".to_string(), 1_100, 1_101, 1)]);
+        assert!(windows.windows.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_empty_count_windows() -> Result<()> {
+        let ds = readings().await?;
+        assert!(ds
+            .count_window(vec![col("sensor")], vec![count(col("sensor"))], 0, None)
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use arrow::array::AsArray;
+use arrow_array::RecordBatch;
+use datafusion::common::{DataFusionError, Result};
+
+/// What a [`Trigger`] decides for the window of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerAction {
+    Continue,
+    /// Emit the result and keep the rows
+    Fire,
+    /// Drop the rows without emitting
+    Purge,
+    /// Emit the result and drop the rows
+    FireAndPurge,
+}
+
+/// Decides when the global window of a key emits a result
+pub trait Trigger: Debug + Send + Sync {
+    /// Identifies the trigger in plans, two triggers with the same name are considered equal
+    fn name(&self) -> &str;
+
+    /// Called with the rows of a key that arrived in one batch, after they were added to the
+    /// window and the evictor ran. `buffered` is the number of rows the window holds now.
+    fn on_rows(&self, rows: &RecordBatch, buffered: usize) -> Result<TriggerAction>;
+
+    /// Called for every key when the watermark advances
+    fn on_watermark(&self, _watermark_ms: i64, _buffered: usize) -> TriggerAction {
+        TriggerAction::Continue
+    }
+}
+
+/// Removes rows from the global window of a key, keeping its state bounded
+pub trait Evictor: Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The rows to keep, `rows` are ordered by arrival
+    fn evict(&self, rows: RecordBatch) -> Result<RecordBatch>;
+}
+
+/// Fires and purges the window of a key once it holds `count` rows
+#[derive(Debug)]
+pub struct CountTrigger {
+    count: usize,
+    name: String,
+}
+
+impl CountTrigger {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            name: format!("count({count})"),
+        }
+    }
+}
+
+impl Trigger for CountTrigger {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_rows(&self, _rows: &RecordBatch, buffered: usize) -> Result<TriggerAction> {
+        Ok(match buffered >= self.count {
+            true => TriggerAction::FireAndPurge,
+            false => TriggerAction::Continue,
+        })
+    }
+}
+
+/// Fires when a row with `true` in the boolean column arrives, such as a control message
+#[derive(Debug)]
+pub struct ControlTrigger {
+    pub column: String,
+}
+
+impl Trigger for ControlTrigger {
+    fn name(&self) -> &str {
+        &self.column
+    }
+
+    fn on_rows(&self, rows: &RecordBatch, _buffered: usize) -> Result<TriggerAction> {
+        let column = rows.column_by_name(&self.column).ok_or_else(|| {
+            DataFusionError::Execution(format!("Control column {} not found", self.column))
+        })?;
+        let Some(control) = column.as_boolean_opt() else {
+            return Err(DataFusionError::Execution(format!(
+                "Control column {} must be a boolean",
+                self.column
+            )));
+        };
+        Ok(match control.true_count() > 0 {
+            true => TriggerAction::Fire,
+            false => TriggerAction::Continue,
+        })
+    }
+}
+
+/// Keeps the last `count` rows of every key
+#[derive(Debug)]
+pub struct CountEvictor {
+    count: usize,
+    name: String,
+}
+
+impl CountEvictor {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            name: format!("count({count})"),
+        }
+    }
+}
+
+impl Evictor for CountEvictor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evict(&self, rows: RecordBatch) -> Result<RecordBatch> {
+        let excess = rows.num_rows().saturating_sub(self.count);
+        Ok(rows.slice(excess, rows.num_rows() - excess))
+    }
+}
+
+/// A window per key spanning all of its rows, emitted whenever its [`Trigger`] fires
+#[derive(Debug, Clone)]
+pub struct GlobalWindow {
+    pub trigger: Arc<dyn Trigger>,
+    pub evictor: Option<Arc<dyn Evictor>>,
+}
+
+impl GlobalWindow {
+    fn names(&self) -> (&str, Option<&str>) {
+        (
+            self.trigger.name(),
+            self.evictor.as_ref().map(|evictor| evictor.name()),
+        )
+    }
+}
+
+impl PartialEq for GlobalWindow {
+    fn eq(&self, other: &Self) -> bool {
+        self.names() == other.names()
+    }
+}
+
+impl Eq for GlobalWindow {}
+
+impl Hash for GlobalWindow {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.names().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::datatypes::Int64Type;
+    use arrow_array::{BooleanArray, Int64Array};
+
+    #[test]
+    fn evict_and_trigger() -> Result<()> {
+        let rows = RecordBatch::try_from_iter(vec![
+            ("value", Arc::new(Int64Array::from(vec![1, 2, 3, 4])) as _),
+            (
+                "flush",
+                Arc::new(BooleanArray::from(vec![false, false, true, false])) as _,
+            ),
+        ])?;
+
+        let kept = CountEvictor::new(3).evict(rows.clone())?;
+        assert_eq!(kept.num_rows(), 3);
+        assert_eq!(
+            kept.column(0).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![2, 3, 4])
+        );
+
+        let control = ControlTrigger {
+            column: "flush".to_string(),
+        };
+        assert_eq!(control.on_rows(&rows, 4)?, TriggerAction::Fire);
+        assert_eq!(
+            control.on_rows(&rows.slice(0, 2), 2)?,
+            TriggerAction::Continue
+        );
+        assert_eq!(
+            CountTrigger::new(4).on_rows(&rows, 4)?,
+            TriggerAction::FireAndPurge
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn windows_differ_by_parameters() {
+        let window = |trigger: usize, evictor: usize| GlobalWindow {
+            trigger: Arc::new(CountTrigger::new(trigger)),
+            evictor: Some(Arc::new(CountEvictor::new(evictor))),
+        };
+        assert_eq!(window(2, 10), window(2, 10));
+        assert_ne!(window(2, 10), window(3, 10));
+        assert_ne!(window(2, 10), window(2, 20));
+    }
+}
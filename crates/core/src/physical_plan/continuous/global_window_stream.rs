@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::UNIX_EPOCH,
+};
+
+use arrow::{
+    array::AsArray,
+    compute::{concat_batches, kernels::aggregate, take_record_batch},
+    datatypes::TimestampMillisecondType,
+};
+use arrow_array::{RecordBatch, UInt32Array};
+use arrow_schema::SchemaRef;
+use datafusion::{
+    common::{not_impl_err, Result, ScalarValue},
+    execution::{RecordBatchStream, TaskContext},
+    physical_plan::{
+        aggregates::{aggregate_expressions, AggregateMode, PhysicalGroupBy},
+        metrics::BaselineMetrics,
+        AggregateExpr, PhysicalExpr,
+    },
+};
+use futures::{ready, Stream, StreamExt};
+
+use crate::physical_plan::utils::{
+    accumulators::create_accumulators,
+    cron::ScheduledEmission,
+    metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN},
+    stream_message::{control_batch, MessageStream, StreamMessage},
+    time::WindowTimezone,
+};
+
+use super::{
+    add_window_columns_to_schema,
+    global_window::{GlobalWindow, TriggerAction},
+    grouped_window_agg_stream::evaluate_group_by,
+    session_window_stream::keyed_window_result,
+    streaming_window::{aggregate_batch, FranzStreamingWindowExec},
+    FiringReason, WindowColumns,
+};
+
+/// Keeps the rows of every key and aggregates them whenever the trigger of the
+/// [`GlobalWindow`] fires. Unlike the other windows, the rows themselves are kept rather than
+/// the aggregate state, so that the evictor can remove them.
+pub struct GlobalWindowStream {
+    schema: SchemaRef,
+    input_schema: SchemaRef,
+    input: MessageStream,
+    baseline_metrics: BaselineMetrics,
+    exec_aggregate_expressions: Vec<Arc<dyn AggregateExpr>>,
+    aggregate_expressions: Vec<Vec<Arc<dyn PhysicalExpr>>>,
+    filter_expressions: Vec<Option<Arc<dyn PhysicalExpr>>>,
+    group_by: PhysicalGroupBy,
+    window: GlobalWindow,
+    buffers: HashMap<Vec<ScalarValue>, RecordBatch>,
+    scheduled_emission: Option<ScheduledEmission>,
+    window_columns: WindowColumns,
+    aggregation_mode: AggregateMode,
+}
+
+impl GlobalWindowStream {
+    pub fn new(
+        exec_operator: &FranzStreamingWindowExec,
+        context: Arc<TaskContext>,
+        partition: usize,
+        window: GlobalWindow,
+    ) -> Result<Self> {
+        if exec_operator.group_by.groups.len() > 1 {
+            return not_impl_err!("Grouping sets are not supported in global windows");
+        }
+
+        let input = MessageStream::new(
+            exec_operator
+                .input
+                .execute(partition, Arc::clone(&context))?,
+        );
+        let aggregate_expressions =
+            aggregate_expressions(&exec_operator.aggregate_expressions, &exec_operator.mode, 0)?;
+        let filter_expressions = match exec_operator.mode {
+            AggregateMode::Partial | AggregateMode::Single | AggregateMode::SinglePartitioned => {
+                exec_operator.filter_expressions.clone()
+            }
+            AggregateMode::Final | AggregateMode::FinalPartitioned => {
+                vec![None; exec_operator.aggregate_expressions.len()]
+            }
+        };
+        let timezone = WindowTimezone::from_task_context(&context)?;
+
+        Ok(Self {
+            schema: Arc::clone(&exec_operator.schema),
+            input_schema: exec_operator.input.schema(),
+            input,
+            baseline_metrics: BaselineMetrics::new(&exec_operator.metrics, partition),
+            exec_aggregate_expressions: exec_operator.aggregate_expressions.clone(),
+            aggregate_expressions,
+            filter_expressions,
+            group_by: exec_operator.group_by.clone(),
+            window,
+            buffers: HashMap::new(),
+            scheduled_emission: exec_operator
+                .emit_schedule
+                .clone()
+                .map(|schedule| ScheduledEmission::new(schedule, timezone)),
+            window_columns: exec_operator.window_columns.clone(),
+            aggregation_mode: exec_operator.mode,
+        })
+    }
+
+    pub fn output_schema_with_window(&self) -> SchemaRef {
+        Arc::new(add_window_columns_to_schema(
+            self.schema.clone(),
+            &self.window_columns,
+        ))
+    }
+
+    /// Add a batch to the windows of its keys and return the results of the triggers it fired
+    fn process_batch(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let keys = evaluate_group_by(&self.group_by, batch)?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let mut rows_by_key: HashMap<Vec<ScalarValue>, Vec<u32>> = HashMap::new();
+        for row in 0..batch.num_rows() {
+            let key = keys
+                .iter()
+                .map(|column| ScalarValue::try_from_array(column, row))
+                .collect::<Result<Vec<_>>>()?;
+            rows_by_key.entry(key).or_default().push(row as u32);
+        }
+
+        let mut results = vec![];
+        for (key, rows) in rows_by_key {
+            let rows = take_record_batch(batch, &UInt32Array::from(rows))?;
+            let mut buffer = match self.buffers.remove(&key) {
+                Some(buffer) => concat_batches(&self.input_schema, [&buffer, &rows])?,
+                None => rows.clone(),
+            };
+            if let Some(evictor) = &self.window.evictor {
+                buffer = evictor.evict(buffer)?;
+            }
+            let action = self.window.trigger.on_rows(&rows, buffer.num_rows())?;
+            if let Some(result) = self.apply(action, key, buffer)? {
+                results.push(result);
+            }
+        }
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    fn process_watermark(&mut self, watermark_ms: i64) -> Result<RecordBatch> {
+        let mut results = vec![];
+        for (key, buffer) in std::mem::take(&mut self.buffers) {
+            let action = self
+                .window
+                .trigger
+                .on_watermark(watermark_ms, buffer.num_rows());
+            if let Some(result) = self.apply(action, key, buffer)? {
+                results.push(result);
+            }
+        }
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    /// Carry out the trigger's decision for the window of `key`, keeping `buffer` unless it
+    /// is purged
+    fn apply(
+        &mut self,
+        action: TriggerAction,
+        key: Vec<ScalarValue>,
+        buffer: RecordBatch,
+    ) -> Result<Option<RecordBatch>> {
+        let result = match action {
+            TriggerAction::Fire | TriggerAction::FireAndPurge => {
+                Some(self.evaluate(&key, &buffer, FiringReason::Trigger)?)
+            }
+            TriggerAction::Continue | TriggerAction::Purge => None,
+        };
+        let purged = matches!(action, TriggerAction::Purge | TriggerAction::FireAndPurge);
+        if !purged && buffer.num_rows() > 0 {
+            self.buffers.insert(key, buffer);
+        }
+        Ok(result)
+    }
+
+    fn evaluate(
+        &self,
+        key: &[ScalarValue],
+        buffer: &RecordBatch,
+        reason: FiringReason,
+    ) -> Result<RecordBatch> {
+        let timestamps = buffer
+            .column_by_name(STREAMING_METADATA_COLUMN)
+            .unwrap()
+            .as_struct()
+            .column_by_name(CANONICAL_TIMESTAMP_FIELD)
+            .unwrap()
+            .as_primitive::<TimestampMillisecondType>();
+        let range = match (aggregate::min(timestamps), aggregate::max(timestamps)) {
+            (Some(min), Some(max)) => (min, max + 1),
+            _ => (0, 0),
+        };
+
+        let mut accumulators = create_accumulators(&self.exec_aggregate_expressions)?;
+        aggregate_batch(
+            &self.aggregation_mode,
+            buffer.clone(),
+            &mut accumulators,
+            &self.aggregate_expressions,
+            &self.filter_expressions,
+        )?;
+        keyed_window_result(
+            &self.schema,
+            key,
+            &mut accumulators,
+            &self.aggregation_mode,
+            range,
+            &self.window_columns,
+            reason,
+        )
+    }
+
+    fn emit_scheduled(&self) -> Result<RecordBatch> {
+        let results = self
+            .buffers
+            .iter()
+            .map(|(key, buffer)| self.evaluate(key, buffer, FiringReason::Schedule))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            let fired = self
+                .scheduled_emission
+                .as_mut()
+                .is_some_and(|emission| emission.poll_fire(cx).is_ready());
+            if fired {
+                let output = self.emit_scheduled()?;
+                if output.num_rows() > 0 {
+                    return Poll::Ready(Some(Ok(output)));
+                }
+            }
+
+            let message = match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            let output = match message {
+                StreamMessage::Data(batch) => self.process_batch(&batch)?,
+                StreamMessage::Watermark(watermark) => {
+                    let watermark_ms = watermark.duration_since(UNIX_EPOCH).unwrap().as_millis();
+                    self.process_watermark(watermark_ms as i64)?
+                }
+                // Window state isn't checkpointed, the barrier is passed on to the operators and
+                // sinks after the window
+                StreamMessage::Barrier(epoch) => {
+                    let control =
+                        control_batch(&self.output_schema_with_window(), Some(epoch), None);
+                    return Poll::Ready(Some(Ok(control)));
+                }
+                StreamMessage::EndOfPartition => return Poll::Ready(None),
+            };
+            if output.num_rows() > 0 {
+                return Poll::Ready(Some(Ok(output)));
+            }
+        }
+    }
+}
+
+impl RecordBatchStream for GlobalWindowStream {
+    fn schema(&self) -> SchemaRef {
+        self.output_schema_with_window()
+    }
+}
+
+impl Stream for GlobalWindowStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
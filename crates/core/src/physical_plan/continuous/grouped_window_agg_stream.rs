@@ -1,7 +1,7 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::Arc,
     task::{Context, Poll},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
@@ -22,7 +22,7 @@ use datafusion::{
     physical_plan::{aggregates::PhysicalGroupBy, PhysicalExpr},
 };
 use datafusion::{
-    execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext},
+    execution::{RecordBatchStream, TaskContext},
     physical_plan::{
         aggregates::{
             aggregate_expressions,
@@ -34,29 +34,45 @@ use datafusion::{
         AggregateExpr,
     },
 };
-use futures::{Stream, StreamExt};
+use futures::{ready, Stream, StreamExt};
 
-use crate::physical_plan::utils::time::RecordBatchWatermark;
+use crate::accumulators::groups_snapshot::{
+    restore_groups_accumulator, snapshot_groups_accumulator, GroupsSnapshot,
+};
+use crate::physical_plan::utils::cron::ScheduledEmission;
+use crate::physical_plan::utils::metadata::STREAMING_METADATA_COLUMN;
+use crate::physical_plan::utils::state_metrics::StateMetrics;
+use crate::physical_plan::utils::stream_message::{control_batch, MessageStream, StreamMessage};
+use crate::physical_plan::utils::time::{RecordBatchWatermark, WindowTimezone};
 
 use super::{
     add_window_columns_to_record_batch, add_window_columns_to_schema, create_group_accumulator,
     streaming_window::{
-        get_windows_for_watermark, FranzStreamingWindowExec, FranzStreamingWindowType,
+        get_windows_for_watermark, FranzStreamingWindowExec, FranzStreamingWindowType, LateRows,
     },
-    GroupsAccumulatorItem,
+    FiringReason, GroupsAccumulatorItem, WindowColumns,
 };
 
 #[allow(dead_code)]
 pub struct GroupedWindowAggStream {
     pub schema: SchemaRef,
-    input: SendableRecordBatchStream,
+    input: MessageStream,
     baseline_metrics: BaselineMetrics,
     exec_aggregate_expressions: Vec<Arc<dyn AggregateExpr>>,
     aggregate_expressions: Vec<Vec<Arc<dyn PhysicalExpr>>>,
     filter_expressions: Vec<Option<Arc<dyn PhysicalExpr>>>,
-    latest_watermark: Arc<Mutex<Option<SystemTime>>>,
+    latest_watermark: Option<SystemTime>,
+    late_data: LateRows,
     window_frames: BTreeMap<SystemTime, GroupedAggWindowFrame>,
+    /// Windows that fired and are kept for late rows, by their start
+    fired_windows: HashSet<SystemTime>,
+    /// Fired windows that late rows updated since the last watermark
+    updated_windows: HashSet<SystemTime>,
+    state_metrics: StateMetrics,
     window_type: FranzStreamingWindowType,
+    timezone: WindowTimezone,
+    scheduled_emission: Option<ScheduledEmission>,
+    window_columns: WindowColumns,
     aggregation_mode: AggregateMode,
     group_by: PhysicalGroupBy,
     group_schema: Arc<Schema>,
@@ -74,7 +90,6 @@ impl GroupedWindowAggStream {
         exec_operator: &FranzStreamingWindowExec,
         context: Arc<TaskContext>,
         partition: usize,
-        watermark: Arc<Mutex<Option<SystemTime>>>,
         window_type: FranzStreamingWindowType,
         aggregation_mode: AggregateMode,
     ) -> Result<Self> {
@@ -82,9 +97,17 @@ impl GroupedWindowAggStream {
         let agg_filter_expr = exec_operator.filter_expressions.clone();
 
         let baseline_metrics = BaselineMetrics::new(&exec_operator.metrics, partition);
-        let input = exec_operator
-            .input
-            .execute(partition, Arc::clone(&context))?;
+        let input = MessageStream::new(
+            exec_operator
+                .input
+                .execute(partition, Arc::clone(&context))?,
+        );
+
+        let late_data = LateRows::try_new(
+            &exec_operator.late_data,
+            &context,
+            exec_operator.input.schema(),
+        )?;
 
         let aggregate_expressions =
             aggregate_expressions(&exec_operator.aggregate_expressions, &exec_operator.mode, 0)?;
@@ -99,6 +122,7 @@ impl GroupedWindowAggStream {
 
         let group_by = exec_operator.group_by.clone();
         let group_schema = group_schema(&agg_schema, group_by.expr.len());
+        let timezone = WindowTimezone::from_task_context(&context)?;
         Ok(Self {
             schema: agg_schema,
             input,
@@ -106,9 +130,19 @@ impl GroupedWindowAggStream {
             exec_aggregate_expressions: exec_operator.aggregate_expressions.clone(),
             aggregate_expressions,
             filter_expressions,
-            latest_watermark: watermark,
+            latest_watermark: None,
+            late_data,
             window_frames: BTreeMap::new(),
+            fired_windows: HashSet::new(),
+            updated_windows: HashSet::new(),
+            state_metrics: StateMetrics::new(&exec_operator.metrics, partition),
             window_type,
+            scheduled_emission: exec_operator
+                .emit_schedule
+                .clone()
+                .map(|schedule| ScheduledEmission::new(schedule, timezone.clone())),
+            timezone,
+            window_columns: exec_operator.window_columns.clone(),
             aggregation_mode,
             group_by,
             group_schema,
@@ -117,60 +151,177 @@ impl GroupedWindowAggStream {
     }
 
     pub fn output_schema_with_window(&self) -> SchemaRef {
-        Arc::new(add_window_columns_to_schema(self.schema.clone()))
+        Arc::new(add_window_columns_to_schema(
+            self.schema.clone(),
+            &self.window_columns,
+        ))
     }
 
     fn trigger_windows(&mut self) -> Result<RecordBatch, DataFusionError> {
         let mut results: Vec<RecordBatch> = Vec::new();
-        let watermark_lock: std::sync::MutexGuard<'_, Option<SystemTime>> =
-            self.latest_watermark.lock().unwrap();
 
-        if let Some(watermark) = *watermark_lock {
+        if let Some(watermark) = self.latest_watermark {
             let mut window_frames_to_remove: Vec<SystemTime> = Vec::new();
+            let mut reclaimed_bytes = 0;
 
             for (timestamp, frame) in self.window_frames.iter_mut() {
-                if watermark >= frame.window_end_time {
-                    let rb = frame.evaluate()?;
-                    let result = add_window_columns_to_record_batch(
+                if watermark < frame.window_end_time {
+                    continue;
+                }
+                // Measured before evaluating, which empties the frame
+                let size = frame.size();
+                let reason = match self.fired_windows.contains(timestamp) {
+                    false => Some(FiringReason::Watermark),
+                    true if self.updated_windows.contains(timestamp) => Some(FiringReason::Late),
+                    true => None,
+                };
+                // Kept for the allowed lateness, any other policy is done with the window
+                let keep = matches!(self.late_data, LateRows::UpdateAndEmit(_))
+                    && !self
+                        .late_data
+                        .is_expired(frame.window_end_time, Some(watermark));
+                if let Some(reason) = reason {
+                    let rb = match keep {
+                        true => frame.evaluate_in_place()?,
+                        false => frame.evaluate()?,
+                    };
+                    results.push(add_window_columns_to_record_batch(
                         rb,
                         frame.window_start_time,
                         frame.window_end_time,
-                    );
-                    results.push(result);
-                    window_frames_to_remove.push(*timestamp);
+                        &self.window_columns,
+                        reason,
+                    ));
+                }
+                match keep {
+                    true => {
+                        self.fired_windows.insert(*timestamp);
+                    }
+                    false => {
+                        reclaimed_bytes += size;
+                        window_frames_to_remove.push(*timestamp);
+                    }
                 }
             }
 
+            let reclaimed_windows = window_frames_to_remove.len();
             for timestamp in window_frames_to_remove {
                 self.window_frames.remove(&timestamp);
+                self.fired_windows.remove(&timestamp);
             }
+            let remaining = self.window_frames.values().map(|frame| frame.size()).sum();
+            self.state_metrics.record_reclaimed(
+                watermark,
+                reclaimed_windows,
+                reclaimed_bytes,
+                remaining,
+            );
         }
+        self.updated_windows.clear();
         concat_batches(&self.output_schema_with_window(), &results)
             .map_err(|err| DataFusionError::ArrowError(err, None))
     }
 
-    fn process_watermark(&mut self, watermark: RecordBatchWatermark) {
-        // should this be within a mutex?
-        let mut watermark_lock: std::sync::MutexGuard<Option<SystemTime>> =
-            self.latest_watermark.lock().unwrap();
+    /// Current results of every open window, the windows keep accumulating
+    fn emit_open_windows(&mut self) -> Result<RecordBatch> {
+        let mut results = Vec::with_capacity(self.window_frames.len());
+        for frame in self.window_frames.values_mut() {
+            let rb = frame.evaluate_in_place()?;
+            results.push(add_window_columns_to_record_batch(
+                rb,
+                frame.window_start_time,
+                frame.window_end_time,
+                &self.window_columns,
+                FiringReason::Schedule,
+            ));
+        }
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    fn process_watermark(&mut self, watermark: SystemTime) {
+        self.latest_watermark = self.latest_watermark.max(Some(watermark));
+    }
+
+    fn process_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let batch = self.late_data.filter(
+            batch,
+            self.latest_watermark,
+            &self.window_type,
+            &self.timezone,
+        )?;
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
 
-        if let Some(current_watermark) = *watermark_lock {
-            if current_watermark <= watermark.min_timestamp {
-                *watermark_lock = Some(watermark.min_timestamp)
+        let watermark = RecordBatchWatermark::try_from(&batch, STREAMING_METADATA_COLUMN)?;
+        let mut ranges = get_windows_for_watermark(&watermark, &self.window_type, &self.timezone);
+        // Rows on time for a later sliding window would bring back windows closed for good
+        ranges.retain(|(_, end)| !self.late_data.is_expired(*end, self.latest_watermark));
+        if self.window_type.merges_windows() {
+            for range in &ranges {
+                self.merge_window(*range)?;
+            }
+            // The assigned windows may have been folded into wider ones
+            ranges = self
+                .window_frames
+                .values()
+                .filter(|frame| {
+                    ranges.iter().any(|(start, end)| {
+                        frame.window_start_time < *end && *start < frame.window_end_time
+                    })
+                })
+                .map(|frame| (frame.window_start_time, frame.window_end_time))
+                .collect();
+        }
+        self.ensure_window_frames_for_ranges(&ranges)?;
+        for range in ranges {
+            let frame = self.window_frames.get_mut(&range.0).unwrap();
+            frame.push(&batch)?;
+            if self.fired_windows.contains(&range.0) {
+                self.updated_windows.insert(range.0);
             }
-        } else {
-            *watermark_lock = Some(watermark.min_timestamp)
         }
+        Ok(())
     }
 
     fn get_window_length(&mut self) -> Duration {
-        match self.window_type {
-            FranzStreamingWindowType::Session(duration) => duration,
-            FranzStreamingWindowType::Sliding(duration, _) => duration,
-            FranzStreamingWindowType::Tumbling(duration) => duration,
+        match &self.window_type {
+            FranzStreamingWindowType::Sliding(duration, _) => *duration,
+            FranzStreamingWindowType::Tumbling(duration) => *duration,
+            FranzStreamingWindowType::Calendar(interval) => interval.max_length(),
+            // Not known up front
+            FranzStreamingWindowType::Session(_)
+            | FranzStreamingWindowType::Custom(_)
+            | FranzStreamingWindowType::Count(..)
+            | FranzStreamingWindowType::Global(_) => Duration::ZERO,
         }
     }
 
+    /// Replace the frames overlapping `range` with a single frame spanning them and `range`
+    fn merge_window(&mut self, (start, end): (SystemTime, SystemTime)) -> Result<()> {
+        let overlapping: Vec<SystemTime> = self
+            .window_frames
+            .values()
+            .filter(|frame| frame.window_start_time < end && start < frame.window_end_time)
+            .map(|frame| frame.window_start_time)
+            .collect();
+        let mut merged_range = (start, end);
+        let mut merged_frames = Vec::with_capacity(overlapping.len());
+        for key in overlapping {
+            let frame = self.window_frames.remove(&key).unwrap();
+            merged_range.0 = merged_range.0.min(frame.window_start_time);
+            merged_range.1 = merged_range.1.max(frame.window_end_time);
+            merged_frames.push(frame);
+        }
+
+        self.ensure_window_frames_for_ranges(&vec![merged_range])?;
+        let merged = self.window_frames.get_mut(&merged_range.0).unwrap();
+        for mut frame in merged_frames {
+            merged.merge(&mut frame)?;
+        }
+        Ok(())
+    }
+
     fn ensure_window_frames_for_ranges(
         &mut self,
         ranges: &Vec<(SystemTime, SystemTime)>,
@@ -212,36 +363,42 @@ impl GroupedWindowAggStream {
 
     #[inline]
     fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
-        let result: std::prelude::v1::Result<RecordBatch, DataFusionError> = match self
-            .input
-            .poll_next_unpin(cx)
-        {
-            Poll::Ready(rdy) => match rdy {
-                Some(Ok(batch)) => {
-                    if batch.num_rows() > 0 {
-                        let watermark: RecordBatchWatermark =
-                            RecordBatchWatermark::try_from(&batch, "_streaming_internal_metadata")?;
-                        let ranges = get_windows_for_watermark(&watermark, self.window_type);
-                        let _ = self.ensure_window_frames_for_ranges(&ranges);
-                        for range in ranges {
-                            let frame = self.window_frames.get_mut(&range.0).unwrap();
-                            let _ = frame.push(&batch);
-                        }
-                        self.process_watermark(watermark);
-
-                        self.trigger_windows()
-                    } else {
-                        Ok(RecordBatch::new_empty(self.output_schema_with_window()))
+        loop {
+            let fired = self
+                .scheduled_emission
+                .as_mut()
+                .is_some_and(|emission| emission.poll_fire(cx).is_ready());
+            if fired {
+                let output = self.emit_open_windows()?;
+                if output.num_rows() > 0 {
+                    return Poll::Ready(Some(Ok(output)));
+                }
+            }
+
+            let message = match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            match message {
+                StreamMessage::Data(batch) => self.process_batch(&batch)?,
+                StreamMessage::Watermark(watermark) => {
+                    self.process_watermark(watermark);
+                    let output = self.trigger_windows()?;
+                    if output.num_rows() > 0 {
+                        return Poll::Ready(Some(Ok(output)));
                     }
                 }
-                Some(Err(e)) => Err(e),
-                None => Ok(RecordBatch::new_empty(self.output_schema_with_window())),
-            },
-            Poll::Pending => {
-                return Poll::Pending;
+                // Window state isn't checkpointed, the barrier is passed on to the operators and
+                // sinks after the window
+                StreamMessage::Barrier(epoch) => {
+                    let control =
+                        control_batch(&self.output_schema_with_window(), Some(epoch), None);
+                    return Poll::Ready(Some(Ok(control)));
+                }
+                StreamMessage::EndOfPartition => return Poll::Ready(None),
             }
-        };
-        Poll::Ready(Some(result))
+        }
     }
 }
 
@@ -361,6 +518,11 @@ impl GroupedAggWindowFrame {
         self.update_memory_reservation()
     }
 
+    /// Bytes held by the groups and accumulators, as of the latest update
+    pub(crate) fn size(&self) -> usize {
+        self.reservation.size()
+    }
+
     fn update_memory_reservation(&mut self) -> Result<()> {
         let acc = self.accumulators.iter().map(|x| x.size()).sum::<usize>();
         self.reservation.try_resize(
@@ -371,9 +533,7 @@ impl GroupedAggWindowFrame {
     }
 
     pub fn push(&mut self, batch: &RecordBatch) -> Result<(), DataFusionError> {
-        let metadata = batch
-            .column_by_name("_streaming_internal_metadata")
-            .unwrap();
+        let metadata = batch.column_by_name(STREAMING_METADATA_COLUMN).unwrap();
         let metadata_struct = metadata.as_any().downcast_ref::<StructArray>().unwrap();
 
         let ts_column = metadata_struct
@@ -406,7 +566,7 @@ impl GroupedAggWindowFrame {
             .as_millis() as i64;
 
         let metadata = filtered_batch
-            .column_by_name("_streaming_internal_metadata")
+            .column_by_name(STREAMING_METADATA_COLUMN)
             .unwrap();
         let metadata_struct = metadata.as_any().downcast_ref::<StructArray>().unwrap();
 
@@ -431,6 +591,80 @@ impl GroupedAggWindowFrame {
         Ok(())
     }
 
+    /// Copy of the frame's groups and accumulator states, the frame itself is left unchanged.
+    pub fn snapshot(&mut self) -> Result<GroupsSnapshot> {
+        let num_groups = self.group_values.len();
+        if num_groups == 0 {
+            return Ok(GroupsSnapshot {
+                group_keys: vec![],
+                states: vec![vec![]; self.accumulators.len()],
+            });
+        }
+
+        // Emitting drains the interning store, interning the keys again in the same order
+        // hands out the same group indices.
+        let group_keys = self.group_values.emit(EmitTo::All)?;
+        self.group_values
+            .intern(&group_keys, &mut self.current_group_indices)?;
+
+        let states = self
+            .accumulators
+            .iter_mut()
+            .map(|acc| snapshot_groups_accumulator(acc.as_mut(), num_groups))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(GroupsSnapshot { group_keys, states })
+    }
+
+    /// Merge a snapshot taken with [`Self::snapshot`] into the frame
+    pub fn restore(&mut self, snapshot: &GroupsSnapshot) -> Result<()> {
+        if snapshot.num_groups() == 0 {
+            return Ok(());
+        }
+        if snapshot.states.len() != self.accumulators.len() {
+            return Err(DataFusionError::Internal(format!(
+                "Snapshot holds state for {} aggregates, the window has {}",
+                snapshot.states.len(),
+                self.accumulators.len()
+            )));
+        }
+
+        let starting_num_groups = self.group_values.len();
+        self.group_values
+            .intern(&snapshot.group_keys, &mut self.current_group_indices)?;
+        let total_num_groups = self.group_values.len();
+        if total_num_groups > starting_num_groups {
+            self.group_ordering.new_groups(
+                &snapshot.group_keys,
+                &self.current_group_indices,
+                total_num_groups,
+            )?;
+        }
+
+        for (acc, state) in self.accumulators.iter_mut().zip(&snapshot.states) {
+            restore_groups_accumulator(
+                acc.as_mut(),
+                state,
+                &self.current_group_indices,
+                total_num_groups,
+            )?;
+        }
+        self.update_memory_reservation()
+    }
+
+    /// Fold the groups and accumulated state of `other` into this frame
+    fn merge(&mut self, other: &mut GroupedAggWindowFrame) -> Result<()> {
+        let snapshot = other.snapshot()?;
+        self.restore(&snapshot)
+    }
+
+    /// Like [`Self::evaluate`], but the groups and their state stay in the frame
+    fn evaluate_in_place(&mut self) -> Result<RecordBatch> {
+        let snapshot = self.snapshot()?;
+        let batch = self.evaluate()?;
+        self.restore(&snapshot)?;
+        Ok(batch)
+    }
+
     /// Create an output RecordBatch with the group keys and
     /// accumulator states/values specified in emit_to
     fn evaluate(&mut self) -> Result<RecordBatch> {
@@ -1,12 +1,12 @@
 use std::{
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use arrow::{
-    array::PrimitiveBuilder, compute::filter_record_batch, datatypes::TimestampMillisecondType,
+use arrow::compute::filter_record_batch;
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, RecordBatch, StringArray, TimestampMillisecondArray,
 };
-use arrow_array::{Array, BooleanArray, RecordBatch};
 use arrow_schema::{DataType, Field, Schema, SchemaBuilder, SchemaRef, TimeUnit};
 use datafusion::{
     common::{downcast_value, DataFusionError, Result},
@@ -14,8 +14,13 @@ use datafusion::{
     physical_expr::GroupsAccumulatorAdapter,
     physical_plan::PhysicalExpr,
 };
+pub mod count_window_stream;
+pub mod global_window;
+pub mod global_window_stream;
 pub mod grouped_window_agg_stream;
+pub mod session_window_stream;
 pub mod streaming_window;
+pub mod window_assigner;
 
 use datafusion::physical_expr::AggregateExpr;
 use log::debug;
@@ -39,7 +44,180 @@ pub(crate) fn create_group_accumulator(
     }
 }
 
-fn add_window_columns_to_schema(schema: SchemaRef) -> Schema {
+/// Why a window emitted a result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FiringReason {
+    /// The watermark passed the end of the window, the result is final
+    Watermark,
+    /// An early result emitted on the window's schedule
+    Schedule,
+    /// A count window received its number of rows
+    Count,
+    /// A count window timed out before it was full
+    Timeout,
+    /// The trigger of a global window fired
+    Trigger,
+    /// Late rows updated a window that already fired, the result replaces the previous one
+    Late,
+}
+
+impl FiringReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Watermark => "watermark",
+            Self::Schedule => "schedule",
+            Self::Count => "count",
+            Self::Timeout => "timeout",
+            Self::Trigger => "trigger",
+            Self::Late => "late",
+        }
+    }
+}
+
+/// What windows do with rows that arrive after the watermark passed the end of their window
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum LateDataPolicy {
+    /// Late rows open their window again, which fires with the next watermark holding just
+    /// the late rows
+    #[default]
+    Reopen,
+    /// Late rows are discarded
+    Drop,
+    /// Late rows are sent to the side output of the tag
+    SideOutput(String),
+    /// Windows are kept for the allowed lateness after the watermark passed their end. Rows
+    /// arriving in that time update their window, whose result is emitted again with the
+    /// `late` firing reason. Rows arriving later are discarded.
+    UpdateAndEmit(Duration),
+}
+
+impl LateDataPolicy {
+    /// How long windows are kept around after the watermark passed their end
+    pub fn allowed_lateness(&self) -> Duration {
+        match self {
+            Self::UpdateAndEmit(lateness) => *lateness,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// The metadata columns appended to window results, and their names. Columns without a name
+/// are left out, by default only the start and end of the window are added.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WindowColumns {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    /// The last millisecond that belongs to the window
+    pub time: Option<String>,
+    /// `false` for early results that a later one replaces
+    pub is_final: Option<String>,
+    /// `watermark`, `schedule`, `count`, `timeout`, `trigger` or `late`, see [`FiringReason`]
+    pub firing_reason: Option<String>,
+}
+
+impl Default for WindowColumns {
+    fn default() -> Self {
+        Self {
+            start: Some("window_start_time".to_string()),
+            end: Some("window_end_time".to_string()),
+            time: None,
+            is_final: None,
+            firing_reason: None,
+        }
+    }
+}
+
+impl WindowColumns {
+    /// No metadata columns at all
+    pub fn none() -> Self {
+        Self {
+            start: None,
+            end: None,
+            time: None,
+            is_final: None,
+            firing_reason: None,
+        }
+    }
+
+    pub fn with_start(mut self, name: Option<&str>) -> Self {
+        self.start = name.map(str::to_string);
+        self
+    }
+
+    pub fn with_end(mut self, name: Option<&str>) -> Self {
+        self.end = name.map(str::to_string);
+        self
+    }
+
+    pub fn with_time(mut self, name: Option<&str>) -> Self {
+        self.time = name.map(str::to_string);
+        self
+    }
+
+    pub fn with_is_final(mut self, name: Option<&str>) -> Self {
+        self.is_final = name.map(str::to_string);
+        self
+    }
+
+    pub fn with_firing_reason(mut self, name: Option<&str>) -> Self {
+        self.firing_reason = name.map(str::to_string);
+        self
+    }
+
+    pub fn fields(&self) -> Vec<Field> {
+        let timestamp = DataType::Timestamp(TimeUnit::Millisecond, None);
+        [
+            (&self.start, timestamp.clone()),
+            (&self.end, timestamp.clone()),
+            (&self.time, timestamp),
+            (&self.is_final, DataType::Boolean),
+            (&self.firing_reason, DataType::Utf8),
+        ]
+        .into_iter()
+        .filter_map(|(name, data_type)| {
+            name.as_ref().map(|name| Field::new(name, data_type, false))
+        })
+        .collect()
+    }
+
+    fn arrays(
+        &self,
+        num_rows: usize,
+        start_ms: i64,
+        end_ms: i64,
+        reason: FiringReason,
+    ) -> Vec<ArrayRef> {
+        let mut arrays: Vec<ArrayRef> = vec![];
+        if self.start.is_some() {
+            arrays.push(Arc::new(TimestampMillisecondArray::from_value(
+                start_ms, num_rows,
+            )));
+        }
+        if self.end.is_some() {
+            arrays.push(Arc::new(TimestampMillisecondArray::from_value(
+                end_ms, num_rows,
+            )));
+        }
+        if self.time.is_some() {
+            arrays.push(Arc::new(TimestampMillisecondArray::from_value(
+                end_ms - 1,
+                num_rows,
+            )));
+        }
+        if self.is_final.is_some() {
+            let is_final = reason != FiringReason::Schedule;
+            arrays.push(Arc::new(BooleanArray::from(vec![is_final; num_rows])));
+        }
+        if self.firing_reason.is_some() {
+            arrays.push(Arc::new(StringArray::from_iter_values(
+                std::iter::repeat(reason.as_str()).take(num_rows),
+            )));
+        }
+        arrays
+    }
+}
+
+fn add_window_columns_to_schema(schema: SchemaRef, columns: &WindowColumns) -> Schema {
     let fields = schema.flattened_fields().to_owned();
 
     let mut builder = SchemaBuilder::new();
@@ -47,16 +225,9 @@ fn add_window_columns_to_schema(schema: SchemaRef) -> Schema {
     for field in fields {
         builder.push(field.clone());
     }
-    builder.push(Field::new(
-        "window_start_time",
-        DataType::Timestamp(TimeUnit::Millisecond, None),
-        false,
-    ));
-    builder.push(Field::new(
-        "window_end_time",
-        DataType::Timestamp(TimeUnit::Millisecond, None),
-        false,
-    ));
+    for field in columns.fields() {
+        builder.push(field);
+    }
 
     builder.finish()
 }
@@ -65,25 +236,20 @@ fn add_window_columns_to_record_batch(
     record_batch: RecordBatch,
     start_time: SystemTime,
     end_time: SystemTime,
+    columns: &WindowColumns,
+    reason: FiringReason,
 ) -> RecordBatch {
     let start_time_duration = start_time.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
     let end_time_duration = end_time.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
 
-    let mut start_builder = PrimitiveBuilder::<TimestampMillisecondType>::new();
-    let mut end_builder = PrimitiveBuilder::<TimestampMillisecondType>::new();
-
-    for _ in 0..record_batch.num_rows() {
-        start_builder.append_value(start_time_duration);
-        end_builder.append_value(end_time_duration);
-    }
-
-    let start_array = start_builder.finish();
-    let end_array = end_builder.finish();
-
-    let new_schema = add_window_columns_to_schema(record_batch.schema());
+    let new_schema = add_window_columns_to_schema(record_batch.schema(), columns);
     let mut new_columns = record_batch.columns().to_vec();
-    new_columns.push(Arc::new(start_array));
-    new_columns.push(Arc::new(end_array));
+    new_columns.extend(columns.arrays(
+        record_batch.num_rows(),
+        start_time_duration,
+        end_time_duration,
+        reason,
+    ));
 
     RecordBatch::try_new(Arc::new(new_schema), new_columns).unwrap()
 }
@@ -92,7 +258,10 @@ pub fn as_boolean_array(array: &dyn Array) -> Result<&BooleanArray> {
     Ok(downcast_value!(array, BooleanArray))
 }
 
-fn batch_filter(batch: &RecordBatch, predicate: &Arc<dyn PhysicalExpr>) -> Result<RecordBatch> {
+pub(crate) fn batch_filter(
+    batch: &RecordBatch,
+    predicate: &Arc<dyn PhysicalExpr>,
+) -> Result<RecordBatch> {
     predicate
         .evaluate(batch)
         .and_then(|v| v.into_array(batch.num_rows()))
@@ -102,3 +271,63 @@ fn batch_filter(batch: &RecordBatch, predicate: &Arc<dyn PhysicalExpr>) -> Resul
                 .and_then(|filter_array| Ok(filter_record_batch(batch, filter_array)?))
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use arrow_array::Int64Array;
+
+    #[test]
+    fn renamed_window_columns() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "count",
+            DataType::Int64,
+            false,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![3, 4]))]).unwrap();
+        let columns = WindowColumns::default()
+            .with_start(Some("window_start"))
+            .with_end(None)
+            .with_time(Some("window_time"))
+            .with_is_final(Some("is_final"))
+            .with_firing_reason(Some("firing_reason"));
+
+        let start = UNIX_EPOCH + Duration::from_secs(60);
+        let end = UNIX_EPOCH + Duration::from_secs(120);
+        let batch =
+            add_window_columns_to_record_batch(batch, start, end, &columns, FiringReason::Schedule);
+
+        let names: Vec<&str> = batch
+            .schema_ref()
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "count",
+                "window_start",
+                "window_time",
+                "is_final",
+                "firing_reason"
+            ]
+        );
+        assert_eq!(
+            batch.column(2).as_ref(),
+            &TimestampMillisecondArray::from_value(119_999, 2)
+        );
+        assert_eq!(
+            batch.column(3).as_ref(),
+            &BooleanArray::from(vec![false; 2])
+        );
+        assert_eq!(
+            batch.column(4).as_ref(),
+            &StringArray::from(vec!["schedule"; 2])
+        );
+    }
+}
@@ -0,0 +1,572 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use arrow::{
+    array::AsArray,
+    compute::{cast, concat_batches, take_record_batch},
+    datatypes::{Int64Type, TimestampMillisecondType},
+};
+use arrow_array::{Array, ArrayRef, Int64Array, RecordBatch, UInt32Array};
+use arrow_schema::{DataType, SchemaRef, TimeUnit};
+use datafusion::{
+    common::{instant::Instant, not_impl_err, DataFusionError, Result, ScalarValue},
+    execution::{RecordBatchStream, TaskContext},
+    physical_plan::{
+        aggregates::{aggregate_expressions, finalize_aggregation, AggregateMode, PhysicalGroupBy},
+        metrics::BaselineMetrics,
+        AggregateExpr, PhysicalExpr,
+    },
+};
+use futures::{ready, Stream, StreamExt};
+use log::debug;
+
+use crate::physical_plan::utils::{
+    accumulators::{create_accumulators, merge_accumulators, AccumulatorItem},
+    cold_state::{decompress, ColdState},
+    cron::ScheduledEmission,
+    metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN},
+    state_metrics::StateMetrics,
+    stream_message::{control_batch, MessageStream, StreamMessage},
+    time::{system_time_from_epoch, WindowTimezone},
+};
+
+use super::{
+    add_window_columns_to_record_batch, add_window_columns_to_schema,
+    grouped_window_agg_stream::evaluate_group_by,
+    streaming_window::{aggregate_batch, FranzStreamingWindowExec, LateRows},
+    FiringReason, WindowColumns,
+};
+
+/// The open session of one key, covering `[start_ms, end_ms)`
+struct Session {
+    id: u64,
+    start_ms: i64,
+    end_ms: i64,
+    accumulators: Vec<AccumulatorItem>,
+    /// The accumulators compressed once the key went cold, `accumulators` is empty meanwhile
+    compressed: Option<Vec<u8>>,
+    /// When rows were last added to the session
+    touched: Instant,
+}
+
+impl Session {
+    fn size(&self) -> usize {
+        let compressed = self.compressed.as_ref().map_or(0, Vec::len);
+        compressed
+            + self
+                .accumulators
+                .iter()
+                .map(|acc| acc.size())
+                .sum::<usize>()
+    }
+
+    /// The accumulators of the session, decompressed if it went cold
+    fn accumulators(
+        &mut self,
+        aggregate_expressions: &[Arc<dyn AggregateExpr>],
+    ) -> Result<&mut Vec<AccumulatorItem>> {
+        if let Some(compressed) = self.compressed.take() {
+            self.accumulators = decompress(&compressed, aggregate_expressions)?;
+        }
+        Ok(&mut self.accumulators)
+    }
+}
+
+/// Session windows with a gap that is evaluated for every row, so that keys can have
+/// different gaps.
+///
+/// A row covers its event time up to its event time plus its gap, and the sessions of a key
+/// that overlap are merged. A session fires once the watermark passes its end.
+pub struct SessionWindowStream {
+    schema: SchemaRef,
+    input: MessageStream,
+    baseline_metrics: BaselineMetrics,
+    exec_aggregate_expressions: Vec<Arc<dyn AggregateExpr>>,
+    aggregate_expressions: Vec<Vec<Arc<dyn PhysicalExpr>>>,
+    filter_expressions: Vec<Option<Arc<dyn PhysicalExpr>>>,
+    group_by: PhysicalGroupBy,
+    gap: Arc<dyn PhysicalExpr>,
+    sessions: HashMap<Vec<ScalarValue>, Vec<Session>>,
+    next_session_id: u64,
+    latest_watermark: Option<SystemTime>,
+    late_data: LateRows,
+    state_metrics: StateMetrics,
+    cold_state: Option<ColdState>,
+    scheduled_emission: Option<ScheduledEmission>,
+    window_columns: WindowColumns,
+    aggregation_mode: AggregateMode,
+}
+
+impl SessionWindowStream {
+    pub fn new(
+        exec_operator: &FranzStreamingWindowExec,
+        context: Arc<TaskContext>,
+        partition: usize,
+        gap: Arc<dyn PhysicalExpr>,
+    ) -> Result<Self> {
+        if exec_operator.group_by.groups.len() > 1 {
+            return not_impl_err!("Grouping sets are not supported in session windows");
+        }
+
+        let input = MessageStream::new(
+            exec_operator
+                .input
+                .execute(partition, Arc::clone(&context))?,
+        );
+        let late_data = LateRows::try_new(
+            &exec_operator.late_data,
+            &context,
+            exec_operator.input.schema(),
+        )?;
+        if let LateRows::UpdateAndEmit(_) = late_data {
+            return not_impl_err!("Allowed lateness is not supported in session windows");
+        }
+        let aggregate_expressions =
+            aggregate_expressions(&exec_operator.aggregate_expressions, &exec_operator.mode, 0)?;
+        let filter_expressions = match exec_operator.mode {
+            AggregateMode::Partial | AggregateMode::Single | AggregateMode::SinglePartitioned => {
+                exec_operator.filter_expressions.clone()
+            }
+            AggregateMode::Final | AggregateMode::FinalPartitioned => {
+                vec![None; exec_operator.aggregate_expressions.len()]
+            }
+        };
+        let timezone = WindowTimezone::from_task_context(&context)?;
+
+        Ok(Self {
+            schema: Arc::clone(&exec_operator.schema),
+            input,
+            baseline_metrics: BaselineMetrics::new(&exec_operator.metrics, partition),
+            exec_aggregate_expressions: exec_operator.aggregate_expressions.clone(),
+            aggregate_expressions,
+            filter_expressions,
+            group_by: exec_operator.group_by.clone(),
+            gap,
+            sessions: HashMap::new(),
+            next_session_id: 0,
+            latest_watermark: None,
+            late_data,
+            state_metrics: StateMetrics::new(&exec_operator.metrics, partition),
+            cold_state: ColdState::from_task_context(&context),
+            scheduled_emission: exec_operator
+                .emit_schedule
+                .clone()
+                .map(|schedule| ScheduledEmission::new(schedule, timezone)),
+            window_columns: exec_operator.window_columns.clone(),
+            aggregation_mode: exec_operator.mode,
+        })
+    }
+
+    pub fn output_schema_with_window(&self) -> SchemaRef {
+        Arc::new(add_window_columns_to_schema(
+            self.schema.clone(),
+            &self.window_columns,
+        ))
+    }
+
+    fn process_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let timestamps = batch
+            .column_by_name(STREAMING_METADATA_COLUMN)
+            .unwrap()
+            .as_struct()
+            .column_by_name(CANONICAL_TIMESTAMP_FIELD)
+            .unwrap()
+            .as_primitive::<TimestampMillisecondType>()
+            .clone();
+        let gaps = gap_millis(&self.gap.evaluate(batch)?.into_array(batch.num_rows())?)?;
+        let keys = evaluate_group_by(&self.group_by, batch)?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let watermark_ms = self.latest_watermark.map(epoch_millis);
+
+        let mut late = vec![];
+        let mut assigned = vec![];
+        let mut merged_ids = HashMap::new();
+        for row in 0..batch.num_rows() {
+            if timestamps.is_null(row) || gaps.is_null(row) {
+                continue;
+            }
+            let start_ms = timestamps.value(row);
+            let end_ms = start_ms + gaps.value(row).max(1);
+            let closed = watermark_ms.is_some_and(|watermark| end_ms <= watermark);
+            if closed && !matches!(self.late_data, LateRows::Reopen) {
+                late.push(row as u32);
+                continue;
+            }
+            let key = keys
+                .iter()
+                .map(|column| ScalarValue::try_from_array(column, row))
+                .collect::<Result<Vec<_>>>()?;
+            let id = self.assign_session(&key, start_ms, end_ms, &mut merged_ids)?;
+            assigned.push((key, id, row as u32));
+        }
+
+        // Rows assigned before a merge still point at the session that was merged away
+        let mut rows_by_session: HashMap<(Vec<ScalarValue>, u64), Vec<u32>> = HashMap::new();
+        for (key, mut id, row) in assigned {
+            while let Some(merged_into) = merged_ids.get(&id) {
+                id = *merged_into;
+            }
+            rows_by_session.entry((key, id)).or_default().push(row);
+        }
+        for ((key, id), rows) in rows_by_session {
+            let session = self
+                .sessions
+                .get_mut(&key)
+                .and_then(|sessions| sessions.iter_mut().find(|session| session.id == id))
+                .ok_or_else(|| DataFusionError::Internal(format!("Unknown session {id}")))?;
+            session.touched = Instant::now();
+            aggregate_batch(
+                &self.aggregation_mode,
+                take_record_batch(batch, &UInt32Array::from(rows))?,
+                session.accumulators(&self.exec_aggregate_expressions)?,
+                &self.aggregate_expressions,
+                &self.filter_expressions,
+            )?;
+        }
+
+        if let LateRows::SideOutput(late_data) = &self.late_data {
+            if !late.is_empty() {
+                late_data.emit(take_record_batch(batch, &UInt32Array::from(late))?);
+            }
+        }
+        Ok(())
+    }
+
+    /// The session of `key` covering `[start_ms, end_ms)`, merging the sessions it overlaps.
+    /// Merged away sessions are recorded in `merged_ids`.
+    fn assign_session(
+        &mut self,
+        key: &[ScalarValue],
+        start_ms: i64,
+        end_ms: i64,
+        merged_ids: &mut HashMap<u64, u64>,
+    ) -> Result<u64> {
+        let sessions = self.sessions.entry(key.to_vec()).or_default();
+        let overlapping: Vec<usize> = sessions
+            .iter()
+            .enumerate()
+            .filter(|(_, session)| session.start_ms < end_ms && start_ms < session.end_ms)
+            .map(|(index, _)| index)
+            .collect();
+
+        let Some(&first) = overlapping.first() else {
+            let id = self.next_session_id;
+            self.next_session_id += 1;
+            sessions.push(Session {
+                id,
+                start_ms,
+                end_ms,
+                accumulators: create_accumulators(&self.exec_aggregate_expressions)?,
+                compressed: None,
+                touched: Instant::now(),
+            });
+            return Ok(id);
+        };
+
+        // Back to front, so that the remaining indices stay valid
+        for &index in overlapping[1..].iter().rev() {
+            let mut merged = sessions.remove(index);
+            let target = &mut sessions[first];
+            target.start_ms = target.start_ms.min(merged.start_ms);
+            target.end_ms = target.end_ms.max(merged.end_ms);
+            let aggregate_expressions = &self.exec_aggregate_expressions;
+            merge_accumulators(
+                target.accumulators(aggregate_expressions)?,
+                merged.accumulators(aggregate_expressions)?,
+            )?;
+            target.touched = target.touched.max(merged.touched);
+            merged_ids.insert(merged.id, target.id);
+        }
+        let target = &mut sessions[first];
+        target.start_ms = target.start_ms.min(start_ms);
+        target.end_ms = target.end_ms.max(end_ms);
+        Ok(target.id)
+    }
+
+    /// Results of the sessions the watermark closed, which are removed, or of every open
+    /// session for scheduled emissions
+    fn emit_sessions(&mut self, reason: FiringReason) -> Result<RecordBatch> {
+        let watermark_ms = self.latest_watermark.map(epoch_millis);
+        let closed = |session: &Session| watermark_ms.is_some_and(|wm| session.end_ms <= wm);
+
+        let mut results = vec![];
+        let (mut reclaimed_sessions, mut reclaimed_bytes) = (0, 0);
+        for (key, sessions) in self.sessions.iter_mut() {
+            for session in sessions.iter_mut() {
+                if reason == FiringReason::Schedule || closed(session) {
+                    results.push(keyed_window_result(
+                        &self.schema,
+                        key,
+                        session.accumulators(&self.exec_aggregate_expressions)?,
+                        &self.aggregation_mode,
+                        (session.start_ms, session.end_ms),
+                        &self.window_columns,
+                        reason,
+                    )?);
+                }
+            }
+            if reason == FiringReason::Watermark {
+                sessions.retain(|session| {
+                    if closed(session) {
+                        reclaimed_sessions += 1;
+                        reclaimed_bytes += session.size();
+                        return false;
+                    }
+                    true
+                });
+            }
+        }
+        self.sessions.retain(|_, sessions| !sessions.is_empty());
+        if let (FiringReason::Watermark, Some(watermark)) = (reason, self.latest_watermark) {
+            self.compress_cold_sessions()?;
+            let remaining = self.sessions.values().flatten().map(Session::size).sum();
+            self.state_metrics.record_reclaimed(
+                watermark,
+                reclaimed_sessions,
+                reclaimed_bytes,
+                remaining,
+            );
+        }
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    /// Compress the accumulators of the sessions that went without rows for long enough
+    fn compress_cold_sessions(&mut self) -> Result<()> {
+        let Some(cold_state) = self.cold_state else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let (mut compressed_sessions, mut saved_bytes) = (0, 0);
+        for session in self.sessions.values_mut().flatten() {
+            if session.compressed.is_some() || !cold_state.is_cold(session.touched, now) {
+                continue;
+            }
+            let size = session.size();
+            let compressed = cold_state.compress(&mut session.accumulators)?;
+            saved_bytes += size.saturating_sub(compressed.len());
+            session.accumulators = vec![];
+            session.compressed = Some(compressed);
+            compressed_sessions += 1;
+        }
+        if compressed_sessions > 0 {
+            debug!("Compressed {compressed_sessions} cold sessions, saving {saved_bytes} bytes");
+        }
+        Ok(())
+    }
+
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            let fired = self
+                .scheduled_emission
+                .as_mut()
+                .is_some_and(|emission| emission.poll_fire(cx).is_ready());
+            if fired {
+                let output = self.emit_sessions(FiringReason::Schedule)?;
+                if output.num_rows() > 0 {
+                    return Poll::Ready(Some(Ok(output)));
+                }
+            }
+
+            let message = match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            match message {
+                StreamMessage::Data(batch) => self.process_batch(&batch)?,
+                StreamMessage::Watermark(watermark) => {
+                    self.latest_watermark = self.latest_watermark.max(Some(watermark));
+                    let output = self.emit_sessions(FiringReason::Watermark)?;
+                    if output.num_rows() > 0 {
+                        return Poll::Ready(Some(Ok(output)));
+                    }
+                }
+                // Window state isn't checkpointed, the barrier is passed on to the operators and
+                // sinks after the window
+                StreamMessage::Barrier(epoch) => {
+                    let control =
+                        control_batch(&self.output_schema_with_window(), Some(epoch), None);
+                    return Poll::Ready(Some(Ok(control)));
+                }
+                StreamMessage::EndOfPartition => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// One row of results for the window `[start_ms, end_ms)` of `key`
+pub(super) fn keyed_window_result(
+    schema: &SchemaRef,
+    key: &[ScalarValue],
+    accumulators: &mut [AccumulatorItem],
+    mode: &AggregateMode,
+    (start_ms, end_ms): (i64, i64),
+    window_columns: &WindowColumns,
+    reason: FiringReason,
+) -> Result<RecordBatch> {
+    let mut columns = key
+        .iter()
+        .map(|value| value.to_array())
+        .collect::<Result<Vec<_>>>()?;
+    // Accumulators keep their state when evaluated, so open windows can be emitted early
+    columns.extend(finalize_aggregation(accumulators, mode)?);
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    Ok(add_window_columns_to_record_batch(
+        batch,
+        system_time_from_epoch(start_ms),
+        system_time_from_epoch(end_ms),
+        window_columns,
+        reason,
+    ))
+}
+
+/// Gaps are given in milliseconds, or as durations of any unit
+fn gap_millis(gap: &ArrayRef) -> Result<Int64Array> {
+    let gap = match gap.data_type() {
+        DataType::Duration(_) => cast(
+            &cast(gap, &DataType::Duration(TimeUnit::Millisecond))?,
+            &DataType::Int64,
+        )?,
+        _ => cast(gap, &DataType::Int64)?,
+    };
+    Ok(gap.as_primitive::<Int64Type>().clone())
+}
+
+fn epoch_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64
+}
+
+impl RecordBatchStream for SessionWindowStream {
+    fn schema(&self) -> SchemaRef {
+        self.output_schema_with_window()
+    }
+}
+
+impl Stream for SessionWindowStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use arrow_array::{DurationSecondArray, Int32Array, StringArray, TimestampMillisecondArray};
+    use arrow_schema::{Field, Schema};
+    use datafusion::datasource::MemTable;
+    use datafusion::functions_aggregate::count::count;
+    use datafusion::logical_expr::col;
+
+    use crate::context::Context;
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+    #[tokio::test]
+    async fn sessions_with_per_row_gaps() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("user", DataType::Utf8, false),
+            Field::new("gap", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let batch = |rows: Vec<(&str, i64, i64)>| {
+            let users: Vec<&str> = rows.iter().map(|(user, ..)| *user).collect();
+            let gaps: Vec<i64> = rows.iter().map(|(_, gap, _)| *gap).collect();
+            let times: Vec<i64> = rows.iter().map(|(.., time)| *time).collect();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(StringArray::from(users)),
+                    Arc::new(Int64Array::from(gaps)),
+                    Arc::new(stream_metadata_array(TimestampMillisecondArray::from(
+                        times,
+                    ))),
+                ],
+            )
+        };
+        let batches = vec![
+            // The last row of `a` bridges its two earlier sessions after rows were assigned
+            // to both, the short gap of `b` keeps its rows apart
+            batch(vec![
+                ("a", 500, 1_000),
+                ("b", 100, 1_000),
+                ("a", 500, 3_000),
+                ("b", 100, 1_200),
+                ("a", 2_000, 1_400),
+            ])?,
+            // Moves the watermark past every earlier session
+            batch(vec![("c", 100, 10_000)])?,
+        ];
+        let table = MemTable::try_new(schema.clone(), vec![batches])?;
+        let ds = Context::new()?
+            .from_source("clicks", Arc::new(table))
+            .await?
+            .session_window(
+                vec![col("user")],
+                vec![count(col("user")).alias("count")],
+                col("gap"),
+            )?;
+
+        let mut output = ds.df.as_ref().clone().execute_stream().await?;
+        let batch = loop {
+            match output.next().await.transpose()? {
+                Some(batch) if batch.num_rows() > 0 => break batch,
+                Some(_) => {}
+                None => panic!("no session fired"),
+            }
+        };
+        let column = |name: &str| batch.column_by_name(name).unwrap();
+        let sessions: BTreeMap<(String, i64, i64), i64> = (0..batch.num_rows())
+            .map(|row| {
+                (
+                    (
+                        column("user").as_string::<i32>().value(row).to_string(),
+                        column("window_start_time")
+                            .as_primitive::<TimestampMillisecondType>()
+                            .value(row),
+                        column("window_end_time")
+                            .as_primitive::<TimestampMillisecondType>()
+                            .value(row),
+                    ),
+                    column("count").as_primitive::<Int64Type>().value(row),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sessions,
+            BTreeMap::from([
+                (("a".to_string(), 1_000, 3_500), 3),
+                (("b".to_string(), 1_000, 1_100), 1),
+                (("b".to_string(), 1_200, 1_300), 1),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gaps_in_milliseconds() {
+        let seconds: ArrayRef = Arc::new(DurationSecondArray::from(vec![Some(30), None]));
+        assert_eq!(
+            gap_millis(&seconds).unwrap(),
+            Int64Array::from(vec![Some(30_000), None])
+        );
+
+        let millis: ArrayRef = Arc::new(Int32Array::from(vec![500]));
+        assert_eq!(gap_millis(&millis).unwrap(), Int64Array::from(vec![500]));
+    }
+}
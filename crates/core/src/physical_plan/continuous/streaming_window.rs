@@ -1,18 +1,21 @@
 use std::{
     any::Any,
     borrow::Cow,
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::Arc,
     task::{Context, Poll},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use arrow::{
-    compute::{concat_batches, filter_record_batch},
+    array::AsArray,
+    compute::{concat_batches, filter_record_batch, not},
     datatypes::TimestampMillisecondType,
 };
-use arrow_array::{Array, PrimitiveArray, RecordBatch, StructArray, TimestampMillisecondArray};
+use arrow_array::{
+    Array, BooleanArray, PrimitiveArray, RecordBatch, StructArray, TimestampMillisecondArray,
+};
 use arrow_ord::cmp;
 use arrow_schema::{Field, Schema, SchemaRef};
 
@@ -33,14 +36,23 @@ use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
     InputOrderMode, PlanProperties,
 };
-use futures::{Stream, StreamExt};
+use futures::{ready, Stream, StreamExt};
 use tracing::debug;
 
+use crate::datasource::side_output::{SideOutput, SideOutputRegistry};
 use crate::physical_plan::{
-    continuous::grouped_window_agg_stream::GroupedWindowAggStream,
+    continuous::{
+        count_window_stream::CountWindowStream, global_window_stream::GlobalWindowStream,
+        grouped_window_agg_stream::GroupedWindowAggStream,
+        session_window_stream::SessionWindowStream,
+    },
     utils::{
-        accumulators::{create_accumulators, AccumulatorItem},
-        time::RecordBatchWatermark,
+        accumulators::{create_accumulators, merge_accumulators, AccumulatorItem},
+        cron::{CronSchedule, ScheduledEmission},
+        metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN},
+        state_metrics::StateMetrics,
+        stream_message::{control_batch, MessageStream, StreamMessage},
+        time::{system_time_from_epoch, CalendarInterval, RecordBatchWatermark, WindowTimezone},
     },
 };
 
@@ -76,7 +88,12 @@ impl DisplayAs for FranzWindowFrame {
 
 use datafusion::common::Result;
 
-use super::{add_window_columns_to_record_batch, add_window_columns_to_schema, batch_filter};
+use super::global_window::GlobalWindow;
+use super::window_assigner::{CustomWindow, WindowMergePolicy};
+use super::{
+    add_window_columns_to_record_batch, add_window_columns_to_schema, batch_filter, FiringReason,
+    LateDataPolicy, WindowColumns,
+};
 
 impl FranzWindowFrame {
     pub fn new(
@@ -104,9 +121,7 @@ impl FranzWindowFrame {
     }
 
     pub fn push(&mut self, batch: &RecordBatch) -> Result<(), DataFusionError> {
-        let metadata = batch
-            .column_by_name("_streaming_internal_metadata")
-            .unwrap();
+        let metadata = batch.column_by_name(STREAMING_METADATA_COLUMN).unwrap();
         let metadata_struct = metadata.as_any().downcast_ref::<StructArray>().unwrap();
 
         let ts_column = metadata_struct
@@ -139,7 +154,7 @@ impl FranzWindowFrame {
             .as_millis() as i64;
 
         let metadata = filtered_batch
-            .column_by_name("_streaming_internal_metadata")
+            .column_by_name(STREAMING_METADATA_COLUMN)
             .unwrap();
         let metadata_struct = metadata.as_any().downcast_ref::<StructArray>().unwrap();
 
@@ -169,6 +184,16 @@ impl FranzWindowFrame {
         Ok(())
     }
 
+    /// Fold the accumulated state of `other` into this frame
+    fn merge(&mut self, other: &mut FranzWindowFrame) -> Result<()> {
+        merge_accumulators(&mut self.accumulators, &mut other.accumulators)
+    }
+
+    /// Bytes held by the accumulators
+    pub(crate) fn size(&self) -> usize {
+        self.accumulators.iter().map(|acc| acc.size()).sum()
+    }
+
     pub fn evaluate(&mut self) -> Result<RecordBatch, DataFusionError> {
         let timer = self.baseline_metrics.elapsed_compute().timer();
         let result = finalize_aggregation(&mut self.accumulators, &self.aggregation_mode).and_then(
@@ -180,11 +205,28 @@ impl FranzWindowFrame {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum FranzStreamingWindowType {
-    Session(Duration),
+    /// Gap in milliseconds or as a duration, evaluated for every row
+    Session(Arc<dyn PhysicalExpr>),
     Sliding(Duration, Duration),
     Tumbling(Duration),
+    Calendar(CalendarInterval),
+    Custom(CustomWindow),
+    /// Every so many rows per key, or once the window has been open for the timeout
+    Count(usize, Option<Duration>),
+    Global(GlobalWindow),
+}
+
+impl FranzStreamingWindowType {
+    /// Whether overlapping windows are combined into one
+    pub(crate) fn merges_windows(&self) -> bool {
+        matches!(
+            self,
+            Self::Custom(CustomWindow(assigner))
+                if assigner.merge_policy() == WindowMergePolicy::MergeOverlapping
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -196,8 +238,13 @@ pub struct FranzStreamingWindowExec {
     pub group_by: PhysicalGroupBy,
     pub schema: SchemaRef,
     pub input_schema: SchemaRef,
+    /// What happens to rows that arrive after their window was triggered
+    pub late_data: LateDataPolicy,
+    /// Emit the current results of open windows whenever the schedule fires
+    pub emit_schedule: Option<CronSchedule>,
+    /// Metadata columns appended to the results
+    pub window_columns: WindowColumns,
 
-    pub watermark: Arc<Mutex<Option<SystemTime>>>,
     pub(crate) metrics: ExecutionPlanMetricsSet,
     cache: PlanProperties,
     pub mode: AggregateMode,
@@ -288,7 +335,10 @@ impl FranzStreamingWindowExec {
 
         let cache = FranzStreamingWindowExec::compute_properties(
             &input,
-            Arc::new(add_window_columns_to_schema(schema.clone())),
+            Arc::new(add_window_columns_to_schema(
+                schema.clone(),
+                &WindowColumns::default(),
+            )),
             &projection_mapping,
             &mode,
             &input_order_mode,
@@ -301,7 +351,9 @@ impl FranzStreamingWindowExec {
             group_by,
             schema,
             input_schema,
-            watermark: Arc::new(Mutex::new(None)),
+            late_data: LateDataPolicy::default(),
+            emit_schedule: None,
+            window_columns: WindowColumns::default(),
             metrics: ExecutionPlanMetricsSet::new(),
             cache,
             mode,
@@ -309,6 +361,34 @@ impl FranzStreamingWindowExec {
         })
     }
 
+    /// Handle rows that arrive after their window was triggered as `late_data` says
+    pub fn with_late_data(mut self, late_data: LateDataPolicy) -> Self {
+        self.late_data = late_data;
+        self
+    }
+
+    pub fn with_emit_schedule(mut self, emit_schedule: Option<CronSchedule>) -> Self {
+        self.emit_schedule = emit_schedule;
+        self
+    }
+
+    pub fn with_window_columns(mut self, window_columns: WindowColumns) -> Result<Self> {
+        let projection_mapping =
+            ProjectionMapping::try_new(&self.group_by.expr, &self.input.schema())?;
+        self.cache = FranzStreamingWindowExec::compute_properties(
+            &self.input,
+            Arc::new(add_window_columns_to_schema(
+                self.schema.clone(),
+                &window_columns,
+            )),
+            &projection_mapping,
+            &self.mode,
+            &InputOrderMode::Linear,
+        );
+        self.window_columns = window_columns;
+        Ok(self)
+    }
+
     /// Input plan
     pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
         &self.input
@@ -391,15 +471,20 @@ impl ExecutionPlan for FranzStreamingWindowExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(FranzStreamingWindowExec::try_new(
-            self.mode,
-            self.group_by.clone(),
-            self.aggregate_expressions.clone(),
-            self.filter_expressions.clone(),
-            children[0].clone(),
-            self.input_schema.clone(),
-            self.window_type,
-        )?))
+        Ok(Arc::new(
+            FranzStreamingWindowExec::try_new(
+                self.mode,
+                self.group_by.clone(),
+                self.aggregate_expressions.clone(),
+                self.filter_expressions.clone(),
+                children[0].clone(),
+                self.input_schema.clone(),
+                self.window_type.clone(),
+            )?
+            .with_late_data(self.late_data.clone())
+            .with_emit_schedule(self.emit_schedule.clone())
+            .with_window_columns(self.window_columns.clone())?,
+        ))
     }
 
     fn execute(
@@ -407,14 +492,34 @@ impl ExecutionPlan for FranzStreamingWindowExec {
         partition: usize,
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        if self.group_by.is_empty() {
+        if let FranzStreamingWindowType::Global(window) = &self.window_type {
+            debug!("Creating a GlobalWindowStream");
+            Ok(Box::pin(GlobalWindowStream::new(
+                self,
+                context,
+                partition,
+                window.clone(),
+            )?))
+        } else if let FranzStreamingWindowType::Count(size, timeout) = &self.window_type {
+            debug!("Creating a CountWindowStream");
+            Ok(Box::pin(CountWindowStream::new(
+                self, context, partition, *size, *timeout,
+            )?))
+        } else if let FranzStreamingWindowType::Session(gap) = &self.window_type {
+            debug!("Creating a SessionWindowStream");
+            Ok(Box::pin(SessionWindowStream::new(
+                self,
+                context,
+                partition,
+                Arc::clone(gap),
+            )?))
+        } else if self.group_by.is_empty() {
             debug!("GROUP BY expression is empty creating a SimpleWindowAggStream");
             Ok(Box::pin(WindowAggStream::new(
                 self,
                 context,
                 partition,
-                self.watermark.clone(),
-                self.window_type,
+                self.window_type.clone(),
                 self.mode,
             )?))
         } else {
@@ -423,8 +528,7 @@ impl ExecutionPlan for FranzStreamingWindowExec {
                 self,
                 context,
                 partition,
-                self.watermark.clone(),
-                self.window_type,
+                self.window_type.clone(),
                 self.mode,
             )?))
         }
@@ -476,7 +580,10 @@ impl ExecutionPlan for FranzStreamingWindowExec {
     }
 
     fn schema(&self) -> SchemaRef {
-        Arc::new(add_window_columns_to_schema(self.schema.clone()))
+        Arc::new(add_window_columns_to_schema(
+            self.schema.clone(),
+            &self.window_columns,
+        ))
     }
 
     fn repartitioned(
@@ -549,6 +656,17 @@ impl DisplayAs for FranzStreamingWindowExec {
                     .collect();
                 write!(f, ", aggr=[{}]", a.join(", "))?;
                 write!(f, ", window_type=[{:?}]", self.window_type)?;
+                match &self.late_data {
+                    LateDataPolicy::Reopen => {}
+                    LateDataPolicy::Drop => write!(f, ", late_data=[drop]")?,
+                    LateDataPolicy::SideOutput(tag) => write!(f, ", late_data=[{tag}]")?,
+                    LateDataPolicy::UpdateAndEmit(lateness) => {
+                        write!(f, ", allowed_lateness=[{lateness:?}]")?
+                    }
+                }
+                if let Some(schedule) = &self.emit_schedule {
+                    write!(f, ", emit_schedule=[{schedule}]")?;
+                }
                 //if let Some(limit) = self.limit {
                 //    write!(f, ", lim=[{limit}]")?;
                 //}
@@ -564,14 +682,23 @@ impl DisplayAs for FranzStreamingWindowExec {
 
 pub struct WindowAggStream {
     pub schema: SchemaRef,
-    input: SendableRecordBatchStream,
+    input: MessageStream,
     baseline_metrics: BaselineMetrics,
     exec_aggregate_expressions: Vec<Arc<dyn AggregateExpr>>,
     aggregate_expressions: Vec<Vec<Arc<dyn PhysicalExpr>>>,
     filter_expressions: Vec<Option<Arc<dyn PhysicalExpr>>>,
-    latest_watermark: Arc<Mutex<Option<SystemTime>>>,
+    latest_watermark: Option<SystemTime>,
+    late_data: LateRows,
     window_frames: BTreeMap<SystemTime, FranzWindowFrame>,
+    /// Windows that fired and are kept for late rows, by their start
+    fired_windows: HashSet<SystemTime>,
+    /// Fired windows that late rows updated since the last watermark
+    updated_windows: HashSet<SystemTime>,
+    state_metrics: StateMetrics,
     window_type: FranzStreamingWindowType,
+    timezone: WindowTimezone,
+    scheduled_emission: Option<ScheduledEmission>,
+    window_columns: WindowColumns,
     aggregation_mode: AggregateMode,
 }
 
@@ -581,7 +708,6 @@ impl WindowAggStream {
         exec_operator: &FranzStreamingWindowExec,
         context: Arc<TaskContext>,
         partition: usize,
-        watermark: Arc<Mutex<Option<SystemTime>>>,
         window_type: FranzStreamingWindowType,
         aggregation_mode: AggregateMode,
     ) -> Result<Self> {
@@ -589,9 +715,17 @@ impl WindowAggStream {
         let agg_filter_expr = exec_operator.filter_expressions.clone();
 
         let baseline_metrics = BaselineMetrics::new(&exec_operator.metrics, partition);
-        let input = exec_operator
-            .input
-            .execute(partition, Arc::clone(&context))?;
+        let input = MessageStream::new(
+            exec_operator
+                .input
+                .execute(partition, Arc::clone(&context))?,
+        );
+
+        let late_data = LateRows::try_new(
+            &exec_operator.late_data,
+            &context,
+            exec_operator.input.schema(),
+        )?;
 
         let aggregate_expressions =
             aggregate_expressions(&exec_operator.aggregate_expressions, &exec_operator.mode, 0)?;
@@ -604,6 +738,7 @@ impl WindowAggStream {
             }
         };
 
+        let timezone = WindowTimezone::from_task_context(&context)?;
         Ok(Self {
             schema: agg_schema,
             input,
@@ -611,69 +746,195 @@ impl WindowAggStream {
             exec_aggregate_expressions: exec_operator.aggregate_expressions.clone(),
             aggregate_expressions,
             filter_expressions,
-            latest_watermark: watermark,
+            latest_watermark: None,
+            late_data,
             window_frames: BTreeMap::new(),
+            fired_windows: HashSet::new(),
+            updated_windows: HashSet::new(),
+            state_metrics: StateMetrics::new(&exec_operator.metrics, partition),
             window_type,
+            scheduled_emission: exec_operator
+                .emit_schedule
+                .clone()
+                .map(|schedule| ScheduledEmission::new(schedule, timezone.clone())),
+            timezone,
+            window_columns: exec_operator.window_columns.clone(),
             aggregation_mode,
         })
     }
 
     pub fn output_schema_with_window(&self) -> SchemaRef {
-        Arc::new(add_window_columns_to_schema(self.schema.clone()))
+        Arc::new(add_window_columns_to_schema(
+            self.schema.clone(),
+            &self.window_columns,
+        ))
     }
 
     fn trigger_windows(&mut self) -> Result<RecordBatch, DataFusionError> {
         let mut results: Vec<RecordBatch> = Vec::new();
-        let watermark_lock: std::sync::MutexGuard<'_, Option<SystemTime>> =
-            self.latest_watermark.lock().unwrap();
 
-        if let Some(watermark) = *watermark_lock {
+        if let Some(watermark) = self.latest_watermark {
             let mut window_frames_to_remove: Vec<SystemTime> = Vec::new();
+            let mut reclaimed_bytes = 0;
 
             for (timestamp, frame) in self.window_frames.iter_mut() {
-                if watermark >= frame.window_end_time {
+                if watermark < frame.window_end_time {
+                    continue;
+                }
+                // Measured before evaluating, which empties the frame
+                let size = frame.size();
+                let reason = match self.fired_windows.contains(timestamp) {
+                    false => Some(FiringReason::Watermark),
+                    true if self.updated_windows.contains(timestamp) => Some(FiringReason::Late),
+                    true => None,
+                };
+                if let Some(reason) = reason {
                     let rb = frame.evaluate()?;
                     let result = add_window_columns_to_record_batch(
                         rb,
                         frame.window_start_time,
                         frame.window_end_time,
+                        &self.window_columns,
+                        reason,
                     );
                     results.push(result);
-                    window_frames_to_remove.push(*timestamp);
+                }
+                // Kept for the allowed lateness, any other policy is done with the window
+                let keep = matches!(self.late_data, LateRows::UpdateAndEmit(_))
+                    && !self
+                        .late_data
+                        .is_expired(frame.window_end_time, Some(watermark));
+                match keep {
+                    true => {
+                        self.fired_windows.insert(*timestamp);
+                    }
+                    false => {
+                        reclaimed_bytes += size;
+                        window_frames_to_remove.push(*timestamp);
+                    }
                 }
             }
 
+            let reclaimed_windows = window_frames_to_remove.len();
             for timestamp in window_frames_to_remove {
                 self.window_frames.remove(&timestamp);
+                self.fired_windows.remove(&timestamp);
             }
+            let remaining = self.window_frames.values().map(|frame| frame.size()).sum();
+            self.state_metrics.record_reclaimed(
+                watermark,
+                reclaimed_windows,
+                reclaimed_bytes,
+                remaining,
+            );
         }
+        self.updated_windows.clear();
         concat_batches(&self.output_schema_with_window(), &results)
             .map_err(|err| DataFusionError::ArrowError(err, None))
     }
 
-    fn process_watermark(&mut self, watermark: RecordBatchWatermark) {
-        // should this be within a mutex?
-        let mut watermark_lock: std::sync::MutexGuard<Option<SystemTime>> =
-            self.latest_watermark.lock().unwrap();
+    /// Current results of every open window, the windows keep accumulating
+    fn emit_open_windows(&mut self) -> Result<RecordBatch> {
+        let mut results = Vec::with_capacity(self.window_frames.len());
+        for frame in self.window_frames.values_mut() {
+            // Accumulators don't give up their state when evaluated
+            let rb = frame.evaluate()?;
+            results.push(add_window_columns_to_record_batch(
+                rb,
+                frame.window_start_time,
+                frame.window_end_time,
+                &self.window_columns,
+                FiringReason::Schedule,
+            ));
+        }
+        Ok(concat_batches(&self.output_schema_with_window(), &results)?)
+    }
+
+    fn process_watermark(&mut self, watermark: SystemTime) {
+        debug!("latest watermark currently is {:?}", self.latest_watermark);
+        self.latest_watermark = self.latest_watermark.max(Some(watermark));
+    }
 
-        debug!("latest watermark currently is {:?}", *watermark_lock);
-        if let Some(current_watermark) = *watermark_lock {
-            if current_watermark <= watermark.min_timestamp {
-                *watermark_lock = Some(watermark.min_timestamp)
+    fn process_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let batch = self.late_data.filter(
+            batch,
+            self.latest_watermark,
+            &self.window_type,
+            &self.timezone,
+        )?;
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        let watermark = RecordBatchWatermark::try_from(&batch, STREAMING_METADATA_COLUMN)?;
+        let mut ranges = get_windows_for_watermark(&watermark, &self.window_type, &self.timezone);
+        // Rows on time for a later sliding window would bring back windows closed for good
+        ranges.retain(|(_, end)| !self.late_data.is_expired(*end, self.latest_watermark));
+        if self.window_type.merges_windows() {
+            for range in &ranges {
+                self.merge_window(*range)?;
             }
-        } else {
-            *watermark_lock = Some(watermark.min_timestamp)
+            // The assigned windows may have been folded into wider ones
+            ranges = self
+                .window_frames
+                .values()
+                .filter(|frame| {
+                    ranges.iter().any(|(start, end)| {
+                        frame.window_start_time < *end && *start < frame.window_end_time
+                    })
+                })
+                .map(|frame| (frame.window_start_time, frame.window_end_time))
+                .collect();
         }
+        self.ensure_window_frames_for_ranges(&ranges)?;
+        for range in ranges {
+            let frame = self.window_frames.get_mut(&range.0).unwrap();
+            frame.push(&batch)?;
+            if self.fired_windows.contains(&range.0) {
+                self.updated_windows.insert(range.0);
+            }
+        }
+        Ok(())
     }
 
     fn get_window_length(&mut self) -> Duration {
-        match self.window_type {
-            FranzStreamingWindowType::Session(duration) => duration,
-            FranzStreamingWindowType::Sliding(duration, _) => duration,
-            FranzStreamingWindowType::Tumbling(duration) => duration,
+        match &self.window_type {
+            FranzStreamingWindowType::Sliding(duration, _) => *duration,
+            FranzStreamingWindowType::Tumbling(duration) => *duration,
+            FranzStreamingWindowType::Calendar(interval) => interval.max_length(),
+            // Not known up front
+            FranzStreamingWindowType::Session(_)
+            | FranzStreamingWindowType::Custom(_)
+            | FranzStreamingWindowType::Count(..)
+            | FranzStreamingWindowType::Global(_) => Duration::ZERO,
         }
     }
 
+    /// Replace the frames overlapping `range` with a single frame spanning them and `range`
+    fn merge_window(&mut self, (start, end): (SystemTime, SystemTime)) -> Result<()> {
+        let overlapping: Vec<SystemTime> = self
+            .window_frames
+            .values()
+            .filter(|frame| frame.window_start_time < end && start < frame.window_end_time)
+            .map(|frame| frame.window_start_time)
+            .collect();
+        let mut merged_range = (start, end);
+        let mut merged_frames = Vec::with_capacity(overlapping.len());
+        for key in overlapping {
+            let frame = self.window_frames.remove(&key).unwrap();
+            merged_range.0 = merged_range.0.min(frame.window_start_time);
+            merged_range.1 = merged_range.1.max(frame.window_end_time);
+            merged_frames.push(frame);
+        }
+
+        self.ensure_window_frames_for_ranges(&vec![merged_range])?;
+        let merged = self.window_frames.get_mut(&merged_range.0).unwrap();
+        for mut frame in merged_frames {
+            merged.merge(&mut frame)?;
+        }
+        Ok(())
+    }
+
     fn ensure_window_frames_for_ranges(
         &mut self,
         ranges: &Vec<(SystemTime, SystemTime)>,
@@ -699,36 +960,42 @@ impl WindowAggStream {
 
     #[inline]
     fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
-        let result: std::prelude::v1::Result<RecordBatch, DataFusionError> = match self
-            .input
-            .poll_next_unpin(cx)
-        {
-            Poll::Ready(rdy) => match rdy {
-                Some(Ok(batch)) => {
-                    if batch.num_rows() > 0 {
-                        let watermark: RecordBatchWatermark =
-                            RecordBatchWatermark::try_from(&batch, "_streaming_internal_metadata")?;
-                        let ranges = get_windows_for_watermark(&watermark, self.window_type);
-                        let _ = self.ensure_window_frames_for_ranges(&ranges);
-                        for range in ranges {
-                            let frame = self.window_frames.get_mut(&range.0).unwrap();
-                            let _ = frame.push(&batch);
-                        }
-                        self.process_watermark(watermark);
-
-                        self.trigger_windows()
-                    } else {
-                        Ok(RecordBatch::new_empty(self.output_schema_with_window()))
+        loop {
+            let fired = self
+                .scheduled_emission
+                .as_mut()
+                .is_some_and(|emission| emission.poll_fire(cx).is_ready());
+            if fired {
+                let output = self.emit_open_windows()?;
+                if output.num_rows() > 0 {
+                    return Poll::Ready(Some(Ok(output)));
+                }
+            }
+
+            let message = match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            match message {
+                StreamMessage::Data(batch) => self.process_batch(&batch)?,
+                StreamMessage::Watermark(watermark) => {
+                    self.process_watermark(watermark);
+                    let output = self.trigger_windows()?;
+                    if output.num_rows() > 0 {
+                        return Poll::Ready(Some(Ok(output)));
                     }
                 }
-                Some(Err(e)) => Err(e),
-                None => Ok(RecordBatch::new_empty(self.output_schema_with_window())),
-            },
-            Poll::Pending => {
-                return Poll::Pending;
+                // Window state isn't checkpointed, the barrier is passed on to the operators and
+                // sinks after the window
+                StreamMessage::Barrier(epoch) => {
+                    let control =
+                        control_batch(&self.output_schema_with_window(), Some(epoch), None);
+                    return Poll::Ready(Some(Ok(control)));
+                }
+                StreamMessage::EndOfPartition => return Poll::Ready(None),
             }
-        };
-        Poll::Ready(Some(result))
+        }
     }
 }
 
@@ -752,47 +1019,218 @@ impl Stream for WindowAggStream {
     }
 }
 
+/// Windows overlapping the event times of a batch. Boundaries are aligned to the wall clock
+/// of `timezone`, see [`WindowTimezone`].
 pub fn get_windows_for_watermark(
     watermark: &RecordBatchWatermark,
-    window_type: FranzStreamingWindowType,
+    window_type: &FranzStreamingWindowType,
+    timezone: &WindowTimezone,
 ) -> Vec<(SystemTime, SystemTime)> {
-    let start_time = watermark.min_timestamp;
-    let end_time = watermark.max_timestamp;
+    let start_ms = epoch_millis(watermark.min_timestamp);
+    let end_ms = epoch_millis(watermark.max_timestamp);
     let mut window_ranges = Vec::new();
 
     match window_type {
-        FranzStreamingWindowType::Session(_) => todo!(),
+        // Assigned by their own streams
+        FranzStreamingWindowType::Session(_)
+        | FranzStreamingWindowType::Count(..)
+        | FranzStreamingWindowType::Global(_) => unreachable!(),
         FranzStreamingWindowType::Sliding(window_length, slide) => {
-            let mut current_start = snap_to_window_start(start_time - window_length, window_length);
-            while current_start <= end_time {
-                let current_end = current_start + window_length;
-                if start_time > current_end || end_time < current_start {
+            let length_ms = window_length.as_millis() as i64;
+            let slide_ms = slide.as_millis() as i64;
+            let mut local_start =
+                snap_to_window_start(timezone.to_local(start_ms - length_ms), length_ms);
+            loop {
+                let current_start = timezone.to_utc(local_start);
+                if current_start > end_ms {
+                    break;
+                }
+                let current_end = timezone.to_utc(local_start + length_ms);
+                local_start += slide_ms;
+                if start_ms > current_end || end_ms < current_start {
                     // out of bounds
-                    current_start += slide;
                     continue;
                 }
-                window_ranges.push((current_start, current_end));
-                current_start += slide;
+                window_ranges.push((
+                    system_time_from_epoch(current_start),
+                    system_time_from_epoch(current_end),
+                ));
+            }
+        }
+        FranzStreamingWindowType::Calendar(interval) => {
+            let mut local_start = interval.period_start(timezone.to_local(start_ms));
+            loop {
+                let current_start = timezone.to_utc(local_start);
+                if current_start > end_ms {
+                    break;
+                }
+                local_start = interval.next_period(local_start);
+                window_ranges.push((
+                    system_time_from_epoch(current_start),
+                    system_time_from_epoch(timezone.to_utc(local_start)),
+                ));
             }
         }
+        FranzStreamingWindowType::Custom(CustomWindow(assigner)) => {
+            window_ranges.extend(
+                assigner
+                    .assign_windows(start_ms, end_ms)
+                    .into_iter()
+                    .map(|(start, end)| {
+                        (system_time_from_epoch(start), system_time_from_epoch(end))
+                    }),
+            );
+        }
         FranzStreamingWindowType::Tumbling(window_length) => {
-            let mut current_start: SystemTime = snap_to_window_start(start_time, window_length);
-            while current_start <= end_time {
-                let current_end = current_start + window_length;
-                window_ranges.push((current_start, current_end));
-                current_start = current_end;
+            let length_ms = window_length.as_millis() as i64;
+            let mut local_start = snap_to_window_start(timezone.to_local(start_ms), length_ms);
+            loop {
+                let current_start = timezone.to_utc(local_start);
+                if current_start > end_ms {
+                    break;
+                }
+                local_start += length_ms;
+                window_ranges.push((
+                    system_time_from_epoch(current_start),
+                    system_time_from_epoch(timezone.to_utc(local_start)),
+                ));
             }
         }
     };
     window_ranges
 }
 
-fn snap_to_window_start(timestamp: SystemTime, window_length: Duration) -> SystemTime {
-    let timestamp_duration = timestamp.duration_since(UNIX_EPOCH).unwrap();
-    let window_length_secs = window_length.as_secs();
-    let timestamp_secs = timestamp_duration.as_secs();
-    let window_start_secs = (timestamp_secs / window_length_secs) * window_length_secs;
-    UNIX_EPOCH + Duration::from_secs(window_start_secs)
+/// End of the last window a row with the given event time is assigned to
+fn last_window_end(
+    timestamp_ms: i64,
+    window_type: &FranzStreamingWindowType,
+    timezone: &WindowTimezone,
+) -> i64 {
+    let (length, step) = match window_type {
+        FranzStreamingWindowType::Custom(CustomWindow(assigner)) => {
+            return assigner.max_timestamp(timestamp_ms);
+        }
+        FranzStreamingWindowType::Calendar(interval) => {
+            let local_start = interval.period_start(timezone.to_local(timestamp_ms));
+            return timezone.to_utc(interval.next_period(local_start));
+        }
+        FranzStreamingWindowType::Tumbling(length) => (length, length),
+        FranzStreamingWindowType::Sliding(length, slide) => (length, slide),
+        // Handled by SessionWindowStream, which checks every row against its own gap
+        FranzStreamingWindowType::Session(_) => unreachable!(),
+        // Count and global windows are never late
+        FranzStreamingWindowType::Count(..) | FranzStreamingWindowType::Global(_) => {
+            unreachable!()
+        }
+    };
+    let local_ms = timezone.to_local(timestamp_ms);
+    timezone
+        .to_utc(snap_to_window_start(local_ms, step.as_millis() as i64) + length.as_millis() as i64)
+}
+
+/// A [`LateDataPolicy`] as applied by a running window operator
+pub(crate) enum LateRows {
+    Reopen,
+    Drop,
+    SideOutput(SideOutput),
+    UpdateAndEmit(Duration),
+}
+
+impl LateRows {
+    pub(crate) fn try_new(
+        policy: &LateDataPolicy,
+        context: &TaskContext,
+        input_schema: SchemaRef,
+    ) -> Result<Self> {
+        Ok(match policy {
+            LateDataPolicy::Reopen => Self::Reopen,
+            LateDataPolicy::Drop => Self::Drop,
+            LateDataPolicy::SideOutput(tag) => Self::SideOutput(
+                SideOutputRegistry::from_task_context(context)?.output(tag, input_schema)?,
+            ),
+            LateDataPolicy::UpdateAndEmit(lateness) => Self::UpdateAndEmit(*lateness),
+        })
+    }
+
+    pub(crate) fn allowed_lateness(&self) -> Duration {
+        match self {
+            Self::UpdateAndEmit(lateness) => *lateness,
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Whether a window ending at `end` can't receive any more rows once it fired
+    pub(crate) fn is_expired(&self, end: SystemTime, watermark: Option<SystemTime>) -> bool {
+        match self {
+            Self::Reopen => false,
+            _ => watermark.is_some_and(|watermark| end + self.allowed_lateness() <= watermark),
+        }
+    }
+
+    /// The rows of `batch` that still have a window to go to. The others are dropped or sent
+    /// to the side output.
+    pub(crate) fn filter(
+        &self,
+        batch: &RecordBatch,
+        watermark: Option<SystemTime>,
+        window_type: &FranzStreamingWindowType,
+        timezone: &WindowTimezone,
+    ) -> Result<RecordBatch> {
+        let (Some(watermark), false) = (watermark, matches!(self, Self::Reopen)) else {
+            return Ok(batch.clone());
+        };
+        let (on_time, late) = split_late_rows(
+            batch,
+            watermark,
+            self.allowed_lateness(),
+            window_type,
+            timezone,
+        )?;
+        if let Self::SideOutput(output) = self {
+            output.emit(late);
+        }
+        Ok(on_time)
+    }
+}
+
+/// Split a batch into the rows that still belong to an open window and the late rows, whose
+/// windows have all been triggered by `watermark` more than `allowed_lateness` ago.
+fn split_late_rows(
+    batch: &RecordBatch,
+    watermark: SystemTime,
+    allowed_lateness: Duration,
+    window_type: &FranzStreamingWindowType,
+    timezone: &WindowTimezone,
+) -> Result<(RecordBatch, RecordBatch)> {
+    let watermark_ms = epoch_millis(watermark) - allowed_lateness.as_millis() as i64;
+    let ts_array = batch
+        .column_by_name(STREAMING_METADATA_COLUMN)
+        .unwrap()
+        .as_struct()
+        .column_by_name(CANONICAL_TIMESTAMP_FIELD)
+        .unwrap()
+        .as_primitive::<TimestampMillisecondType>();
+
+    let late: BooleanArray = ts_array
+        .iter()
+        .map(|ts| {
+            Some(ts.is_some_and(|ts| last_window_end(ts, window_type, timezone) <= watermark_ms))
+        })
+        .collect();
+    let on_time = not(&late)?;
+    Ok((
+        filter_record_batch(batch, &on_time)?,
+        filter_record_batch(batch, &late)?,
+    ))
+}
+
+fn snap_to_window_start(timestamp_ms: i64, window_length_ms: i64) -> i64 {
+    let window_length_ms = window_length_ms.max(1);
+    timestamp_ms.div_euclid(window_length_ms) * window_length_ms
+}
+
+fn epoch_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64
 }
 
 fn create_schema(
@@ -884,3 +1322,36 @@ pub fn aggregate_batch(
 
     Ok(allocated)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+    #[test]
+    fn late_rows_within_allowed_lateness() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![stream_metadata_field()]));
+        let timestamps = TimestampMillisecondArray::from(vec![5_000, 15_000, 25_000]);
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(stream_metadata_array(timestamps))])?;
+        let window_type = FranzStreamingWindowType::Tumbling(Duration::from_secs(10));
+        let timezone = WindowTimezone::utc();
+        let watermark = Some(system_time_from_epoch(22_000));
+
+        // The window of the first row fired 12s ago, the one of the second 2s ago
+        let rows = |late_data: LateRows| {
+            late_data
+                .filter(&batch, watermark, &window_type, &timezone)
+                .map(|batch| batch.num_rows())
+        };
+        assert_eq!(rows(LateRows::Reopen)?, 3);
+        assert_eq!(rows(LateRows::Drop)?, 1);
+        assert_eq!(rows(LateRows::UpdateAndEmit(Duration::from_secs(5)))?, 2);
+
+        let late_data = LateRows::UpdateAndEmit(Duration::from_secs(5));
+        assert!(late_data.is_expired(system_time_from_epoch(10_000), watermark));
+        assert!(!late_data.is_expired(system_time_from_epoch(20_000), watermark));
+        Ok(())
+    }
+}
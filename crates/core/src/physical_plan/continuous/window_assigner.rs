@@ -0,0 +1,247 @@
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// How a window operator treats windows of a custom assigner that overlap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WindowMergePolicy {
+    /// Every window is aggregated on its own, like sliding windows
+    #[default]
+    Separate,
+    /// Overlapping windows are combined into one spanning all of them, like session windows
+    MergeOverlapping,
+}
+
+/// Decides which windows rows belong to, for windows the built in types don't cover.
+///
+/// Windows are `[start, end)` ranges in milliseconds since the epoch and are identified by
+/// their start, so an assigner must not hand out two windows with the same start but
+/// different ends unless they are merged. A window fires once the watermark passes its end.
+pub trait WindowAssigner: Debug + Send + Sync {
+    /// Identifies the assigner in plans, two assigners with the same name are considered equal
+    fn name(&self) -> &str;
+
+    /// The windows rows with event times from `min_ms` to `max_ms` belong to. Each row is
+    /// aggregated into every returned window that contains its event time, so windows only
+    /// some of the rows fall in are fine, but every window of every row has to be included.
+    fn assign_windows(&self, min_ms: i64, max_ms: i64) -> Vec<(i64, i64)>;
+
+    /// The end of the last window a row with event time `timestamp_ms` is assigned to. Rows
+    /// are late once the watermark reaches it.
+    fn max_timestamp(&self, timestamp_ms: i64) -> i64;
+
+    fn merge_policy(&self) -> WindowMergePolicy {
+        WindowMergePolicy::Separate
+    }
+}
+
+/// A [`WindowAssigner`] as part of a plan
+#[derive(Debug, Clone)]
+pub struct CustomWindow(pub Arc<dyn WindowAssigner>);
+
+impl PartialEq for CustomWindow {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.name() == other.0.name()
+    }
+}
+
+impl Eq for CustomWindow {}
+
+impl Hash for CustomWindow {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.name().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::{Int64Type, TimestampMillisecondType};
+    use arrow_array::{RecordBatch, StringArray, TimestampMillisecondArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::common::Result;
+    use datafusion::datasource::MemTable;
+    use datafusion::functions_aggregate::count::count;
+    use datafusion::logical_expr::col;
+    use futures::StreamExt;
+
+    use crate::context::Context;
+    use crate::physical_plan::continuous::streaming_window::{
+        get_windows_for_watermark, FranzStreamingWindowType,
+    };
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+    use crate::physical_plan::utils::time::{RecordBatchWatermark, WindowTimezone};
+
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+    const DAY_MS: i64 = 24 * HOUR_MS;
+
+    /// One window per day from 09:00 to 17:00 UTC
+    #[derive(Debug)]
+    struct BusinessHours;
+
+    impl WindowAssigner for BusinessHours {
+        fn name(&self) -> &str {
+            "business_hours"
+        }
+
+        fn assign_windows(&self, min_ms: i64, max_ms: i64) -> Vec<(i64, i64)> {
+            (min_ms.div_euclid(DAY_MS)..=max_ms.div_euclid(DAY_MS))
+                .map(|day| (day * DAY_MS + 9 * HOUR_MS, day * DAY_MS + 17 * HOUR_MS))
+                .collect()
+        }
+
+        fn max_timestamp(&self, timestamp_ms: i64) -> i64 {
+            timestamp_ms.div_euclid(DAY_MS) * DAY_MS + 17 * HOUR_MS
+        }
+    }
+
+    /// A window from the first to a second past the last row of every batch, windows that
+    /// overlap are merged like sessions
+    #[derive(Debug)]
+    struct Padded;
+
+    impl WindowAssigner for Padded {
+        fn name(&self) -> &str {
+            "padded"
+        }
+
+        fn assign_windows(&self, min_ms: i64, max_ms: i64) -> Vec<(i64, i64)> {
+            vec![(min_ms, max_ms + 1_000)]
+        }
+
+        fn max_timestamp(&self, timestamp_ms: i64) -> i64 {
+            timestamp_ms + 1_000
+        }
+
+        fn merge_policy(&self) -> WindowMergePolicy {
+            WindowMergePolicy::MergeOverlapping
+        }
+    }
+
+    /// Counts per key of the first window fired over batches of `(key, event time)` rows,
+    /// with `None` as the key when the rows aren't grouped
+    async fn first_window_counts(
+        batches: Vec<Vec<(&str, i64)>>,
+        grouped: bool,
+    ) -> Result<(i64, i64, BTreeMap<Option<String>, i64>)> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            stream_metadata_field(),
+        ]));
+        let batches = batches
+            .into_iter()
+            .map(|rows| {
+                let (sensors, times): (Vec<&str>, Vec<i64>) = rows.into_iter().unzip();
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(StringArray::from(sensors)),
+                        Arc::new(stream_metadata_array(TimestampMillisecondArray::from(
+                            times,
+                        ))),
+                    ],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let table = MemTable::try_new(schema, vec![batches])?;
+        let group_expr = if grouped { vec![col("sensor")] } else { vec![] };
+        let ds = Context::new()?
+            .from_source("readings", Arc::new(table))
+            .await?
+            .custom_window(
+                group_expr,
+                vec![count(col("sensor")).alias("count")],
+                Arc::new(Padded),
+            )?;
+
+        let mut output = ds.df.as_ref().clone().execute_stream().await?;
+        let batch = loop {
+            match output.next().await.transpose()? {
+                Some(batch) if batch.num_rows() > 0 => break batch,
+                Some(_) => {}
+                None => panic!("no window fired"),
+            }
+        };
+        let time = |name: &str| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_primitive::<TimestampMillisecondType>()
+                .value(0)
+        };
+        let counts = batch
+            .column_by_name("count")
+            .unwrap()
+            .as_primitive::<Int64Type>();
+        let counts = (0..batch.num_rows())
+            .map(|row| {
+                let sensor = batch
+                    .column_by_name("sensor")
+                    .map(|sensors| sensors.as_string::<i32>().value(row).to_string());
+                (sensor, counts.value(row))
+            })
+            .collect();
+        Ok((time("window_start_time"), time("window_end_time"), counts))
+    }
+
+    #[tokio::test]
+    async fn merge_overlapping_windows() -> Result<()> {
+        // The second batch overlaps the window of the first, the third fires the merged one
+        let batches = || {
+            vec![
+                vec![("a", 1_000), ("b", 1_200)],
+                vec![("a", 2_000)],
+                vec![("b", 5_000)],
+            ]
+        };
+
+        let (start, end, counts) = first_window_counts(batches(), false).await?;
+        assert_eq!((start, end), (1_000, 3_000));
+        assert_eq!(counts, BTreeMap::from([(None, 3)]));
+
+        // Merging grouped windows restores the groups of one frame into the other
+        let (start, end, counts) = first_window_counts(batches(), true).await?;
+        assert_eq!((start, end), (1_000, 3_000));
+        assert_eq!(
+            counts,
+            BTreeMap::from([(Some("a".to_string()), 2), (Some("b".to_string()), 1)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn custom_assigner_windows() {
+        let window_type = FranzStreamingWindowType::Custom(CustomWindow(Arc::new(BusinessHours)));
+        assert!(!window_type.merges_windows());
+
+        let watermark = RecordBatchWatermark {
+            min_timestamp: UNIX_EPOCH + Duration::from_millis((10 * HOUR_MS) as u64),
+            max_timestamp: UNIX_EPOCH + Duration::from_millis((DAY_MS + 12 * HOUR_MS) as u64),
+        };
+        let windows = get_windows_for_watermark(&watermark, &window_type, &WindowTimezone::utc());
+        let windows: Vec<(u128, u128)> = windows
+            .into_iter()
+            .map(|(start, end)| {
+                (
+                    start.duration_since(UNIX_EPOCH).unwrap().as_millis(),
+                    end.duration_since(UNIX_EPOCH).unwrap().as_millis(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            windows,
+            [
+                (9 * HOUR_MS as u128, 17 * HOUR_MS as u128),
+                (
+                    (DAY_MS + 9 * HOUR_MS) as u128,
+                    (DAY_MS + 17 * HOUR_MS) as u128
+                ),
+            ]
+        );
+    }
+}
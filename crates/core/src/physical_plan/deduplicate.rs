@@ -0,0 +1,483 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::AsArray;
+use arrow::compute::filter_record_batch;
+use arrow::datatypes::{Int64Type, TimestampMillisecondType};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{Array, BinaryArray, BooleanArray, Int64Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use futures::{stream, StreamExt};
+
+use datafusion::common::{exec_err, plan_err, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_expr::Partitioning;
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    displayable, DisplayAs, DisplayFormatType, Distribution, ExecutionPlan, PlanProperties,
+};
+
+use crate::accumulators::groups_snapshot::{batch_from_ipc, batch_to_ipc};
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::physical_plan::utils::metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN};
+use crate::physical_plan::utils::stream_message::{control_of, StreamMessage};
+use crate::state_backend::backend::{get_global_state_backend, StateBackend};
+use crate::utils::determinism::stable_hash;
+use crate::utils::job::JobIdentity;
+
+/// State key the seen keys are checkpointed under
+const SEEN_KEYS_KEY: &[u8] = b"seen_keys";
+/// Schema metadata of the checkpointed keys holding the latest event time seen
+const MAX_TIMESTAMP_METADATA: &str = "max_timestamp";
+
+/// Drop rows repeating the idempotency key of a row seen before, as at least once producers
+/// retrying a send deliver them. A key is remembered for `ttl` of event time after the row
+/// it was first seen on, and at most `max_keys` keys are, the oldest are forgotten first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Deduplication {
+    pub key_column: String,
+    pub ttl: Duration,
+    pub max_keys: usize,
+}
+
+impl fmt::Display for Deduplication {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "key={}, ttl={:?}, max_keys={}",
+            self.key_column, self.ttl, self.max_keys
+        )
+    }
+}
+
+/// Applies a [`Deduplication`]. Duplicates may arrive on any partition, so the input is
+/// merged into a single partition. With checkpointing on, the seen keys are saved at every
+/// barrier and restored on start, so retries that straddle a restart are still dropped.
+#[derive(Debug)]
+pub struct DeduplicateExec {
+    input: Arc<dyn ExecutionPlan>,
+    deduplication: Deduplication,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl DeduplicateExec {
+    pub fn try_new(input: Arc<dyn ExecutionPlan>, deduplication: Deduplication) -> Result<Self> {
+        if input
+            .schema()
+            .field_with_name(&deduplication.key_column)
+            .is_err()
+        {
+            return plan_err!(
+                "Idempotency key column {} not found",
+                deduplication.key_column
+            );
+        }
+        let cache = input
+            .properties()
+            .clone()
+            .with_partitioning(Partitioning::UnknownPartitioning(1));
+        Ok(Self {
+            input,
+            deduplication,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        })
+    }
+
+    /// State namespace of the seen keys. The global state backend is shared by the pipelines
+    /// of a process, so it names the job, and the deduplication and the plan below it tell
+    /// the operators of a job apart the same way on every restart.
+    fn state_namespace(&self, job: Option<&JobIdentity>) -> String {
+        let job_id = job.map(|job| job.job_id.as_str()).unwrap_or_default();
+        let input = displayable(self.input.as_ref()).indent(true).to_string();
+        let operator = stable_hash(
+            0,
+            &[self.deduplication.to_string().as_bytes(), input.as_bytes()],
+        );
+        format!(
+            "deduplicate_{job_id}_{}_{operator:016x}",
+            self.deduplication.key_column
+        )
+    }
+}
+
+impl DisplayAs for DeduplicateExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "DeduplicateExec: {}", self.deduplication)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for DeduplicateExec {
+    fn name(&self) -> &'static str {
+        "DeduplicateExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn required_input_distribution(&self) -> Vec<Distribution> {
+        vec![Distribution::SinglePartition]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(DeduplicateExec::try_new(
+            children[0].clone(),
+            self.deduplication.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let should_checkpoint = context
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(false, |c| c.checkpoint);
+        let job = JobIdentity::from_task_context(&context);
+        let input = self.input.execute(partition, context)?;
+        let schema = input.schema();
+        let mut keys = SeenKeys::try_new(self.deduplication.clone(), &schema)?;
+        let backend = if should_checkpoint {
+            let backend = get_global_state_backend()?;
+            let namespace = self.state_namespace(job.as_deref());
+            backend.ensure_namespace(&namespace)?;
+            keys.load(backend.as_ref(), &namespace)?;
+            Some((backend, namespace))
+        } else {
+            None
+        };
+        let duplicates = MetricBuilder::new(&self.metrics).counter("duplicates", partition);
+        let evicted = MetricBuilder::new(&self.metrics).counter("evicted_keys", partition);
+        let key_count = MetricBuilder::new(&self.metrics).gauge("idempotency_keys", partition);
+
+        let stream = input.flat_map(move |batch| {
+            let output = batch.and_then(|batch| {
+                let mut output = vec![];
+                if batch.num_rows() == 0 {
+                    output.push(batch.clone());
+                } else {
+                    let first_seen = keys.first_seen(&batch, &evicted)?;
+                    duplicates.add(first_seen.false_count());
+                    key_count.set(keys.len());
+                    let kept = filter_record_batch(&batch, &first_seen)?;
+                    if kept.num_rows() > 0 {
+                        output.push(kept);
+                    }
+                    // The dropped rows may have carried the watermark or the barrier
+                    if first_seen.false_count() > 0 {
+                        output.extend(control_of(&batch)?);
+                    }
+                }
+                if let Some((backend, namespace)) = &backend {
+                    let closes_epoch = StreamMessage::from_batch(batch)?
+                        .iter()
+                        .any(|message| matches!(message, StreamMessage::Barrier(_)));
+                    if closes_epoch {
+                        keys.save(backend.as_ref(), namespace)?;
+                    }
+                }
+                Ok(output)
+            });
+            stream::iter(match output {
+                Ok(output) => output.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            })
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+/// The idempotency keys seen within the ttl, with the event time they were first seen at
+struct SeenKeys {
+    deduplication: Deduplication,
+    converter: RowConverter,
+    seen: HashMap<OwnedRow, i64>,
+    /// The keys by the event time they were first seen at, oldest first
+    by_time: BTreeMap<i64, Vec<OwnedRow>>,
+    max_timestamp: i64,
+}
+
+impl SeenKeys {
+    fn try_new(deduplication: Deduplication, schema: &SchemaRef) -> Result<Self> {
+        let key_type = schema
+            .field_with_name(&deduplication.key_column)?
+            .data_type()
+            .clone();
+        Ok(Self {
+            deduplication,
+            converter: RowConverter::new(vec![SortField::new(key_type)])?,
+            seen: HashMap::new(),
+            by_time: BTreeMap::new(),
+            max_timestamp: i64::MIN,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Mask of the rows whose key wasn't seen before, remembering their keys. Rows without
+    /// a key always pass.
+    fn first_seen(&mut self, batch: &RecordBatch, evicted: &Count) -> Result<BooleanArray> {
+        let Some(key) = batch
+            .column_by_name(&self.deduplication.key_column)
+            .cloned()
+        else {
+            return exec_err!(
+                "Idempotency key column {} not found",
+                self.deduplication.key_column
+            );
+        };
+        let rows = self.converter.convert_columns(&[key.clone()])?;
+        let Some(timestamps) = batch
+            .column_by_name(STREAMING_METADATA_COLUMN)
+            .and_then(|metadata| metadata.as_struct_opt())
+            .and_then(|metadata| metadata.column_by_name(CANONICAL_TIMESTAMP_FIELD))
+        else {
+            return exec_err!("Deduplication needs the stream metadata column for event times");
+        };
+        let timestamps = timestamps.as_primitive::<TimestampMillisecondType>();
+
+        let ttl_ms = self.deduplication.ttl.as_millis() as i64;
+        let mut first_seen = Vec::with_capacity(batch.num_rows());
+        for (index, (row, timestamp)) in rows.iter().zip(timestamps.iter()).enumerate() {
+            if key.is_null(index) {
+                first_seen.push(true);
+                continue;
+            }
+            let timestamp = timestamp.unwrap_or(self.max_timestamp);
+            self.max_timestamp = self.max_timestamp.max(timestamp);
+            let row = row.owned();
+            let repeat = self
+                .seen
+                .get(&row)
+                .is_some_and(|first| timestamp.saturating_sub(*first) < ttl_ms);
+            if !repeat {
+                // A key seen again after its ttl starts over, its earlier entry is stale
+                self.seen.insert(row.clone(), timestamp);
+                self.by_time.entry(timestamp).or_default().push(row);
+            }
+            first_seen.push(!repeat);
+        }
+
+        let horizon = self.max_timestamp.saturating_sub(ttl_ms);
+        while let Some(entry) = self.by_time.first_entry() {
+            let expired = *entry.key() < horizon;
+            if !expired && self.seen.len() <= self.deduplication.max_keys {
+                break;
+            }
+            let (time, rows) = entry.remove_entry();
+            for row in rows {
+                if self.seen.get(&row) == Some(&time) {
+                    self.seen.remove(&row);
+                    if !expired {
+                        evicted.add(1);
+                    }
+                }
+            }
+        }
+        Ok(BooleanArray::from(first_seen))
+    }
+
+    /// Checkpoint the keys with the event time they were first seen at under `namespace`
+    fn save(&self, backend: &dyn StateBackend, namespace: &str) -> Result<()> {
+        let (keys, first_seen): (Vec<Vec<u8>>, Vec<i64>) = self
+            .seen
+            .iter()
+            .map(|(row, time)| (row.row().as_ref().to_vec(), *time))
+            .unzip();
+        let schema = Schema::new(vec![
+            Field::new("key", DataType::Binary, false),
+            Field::new("first_seen", DataType::Int64, false),
+        ])
+        .with_metadata(HashMap::from([(
+            MAX_TIMESTAMP_METADATA.to_string(),
+            self.max_timestamp.to_string(),
+        )]));
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(BinaryArray::from_iter_values(keys)),
+                Arc::new(Int64Array::from(first_seen)),
+            ],
+        )?;
+        backend.put_state(namespace, SEEN_KEYS_KEY.to_vec(), batch_to_ipc(&batch)?)
+    }
+
+    /// Restore the keys [`save`](Self::save)d under `namespace`, if any
+    fn load(&mut self, backend: &dyn StateBackend, namespace: &str) -> Result<()> {
+        let Some(bytes) = backend.get_state(namespace, SEEN_KEYS_KEY.to_vec())? else {
+            return Ok(());
+        };
+        let batch = batch_from_ipc(&bytes)?;
+        self.max_timestamp = batch
+            .schema()
+            .metadata()
+            .get(MAX_TIMESTAMP_METADATA)
+            .and_then(|time| time.parse().ok())
+            .unwrap_or(i64::MIN);
+        let parser = self.converter.parser();
+        let keys = batch.column(0).as_binary::<i32>();
+        let first_seen = batch.column(1).as_primitive::<Int64Type>();
+        for (key, time) in keys.iter().flatten().zip(first_seen.values()) {
+            let row = parser.parse(key).owned();
+            self.seen.insert(row.clone(), *time);
+            self.by_time.entry(*time).or_default().push(row);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::StringArray;
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    use crate::physical_plan::utils::metadata::{
+        stream_metadata_array_with_barrier, stream_metadata_field,
+    };
+    use crate::physical_plan::utils::stream_message::{barrier_marker, NO_BARRIER};
+    use crate::state_backend::rocksdb_backend::RocksDBBackend;
+
+    fn batch(keys: Vec<Option<&str>>, timestamps: Vec<i64>) -> Result<RecordBatch> {
+        closing_batch(keys, timestamps, NO_BARRIER)
+    }
+
+    fn closing_batch(
+        keys: Vec<Option<&str>>,
+        timestamps: Vec<i64>,
+        barrier: &str,
+    ) -> Result<RecordBatch> {
+        let ids = (0..keys.len() as i64).collect::<Vec<_>>();
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("request_id", DataType::Utf8, true),
+                stream_metadata_field(),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(StringArray::from(keys)),
+                Arc::new(stream_metadata_array_with_barrier(
+                    timestamps.into(),
+                    barrier,
+                )),
+            ],
+        )?)
+    }
+
+    fn kept(keys: &mut SeenKeys, batch: &RecordBatch) -> Result<Vec<i64>> {
+        let first_seen = keys.first_seen(batch, &Count::new())?;
+        Ok(filter_record_batch(batch, &first_seen)?
+            .column(0)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec())
+    }
+
+    fn deduplication() -> Deduplication {
+        Deduplication {
+            key_column: "request_id".to_string(),
+            ttl: Duration::from_secs(10),
+            max_keys: 3,
+        }
+    }
+
+    #[test]
+    fn drop_repeated_keys() -> Result<()> {
+        let first = batch(
+            vec![Some("a"), Some("b"), Some("a"), None, None],
+            vec![1_000, 2_000, 3_000, 3_000, 3_000],
+        )?;
+        let mut keys = SeenKeys::try_new(deduplication(), &first.schema())?;
+        assert_eq!(kept(&mut keys, &first)?, vec![0, 1, 3, 4]);
+
+        // `a` expired, `b` is still remembered
+        let second = batch(vec![Some("a"), Some("b")], vec![11_500, 11_500])?;
+        assert_eq!(kept(&mut keys, &second)?, vec![0]);
+        assert_eq!(keys.len(), 2);
+
+        // Past `max_keys` the oldest key, `b`, is forgotten
+        let third = batch(vec![Some("c"), Some("d"), Some("b")], vec![11_900; 3])?;
+        assert_eq!(kept(&mut keys, &third)?, vec![0, 1]);
+        let fourth = batch(vec![Some("b"), Some("c")], vec![12_000; 2])?;
+        assert_eq!(kept(&mut keys, &fourth)?, vec![0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pass_on_the_barrier_of_dropped_rows() -> Result<()> {
+        let first = batch(vec![Some("a")], vec![1_000])?;
+        let retry = closing_batch(vec![Some("a")], vec![1_500], &barrier_marker(4))?;
+        let schema = first.schema();
+        let input = MemoryExec::try_new(&[vec![first, retry]], schema, None)?;
+        let exec = DeduplicateExec::try_new(Arc::new(input), deduplication())?;
+        let output = collect(exec.execute(0, Arc::new(TaskContext::default()))?).await?;
+
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[1].num_rows(), 0);
+        let messages = StreamMessage::from_batch(output[1].clone())?;
+        assert!(matches!(messages[..], [_, StreamMessage::Barrier(4)]));
+        Ok(())
+    }
+
+    #[test]
+    fn restore_seen_keys() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("deduplicate_state_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let backend = RocksDBBackend::open(&path)?;
+        backend.ensure_namespace("deduplicate_request_id")?;
+
+        let first = batch(vec![Some("a"), Some("b")], vec![1_000, 2_000])?;
+        let mut keys = SeenKeys::try_new(deduplication(), &first.schema())?;
+        kept(&mut keys, &first)?;
+        keys.save(&backend, "deduplicate_request_id")?;
+
+        let mut restored = SeenKeys::try_new(deduplication(), &first.schema())?;
+        restored.load(&backend, "deduplicate_request_id")?;
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.max_timestamp, 2_000);
+        // `a` expires on time after the restore, `b` is still remembered
+        let second = batch(vec![Some("a"), Some("b"), Some("c")], vec![11_500; 3])?;
+        assert_eq!(kept(&mut restored, &second)?, vec![0, 2]);
+        Ok(())
+    }
+}
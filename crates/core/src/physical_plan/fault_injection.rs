@@ -0,0 +1,130 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use futures::StreamExt;
+
+use datafusion::common::Result;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+
+use crate::physical_plan::utils::metadata::has_stream_metadata;
+use crate::physical_plan::utils::stream_message::barrier_epoch;
+use crate::utils::fault_injection::{BatchFault, FaultInjector};
+
+/// Where a [`FaultInjectionExec`] sits in the plan, which decides the faults it injects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultPoint {
+    /// After a source, batches get dropped or delayed and the crash epoch is checked against
+    /// the barriers passing by. Batches closing an epoch are never dropped.
+    Source,
+    /// Before a sink, writes of batches fail
+    Sink,
+}
+
+/// Passes the batches of its input through the session's [`FaultInjector`], or untouched when
+/// fault injection is disabled
+#[derive(Debug)]
+pub struct FaultInjectionExec {
+    input: Arc<dyn ExecutionPlan>,
+    point: FaultPoint,
+}
+
+impl FaultInjectionExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, point: FaultPoint) -> Self {
+        Self { input, point }
+    }
+
+    pub fn point(&self) -> FaultPoint {
+        self.point
+    }
+}
+
+impl DisplayAs for FaultInjectionExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "FaultInjectionExec: point={:?}", self.point)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for FaultInjectionExec {
+    fn name(&self) -> &'static str {
+        "FaultInjectionExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(FaultInjectionExec::new(
+            children[0].clone(),
+            self.point,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let injector = FaultInjector::from_task_context(&context);
+        let input = self.input.execute(partition, context)?;
+        let Some(injector) = injector else {
+            return Ok(input);
+        };
+        let schema = input.schema();
+        let barriers = has_stream_metadata(&schema);
+        let point = self.point;
+        let stream = input.filter_map(move |batch| {
+            let injector = injector.clone();
+            async move {
+                let batch = match batch {
+                    Ok(batch) => batch,
+                    Err(err) => return Some(Err(err)),
+                };
+                if point == FaultPoint::Sink {
+                    return Some(injector.check_send("sink").map(|_| batch));
+                }
+                let epoch = match barriers.then(|| barrier_epoch(&batch)) {
+                    Some(Ok(epoch)) => epoch,
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => None,
+                };
+                if let Some(epoch) = epoch {
+                    injector.check_epoch(epoch);
+                }
+                match injector.batch_fault() {
+                    // Losing a barrier would stall checkpoints rather than lose data
+                    BatchFault::Drop if epoch.is_some() => Some(Ok(batch)),
+                    BatchFault::Drop => None,
+                    BatchFault::Delay(delay) => {
+                        tokio::time::sleep(delay).await;
+                        Some(Ok(batch))
+                    }
+                    BatchFault::None => Some(Ok(batch)),
+                }
+            }
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+}
@@ -0,0 +1,173 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use arrow_array::{RecordBatch, RecordBatchOptions};
+use arrow_schema::SchemaRef;
+use futures::StreamExt;
+
+use datafusion::common::Result;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_plan::metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+
+use crate::physical_plan::continuous::batch_filter;
+use crate::physical_plan::utils::stream_message::{forward_control, is_control_batch};
+
+/// A single stateless step applied by [`FusedStatelessExec`]
+#[derive(Debug, Clone)]
+pub enum FusedStep {
+    Filter(Arc<dyn PhysicalExpr>),
+    Projection {
+        exprs: Vec<(Arc<dyn PhysicalExpr>, String)>,
+        schema: SchemaRef,
+    },
+}
+
+impl FusedStep {
+    fn apply(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        match self {
+            FusedStep::Filter(predicate) => batch_filter(&batch, predicate),
+            FusedStep::Projection { exprs, schema } => {
+                let num_rows = batch.num_rows();
+                let columns = exprs
+                    .iter()
+                    .map(|(expr, _)| expr.evaluate(&batch)?.into_array(num_rows))
+                    .collect::<Result<Vec<_>>>()?;
+                let options = RecordBatchOptions::new().with_row_count(Some(num_rows));
+                Ok(RecordBatch::try_new_with_options(
+                    schema.clone(),
+                    columns,
+                    &options,
+                )?)
+            }
+        }
+    }
+}
+
+impl fmt::Display for FusedStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FusedStep::Filter(predicate) => write!(f, "filter({predicate})"),
+            FusedStep::Projection { exprs, .. } => {
+                let exprs: Vec<String> = exprs
+                    .iter()
+                    .map(|(expr, alias)| format!("{expr} as {alias}"))
+                    .collect();
+                write!(f, "projection({})", exprs.join(", "))
+            }
+        }
+    }
+}
+
+/// Runs a chain of filters and projections as a single operator, applying every step to a
+/// batch before handing it downstream. Control batches are passed on as they are, unlike
+/// DataFusion's filter, which drops batches without rows, and projection, which drops their
+/// schema metadata.
+#[derive(Debug)]
+pub struct FusedStatelessExec {
+    input: Arc<dyn ExecutionPlan>,
+    /// Steps in the order they are applied, starting from the input
+    steps: Vec<FusedStep>,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl FusedStatelessExec {
+    /// `properties` are the properties of the last operator in the fused chain
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        steps: Vec<FusedStep>,
+        properties: PlanProperties,
+    ) -> Self {
+        Self {
+            input,
+            steps,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache: properties,
+        }
+    }
+
+    pub fn steps(&self) -> &[FusedStep] {
+        &self.steps
+    }
+}
+
+impl DisplayAs for FusedStatelessExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                let steps: Vec<String> = self.steps.iter().map(|s| s.to_string()).collect();
+                write!(f, "FusedStatelessExec: steps=[{}]", steps.join(" -> "))
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for FusedStatelessExec {
+    fn name(&self) -> &'static str {
+        "FusedStatelessExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(FusedStatelessExec::new(
+            children[0].clone(),
+            self.steps.clone(),
+            self.cache.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        let baseline_metrics = BaselineMetrics::new(&self.metrics, partition);
+        let steps = self.steps.clone();
+        let schema = self.schema();
+
+        let stream = input.map(move |batch| {
+            let batch = batch?;
+            if is_control_batch(&batch) {
+                return Ok(forward_control(&batch, &schema));
+            }
+            let _timer = baseline_metrics.elapsed_compute().timer();
+            let batch = steps
+                .iter()
+                .try_fold(batch, |batch, step| step.apply(batch))?;
+            baseline_metrics.record_output(batch.num_rows());
+            Ok(batch)
+        });
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
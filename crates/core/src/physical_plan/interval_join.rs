@@ -0,0 +1,454 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use arrow::array::AsArray;
+use arrow::compute::{cast, interleave, take};
+use arrow::datatypes::TimestampMillisecondType;
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{Array, ArrayRef, RecordBatch, StringArray, StructArray, UInt32Array};
+use arrow_schema::{DataType, SchemaRef, TimeUnit};
+
+use datafusion::common::Result;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_expr::{EquivalenceProperties, Partitioning, PhysicalExpr};
+use datafusion::physical_plan::metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet};
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, Distribution, ExecutionMode, ExecutionPlan, PlanProperties,
+};
+
+use super::two_input::{InputSide, TwoInputStream, TwoInputStreamOperator};
+use super::utils::metadata::{BARRIER_FIELD, STREAMING_METADATA_COLUMN};
+use super::utils::stream_message::NO_BARRIER;
+
+/// Inner join of two streams on equal keys, pairing rows whose event times are within a
+/// bound: the left event time minus the right one lies within `lower_ms..=upper_ms`.
+///
+/// Both sides keep their rows until the watermark shows no row of the other side can match
+/// them anymore. The buffered rows aren't checkpointed, after a restart rows only match
+/// rows read since.
+#[derive(Debug)]
+pub struct IntervalJoinExec {
+    left: Arc<dyn ExecutionPlan>,
+    right: Arc<dyn ExecutionPlan>,
+    on: Vec<(Arc<dyn PhysicalExpr>, Arc<dyn PhysicalExpr>)>,
+    left_time: Arc<dyn PhysicalExpr>,
+    right_time: Arc<dyn PhysicalExpr>,
+    lower_ms: i64,
+    upper_ms: i64,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl IntervalJoinExec {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        on: Vec<(Arc<dyn PhysicalExpr>, Arc<dyn PhysicalExpr>)>,
+        left_time: Arc<dyn PhysicalExpr>,
+        right_time: Arc<dyn PhysicalExpr>,
+        lower_ms: i64,
+        upper_ms: i64,
+        schema: SchemaRef,
+    ) -> Self {
+        let cache = PlanProperties::new(
+            EquivalenceProperties::new(schema.clone()),
+            Partitioning::UnknownPartitioning(1),
+            ExecutionMode::Unbounded,
+        );
+        Self {
+            left,
+            right,
+            on,
+            left_time,
+            right_time,
+            lower_ms,
+            upper_ms,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        }
+    }
+}
+
+impl DisplayAs for IntervalJoinExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                let on = self
+                    .on
+                    .iter()
+                    .map(|(left, right)| format!("({left}, {right})"))
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "IntervalJoinExec: on=[{}], bound={}ms..={}ms",
+                    on.join(", "),
+                    self.lower_ms,
+                    self.upper_ms
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for IntervalJoinExec {
+    fn name(&self) -> &'static str {
+        "IntervalJoinExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.left, &self.right]
+    }
+
+    fn required_input_distribution(&self) -> Vec<Distribution> {
+        vec![Distribution::SinglePartition, Distribution::SinglePartition]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(IntervalJoinExec::new(
+            children[0].clone(),
+            children[1].clone(),
+            self.on.clone(),
+            self.left_time.clone(),
+            self.right_time.clone(),
+            self.lower_ms,
+            self.upper_ms,
+            self.schema.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let left = self.left.execute(partition, context.clone())?;
+        let right = self.right.execute(partition, context)?;
+        let key_types = self
+            .on
+            .iter()
+            .map(|(left_key, _)| Ok(SortField::new(left_key.data_type(&left.schema())?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let operator = IntervalJoinOperator {
+            schema: self.schema.clone(),
+            on: self.on.clone(),
+            times: [self.left_time.clone(), self.right_time.clone()],
+            lower_ms: self.lower_ms,
+            upper_ms: self.upper_ms,
+            converter: RowConverter::new(key_types)?,
+            sides: [BufferedSide::default(), BufferedSide::default()],
+            next_batch: 0,
+            watermark: None,
+        };
+        Ok(Box::pin(TwoInputStream::new(
+            operator,
+            left,
+            right,
+            BaselineMetrics::new(&self.metrics, partition),
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BufferedRow {
+    batch: u64,
+    row: usize,
+    time: i64,
+}
+
+/// The rows of one input that may still match rows of the other, by key
+#[derive(Default)]
+struct BufferedSide {
+    /// The batches rows are buffered from and how many of their rows are left
+    batches: HashMap<u64, (RecordBatch, usize)>,
+    rows: HashMap<OwnedRow, Vec<BufferedRow>>,
+}
+
+struct IntervalJoinOperator {
+    schema: SchemaRef,
+    on: Vec<(Arc<dyn PhysicalExpr>, Arc<dyn PhysicalExpr>)>,
+    times: [Arc<dyn PhysicalExpr>; 2],
+    lower_ms: i64,
+    upper_ms: i64,
+    converter: RowConverter,
+    sides: [BufferedSide; 2],
+    next_batch: u64,
+    watermark: Option<i64>,
+}
+
+impl IntervalJoinOperator {
+    fn index(side: InputSide) -> usize {
+        match side {
+            InputSide::Left => 0,
+            InputSide::Right => 1,
+        }
+    }
+
+    fn matches(&self, side: InputSide, time: i64, other_time: i64) -> bool {
+        let difference = match side {
+            InputSide::Left => time - other_time,
+            InputSide::Right => other_time - time,
+        };
+        (self.lower_ms..=self.upper_ms).contains(&difference)
+    }
+
+    /// Joins `matches`, pairs of a row of `batch` and a buffered row of the other side
+    fn join_rows(
+        &self,
+        side: InputSide,
+        batch: &RecordBatch,
+        matches: &[(usize, BufferedRow)],
+    ) -> Result<RecordBatch> {
+        let indices = UInt32Array::from_iter_values(matches.iter().map(|(row, _)| *row as u32));
+        let own = batch
+            .columns()
+            .iter()
+            .map(|column| Ok(take(column, &indices, None)?))
+            .collect::<Result<Vec<ArrayRef>>>()?;
+
+        let other = &self.sides[1 - Self::index(side)];
+        let mut positions = HashMap::new();
+        let mut batches = vec![];
+        let rows = matches
+            .iter()
+            .map(|(_, buffered)| {
+                let position = *positions.entry(buffered.batch).or_insert_with(|| {
+                    batches.push(&other.batches[&buffered.batch].0);
+                    batches.len() - 1
+                });
+                (position, buffered.row)
+            })
+            .collect::<Vec<_>>();
+        let other_columns = (0..batches[0].num_columns())
+            .map(|column| {
+                let arrays = batches
+                    .iter()
+                    .map(|batch| batch.column(column).as_ref())
+                    .collect::<Vec<_>>();
+                Ok(interleave(&arrays, &rows)?)
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+
+        let columns = match side {
+            InputSide::Left => [own, other_columns].concat(),
+            InputSide::Right => [other_columns, own].concat(),
+        };
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+}
+
+impl TwoInputStreamOperator for IntervalJoinOperator {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn process_batch(&mut self, side: InputSide, batch: RecordBatch) -> Result<Vec<RecordBatch>> {
+        let index = Self::index(side);
+        let keys = self
+            .on
+            .iter()
+            .map(|on| {
+                let key = if index == 0 { &on.0 } else { &on.1 };
+                key.evaluate(&batch)?.into_array(batch.num_rows())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let rows = self.converter.convert_columns(&keys)?;
+        let times = cast(
+            &self.times[index]
+                .evaluate(&batch)?
+                .into_array(batch.num_rows())?,
+            &DataType::Timestamp(TimeUnit::Millisecond, None),
+        )?;
+        let times = times.as_primitive::<TimestampMillisecondType>();
+
+        // Rows with a null key or event time match nothing
+        let joinable = (0..batch.num_rows())
+            .filter(|row| times.is_valid(*row) && keys.iter().all(|key| key.is_valid(*row)))
+            .map(|row| (row, rows.row(row).owned(), times.value(row)))
+            .collect::<Vec<_>>();
+
+        let other = &self.sides[1 - index];
+        let mut matches = vec![];
+        for (row, key, time) in &joinable {
+            for buffered in other.rows.get(key).into_iter().flatten() {
+                if self.matches(side, *time, buffered.time) {
+                    matches.push((*row, *buffered));
+                }
+            }
+        }
+        let output = match matches.is_empty() {
+            true => vec![],
+            false => vec![self.join_rows(side, &batch, &matches)?],
+        };
+
+        let watermark = self.watermark;
+        let buffered = joinable
+            .into_iter()
+            .filter(|(_, _, time)| {
+                watermark.map_or(true, |watermark| {
+                    horizon(side, *time, self.lower_ms, self.upper_ms) >= watermark
+                })
+            })
+            .collect::<Vec<_>>();
+        if !buffered.is_empty() {
+            let id = self.next_batch;
+            self.next_batch += 1;
+            let state = &mut self.sides[index];
+            state
+                .batches
+                .insert(id, (clear_barriers(batch)?, buffered.len()));
+            for (row, key, time) in buffered {
+                state.rows.entry(key).or_default().push(BufferedRow {
+                    batch: id,
+                    row,
+                    time,
+                });
+            }
+        }
+        Ok(output)
+    }
+
+    fn on_watermark(&mut self, watermark: SystemTime) -> Result<Vec<RecordBatch>> {
+        let watermark = watermark
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_millis() as i64);
+        self.watermark = Some(watermark);
+
+        for side in [InputSide::Left, InputSide::Right] {
+            let (lower_ms, upper_ms) = (self.lower_ms, self.upper_ms);
+            let BufferedSide { batches, rows } = &mut self.sides[Self::index(side)];
+            rows.retain(|_, buffered| {
+                buffered.retain(|row| {
+                    let keep = horizon(side, row.time, lower_ms, upper_ms) >= watermark;
+                    if !keep {
+                        if let Some((_, live)) = batches.get_mut(&row.batch) {
+                            *live -= 1;
+                        }
+                    }
+                    keep
+                });
+                !buffered.is_empty()
+            });
+            batches.retain(|_, (_, live)| *live > 0);
+        }
+        Ok(vec![])
+    }
+}
+
+/// The latest watermark at which a row of `side` at `time` can still find a match
+fn horizon(side: InputSide, time: i64, lower_ms: i64, upper_ms: i64) -> i64 {
+    match side {
+        InputSide::Left => time - lower_ms,
+        InputSide::Right => time + upper_ms,
+    }
+}
+
+/// `batch` with no barriers in its metadata column. A buffered row joins with rows arriving
+/// long after it, which mustn't carry the barrier it came with downstream again.
+fn clear_barriers(batch: RecordBatch) -> Result<RecordBatch> {
+    let Ok(index) = batch.schema().index_of(STREAMING_METADATA_COLUMN) else {
+        return Ok(batch);
+    };
+    let (fields, mut arrays, nulls) = batch.column(index).as_struct().clone().into_parts();
+    if let Some((barrier, _)) = fields.find(BARRIER_FIELD) {
+        arrays[barrier] = Arc::new(StringArray::from(vec![NO_BARRIER; batch.num_rows()]));
+    }
+    let mut columns = batch.columns().to_vec();
+    columns[index] = Arc::new(StructArray::try_new(fields, arrays, nulls)?);
+    Ok(RecordBatch::try_new(batch.schema(), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, TimestampMillisecondArray};
+    use arrow_schema::{Field, Schema};
+    use datafusion::physical_expr::expressions::col;
+
+    #[test]
+    fn join_rows_within_bound_until_watermark() -> Result<()> {
+        let side_schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::Int64, false),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+        ]));
+        let schema = Arc::new(Schema::new(
+            [side_schema.fields().to_vec(), side_schema.fields().to_vec()].concat(),
+        ));
+        let batch = |keys: Vec<i64>, times: Vec<i64>| {
+            RecordBatch::try_new(
+                side_schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(keys)),
+                    Arc::new(TimestampMillisecondArray::from(times)),
+                ],
+            )
+        };
+        let key = col("key", &side_schema)?;
+        let ts = col("ts", &side_schema)?;
+        let mut operator = IntervalJoinOperator {
+            schema,
+            on: vec![(key.clone(), key)],
+            times: [ts.clone(), ts],
+            lower_ms: -1_000,
+            upper_ms: 1_000,
+            converter: RowConverter::new(vec![SortField::new(DataType::Int64)])?,
+            sides: [BufferedSide::default(), BufferedSide::default()],
+            next_batch: 0,
+            watermark: None,
+        };
+        let joined = |output: Vec<RecordBatch>| {
+            output
+                .iter()
+                .flat_map(|batch| {
+                    let times = batch.column(1).as_primitive::<TimestampMillisecondType>();
+                    let other = batch.column(3).as_primitive::<TimestampMillisecondType>();
+                    times
+                        .values()
+                        .iter()
+                        .zip(other.values())
+                        .map(|(a, b)| (*a, *b))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        operator.process_batch(InputSide::Left, batch(vec![1, 2], vec![10_000, 10_000])?)?;
+        let output =
+            operator.process_batch(InputSide::Right, batch(vec![1, 1], vec![10_500, 12_000])?)?;
+        assert_eq!(joined(output), [(10_000, 10_500)]);
+
+        // Past the watermark the left rows can't match anything anymore and are dropped
+        operator.on_watermark(UNIX_EPOCH + std::time::Duration::from_millis(11_500))?;
+        let output = operator.process_batch(InputSide::Right, batch(vec![1], vec![10_800])?)?;
+        assert!(joined(output).is_empty());
+        let output = operator.process_batch(InputSide::Left, batch(vec![1], vec![11_800])?)?;
+        assert_eq!(joined(output), [(11_800, 12_000), (11_800, 10_800)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,482 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::compute::{cast, concat_batches, interleave, take, take_record_batch};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{new_null_array, Array, ArrayRef, RecordBatch, UInt32Array};
+use arrow_schema::{DataType, SchemaRef};
+use futures::StreamExt;
+
+use datafusion::common::instant::Instant;
+use datafusion::common::{Column, Result, ScalarValue};
+use datafusion::datasource::TableProvider;
+use datafusion::execution::session_state::{SessionState, SessionStateBuilder};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{lit, Expr};
+use datafusion::physical_expr::{EquivalenceProperties, PhysicalExpr};
+use datafusion::physical_plan::metrics::{
+    BaselineMetrics, Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    collect, DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+};
+
+/// A table of reference data that streams are enriched with by looking up the rows matching
+/// their keys, see [`LookupJoinExec`]
+#[derive(Clone)]
+pub struct LookupTable {
+    name: String,
+    provider: Arc<dyn TableProvider>,
+    cache_capacity: usize,
+    ttl: Duration,
+}
+
+impl LookupTable {
+    /// Look up rows of `provider`, whose columns are qualified by `name` in joins. Up to
+    /// 100,000 keys are cached for five minutes.
+    pub fn new(name: &str, provider: Arc<dyn TableProvider>) -> Self {
+        Self {
+            name: name.to_string(),
+            provider,
+            cache_capacity: 100_000,
+            ttl: Duration::from_secs(300),
+        }
+    }
+
+    /// Cache the rows of at most `keys` keys per partition, dropping the keys loaded first
+    pub fn with_cache_capacity(mut self, keys: usize) -> Self {
+        self.cache_capacity = keys;
+        self
+    }
+
+    /// Load the rows of a key from the table again once they have been cached for `ttl`, which
+    /// bounds how long changes of the table take to reach the stream
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn provider(&self) -> &Arc<dyn TableProvider> {
+        &self.provider
+    }
+}
+
+impl fmt::Display for LookupTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}(cache_capacity={}, ttl={:?})",
+            self.name, self.cache_capacity, self.ttl
+        )
+    }
+}
+
+impl fmt::Debug for LookupTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl PartialEq for LookupTable {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && Arc::ptr_eq(&self.provider, &other.provider)
+            && self.cache_capacity == other.cache_capacity
+            && self.ttl == other.ttl
+    }
+}
+
+impl Eq for LookupTable {}
+
+impl Hash for LookupTable {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.cache_capacity.hash(state);
+        self.ttl.hash(state);
+    }
+}
+
+/// Left join of a stream with a [`LookupTable`]. Every row of the stream is joined with the
+/// rows of the table whose `on` columns equal its keys, or with nulls if there are none.
+///
+/// Each partition caches the rows it looked up by key, including the keys without rows. Keys
+/// missing from the cache are loaded with a single scan of the table per batch, filtered to
+/// those keys.
+#[derive(Debug)]
+pub struct LookupJoinExec {
+    input: Arc<dyn ExecutionPlan>,
+    table: LookupTable,
+    /// Pairs of keys of the stream and the indices of the table columns they look up
+    on: Vec<(Arc<dyn PhysicalExpr>, usize)>,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl LookupJoinExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        table: LookupTable,
+        on: Vec<(Arc<dyn PhysicalExpr>, usize)>,
+        schema: SchemaRef,
+    ) -> Self {
+        let cache = PlanProperties::new(
+            EquivalenceProperties::new(schema.clone()),
+            input.output_partitioning().clone(),
+            input.properties().execution_mode,
+        );
+        Self {
+            input,
+            table,
+            on,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        }
+    }
+}
+
+impl DisplayAs for LookupJoinExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                let table_schema = self.table.provider.schema();
+                let on = self
+                    .on
+                    .iter()
+                    .map(|(key, column)| format!("({key}, {})", table_schema.field(*column).name()))
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "LookupJoinExec: table={}, on=[{}]",
+                    self.table,
+                    on.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for LookupJoinExec {
+    fn name(&self) -> &'static str {
+        "LookupJoinExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(LookupJoinExec::new(
+            children[0].clone(),
+            self.table.clone(),
+            self.on.clone(),
+            self.schema.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context.clone())?;
+        let key_types = self
+            .on
+            .iter()
+            .map(|(key, _)| key.data_type(&input.schema()))
+            .collect::<Result<Vec<_>>>()?;
+        let session = SessionStateBuilder::new()
+            .with_config(context.session_config().clone())
+            .with_runtime_env(context.runtime_env())
+            .with_default_features()
+            .build();
+
+        let lookup = Box::new(Lookup {
+            schema: self.schema.clone(),
+            table: self.table.clone(),
+            on: self.on.clone(),
+            converter: RowConverter::new(key_types.iter().cloned().map(SortField::new).collect())?,
+            key_types,
+            cache: LookupCache::new(self.table.cache_capacity, self.table.ttl),
+            session,
+            context,
+            cache_hits: MetricBuilder::new(&self.metrics).counter("cache_hits", partition),
+            cache_misses: MetricBuilder::new(&self.metrics).counter("cache_misses", partition),
+            baseline_metrics: BaselineMetrics::new(&self.metrics, partition),
+        });
+        let stream =
+            futures::stream::try_unfold((input, lookup), |(mut input, mut lookup)| async move {
+                let Some(batch) = input.next().await.transpose()? else {
+                    return Ok(None);
+                };
+                let joined = lookup.join(&batch).await?;
+                lookup.baseline_metrics.record_output(joined.num_rows());
+                Ok(Some((joined, (input, lookup))))
+            });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+/// The rows of the table looked up by key, each cached for at most `ttl`
+struct LookupCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<OwnedRow, (RecordBatch, Instant)>,
+    /// Keys by the time they were loaded, the earliest first. Keys loaded again are queued
+    /// again, their earlier place in the queue is skipped.
+    loaded: VecDeque<(OwnedRow, Instant)>,
+}
+
+impl LookupCache {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            loaded: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, key: &OwnedRow) -> Option<&RecordBatch> {
+        self.entries.get(key).map(|(rows, _)| rows)
+    }
+
+    fn insert(&mut self, key: OwnedRow, rows: RecordBatch, now: Instant) {
+        self.entries.insert(key.clone(), (rows, now));
+        self.loaded.push_back((key, now));
+        while self.entries.len() > self.capacity {
+            self.evict_first();
+        }
+    }
+
+    /// Drop the keys loaded more than `ttl` before `now`
+    fn expire(&mut self, now: Instant) {
+        while self
+            .loaded
+            .front()
+            .is_some_and(|(_, loaded)| now.duration_since(*loaded) >= self.ttl)
+        {
+            self.evict_first();
+        }
+    }
+
+    fn evict_first(&mut self) {
+        if let Some((key, loaded)) = self.loaded.pop_front() {
+            if self.entries.get(&key).is_some_and(|(_, at)| *at == loaded) {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
+
+struct Lookup {
+    schema: SchemaRef,
+    table: LookupTable,
+    on: Vec<(Arc<dyn PhysicalExpr>, usize)>,
+    key_types: Vec<DataType>,
+    converter: RowConverter,
+    cache: LookupCache,
+    session: SessionState,
+    context: Arc<TaskContext>,
+    cache_hits: Count,
+    cache_misses: Count,
+    baseline_metrics: BaselineMetrics,
+}
+
+impl Lookup {
+    async fn join(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let keys = self
+            .on
+            .iter()
+            .map(|(key, _)| key.evaluate(batch)?.into_array(batch.num_rows()))
+            .collect::<Result<Vec<_>>>()?;
+        let rows = self.converter.convert_columns(&keys)?;
+
+        // Rows with a null key match nothing
+        let row_keys = (0..batch.num_rows())
+            .map(|row| {
+                keys.iter()
+                    .all(|key| key.is_valid(row))
+                    .then(|| rows.row(row).owned())
+            })
+            .collect::<Vec<_>>();
+
+        self.cache.expire(Instant::now());
+        let mut missing = HashMap::new();
+        for (row, key) in row_keys.iter().enumerate() {
+            let Some(key) = key else { continue };
+            if self.cache.get(key).is_some() {
+                self.cache_hits.add(1);
+            } else if !missing.contains_key(key) {
+                self.cache_misses.add(1);
+                missing.insert(key.clone(), row);
+            }
+        }
+        if !missing.is_empty() {
+            self.load(&keys, missing).await?;
+        }
+
+        // The first source of table columns is a row of nulls for the rows without matches
+        let table_schema = self.table.provider.schema();
+        let nulls = table_schema
+            .fields()
+            .iter()
+            .map(|field| new_null_array(field.data_type(), 1))
+            .collect::<Vec<_>>();
+        let mut sources = vec![RecordBatch::try_new(table_schema.clone(), nulls)?];
+        let mut positions = HashMap::new();
+        let (mut input_rows, mut table_rows) = (vec![], vec![]);
+        for (row, key) in row_keys.iter().enumerate() {
+            let matches = key.as_ref().and_then(|key| self.cache.get(key));
+            match matches {
+                Some(matches) if matches.num_rows() > 0 => {
+                    let source = *positions.entry(key.clone().unwrap()).or_insert_with(|| {
+                        sources.push(matches.clone());
+                        sources.len() - 1
+                    });
+                    for table_row in 0..matches.num_rows() {
+                        input_rows.push(row as u32);
+                        table_rows.push((source, table_row));
+                    }
+                }
+                _ => {
+                    input_rows.push(row as u32);
+                    table_rows.push((0, 0));
+                }
+            }
+        }
+
+        let input_rows = UInt32Array::from(input_rows);
+        let mut columns = batch
+            .columns()
+            .iter()
+            .map(|column| Ok(take(column, &input_rows, None)?))
+            .collect::<Result<Vec<ArrayRef>>>()?;
+        for column in 0..table_schema.fields().len() {
+            let arrays = sources
+                .iter()
+                .map(|source| source.column(column).as_ref())
+                .collect::<Vec<_>>();
+            columns.push(interleave(&arrays, &table_rows)?);
+        }
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+
+    /// Load the rows of the `missing` keys, given with a row of `keys` holding them, into the
+    /// cache with a single scan of the table
+    async fn load(&mut self, keys: &[ArrayRef], missing: HashMap<OwnedRow, usize>) -> Result<()> {
+        let table_schema = self.table.provider.schema();
+        let filters = self
+            .on
+            .iter()
+            .zip(keys)
+            .map(|((_, column), key)| {
+                let values = missing
+                    .values()
+                    .map(|row| ScalarValue::try_from_array(key, *row))
+                    .collect::<Result<HashSet<_>>>()?;
+                let column = Column::new_unqualified(table_schema.field(*column).name());
+                Ok(Expr::Column(column).in_list(values.into_iter().map(lit).collect(), false))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Filtering on every key column separately loads a superset of the keys' rows, the
+        // rows are matched to the keys exactly below
+        let plan = self
+            .table
+            .provider
+            .scan(&self.session, None, &filters, None)
+            .await?;
+        let loaded = concat_batches(&table_schema, &collect(plan, self.context.clone()).await?)?;
+
+        let table_keys = self
+            .on
+            .iter()
+            .zip(&self.key_types)
+            .map(|((_, column), key_type)| Ok(cast(loaded.column(*column), key_type)?))
+            .collect::<Result<Vec<_>>>()?;
+        let rows = self.converter.convert_columns(&table_keys)?;
+        let mut matches: HashMap<OwnedRow, Vec<u32>> = HashMap::new();
+        for row in 0..loaded.num_rows() {
+            if !table_keys.iter().all(|key| key.is_valid(row)) {
+                continue;
+            }
+            let key = rows.row(row).owned();
+            if missing.contains_key(&key) {
+                matches.entry(key).or_default().push(row as u32);
+            }
+        }
+
+        let now = Instant::now();
+        for key in missing.into_keys() {
+            let indices = UInt32Array::from(matches.remove(&key).unwrap_or_default());
+            self.cache
+                .insert(key, take_record_batch(&loaded, &indices)?, now);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::{Field, Schema};
+
+    #[test]
+    fn cache_evicts_keys_loaded_first_and_expires_them() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let converter = RowConverter::new(vec![SortField::new(DataType::Int64)])?;
+        let ids: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3]));
+        let keys = converter.convert_columns(&[ids.clone()])?;
+        let key = |row: usize| keys.row(row).owned();
+        let rows = RecordBatch::try_new(schema, vec![ids])?;
+
+        let start = Instant::now();
+        let mut cache = LookupCache::new(2, Duration::from_secs(60));
+        cache.insert(key(0), rows.clone(), start);
+        cache.insert(key(1), rows.clone(), start + Duration::from_secs(10));
+        cache.insert(key(2), rows.clone(), start + Duration::from_secs(20));
+        assert!(cache.get(&key(0)).is_none());
+        assert!(cache.get(&key(1)).is_some());
+
+        // Loading a key again restarts its time to live
+        cache.insert(key(1), rows, start + Duration::from_secs(50));
+        cache.expire(start + Duration::from_secs(85));
+        assert!(cache.get(&key(1)).is_some());
+        assert!(cache.get(&key(2)).is_none());
+        Ok(())
+    }
+}
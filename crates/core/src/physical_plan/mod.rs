@@ -1,2 +1,15 @@
+pub mod coalesce;
 pub mod continuous;
+pub mod deduplicate;
+pub mod fault_injection;
+pub mod fused;
+pub mod interval_join;
+pub mod lookup_join;
+pub mod profile;
+pub mod quality;
+pub mod reorder;
+pub mod sample;
+pub mod streaming_repartition;
+pub mod tap;
+pub mod two_input;
 pub mod utils;
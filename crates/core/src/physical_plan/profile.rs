@@ -0,0 +1,153 @@
+use std::any::Any;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use futures::{Stream, StreamExt};
+
+use datafusion::common::instant::Instant;
+use datafusion::common::Result;
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+
+use crate::utils::profiling::{allocated_bytes, Profiler};
+
+/// Wraps an operator and records the time spent polling it, the time spent waiting on it, the
+/// time its output waited on the consumer and the bytes allocated while polling into the
+/// session's [`Profiler`].
+#[derive(Debug)]
+pub struct ProfileExec {
+    input: Arc<dyn ExecutionPlan>,
+    stack: String,
+}
+
+impl ProfileExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, stack: String) -> Self {
+        Self { input, stack }
+    }
+
+    pub fn stack(&self) -> &str {
+        &self.stack
+    }
+}
+
+impl DisplayAs for ProfileExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "ProfileExec: stack={}", self.stack)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for ProfileExec {
+    fn name(&self) -> &'static str {
+        "ProfileExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(ProfileExec::new(
+            children[0].clone(),
+            self.stack.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let profiler = Profiler::from_task_context(&context);
+        let input = self.input.execute(partition, context)?;
+        match profiler {
+            Some(profiler) => Ok(Box::pin(ProfiledStream {
+                input,
+                stack: self.stack.clone(),
+                profiler,
+                pending_since: None,
+                ready_since: None,
+            })),
+            None => Ok(input),
+        }
+    }
+}
+
+struct ProfiledStream {
+    input: SendableRecordBatchStream,
+    stack: String,
+    profiler: Arc<Profiler>,
+    pending_since: Option<Instant>,
+    /// Set while a batch we returned is being processed downstream
+    ready_since: Option<Instant>,
+}
+
+impl Stream for ProfiledStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let wait_nanos = self
+            .pending_since
+            .take()
+            .map_or(0, |since| since.elapsed().as_nanos() as u64);
+        let blocked_nanos = self
+            .ready_since
+            .take()
+            .map_or(0, |since| since.elapsed().as_nanos() as u64);
+
+        let allocated_before = allocated_bytes();
+        let start = Instant::now();
+        let poll = self.input.poll_next_unpin(cx);
+        let cpu_nanos = start.elapsed().as_nanos() as u64;
+        let allocated = allocated_bytes().saturating_sub(allocated_before);
+
+        if poll.is_pending() {
+            self.pending_since = Some(Instant::now());
+        }
+        let rows = match &poll {
+            Poll::Ready(Some(Ok(batch))) => {
+                self.ready_since = Some(Instant::now());
+                batch.num_rows() as u64
+            }
+            _ => 0,
+        };
+
+        self.profiler.record(&self.stack, |profile| {
+            profile.cpu_nanos += cpu_nanos;
+            profile.wait_nanos += wait_nanos;
+            profile.blocked_nanos += blocked_nanos;
+            profile.allocated_bytes += allocated;
+            profile.output_rows += rows;
+        });
+        self.profiler.maybe_report();
+        poll
+    }
+}
+
+impl RecordBatchStream for ProfiledStream {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+}
@@ -0,0 +1,464 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::AsArray;
+use arrow::compute::kernels::{boolean, cmp};
+use arrow::compute::{filter_record_batch, is_null, prep_null_mask_filter};
+use arrow::datatypes::TimestampMillisecondType;
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{Array, BooleanArray, RecordBatch, TimestampMillisecondArray};
+use futures::StreamExt;
+
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_expr::Partitioning;
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, Distribution, ExecutionPlan, PlanProperties,
+};
+
+use crate::datasource::message::now_ms;
+use crate::datasource::side_output::{SideOutput, SideOutputRegistry};
+use crate::physical_plan::utils::metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN};
+
+/// A property every row of a stream is expected to have
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum QualityRule {
+    NotNull(String),
+    /// Values between `min` and `max`, both inclusive. Nulls pass, see [`Self::NotNull`].
+    Range {
+        column: String,
+        min: Option<ScalarValue>,
+        max: Option<ScalarValue>,
+    },
+    /// Every combination of values of `columns` appears at most once within `window` of
+    /// event time. The first row of a combination passes, repeats violate the rule.
+    UniqueWithin {
+        columns: Vec<String>,
+        window: Duration,
+    },
+    /// Event times at most this far behind the wall clock
+    Freshness(Duration),
+}
+
+impl QualityRule {
+    /// Columns the rule reads, other than the stream metadata
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Self::NotNull(column) | Self::Range { column, .. } => vec![column.as_str()],
+            Self::UniqueWithin { columns, .. } => columns.iter().map(String::as_str).collect(),
+            Self::Freshness(_) => vec![],
+        }
+    }
+
+    /// Whether the rule reads the event times of the stream metadata
+    pub fn needs_event_time(&self) -> bool {
+        matches!(self, Self::UniqueWithin { .. } | Self::Freshness(_))
+    }
+}
+
+impl fmt::Display for QualityRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotNull(column) => write!(f, "not_null({column})"),
+            Self::Range { column, min, max } => {
+                let bound = |value: &Option<ScalarValue>| {
+                    value
+                        .as_ref()
+                        .map_or("_".to_string(), |value| value.to_string())
+                };
+                write!(f, "range({column}, {}..={})", bound(min), bound(max))
+            }
+            Self::UniqueWithin { columns, window } => {
+                write!(f, "unique_within({}, {window:?})", columns.join(", "))
+            }
+            Self::Freshness(max_delay) => write!(f, "freshness({max_delay:?})"),
+        }
+    }
+}
+
+/// What happens to the rows that violate a [`QualityCheck`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum QualityAction {
+    Drop,
+    /// Send the rows to the side output with this tag, see
+    /// [`Context::side_output`](crate::context::Context::side_output)
+    DeadLetter(String),
+    /// Fail the job
+    Fail,
+}
+
+/// A named rule and what to do with the rows violating it. The name labels the check's
+/// violation counter in the operator metrics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QualityCheck {
+    pub name: String,
+    pub rule: QualityRule,
+    pub action: QualityAction,
+}
+
+impl QualityCheck {
+    pub fn new(name: &str, rule: QualityRule, action: QualityAction) -> Self {
+        Self {
+            name: name.to_string(),
+            rule,
+            action,
+        }
+    }
+}
+
+/// Applies [`QualityCheck`]s to its input, in order. A check only sees the rows the checks
+/// before it let through, so a row is counted against the first check it violates.
+/// Uniqueness has to see every row of the stream, with such a check the input is merged into
+/// a single partition.
+#[derive(Debug)]
+pub struct QualityExec {
+    input: Arc<dyn ExecutionPlan>,
+    checks: Vec<QualityCheck>,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl QualityExec {
+    pub fn try_new(input: Arc<dyn ExecutionPlan>, checks: Vec<QualityCheck>) -> Result<Self> {
+        let schema = input.schema();
+        for check in &checks {
+            for column in check.rule.columns() {
+                if schema.field_with_name(column).is_err() {
+                    return plan_err!("Column {column} of quality check {} not found", check.name);
+                }
+            }
+        }
+        let mut cache = input.properties().clone();
+        if has_unique_check(&checks) {
+            cache = cache.with_partitioning(Partitioning::UnknownPartitioning(1));
+        }
+        Ok(Self {
+            input,
+            checks,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        })
+    }
+}
+
+fn has_unique_check(checks: &[QualityCheck]) -> bool {
+    checks
+        .iter()
+        .any(|check| matches!(check.rule, QualityRule::UniqueWithin { .. }))
+}
+
+impl DisplayAs for QualityExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                let checks: Vec<String> = self
+                    .checks
+                    .iter()
+                    .map(|check| format!("{}={}", check.name, check.rule))
+                    .collect();
+                write!(f, "QualityExec: checks=[{}]", checks.join(", "))
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for QualityExec {
+    fn name(&self) -> &'static str {
+        "QualityExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn required_input_distribution(&self) -> Vec<Distribution> {
+        match has_unique_check(&self.checks) {
+            true => vec![Distribution::SinglePartition],
+            false => vec![Distribution::UnspecifiedDistribution],
+        }
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(QualityExec::try_new(
+            children[0].clone(),
+            self.checks.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context.clone())?;
+        let schema = input.schema();
+        let mut evaluators = self
+            .checks
+            .iter()
+            .map(|check| {
+                let dead_letter = match &check.action {
+                    QualityAction::DeadLetter(tag) => Some(
+                        SideOutputRegistry::from_task_context(&context)?
+                            .output(tag, schema.clone())?,
+                    ),
+                    _ => None,
+                };
+                Ok(CheckEvaluator {
+                    check: check.clone(),
+                    violations: MetricBuilder::new(&self.metrics)
+                        .counter(format!("{}_violations", check.name), partition),
+                    dead_letter,
+                    seen: HashMap::new(),
+                    max_timestamp: i64::MIN,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let stream = input.map(move |batch| {
+            let mut batch = batch?;
+            for evaluator in &mut evaluators {
+                batch = evaluator.apply(batch)?;
+            }
+            Ok(batch)
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+struct CheckEvaluator {
+    check: QualityCheck,
+    violations: Count,
+    dead_letter: Option<SideOutput>,
+    /// For uniqueness, the latest event time of every combination of values
+    seen: HashMap<OwnedRow, i64>,
+    max_timestamp: i64,
+}
+
+impl CheckEvaluator {
+    /// The rows of `batch` that pass the check, acting on the others
+    fn apply(&mut self, batch: RecordBatch) -> Result<RecordBatch> {
+        if batch.num_rows() == 0 {
+            return Ok(batch);
+        }
+        let violating = self.violations(&batch)?;
+        let count = violating.true_count();
+        if count == 0 {
+            return Ok(batch);
+        }
+        self.violations.add(count);
+        match &self.check.action {
+            QualityAction::Fail => {
+                return exec_err!(
+                    "{count} rows violate quality check {} ({})",
+                    self.check.name,
+                    self.check.rule
+                );
+            }
+            QualityAction::DeadLetter(_) => {
+                if let Some(output) = &self.dead_letter {
+                    output.emit(filter_record_batch(&batch, &violating)?);
+                }
+            }
+            QualityAction::Drop => {}
+        }
+        Ok(filter_record_batch(&batch, &boolean::not(&violating)?)?)
+    }
+
+    /// Mask of the rows violating the rule, without nulls
+    fn violations(&mut self, batch: &RecordBatch) -> Result<BooleanArray> {
+        let mask = match &self.check.rule {
+            QualityRule::NotNull(column) => is_null(batch.column_by_name(column).unwrap())?,
+            QualityRule::Range { column, min, max } => {
+                let values = batch.column_by_name(column).unwrap();
+                let mut mask = BooleanArray::from(vec![false; batch.num_rows()]);
+                if let Some(min) = min {
+                    let min = min.cast_to(values.data_type())?.to_scalar()?;
+                    mask = boolean::or(&mask, &prep_null_mask_filter(&cmp::lt(values, &min)?))?;
+                }
+                if let Some(max) = max {
+                    let max = max.cast_to(values.data_type())?.to_scalar()?;
+                    mask = boolean::or(&mask, &prep_null_mask_filter(&cmp::gt(values, &max)?))?;
+                }
+                mask
+            }
+            QualityRule::UniqueWithin { columns, window } => {
+                self.repeats(batch, columns, window.as_millis() as i64)?
+            }
+            QualityRule::Freshness(max_delay) => {
+                let oldest = now_ms() - max_delay.as_millis() as i64;
+                event_times(batch)?
+                    .iter()
+                    .map(|timestamp| Some(timestamp.is_some_and(|timestamp| timestamp < oldest)))
+                    .collect()
+            }
+        };
+        Ok(prep_null_mask_filter(&mask))
+    }
+
+    fn repeats(
+        &mut self,
+        batch: &RecordBatch,
+        columns: &[String],
+        window_ms: i64,
+    ) -> Result<BooleanArray> {
+        let keys = columns
+            .iter()
+            .map(|column| batch.column_by_name(column).unwrap().clone())
+            .collect::<Vec<_>>();
+        let converter = RowConverter::new(
+            keys.iter()
+                .map(|key| SortField::new(key.data_type().clone()))
+                .collect(),
+        )?;
+        let rows = converter.convert_columns(&keys)?;
+        let timestamps = event_times(batch)?;
+
+        let mut repeats = Vec::with_capacity(batch.num_rows());
+        for (row, timestamp) in rows.iter().zip(timestamps.iter()) {
+            let timestamp = timestamp.unwrap_or(self.max_timestamp);
+            self.max_timestamp = self.max_timestamp.max(timestamp);
+            let key = row.owned();
+            let repeat = self
+                .seen
+                .get(&key)
+                .is_some_and(|seen| (timestamp - seen).abs() < window_ms);
+            if !repeat {
+                self.seen.insert(key, timestamp);
+            }
+            repeats.push(repeat);
+        }
+        let horizon = self.max_timestamp.saturating_sub(window_ms);
+        self.seen.retain(|_, seen| *seen >= horizon);
+        Ok(BooleanArray::from(repeats))
+    }
+}
+
+fn event_times(batch: &RecordBatch) -> Result<TimestampMillisecondArray> {
+    let Some(metadata) = batch.column_by_name(STREAMING_METADATA_COLUMN) else {
+        return exec_err!("Quality checks on event time need the stream metadata column");
+    };
+    Ok(metadata
+        .as_struct()
+        .column_by_name(CANONICAL_TIMESTAMP_FIELD)
+        .unwrap()
+        .as_primitive::<TimestampMillisecondType>()
+        .clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::datatypes::Int64Type;
+    use arrow_array::{Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+    fn evaluator(rule: QualityRule, action: QualityAction) -> CheckEvaluator {
+        CheckEvaluator {
+            check: QualityCheck::new("check", rule, action),
+            violations: Default::default(),
+            dead_letter: None,
+            seen: HashMap::new(),
+            max_timestamp: i64::MIN,
+        }
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<i64> {
+        batch
+            .column(0)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn apply_rules() -> Result<()> {
+        let now = now_ms();
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("sku", DataType::Utf8, true),
+                Field::new("amount", DataType::Int64, true),
+                stream_metadata_field(),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4, 5])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    None,
+                    Some("b"),
+                    Some("a"),
+                    Some("c"),
+                ])),
+                Arc::new(Int64Array::from(vec![
+                    Some(10),
+                    Some(20),
+                    Some(-5),
+                    None,
+                    Some(500),
+                ])),
+                Arc::new(stream_metadata_array(TimestampMillisecondArray::from(
+                    vec![now, now, now, now + 1, now - 3_600_000],
+                ))),
+            ],
+        )?;
+
+        let mut not_null = evaluator(QualityRule::NotNull("sku".into()), QualityAction::Drop);
+        assert_eq!(ids(&not_null.apply(batch.clone())?), vec![1, 3, 4, 5]);
+        assert_eq!(not_null.violations.value(), 1);
+
+        let mut range = evaluator(
+            QualityRule::Range {
+                column: "amount".into(),
+                min: Some(ScalarValue::Int32(Some(0))),
+                max: Some(ScalarValue::Int64(Some(100))),
+            },
+            QualityAction::Drop,
+        );
+        assert_eq!(ids(&range.apply(batch.clone())?), vec![1, 2, 4]);
+
+        let mut unique = evaluator(
+            QualityRule::UniqueWithin {
+                columns: vec!["sku".into()],
+                window: Duration::from_secs(60),
+            },
+            QualityAction::Drop,
+        );
+        assert_eq!(ids(&unique.apply(batch.clone())?), vec![1, 2, 3, 5]);
+
+        let mut fresh = evaluator(
+            QualityRule::Freshness(Duration::from_secs(60)),
+            QualityAction::Fail,
+        );
+        assert!(fresh.apply(batch).is_err());
+        Ok(())
+    }
+}
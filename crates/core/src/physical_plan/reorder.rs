@@ -0,0 +1,431 @@
+use std::any::Any;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use arrow::array::AsArray;
+use arrow::compute::{concat_batches, filter_record_batch, take_record_batch};
+use arrow::datatypes::TimestampMillisecondType;
+use arrow_array::{BooleanArray, RecordBatch, TimestampMillisecondArray, UInt32Array};
+use arrow_schema::SchemaRef;
+use futures::{ready, Stream, StreamExt};
+
+use datafusion::common::{plan_err, Result};
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::metrics::{
+    BaselineMetrics, Count, ExecutionPlanMetricsSet, Gauge, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+
+use crate::physical_plan::utils::metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN};
+use crate::physical_plan::utils::stream_message::{restamp, MessageStream, StreamMessage};
+
+/// Holds rows back for up to `delay` of event time and passes them on sorted by event time,
+/// for operators that expect their input in order from sources that deliver it only mostly
+/// in order. A row is released once a row with an event time `delay` past its own arrived, or
+/// once the watermark passed it. Rows older than the rows already released are late, they are
+/// passed on right away and counted as `late_rows`. Every partition is sorted on its own.
+///
+/// Watermarks and barriers are passed on with the rows released after them. A barrier
+/// releases every buffered row first, as the rows it covers can't be held past it.
+#[derive(Debug)]
+pub struct ReorderExec {
+    input: Arc<dyn ExecutionPlan>,
+    delay: Duration,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl ReorderExec {
+    pub fn try_new(input: Arc<dyn ExecutionPlan>, delay: Duration) -> Result<Self> {
+        if input
+            .schema()
+            .column_with_name(STREAMING_METADATA_COLUMN)
+            .is_none()
+        {
+            return plan_err!("Reordering needs the event times of a streaming source");
+        }
+        let cache = input.properties().clone();
+        Ok(Self {
+            input,
+            delay,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        })
+    }
+}
+
+impl DisplayAs for ReorderExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "ReorderExec: delay={:?}", self.delay)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for ReorderExec {
+    fn name(&self) -> &'static str {
+        "ReorderExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![false]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(ReorderExec::try_new(
+            children[0].clone(),
+            self.delay,
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        Ok(Box::pin(ReorderStream {
+            schema: input.schema(),
+            input: MessageStream::new(input),
+            buffer: ReorderBuffer::new(self.delay),
+            watermark: None,
+            pending_barrier: None,
+            late_rows: MetricBuilder::new(&self.metrics).counter("late_rows", partition),
+            buffered_rows: MetricBuilder::new(&self.metrics).gauge("buffered_rows", partition),
+            baseline_metrics: BaselineMetrics::new(&self.metrics, partition),
+        }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+fn event_times(batch: &RecordBatch) -> &TimestampMillisecondArray {
+    batch
+        .column_by_name(STREAMING_METADATA_COLUMN)
+        .unwrap()
+        .as_struct()
+        .column_by_name(CANONICAL_TIMESTAMP_FIELD)
+        .unwrap()
+        .as_primitive::<TimestampMillisecondType>()
+}
+
+/// The rows of one partition not released yet, sorted by event time
+struct ReorderBuffer {
+    delay_ms: i64,
+    rows: Option<RecordBatch>,
+    latest: Option<i64>,
+    /// Event time up to which rows were released
+    released: Option<i64>,
+}
+
+impl ReorderBuffer {
+    fn new(delay: Duration) -> Self {
+        Self {
+            delay_ms: delay.as_millis() as i64,
+            rows: None,
+            latest: None,
+            released: None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.rows.as_ref().map_or(0, |rows| rows.num_rows())
+    }
+
+    /// Buffer the rows of `batch` and release the ones `delay` behind the latest event time,
+    /// returning the late rows of the batch first and the released rows after them. Rows
+    /// without an event time are passed on like late rows.
+    fn push(&mut self, batch: &RecordBatch) -> Result<(usize, RecordBatch)> {
+        let times = event_times(batch);
+        let late: BooleanArray = times
+            .iter()
+            .map(|time| Some(time.map_or(true, |time| Some(time) <= self.released)))
+            .collect();
+        self.latest = self.latest.max(arrow::compute::max(times));
+
+        let schema = batch.schema();
+        let mut buffered: Vec<RecordBatch> = self.rows.take().into_iter().collect();
+        buffered.push(filter_record_batch(batch, &arrow::compute::not(&late)?)?);
+        let buffered = concat_batches(&schema, &buffered)?;
+        let horizon = self
+            .latest
+            .map(|latest| latest.saturating_sub(self.delay_ms));
+        let released = self.release(buffered, horizon)?;
+
+        let late = filter_record_batch(batch, &late)?;
+        Ok((late.num_rows(), concat_batches(&schema, &[late, released])?))
+    }
+
+    /// Sort `buffered` and release the rows up to `horizon`, or all of them, keeping the rest
+    fn release(&mut self, buffered: RecordBatch, horizon: Option<i64>) -> Result<RecordBatch> {
+        // Rows with the same event time stay in the order they arrived in
+        let mut order: Vec<(i64, u32)> = event_times(&buffered)
+            .values()
+            .iter()
+            .enumerate()
+            .map(|(row, time)| (*time, row as u32))
+            .collect();
+        order.sort_unstable();
+        let ready = match horizon {
+            Some(horizon) => order.partition_point(|(time, _)| *time <= horizon),
+            None => order.len(),
+        };
+        if ready > 0 {
+            self.released = self.released.max(Some(order[ready - 1].0));
+        }
+        let take = |rows: &[(i64, u32)]| {
+            take_record_batch(
+                &buffered,
+                &UInt32Array::from_iter_values(rows.iter().map(|(_, row)| *row)),
+            )
+        };
+        let kept = take(&order[ready..])?;
+        self.rows = (kept.num_rows() > 0).then_some(kept);
+        Ok(take(&order[..ready])?)
+    }
+
+    /// Release the rows up to `watermark_ms`, no rows older than those are expected anymore
+    fn release_to(&mut self, watermark_ms: i64) -> Result<Option<RecordBatch>> {
+        self.rows
+            .take()
+            .map(|rows| self.release(rows, Some(watermark_ms)))
+            .transpose()
+    }
+
+    /// Release everything still buffered
+    fn flush(&mut self) -> Result<Option<RecordBatch>> {
+        self.rows
+            .take()
+            .map(|rows| self.release(rows, None))
+            .transpose()
+    }
+}
+
+struct ReorderStream {
+    schema: SchemaRef,
+    input: MessageStream,
+    buffer: ReorderBuffer,
+    /// The latest watermark of the input, passed on with the rows released after it
+    watermark: Option<SystemTime>,
+    /// The latest epoch closed since rows were last passed on
+    pending_barrier: Option<u64>,
+    late_rows: Count,
+    buffered_rows: Gauge,
+    baseline_metrics: BaselineMetrics,
+}
+
+impl ReorderStream {
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            let output = match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(StreamMessage::Data(batch))) => {
+                    let (late, output) = self.buffer.push(&batch)?;
+                    self.late_rows.add(late);
+                    Some(output)
+                }
+                Some(Ok(StreamMessage::Watermark(watermark))) => {
+                    self.watermark = self.watermark.max(Some(watermark));
+                    let watermark_ms = watermark.duration_since(UNIX_EPOCH).unwrap().as_millis();
+                    self.buffer.release_to(watermark_ms as i64)?
+                }
+                Some(Ok(StreamMessage::Barrier(epoch))) => {
+                    self.pending_barrier = self.pending_barrier.max(Some(epoch));
+                    self.buffer.flush()?
+                }
+                Some(Ok(StreamMessage::EndOfPartition)) | None => match self.buffer.flush()? {
+                    Some(output) => Some(output),
+                    None => return Poll::Ready(None),
+                },
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+            };
+            self.buffered_rows.set(self.buffer.len());
+            match output {
+                Some(output) if output.num_rows() > 0 => {
+                    // The released rows still carry the metadata of the batches they came from
+                    let output = restamp(&output, self.pending_barrier.take(), self.watermark);
+                    return Poll::Ready(Some(output));
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Stream for ReorderStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+}
+
+impl RecordBatchStream for ReorderStream {
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::datatypes::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    use crate::physical_plan::utils::metadata::{
+        stream_metadata_array, stream_metadata_array_with_watermark, stream_metadata_field,
+        stream_metadata_field_with_watermark, BARRIER_FIELD, WATERMARK_FIELD,
+    };
+    use crate::physical_plan::utils::stream_message::{barrier_marker, NO_BARRIER};
+
+    fn batch(ids: Vec<i64>, timestamps: Vec<Option<i64>>) -> Result<RecordBatch> {
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                stream_metadata_field(),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(stream_metadata_array(timestamps.into())),
+            ],
+        )?)
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<i64> {
+        batch
+            .column(0)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn release_rows_in_event_time_order() -> Result<()> {
+        let mut buffer = ReorderBuffer::new(Duration::from_secs(2));
+
+        let (late, released) = buffer.push(&batch(
+            vec![1, 2, 3],
+            vec![Some(3_000), Some(1_000), Some(2_000)],
+        )?)?;
+        assert_eq!((late, ids(&released)), (0, vec![2]));
+
+        let (late, released) = buffer.push(&batch(
+            vec![4, 5, 6, 7],
+            vec![Some(5_500), Some(2_500), Some(500), None],
+        )?)?;
+        // 6 is older than 2, which was already released, and 7 has no event time
+        assert_eq!((late, ids(&released)), (2, vec![6, 7, 3, 5, 1]));
+        assert_eq!(buffer.len(), 1);
+
+        assert_eq!(ids(&buffer.flush()?.unwrap()), vec![4]);
+        assert!(buffer.flush()?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pass_watermarks_and_barriers_on() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            stream_metadata_field_with_watermark(),
+        ]));
+        let batches = [
+            (vec![1, 2, 3], vec![3_000, 1_000, 2_000], NO_BARRIER, 1_500),
+            (
+                vec![4, 5],
+                vec![2_500, 500],
+                barrier_marker(7).as_str(),
+                2_600,
+            ),
+            (vec![6], vec![4_000], NO_BARRIER, 3_000),
+        ]
+        .into_iter()
+        .map(|(ids, timestamps, barrier, watermark)| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(stream_metadata_array_with_watermark(
+                        timestamps.into(),
+                        barrier,
+                        Some(watermark),
+                    )),
+                ],
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+        let input = MemoryExec::try_new(&[batches], schema, None)?;
+        let exec = ReorderExec::try_new(Arc::new(input), Duration::from_secs(2))?;
+        let output = collect(exec.execute(0, Arc::new(TaskContext::default()))?).await?;
+
+        let metadata = |batch: &RecordBatch, field: &str| {
+            batch
+                .column_by_name(STREAMING_METADATA_COLUMN)
+                .unwrap()
+                .as_struct()
+                .column_by_name(field)
+                .unwrap()
+                .clone()
+        };
+        // 3 and 4 are released by the second watermark, 1 by the barrier and 6 at the end
+        assert_eq!(
+            output.iter().map(ids).collect::<Vec<_>>(),
+            vec![vec![2], vec![5], vec![3, 4], vec![1], vec![6]]
+        );
+        let barriers: Vec<Vec<String>> = output
+            .iter()
+            .map(|batch| {
+                metadata(batch, BARRIER_FIELD)
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|marker| marker.unwrap().to_string())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(barriers[3], vec![barrier_marker(7)]);
+        assert!(barriers
+            .iter()
+            .enumerate()
+            .all(|(i, markers)| i == 3 || markers.iter().all(|marker| marker == NO_BARRIER)));
+        assert_eq!(
+            metadata(&output[2], WATERMARK_FIELD)
+                .as_primitive::<TimestampMillisecondType>()
+                .values(),
+            &[2_600, 2_600]
+        );
+        assert_eq!(
+            exec.metrics()
+                .unwrap()
+                .sum_by_name("late_rows")
+                .unwrap()
+                .as_usize(),
+            1
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,515 @@
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use arrow::array::AsArray;
+use arrow::compute::{concat_batches, interleave, take_record_batch};
+use arrow::datatypes::TimestampMillisecondType;
+use arrow_array::{Array, RecordBatch, UInt32Array};
+use arrow_schema::SchemaRef;
+use futures::{ready, stream, Stream, StreamExt};
+use rand::rngs::StdRng;
+use rand::Rng;
+use tokio::time::Instant;
+
+use datafusion::common::Result;
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+
+use crate::physical_plan::utils::metadata::{CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN};
+use crate::physical_plan::utils::stream_message::{
+    control_batch, control_of, is_control_batch, restamp, MessageStream, StreamMessage,
+};
+use crate::utils::determinism::operator_rng;
+
+/// How [`SampleExec`] picks the rows it passes on. Watermarks and checkpoint barriers are
+/// passed on in control batches rather than with the rows that carried them, so sampling
+/// never holds back event time or checkpoints and keeps no row it didn't pick.
+#[derive(Debug, Clone, Copy)]
+pub enum SampleMethod {
+    /// Every row independently with the given probability
+    Bernoulli(f64),
+    /// A uniform sample of at most `size` rows from every tumbling event time window of
+    /// length `window`, emitted once the watermark passes the end of the window
+    Reservoir { size: usize, window: Duration },
+    /// At most this many rows per second of processing time, picked at random from every
+    /// batch that exceeds the budget
+    Rate(f64),
+}
+
+impl SampleMethod {
+    fn key(&self) -> (u8, u64, u128) {
+        match self {
+            Self::Bernoulli(probability) => (0, probability.to_bits(), 0),
+            Self::Reservoir { size, window } => (1, *size as u64, window.as_nanos()),
+            Self::Rate(rows_per_second) => (2, rows_per_second.to_bits(), 0),
+        }
+    }
+}
+
+impl PartialEq for SampleMethod {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SampleMethod {}
+
+impl Hash for SampleMethod {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+impl fmt::Display for SampleMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bernoulli(probability) => write!(f, "bernoulli({probability})"),
+            Self::Reservoir { size, window } => write!(f, "reservoir({size}, {window:?})"),
+            Self::Rate(rows_per_second) => write!(f, "rate({rows_per_second}/s)"),
+        }
+    }
+}
+
+/// Passes on a representative subset of its input, see [`SampleMethod`]
+#[derive(Debug)]
+pub struct SampleExec {
+    input: Arc<dyn ExecutionPlan>,
+    method: SampleMethod,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl SampleExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, method: SampleMethod) -> Self {
+        let cache = input.properties().clone();
+        Self {
+            input,
+            method,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        }
+    }
+}
+
+impl DisplayAs for SampleExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "SampleExec: method={}", self.method)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for SampleExec {
+    fn name(&self) -> &'static str {
+        "SampleExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![!matches!(self.method, SampleMethod::Reservoir { .. })]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(SampleExec::new(children[0].clone(), self.method)))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context.clone())?;
+        let schema = input.schema();
+        let baseline_metrics = BaselineMetrics::new(&self.metrics, partition);
+        let mut rng = operator_rng(&context, "sample", partition);
+
+        match self.method {
+            SampleMethod::Bernoulli(probability) => {
+                let stream = input.flat_map(move |batch| {
+                    let sample = batch.and_then(|batch| {
+                        let picked = (0..batch.num_rows())
+                            .filter(|_| rng.gen_bool(probability))
+                            .collect();
+                        take_sample(&batch, picked)
+                    });
+                    output_stream(sample, &baseline_metrics)
+                });
+                Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+            }
+            SampleMethod::Rate(rows_per_second) => {
+                let mut limiter = RateLimiter::new(rows_per_second);
+                let stream = input.flat_map(move |batch| {
+                    let sample = batch.and_then(|batch| {
+                        let budget = limiter.take(batch.num_rows());
+                        match budget < batch.num_rows() {
+                            true => {
+                                let picked =
+                                    rand::seq::index::sample(&mut rng, batch.num_rows(), budget)
+                                        .into_vec();
+                                take_sample(&batch, picked)
+                            }
+                            false => Ok(vec![batch]),
+                        }
+                    });
+                    output_stream(sample, &baseline_metrics)
+                });
+                Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+            }
+            SampleMethod::Reservoir { size, window } => Ok(Box::pin(ReservoirStream {
+                schema,
+                input: MessageStream::new(input),
+                size,
+                window_ms: (window.as_millis() as i64).max(1),
+                reservoirs: BTreeMap::new(),
+                watermark: None,
+                pending: VecDeque::new(),
+                rng,
+                baseline_metrics,
+            })),
+        }
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+/// The `picked` rows of `batch`, followed by the control batch of all its rows. Control
+/// batches pass as they are, since no rows are picked from them.
+fn take_sample(batch: &RecordBatch, picked: Vec<usize>) -> Result<Vec<RecordBatch>> {
+    let indices = UInt32Array::from_iter_values(picked.into_iter().map(|index| index as u32));
+    let sample = take_record_batch(batch, &indices)?;
+    let sample = (sample.num_rows() > 0 || is_control_batch(&sample)).then_some(sample);
+    Ok(sample.into_iter().chain(control_of(batch)?).collect())
+}
+
+/// The batches of one input batch, recording the rows passed on
+fn output_stream(
+    batches: Result<Vec<RecordBatch>>,
+    baseline_metrics: &BaselineMetrics,
+) -> impl Stream<Item = Result<RecordBatch>> {
+    let batches = match batches {
+        Ok(batches) => batches.into_iter().map(Ok).collect(),
+        Err(e) => vec![Err(e)],
+    };
+    for batch in batches.iter().flatten() {
+        baseline_metrics.record_output(batch.num_rows());
+    }
+    stream::iter(batches)
+}
+
+/// Token bucket that refills at `rows_per_second`, holding at most a second's worth of rows
+struct RateLimiter {
+    rows_per_second: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    fn new(rows_per_second: f64) -> Self {
+        Self {
+            rows_per_second,
+            tokens: rows_per_second,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// How many of `rows` fit into the budget, which are deducted from it
+    fn take(&mut self, rows: usize) -> usize {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rows_per_second).min(self.rows_per_second);
+        self.refilled = now;
+
+        let granted = (rows as f64).min(self.tokens.floor());
+        self.tokens -= granted;
+        granted as usize
+    }
+}
+
+/// Sample of one window, see Algorithm R
+#[derive(Default)]
+struct Reservoir {
+    seen: u64,
+    sample: Option<RecordBatch>,
+}
+
+struct ReservoirStream {
+    schema: SchemaRef,
+    input: MessageStream,
+    size: usize,
+    window_ms: i64,
+    /// By window start
+    reservoirs: BTreeMap<i64, Reservoir>,
+    watermark: Option<SystemTime>,
+    /// Samples and the control batch after them, not yet returned
+    pending: VecDeque<RecordBatch>,
+    rng: StdRng,
+    baseline_metrics: BaselineMetrics,
+}
+
+impl ReservoirStream {
+    fn process_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let timestamps = batch
+            .column_by_name(STREAMING_METADATA_COLUMN)
+            .unwrap()
+            .as_struct()
+            .column_by_name(CANONICAL_TIMESTAMP_FIELD)
+            .unwrap()
+            .as_primitive::<TimestampMillisecondType>();
+        let mut rows_by_window: BTreeMap<i64, Vec<usize>> = BTreeMap::new();
+        for (row, timestamp) in timestamps.iter().enumerate() {
+            if let Some(timestamp) = timestamp {
+                let start = timestamp.div_euclid(self.window_ms) * self.window_ms;
+                rows_by_window.entry(start).or_default().push(row);
+            }
+        }
+
+        for (start, rows) in rows_by_window {
+            let reservoir = self.reservoirs.entry(start).or_default();
+            let mut sources = vec![];
+            if let Some(sample) = reservoir.sample.take() {
+                sources.push(sample);
+            }
+            // Slots refer to a row of one of the sources
+            let mut slots: Vec<(usize, usize)> = match sources.first() {
+                Some(sample) => (0..sample.num_rows()).map(|row| (0, row)).collect(),
+                None => vec![],
+            };
+            let incoming = sources.len();
+            sources.push(batch.clone());
+
+            for row in rows {
+                reservoir.seen += 1;
+                if slots.len() < self.size {
+                    slots.push((incoming, row));
+                } else {
+                    let slot = self.rng.gen_range(0..reservoir.seen) as usize;
+                    if slot < self.size {
+                        slots[slot] = (incoming, row);
+                    }
+                }
+            }
+
+            // Copy the kept rows so the sample doesn't hold on to whole input batches
+            let columns = (0..self.schema.fields().len())
+                .map(|column| {
+                    let arrays: Vec<&dyn Array> = sources
+                        .iter()
+                        .map(|source| source.column(column).as_ref())
+                        .collect();
+                    interleave(&arrays, &slots)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            reservoir.sample = Some(RecordBatch::try_new(Arc::clone(&self.schema), columns)?);
+        }
+        Ok(())
+    }
+
+    /// The samples of the windows ending before `watermark_ms`, or of every window
+    fn emit(&mut self, watermark_ms: Option<i64>) -> Result<RecordBatch> {
+        let closed: Vec<i64> = self
+            .reservoirs
+            .keys()
+            .copied()
+            .filter(|start| watermark_ms.map_or(true, |wm| start + self.window_ms <= wm))
+            .collect();
+        let samples: Vec<RecordBatch> = closed
+            .into_iter()
+            .filter_map(|start| self.reservoirs.remove(&start)?.sample)
+            .collect();
+        let output = concat_batches(&self.schema, &samples)?;
+        if output.num_rows() == 0 {
+            return Ok(output);
+        }
+        // The sampled rows still carry the barriers of the batches they came from, which
+        // were passed on already
+        restamp(&output, None, self.watermark)
+    }
+
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            if let Some(batch) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(batch)));
+            }
+            match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(StreamMessage::Data(batch))) => self.process_batch(&batch)?,
+                Some(Ok(StreamMessage::Watermark(watermark))) => {
+                    self.watermark = self.watermark.max(Some(watermark));
+                    let watermark_ms = watermark.duration_since(UNIX_EPOCH).unwrap().as_millis();
+                    let output = self.emit(Some(watermark_ms as i64))?;
+                    if output.num_rows() > 0 {
+                        self.pending.push_back(output);
+                    }
+                    self.pending
+                        .push_back(control_batch(&self.schema, None, Some(watermark)));
+                }
+                // Reservoirs aren't checkpointed, the barrier is passed on right away
+                Some(Ok(StreamMessage::Barrier(epoch))) => {
+                    return Poll::Ready(Some(Ok(control_batch(&self.schema, Some(epoch), None))))
+                }
+                Some(Ok(StreamMessage::EndOfPartition)) | None => {
+                    let output = self.emit(None)?;
+                    if output.num_rows() == 0 {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(Ok(output)));
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+impl Stream for ReservoirStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+}
+
+impl RecordBatchStream for ReservoirStream {
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{ArrayRef, Int64Array, TimestampMillisecondArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    use crate::physical_plan::utils::metadata::{
+        stream_metadata_array_with_barrier, stream_metadata_field, BARRIER_FIELD,
+    };
+    use crate::physical_plan::utils::stream_message::{barrier_marker, NO_BARRIER};
+
+    fn sample(method: SampleMethod, batches: Vec<(Vec<i64>, &str)>) -> Result<SampleExec> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let batches = batches
+            .into_iter()
+            .map(|(timestamps, barrier)| {
+                let ids = Int64Array::from_iter_values(0..timestamps.len() as i64);
+                let metadata = stream_metadata_array_with_barrier(
+                    TimestampMillisecondArray::from(timestamps),
+                    barrier,
+                );
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(ids) as ArrayRef, Arc::new(metadata)],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let input = MemoryExec::try_new(&[batches], schema, None)?;
+        Ok(SampleExec::new(Arc::new(input), method))
+    }
+
+    fn barriers(batch: &RecordBatch) -> Vec<String> {
+        batch
+            .column_by_name(STREAMING_METADATA_COLUMN)
+            .unwrap()
+            .as_struct()
+            .column_by_name(BARRIER_FIELD)
+            .unwrap()
+            .as_string::<i32>()
+            .iter()
+            .map(|marker| marker.unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn control_passes_without_unsampled_rows() -> Result<()> {
+        let exec = sample(
+            SampleMethod::Bernoulli(0.0),
+            vec![(vec![3000, 1000, 2000], barrier_marker(5).as_str())],
+        )?;
+        let output = collect(exec.execute(0, Arc::new(TaskContext::default()))?).await?;
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].num_rows(), 0);
+
+        let messages = StreamMessage::from_batch(output[0].clone())?;
+        assert!(matches!(
+            messages[0],
+            StreamMessage::Watermark(watermark)
+                if watermark == UNIX_EPOCH + Duration::from_millis(1000)
+        ));
+        assert!(matches!(messages[1], StreamMessage::Barrier(5)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reservoirs_pass_barriers_on() -> Result<()> {
+        let method = SampleMethod::Reservoir {
+            size: 1,
+            window: Duration::from_secs(1),
+        };
+        let exec = sample(
+            method,
+            vec![
+                (vec![1000, 1100], barrier_marker(3).as_str()),
+                (vec![2500], NO_BARRIER),
+            ],
+        )?;
+        let output = collect(exec.execute(0, Arc::new(TaskContext::default()))?).await?;
+
+        // The barrier right away, the first window with the second batch and the second one
+        // at the end
+        assert_eq!(output.len(), 4);
+        assert!(matches!(
+            StreamMessage::from_batch(output[0].clone())?[..],
+            [StreamMessage::Barrier(3)]
+        ));
+        assert_eq!(output[1].num_rows(), 1);
+        assert_eq!(barriers(&output[1]), vec![NO_BARRIER.to_string()]);
+        assert_eq!(output[2].num_rows(), 0);
+        assert_eq!(output[3].num_rows(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn rate_limiter_refills() {
+        let mut limiter = RateLimiter::new(100.0);
+        assert_eq!(limiter.take(60), 60);
+        assert_eq!(limiter.take(60), 40);
+        assert_eq!(limiter.take(10), 0);
+
+        limiter.refilled -= Duration::from_millis(250);
+        assert_eq!(limiter.take(60), 25);
+
+        limiter.refilled -= Duration::from_secs(10);
+        assert_eq!(limiter.take(500), 100);
+    }
+}
@@ -0,0 +1,482 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use futures::{stream, Stream, StreamExt};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use datafusion::common::{internal_err, DataFusionError, Result};
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext};
+use datafusion::physical_expr::{EquivalenceProperties, Partitioning};
+use datafusion::physical_plan::metrics::{
+    BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::repartition::BatchPartitioner;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionPlan, ExecutionPlanProperties, PlanProperties,
+};
+
+use crate::physical_plan::utils::stream_message::{
+    control_batch, is_control_batch, restamp, MessageStream, StreamMessage,
+};
+
+/// Batches buffered between an input and an output partition
+const CHANNEL_CAPACITY: usize = 8;
+
+type Tasks = Arc<Mutex<Vec<SpawnedTask<()>>>>;
+
+/// Repartitions a stream like DataFusion's `RepartitionExec`, without losing its control
+/// messages on the way.
+///
+/// Rows are distributed by `partitioning`, while watermarks and barriers are sent to every
+/// output partition. Each output merges the inputs the way [`TwoInputStream`] merges two:
+/// its watermark is the minimum over the inputs, and a barrier is passed on once every input
+/// delivered it, holding back inputs that already did. Barriers carried by the rows are
+/// cleared, the aligned ones follow in control batches.
+///
+/// [`TwoInputStream`]: super::two_input::TwoInputStream
+pub struct StreamingRepartitionExec {
+    input: Arc<dyn ExecutionPlan>,
+    partitioning: Partitioning,
+    /// Receivers of every output partition, by input partition, created on first execution
+    receivers: Mutex<Option<Vec<Option<Vec<Receiver<Result<RecordBatch>>>>>>>,
+    /// Tasks distributing the input partitions, shared by the output streams
+    tasks: Mutex<Option<Tasks>>,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl fmt::Debug for StreamingRepartitionExec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamingRepartitionExec")
+            .field("input", &self.input)
+            .field("partitioning", &self.partitioning)
+            .finish()
+    }
+}
+
+impl StreamingRepartitionExec {
+    pub fn try_new(input: Arc<dyn ExecutionPlan>, partitioning: Partitioning) -> Result<Self> {
+        if matches!(partitioning, Partitioning::UnknownPartitioning(_)) {
+            return internal_err!("Streams can't be repartitioned into an unknown partitioning");
+        }
+        let cache = PlanProperties::new(
+            EquivalenceProperties::new(input.schema()),
+            partitioning.clone(),
+            input.execution_mode(),
+        );
+        Ok(Self {
+            input,
+            partitioning,
+            receivers: Mutex::new(None),
+            tasks: Mutex::new(None),
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        })
+    }
+
+    pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.input
+    }
+
+    pub fn partitioning(&self) -> &Partitioning {
+        &self.partitioning
+    }
+
+    /// Start distributing the input partitions, once for all output partitions
+    fn start(&self, context: &Arc<TaskContext>) -> Result<()> {
+        let mut receivers = self.receivers.lock().unwrap();
+        if receivers.is_some() {
+            return Ok(());
+        }
+        let outputs = self.partitioning.partition_count();
+        let inputs = self.input.output_partitioning().partition_count();
+
+        let mut by_output: Vec<Vec<Receiver<Result<RecordBatch>>>> =
+            (0..outputs).map(|_| Vec::with_capacity(inputs)).collect();
+        let mut tasks = Vec::with_capacity(inputs);
+        for partition in 0..inputs {
+            let mut senders = Vec::with_capacity(outputs);
+            for output in by_output.iter_mut() {
+                let (sender, receiver) = channel(CHANNEL_CAPACITY);
+                senders.push(Some(sender));
+                output.push(receiver);
+            }
+            let input = self.input.execute(partition, Arc::clone(context))?;
+            let partitioner = BatchPartitioner::try_new(
+                self.partitioning.clone(),
+                MetricBuilder::new(&self.metrics).subset_time("repartition_time", partition),
+            )?;
+            tasks.push(SpawnedTask::spawn(distribute(input, partitioner, senders)));
+        }
+
+        *receivers = Some(by_output.into_iter().map(Some).collect());
+        *self.tasks.lock().unwrap() = Some(Arc::new(Mutex::new(tasks)));
+        Ok(())
+    }
+}
+
+/// Send the rows of an input partition to the output partitions `partitioner` picks, and its
+/// control batches to all of them
+async fn distribute(
+    mut input: SendableRecordBatchStream,
+    mut partitioner: BatchPartitioner,
+    mut senders: Vec<Option<Sender<Result<RecordBatch>>>>,
+) {
+    while let Some(batch) = input.next().await {
+        let parts = match batch {
+            Ok(batch) if is_control_batch(&batch) => (0..senders.len())
+                .map(|output| (output, Ok(batch.clone())))
+                .collect(),
+            Ok(batch) => {
+                let mut parts = vec![];
+                let partitioned = partitioner.partition(batch, |output, part| {
+                    parts.push((output, Ok(part)));
+                    Ok(())
+                });
+                match partitioned {
+                    Ok(()) => parts,
+                    Err(e) => broadcast_error(e, senders.len()),
+                }
+            }
+            Err(e) => broadcast_error(e, senders.len()),
+        };
+        let failed = parts.iter().any(|(_, part)| part.is_err());
+        for (output, part) in parts {
+            let Some(sender) = &senders[output] else {
+                continue;
+            };
+            // The output partition is gone, its consumer stopped reading
+            if sender.send(part).await.is_err() {
+                senders[output] = None;
+            }
+        }
+        if failed || senders.iter().all(Option::is_none) {
+            return;
+        }
+    }
+}
+
+fn broadcast_error(e: DataFusionError, outputs: usize) -> Vec<(usize, Result<RecordBatch>)> {
+    let e = Arc::new(e);
+    (0..outputs)
+        .map(|output| {
+            let e = DataFusionError::External(Box::new(Arc::clone(&e)));
+            (output, Err(e))
+        })
+        .collect()
+}
+
+impl DisplayAs for StreamingRepartitionExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => write!(
+                f,
+                "StreamingRepartitionExec: partitioning={}, input_partitions={}",
+                self.partitioning,
+                self.input.output_partitioning().partition_count()
+            ),
+        }
+    }
+}
+
+impl ExecutionPlan for StreamingRepartitionExec {
+    fn name(&self) -> &'static str {
+        "StreamingRepartitionExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![false]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(StreamingRepartitionExec::try_new(
+            children[0].clone(),
+            self.partitioning.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.start(&context)?;
+        let receivers = self
+            .receivers
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|receivers| receivers.get_mut(partition)?.take());
+        let Some(receivers) = receivers else {
+            return internal_err!("Partition {partition} of {} executed twice", self.name());
+        };
+        let tasks = self.tasks.lock().unwrap().clone().unwrap_or_default();
+
+        let schema = self.schema();
+        let inputs = receivers
+            .into_iter()
+            .map(|receiver| {
+                let batches = stream::unfold(receiver, |mut receiver| async move {
+                    receiver.recv().await.map(|batch| (batch, receiver))
+                });
+                AlignedInput::new(Box::pin(RecordBatchStreamAdapter::new(
+                    Arc::clone(&schema),
+                    batches,
+                )))
+            })
+            .collect();
+        Ok(Box::pin(AlignedMergeStream {
+            schema,
+            inputs,
+            next_input: 0,
+            watermark: None,
+            output: VecDeque::new(),
+            _tasks: tasks,
+            baseline_metrics: BaselineMetrics::new(&self.metrics, partition),
+        }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+struct AlignedInput {
+    stream: MessageStream,
+    watermark: Option<SystemTime>,
+    /// Set while the input waits for the others to reach the same barrier
+    pending_barrier: Option<u64>,
+    finished: bool,
+}
+
+impl AlignedInput {
+    fn new(stream: SendableRecordBatchStream) -> Self {
+        Self {
+            stream: MessageStream::new(stream),
+            watermark: None,
+            pending_barrier: None,
+            finished: false,
+        }
+    }
+
+    fn can_poll(&self) -> bool {
+        !self.finished && self.pending_barrier.is_none()
+    }
+}
+
+/// Merges the streams an output partition receives from every input partition
+struct AlignedMergeStream {
+    schema: SchemaRef,
+    inputs: Vec<AlignedInput>,
+    next_input: usize,
+    watermark: Option<SystemTime>,
+    output: VecDeque<RecordBatch>,
+    /// Dropping the last output stream stops the distribution
+    _tasks: Tasks,
+    baseline_metrics: BaselineMetrics,
+}
+
+impl AlignedMergeStream {
+    fn handle(&mut self, index: usize, message: StreamMessage) -> Result<()> {
+        match message {
+            StreamMessage::Data(batch) => self.output.push_back(restamp(&batch, None, None)?),
+            StreamMessage::Watermark(watermark) => {
+                let input = &mut self.inputs[index];
+                input.watermark = input.watermark.max(Some(watermark));
+                self.advance_watermark();
+            }
+            StreamMessage::Barrier(epoch) => {
+                self.inputs[index].pending_barrier = Some(epoch);
+                self.align_barriers();
+            }
+            StreamMessage::EndOfPartition => {
+                self.inputs[index].finished = true;
+                self.advance_watermark();
+                self.align_barriers();
+            }
+        }
+        Ok(())
+    }
+
+    /// The minimum over the inputs that are still running, once each of them reported one
+    fn advance_watermark(&mut self) {
+        let mut running = self.inputs.iter().filter(|input| !input.finished);
+        let Some(first) = running.next() else {
+            return;
+        };
+        let combined = running.fold(first.watermark, |combined, input| {
+            combined.zip(input.watermark).map(|(a, b)| a.min(b))
+        });
+        if combined > self.watermark {
+            self.watermark = combined;
+            self.output
+                .push_back(control_batch(&self.schema, None, combined));
+        }
+    }
+
+    fn align_barriers(&mut self) {
+        let aligned = self
+            .inputs
+            .iter()
+            .all(|input| input.finished || input.pending_barrier.is_some());
+        if !aligned {
+            return;
+        }
+        // Sources number their epochs independently, the latest one covers them all
+        let epoch = self
+            .inputs
+            .iter_mut()
+            .filter_map(|input| input.pending_barrier.take())
+            .max();
+        if let Some(epoch) = epoch {
+            self.output
+                .push_back(control_batch(&self.schema, Some(epoch), None));
+        }
+    }
+
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            if let Some(batch) = self.output.pop_front() {
+                return Poll::Ready(Some(Ok(batch)));
+            }
+            if self.inputs.iter().all(|input| input.finished) {
+                return Poll::Ready(None);
+            }
+
+            let mut progressed = false;
+            let count = self.inputs.len();
+            for offset in 0..count {
+                let index = (self.next_input + offset) % count;
+                if !self.inputs[index].can_poll() {
+                    continue;
+                }
+                match self.inputs[index].stream.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(message))) => {
+                        self.next_input = (index + 1) % count;
+                        self.handle(index, message)?;
+                        progressed = true;
+                        break;
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                    Poll::Ready(None) => {
+                        self.handle(index, StreamMessage::EndOfPartition)?;
+                        progressed = true;
+                        break;
+                    }
+                    Poll::Pending => {}
+                }
+            }
+            if !progressed {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl Stream for AlignedMergeStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+}
+
+impl RecordBatchStream for AlignedMergeStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{ArrayRef, Int64Array, TimestampMillisecondArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    use crate::physical_plan::utils::metadata::{
+        stream_metadata_array_with_barrier, stream_metadata_field,
+    };
+    use crate::physical_plan::utils::stream_message::{barrier_marker, NO_BARRIER};
+    use crate::physical_plan::utils::time::system_time_from_epoch;
+
+    fn batch(schema: &SchemaRef, id: i64, barrier: &str) -> RecordBatch {
+        let metadata = stream_metadata_array_with_barrier(
+            TimestampMillisecondArray::from(vec![id * 1000]),
+            barrier,
+        );
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![id])) as ArrayRef,
+                Arc::new(metadata),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn barriers_follow_the_rows_of_every_input() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let at = |ms| Some(system_time_from_epoch(ms));
+        // The barrier rows of the first partition are filtered away, its control batch isn't
+        let partitions = vec![
+            vec![
+                batch(&schema, 1, NO_BARRIER),
+                control_batch(&schema, Some(3), at(1000)),
+            ],
+            vec![
+                batch(&schema, 2, NO_BARRIER),
+                batch(&schema, 4, &barrier_marker(3)),
+                control_batch(&schema, Some(3), at(2000)),
+            ],
+        ];
+        let input = Arc::new(MemoryExec::try_new(&partitions, schema.clone(), None)?);
+        let repartition =
+            StreamingRepartitionExec::try_new(input, Partitioning::RoundRobinBatch(1))?;
+        let mut output = repartition.execute(0, Arc::new(TaskContext::default()))?;
+
+        let mut messages = vec![];
+        while let Some(batch) = output.next().await {
+            for message in StreamMessage::from_batch(batch?)? {
+                messages.push(match message {
+                    StreamMessage::Data(batch) => format!("data({})", batch.num_rows()),
+                    StreamMessage::Barrier(epoch) => format!("barrier({epoch})"),
+                    StreamMessage::Watermark(_) | StreamMessage::EndOfPartition => continue,
+                });
+            }
+        }
+        // Every row of both partitions comes before the barrier, which is passed on once
+        assert_eq!(messages, ["data(1)", "data(1)", "data(1)", "barrier(3)"]);
+        Ok(())
+    }
+}
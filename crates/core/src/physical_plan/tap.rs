@@ -0,0 +1,278 @@
+use std::any::Any;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use arrow::compute::filter_record_batch;
+use arrow::util::pretty::pretty_format_batches;
+use arrow_array::{BooleanArray, RecordBatch};
+use futures::StreamExt;
+use rand::Rng;
+use tracing::info;
+
+use datafusion::common::Result;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricBuilder, MetricsSet};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+
+use crate::datasource::epoch::EpochTracker;
+use crate::physical_plan::utils::metadata::has_stream_metadata;
+use crate::physical_plan::utils::stream_message::barrier_epoch;
+use crate::utils::determinism::operator_rng;
+
+/// A row sample captured by a tap
+#[derive(Debug, Clone)]
+pub struct TapRecord {
+    pub name: String,
+    /// The operator whose output was sampled
+    pub operator: String,
+    pub partition: usize,
+    /// Checkpoint epoch of the sampled rows: the one their batch closes, or else the one the
+    /// sources are in. None for pipelines that don't track epochs.
+    pub epoch: Option<u64>,
+    pub batch: RecordBatch,
+}
+
+/// In-memory buffer that collects [`TapRecord`]s, mostly useful in tests
+#[derive(Debug, Clone, Default)]
+pub struct TapBuffer(Arc<Mutex<Vec<TapRecord>>>);
+
+impl TapBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take all the records captured so far, leaving the buffer empty
+    pub fn drain(&self) -> Vec<TapRecord> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl PartialEq for TapBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for TapBuffer {}
+
+impl Hash for TapBuffer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state)
+    }
+}
+
+/// Where sampled rows from a tap are sent
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TapSink {
+    /// Logged at info level, so the samples go wherever the pipeline's logs go
+    Console,
+    Memory(TapBuffer),
+}
+
+impl TapSink {
+    fn record(&self, record: TapRecord) -> Result<()> {
+        match self {
+            TapSink::Console => {
+                info!(
+                    tap = %record.name,
+                    operator = %record.operator,
+                    partition = record.partition,
+                    epoch = ?record.epoch,
+                    "Sampled rows\n{}",
+                    pretty_format_batches(&[record.batch])?
+                );
+            }
+            TapSink::Memory(buffer) => buffer.0.lock().unwrap().push(record),
+        }
+        Ok(())
+    }
+}
+
+/// Passes its input through unchanged while sending a random sample of the rows to a [`TapSink`]
+#[derive(Debug)]
+pub struct TapExec {
+    input: Arc<dyn ExecutionPlan>,
+    name: String,
+    sample_rate: f64,
+    sink: TapSink,
+    metrics: ExecutionPlanMetricsSet,
+    cache: PlanProperties,
+}
+
+impl TapExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        name: String,
+        sample_rate: f64,
+        sink: TapSink,
+    ) -> Self {
+        let cache = input.properties().clone();
+        Self {
+            input,
+            name,
+            sample_rate,
+            sink,
+            metrics: ExecutionPlanMetricsSet::new(),
+            cache,
+        }
+    }
+}
+
+impl DisplayAs for TapExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "TapExec: name={}, sample_rate={}",
+                    self.name, self.sample_rate
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for TapExec {
+    fn name(&self) -> &'static str {
+        "TapExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(TapExec::new(
+            children[0].clone(),
+            self.name.clone(),
+            self.sample_rate,
+            self.sink.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context.clone())?;
+        let sampled_rows = MetricBuilder::new(&self.metrics).counter("sampled_rows", partition);
+
+        let name = self.name.clone();
+        let operator = self.input.name().to_string();
+        let sample_rate = self.sample_rate;
+        let sink = self.sink.clone();
+        let mut rng = operator_rng(&context, &format!("tap[{name}]"), partition);
+        let epoch_tracker = EpochTracker::from_task_context(&context);
+
+        let stream = input.map(move |batch| {
+            let batch = batch?;
+            let sample = sample_batch(&batch, sample_rate, &mut rng)?;
+            if sample.num_rows() > 0 {
+                let closed = match has_stream_metadata(&batch.schema()) {
+                    true => barrier_epoch(&batch)?,
+                    false => None,
+                };
+                sampled_rows.add(sample.num_rows());
+                sink.record(TapRecord {
+                    name: name.clone(),
+                    operator: operator.clone(),
+                    partition,
+                    epoch: closed.or(epoch_tracker.as_ref().map(|tracker| tracker.current())),
+                    batch: sample,
+                })?;
+            }
+            Ok(batch)
+        });
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.input.schema(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+fn sample_batch(batch: &RecordBatch, sample_rate: f64, rng: &mut impl Rng) -> Result<RecordBatch> {
+    if sample_rate >= 1.0 {
+        return Ok(batch.clone());
+    }
+    let mask: BooleanArray = (0..batch.num_rows())
+        .map(|_| Some(rng.gen_bool(sample_rate)))
+        .collect();
+    Ok(filter_record_batch(batch, &mask)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    #[tokio::test]
+    async fn tap_passes_rows_through() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = |ids: Vec<i64>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(ids))])
+        };
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![batch(vec![1, 2])?, batch(vec![3])?]],
+            schema.clone(),
+            None,
+        )?);
+
+        let buffer = TapBuffer::new();
+        let tap = TapExec::new(
+            input,
+            "ids".to_string(),
+            1.0,
+            TapSink::Memory(buffer.clone()),
+        );
+        let output = collect(tap.execute(0, Arc::new(TaskContext::default()))?).await?;
+        assert_eq!(output, vec![batch(vec![1, 2])?, batch(vec![3])?]);
+
+        let records = buffer.drain();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (
+                    record.operator.as_str(),
+                    record.epoch,
+                    record.batch.num_rows()
+                ))
+                .collect::<Vec<_>>(),
+            vec![("MemoryExec", None, 2), ("MemoryExec", None, 1)]
+        );
+        assert!(buffer.drain().is_empty());
+
+        let none = TapBuffer::new();
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch(vec![1])?]], schema, None)?);
+        let tap = TapExec::new(input, "ids".to_string(), 0.0, TapSink::Memory(none.clone()));
+        collect(tap.execute(0, Arc::new(TaskContext::default()))?).await?;
+        assert!(none.drain().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::common::Result;
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream};
+use datafusion::physical_plan::metrics::BaselineMetrics;
+use futures::{Stream, StreamExt};
+
+use super::utils::stream_message::{MessageStream, StreamMessage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSide {
+    Left,
+    Right,
+}
+
+impl InputSide {
+    fn index(self) -> usize {
+        match self {
+            InputSide::Left => 0,
+            InputSide::Right => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        if index == 0 {
+            InputSide::Left
+        } else {
+            InputSide::Right
+        }
+    }
+}
+
+/// Logic of an operator that consumes two streams, e.g. a join or a connect-style transform.
+///
+/// Implementations only deal with their own state. Coordinating the inputs is left to
+/// [`TwoInputStream`], which calls [`Self::on_watermark`] with the minimum of the input
+/// watermarks and [`Self::on_barrier`] once both inputs have delivered the barrier.
+pub trait TwoInputStreamOperator: Send {
+    fn schema(&self) -> SchemaRef;
+
+    fn process_batch(&mut self, side: InputSide, batch: RecordBatch) -> Result<Vec<RecordBatch>>;
+
+    fn on_watermark(&mut self, _watermark: SystemTime) -> Result<Vec<RecordBatch>> {
+        Ok(vec![])
+    }
+
+    /// Called with every row before the barrier processed on both inputs, the point at which
+    /// the operator's state is consistent for `epoch`.
+    fn on_barrier(&mut self, _epoch: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_end(&mut self) -> Result<Vec<RecordBatch>> {
+        Ok(vec![])
+    }
+}
+
+struct InputState {
+    stream: MessageStream,
+    watermark: Option<SystemTime>,
+    /// Set while the input waits for the other one to reach the same barrier
+    pending_barrier: Option<u64>,
+    finished: bool,
+}
+
+impl InputState {
+    fn new(stream: SendableRecordBatchStream) -> Self {
+        Self {
+            stream: MessageStream::new(stream),
+            watermark: None,
+            pending_barrier: None,
+            finished: false,
+        }
+    }
+
+    fn can_poll(&self) -> bool {
+        !self.finished && self.pending_barrier.is_none()
+    }
+}
+
+/// Drives a [`TwoInputStreamOperator`] from a pair of input streams.
+///
+/// Inputs are polled in turn. An input that delivers a barrier is not polled again until the
+/// other input delivers one too, so the operator never sees rows from after a checkpoint on
+/// one side before the checkpoint is complete on the other.
+pub struct TwoInputStream<O> {
+    operator: O,
+    schema: SchemaRef,
+    inputs: [InputState; 2],
+    next_input: usize,
+    watermark: Option<SystemTime>,
+    output: VecDeque<RecordBatch>,
+    done: bool,
+    baseline_metrics: BaselineMetrics,
+}
+
+impl<O: TwoInputStreamOperator> TwoInputStream<O> {
+    pub fn new(
+        operator: O,
+        left: SendableRecordBatchStream,
+        right: SendableRecordBatchStream,
+        baseline_metrics: BaselineMetrics,
+    ) -> Self {
+        Self {
+            schema: operator.schema(),
+            operator,
+            inputs: [InputState::new(left), InputState::new(right)],
+            next_input: 0,
+            watermark: None,
+            output: VecDeque::new(),
+            done: false,
+            baseline_metrics,
+        }
+    }
+
+    /// Watermark of a single input, as opposed to the combined one passed to the operator
+    pub fn input_watermark(&self, side: InputSide) -> Option<SystemTime> {
+        self.inputs[side.index()].watermark
+    }
+
+    fn handle(&mut self, index: usize, message: StreamMessage) -> Result<()> {
+        match message {
+            StreamMessage::Data(batch) => {
+                let output = self
+                    .operator
+                    .process_batch(InputSide::from_index(index), batch)?;
+                self.output.extend(output);
+            }
+            StreamMessage::Watermark(watermark) => {
+                let input = &mut self.inputs[index];
+                input.watermark = input.watermark.max(Some(watermark));
+                self.advance_watermark()?;
+            }
+            StreamMessage::Barrier(epoch) => {
+                self.inputs[index].pending_barrier = Some(epoch);
+                self.align_barriers()?;
+            }
+            StreamMessage::EndOfPartition => {
+                self.inputs[index].finished = true;
+                self.advance_watermark()?;
+                self.align_barriers()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The combined watermark is the minimum over the inputs that are still running, and only
+    /// exists once each of them has reported one.
+    fn advance_watermark(&mut self) -> Result<()> {
+        let mut running = self.inputs.iter().filter(|input| !input.finished);
+        let Some(first) = running.next() else {
+            return Ok(());
+        };
+        let combined = running.fold(first.watermark, |combined, input| {
+            combined.zip(input.watermark).map(|(a, b)| a.min(b))
+        });
+
+        if let Some(watermark) = combined {
+            if self.watermark.map_or(true, |current| watermark > current) {
+                self.watermark = Some(watermark);
+                let output = self.operator.on_watermark(watermark)?;
+                self.output.extend(output);
+            }
+        }
+        Ok(())
+    }
+
+    fn align_barriers(&mut self) -> Result<()> {
+        let aligned = self
+            .inputs
+            .iter()
+            .all(|input| input.finished || input.pending_barrier.is_some());
+        if !aligned {
+            return Ok(());
+        }
+
+        // Sources number their epochs independently, the later of the two covers both
+        let epoch = self
+            .inputs
+            .iter_mut()
+            .filter_map(|input| input.pending_barrier.take())
+            .max();
+        if let Some(epoch) = epoch {
+            self.operator.on_barrier(epoch)?;
+        }
+        Ok(())
+    }
+
+    fn poll_next_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<RecordBatch>>> {
+        loop {
+            if let Some(batch) = self.output.pop_front() {
+                return Poll::Ready(Some(Ok(batch)));
+            }
+            if self.done {
+                return Poll::Ready(None);
+            }
+            if self.inputs.iter().all(|input| input.finished) {
+                let output = self.operator.on_end()?;
+                self.output.extend(output);
+                self.done = true;
+                continue;
+            }
+
+            let mut progressed = false;
+            for index in [self.next_input, 1 - self.next_input] {
+                if !self.inputs[index].can_poll() {
+                    continue;
+                }
+                match self.inputs[index].stream.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(message))) => {
+                        self.next_input = 1 - index;
+                        self.handle(index, message)?;
+                        progressed = true;
+                        break;
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                    Poll::Ready(None) => {
+                        self.handle(index, StreamMessage::EndOfPartition)?;
+                        progressed = true;
+                        break;
+                    }
+                    Poll::Pending => {}
+                }
+            }
+            if !progressed {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl<O: TwoInputStreamOperator + Unpin> Stream for TwoInputStream<O> {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.poll_next_inner(cx);
+        self.baseline_metrics.record_poll(poll)
+    }
+}
+
+impl<O: TwoInputStreamOperator + Unpin> RecordBatchStream for TwoInputStream<O> {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+    use std::time::UNIX_EPOCH;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+    use arrow_array::{ArrayRef, Int64Array, TimestampMillisecondArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::stream;
+
+    use crate::physical_plan::utils::metadata::{
+        stream_metadata_array_with_barrier, stream_metadata_field,
+    };
+    use crate::physical_plan::utils::stream_message::NO_BARRIER;
+
+    /// Writes down everything the driver hands to it
+    struct Recorder {
+        schema: SchemaRef,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Recorder {
+        fn record(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    impl TwoInputStreamOperator for Recorder {
+        fn schema(&self) -> SchemaRef {
+            self.schema.clone()
+        }
+
+        fn process_batch(
+            &mut self,
+            side: InputSide,
+            batch: RecordBatch,
+        ) -> Result<Vec<RecordBatch>> {
+            for id in batch.column(0).as_primitive::<Int64Type>().values() {
+                self.record(format!("{side:?} {id}"));
+            }
+            Ok(vec![])
+        }
+
+        fn on_watermark(&mut self, watermark: SystemTime) -> Result<Vec<RecordBatch>> {
+            let millis = watermark.duration_since(UNIX_EPOCH).unwrap().as_millis();
+            self.record(format!("watermark {millis}"));
+            Ok(vec![])
+        }
+
+        fn on_barrier(&mut self, epoch: u64) -> Result<()> {
+            self.record(format!("barrier {epoch}"));
+            Ok(())
+        }
+
+        fn on_end(&mut self) -> Result<Vec<RecordBatch>> {
+            self.record("end".to_string());
+            Ok(vec![])
+        }
+    }
+
+    fn input(schema: &SchemaRef, rows: &[(i64, i64, &str)]) -> SendableRecordBatchStream {
+        let batches = rows
+            .iter()
+            .map(|&(id, timestamp, barrier)| {
+                let metadata = stream_metadata_array_with_barrier(
+                    TimestampMillisecondArray::from(vec![timestamp]),
+                    barrier,
+                );
+                Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int64Array::from(vec![id])) as ArrayRef,
+                        Arc::new(metadata),
+                    ],
+                )?)
+            })
+            .collect::<Vec<Result<RecordBatch>>>();
+        Box::pin(RecordBatchStreamAdapter::new(
+            schema.clone(),
+            stream::iter(batches),
+        ))
+    }
+
+    #[tokio::test]
+    async fn align_barriers_and_combine_watermarks() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let left = input(
+            &schema,
+            &[(1, 1000, NO_BARRIER), (2, 2000, "1"), (3, 5000, NO_BARRIER)],
+        );
+        let right = input(
+            &schema,
+            &[
+                (10, 1500, NO_BARRIER),
+                (11, 2500, NO_BARRIER),
+                (12, 3000, "2"),
+            ],
+        );
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorder = Recorder {
+            schema: schema.clone(),
+            events: events.clone(),
+        };
+        let metrics = BaselineMetrics::new(&ExecutionPlanMetricsSet::new(), 0);
+        TwoInputStream::new(recorder, left, right, metrics)
+            .collect::<Vec<_>>()
+            .await;
+
+        // The left input isn't polled between its barrier and the right one's, the later
+        // epoch of the two closes the checkpoint
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "Left 1",
+                "Right 10",
+                "watermark 1000",
+                "Left 2",
+                "Right 11",
+                "watermark 1500",
+                "watermark 2000",
+                "Right 12",
+                "barrier 2",
+                "Left 3",
+                "watermark 5000",
+                "end",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -14,3 +14,19 @@ pub(crate) fn create_accumulators(
         .map(|expr| expr.create_accumulator())
         .collect()
 }
+
+/// Fold the state of every accumulator in `source` into its counterpart in `target`
+pub(crate) fn merge_accumulators(
+    target: &mut [AccumulatorItem],
+    source: &mut [AccumulatorItem],
+) -> Result<()> {
+    for (acc, other) in target.iter_mut().zip(source.iter_mut()) {
+        let state = other
+            .state()?
+            .iter()
+            .map(|value| value.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        acc.merge_batch(&state)?;
+    }
+    Ok(())
+}
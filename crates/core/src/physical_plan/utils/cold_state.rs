@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use datafusion::common::instant::Instant;
+use datafusion::common::{internal_err, Result};
+use datafusion::execution::TaskContext;
+use datafusion::physical_expr::AggregateExpr;
+
+use crate::accumulators::state_serde::{decode_state, encode_state, IpcStateSerde};
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+
+use super::accumulators::{create_accumulators, AccumulatorItem};
+
+/// zstd level of compressed state, cold keys are compressed once so the level favours size
+const COLD_STATE_LEVEL: i32 = 3;
+
+/// Compresses the accumulators of keys that received no rows for `cold_state_after_secs`,
+/// which are decompressed again when the key sees rows or its window fires
+#[derive(Debug, Clone, Copy)]
+pub struct ColdState {
+    idle: Duration,
+}
+
+impl ColdState {
+    /// `None` unless `cold_state_after_secs` is set
+    pub fn from_task_context(context: &TaskContext) -> Option<Self> {
+        let idle_secs = context
+            .session_config()
+            .options()
+            .extensions
+            .get::<DenormalizedConfig>()
+            .map_or(0, |config| config.cold_state_after_secs);
+        (idle_secs > 0).then(|| Self::new(Duration::from_secs(idle_secs)))
+    }
+
+    pub fn new(idle: Duration) -> Self {
+        Self { idle }
+    }
+
+    /// Whether state last touched at `touched` has gone cold by `now`
+    pub fn is_cold(&self, touched: Instant, now: Instant) -> bool {
+        now.duration_since(touched) >= self.idle
+    }
+
+    /// The states of `accumulators` as one zstd frame, each behind its length
+    pub(crate) fn compress(&self, accumulators: &mut [AccumulatorItem]) -> Result<Vec<u8>> {
+        let mut encoded = vec![];
+        for acc in accumulators {
+            let state = encode_state(&IpcStateSerde, &acc.state()?)?;
+            encoded.extend((state.len() as u32).to_le_bytes());
+            encoded.extend(state);
+        }
+        Ok(zstd::encode_all(encoded.as_slice(), COLD_STATE_LEVEL)?)
+    }
+}
+
+/// Fresh accumulators for `aggregate_expressions` holding the states compressed by
+/// [`ColdState::compress`]
+pub(crate) fn decompress(
+    bytes: &[u8],
+    aggregate_expressions: &[Arc<dyn AggregateExpr>],
+) -> Result<Vec<AccumulatorItem>> {
+    let decoded = zstd::decode_all(bytes)?;
+    let mut accumulators = create_accumulators(aggregate_expressions)?;
+    let mut rest = decoded.as_slice();
+    for acc in accumulators.iter_mut() {
+        let Some((len, tail)) = rest.split_first_chunk::<4>() else {
+            return internal_err!("Compressed state holds fewer accumulators than expected");
+        };
+        let len = u32::from_le_bytes(*len) as usize;
+        if tail.len() < len {
+            return internal_err!("Truncated compressed state");
+        }
+        let (state, tail) = tail.split_at(len);
+        let state = decode_state(state)?
+            .into_iter()
+            .map(|value| value.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        acc.merge_batch(&state)?;
+        rest = tail;
+    }
+    Ok(accumulators)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Array, Int64Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::common::ScalarValue;
+    use datafusion::functions_aggregate::sum::sum_udaf;
+    use datafusion::physical_expr::expressions::col;
+    use datafusion::physical_expr_common::aggregate::AggregateExprBuilder;
+
+    #[test]
+    fn decompress_compressed_accumulators() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, true)]));
+        let sum = AggregateExprBuilder::new(sum_udaf(), vec![col("v", &schema)?])
+            .schema(schema)
+            .alias("sum")
+            .build()?;
+        let expressions: Vec<Arc<dyn AggregateExpr>> = vec![sum.clone(), sum];
+        let mut accumulators = create_accumulators(&expressions)?;
+        let values: Arc<dyn Array> = Arc::new(Int64Array::from(vec![1, 2, 3]));
+        accumulators[0].update_batch(&[values.clone()])?;
+        accumulators[1].update_batch(&[values.slice(0, 1)])?;
+
+        let compressed = ColdState::new(Duration::from_secs(60)).compress(&mut accumulators)?;
+        let mut restored = decompress(&compressed, &expressions)?;
+        assert_eq!(restored[0].evaluate()?, ScalarValue::Int64(Some(6)));
+        assert_eq!(restored[1].evaluate()?, ScalarValue::Int64(Some(1)));
+        assert!(decompress(&compressed[..compressed.len() / 2], &expressions).is_err());
+        Ok(())
+    }
+}
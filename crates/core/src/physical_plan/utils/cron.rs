@@ -0,0 +1,252 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Timelike};
+use datafusion::common::{DataFusionError, Result};
+use futures::ready;
+use tokio::time::Sleep;
+
+use super::time::WindowTimezone;
+
+const MINUTE_MS: i64 = 60 * 1000;
+
+/// A five field cron expression: minute, hour, day of month, month and day of week.
+///
+/// Fields take `*`, single values, ranges `a-b`, steps `*/n` or `a-b/n`, and comma separated
+/// lists of those. Days of the week run from 0 for Sunday to 6, 7 is Sunday as well. As in
+/// cron, when both day fields are restricted a day matching either of them fires. The
+/// shorthands `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are accepted too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day fields are restricted, which decides how they combine
+    restricted_days: (bool, bool),
+}
+
+impl FromStr for CronSchedule {
+    type Err = DataFusionError;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expanded => expanded,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(DataFusionError::Plan(format!(
+                "Invalid cron expression '{expression}', expected 5 fields"
+            )));
+        };
+
+        let mut days_of_week_bits = parse_field(days_of_week, 0, 7, expression)?;
+        if days_of_week_bits & (1 << 7) != 0 {
+            days_of_week_bits = (days_of_week_bits | 1) & !(1 << 7);
+        }
+        let schedule = Self {
+            expression: expression.trim().to_string(),
+            minutes: parse_field(minutes, 0, 59, expression)?,
+            hours: parse_field(hours, 0, 23, expression)?,
+            days_of_month: parse_field(days_of_month, 1, 31, expression)?,
+            months: parse_field(months, 1, 12, expression)?,
+            days_of_week: days_of_week_bits,
+            restricted_days: (
+                !days_of_month.starts_with('*'),
+                !days_of_week.starts_with('*'),
+            ),
+        };
+        if schedule.next_after(0).is_none() {
+            return Err(DataFusionError::Plan(format!(
+                "Cron expression '{expression}' never fires"
+            )));
+        }
+        Ok(schedule)
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl CronSchedule {
+    /// The first time after the wall clock time `local_ms` the schedule fires, on the same
+    /// wall clock. `None` for schedules that never fire, such as the 30th of February.
+    pub fn next_after(&self, local_ms: i64) -> Option<i64> {
+        let start = local_ms.div_euclid(MINUTE_MS) * MINUTE_MS + MINUTE_MS;
+        let mut time = DateTime::from_timestamp_millis(start)?.naive_utc();
+        // A leap day falls on every day of the week within 28 years
+        let last_year = time.year() + 28;
+        while time.year() <= last_year {
+            time = if !is_set(self.months, time.month()) {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                midnight(NaiveDate::from_ymd_opt(year, month, 1)?)?
+            } else if !self.matches_day(time.date()) {
+                midnight(time.date().succ_opt()?)?
+            } else if !is_set(self.hours, time.hour()) {
+                time.date().and_hms_opt(time.hour(), 0, 0)? + chrono::Duration::hours(1)
+            } else if !is_set(self.minutes, time.minute()) {
+                time + chrono::Duration::minutes(1)
+            } else {
+                return Some(time.and_utc().timestamp_millis());
+            };
+        }
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day_of_month = is_set(self.days_of_month, date.day());
+        let day_of_week = is_set(self.days_of_week, date.weekday().num_days_from_sunday());
+        match self.restricted_days {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32, expression: &str) -> Result<u64> {
+    let invalid = || {
+        DataFusionError::Plan(format!(
+            "Invalid cron field '{field}' in '{expression}', values range from {min} to {max}"
+        ))
+    };
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>().map_err(|_| invalid())?)),
+            None => (part, None),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (
+                first.parse().map_err(|_| invalid())?,
+                last.parse().map_err(|_| invalid())?,
+            ),
+            None => {
+                let first = range.parse().map_err(|_| invalid())?;
+                (first, if step.is_some() { max } else { first })
+            }
+        };
+        if step == Some(0) || first < min || last > max || first > last {
+            return Err(invalid());
+        }
+        for value in (first..=last).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn is_set(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn midnight(date: NaiveDate) -> Option<NaiveDateTime> {
+    date.and_hms_opt(0, 0, 0)
+}
+
+/// Wakes a window operator whenever its [`CronSchedule`] fires, on the wall clock of the
+/// session time zone
+pub(crate) struct ScheduledEmission {
+    schedule: CronSchedule,
+    timezone: WindowTimezone,
+    /// Wall clock time of the next firing
+    due: Option<i64>,
+    timer: Pin<Box<Sleep>>,
+}
+
+impl ScheduledEmission {
+    pub fn new(schedule: CronSchedule, timezone: WindowTimezone) -> Self {
+        let mut emission = Self {
+            schedule,
+            timezone,
+            due: None,
+            timer: Box::pin(tokio::time::sleep(Duration::ZERO)),
+        };
+        emission.schedule_next();
+        emission
+    }
+
+    /// Ready once for every time the schedule fires
+    pub fn poll_fire(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        ready!(self.timer.as_mut().poll(cx));
+        self.schedule_next();
+        Poll::Ready(())
+    }
+
+    fn schedule_next(&mut self) {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        // The timer may go off a little ahead of the system clock, which must not fire the
+        // same minute twice
+        let after = self
+            .timezone
+            .to_local(now_ms)
+            .max(self.due.unwrap_or(i64::MIN));
+        self.due = self.schedule.next_after(after);
+        let delay = match self.due {
+            Some(due) => Duration::from_millis((self.timezone.to_utc(due) - now_ms).max(0) as u64),
+            // Ruled out when parsing, sleep for a year rather than spinning
+            None => Duration::from_secs(365 * 24 * 60 * 60),
+        };
+        self.timer
+            .as_mut()
+            .reset(tokio::time::Instant::now() + delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_ms(time: &str) -> i64 {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_utc()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn next_firing() {
+        let hourly: CronSchedule = "@hourly".parse().unwrap();
+        assert_eq!(
+            hourly.next_after(local_ms("2024-05-01 10:00")),
+            Some(local_ms("2024-05-01 11:00"))
+        );
+
+        let weekday_mornings: CronSchedule = "30 9 * * 1-5".parse().unwrap();
+        // 2024-05-03 is a Friday
+        assert_eq!(
+            weekday_mornings.next_after(local_ms("2024-05-03 09:30")),
+            Some(local_ms("2024-05-06 09:30"))
+        );
+
+        let quarter_hours: CronSchedule = "*/15 8-9 1,15 * *".parse().unwrap();
+        assert_eq!(
+            quarter_hours.next_after(local_ms("2024-05-01 09:50")),
+            Some(local_ms("2024-05-15 08:00"))
+        );
+
+        assert!("0 0 30 2 *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("* * *".parse::<CronSchedule>().is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, StringArray, StructArray, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Fields, Schema, TimeUnit};
+
+use super::stream_message::NO_BARRIER;
+
+/// Name of the struct column every streaming source attaches to its batches. It carries the
+/// event time of each row and travels through the plan like any other column.
+pub const STREAMING_METADATA_COLUMN: &str = "_streaming_internal_metadata";
+pub const BARRIER_FIELD: &str = "barrier_batch";
+pub const CANONICAL_TIMESTAMP_FIELD: &str = "canonical_timestamp";
+/// Optional field with the watermark of the source as of each row, for sources that declare a
+/// [`WatermarkStrategy`](super::watermark::WatermarkStrategy). Without it the watermark of a
+/// batch is its oldest event time.
+pub const WATERMARK_FIELD: &str = "watermark";
+
+pub fn has_stream_metadata(schema: &Schema) -> bool {
+    schema.column_with_name(STREAMING_METADATA_COLUMN).is_some()
+}
+
+fn stream_metadata_fields(with_watermark: bool) -> Fields {
+    let mut fields = vec![
+        Field::new(BARRIER_FIELD, DataType::Utf8, false),
+        Field::new(
+            CANONICAL_TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        ),
+    ];
+    if with_watermark {
+        fields.push(Field::new(
+            WATERMARK_FIELD,
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        ));
+    }
+    Fields::from(fields)
+}
+
+/// The field of the metadata column, for sources building their schema
+pub fn stream_metadata_field() -> Field {
+    Field::new(
+        STREAMING_METADATA_COLUMN,
+        DataType::Struct(stream_metadata_fields(false)),
+        true,
+    )
+}
+
+/// The field of the metadata column of sources that write their own watermarks
+pub fn stream_metadata_field_with_watermark() -> Field {
+    Field::new(
+        STREAMING_METADATA_COLUMN,
+        DataType::Struct(stream_metadata_fields(true)),
+        true,
+    )
+}
+
+/// The metadata column for rows with the given event times, closing no checkpoint epoch
+pub fn stream_metadata_array(timestamps: TimestampMillisecondArray) -> StructArray {
+    stream_metadata_array_with_barrier(timestamps, NO_BARRIER)
+}
+
+/// The metadata column for rows with the given event times, all carrying `barrier`
+pub fn stream_metadata_array_with_barrier(
+    timestamps: TimestampMillisecondArray,
+    barrier: &str,
+) -> StructArray {
+    let barriers = StringArray::from(vec![barrier; timestamps.len()]);
+    StructArray::new(
+        stream_metadata_fields(false),
+        vec![Arc::new(barriers) as ArrayRef, Arc::new(timestamps)],
+        None,
+    )
+}
+
+/// The metadata column for rows with the given event times, all carrying `barrier` and the
+/// source's `watermark`
+pub fn stream_metadata_array_with_watermark(
+    timestamps: TimestampMillisecondArray,
+    barrier: &str,
+    watermark: Option<i64>,
+) -> StructArray {
+    let barriers = StringArray::from(vec![barrier; timestamps.len()]);
+    let watermarks = TimestampMillisecondArray::from(vec![watermark; timestamps.len()]);
+    StructArray::new(
+        stream_metadata_fields(true),
+        vec![
+            Arc::new(barriers) as ArrayRef,
+            Arc::new(timestamps),
+            Arc::new(watermarks),
+        ],
+        None,
+    )
+}
@@ -3,6 +3,12 @@ use std::result;
 use datafusion::common::DataFusionError;
 
 pub mod accumulators;
+pub mod cold_state;
+pub mod cron;
+pub mod metadata;
+pub mod state_metrics;
+pub mod stream_message;
 pub mod time;
+pub mod watermark;
 
 pub type Result<T, E = DataFusionError> = result::Result<T, E>;
@@ -0,0 +1,70 @@
+use std::time::SystemTime;
+
+use datafusion::physical_plan::metrics::{Count, ExecutionPlanMetricsSet, Gauge, MetricBuilder};
+use log::debug;
+
+/// State metrics of the operators that drop windows as the watermark closes them:
+/// `state_bytes` held after the latest watermark, and the `reclaimed_state_bytes` and
+/// `reclaimed_windows` the watermark advances dropped so far.
+#[derive(Debug, Clone)]
+pub struct StateMetrics {
+    state_bytes: Gauge,
+    reclaimed_bytes: Count,
+    reclaimed_windows: Count,
+}
+
+impl StateMetrics {
+    pub fn new(metrics: &ExecutionPlanMetricsSet, partition: usize) -> Self {
+        Self {
+            state_bytes: MetricBuilder::new(metrics).gauge("state_bytes", partition),
+            reclaimed_bytes: MetricBuilder::new(metrics)
+                .counter("reclaimed_state_bytes", partition),
+            reclaimed_windows: MetricBuilder::new(metrics).counter("reclaimed_windows", partition),
+        }
+    }
+
+    /// Record an advance to `watermark` that dropped `windows` windows holding `bytes`,
+    /// leaving `remaining` bytes of state
+    pub fn record_reclaimed(
+        &self,
+        watermark: SystemTime,
+        windows: usize,
+        bytes: usize,
+        remaining: usize,
+    ) {
+        self.state_bytes.set(remaining);
+        if windows == 0 {
+            return;
+        }
+        self.reclaimed_bytes.add(bytes);
+        self.reclaimed_windows.add(windows);
+        debug!(
+            "Watermark {watermark:?} closed {windows} windows, reclaiming {bytes} bytes of state \
+             with {remaining} bytes left"
+        );
+    }
+
+    pub fn state_bytes(&self) -> usize {
+        self.state_bytes.value()
+    }
+
+    pub fn reclaimed_bytes(&self) -> usize {
+        self.reclaimed_bytes.value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_reclaimed_state() {
+        let metrics = StateMetrics::new(&ExecutionPlanMetricsSet::new(), 0);
+        metrics.record_reclaimed(SystemTime::UNIX_EPOCH, 2, 512, 1024);
+        metrics.record_reclaimed(SystemTime::UNIX_EPOCH, 0, 0, 2048);
+        metrics.record_reclaimed(SystemTime::UNIX_EPOCH, 1, 256, 1792);
+
+        assert_eq!(metrics.reclaimed_bytes(), 768);
+        assert_eq!(metrics.state_bytes(), 1792);
+    }
+}
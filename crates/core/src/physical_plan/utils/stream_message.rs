@@ -0,0 +1,396 @@
+//! Typed view over the control information that travels in-band with the data.
+//!
+//! Sources attach the [`STREAMING_METADATA_COLUMN`] to every batch they produce. Since the
+//! column is carried through projections, filters and joins like any other column, watermarks
+//! and checkpoint barriers reach every operator in the same order as the rows they describe.
+//! Operators that need to react to them wrap their input in a [`MessageStream`] instead of
+//! inspecting the metadata column themselves.
+//!
+//! Rows can be filtered away, and a read that found nothing has no rows to carry the column.
+//! Sources therefore also send a [`control_batch`] after every batch: a batch without rows
+//! that carries the watermark and barrier in its schema metadata. Operators that hold rows
+//! back or produce new ones pass control on the same way.
+
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use arrow::array::AsArray;
+use arrow::compute::min;
+use arrow::datatypes::TimestampMillisecondType;
+use arrow_array::{ArrayRef, RecordBatch, StringArray, StructArray, TimestampMillisecondArray};
+use arrow_schema::SchemaRef;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::execution::SendableRecordBatchStream;
+use futures::{ready, Stream, StreamExt};
+
+use super::metadata::{
+    has_stream_metadata, BARRIER_FIELD, STREAMING_METADATA_COLUMN, WATERMARK_FIELD,
+};
+use super::time::{system_time_from_epoch, RecordBatchWatermark};
+
+/// Value of the barrier field for rows that don't close a checkpoint epoch.
+pub const NO_BARRIER: &str = "no_barrier";
+/// Schema metadata of a [`control_batch`] holding the epoch it closes
+const BARRIER_METADATA_KEY: &str = "denormalized.barrier";
+/// Schema metadata of a [`control_batch`] holding its watermark in milliseconds
+const WATERMARK_METADATA_KEY: &str = "denormalized.watermark";
+
+#[derive(Debug, Clone)]
+pub enum StreamMessage {
+    Data(RecordBatch),
+    /// No further rows older than this are expected on the partition
+    Watermark(SystemTime),
+    /// All rows up to and including the epoch have been delivered
+    Barrier(u64),
+    EndOfPartition,
+}
+
+impl StreamMessage {
+    /// Splits a batch into the data and the control messages encoded in its metadata column,
+    /// or in the schema metadata of a [`control_batch`]. The data always comes first so
+    /// operators see the rows before the watermark or barrier that covers them. Empty batches
+    /// produce no messages other than those of a control batch.
+    pub fn from_batch(batch: RecordBatch) -> Result<Vec<StreamMessage>> {
+        if batch.num_rows() == 0 {
+            return control_messages(&batch);
+        }
+        if !has_stream_metadata(&batch.schema()) {
+            return Ok(vec![StreamMessage::Data(batch)]);
+        }
+
+        let (watermark, barrier) = row_control(&batch)?;
+
+        let mut messages = vec![
+            StreamMessage::Data(batch),
+            StreamMessage::Watermark(watermark),
+        ];
+        if let Some(epoch) = barrier {
+            messages.push(StreamMessage::Barrier(epoch));
+        }
+        Ok(messages)
+    }
+}
+
+/// The value a source writes to the barrier field of the batch that closes `epoch`
+pub fn barrier_marker(epoch: u64) -> String {
+    epoch.to_string()
+}
+
+/// A batch of `schema` without rows that closes `barrier` and carries `watermark`. It reaches
+/// the next operator whether or not any rows made it there.
+pub fn control_batch(
+    schema: &SchemaRef,
+    barrier: Option<u64>,
+    watermark: Option<SystemTime>,
+) -> RecordBatch {
+    let mut metadata = schema.metadata().clone();
+    if let Some(epoch) = barrier {
+        metadata.insert(BARRIER_METADATA_KEY.to_string(), barrier_marker(epoch));
+    }
+    if let Some(watermark) = watermark {
+        let watermark_ms = watermark
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as i64);
+        metadata.insert(WATERMARK_METADATA_KEY.to_string(), watermark_ms.to_string());
+    }
+    RecordBatch::new_empty(Arc::new(schema.as_ref().clone().with_metadata(metadata)))
+}
+
+/// Whether `batch` is a [`control_batch`]
+pub(crate) fn is_control_batch(batch: &RecordBatch) -> bool {
+    let schema = batch.schema();
+    batch.num_rows() == 0
+        && (schema.metadata().contains_key(BARRIER_METADATA_KEY)
+            || schema.metadata().contains_key(WATERMARK_METADATA_KEY))
+}
+
+/// The control batch `control` as a batch of `schema`, for operators whose output schema
+/// differs from their input's
+pub(crate) fn forward_control(control: &RecordBatch, schema: &SchemaRef) -> RecordBatch {
+    let mut metadata = schema.metadata().clone();
+    for key in [BARRIER_METADATA_KEY, WATERMARK_METADATA_KEY] {
+        if let Some(value) = control.schema().metadata().get(key) {
+            metadata.insert(key.to_string(), value.clone());
+        }
+    }
+    RecordBatch::new_empty(Arc::new(schema.as_ref().clone().with_metadata(metadata)))
+}
+
+/// The watermark and barrier of a [`control_batch`], in that order
+fn control_messages(batch: &RecordBatch) -> Result<Vec<StreamMessage>> {
+    let schema = batch.schema();
+    let metadata: &HashMap<String, String> = schema.metadata();
+    let mut messages = vec![];
+    if let Some(watermark) = metadata.get(WATERMARK_METADATA_KEY) {
+        let watermark_ms = watermark
+            .parse::<i64>()
+            .map_err(|_| DataFusionError::Execution(format!("Invalid watermark '{watermark}'")))?;
+        messages.push(StreamMessage::Watermark(system_time_from_epoch(
+            watermark_ms,
+        )));
+    }
+    if let Some(marker) = metadata.get(BARRIER_METADATA_KEY) {
+        let epoch = marker.parse::<u64>().map_err(|_| {
+            DataFusionError::Execution(format!("Invalid checkpoint barrier '{marker}'"))
+        })?;
+        messages.push(StreamMessage::Barrier(epoch));
+    }
+    Ok(messages)
+}
+
+/// The oldest watermark sources wrote to the rows of the batch, None for sources that leave
+/// the watermark to the event times
+fn source_watermark(batch: &RecordBatch) -> Result<Option<SystemTime>> {
+    let watermarks = metadata_struct(batch)?
+        .column_by_name(WATERMARK_FIELD)
+        .map(|column| column.as_primitive::<TimestampMillisecondType>());
+    Ok(watermarks
+        .and_then(|watermarks| min(watermarks))
+        .map(system_time_from_epoch))
+}
+
+fn metadata_struct(batch: &RecordBatch) -> Result<&StructArray> {
+    batch
+        .column_by_name(STREAMING_METADATA_COLUMN)
+        .and_then(|column| column.as_any().downcast_ref::<StructArray>())
+        .ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "{STREAMING_METADATA_COLUMN} is expected to be a struct column"
+            ))
+        })
+}
+
+/// The latest epoch closed by any row of the batch
+pub(crate) fn barrier_epoch(batch: &RecordBatch) -> Result<Option<u64>> {
+    let Some(barriers) = metadata_struct(batch)?.column_by_name(BARRIER_FIELD) else {
+        return Ok(None);
+    };
+
+    let mut latest = None;
+    for marker in barriers.as_string::<i32>().iter().flatten() {
+        if marker == NO_BARRIER {
+            continue;
+        }
+        let epoch = marker.parse::<u64>().map_err(|_| {
+            DataFusionError::Execution(format!("Invalid checkpoint barrier '{marker}'"))
+        })?;
+        latest = latest.max(Some(epoch));
+    }
+    Ok(latest)
+}
+
+/// The watermark and the latest barrier the rows of a batch with stream metadata carry
+fn row_control(batch: &RecordBatch) -> Result<(SystemTime, Option<u64>)> {
+    let watermark = match source_watermark(batch)? {
+        Some(watermark) => watermark,
+        None => RecordBatchWatermark::try_from(batch, STREAMING_METADATA_COLUMN)?.min_timestamp,
+    };
+    Ok((watermark, barrier_epoch(batch)?))
+}
+
+/// The [`control_batch`] carrying the watermark and barrier of the rows of `batch`, None if
+/// the batch has no rows or no metadata. Operators that drop rows send it after the rows
+/// they keep, so the control messages reach the operators after them either way.
+pub(crate) fn control_of(batch: &RecordBatch) -> Result<Option<RecordBatch>> {
+    if batch.num_rows() == 0 || !has_stream_metadata(&batch.schema()) {
+        return Ok(None);
+    }
+    let (watermark, barrier) = row_control(batch)?;
+    Ok(Some(control_batch(
+        &batch.schema(),
+        barrier,
+        Some(watermark),
+    )))
+}
+
+/// `batch` with the metadata of its rows rewritten to close `barrier`, or no epoch, and to
+/// carry `watermark` if the metadata has a watermark field. Operators that emit rows they
+/// held back use it to pass on the control messages they consumed meanwhile.
+pub(crate) fn restamp(
+    batch: &RecordBatch,
+    barrier: Option<u64>,
+    watermark: Option<SystemTime>,
+) -> Result<RecordBatch> {
+    let Ok(index) = batch.schema().index_of(STREAMING_METADATA_COLUMN) else {
+        return Ok(batch.clone());
+    };
+    let rows = batch.num_rows();
+    let (fields, mut arrays, nulls) = metadata_struct(batch)?.clone().into_parts();
+    for (field, array) in fields.iter().zip(arrays.iter_mut()) {
+        match (field.name().as_str(), watermark) {
+            (BARRIER_FIELD, _) => {
+                let marker = barrier.map_or_else(|| NO_BARRIER.to_string(), barrier_marker);
+                *array = Arc::new(StringArray::from(vec![marker; rows])) as ArrayRef;
+            }
+            (WATERMARK_FIELD, Some(watermark)) => {
+                let watermark_ms = watermark
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_millis() as i64);
+                *array =
+                    Arc::new(TimestampMillisecondArray::from(vec![watermark_ms; rows])) as ArrayRef;
+            }
+            _ => {}
+        }
+    }
+
+    let mut columns = batch.columns().to_vec();
+    columns[index] = Arc::new(StructArray::try_new(fields, arrays, nulls)?);
+    Ok(RecordBatch::try_new(batch.schema(), columns)?)
+}
+
+/// Adapts a record batch stream into a stream of [`StreamMessage`]s, ending with a single
+/// [`StreamMessage::EndOfPartition`] once the input is exhausted.
+///
+/// A batch and the control batch after it carry the same watermark and barrier, the repeats
+/// are skipped. So are barriers of epochs that were already closed.
+pub struct MessageStream {
+    input: SendableRecordBatchStream,
+    pending: VecDeque<StreamMessage>,
+    finished: bool,
+    /// Latest watermark passed on since the last data
+    latest_watermark: Option<SystemTime>,
+    latest_barrier: Option<u64>,
+}
+
+impl MessageStream {
+    pub fn new(input: SendableRecordBatchStream) -> Self {
+        Self {
+            input,
+            pending: VecDeque::new(),
+            finished: false,
+            latest_watermark: None,
+            latest_barrier: None,
+        }
+    }
+
+    fn push(&mut self, message: StreamMessage) {
+        match message {
+            StreamMessage::Watermark(watermark) if Some(watermark) <= self.latest_watermark => {}
+            StreamMessage::Watermark(watermark) => {
+                self.latest_watermark = Some(watermark);
+                self.pending.push_back(message);
+            }
+            StreamMessage::Barrier(epoch) if Some(epoch) <= self.latest_barrier => {}
+            StreamMessage::Barrier(epoch) => {
+                self.latest_barrier = Some(epoch);
+                self.pending.push_back(message);
+            }
+            StreamMessage::Data(_) => {
+                self.latest_watermark = None;
+                self.pending.push_back(message);
+            }
+            StreamMessage::EndOfPartition => self.pending.push_back(message),
+        }
+    }
+}
+
+impl Stream for MessageStream {
+    type Item = Result<StreamMessage>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(message)));
+            }
+            if self.finished {
+                return Poll::Ready(None);
+            }
+            match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(batch)) => {
+                    for message in StreamMessage::from_batch(batch)? {
+                        self.push(message);
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    self.finished = true;
+                    return Poll::Ready(Some(Ok(StreamMessage::EndOfPartition)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::stream;
+
+    use crate::physical_plan::utils::metadata::{
+        stream_metadata_array_with_barrier, stream_metadata_field,
+    };
+
+    fn batch(schema: &Arc<Schema>, timestamps: Vec<i64>, barrier: &str) -> RecordBatch {
+        let ids = Int64Array::from_iter_values(0..timestamps.len() as i64);
+        let metadata = stream_metadata_array_with_barrier(
+            TimestampMillisecondArray::from(timestamps),
+            barrier,
+        );
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(ids) as ArrayRef, Arc::new(metadata)],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn messages_follow_the_rows_they_cover() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let at = |ms| Some(system_time_from_epoch(ms));
+        let batches = vec![
+            Ok(batch(&schema, vec![2000, 1000], NO_BARRIER)),
+            Ok(batch(&schema, vec![], NO_BARRIER)),
+            Ok(batch(&schema, vec![3000], &barrier_marker(4))),
+            // Repeats what the rows of the batch before carried
+            Ok(control_batch(&schema, Some(4), at(3000))),
+            // A read that found nothing
+            Ok(control_batch(&schema, Some(5), at(4000))),
+        ];
+        let input = RecordBatchStreamAdapter::new(schema, stream::iter(batches));
+        let messages = MessageStream::new(Box::pin(input))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        let kinds = messages
+            .iter()
+            .map(|message| match message {
+                StreamMessage::Data(batch) => format!("data({})", batch.num_rows()),
+                StreamMessage::Watermark(watermark) => format!(
+                    "watermark({})",
+                    watermark
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap()
+                        .as_millis()
+                ),
+                StreamMessage::Barrier(epoch) => format!("barrier({epoch})"),
+                StreamMessage::EndOfPartition => "end".to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                "data(2)",
+                "watermark(1000)",
+                "data(1)",
+                "watermark(3000)",
+                "barrier(4)",
+                "watermark(4000)",
+                "barrier(5)",
+                "end"
+            ]
+        );
+        Ok(())
+    }
+}
@@ -4,12 +4,14 @@ use arrow::{
     compute::{max, min},
     datatypes::TimestampMillisecondType,
 };
+use arrow_array::timezone::Tz;
 use arrow_array::{
     Array, Int64Array, PrimitiveArray, RecordBatch, StringArray, StructArray,
     TimestampMillisecondArray,
 };
-use chrono::NaiveDateTime;
-use datafusion::common::DataFusionError;
+use chrono::{DateTime, Datelike, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone};
+use datafusion::common::{DataFusionError, ScalarValue};
+use datafusion::execution::TaskContext;
 
 #[derive(Debug, Clone)]
 pub enum TimestampUnit {
@@ -28,6 +30,20 @@ pub fn system_time_from_epoch(epoch: i64) -> SystemTime {
     SystemTime::UNIX_EPOCH + Duration::from_millis(epoch as u64)
 }
 
+/// Milliseconds of an interval literal such as `INTERVAL '10 seconds'`, None for values that
+/// aren't intervals or count months, which vary in length
+pub fn interval_millis(value: &ScalarValue) -> Option<i64> {
+    match value {
+        ScalarValue::IntervalMonthDayNano(Some(interval)) if interval.months == 0 => {
+            Some(interval.days as i64 * 86_400_000 + interval.nanoseconds / 1_000_000)
+        }
+        ScalarValue::IntervalDayTime(Some(interval)) => {
+            Some(interval.days as i64 * 86_400_000 + interval.milliseconds as i64)
+        }
+        _ => None,
+    }
+}
+
 impl RecordBatchWatermark {
     pub fn try_from(
         record_batch: &RecordBatch,
@@ -92,3 +108,232 @@ pub fn array_to_timestamp_array(
         }
     }
 }
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// The time zone window boundaries are aligned to. Windows are laid out on the local wall
+/// clock, so a daily window starts at local midnight and lasts 23 or 25 hours on the days
+/// daylight saving time starts or ends.
+#[derive(Debug, Clone, Default)]
+pub struct WindowTimezone(Option<Tz>);
+
+impl WindowTimezone {
+    pub fn utc() -> Self {
+        Self(None)
+    }
+
+    /// Accepts the forms of `datafusion.execution.time_zone`, e.g. `+05:30` or
+    /// `Europe/Berlin`. An empty string or a zero offset is UTC.
+    pub fn try_new(timezone: &str) -> Result<Self, DataFusionError> {
+        match timezone {
+            "" | "+00:00" | "UTC" | "Z" => Ok(Self::utc()),
+            timezone => Ok(Self(Some(timezone.parse()?))),
+        }
+    }
+
+    /// The time zone configured for the session in `datafusion.execution.time_zone`
+    pub fn from_task_context(context: &TaskContext) -> Result<Self, DataFusionError> {
+        match &context.session_config().options().execution.time_zone {
+            Some(timezone) => Self::try_new(timezone),
+            None => Ok(Self::utc()),
+        }
+    }
+
+    /// Wall clock time at `utc_ms`, as milliseconds since the local epoch
+    pub fn to_local(&self, utc_ms: i64) -> i64 {
+        match &self.0 {
+            Some(tz) => utc_ms + offset_ms(tz, utc_ms),
+            None => utc_ms,
+        }
+    }
+
+    /// The instant the wall clock shows `local_ms`. Times repeated when clocks go back map to
+    /// their first occurrence. Times skipped when clocks go forward are read with the offset
+    /// from before the jump, so a boundary at the skipped hour falls on the jump itself.
+    pub fn to_utc(&self, local_ms: i64) -> i64 {
+        let Some(tz) = &self.0 else {
+            return local_ms;
+        };
+        let naive = DateTime::from_timestamp_millis(local_ms)
+            .unwrap_or_default()
+            .naive_utc();
+        match tz.from_local_datetime(&naive) {
+            LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.timestamp_millis(),
+            // Transitions are far enough apart for the offset a day earlier to be the one
+            // in effect before the gap
+            LocalResult::None => local_ms - offset_ms(tz, local_ms - DAY_MS),
+        }
+    }
+}
+
+/// Window sizes in calendar units, whose length in time varies. Periods are laid out on the
+/// local wall clock, see [`WindowTimezone`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CalendarInterval {
+    /// Starting at midnight
+    Days(u32),
+    /// Starting at midnight on Monday
+    Weeks(u32),
+    /// Starting at midnight on the first day of the month
+    Months(u32),
+}
+
+/// 1970-01-01 was a Thursday, Mondays are 3 days off from multiples of 7 days since the epoch
+const EPOCH_DAYS_AFTER_MONDAY: i64 = 3;
+
+impl CalendarInterval {
+    /// Start of the period containing the wall clock time `local_ms`
+    pub fn period_start(&self, local_ms: i64) -> i64 {
+        let days = local_ms.div_euclid(DAY_MS);
+        match *self {
+            Self::Days(n) => days.div_euclid(n.max(1) as i64) * n.max(1) as i64 * DAY_MS,
+            Self::Weeks(n) => {
+                let week_days = 7 * n.max(1) as i64;
+                let since_monday = days + EPOCH_DAYS_AFTER_MONDAY;
+                (since_monday.div_euclid(week_days) * week_days - EPOCH_DAYS_AFTER_MONDAY) * DAY_MS
+            }
+            Self::Months(n) => {
+                let month = month_index(days);
+                month_start(month.div_euclid(n.max(1) as i64) * n.max(1) as i64)
+            }
+        }
+    }
+
+    /// Start of the period after the one starting at `period_start`
+    pub fn next_period(&self, period_start: i64) -> i64 {
+        match *self {
+            Self::Days(n) => period_start + n.max(1) as i64 * DAY_MS,
+            Self::Weeks(n) => period_start + 7 * n.max(1) as i64 * DAY_MS,
+            Self::Months(n) => {
+                month_start(month_index(period_start.div_euclid(DAY_MS)) + n.max(1) as i64)
+            }
+        }
+    }
+
+    /// The longest a period can last, for estimates that need a fixed length
+    pub fn max_length(&self) -> Duration {
+        let days = match *self {
+            Self::Days(n) => n as u64,
+            Self::Weeks(n) => 7 * n as u64,
+            Self::Months(n) => 31 * n as u64,
+        };
+        // Plus the hour gained when clocks go back
+        Duration::from_millis(days * DAY_MS as u64 + 60 * 60 * 1000)
+    }
+}
+
+/// Months since January 1970 of the day `days` after the epoch
+fn month_index(days: i64) -> i64 {
+    let date = DateTime::from_timestamp(days * 86_400, 0)
+        .unwrap_or_default()
+        .date_naive();
+    (date.year() as i64 - 1970) * 12 + date.month0() as i64
+}
+
+fn month_start(month_index: i64) -> i64 {
+    let year = 1970 + month_index.div_euclid(12);
+    let month = month_index.rem_euclid(12) as u32 + 1;
+    NaiveDate::from_ymd_opt(year as i32, month, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap_or_default()
+        .and_utc()
+        .timestamp_millis()
+}
+
+fn offset_ms(tz: &Tz, utc_ms: i64) -> i64 {
+    let naive = DateTime::from_timestamp_millis(utc_ms)
+        .unwrap_or_default()
+        .naive_utc();
+    tz.offset_from_utc_datetime(&naive).fix().local_minus_utc() as i64 * 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc_ms(timestamp: &str) -> i64 {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    /// Start and end of the local day containing `utc_ms`
+    fn local_day(timezone: &WindowTimezone, utc_ms: i64) -> (i64, i64) {
+        let start = timezone.to_local(utc_ms).div_euclid(DAY_MS) * DAY_MS;
+        (timezone.to_utc(start), timezone.to_utc(start + DAY_MS))
+    }
+
+    #[test]
+    fn days_across_dst_transitions() {
+        let new_york = WindowTimezone::try_new("America/New_York").unwrap();
+
+        // Clocks go forward on 2024-03-10, the day is 23 hours long
+        let (start, end) = local_day(&new_york, utc_ms("2024-03-10T16:00:00Z"));
+        assert_eq!(start, utc_ms("2024-03-10T05:00:00Z"));
+        assert_eq!(end, utc_ms("2024-03-11T04:00:00Z"));
+
+        // Clocks go back on 2024-11-03, the day is 25 hours long
+        let (start, end) = local_day(&new_york, utc_ms("2024-11-03T12:00:00Z"));
+        assert_eq!(start, utc_ms("2024-11-03T04:00:00Z"));
+        assert_eq!(end, utc_ms("2024-11-04T05:00:00Z"));
+
+        // 02:00 doesn't exist on 2024-03-10, an hourly boundary there falls on the jump
+        let skipped = utc_ms("2024-03-10T02:00:00Z");
+        assert_eq!(new_york.to_utc(skipped), utc_ms("2024-03-10T07:00:00Z"));
+    }
+
+    #[test]
+    fn calendar_periods() {
+        let berlin = WindowTimezone::try_new("Europe/Berlin").unwrap();
+        let period = |interval: CalendarInterval, timestamp: &str| {
+            let start = interval.period_start(berlin.to_local(utc_ms(timestamp)));
+            (
+                berlin.to_utc(start),
+                berlin.to_utc(interval.next_period(start)),
+            )
+        };
+
+        // Wednesday, the week runs from Monday 2024-03-25 across the switch to summer time
+        assert_eq!(
+            period(CalendarInterval::Weeks(1), "2024-03-27T12:00:00Z"),
+            (
+                utc_ms("2024-03-24T23:00:00Z"),
+                utc_ms("2024-03-31T22:00:00Z")
+            )
+        );
+        assert_eq!(
+            period(CalendarInterval::Months(1), "2024-02-29T23:30:00Z"),
+            (
+                utc_ms("2024-02-29T23:00:00Z"),
+                utc_ms("2024-03-31T22:00:00Z")
+            )
+        );
+        assert_eq!(
+            period(CalendarInterval::Months(3), "2024-05-15T00:00:00Z"),
+            (
+                utc_ms("2024-03-31T22:00:00Z"),
+                utc_ms("2024-06-30T22:00:00Z")
+            )
+        );
+        assert_eq!(
+            period(CalendarInterval::Days(1), "2024-10-27T12:00:00Z"),
+            (
+                utc_ms("2024-10-26T22:00:00Z"),
+                utc_ms("2024-10-27T23:00:00Z")
+            )
+        );
+    }
+
+    #[test]
+    fn fixed_offsets_and_utc() {
+        let india = WindowTimezone::try_new("+05:30").unwrap();
+        let hour = 60 * 60 * 1000;
+        let local = india.to_local(utc_ms("2024-01-01T10:00:00Z"));
+        let start = india.to_utc(local.div_euclid(hour) * hour);
+        assert_eq!(start, utc_ms("2024-01-01T09:30:00Z"));
+
+        let utc = WindowTimezone::try_new("+00:00").unwrap();
+        assert_eq!(utc.to_local(1_234), 1_234);
+        assert_eq!(utc.to_utc(1_234), 1_234);
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+/// How a source derives its watermark from the event times it reads. The watermark tells
+/// windows that no rows older than it are expected anymore, so they fire on the event time
+/// the source has reached rather than on when rows happen to arrive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatermarkStrategy {
+    /// Every batch advances the watermark to its oldest row
+    #[default]
+    BatchMinimum,
+    /// Event times never decrease, the watermark follows the latest one
+    Monotonic,
+    /// Rows arrive at most the given duration after rows with later event times, the
+    /// watermark trails the latest event time by that much
+    BoundedOutOfOrderness(Duration),
+}
+
+/// Tracks the watermark of a single partition under a [`WatermarkStrategy`]
+#[derive(Debug, Clone, Default)]
+pub struct WatermarkGenerator {
+    strategy: WatermarkStrategy,
+    watermark: Option<i64>,
+}
+
+impl WatermarkGenerator {
+    pub fn new(strategy: WatermarkStrategy) -> Self {
+        Self {
+            strategy,
+            watermark: None,
+        }
+    }
+
+    /// Advance the watermark by the event times of a batch and return it. The watermark never
+    /// moves backwards and is None until the partition produced its first row.
+    pub fn advance(&mut self, event_times: impl IntoIterator<Item = i64>) -> Option<i64> {
+        let (oldest, latest) = event_times.into_iter().fold(
+            (None, None),
+            |(oldest, latest): (Option<i64>, _), time| {
+                (
+                    Some(oldest.map_or(time, |oldest| oldest.min(time))),
+                    latest.max(Some(time)),
+                )
+            },
+        );
+        let candidate = match self.strategy {
+            WatermarkStrategy::BatchMinimum => oldest,
+            WatermarkStrategy::Monotonic => latest,
+            WatermarkStrategy::BoundedOutOfOrderness(bound) => {
+                latest.map(|latest| latest.saturating_sub(bound.as_millis() as i64))
+            }
+        };
+        self.watermark = self.watermark.max(candidate);
+        self.watermark
+    }
+
+    pub fn current(&self) -> Option<i64> {
+        self.watermark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watermarks_trail_event_times() {
+        let batches = [vec![1_000, 4_000, 2_000], vec![3_000], vec![]];
+        let watermarks = |strategy| {
+            let mut generator = WatermarkGenerator::new(strategy);
+            batches
+                .iter()
+                .map(|batch| generator.advance(batch.iter().copied()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            watermarks(WatermarkStrategy::BatchMinimum),
+            [Some(1_000), Some(3_000), Some(3_000)]
+        );
+        assert_eq!(
+            watermarks(WatermarkStrategy::Monotonic),
+            [Some(4_000), Some(4_000), Some(4_000)]
+        );
+        // A row 2s behind the latest one is still ahead of the watermark
+        assert_eq!(
+            watermarks(WatermarkStrategy::BoundedOutOfOrderness(
+                Duration::from_millis(2_500)
+            )),
+            [Some(1_500), Some(1_500), Some(1_500)]
+        );
+        assert_eq!(WatermarkGenerator::default().current(), None);
+    }
+}
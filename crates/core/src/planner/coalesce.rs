@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNode};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_planner::{ExtensionPlanner, PhysicalPlanner};
+
+use crate::logical_plan::coalesce::CoalescePlanNode;
+use crate::physical_plan::coalesce::StreamingCoalesceExec;
+
+/// Physical planner for Coalesce nodes
+pub struct CoalescePlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for CoalescePlanner {
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        _logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        _session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        Ok(node
+            .as_any()
+            .downcast_ref::<CoalescePlanNode>()
+            .map(|coalesce| {
+                Arc::new(StreamingCoalesceExec::new(
+                    physical_inputs[0].clone(),
+                    coalesce.target_rows,
+                    coalesce.max_wait,
+                )) as Arc<dyn ExecutionPlan>
+            }))
+    }
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNode};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_planner::{ExtensionPlanner, PhysicalPlanner};
+
+use crate::logical_plan::interval_join::IntervalJoinPlanNode;
+use crate::physical_plan::interval_join::IntervalJoinExec;
+
+/// Physical planner for IntervalJoin nodes
+pub struct IntervalJoinPlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for IntervalJoinPlanner {
+    async fn plan_extension(
+        &self,
+        planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        let Some(join) = node.as_any().downcast_ref::<IntervalJoinPlanNode>() else {
+            return Ok(None);
+        };
+        let (left_schema, right_schema) = (logical_inputs[0].schema(), logical_inputs[1].schema());
+        let on = join
+            .on
+            .iter()
+            .map(|(left, right)| {
+                Ok((
+                    planner.create_physical_expr(left, left_schema, session_state)?,
+                    planner.create_physical_expr(right, right_schema, session_state)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let time_bound = &join.time_bound;
+
+        Ok(Some(Arc::new(IntervalJoinExec::new(
+            physical_inputs[0].clone(),
+            physical_inputs[1].clone(),
+            on,
+            planner.create_physical_expr(&time_bound.left_time, left_schema, session_state)?,
+            planner.create_physical_expr(&time_bound.right_time, right_schema, session_state)?,
+            time_bound.lower_ms,
+            time_bound.upper_ms,
+            Arc::new(join.schema.as_arrow().clone()),
+        ))))
+    }
+}
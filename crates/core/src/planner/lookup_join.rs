@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNode};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_planner::{ExtensionPlanner, PhysicalPlanner};
+
+use crate::logical_plan::lookup_join::LookupJoinPlanNode;
+use crate::physical_plan::lookup_join::LookupJoinExec;
+
+/// Physical planner for LookupJoin nodes
+pub struct LookupJoinPlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for LookupJoinPlanner {
+    async fn plan_extension(
+        &self,
+        planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        let Some(join) = node.as_any().downcast_ref::<LookupJoinPlanNode>() else {
+            return Ok(None);
+        };
+        let table_schema = join.table.provider().schema();
+        let on = join
+            .on
+            .iter()
+            .map(|(key, column)| {
+                Ok((
+                    planner.create_physical_expr(key, logical_inputs[0].schema(), session_state)?,
+                    table_schema.index_of(column)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(Arc::new(LookupJoinExec::new(
+            physical_inputs[0].clone(),
+            join.table.clone(),
+            on,
+            Arc::new(join.schema.as_arrow().clone()),
+        ))))
+    }
+}
@@ -1 +1,9 @@
+pub mod coalesce;
+pub mod deduplicate;
+pub mod interval_join;
+pub mod lookup_join;
+pub mod quality;
+pub mod reorder;
+pub mod sample;
 pub mod streaming_window;
+pub mod tap;
@@ -0,0 +1,34 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNode};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_planner::{ExtensionPlanner, PhysicalPlanner};
+
+use crate::logical_plan::quality::QualityPlanNode;
+use crate::physical_plan::quality::QualityExec;
+
+/// Physical planner for AssertQuality nodes
+pub struct QualityPlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for QualityPlanner {
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        _logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        _session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        let Some(quality) = node.as_any().downcast_ref::<QualityPlanNode>() else {
+            return Ok(None);
+        };
+        Ok(Some(Arc::new(QualityExec::try_new(
+            physical_inputs[0].clone(),
+            quality.checks.clone(),
+        )?)))
+    }
+}
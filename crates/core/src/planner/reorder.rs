@@ -0,0 +1,34 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNode};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_planner::{ExtensionPlanner, PhysicalPlanner};
+
+use crate::logical_plan::reorder::ReorderPlanNode;
+use crate::physical_plan::reorder::ReorderExec;
+
+/// Physical planner for Reorder nodes
+pub struct ReorderPlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for ReorderPlanner {
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        _logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        _session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        let Some(reorder) = node.as_any().downcast_ref::<ReorderPlanNode>() else {
+            return Ok(None);
+        };
+        Ok(Some(Arc::new(ReorderExec::try_new(
+            physical_inputs[0].clone(),
+            reorder.delay,
+        )?)))
+    }
+}
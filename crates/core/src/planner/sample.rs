@@ -0,0 +1,34 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNode};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_planner::{ExtensionPlanner, PhysicalPlanner};
+
+use crate::logical_plan::sample::SamplePlanNode;
+use crate::physical_plan::sample::SampleExec;
+
+/// Physical planner for Sample nodes
+pub struct SamplePlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for SamplePlanner {
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        _logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        _session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        Ok(node
+            .as_any()
+            .downcast_ref::<SamplePlanNode>()
+            .map(|sample| {
+                Arc::new(SampleExec::new(physical_inputs[0].clone(), sample.method))
+                    as Arc<dyn ExecutionPlan>
+            }))
+    }
+}
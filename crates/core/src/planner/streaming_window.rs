@@ -76,20 +76,20 @@ impl ExtensionPlanner for StreamingWindowPlanner {
         physical_inputs: &[Arc<dyn ExecutionPlan>],
         _session_state: &SessionState,
     ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
-        assert_eq!(
-            logical_inputs.len(),
-            1,
-            "Inconsistent number of logical inputs. A Streaming Window should have only 1 input."
-        );
-        assert_eq!(
-            physical_inputs.len(),
-            1,
-            "Inconsistent number of physical inputs. A Streaming Window should have only 1 input."
-        );
         Ok(
             if let Some(streaming_window_node) =
                 node.as_any().downcast_ref::<StreamingWindowPlanNode>()
             {
+                assert_eq!(
+                    logical_inputs.len(),
+                    1,
+                    "Inconsistent number of logical inputs. A Streaming Window should have only 1 input."
+                );
+                assert_eq!(
+                    physical_inputs.len(),
+                    1,
+                    "Inconsistent number of physical inputs. A Streaming Window should have only 1 input."
+                );
                 // Initially need to perform the aggregate and then merge the partitions
 
                 let logical_input = logical_inputs[0];
@@ -120,25 +120,48 @@ impl ExtensionPlanner for StreamingWindowPlanner {
 
                 let (aggregates, filters, _order_bys): (Vec<_>, Vec<_>, Vec<_>) =
                     multiunzip(agg_filter);
-                let franz_window_type = match streaming_window_node.window_type {
+                let franz_window_type = match &streaming_window_node.window_type {
                     StreamingWindowType::Tumbling(length) => {
-                        FranzStreamingWindowType::Tumbling(length)
+                        FranzStreamingWindowType::Tumbling(*length)
                     }
                     StreamingWindowType::Sliding(length, slide) => {
-                        FranzStreamingWindowType::Sliding(length, slide)
+                        FranzStreamingWindowType::Sliding(*length, *slide)
+                    }
+                    StreamingWindowType::Calendar(interval) => {
+                        FranzStreamingWindowType::Calendar(*interval)
+                    }
+                    StreamingWindowType::Custom(assigner) => {
+                        FranzStreamingWindowType::Custom(assigner.clone())
+                    }
+                    StreamingWindowType::Global(window) => {
+                        FranzStreamingWindowType::Global(window.clone())
+                    }
+                    StreamingWindowType::Count(size, timeout) => {
+                        FranzStreamingWindowType::Count(*size, *timeout)
+                    }
+                    StreamingWindowType::Session(gap) => {
+                        FranzStreamingWindowType::Session(create_physical_expr(
+                            gap,
+                            logical_input_schema,
+                            _session_state.execution_props(),
+                        )?)
                     }
-                    StreamingWindowType::Session(..) => todo!(),
                 };
 
-                let initial_aggr = Arc::new(FranzStreamingWindowExec::try_new(
-                    AggregateMode::Single,
-                    groups.clone(),
-                    aggregates.clone(),
-                    filters.clone(),
-                    input_exec.clone(),
-                    physical_input_schema.clone(),
-                    franz_window_type,
-                )?);
+                let initial_aggr = Arc::new(
+                    FranzStreamingWindowExec::try_new(
+                        AggregateMode::Single,
+                        groups.clone(),
+                        aggregates.clone(),
+                        filters.clone(),
+                        input_exec.clone(),
+                        physical_input_schema.clone(),
+                        franz_window_type,
+                    )?
+                    .with_late_data(streaming_window_node.late_data.clone())
+                    .with_emit_schedule(streaming_window_node.emit_schedule.clone())
+                    .with_window_columns(streaming_window_node.window_columns.clone())?,
+                );
                 Some(initial_aggr)
             } else {
                 None
@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{LogicalPlan, UserDefinedLogicalNode};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_planner::{ExtensionPlanner, PhysicalPlanner};
+
+use crate::logical_plan::tap::TapPlanNode;
+use crate::physical_plan::tap::TapExec;
+
+/// Physical planner for Tap nodes
+pub struct TapPlanner {}
+
+#[async_trait]
+impl ExtensionPlanner for TapPlanner {
+    async fn plan_extension(
+        &self,
+        _planner: &dyn PhysicalPlanner,
+        node: &dyn UserDefinedLogicalNode,
+        _logical_inputs: &[&LogicalPlan],
+        physical_inputs: &[Arc<dyn ExecutionPlan>],
+        _session_state: &SessionState,
+    ) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        Ok(node.as_any().downcast_ref::<TapPlanNode>().map(|tap| {
+            Arc::new(TapExec::new(
+                physical_inputs[0].clone(),
+                tap.name.clone(),
+                tap.sample_rate,
+                tap.sink.clone(),
+            )) as Arc<dyn ExecutionPlan>
+        }))
+    }
+}
@@ -7,7 +7,15 @@ use datafusion::logical_expr::LogicalPlan;
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::physical_planner::{DefaultPhysicalPlanner, PhysicalPlanner};
 
+use crate::planner::coalesce::CoalescePlanner;
+use crate::planner::deduplicate::DeduplicatePlanner;
+use crate::planner::interval_join::IntervalJoinPlanner;
+use crate::planner::lookup_join::LookupJoinPlanner;
+use crate::planner::quality::QualityPlanner;
+use crate::planner::reorder::ReorderPlanner;
+use crate::planner::sample::SamplePlanner;
 use crate::planner::streaming_window::StreamingWindowPlanner;
+use crate::planner::tap::TapPlanner;
 pub struct StreamingQueryPlanner {}
 
 #[async_trait]
@@ -19,9 +27,17 @@ impl QueryPlanner for StreamingQueryPlanner {
         logical_plan: &LogicalPlan,
         session_state: &SessionState,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        let physical_planner = DefaultPhysicalPlanner::with_extension_planners(vec![Arc::new(
-            StreamingWindowPlanner {},
-        )]);
+        let physical_planner = DefaultPhysicalPlanner::with_extension_planners(vec![
+            Arc::new(StreamingWindowPlanner {}),
+            Arc::new(TapPlanner {}),
+            Arc::new(CoalescePlanner {}),
+            Arc::new(SamplePlanner {}),
+            Arc::new(QualityPlanner {}),
+            Arc::new(DeduplicatePlanner {}),
+            Arc::new(ReorderPlanner {}),
+            Arc::new(IntervalJoinPlanner {}),
+            Arc::new(LookupJoinPlanner {}),
+        ]);
 
         physical_planner
             .create_physical_plan(logical_plan, session_state)
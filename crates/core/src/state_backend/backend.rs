@@ -0,0 +1,63 @@
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use datafusion::common::{DataFusionError, Result};
+
+use super::rocksdb_backend::get_global_rocksdb;
+
+/// Storage of checkpointed operator state: source offsets, processed files, accumulator
+/// states and whatever else operators keep under their namespace.
+///
+/// Reads and writes go to a local working copy and stay synchronous, they sit on the hot path
+/// of every batch. Checkpoints make that copy durable under an epoch, which can mean uploading
+/// it, so managing them is async.
+#[async_trait]
+pub trait StateBackend: Send + Sync {
+    /// Create `namespace` unless it exists
+    fn ensure_namespace(&self, namespace: &str) -> Result<()>;
+
+    fn get_state(&self, namespace: &str, key: Vec<u8>) -> Result<Option<Vec<u8>>>;
+
+    fn put_state(&self, namespace: &str, key: Vec<u8>, value: Vec<u8>) -> Result<()>;
+
+    fn delete_state(&self, namespace: &str, key: Vec<u8>) -> Result<()>;
+
+    /// Estimated bytes held by `namespace`
+    fn state_size(&self, namespace: &str) -> Result<u64>;
+
+    /// Persist a consistent copy of the state as the checkpoint of `epoch`
+    async fn checkpoint(&self, epoch: u64) -> Result<()>;
+
+    /// Epochs of the completed checkpoints, oldest first
+    async fn list_checkpoints(&self) -> Result<Vec<u64>>;
+
+    /// Delete the checkpoints of epochs before `epoch`, returning the deleted epochs
+    async fn prune_checkpoints(&self, epoch: u64) -> Result<Vec<u64>>;
+
+    /// Delete all but the latest `keep_last` checkpoints, returning the deleted epochs
+    async fn retain_checkpoints(&self, keep_last: usize) -> Result<Vec<u64>> {
+        let checkpoints = self.list_checkpoints().await?;
+        let expired = checkpoints.len().saturating_sub(keep_last.max(1));
+        match checkpoints.get(expired) {
+            Some(&oldest_kept) if expired > 0 => self.prune_checkpoints(oldest_kept).await,
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+static GLOBAL_STATE_BACKEND: OnceLock<Arc<dyn StateBackend>> = OnceLock::new();
+
+/// Make `backend` the one sources and operators checkpoint to, instead of the global RocksDB
+pub fn initialize_global_state_backend(backend: Arc<dyn StateBackend>) -> Result<()> {
+    GLOBAL_STATE_BACKEND.set(backend).map_err(|_| {
+        DataFusionError::Internal("Global state backend already initialized".to_string())
+    })
+}
+
+/// The backend set with [`initialize_global_state_backend`], or else the global RocksDB
+pub fn get_global_state_backend() -> Result<Arc<dyn StateBackend>> {
+    if let Some(backend) = GLOBAL_STATE_BACKEND.get() {
+        return Ok(backend.clone());
+    }
+    Ok(get_global_rocksdb()?)
+}
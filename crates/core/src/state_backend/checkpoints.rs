@@ -0,0 +1,265 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use datafusion::common::{DataFusionError, Result};
+use datafusion::common_runtime::SpawnedTask;
+use log::{debug, warn};
+
+use super::rocksdb_backend::RocksDBBackend;
+
+pub(crate) const CHECKPOINT_PREFIX: &str = "chk-";
+const SAVEPOINT_PREFIX: &str = "sp-";
+pub(crate) const IN_PROGRESS_SUFFIX: &str = ".inprogress";
+/// Written last, a checkpoint directory without it was never completed
+pub(crate) const COMPLETED_MARKER: &str = "_COMPLETED";
+
+/// Manages the on-disk copies of the state backend taken at checkpoint barriers.
+///
+/// Checkpoints are numbered by epoch and only the latest `keep_last` are retained. Savepoints
+/// are taken on request, are named, and are never removed by the store. Expired checkpoints
+/// and directories left behind by interrupted checkpoints are deleted in the background
+/// after every new checkpoint.
+pub struct CheckpointStore {
+    dir: PathBuf,
+    keep_last: usize,
+    /// Held while creating checkpoints and while collecting garbage
+    lock: Arc<Mutex<()>>,
+    gc_task: Mutex<Option<SpawnedTask<()>>>,
+}
+
+impl CheckpointStore {
+    pub fn new(dir: impl Into<PathBuf>, keep_last: usize) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            keep_last: keep_last.max(1),
+            lock: Arc::new(Mutex::new(())),
+            gc_task: Mutex::new(None),
+        })
+    }
+
+    /// Every source closing `epoch` asks for its checkpoint, the first one takes it and the
+    /// others get its path
+    pub fn checkpoint(&self, backend: &RocksDBBackend, epoch: u64) -> Result<PathBuf> {
+        let target = self.dir.join(format!("{CHECKPOINT_PREFIX}{epoch:020}"));
+        {
+            let _guard = self.lock.lock().unwrap();
+            if target.exists() {
+                return Ok(target);
+            }
+            self.write(backend, &target)?;
+        }
+        self.schedule_gc();
+        Ok(target)
+    }
+
+    pub fn savepoint(&self, backend: &RocksDBBackend, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.ends_with(IN_PROGRESS_SUFFIX) {
+            return Err(DataFusionError::Plan(format!(
+                "Invalid savepoint name '{name}'"
+            )));
+        }
+        self.create(backend, &format!("{SAVEPOINT_PREFIX}{name}"))
+    }
+
+    /// Completed checkpoints, oldest first
+    pub fn checkpoints(&self) -> Result<Vec<(u64, PathBuf)>> {
+        Ok(completed_checkpoints(&self.dir)?)
+    }
+
+    pub fn latest_checkpoint(&self) -> Result<Option<PathBuf>> {
+        Ok(self.checkpoints()?.pop().map(|(_, path)| path))
+    }
+
+    /// Delete the completed checkpoints of epochs before `epoch` regardless of how many are
+    /// kept, returning their epochs
+    pub fn prune_before(&self, epoch: u64) -> Result<Vec<u64>> {
+        let _guard = self.lock.lock().unwrap();
+        let mut pruned = vec![];
+        for (checkpoint, path) in completed_checkpoints(&self.dir)? {
+            if checkpoint >= epoch {
+                break;
+            }
+            debug!("Deleting checkpoint {}", path.display());
+            // Without the marker a half deleted checkpoint is never mistaken for a complete one
+            fs::remove_file(path.join(COMPLETED_MARKER))?;
+            fs::remove_dir_all(&path)?;
+            pruned.push(checkpoint);
+        }
+        Ok(pruned)
+    }
+
+    /// Delete expired checkpoints and leftovers of interrupted ones right away
+    pub fn collect_garbage(&self) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(collect_garbage(&self.dir, self.keep_last)?)
+    }
+
+    fn create(&self, backend: &RocksDBBackend, name: &str) -> Result<PathBuf> {
+        let _guard = self.lock.lock().unwrap();
+        let target = self.dir.join(name);
+        if target.exists() {
+            return Err(DataFusionError::Execution(format!(
+                "{} already exists",
+                target.display()
+            )));
+        }
+        self.write(backend, &target)?;
+        Ok(target)
+    }
+
+    /// Copy the database to `target`, the caller holds the lock
+    fn write(&self, backend: &RocksDBBackend, target: &Path) -> Result<()> {
+        // RocksDB creates the directory itself and refuses to write into an existing one
+        let mut in_progress = target.as_os_str().to_owned();
+        in_progress.push(IN_PROGRESS_SUFFIX);
+        let in_progress = PathBuf::from(in_progress);
+        if in_progress.exists() {
+            fs::remove_dir_all(&in_progress)?;
+        }
+        backend.create_checkpoint(&in_progress)?;
+        fs::write(in_progress.join(COMPLETED_MARKER), b"")?;
+        fs::rename(&in_progress, target)?;
+        debug!("Created checkpoint {}", target.display());
+        Ok(())
+    }
+
+    fn schedule_gc(&self) {
+        let dir = self.dir.clone();
+        let keep_last = self.keep_last;
+        let lock = Arc::clone(&self.lock);
+        let gc = move || {
+            let _guard = lock.lock().unwrap();
+            if let Err(e) = collect_garbage(&dir, keep_last) {
+                warn!(
+                    "Failed to delete expired checkpoints in {}: {e}",
+                    dir.display()
+                );
+            }
+        };
+
+        if tokio::runtime::Handle::try_current().is_ok() {
+            // Replacing the handle cancels a previous collection that hasn't started yet, one
+            // that is already running completes regardless
+            *self.gc_task.lock().unwrap() = Some(SpawnedTask::spawn_blocking(gc));
+        } else {
+            gc();
+        }
+    }
+}
+
+pub(crate) fn completed_checkpoints(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut checkpoints = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(epoch) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(CHECKPOINT_PREFIX))
+            .and_then(|epoch| epoch.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        if path.join(COMPLETED_MARKER).exists() {
+            checkpoints.push((epoch, path));
+        }
+    }
+    checkpoints.sort();
+    Ok(checkpoints)
+}
+
+fn collect_garbage(dir: &Path, keep_last: usize) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let orphaned = name.ends_with(IN_PROGRESS_SUFFIX)
+            || (name.starts_with(CHECKPOINT_PREFIX) && !path.join(COMPLETED_MARKER).exists());
+        if orphaned {
+            debug!("Deleting incomplete checkpoint {}", path.display());
+            fs::remove_dir_all(&path)?;
+        }
+    }
+
+    let checkpoints = completed_checkpoints(dir)?;
+    let expired = checkpoints.len().saturating_sub(keep_last);
+    for (_, path) in &checkpoints[..expired] {
+        debug!("Deleting expired checkpoint {}", path.display());
+        fs::remove_dir_all(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_latest_checkpoints_and_savepoints() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("denormalized-gc-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for name in ["chk-1", "chk-2", "chk-3", "sp-before-upgrade"] {
+            fs::create_dir_all(dir.join(name))?;
+            fs::write(dir.join(name).join(COMPLETED_MARKER), b"")?;
+        }
+        fs::create_dir_all(dir.join("chk-4"))?;
+        fs::create_dir_all(dir.join("chk-5.inprogress"))?;
+
+        collect_garbage(&dir, 2)?;
+
+        let mut remaining = fs::read_dir(&dir)?
+            .map(|entry| Ok(entry?.file_name().into_string().unwrap()))
+            .collect::<io::Result<Vec<_>>>()?;
+        remaining.sort();
+        assert_eq!(remaining, vec!["chk-2", "chk-3", "sp-before-upgrade"]);
+
+        fs::remove_dir_all(&dir)
+    }
+
+    #[test]
+    fn retain_checkpoints_of_the_backend() -> Result<()> {
+        use crate::state_backend::backend::StateBackend;
+
+        let root = std::env::temp_dir().join(format!("denormalized-chk-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let backend = RocksDBBackend::open(&root.join("db"))?
+            .with_checkpoint_store(CheckpointStore::new(root.join("checkpoints"), 2)?);
+        backend.ensure_namespace("offsets")?;
+        for epoch in 1..=3 {
+            backend.put_state("offsets", b"0".to_vec(), vec![epoch as u8])?;
+            // Outside a runtime the expired checkpoints are deleted right away
+            futures::executor::block_on(backend.checkpoint(epoch))?;
+        }
+        // Taken by another source closing the same epoch
+        futures::executor::block_on(backend.checkpoint(3))?;
+        assert_eq!(
+            futures::executor::block_on(backend.list_checkpoints())?,
+            [2, 3]
+        );
+        // Sources closing an epoch at the same time all succeed
+        std::thread::scope(|scope| {
+            let closing: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| futures::executor::block_on(backend.checkpoint(4))))
+                .collect();
+            closing
+                .into_iter()
+                .try_for_each(|source| source.join().unwrap())
+        })?;
+        assert_eq!(
+            futures::executor::block_on(backend.list_checkpoints())?,
+            [3, 4]
+        );
+
+        let latest =
+            RocksDBBackend::open(&root.join("checkpoints").join(format!("chk-{:020}", 4)))?;
+        assert_eq!(latest.get_state("offsets", b"0".to_vec())?, Some(vec![3]));
+
+        drop(latest);
+        drop(backend);
+        Ok(fs::remove_dir_all(&root)?)
+    }
+}
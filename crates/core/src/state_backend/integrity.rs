@@ -0,0 +1,327 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use datafusion::common::{DataFusionError, Result};
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::utils::secrets::SecretsProvider;
+
+const MAGIC: &[u8; 4] = b"DNST";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 1 + 4 + 8;
+
+const FLAG_ZSTD: u8 = 0b01;
+const FLAG_ENCRYPTED: u8 = 0b10;
+const NONCE_LEN: usize = 12;
+
+/// What to do with a state entry that fails verification when it is read back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CorruptStatePolicy {
+    /// Return an error, the job has to be restored from elsewhere
+    #[default]
+    Fail,
+    /// Treat the entry as missing but leave it in place for inspection
+    Skip,
+    /// Treat the entry as missing and delete it
+    Repair,
+}
+
+impl FromStr for CorruptStatePolicy {
+    type Err = DataFusionError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            "repair" => Ok(Self::Repair),
+            other => Err(DataFusionError::Configuration(format!(
+                "Unknown corrupt state policy '{other}', expected fail, skip or repair"
+            ))),
+        }
+    }
+}
+
+/// How state entries are framed on disk. Every entry carries a CRC32 of its payload and its
+/// length, so bit flips and truncated writes are caught on restore.
+///
+/// With an encryption key, payloads are additionally sealed with AES-256-GCM after
+/// compression, using a fresh random nonce per entry. The entry's key is bound to the
+/// ciphertext as associated data, so sealed values can't be moved between entries.
+#[derive(Clone)]
+pub struct StateEncoding {
+    /// zstd level used for new entries, `None` stores them uncompressed
+    pub compression_level: Option<i32>,
+    pub on_corruption: CorruptStatePolicy,
+    cipher: Option<Arc<Aes256Gcm>>,
+}
+
+impl std::fmt::Debug for StateEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StateEncoding")
+            .field("compression_level", &self.compression_level)
+            .field("on_corruption", &self.on_corruption)
+            .field("encrypted", &self.cipher.is_some())
+            .finish()
+    }
+}
+
+impl Default for StateEncoding {
+    fn default() -> Self {
+        Self {
+            compression_level: Some(3),
+            on_corruption: CorruptStatePolicy::Fail,
+            cipher: None,
+        }
+    }
+}
+
+impl StateEncoding {
+    /// Secrets are only looked up when `checkpoint_encryption_key` names one
+    pub fn from_config(config: &DenormalizedConfig, secrets: &dyn SecretsProvider) -> Result<Self> {
+        let encoding = Self {
+            compression_level: (config.checkpoint_compression_level != 0)
+                .then_some(config.checkpoint_compression_level),
+            on_corruption: config.checkpoint_corruption_policy.parse()?,
+            cipher: None,
+        };
+        if config.checkpoint_encryption_key.is_empty() {
+            return Ok(encoding);
+        }
+        let key = secrets
+            .get_secret(&config.checkpoint_encryption_key)?
+            .ok_or_else(|| {
+                DataFusionError::Configuration(format!(
+                    "Checkpoint encryption key {} not found",
+                    config.checkpoint_encryption_key
+                ))
+            })?;
+        encoding.with_encryption_key(&key)
+    }
+
+    /// Encrypt new entries with a 256 bit key. Once a key is set, entries that aren't
+    /// encrypted are rejected instead of being trusted as plaintext.
+    pub fn with_encryption_key(mut self, key: &[u8]) -> Result<Self> {
+        if key.len() != 32 {
+            return Err(DataFusionError::Configuration(format!(
+                "Checkpoint encryption keys must be 32 bytes, got {}",
+                key.len()
+            )));
+        }
+        let key = Key::<Aes256Gcm>::from_slice(key);
+        self.cipher = Some(Arc::new(Aes256Gcm::new(key)));
+        Ok(self)
+    }
+
+    /// Frame `value` for storage under `key`, the fully namespaced key of the entry
+    pub fn encode(&self, key: &[u8], value: &[u8]) -> Result<Vec<u8>> {
+        let mut flags = 0;
+        let mut payload = match self.compression_level {
+            Some(level) => {
+                flags |= FLAG_ZSTD;
+                zstd::encode_all(value, level)?
+            }
+            None => value.to_vec(),
+        };
+        if let Some(cipher) = &self.cipher {
+            flags |= FLAG_ENCRYPTED;
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let sealed = cipher
+                .encrypt(
+                    &nonce,
+                    Payload {
+                        msg: &payload,
+                        aad: key,
+                    },
+                )
+                .map_err(|_| {
+                    DataFusionError::Internal("Failed to encrypt state entry".to_string())
+                })?;
+            payload = nonce.to_vec();
+            payload.extend_from_slice(&sealed);
+        }
+
+        let mut encoded = Vec::with_capacity(HEADER_LEN + payload.len());
+        encoded.extend_from_slice(MAGIC);
+        encoded.push(VERSION);
+        encoded.push(flags);
+        encoded.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        encoded.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        encoded.extend_from_slice(&payload);
+        Ok(encoded)
+    }
+
+    /// Verify and unpack an entry written by [`Self::encode`] under the same `key`. Entries
+    /// without the header were written before state was framed and are returned as they are,
+    /// unless an encryption key is set. Encrypted entries that don't authenticate with the
+    /// configured key are reported as corrupt, a missing key is a configuration error.
+    pub fn decode(&self, key: &[u8], encoded: &[u8]) -> Result<Vec<u8>> {
+        if !encoded.starts_with(MAGIC) {
+            if self.cipher.is_some() {
+                return corrupt("missing header on an unencrypted entry");
+            }
+            return Ok(encoded.to_vec());
+        }
+        if encoded.len() < HEADER_LEN {
+            return corrupt("truncated header");
+        }
+        if encoded[4] != VERSION {
+            return corrupt(&format!("unsupported version {}", encoded[4]));
+        }
+        let flags = encoded[5];
+        let checksum = u32::from_le_bytes(encoded[6..10].try_into().unwrap());
+        let length = u64::from_le_bytes(encoded[10..18].try_into().unwrap()) as usize;
+
+        let payload = &encoded[HEADER_LEN..];
+        if payload.len() != length {
+            return corrupt(&format!(
+                "expected {length} bytes of payload, found {}",
+                payload.len()
+            ));
+        }
+        if crc32fast::hash(payload) != checksum {
+            return corrupt("checksum mismatch");
+        }
+
+        if flags & !(FLAG_ZSTD | FLAG_ENCRYPTED) != 0 {
+            return corrupt(&format!("unknown flags {flags:#04b}"));
+        }
+
+        let decrypted;
+        let payload = if flags & FLAG_ENCRYPTED != 0 {
+            let Some(cipher) = &self.cipher else {
+                return Err(DataFusionError::Configuration(
+                    "State entry is encrypted but no checkpoint encryption key is set".to_string(),
+                ));
+            };
+            if payload.len() < NONCE_LEN {
+                return corrupt("encrypted payload too short");
+            }
+            let (nonce, sealed) = payload.split_at(NONCE_LEN);
+            let sealed = Payload {
+                msg: sealed,
+                aad: key,
+            };
+            decrypted = match cipher.decrypt(Nonce::from_slice(nonce), sealed) {
+                Ok(decrypted) => decrypted,
+                Err(_) => return corrupt("failed to authenticate encrypted payload"),
+            };
+            decrypted.as_slice()
+        } else if self.cipher.is_some() {
+            return corrupt("entry is not encrypted but an encryption key is set");
+        } else {
+            payload
+        };
+
+        if flags & FLAG_ZSTD != 0 {
+            Ok(zstd::decode_all(payload)?)
+        } else {
+            Ok(payload.to_vec())
+        }
+    }
+}
+
+fn corrupt<T>(reason: &str) -> Result<T> {
+    Err(DataFusionError::Internal(format!(
+        "Corrupt state entry: {reason}"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_and_detect_corruption() {
+        let value = b"offsets for partition 0".repeat(10);
+        for compression_level in [None, Some(3)] {
+            let encoding = StateEncoding {
+                compression_level,
+                ..Default::default()
+            };
+            let encoded = encoding.encode(b"offsets:0", &value).unwrap();
+            assert_eq!(encoding.decode(b"offsets:0", &encoded).unwrap(), value);
+
+            let mut flipped = encoded.clone();
+            let last = flipped.len() - 1;
+            flipped[last] ^= 0x01;
+            assert!(encoding.decode(b"offsets:0", &flipped).is_err());
+
+            assert!(encoding
+                .decode(b"offsets:0", &encoded[..encoded.len() - 2])
+                .is_err());
+        }
+        // Written before entries had a header
+        assert_eq!(
+            StateEncoding::default()
+                .decode(b"offsets:0", &value)
+                .unwrap(),
+            value
+        );
+    }
+
+    #[derive(Debug)]
+    struct Secrets;
+
+    impl SecretsProvider for Secrets {
+        fn get_secret(&self, name: &str) -> Result<Option<Vec<u8>>> {
+            Ok((name == "state-key").then(|| vec![7; 32]))
+        }
+    }
+
+    #[test]
+    fn encoding_from_config() {
+        let mut config = DenormalizedConfig {
+            checkpoint_compression_level: 0,
+            checkpoint_corruption_policy: "Repair".to_string(),
+            ..Default::default()
+        };
+        let encoding = StateEncoding::from_config(&config, &Secrets).unwrap();
+        assert_eq!(encoding.compression_level, None);
+        assert_eq!(encoding.on_corruption, CorruptStatePolicy::Repair);
+        assert!(encoding.cipher.is_none());
+
+        config.checkpoint_encryption_key = "state-key".to_string();
+        let encoded = StateEncoding::from_config(&config, &Secrets)
+            .unwrap()
+            .encode(b"offsets:0", b"offsets")
+            .unwrap();
+        let keyed = StateEncoding::default()
+            .with_encryption_key(&[7; 32])
+            .unwrap();
+        assert_eq!(keyed.decode(b"offsets:0", &encoded).unwrap(), b"offsets");
+
+        config.checkpoint_encryption_key = "missing".to_string();
+        assert!(StateEncoding::from_config(&config, &Secrets).is_err());
+    }
+
+    #[test]
+    fn encrypted_roundtrip() {
+        let value = b"customer@example.com".to_vec();
+        let encoding = StateEncoding::default()
+            .with_encryption_key(&[7; 32])
+            .unwrap();
+        let encoded = encoding.encode(b"users:1", &value).unwrap();
+        assert!(!encoded
+            .windows(value.len())
+            .any(|window| window == value.as_slice()));
+        assert_eq!(encoding.decode(b"users:1", &encoded).unwrap(), value);
+        // Sealed values are bound to the key they were written under
+        assert!(encoding.decode(b"users:2", &encoded).is_err());
+
+        let other_key = StateEncoding::default()
+            .with_encryption_key(&[8; 32])
+            .unwrap();
+        assert!(other_key.decode(b"users:1", &encoded).is_err());
+        assert!(matches!(
+            StateEncoding::default().decode(b"users:1", &encoded),
+            Err(DataFusionError::Configuration(_))
+        ));
+
+        // Plaintext can't be slipped in once a key is set
+        let plain = StateEncoding::default().encode(b"users:1", &value).unwrap();
+        assert!(encoding.decode(b"users:1", &plain).is_err());
+        assert!(encoding.decode(b"users:1", &value).is_err());
+    }
+}
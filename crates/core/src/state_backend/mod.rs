@@ -1 +1,6 @@
+pub mod backend;
+pub mod checkpoints;
+pub mod integrity;
+pub mod object_store_backend;
 pub mod rocksdb_backend;
+pub mod standby;
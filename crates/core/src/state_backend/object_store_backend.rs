@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::common::{DataFusionError, Result};
+use futures::TryStreamExt;
+use log::{debug, info};
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore, PutPayload};
+
+use super::backend::StateBackend;
+use super::checkpoints::{CHECKPOINT_PREFIX, COMPLETED_MARKER, IN_PROGRESS_SUFFIX};
+use super::integrity::StateEncoding;
+use super::rocksdb_backend::RocksDBBackend;
+
+/// State kept in a local RocksDB and checkpointed to an object store such as S3, so that a job
+/// on an ephemeral container resumes wherever it is restarted.
+///
+/// Checkpoints are laid out like those of a
+/// [`CheckpointStore`](super::checkpoints::CheckpointStore), one `chk-<epoch>` prefix per
+/// epoch holding the RocksDB files, with the completion marker uploaded last.
+pub struct ObjectStoreBackend {
+    local: RocksDBBackend,
+    local_dir: PathBuf,
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl ObjectStoreBackend {
+    /// Restore the latest completed checkpoint under `prefix` into `local_dir`, or start with
+    /// empty state if there is none. Whatever `local_dir` held before is deleted.
+    pub async fn open(
+        store: Arc<dyn ObjectStore>,
+        prefix: Path,
+        local_dir: impl Into<PathBuf>,
+    ) -> Result<Self> {
+        let local_dir = local_dir.into();
+        if local_dir.exists() {
+            fs::remove_dir_all(&local_dir)?;
+        }
+        let db_dir = local_dir.join("db");
+        fs::create_dir_all(&local_dir)?;
+
+        if let Some(epoch) = completed_epochs(store.as_ref(), &prefix).await?.pop() {
+            fs::create_dir_all(&db_dir)?;
+            let checkpoint = checkpoint_prefix(&prefix, epoch);
+            let objects: Vec<ObjectMeta> = store.list(Some(&checkpoint)).try_collect().await?;
+            for object in objects {
+                let Some(name) = object.location.filename() else {
+                    continue;
+                };
+                if name == COMPLETED_MARKER {
+                    continue;
+                }
+                let bytes = store.get(&object.location).await?.bytes().await?;
+                fs::write(db_dir.join(name), bytes)?;
+            }
+            info!("Restored state from checkpoint {epoch} in {prefix}");
+        }
+
+        Ok(Self {
+            local: RocksDBBackend::open(&db_dir)?,
+            local_dir,
+            store,
+            prefix,
+        })
+    }
+
+    pub fn with_encoding(mut self, encoding: StateEncoding) -> Self {
+        self.local = self.local.with_encoding(encoding);
+        self
+    }
+}
+
+#[async_trait]
+impl StateBackend for ObjectStoreBackend {
+    fn ensure_namespace(&self, namespace: &str) -> Result<()> {
+        StateBackend::ensure_namespace(&self.local, namespace)
+    }
+
+    fn get_state(&self, namespace: &str, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
+        self.local.get_state(namespace, key)
+    }
+
+    fn put_state(&self, namespace: &str, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+        self.local.put_state(namespace, key, value)
+    }
+
+    fn delete_state(&self, namespace: &str, key: Vec<u8>) -> Result<()> {
+        self.local.delete_state(namespace, key)
+    }
+
+    fn state_size(&self, namespace: &str) -> Result<u64> {
+        self.local.state_size(namespace)
+    }
+
+    async fn checkpoint(&self, epoch: u64) -> Result<()> {
+        let name = format!("{CHECKPOINT_PREFIX}{epoch:020}");
+        let staging = self.local_dir.join(format!("{name}{IN_PROGRESS_SUFFIX}"));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        self.local.create_checkpoint(&staging)?;
+
+        let target = checkpoint_prefix(&self.prefix, epoch);
+        let uploaded = async {
+            for entry in fs::read_dir(&staging)? {
+                let entry = entry?;
+                let bytes = fs::read(entry.path())?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                self.store
+                    .put(&target.child(name), PutPayload::from(bytes))
+                    .await?;
+            }
+            self.store
+                .put(&target.child(COMPLETED_MARKER), PutPayload::new())
+                .await?;
+            Ok::<_, DataFusionError>(())
+        }
+        .await;
+        fs::remove_dir_all(&staging)?;
+        uploaded?;
+        debug!("Uploaded checkpoint {epoch} to {target}");
+        Ok(())
+    }
+
+    async fn list_checkpoints(&self) -> Result<Vec<u64>> {
+        completed_epochs(self.store.as_ref(), &self.prefix).await
+    }
+
+    async fn prune_checkpoints(&self, epoch: u64) -> Result<Vec<u64>> {
+        let objects: Vec<ObjectMeta> = self.store.list(Some(&self.prefix)).try_collect().await?;
+        let (markers, files): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .filter_map(|object| {
+                let (checkpoint, name) = checkpoint_object(&self.prefix, &object.location)?;
+                (checkpoint < epoch).then_some((checkpoint, name == COMPLETED_MARKER, object))
+            })
+            .partition(|(_, marker, _)| *marker);
+
+        // Markers go first, a checkpoint missing some of its files is never listed as complete
+        let mut pruned = vec![];
+        for (checkpoint, _, object) in markers {
+            self.store.delete(&object.location).await?;
+            pruned.push(checkpoint);
+        }
+        for (_, _, object) in files {
+            self.store.delete(&object.location).await?;
+        }
+        pruned.sort();
+        Ok(pruned)
+    }
+}
+
+fn checkpoint_prefix(prefix: &Path, epoch: u64) -> Path {
+    prefix.child(format!("{CHECKPOINT_PREFIX}{epoch:020}"))
+}
+
+/// Epoch of the checkpoint `location` belongs to and its file name
+fn checkpoint_object(prefix: &Path, location: &Path) -> Option<(u64, String)> {
+    let mut parts = location.prefix_match(prefix)?;
+    let epoch = parts
+        .next()?
+        .as_ref()
+        .strip_prefix(CHECKPOINT_PREFIX)?
+        .parse()
+        .ok()?;
+    Some((epoch, parts.next()?.as_ref().to_string()))
+}
+
+async fn completed_epochs(store: &dyn ObjectStore, prefix: &Path) -> Result<Vec<u64>> {
+    let objects: Vec<ObjectMeta> = store.list(Some(prefix)).try_collect().await?;
+    let mut epochs = objects
+        .iter()
+        .filter_map(|object| checkpoint_object(prefix, &object.location))
+        .filter(|(_, name)| name == COMPLETED_MARKER)
+        .map(|(epoch, _)| epoch)
+        .collect::<Vec<_>>();
+    epochs.sort();
+    Ok(epochs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use object_store::local::LocalFileSystem;
+
+    #[tokio::test]
+    async fn restore_from_remote_checkpoints() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("object_store_state_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("remote"))?;
+        let store: Arc<dyn ObjectStore> =
+            Arc::new(LocalFileSystem::new_with_prefix(root.join("remote"))?);
+        let prefix = Path::from("jobs/orders");
+
+        let backend =
+            ObjectStoreBackend::open(store.clone(), prefix.clone(), root.join("a")).await?;
+        backend.ensure_namespace("offsets")?;
+        for epoch in 1..=3 {
+            backend.put_state("offsets", b"0".to_vec(), vec![epoch as u8])?;
+            backend.checkpoint(epoch).await?;
+        }
+        assert_eq!(backend.list_checkpoints().await?, [1, 2, 3]);
+        assert_eq!(backend.retain_checkpoints(2).await?, [1]);
+        assert_eq!(backend.list_checkpoints().await?, [2, 3]);
+
+        // A new container starts from what was uploaded last
+        let restored = ObjectStoreBackend::open(store, prefix, root.join("b")).await?;
+        assert_eq!(restored.get_state("offsets", b"0".to_vec())?, Some(vec![3]));
+
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -1,37 +1,62 @@
 use std::{
     env,
+    path::Path,
     sync::{Arc, OnceLock},
 };
 
+use async_trait::async_trait;
 use datafusion::common::DataFusionError;
-use log::debug;
+use log::{debug, info, warn};
 use rocksdb::{
-    BoundColumnFamily, ColumnFamilyDescriptor, DBWithThreadMode, MultiThreaded, Options, DB,
+    checkpoint::Checkpoint, BoundColumnFamily, ColumnFamilyDescriptor, DBWithThreadMode,
+    IteratorMode, MultiThreaded, Options, WriteBatch, DB,
 };
 
+use super::backend::StateBackend;
+use super::checkpoints::CheckpointStore;
+use super::integrity::{CorruptStatePolicy, StateEncoding};
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+use crate::utils::job::JobIdentity;
+use crate::utils::secrets::SecretsProvider;
+
 pub struct RocksDBBackend {
     db: DBWithThreadMode<MultiThreaded>,
+    encoding: StateEncoding,
+    checkpoints: Option<CheckpointStore>,
 }
 
 impl RocksDBBackend {
     pub fn new(path: &str) -> Result<Self, DataFusionError> {
         let dir = env::temp_dir();
         let db_path = format!("{}{}", dir.display(), path);
-        debug!("Opening rocksdb at {}", db_path);
+        Self::open(Path::new(&db_path))
+    }
+
+    /// Open the database at `db_path` as is, e.g. a checkpoint kept warm by a standby
+    pub fn open(db_path: &Path) -> Result<Self, DataFusionError> {
+        debug!("Opening rocksdb at {}", db_path.display());
 
         let mut db_opts = Options::default();
         db_opts.create_if_missing(true);
 
-        // List all column families in the existing database
-        let cf_names = DB::list_cf(&db_opts, &db_path).map_err(|e| {
-            DataFusionError::Internal(format!("Failed to list column families: {}", e))
-        })?;
+        // List all column families in the existing database, a new one has none to list
+        let cf_names = if db_path.join("CURRENT").exists() {
+            DB::list_cf(&db_opts, db_path).map_err(|e| {
+                DataFusionError::Internal(format!("Failed to list column families: {}", e))
+            })?
+        } else {
+            vec![]
+        };
 
         if cf_names.is_empty() {
             // If no column families, open the DB normally
-            let db = DBWithThreadMode::<MultiThreaded>::open(&db_opts, &db_path)
+            let db = DBWithThreadMode::<MultiThreaded>::open(&db_opts, db_path)
                 .map_err(|e| DataFusionError::Internal(format!("Failed to open RocksDB: {}", e)))?;
-            Ok(RocksDBBackend { db })
+            Ok(RocksDBBackend {
+                db,
+                encoding: StateEncoding::default(),
+                checkpoints: None,
+            })
         } else {
             // If column families exist, open the DB with all existing column families
             let cf_descriptors: Vec<ColumnFamilyDescriptor> = cf_names
@@ -41,7 +66,7 @@ impl RocksDBBackend {
 
             let db = DBWithThreadMode::<MultiThreaded>::open_cf_descriptors(
                 &db_opts,
-                &db_path,
+                db_path,
                 cf_descriptors,
             )
             .map_err(|e| {
@@ -50,10 +75,31 @@ impl RocksDBBackend {
                     e
                 ))
             })?;
-            Ok(RocksDBBackend { db })
+            Ok(RocksDBBackend {
+                db,
+                encoding: StateEncoding::default(),
+                checkpoints: None,
+            })
         }
     }
 
+    pub fn with_encoding(mut self, encoding: StateEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Where [`StateBackend::checkpoint`] copies the database to
+    pub fn with_checkpoint_store(mut self, checkpoints: CheckpointStore) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    fn checkpoint_store(&self) -> Result<&CheckpointStore, DataFusionError> {
+        self.checkpoints.as_ref().ok_or_else(|| {
+            DataFusionError::Configuration("No checkpoint directory for RocksDB".to_string())
+        })
+    }
+
     pub fn create_cf(&self, namespace: &str) -> Result<(), DataFusionError> {
         let cf_opts: Options = Options::default();
         DBWithThreadMode::<MultiThreaded>::create_cf(&self.db, namespace, &cf_opts)
@@ -75,11 +121,35 @@ impl RocksDBBackend {
         nk
     }
 
+    /// Consistent copy of the database at `path`, hard linking the immutable files
+    pub fn create_checkpoint(&self, path: &Path) -> Result<(), DataFusionError> {
+        Checkpoint::new(&self.db)
+            .and_then(|checkpoint| checkpoint.create_checkpoint(path))
+            .map_err(|e| DataFusionError::Internal(format!("Failed to create checkpoint: {e}")))
+    }
+
     pub fn destroy(&self) -> Result<(), DataFusionError> {
         let _ret = DB::destroy(&Options::default(), self.db.path());
         Ok(())
     }
 
+    /// Estimated bytes held by `namespace`, on disk and in memtables
+    pub fn state_size(&self, namespace: &str) -> Result<u64, DataFusionError> {
+        let cf = self.get_cf(namespace)?;
+        let mut size = 0;
+        for property in [
+            "rocksdb.estimate-live-data-size",
+            "rocksdb.cur-size-all-mem-tables",
+        ] {
+            size += self
+                .db
+                .property_int_value_cf(&cf, property)
+                .map_err(|e| DataFusionError::Internal(e.to_string()))?
+                .unwrap_or(0);
+        }
+        Ok(size)
+    }
+
     pub fn put_state(
         &self,
         namespace: &str,
@@ -93,6 +163,7 @@ impl RocksDBBackend {
         // }
         let cf: Arc<BoundColumnFamily> = self.get_cf(namespace)?;
         let namespaced_key: Vec<u8> = self.namespaced_key(namespace, &key);
+        let value = self.encoding.encode(&namespaced_key, &value)?;
         self.db
             .put_cf(&cf, namespaced_key, value)
             .map_err(|e| DataFusionError::Internal(e.to_string()))
@@ -106,17 +177,69 @@ impl RocksDBBackend {
         let cf: Arc<BoundColumnFamily> = self.get_cf(namespace)?;
         let namespaced_key: Vec<u8> = self.namespaced_key(namespace, &key);
 
-        match self
+        let Some(serialized_value) = self
             .db
-            .get_cf(&cf, namespaced_key)
+            .get_cf(&cf, &namespaced_key)
             .map_err(|e| DataFusionError::Internal(e.to_string()))?
-        {
-            Some(serialized_value) => Ok(Some(serialized_value)),
-            None => Ok(None),
+        else {
+            return Ok(None);
+        };
+
+        match self.encoding.decode(&namespaced_key, &serialized_value) {
+            Ok(value) => Ok(Some(value)),
+            Err(e @ DataFusionError::Configuration(_)) => Err(e),
+            Err(e) => match self.encoding.on_corruption {
+                CorruptStatePolicy::Fail => Err(e),
+                CorruptStatePolicy::Skip => {
+                    warn!("Ignoring state for {namespace}: {e}");
+                    Ok(None)
+                }
+                CorruptStatePolicy::Repair => {
+                    warn!("Deleting state for {namespace}: {e}");
+                    self.db
+                        .delete_cf(&cf, namespaced_key)
+                        .map_err(|e| DataFusionError::Internal(e.to_string()))?;
+                    Ok(None)
+                }
+            },
+        }
+    }
+
+    /// Delete all entries matched by one of the rules and return how many were deleted.
+    /// Meant to run after a restore and before the job reads any state. Entries that fail
+    /// to decode are left for [`Self::get_state`] to deal with.
+    pub fn prune_state(&self, rules: &[StatePruneRule]) -> Result<usize, DataFusionError> {
+        let mut pruned = 0;
+        for rule in rules {
+            let Ok(cf) = self.get_cf(&rule.namespace) else {
+                continue;
+            };
+            let prefix_len = rule.namespace.len() + 1;
+            let mut batch = WriteBatch::default();
+            for entry in self.db.iterator_cf(&cf, IteratorMode::Start) {
+                let (key, value) = entry.map_err(|e| DataFusionError::Internal(e.to_string()))?;
+                let Some(state_key) = key.get(prefix_len..) else {
+                    continue;
+                };
+                let Ok(value) = self.encoding.decode(&key, &value) else {
+                    continue;
+                };
+                if rule.matches(state_key, &value) {
+                    batch.delete_cf(&cf, &key);
+                    pruned += 1;
+                }
+            }
+            self.db
+                .write(batch)
+                .map_err(|e| DataFusionError::Internal(e.to_string()))?;
         }
+        if pruned > 0 {
+            info!("Pruned {pruned} state entries on restore");
+        }
+        Ok(pruned)
     }
 
-    pub async fn delete_state(&self, namespace: &str, key: Vec<u8>) -> Result<(), DataFusionError> {
+    pub fn delete_state(&self, namespace: &str, key: Vec<u8>) -> Result<(), DataFusionError> {
         let cf: Arc<BoundColumnFamily> = self.get_cf(namespace)?;
         let namespaced_key: Vec<u8> = self.namespaced_key(namespace, &key);
 
@@ -127,17 +250,193 @@ impl RocksDBBackend {
     }
 }
 
+#[async_trait]
+impl StateBackend for RocksDBBackend {
+    fn ensure_namespace(&self, namespace: &str) -> Result<(), DataFusionError> {
+        if self.get_cf(namespace).is_err() {
+            self.create_cf(namespace)?;
+        }
+        Ok(())
+    }
+
+    fn get_state(&self, namespace: &str, key: Vec<u8>) -> Result<Option<Vec<u8>>, DataFusionError> {
+        RocksDBBackend::get_state(self, namespace, key)
+    }
+
+    fn put_state(
+        &self,
+        namespace: &str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<(), DataFusionError> {
+        RocksDBBackend::put_state(self, namespace, key, value)
+    }
+
+    fn delete_state(&self, namespace: &str, key: Vec<u8>) -> Result<(), DataFusionError> {
+        RocksDBBackend::delete_state(self, namespace, key)
+    }
+
+    fn state_size(&self, namespace: &str) -> Result<u64, DataFusionError> {
+        RocksDBBackend::state_size(self, namespace)
+    }
+
+    async fn checkpoint(&self, epoch: u64) -> Result<(), DataFusionError> {
+        self.checkpoint_store()?.checkpoint(self, epoch)?;
+        Ok(())
+    }
+
+    async fn list_checkpoints(&self) -> Result<Vec<u64>, DataFusionError> {
+        let checkpoints = self.checkpoint_store()?.checkpoints()?;
+        Ok(checkpoints.into_iter().map(|(epoch, _)| epoch).collect())
+    }
+
+    async fn prune_checkpoints(&self, epoch: u64) -> Result<Vec<u64>, DataFusionError> {
+        self.checkpoint_store()?.prune_before(epoch)
+    }
+}
+
+/// Selects state entries to drop when a job is restored, e.g. everything belonging to a
+/// tenant being offboarded. The predicate sees the key without its namespace and the decoded
+/// value, and returns true for entries that should be deleted.
+#[derive(Clone)]
+pub struct StatePruneRule {
+    pub namespace: String,
+    predicate: Arc<dyn Fn(&[u8], &[u8]) -> bool + Send + Sync>,
+}
+
+impl StatePruneRule {
+    pub fn new(
+        namespace: impl Into<String>,
+        predicate: impl Fn(&[u8], &[u8]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            namespace: namespace.into(),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    /// Drop every entry whose key starts with `prefix`
+    pub fn key_prefix(namespace: impl Into<String>, prefix: impl Into<Vec<u8>>) -> Self {
+        let prefix = prefix.into();
+        Self::new(namespace, move |key, _| key.starts_with(&prefix))
+    }
+
+    /// Key prefix rules in the `namespace=prefix,...` form of the `restore_prune_prefixes`
+    /// option
+    pub fn parse_key_prefixes(rules: &str) -> Result<Vec<Self>, DataFusionError> {
+        rules
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| match rule.split_once('=') {
+                Some((namespace, prefix)) if !namespace.trim().is_empty() => {
+                    Ok(Self::key_prefix(namespace.trim(), prefix.trim().as_bytes()))
+                }
+                _ => Err(DataFusionError::Configuration(format!(
+                    "Invalid state prune rule '{rule}', expected namespace=prefix"
+                ))),
+            })
+            .collect()
+    }
+
+    pub fn matches(&self, key: &[u8], value: &[u8]) -> bool {
+        (self.predicate)(key, value)
+    }
+}
+
+impl std::fmt::Debug for StatePruneRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StatePruneRule")
+            .field("namespace", &self.namespace)
+            .finish_non_exhaustive()
+    }
+}
+
 static GLOBAL_ROCKSDB: OnceLock<Arc<RocksDBBackend>> = OnceLock::new();
 
-pub fn initialize_global_rocksdb(path: &str) -> Result<(), DataFusionError> {
-    let backend = RocksDBBackend::new(path)?;
+pub fn initialize_global_rocksdb(
+    path: &str,
+    encoding: StateEncoding,
+) -> Result<(), DataFusionError> {
+    let backend = RocksDBBackend::new(path)?.with_encoding(encoding);
+    GLOBAL_ROCKSDB.set(Arc::new(backend)).map_err(|_| {
+        DataFusionError::Internal("Global RocksDBBackend already initialized".to_string())
+    })
+}
+
+/// Like [`initialize_global_rocksdb`] but for a database at an absolute path, such as the
+/// one returned by [`super::standby::StandbyReplica::promote`]
+pub fn initialize_global_rocksdb_at(
+    path: &Path,
+    encoding: StateEncoding,
+) -> Result<(), DataFusionError> {
+    let backend = RocksDBBackend::open(path)?.with_encoding(encoding);
     GLOBAL_ROCKSDB.set(Arc::new(backend)).map_err(|_| {
         DataFusionError::Internal("Global RocksDBBackend already initialized".to_string())
     })
 }
 
+/// Open the global RocksDB of a job that checkpoints, encoding its state as the
+/// `checkpoint_*` options of `config` say and pruning it as `restore_prune_prefixes` says. The database lives under the temp directory and is
+/// named after the job, its checkpoints go to the job's directory under `checkpoint_dir` when
+/// one is set. Contexts created later in the process share the database opened by the first.
+pub fn initialize_global_rocksdb_from_config(
+    config: &DenormalizedConfig,
+    job: &JobIdentity,
+    secrets: &dyn SecretsProvider,
+) -> Result<(), DataFusionError> {
+    if GLOBAL_ROCKSDB.get().is_some() {
+        return Ok(());
+    }
+    let encoding = StateEncoding::from_config(config, secrets)?;
+    let prune_rules = StatePruneRule::parse_key_prefixes(&config.restore_prune_prefixes)?;
+    let path = env::temp_dir().join("denormalized").join(&job.job_id);
+    std::fs::create_dir_all(&path)?;
+    let mut backend = RocksDBBackend::open(&path)?.with_encoding(encoding);
+    if !config.checkpoint_dir.is_empty() {
+        let dir = job.checkpoint_dir(Path::new(&config.checkpoint_dir));
+        backend =
+            backend.with_checkpoint_store(CheckpointStore::new(dir, config.checkpoint_retain)?);
+    }
+    backend.prune_state(&prune_rules)?;
+    info!("Opened state of {job} at {}", path.display());
+    let _ = GLOBAL_ROCKSDB.set(Arc::new(backend));
+    Ok(())
+}
+
 pub fn get_global_rocksdb() -> Result<Arc<RocksDBBackend>, DataFusionError> {
     GLOBAL_ROCKSDB.get().cloned().ok_or_else(|| {
         DataFusionError::Internal("Global RocksDBBackend not initialized".to_string())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_matching_keys_on_restore() -> Result<(), DataFusionError> {
+        let path = env::temp_dir().join(format!("denormalized-prune-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let backend = RocksDBBackend::open(&path)?;
+        backend.create_cf("accounts")?;
+        for key in ["tenant-7/alice", "tenant-7/bob", "tenant-8/carol"] {
+            backend.put_state("accounts", key.as_bytes().to_vec(), b"1".to_vec())?;
+        }
+
+        let rules = StatePruneRule::parse_key_prefixes("accounts=tenant-7/, missing=x")?;
+        assert_eq!(backend.prune_state(&rules)?, 2);
+        assert_eq!(
+            backend.get_state("accounts", b"tenant-7/alice".to_vec())?,
+            None
+        );
+        assert_eq!(
+            backend.get_state("accounts", b"tenant-8/carol".to_vec())?,
+            Some(b"1".to_vec())
+        );
+        assert!(StatePruneRule::parse_key_prefixes("tenant-7/").is_err());
+
+        drop(backend);
+        Ok(std::fs::remove_dir_all(&path)?)
+    }
+}
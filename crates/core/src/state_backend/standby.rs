@@ -0,0 +1,194 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use datafusion::common::{DataFusionError, Result};
+use datafusion::common_runtime::SpawnedTask;
+use log::{debug, info, warn};
+
+use super::checkpoints::{
+    completed_checkpoints, CHECKPOINT_PREFIX, COMPLETED_MARKER, IN_PROGRESS_SUFFIX,
+};
+
+/// Keeps a local copy of the latest checkpoint of a primary, so that on failover the state
+/// only has to be opened instead of downloaded.
+///
+/// `source` is the checkpoint directory of the primary, typically on shared or mounted
+/// storage. Only files not already in the replica are copied: SST files never change once
+/// written, so those carried over from the previous checkpoint are hard linked instead.
+pub struct StandbyReplica {
+    source: PathBuf,
+    local: PathBuf,
+    latest: Arc<Mutex<Option<(u64, PathBuf)>>>,
+    poll_task: Option<SpawnedTask<()>>,
+}
+
+impl StandbyReplica {
+    pub fn new(source: impl Into<PathBuf>, local: impl Into<PathBuf>) -> Result<Self> {
+        let local = local.into();
+        fs::create_dir_all(&local)?;
+        let latest = completed_checkpoints(&local)?.pop();
+        Ok(Self {
+            source: source.into(),
+            local,
+            latest: Arc::new(Mutex::new(latest)),
+            poll_task: None,
+        })
+    }
+
+    /// Epoch and path of the replicated checkpoint
+    pub fn latest(&self) -> Option<(u64, PathBuf)> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Replicate the latest checkpoint of the primary if it is newer than the local one,
+    /// returning its epoch
+    pub fn sync(&self) -> Result<Option<u64>> {
+        Ok(sync(&self.source, &self.local, &self.latest)?)
+    }
+
+    /// Sync every `interval` in the background until the replica is promoted or dropped
+    pub fn start(&mut self, interval: Duration) {
+        let source = self.source.clone();
+        let local = self.local.clone();
+        let latest = Arc::clone(&self.latest);
+        self.poll_task = Some(SpawnedTask::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let (source, local, latest) = (source.clone(), local.clone(), latest.clone());
+                let synced =
+                    SpawnedTask::spawn_blocking(move || sync(&source, &local, &latest)).join();
+                match synced.await {
+                    Ok(Err(e)) => warn!("Failed to replicate checkpoint: {e}"),
+                    Err(e) => warn!("Checkpoint replication task failed: {e}"),
+                    Ok(Ok(_)) => {}
+                }
+            }
+        }));
+    }
+
+    /// Stop replicating and hand out the checkpoint to resume from, after a last sync in case
+    /// the primary completed one more checkpoint before it died. The returned directory can
+    /// be opened with [`super::rocksdb_backend::RocksDBBackend::open`].
+    pub fn promote(mut self) -> Result<PathBuf> {
+        drop(self.poll_task.take());
+        if let Err(e) = self.sync() {
+            warn!("Final sync before promotion failed, using the last replicated checkpoint: {e}");
+        }
+        match self.latest() {
+            Some((epoch, path)) => {
+                info!("Promoting standby at epoch {epoch}");
+                Ok(path)
+            }
+            None => Err(DataFusionError::Execution(format!(
+                "No checkpoint of {} has been replicated yet",
+                self.source.display()
+            ))),
+        }
+    }
+}
+
+fn sync(
+    source: &Path,
+    local: &Path,
+    latest: &Mutex<Option<(u64, PathBuf)>>,
+) -> io::Result<Option<u64>> {
+    let Some((epoch, checkpoint)) = completed_checkpoints(source)?.pop() else {
+        return Ok(None);
+    };
+    let previous = latest.lock().unwrap().clone();
+    if previous
+        .as_ref()
+        .is_some_and(|(replicated, _)| *replicated >= epoch)
+    {
+        return Ok(None);
+    }
+
+    let name = format!("{CHECKPOINT_PREFIX}{epoch:020}");
+    let target = local.join(&name);
+    let in_progress = local.join(format!("{name}{IN_PROGRESS_SUFFIX}"));
+    if in_progress.exists() {
+        fs::remove_dir_all(&in_progress)?;
+    }
+    fs::create_dir_all(&in_progress)?;
+
+    let (mut copied, mut linked) = (0, 0);
+    for entry in fs::read_dir(&checkpoint)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        if file_name == COMPLETED_MARKER {
+            continue;
+        }
+        let destination = in_progress.join(&file_name);
+        let reusable = previous
+            .as_ref()
+            .map(|(_, path)| path.join(&file_name))
+            .filter(|existing| {
+                existing.extension().is_some_and(|ext| ext == "sst")
+                    && fs::metadata(existing)
+                        .and_then(|existing| Ok(existing.len() == entry.metadata()?.len()))
+                        .unwrap_or(false)
+            });
+        match reusable {
+            Some(existing) if fs::hard_link(&existing, &destination).is_ok() => linked += 1,
+            _ => {
+                fs::copy(entry.path(), &destination)?;
+                copied += 1;
+            }
+        }
+    }
+    fs::write(in_progress.join(COMPLETED_MARKER), b"")?;
+    fs::rename(&in_progress, &target)?;
+    debug!("Replicated checkpoint {epoch}, copied {copied} files and reused {linked}");
+
+    *latest.lock().unwrap() = Some((epoch, target));
+    if let Some((_, previous)) = previous {
+        fs::remove_dir_all(previous)?;
+    }
+    Ok(Some(epoch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_checkpoint(dir: &Path, epoch: u64, files: &[(&str, &str)]) -> io::Result<()> {
+        let path = dir.join(format!("{CHECKPOINT_PREFIX}{epoch:020}"));
+        fs::create_dir_all(&path)?;
+        for (name, contents) in files {
+            fs::write(path.join(name), contents)?;
+        }
+        fs::write(path.join(COMPLETED_MARKER), b"")
+    }
+
+    #[test]
+    fn replicates_latest_checkpoint() -> Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("denormalized-standby-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let (primary, local) = (root.join("primary"), root.join("standby"));
+        write_checkpoint(&primary, 1, &[("000001.sst", "a"), ("MANIFEST", "1")])?;
+
+        let replica = StandbyReplica::new(&primary, &local)?;
+        assert_eq!(replica.sync()?, Some(1));
+        assert_eq!(replica.sync()?, None);
+
+        write_checkpoint(
+            &primary,
+            2,
+            &[("000001.sst", "a"), ("000002.sst", "b"), ("MANIFEST", "2")],
+        )?;
+        assert_eq!(replica.sync()?, Some(2));
+
+        let path = replica.promote()?;
+        assert_eq!(fs::read_to_string(path.join("000001.sst"))?, "a");
+        assert_eq!(fs::read_to_string(path.join("MANIFEST"))?, "2");
+        assert_eq!(completed_checkpoints(&local)?.len(), 1);
+
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -5,7 +5,10 @@ use std::{collections::HashMap, sync::Arc};
 use apache_avro::{types::Value, Error, Schema as AvSchema};
 use arrow::array::{Array, ListArray, StringBuilder, StructArray};
 use arrow::array::{BooleanBuilder, Float32Builder, Float64Builder, Int32Builder, Int64Builder};
+use arrow::array::{Date32Builder, Decimal128Array, TimestampMicrosecondArray};
+use arrow::compute::cast;
 use arrow::datatypes::Fields;
+use arrow::datatypes::TimeUnit;
 use arrow::error::ArrowError;
 use arrow::{
     array::{GenericListBuilder, ListBuilder},
@@ -16,6 +19,7 @@ use arrow::{
 };
 use arrow_json::ReaderBuilder;
 use base64::{engine::general_purpose, Engine as _};
+use datafusion::common::{exec_err, not_impl_err, plan_err, DataFusionError};
 use serde_json::{json, Value as JValue};
 
 //use crate::record::Record;
@@ -30,23 +34,36 @@ pub struct AvroSchema {
     pub schema: AvSchema,
 }
 
-/// .
-///
-/// # Panics
-///
-/// Panics if .
+/// A map of strings to nullable strings, the type `MapBuilder<StringBuilder, StringBuilder>`
+/// builds with its default field names. Sources use it for labels and attributes.
+pub fn string_map_type() -> DataType {
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", DataType::Utf8, true),
+    ]);
+    DataType::Map(
+        Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+        false,
+    )
+}
+
+/// Read JSON `records` into a batch of `schema`, failing when a record doesn't fit it
 pub fn json_records_to_arrow_record_batch(
-    records: Vec<serde_json::Value>,
+    records: &[serde_json::Value],
     schema: Arc<Schema>,
-) -> RecordBatch {
+) -> Result<RecordBatch, ArrowError> {
     if records.is_empty() {
-        return RecordBatch::new_empty(schema);
+        return Ok(RecordBatch::new_empty(schema));
     }
     let string_stream: Vec<String> = records.iter().map(|r| r.to_string()).collect();
     let cursor: Cursor<String> = Cursor::new(string_stream.join("\n"));
 
-    let mut reader = ReaderBuilder::new(schema).build(cursor).unwrap();
-    reader.next().unwrap().unwrap()
+    let mut reader = ReaderBuilder::new(schema.clone())
+        .with_batch_size(records.len())
+        .build(cursor)?;
+    reader
+        .next()
+        .unwrap_or_else(|| Ok(RecordBatch::new_empty(schema)))
 }
 
 pub fn avro_value_to_json(value: &Value) -> serde_json::Value {
@@ -128,8 +145,11 @@ pub fn avro_schema_to_json(schema: &AvSchema) -> Result<String, serde_json::Erro
     serde_json::to_string(schema)
 }
 
-fn infer_arrow_schema_from_avro_value(value: &Value, name: String) -> Field {
-    match value {
+fn infer_arrow_schema_from_avro_value(
+    value: &Value,
+    name: String,
+) -> datafusion::common::Result<Field> {
+    Ok(match value {
         Value::Null => Field::new(name, DataType::Null, false),
         Value::Boolean(_) => Field::new(name, DataType::Boolean, false),
         Value::Int(_) => Field::new(name, DataType::Int32, true),
@@ -141,7 +161,7 @@ fn infer_arrow_schema_from_avro_value(value: &Value, name: String) -> Field {
         Value::Array(items) => {
             if let Some(first_item) = items.first() {
                 let item_type =
-                    infer_arrow_schema_from_avro_value(first_item, format!("{}_item", name));
+                    infer_arrow_schema_from_avro_value(first_item, format!("{}_item", name))?;
                 Field::new(name, DataType::List(Arc::new(item_type)), true)
             } else {
                 Field::new(
@@ -158,25 +178,44 @@ fn infer_arrow_schema_from_avro_value(value: &Value, name: String) -> Field {
                 .map(|(field_name, value)| {
                     infer_arrow_schema_from_avro_value(value, String::from(field_name))
                 })
-                .collect();
+                .collect::<datafusion::common::Result<_>>()?;
             Field::new(name, DataType::Struct(schema_fields.into()), true)
         }
-        Value::Union(_, value) => infer_arrow_schema_from_avro_value(value, name),
-        Value::Enum(_, _) => todo!(),
-        Value::Date(_) => todo!(),
-        Value::Decimal(_) => todo!(),
-        Value::TimeMillis(_) => todo!(),
-        Value::TimeMicros(_) => todo!(),
-        Value::TimestampMillis(_) => todo!(),
-        Value::TimestampMicros(_) => todo!(),
-        Value::LocalTimestampMillis(_) => todo!(),
-        Value::LocalTimestampMicros(_) => todo!(),
-        Value::Duration(_) => todo!(),
-        Value::Uuid(_) => todo!(),
-    }
+        Value::Union(_, value) => return infer_arrow_schema_from_avro_value(value, name),
+        Value::Enum(_, _) => return not_impl_err!("Inferring the type of Avro enum {name}"),
+        Value::Date(_) => Field::new(name, DataType::Date32, true),
+        // Precision and scale are only part of the Avro schema, not of the value
+        Value::Decimal(_) => {
+            return plan_err!(
+                "The precision and scale of Avro decimal {name} can't be inferred from a value, \
+                 declare its type in the schema"
+            )
+        }
+        Value::TimeMillis(_) => Field::new(name, DataType::Time32(TimeUnit::Millisecond), true),
+        Value::TimeMicros(_) => Field::new(name, DataType::Time64(TimeUnit::Microsecond), true),
+        // Avro timestamps are instants in UTC, local timestamps carry no timezone
+        Value::TimestampMillis(_) => Field::new(
+            name,
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            true,
+        ),
+        Value::TimestampMicros(_) => Field::new(
+            name,
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            true,
+        ),
+        Value::LocalTimestampMillis(_) => {
+            Field::new(name, DataType::Timestamp(TimeUnit::Millisecond, None), true)
+        }
+        Value::LocalTimestampMicros(_) => {
+            Field::new(name, DataType::Timestamp(TimeUnit::Microsecond, None), true)
+        }
+        Value::Duration(_) => return not_impl_err!("Inferring the type of Avro duration {name}"),
+        Value::Uuid(_) => Field::new(name, DataType::Utf8, true),
+    })
 }
 
-fn avro_record_to_arrow_schema(record: &Value) -> Schema {
+fn avro_record_to_arrow_schema(record: &Value) -> datafusion::common::Result<Schema> {
     match record {
         Value::Record(fields) => {
             let schema_fields: Vec<Field> = fields
@@ -184,10 +223,10 @@ fn avro_record_to_arrow_schema(record: &Value) -> Schema {
                 .map(|(field_name, value)| {
                     infer_arrow_schema_from_avro_value(value, String::from(field_name))
                 })
-                .collect();
-            Schema::new(schema_fields)
+                .collect::<datafusion::common::Result<_>>()?;
+            Ok(Schema::new(schema_fields))
         }
-        _ => panic!("Excpected an avro record."),
+        _ => exec_err!("Expected an Avro record"),
     }
 }
 
@@ -203,7 +242,10 @@ fn strip_union_value(value: &Value) -> &Value {
  * fn make_struct_array recursively converts values from an Avro Struct Record
  * into a StructArray.
  */
-fn make_struct_array(fields: &Fields, values: &[Value]) -> Arc<StructArray> {
+fn make_struct_array(
+    fields: &Fields,
+    values: &[Value],
+) -> datafusion::common::Result<Arc<StructArray>> {
     let mut child_arrays: Vec<(Arc<Field>, Arc<dyn Array>)> = Vec::new();
     for field in fields {
         let target_name = field.name();
@@ -212,21 +254,19 @@ fn make_struct_array(fields: &Fields, values: &[Value]) -> Arc<StructArray> {
             .map(|v: &Value| {
                 let extracted_value = strip_union_value(v);
                 match extracted_value {
-                    Value::Record(r) => r
-                        .iter()
-                        .find(|(name, _)| name == target_name)
-                        .unwrap()
-                        .1
-                        .clone(),
-                    _ => panic!("Expected Avro Record"),
+                    Value::Record(r) => match r.iter().find(|(name, _)| name == target_name) {
+                        Some((_, value)) => Ok(value.clone()),
+                        None => exec_err!("Field {target_name} not found in Avro record"),
+                    },
+                    _ => exec_err!("Expected an Avro record for {target_name}"),
                 }
             })
-            .collect();
+            .collect::<datafusion::common::Result<_>>()?;
         let builder: Arc<dyn Array> =
-            avro_values_to_arrow_array(field_values.as_slice(), field.data_type());
+            avro_values_to_arrow_array(field_values.as_slice(), field.data_type())?;
         child_arrays.push((field.clone(), builder));
     }
-    Arc::new(StructArray::from(child_arrays))
+    Ok(Arc::new(StructArray::from(child_arrays)))
 }
 
 fn infer_arrow_schema_fields_from_json_value(value: &JValue, name: String) -> Field {
@@ -297,8 +337,11 @@ pub fn infer_arrow_schema_from_json_value(value: &JValue) -> Result<Schema, Arro
 /**
  *  Main entry point to Avro > Arrow conversion. Currently handles primitive types, lists and nested structs.
  */
-fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn Array> {
-    match data_type {
+fn avro_values_to_arrow_array(
+    values: &[Value],
+    data_type: &DataType,
+) -> datafusion::common::Result<Arc<dyn Array>> {
+    Ok(match data_type {
         DataType::Boolean => {
             let mut builder = BooleanBuilder::new();
             for value in values {
@@ -306,7 +349,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                 if let Value::Boolean(b) = extracted_value {
                     builder.append_value(*b);
                 } else {
-                    panic!("Type mismatch for Boolean");
+                    return exec_err!("Type mismatch for Boolean");
                 }
             }
             Arc::new(builder.finish())
@@ -318,7 +361,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                 if let Value::Int(i) = extracted_value {
                     builder.append_value(*i);
                 } else {
-                    panic!("Type mismatch for Int32");
+                    return exec_err!("Type mismatch for Int32");
                 }
             }
             Arc::new(builder.finish())
@@ -330,7 +373,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                 if let Value::Long(l) = extracted_value {
                     builder.append_value(*l);
                 } else {
-                    panic!("Type mismatch for Int64");
+                    return exec_err!("Type mismatch for Int64");
                 }
             }
             Arc::new(builder.finish())
@@ -342,7 +385,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                 if let Value::Float(f) = extracted_value {
                     builder.append_value(*f);
                 } else {
-                    panic!("Type mismatch for Float32");
+                    return exec_err!("Type mismatch for Float32");
                 }
             }
             Arc::new(builder.finish())
@@ -354,7 +397,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                 if let Value::Double(d) = extracted_value {
                     builder.append_value(*d);
                 } else {
-                    panic!("Type mismatch for Float64");
+                    return exec_err!("Type mismatch for Float64");
                 }
             }
             Arc::new(builder.finish())
@@ -366,12 +409,49 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                 if let Value::String(s) = extracted_value {
                     builder.append_value(s);
                 } else {
-                    panic!("Type mismatch for String");
+                    return exec_err!("Type mismatch for String");
                 }
             }
             Arc::new(builder.finish())
         }
-        DataType::Struct(fields) => make_struct_array(fields, values),
+        DataType::Date32 => {
+            let mut builder = Date32Builder::new();
+            for value in values {
+                match strip_union_value(value) {
+                    Value::Date(d) | Value::Int(d) => builder.append_value(*d),
+                    Value::Null => builder.append_null(),
+                    _ => return exec_err!("Type mismatch for Date32"),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Timestamp(unit, tz) => {
+            let micros: TimestampMicrosecondArray = values
+                .iter()
+                .map(|value| match strip_union_value(value) {
+                    Value::TimestampMillis(ts) | Value::LocalTimestampMillis(ts) => {
+                        Ok(Some(ts * 1_000))
+                    }
+                    Value::TimestampMicros(ts) | Value::LocalTimestampMicros(ts) => Ok(Some(*ts)),
+                    Value::Null => Ok(None),
+                    _ => exec_err!("Type mismatch for Timestamp"),
+                })
+                .collect::<datafusion::common::Result<_>>()?;
+            let micros = micros.with_timezone_opt(tz.clone());
+            cast(&micros, &DataType::Timestamp(*unit, tz.clone()))?
+        }
+        DataType::Decimal128(precision, scale) => {
+            let decimals: Decimal128Array = values
+                .iter()
+                .map(|value| match strip_union_value(value) {
+                    Value::Decimal(decimal) => avro_decimal_to_i128(decimal).map(Some),
+                    Value::Null => Ok(None),
+                    _ => exec_err!("Type mismatch for Decimal128"),
+                })
+                .collect::<datafusion::common::Result<_>>()?;
+            Arc::new(decimals.with_precision_and_scale(*precision, *scale)?)
+        }
+        DataType::Struct(fields) => make_struct_array(fields, values)?,
         DataType::List(field) => {
             match field.data_type() {
                 DataType::Int32 => {
@@ -392,7 +472,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                             }
                             list_builder.append(true)
                         } else {
-                            panic!("Expected an array.")
+                            return exec_err!("Expected an Avro array");
                         }
                     }
                     Arc::new(list_builder.finish())
@@ -411,7 +491,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                             }
                             list_builder.append(true)
                         } else {
-                            panic!("Expected an array.")
+                            return exec_err!("Expected an Avro array");
                         }
                     }
                     Arc::new(list_builder.finish())
@@ -430,7 +510,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                             }
                             list_builder.append(true)
                         } else {
-                            panic!("Expected an array.")
+                            return exec_err!("Expected an Avro array");
                         }
                     }
                     Arc::new(list_builder.finish())
@@ -449,7 +529,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                             }
                             list_builder.append(true)
                         } else {
-                            panic!("Expected an array.")
+                            return exec_err!("Expected an Avro array");
                         }
                     }
                     Arc::new(list_builder.finish())
@@ -468,7 +548,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                             }
                             list_builder.append(true)
                         } else {
-                            panic!("Expected an array.")
+                            return exec_err!("Expected an Avro array");
                         }
                     }
                     Arc::new(list_builder.finish())
@@ -479,7 +559,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                         let extracted_value = strip_union_value(value);
                         if let Value::Array(arr) = extracted_value {
                             let child_array: Arc<dyn Array> =
-                                avro_values_to_arrow_array(arr, field.data_type());
+                                avro_values_to_arrow_array(arr, field.data_type())?;
                             all_arrays.push(child_array);
                         }
                     }
@@ -495,7 +575,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                         .map(|a: &Arc<dyn Array>| a.as_ref())
                         .collect();
 
-                    let concatenated_array = arrow::compute::concat(&array_refs).unwrap();
+                    let concatenated_array = arrow::compute::concat(&array_refs)?;
 
                     //TODO: Add null handling. For now we test without nulls.
                     let list_array: arrow::array::GenericListArray<i32> = ListArray::new(
@@ -513,7 +593,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                         let child_array: Arc<dyn Array> = avro_values_to_arrow_array(
                             slice::from_ref(extracted_value),
                             field.data_type(),
-                        );
+                        )?;
                         all_arrays.push(child_array);
                     }
                     let mut array_lenghts: Vec<usize> = vec![];
@@ -527,7 +607,7 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                         .iter()
                         .map(|a: &Arc<dyn Array>| a.as_ref())
                         .collect();
-                    let concatenated_array = arrow::compute::concat(&array_refs).unwrap();
+                    let concatenated_array = arrow::compute::concat(&array_refs)?;
 
                     let list_array: arrow::array::GenericListArray<i32> = ListArray::new(
                         field.clone(),
@@ -537,34 +617,83 @@ fn avro_values_to_arrow_array(values: &[Value], data_type: &DataType) -> Arc<dyn
                     );
                     Arc::new(list_array)
                 }
-                _ => panic!("at the list disco."),
+                data_type => {
+                    return not_impl_err!("Converter for lists of {data_type:?} not implemented")
+                }
             }
         }
-        _ => unimplemented!("Converter for {:?} not implemented", data_type),
+        _ => return not_impl_err!("Converter for {data_type:?} not implemented"),
+    })
+}
+
+/// Avro decimals are big endian two's complement integers of arbitrary width
+fn avro_decimal_to_i128(decimal: &apache_avro::Decimal) -> datafusion::common::Result<i128> {
+    let bytes: Vec<u8> = decimal
+        .try_into()
+        .map_err(|e| DataFusionError::Execution(format!("Invalid Avro decimal: {e}")))?;
+    if bytes.len() > 16 {
+        return exec_err!("Avro decimal does not fit in a Decimal128");
     }
+    let fill = match bytes.first() {
+        Some(byte) if byte & 0x80 != 0 => 0xff,
+        _ => 0,
+    };
+    let mut buffer = [fill; 16];
+    buffer[16 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(i128::from_be_bytes(buffer))
 }
 
-fn avro_record_to_arrow_record_batch(avro_record: &Value, schema: Arc<Schema>) -> RecordBatch {
+fn avro_record_to_arrow_record_batch(
+    avro_record: &Value,
+    schema: Arc<Schema>,
+) -> datafusion::common::Result<RecordBatch> {
     match avro_record {
         Value::Record(fields) => {
             let arrays: Vec<Arc<dyn Array>> = schema
                 .fields()
                 .iter()
                 .map(|field_schema| {
-                    let field_value: &Value = fields
-                        .iter()
-                        .find(|(name, _)| name == field_schema.name())
-                        .map(|(_, v)| v)
-                        .expect("Field not found in record");
+                    let Some((_, field_value)) =
+                        fields.iter().find(|(name, _)| name == field_schema.name())
+                    else {
+                        return exec_err!("Field {} not found in Avro record", field_schema.name());
+                    };
                     avro_values_to_arrow_array(
                         slice::from_ref(field_value),
                         field_schema.data_type(),
                     )
                 })
-                .collect();
+                .collect::<datafusion::common::Result<_>>()?;
 
-            RecordBatch::try_new(schema, arrays).expect("Failed to create RecordBatch")
+            Ok(RecordBatch::try_new(schema, arrays)?)
         }
-        _ => panic!("Expected Value::Record for conversion"),
+        _ => exec_err!("Expected an Avro record for conversion"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fail_on_mismatched_avro_values() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "price",
+            DataType::Decimal128(10, 2),
+            true,
+        )]));
+        let record = |value: Value| Value::Record(vec![("price".to_string(), value)]);
+
+        let decimal = Value::Decimal(apache_avro::Decimal::from(vec![0x04, 0xd2]));
+        let batch =
+            avro_record_to_arrow_record_batch(&record(decimal.clone()), schema.clone()).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+
+        assert!(
+            avro_record_to_arrow_record_batch(&record(Value::String("12.34".into())), schema)
+                .is_err()
+        );
+        // The precision and scale of a decimal can't be told from its value
+        assert!(avro_record_to_arrow_schema(&record(decimal)).is_err());
     }
 }
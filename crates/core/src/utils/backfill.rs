@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::available_parallelism;
+use std::time::Duration;
+
+use datafusion::common::{plan_err, Result};
+use datafusion::execution::TaskContext;
+use log::info;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+
+/// Switches a pipeline between backfill and steady state settings by how far its sources lag
+/// behind event time.
+///
+/// A source starts lagging once its lag exceeds the entry threshold and stops once it falls
+/// below the lower exit threshold, so a lag hovering around a single threshold doesn't flip
+/// the settings back and forth. The pipeline backfills while any source lags: sources read
+/// and coalescers buffer `batch_factor` times larger batches and sources decode on several
+/// tasks, trading latency for throughput until the backlog is gone.
+#[derive(Debug)]
+pub struct BackfillController {
+    enter_lag: Duration,
+    exit_lag: Duration,
+    batch_factor: usize,
+    parallelism: usize,
+    lagging: Mutex<HashSet<String>>,
+    backfilling: AtomicBool,
+}
+
+impl BackfillController {
+    /// None unless `backfill_lag_ms` is set
+    pub fn from_config(config: &DenormalizedConfig) -> Result<Option<Self>> {
+        if config.backfill_lag_ms == 0 {
+            return Ok(None);
+        }
+        let exit_lag_ms = match config.backfill_exit_lag_ms {
+            0 => config.backfill_lag_ms / 2,
+            exit_lag_ms => exit_lag_ms,
+        };
+        if exit_lag_ms > config.backfill_lag_ms {
+            return plan_err!(
+                "backfill_exit_lag_ms of {exit_lag_ms} exceeds backfill_lag_ms of {}",
+                config.backfill_lag_ms
+            );
+        }
+        let parallelism = match config.backfill_parallelism {
+            0 => available_parallelism().map_or(1, |cores| cores.get()),
+            parallelism => parallelism,
+        };
+        Ok(Some(Self {
+            enter_lag: Duration::from_millis(config.backfill_lag_ms),
+            exit_lag: Duration::from_millis(exit_lag_ms),
+            batch_factor: config.backfill_batch_factor.max(1),
+            parallelism,
+            lagging: Mutex::new(HashSet::new()),
+            backfilling: AtomicBool::new(false),
+        }))
+    }
+
+    pub fn from_task_context(context: &TaskContext) -> Option<Arc<Self>> {
+        context.session_config().get_extension::<Self>()
+    }
+
+    /// Record the latest lag of `source`, e.g. a topic partition, and return whether the
+    /// pipeline is backfilling
+    pub fn observe_lag(&self, source: &str, lag: Duration) -> bool {
+        let mut lagging = self.lagging.lock().unwrap();
+        if lag > self.enter_lag {
+            lagging.insert(source.to_string());
+        } else if lag < self.exit_lag {
+            lagging.remove(source);
+        }
+
+        let backfilling = !lagging.is_empty();
+        if self.backfilling.swap(backfilling, Ordering::Relaxed) != backfilling {
+            match backfilling {
+                true => info!(
+                    "{source} lags by {lag:?}, backfilling with {}x batches on {} tasks",
+                    self.batch_factor, self.parallelism
+                ),
+                false => info!("Caught up with a lag of {lag:?}, back to steady state"),
+            }
+        }
+        backfilling
+    }
+
+    pub fn is_backfilling(&self) -> bool {
+        self.backfilling.load(Ordering::Relaxed)
+    }
+
+    /// How many times larger batches are than in steady state
+    pub fn batch_factor(&self) -> usize {
+        match self.is_backfilling() {
+            true => self.batch_factor,
+            false => 1,
+        }
+    }
+
+    /// Tasks a source decodes a batch on
+    pub fn parallelism(&self) -> usize {
+        match self.is_backfilling() {
+            true => self.parallelism,
+            false => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_down_once_every_source_caught_up() -> Result<()> {
+        let mut config = DenormalizedConfig {
+            backfill_lag_ms: 60_000,
+            backfill_batch_factor: 8,
+            backfill_parallelism: 4,
+            ..Default::default()
+        };
+        let controller = BackfillController::from_config(&config)?.unwrap();
+        let lag = |secs| Duration::from_secs(secs);
+
+        assert!(!controller.observe_lag("orders-0", lag(10)));
+        assert!(controller.observe_lag("orders-0", lag(120)));
+        assert!(controller.observe_lag("orders-1", lag(90)));
+        assert_eq!(
+            (controller.batch_factor(), controller.parallelism()),
+            (8, 4)
+        );
+
+        // Between the thresholds a lagging source keeps lagging
+        assert!(controller.observe_lag("orders-0", lag(45)));
+        assert!(controller.observe_lag("orders-0", lag(5)));
+        assert!(!controller.observe_lag("orders-1", lag(29)));
+        assert_eq!(
+            (controller.batch_factor(), controller.parallelism()),
+            (1, 1)
+        );
+
+        config.backfill_exit_lag_ms = 120_000;
+        assert!(BackfillController::from_config(&config).is_err());
+        Ok(())
+    }
+}
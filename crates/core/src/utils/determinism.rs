@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+
+use datafusion::execution::TaskContext;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+
+static LOGICAL_CLOCK_ENABLED: AtomicBool = AtomicBool::new(false);
+static LOGICAL_NOW_MS: AtomicI64 = AtomicI64::new(0);
+
+/// Replace the wall clock of the process with a logical one standing still at `start_ms`,
+/// until [`advance_logical_clock`] moves it. Arrival times of messages, freshness checks and
+/// everything else reading [`now_ms`](crate::datasource::message::now_ms) see the logical time.
+///
+/// The clock is shared by every context of the process, like the global RocksDB backend.
+pub fn enable_logical_clock(start_ms: i64) {
+    LOGICAL_NOW_MS.store(start_ms, Ordering::SeqCst);
+    LOGICAL_CLOCK_ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Move the logical clock forward by `ms`, e.g. between the inputs of a test
+pub fn advance_logical_clock(ms: i64) {
+    LOGICAL_NOW_MS.fetch_add(ms, Ordering::SeqCst);
+}
+
+/// The time of the logical clock, none unless it was enabled
+pub fn logical_now_ms() -> Option<i64> {
+    LOGICAL_CLOCK_ENABLED
+        .load(Ordering::SeqCst)
+        .then(|| LOGICAL_NOW_MS.load(Ordering::SeqCst))
+}
+
+/// A random number generator for `partition` of `operator`. In deterministic mode it is seeded
+/// from the configured seed, the operator name and the partition, so every run draws the same
+/// numbers, otherwise from entropy.
+pub fn operator_rng(context: &TaskContext, operator: &str, partition: usize) -> StdRng {
+    let config = context
+        .session_config()
+        .options()
+        .extensions
+        .get::<DenormalizedConfig>();
+    match config {
+        Some(config) if config.deterministic => StdRng::seed_from_u64(stable_hash(
+            config.deterministic_seed,
+            &[operator.as_bytes(), &partition.to_le_bytes()],
+        )),
+        _ => StdRng::from_entropy(),
+    }
+}
+
+/// FNV-1a, which unlike the std hashers is guaranteed to hash the same across Rust versions
+pub(crate) fn stable_hash(seed: u64, parts: &[&[u8]]) -> u64 {
+    let mut hash = 0xcbf29ce484222325 ^ seed;
+    for part in parts {
+        for byte in part.iter() {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        // Keeps ["ab", "c"] and ["a", "bc"] apart
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use datafusion::prelude::SessionConfig;
+    use rand::Rng;
+
+    #[test]
+    fn seed_operators_deterministically() {
+        let config = DenormalizedConfig {
+            deterministic: true,
+            deterministic_seed: 7,
+            ..Default::default()
+        };
+        let context = TaskContext::default()
+            .with_session_config(SessionConfig::new().with_option_extension(config));
+        let draw = |operator: &str, partition: usize| {
+            operator_rng(&context, operator, partition).gen::<u64>()
+        };
+        assert_eq!(draw("sample", 0), draw("sample", 0));
+        assert_ne!(draw("sample", 0), draw("sample", 1));
+        assert_ne!(draw("sample", 0), draw("tap", 0));
+
+        let random = Arc::new(TaskContext::default());
+        assert_ne!(
+            operator_rng(&random, "sample", 0).gen::<u64>(),
+            operator_rng(&random, "sample", 0).gen::<u64>()
+        );
+    }
+}
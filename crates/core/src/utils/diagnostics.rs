@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use super::profiling::{direct_children, OperatorProfile};
+
+/// Where an operator spent the profiled time, as fractions of the elapsed wall clock time.
+/// Operators running several partitions can exceed 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperatorLoad {
+    pub stack: String,
+    /// Processing its own input, excluding time spent in its children
+    pub busy: f64,
+    /// Waiting on its input
+    pub idle: f64,
+    /// Holding a batch the consumer hadn't asked for yet, i.e. occupancy of its output edge
+    pub blocked: f64,
+}
+
+/// Which operator holds a backpressured pipeline back.
+///
+/// The bottleneck is the busiest operator. Its inputs typically show up as blocked, since it
+/// can't take their batches any faster, while the operators after it sit idle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackpressureReport {
+    pub elapsed: Duration,
+    pub operators: Vec<OperatorLoad>,
+}
+
+impl BackpressureReport {
+    pub fn new(profiles: &BTreeMap<String, OperatorProfile>, elapsed: Duration) -> Self {
+        let wall_nanos = elapsed.as_nanos().max(1) as f64;
+        let operators = profiles
+            .iter()
+            .map(|(stack, profile)| {
+                let children_nanos: u64 = direct_children(profiles, stack)
+                    .map(|child| child.cpu_nanos)
+                    .sum();
+                OperatorLoad {
+                    stack: stack.clone(),
+                    busy: profile.cpu_nanos.saturating_sub(children_nanos) as f64 / wall_nanos,
+                    idle: profile.wait_nanos as f64 / wall_nanos,
+                    blocked: profile.blocked_nanos as f64 / wall_nanos,
+                }
+            })
+            .collect();
+        Self { elapsed, operators }
+    }
+
+    pub fn bottleneck(&self) -> Option<&OperatorLoad> {
+        self.operators
+            .iter()
+            .max_by(|a, b| a.busy.total_cmp(&b.busy))
+    }
+
+    /// The input of the bottleneck whose output waits the longest on it, `None` when the
+    /// bottleneck is a source
+    pub fn bottleneck_input(&self) -> Option<&OperatorLoad> {
+        let bottleneck = self.bottleneck()?;
+        let prefix = format!("{};", bottleneck.stack);
+        self.operators
+            .iter()
+            .filter(|load| {
+                load.stack
+                    .strip_prefix(&prefix)
+                    .is_some_and(|rest| !rest.contains(';'))
+            })
+            .max_by(|a, b| a.blocked.total_cmp(&b.blocked))
+    }
+}
+
+impl fmt::Display for BackpressureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Operator load over {:?}", self.elapsed)?;
+        writeln!(f, "operator | busy % | idle % | output blocked %")?;
+        for load in &self.operators {
+            writeln!(
+                f,
+                "{} | {:.1} | {:.1} | {:.1}",
+                load.stack,
+                load.busy * 100.0,
+                load.idle * 100.0,
+                load.blocked * 100.0
+            )?;
+        }
+        match (self.bottleneck(), self.bottleneck_input()) {
+            (Some(bottleneck), Some(input)) => write!(
+                f,
+                "Bottleneck: {} -> {}, input blocked {:.1}% of the time",
+                input.stack,
+                bottleneck.stack,
+                input.blocked * 100.0
+            ),
+            (Some(bottleneck), None) => write!(f, "Bottleneck: {}", bottleneck.stack),
+            (None, _) => write!(f, "No operator profiles collected yet"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_busiest_operator_and_its_blocked_input() {
+        let ms = 1_000_000;
+        let profiles = BTreeMap::from([
+            (
+                "Sink".to_string(),
+                OperatorProfile {
+                    cpu_nanos: 900 * ms,
+                    wait_nanos: 50 * ms,
+                    ..Default::default()
+                },
+            ),
+            (
+                "Sink;Window".to_string(),
+                OperatorProfile {
+                    cpu_nanos: 850 * ms,
+                    ..Default::default()
+                },
+            ),
+            (
+                "Sink;Window;Source".to_string(),
+                OperatorProfile {
+                    cpu_nanos: 50 * ms,
+                    blocked_nanos: 700 * ms,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let report = BackpressureReport::new(&profiles, Duration::from_secs(1));
+        assert_eq!(report.bottleneck().unwrap().stack, "Sink;Window");
+        assert_eq!(
+            report.bottleneck_input().unwrap().stack,
+            "Sink;Window;Source"
+        );
+        assert!(report.to_string().ends_with(
+            "Bottleneck: Sink;Window;Source -> Sink;Window, input blocked 70.0% of the time"
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+use std::sync::Arc;
+
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::common::Result;
+use datafusion::datasource::source_as_provider;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::ExecutionPlan;
+
+use crate::datasource::kafka::TopicReader;
+use crate::physical_plan::continuous::streaming_window::{
+    FranzStreamingWindowExec, FranzStreamingWindowType,
+};
+
+/// The result of planning a pipeline without running it.
+///
+/// Building the report validates every connector the pipeline reads from, so a successful
+/// dry run means the job made it through planning with all of its configuration intact.
+#[derive(Debug)]
+pub struct DryRunReport {
+    pub sources: Vec<SourceSummary>,
+    pub stateful_operators: Vec<StateEstimate>,
+    pub physical_plan: String,
+}
+
+#[derive(Debug)]
+pub struct SourceSummary {
+    pub name: String,
+    pub connector: &'static str,
+    /// Number of partitions the source will be read with, if known ahead of time
+    pub partitions: Option<usize>,
+    pub columns: usize,
+}
+
+/// A rough estimate of the state held by a single stateful operator
+#[derive(Debug)]
+pub struct StateEstimate {
+    pub operator: String,
+    /// Upper bound on the number of windows that are open at the same time
+    pub concurrent_windows: usize,
+    pub aggregates: usize,
+    /// Accumulator state held per group for each open window. `None` when any of the
+    /// accumulators keeps variable width state (e.g. strings or lists).
+    pub bytes_per_group: Option<usize>,
+}
+
+impl DryRunReport {
+    pub fn try_new(
+        logical_plan: &LogicalPlan,
+        physical_plan: &Arc<dyn ExecutionPlan>,
+    ) -> Result<Self> {
+        let mut sources = vec![];
+        logical_plan.apply(|node| {
+            if let LogicalPlan::TableScan(scan) = node {
+                let provider = source_as_provider(&scan.source)?;
+                let summary = match provider.as_any().downcast_ref::<TopicReader>() {
+                    Some(reader) => {
+                        reader.0.validate()?;
+                        SourceSummary {
+                            name: scan.table_name.to_string(),
+                            connector: "kafka",
+                            partitions: (!reader.0.dry_run)
+                                .then_some(reader.0.partition_count as usize),
+                            columns: reader.0.schema.fields().len(),
+                        }
+                    }
+                    None => SourceSummary {
+                        name: scan.table_name.to_string(),
+                        connector: "table",
+                        partitions: None,
+                        columns: provider.schema().fields().len(),
+                    },
+                };
+                sources.push(summary);
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+
+        let mut stateful_operators = vec![];
+        collect_state_estimates(physical_plan, &mut stateful_operators)?;
+
+        Ok(Self {
+            sources,
+            stateful_operators,
+            physical_plan: DisplayableExecutionPlan::new(physical_plan.as_ref())
+                .indent(true)
+                .to_string(),
+        })
+    }
+}
+
+fn collect_state_estimates(
+    plan: &Arc<dyn ExecutionPlan>,
+    estimates: &mut Vec<StateEstimate>,
+) -> Result<()> {
+    if let Some(window) = plan.as_any().downcast_ref::<FranzStreamingWindowExec>() {
+        estimates.push(StateEstimate::try_from_window(window)?);
+    }
+    for child in plan.children() {
+        collect_state_estimates(child, estimates)?;
+    }
+    Ok(())
+}
+
+impl StateEstimate {
+    fn try_from_window(window: &FranzStreamingWindowExec) -> Result<Self> {
+        let concurrent_windows = match &window.window_type {
+            FranzStreamingWindowType::Sliding(length, slide) => {
+                length.as_millis().div_ceil(slide.as_millis().max(1)) as usize
+            }
+            FranzStreamingWindowType::Tumbling(_)
+            | FranzStreamingWindowType::Session(_)
+            | FranzStreamingWindowType::Calendar(_)
+            | FranzStreamingWindowType::Custom(_)
+            | FranzStreamingWindowType::Count(..)
+            | FranzStreamingWindowType::Global(_) => 1,
+        };
+
+        let mut bytes_per_group = Some(0);
+        for expr in window.aggr_expr() {
+            for field in expr.state_fields()? {
+                bytes_per_group = bytes_per_group
+                    .zip(field.data_type().primitive_width())
+                    .map(|(total, width)| total + width);
+            }
+        }
+
+        Ok(Self {
+            operator: format!("{:?}", window.window_type),
+            concurrent_windows,
+            aggregates: window.aggr_expr().len(),
+            bytes_per_group,
+        })
+    }
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Sources:")?;
+        for source in &self.sources {
+            let partitions = source
+                .partitions
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            writeln!(
+                f,
+                "  {} ({}): {} columns, {} partitions",
+                source.name, source.connector, source.columns, partitions
+            )?;
+        }
+
+        writeln!(f, "Stateful operators:")?;
+        for estimate in &self.stateful_operators {
+            let bytes = estimate
+                .bytes_per_group
+                .map(|b| format!("~{b} bytes"))
+                .unwrap_or_else(|| "variable".to_string());
+            writeln!(
+                f,
+                "  window {}: {} aggregates, up to {} open windows, {} of state per group per window",
+                estimate.operator, estimate.aggregates, estimate.concurrent_windows, bytes
+            )?;
+        }
+
+        writeln!(f, "Physical plan:")?;
+        write!(f, "{}", self.physical_plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use datafusion::functions_aggregate::expr_fn::max;
+    use datafusion::logical_expr::col;
+
+    use crate::config_extensions::denormalized_config::DenormalizedConfig;
+    use crate::context::Context;
+    use crate::datasource::kafka::{ConnectionOpts, KafkaTopicBuilder, TopicReader};
+    use crate::physical_plan::utils::time::TimestampUnit;
+
+    #[tokio::test]
+    async fn dry_run_without_brokers() -> datafusion::common::Result<()> {
+        // Nothing listens on port 1, connecting would fail the test
+        let reader = KafkaTopicBuilder::new("localhost:1".to_string())
+            .with_timestamp("occurred_at_ms".to_string(), TimestampUnit::Int64Millis)
+            .with_encoding("json")?
+            .with_topic("temperature".to_string())
+            .infer_schema_from_json(r#"{"occurred_at_ms": 1, "sensor": "a", "reading": 1.5}"#)?
+            .with_dry_run(true)
+            .build_reader(ConnectionOpts::new())
+            .await?;
+
+        let copy = TopicReader(reader.0.clone());
+        assert!(Context::new()?.from_topic(copy).await.is_err());
+
+        let ctx = Context::with_config(DenormalizedConfig {
+            dry_run: true,
+            ..Default::default()
+        })?;
+        let report = ctx
+            .from_topic(reader)
+            .await?
+            .window(
+                vec![col("sensor")],
+                vec![max(col("reading")).alias("max")],
+                Duration::from_secs(10),
+                Some(Duration::from_secs(5)),
+            )?
+            .dry_run()
+            .await?;
+
+        assert_eq!(report.sources.len(), 1);
+        assert_eq!(report.sources[0].connector, "kafka");
+        assert_eq!(report.sources[0].partitions, None);
+        assert_eq!(report.stateful_operators.len(), 1);
+        assert_eq!(report.stateful_operators[0].concurrent_windows, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use datafusion::common::{exec_err, plan_err, Result};
+use datafusion::execution::TaskContext;
+use log::{error, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+
+/// What happens to a batch on its way out of a source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchFault {
+    None,
+    Drop,
+    Delay(Duration),
+}
+
+/// Injects the faults configured with the `fault_*` options of [`DenormalizedConfig`] into a
+/// running pipeline, to check that it recovers from them. Random faults are drawn from a
+/// seeded generator, a run with the same seed and input sees the same faults as long as the
+/// batches arrive the same way.
+#[derive(Debug)]
+pub struct FaultInjector {
+    drop_probability: f64,
+    delay_probability: f64,
+    delay: Duration,
+    send_failure_probability: f64,
+    crash_at_epoch: Option<u64>,
+    rng: Mutex<StdRng>,
+}
+
+impl FaultInjector {
+    /// None unless the config asks for any fault
+    pub fn from_config(config: &DenormalizedConfig) -> Result<Option<Self>> {
+        if !config.fault_injection_enabled() {
+            return Ok(None);
+        }
+        for (option, probability) in [
+            ("fault_drop_probability", config.fault_drop_probability),
+            ("fault_delay_probability", config.fault_delay_probability),
+            (
+                "fault_send_failure_probability",
+                config.fault_send_failure_probability,
+            ),
+        ] {
+            if !(0.0..=1.0).contains(&probability) {
+                return plan_err!("{option} must be between 0 and 1, got {probability}");
+            }
+        }
+        warn!("Fault injection is enabled, the pipeline will drop, delay and fail on purpose");
+        Ok(Some(Self {
+            drop_probability: config.fault_drop_probability,
+            delay_probability: config.fault_delay_probability,
+            delay: Duration::from_millis(config.fault_delay_ms),
+            send_failure_probability: config.fault_send_failure_probability,
+            crash_at_epoch: (config.fault_crash_at_epoch > 0)
+                .then_some(config.fault_crash_at_epoch),
+            rng: Mutex::new(StdRng::seed_from_u64(config.fault_seed)),
+        }))
+    }
+
+    /// The injector registered with the session, if fault injection is enabled
+    pub fn from_task_context(context: &TaskContext) -> Option<Arc<Self>> {
+        context.session_config().get_extension::<Self>()
+    }
+
+    fn draw(&self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.lock().unwrap().gen_bool(probability)
+    }
+
+    pub fn batch_fault(&self) -> BatchFault {
+        if self.draw(self.drop_probability) {
+            BatchFault::Drop
+        } else if self.draw(self.delay_probability) {
+            BatchFault::Delay(self.delay)
+        } else {
+            BatchFault::None
+        }
+    }
+
+    /// Fails the write of a batch to `sink` with the configured probability
+    pub fn check_send(&self, sink: &str) -> Result<()> {
+        if self.draw(self.send_failure_probability) {
+            return exec_err!("Injected failure writing a batch to {sink}");
+        }
+        Ok(())
+    }
+
+    /// Aborts the process, without any cleanup, once a source closes the configured epoch
+    pub fn check_epoch(&self, epoch: u64) {
+        if self
+            .crash_at_epoch
+            .is_some_and(|crash_at| epoch >= crash_at)
+        {
+            error!("Injected crash at checkpoint epoch {epoch}");
+            std::process::abort();
+        }
+    }
+}
+
+/// Overwrite a few bytes in the middle of one of the files of the checkpoint in `dir`, picked
+/// with `seed`, to check that restoring it fails or repairs as the corruption policy says.
+/// Returns the corrupted file.
+pub fn corrupt_checkpoint(dir: &Path, seed: u64) -> Result<PathBuf> {
+    let mut files = vec![];
+    collect_files(dir, &mut files)?;
+    files.retain(|(_, len)| *len > 0);
+    if files.is_empty() {
+        return plan_err!("No checkpoint files to corrupt in {}", dir.display());
+    }
+    files.sort();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (path, len) = files.swap_remove(rng.gen_range(0..files.len()));
+
+    let mut bytes = fs::read(&path)?;
+    let start = (len / 2) as usize;
+    for byte in bytes.iter_mut().skip(start).take(8) {
+        *byte = !*byte;
+    }
+    fs::write(&path, bytes)?;
+    warn!("Corrupted checkpoint file {}", path.display());
+    Ok(path)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<(PathBuf, u64)>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else {
+            files.push((entry.path(), metadata.len()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inject_configured_faults() -> Result<()> {
+        assert!(FaultInjector::from_config(&DenormalizedConfig::default())?.is_none());
+
+        let config = DenormalizedConfig {
+            fault_drop_probability: 1.0,
+            fault_send_failure_probability: 1.0,
+            ..Default::default()
+        };
+        let injector = FaultInjector::from_config(&config)?.unwrap();
+        assert_eq!(injector.batch_fault(), BatchFault::Drop);
+        assert!(injector.check_send("orders").is_err());
+
+        let dir = std::env::temp_dir().join(format!("corrupt_{}", std::process::id()));
+        fs::create_dir_all(dir.join("shared"))?;
+        fs::write(dir.join("shared").join("000001.sst"), vec![0_u8; 32])?;
+        let corrupted = corrupt_checkpoint(&dir, 0)?;
+        assert_eq!(fs::read(corrupted)?[16], 0xff);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
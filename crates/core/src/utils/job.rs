@@ -0,0 +1,93 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use datafusion::execution::TaskContext;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+
+const DEFAULT_JOB_ID: &str = "denormalized";
+
+/// Identifies a pipeline and the particular run of it, so the logs, metrics, checkpoints and
+/// Kafka clients of pipelines sharing a deployment can be told apart.
+///
+/// The job id is meant to stay the same across deployments of a pipeline. The run id changes
+/// every time a context is created, unless one is configured, and the attempt counts restarts
+/// of the same run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobIdentity {
+    pub job_id: String,
+    pub run_id: String,
+    pub attempt: u64,
+}
+
+impl JobIdentity {
+    pub fn from_config(config: &DenormalizedConfig) -> Self {
+        let job_id = match config.job_id.as_str() {
+            "" => DEFAULT_JOB_ID.to_string(),
+            job_id => job_id.to_string(),
+        };
+        let run_id = match config.run_id.as_str() {
+            "" if config.deterministic => format!("{:016x}", config.deterministic_seed),
+            "" => format!("{:016x}", rand::random::<u64>()),
+            run_id => run_id.to_string(),
+        };
+        Self {
+            job_id,
+            run_id,
+            attempt: config.attempt,
+        }
+    }
+
+    pub fn from_task_context(context: &TaskContext) -> Option<Arc<Self>> {
+        context.session_config().get_extension::<Self>()
+    }
+
+    /// Kafka `client.id` for the client playing `role` in this run, e.g. `source-rides`
+    pub fn client_id(&self, role: &str) -> String {
+        format!("{}-{}-{}-{role}", self.job_id, self.run_id, self.attempt)
+    }
+
+    /// Where the checkpoints of this job go under `base`. Shared by all runs of the job so a
+    /// new run can restore from the checkpoints of the previous one.
+    pub fn checkpoint_dir(&self, base: &Path) -> PathBuf {
+        base.join(&self.job_id)
+    }
+}
+
+impl fmt::Display for JobIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "job={} run={} attempt={}",
+            self.job_id, self.run_id, self.attempt
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_differ_unless_configured() {
+        let config = DenormalizedConfig::default();
+        let first = JobIdentity::from_config(&config);
+        let second = JobIdentity::from_config(&config);
+        assert_eq!(first.job_id, "denormalized");
+        assert_ne!(first.run_id, second.run_id);
+
+        let config = DenormalizedConfig {
+            job_id: "rides".to_string(),
+            run_id: "r1".to_string(),
+            attempt: 2,
+            ..Default::default()
+        };
+        let job = JobIdentity::from_config(&config);
+        assert_eq!(job.client_id("sink-out"), "rides-r1-2-sink-out");
+        assert_eq!(
+            job.checkpoint_dir(Path::new("/var/lib/checkpoints")),
+            PathBuf::from("/var/lib/checkpoints/rides")
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use std::sync::Arc;
+
+use arrow::array::{AsArray, BinaryBuilder, StringBuilder};
+use arrow::compute::cast;
+use arrow_array::{new_null_array, Array, ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use base64::{engine::general_purpose, Engine as _};
+use datafusion::common::{exec_err, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// ISO 8601 strings, with an offset only for columns that have a timezone
+    #[default]
+    Iso8601,
+    /// RFC 3339 strings, timestamps without a timezone are written as UTC
+    Rfc3339,
+    /// Milliseconds since the unix epoch as a JSON number
+    EpochMillis,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecimalFormat {
+    #[default]
+    Number,
+    /// Quoted strings, for consumers that would otherwise parse decimals as floats
+    String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NullFormat {
+    /// Leave null fields out of the object
+    #[default]
+    Omit,
+    /// Write null fields as `"field": null`
+    Explicit,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryFormat {
+    #[default]
+    Hex,
+    Base64,
+    /// Leave binary columns out when writing and read them as null
+    Skip,
+}
+
+/// Controls how values without a native JSON representation are written and read.
+///
+/// The options apply to the top level columns of a batch. On the read side both string and
+/// numeric decimals are accepted regardless of [`DecimalFormat`], and missing fields are
+/// always read as null.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonFormatOptions {
+    pub timestamps: TimestampFormat,
+    pub decimals: DecimalFormat,
+    pub nulls: NullFormat,
+    pub binary: BinaryFormat,
+}
+
+impl JsonFormatOptions {
+    pub fn with_timestamps(mut self, timestamps: TimestampFormat) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    pub fn with_decimals(mut self, decimals: DecimalFormat) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    pub fn with_nulls(mut self, nulls: NullFormat) -> Self {
+        self.nulls = nulls;
+        self
+    }
+
+    pub fn with_binary(mut self, binary: BinaryFormat) -> Self {
+        self.binary = binary;
+        self
+    }
+
+    pub fn explicit_nulls(&self) -> bool {
+        self.nulls == NullFormat::Explicit
+    }
+
+    /// The type a column is written as, so that the arrow JSON writer produces the
+    /// requested representation.
+    fn encoded_type(&self, data_type: &DataType) -> Option<DataType> {
+        match data_type {
+            DataType::Timestamp(_, _) if self.timestamps == TimestampFormat::EpochMillis => {
+                Some(DataType::Int64)
+            }
+            DataType::Timestamp(unit, None) if self.timestamps == TimestampFormat::Rfc3339 => {
+                Some(DataType::Timestamp(*unit, Some("+00:00".into())))
+            }
+            DataType::Decimal128(_, _) | DataType::Decimal256(_, _)
+                if self.decimals == DecimalFormat::String =>
+            {
+                Some(DataType::Utf8)
+            }
+            data_type if is_binary(data_type) => Some(DataType::Utf8),
+            _ => None,
+        }
+    }
+
+    /// Convert the columns of a batch into the types they should be written as
+    pub fn encode_batch(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        if !schema
+            .fields()
+            .iter()
+            .any(|field| self.encoded_type(field.data_type()).is_some())
+        {
+            return Ok(batch.clone());
+        }
+
+        let mut fields = Vec::with_capacity(schema.fields().len());
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            if is_binary(field.data_type()) && self.binary == BinaryFormat::Skip {
+                continue;
+            }
+            match self.encoded_type(field.data_type()) {
+                Some(encoded_type) => {
+                    let column = match field.data_type() {
+                        data_type if is_binary(data_type) => self.encode_binary(column)?,
+                        // Go through millisecond precision first so the integer is in millis
+                        DataType::Timestamp(_, tz) if encoded_type == DataType::Int64 => cast(
+                            &cast(
+                                column,
+                                &DataType::Timestamp(TimeUnit::Millisecond, tz.clone()),
+                            )?,
+                            &encoded_type,
+                        )?,
+                        _ => cast(column, &encoded_type)?,
+                    };
+                    fields.push(Field::new(field.name(), encoded_type, field.is_nullable()));
+                    columns.push(column);
+                }
+                None => {
+                    fields.push(field.as_ref().clone());
+                    columns.push(column.clone());
+                }
+            }
+        }
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+
+    fn encode_binary(&self, column: &ArrayRef) -> Result<ArrayRef> {
+        let column = cast(column, &DataType::LargeBinary)?;
+        let mut builder = StringBuilder::with_capacity(column.len(), column.len() * 2);
+        for value in column.as_binary::<i64>() {
+            match value {
+                Some(bytes) => builder.append_value(self.encode_binary_value(bytes)),
+                None => builder.append_null(),
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+
+    /// A binary value the way it's written, and read back into a binary column
+    pub fn encode_binary_value(&self, bytes: &[u8]) -> String {
+        match self.binary {
+            BinaryFormat::Base64 => general_purpose::STANDARD.encode(bytes),
+            _ => hex::encode(bytes),
+        }
+    }
+
+    fn decode_binary(&self, column: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+        if self.binary == BinaryFormat::Skip {
+            return Ok(new_null_array(data_type, column.len()));
+        }
+        let mut builder = BinaryBuilder::with_capacity(column.len(), column.len());
+        for value in column.as_string::<i32>() {
+            let Some(encoded) = value else {
+                builder.append_null();
+                continue;
+            };
+            let decoded = match self.binary {
+                BinaryFormat::Base64 => general_purpose::STANDARD.decode(encoded).ok(),
+                _ => hex::decode(encoded).ok(),
+            };
+            match decoded {
+                Some(bytes) => builder.append_value(bytes),
+                None => return exec_err!("Invalid {:?} encoded binary value", self.binary),
+            }
+        }
+        Ok(cast(&builder.finish(), data_type)?)
+    }
+
+    /// The schema to hand to the arrow JSON reader when reading data into `schema`
+    pub fn decode_schema(&self, schema: &SchemaRef) -> SchemaRef {
+        if self.timestamps != TimestampFormat::EpochMillis
+            && !schema
+                .fields()
+                .iter()
+                .any(|f| is_binary(f.data_type()) || is_dictionary(f.data_type()))
+        {
+            return schema.clone();
+        }
+        let fields: Vec<Field> = schema
+            .fields()
+            .iter()
+            .map(|field| match field.data_type() {
+                // Binary values are read as strings and decoded afterwards
+                data_type if is_binary(data_type) => {
+                    field.as_ref().clone().with_data_type(DataType::Utf8)
+                }
+                // Dictionary columns are read as their values and encoded afterwards
+                DataType::Dictionary(_, value_type) => field
+                    .as_ref()
+                    .clone()
+                    .with_data_type(value_type.as_ref().clone()),
+                // The reader interprets numbers in the unit of the column
+                DataType::Timestamp(_, tz) if self.timestamps == TimestampFormat::EpochMillis => {
+                    field
+                        .as_ref()
+                        .clone()
+                        .with_data_type(DataType::Timestamp(TimeUnit::Millisecond, tz.clone()))
+                }
+                _ => field.as_ref().clone(),
+            })
+            .collect();
+        Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+    }
+
+    /// Cast a batch read with [`Self::decode_schema`] back into `schema`
+    pub fn decode_batch(&self, batch: RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+        if batch.schema() == *schema {
+            return Ok(batch);
+        }
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(schema.fields())
+            .map(|(column, field)| {
+                if column.data_type() == field.data_type() {
+                    Ok(column.clone())
+                } else if is_binary(field.data_type()) {
+                    self.decode_binary(column, field.data_type())
+                } else {
+                    Ok(cast(column, field.data_type())?)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+}
+
+fn is_binary(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_)
+    )
+}
+
+fn is_dictionary(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Dictionary(_, _))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinaryFormat, JsonFormatOptions};
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int32Type;
+    use arrow_array::{Array, BinaryArray, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    #[test]
+    fn binary_columns_round_trip() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "payload",
+            DataType::Binary,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(BinaryArray::from(vec![
+                Some(b"\x00\xffabc".as_ref()),
+                None,
+            ]))],
+        )
+        .unwrap();
+
+        for (binary, expected) in [
+            (BinaryFormat::Hex, "00ff616263"),
+            (BinaryFormat::Base64, "AP9hYmM="),
+        ] {
+            let options = JsonFormatOptions::default().with_binary(binary);
+            let encoded = options.encode_batch(&batch).unwrap();
+            let strings = encoded
+                .column(0)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            assert_eq!(strings.value(0), expected);
+            assert!(strings.is_null(1));
+
+            let decoded = options.decode_batch(encoded, &schema).unwrap();
+            assert_eq!(decoded, batch);
+        }
+    }
+
+    #[test]
+    fn decode_dictionary_columns() {
+        let dictionary = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "event_type",
+            dictionary.clone(),
+            true,
+        )]));
+        let options = JsonFormatOptions::default();
+        let decode_schema = options.decode_schema(&schema);
+        assert_eq!(decode_schema.field(0).data_type(), &DataType::Utf8);
+
+        let read = RecordBatch::try_new(
+            decode_schema,
+            vec![Arc::new(StringArray::from(vec![
+                Some("click"),
+                Some("view"),
+                Some("click"),
+                None,
+            ]))],
+        )
+        .unwrap();
+        let decoded = options.decode_batch(read, &schema).unwrap();
+        let events = decoded.column(0).as_dictionary::<Int32Type>();
+        assert_eq!(events.values().len(), 2);
+        assert_eq!(events.keys().value(0), events.keys().value(2));
+        assert!(events.is_null(3));
+    }
+}
@@ -0,0 +1,36 @@
+use datafusion::common::{DataFusionError, Result};
+use serde_json::Value;
+
+/// Parse a JSON document. With the `simd-json` feature documents are parsed by simd-json,
+/// which parses a copy of the document in place.
+#[cfg(not(feature = "simd-json"))]
+pub fn parse_json(document: &[u8]) -> Result<Value> {
+    serde_json::from_slice(document).map_err(|err| DataFusionError::External(Box::new(err)))
+}
+
+/// Parse a JSON document. With the `simd-json` feature documents are parsed by simd-json,
+/// which parses a copy of the document in place.
+#[cfg(feature = "simd-json")]
+pub fn parse_json(document: &[u8]) -> Result<Value> {
+    let mut document = document.to_vec();
+    simd_json::serde::from_slice(&mut document)
+        .map_err(|err| DataFusionError::External(Box::new(err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn parse_documents() -> Result<()> {
+        let value = parse_json(br#"{"id": 7, "price": 1.25, "tags": ["a"], "bid": null}"#)?;
+        assert_eq!(
+            value,
+            json!({"id": 7, "price": 1.25, "tags": ["a"], "bid": null})
+        );
+        assert!(parse_json(br#"{"id": "#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use arrow_array::{ArrayRef, RecordBatch};
+use datafusion::common::{plan_err, Result};
+
+pub(crate) const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const CHANGED: &str = "\x1b[1;33m";
+const ADDED: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Latest row per key of a continuously updating result, rendered as a table that is redrawn
+/// in place. Cells that changed with the last batch are highlighted, rows seen for the first
+/// time are shown in a different colour.
+pub struct LiveTable {
+    columns: Vec<String>,
+    key_indices: Vec<usize>,
+    max_rows: usize,
+    /// Encodes keys so they compare by value and type rather than by how they are displayed,
+    /// created with the first batch
+    converter: Option<RowConverter>,
+    rows: BTreeMap<OwnedRow, Vec<String>>,
+    added: HashSet<OwnedRow>,
+    changed: HashSet<(OwnedRow, usize)>,
+}
+
+impl LiveTable {
+    /// Rows are identified by the values of `key_columns` and ordered by them. Only the last
+    /// `max_rows` keys are displayed.
+    pub fn new(columns: Vec<String>, key_columns: &[&str], max_rows: usize) -> Result<Self> {
+        let key_indices = key_columns
+            .iter()
+            .map(
+                |key| match columns.iter().position(|column| column == key) {
+                    Some(index) => Ok(index),
+                    None => plan_err!("Key column {key} not found in the stream"),
+                },
+            )
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            columns,
+            key_indices,
+            max_rows,
+            converter: None,
+            rows: BTreeMap::new(),
+            added: HashSet::new(),
+            changed: HashSet::new(),
+        })
+    }
+
+    pub fn update(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.added.clear();
+        self.changed.clear();
+
+        let key_columns: Vec<ArrayRef> = self
+            .key_indices
+            .iter()
+            .map(|&i| batch.column(i).clone())
+            .collect();
+        let converter = match &mut self.converter {
+            Some(converter) => converter,
+            None => self.converter.insert(RowConverter::new(
+                key_columns
+                    .iter()
+                    .map(|column| SortField::new(column.data_type().clone()))
+                    .collect(),
+            )?),
+        };
+        let keys = converter.convert_columns(&key_columns)?;
+
+        let options = FormatOptions::default().with_null("NULL");
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        for row in 0..batch.num_rows() {
+            let values: Vec<String> = formatters
+                .iter()
+                .map(|formatter| formatter.value(row).to_string())
+                .collect();
+            let key = keys.row(row).owned();
+            match self.rows.get(&key) {
+                Some(previous) => {
+                    for (column, (old, new)) in previous.iter().zip(&values).enumerate() {
+                        if old != new {
+                            self.changed.insert((key.clone(), column));
+                        }
+                    }
+                }
+                None => {
+                    self.added.insert(key.clone());
+                }
+            }
+            self.rows.insert(key, values);
+        }
+
+        while self.rows.len() > self.max_rows {
+            self.rows.pop_first();
+        }
+        Ok(())
+    }
+
+    pub fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.columns.iter().map(|c| c.chars().count()).collect();
+        for values in self.rows.values() {
+            for (width, value) in widths.iter_mut().zip(values) {
+                *width = (*width).max(value.chars().count());
+            }
+        }
+        let separator = widths.iter().fold(String::from("+"), |mut line, width| {
+            line.push_str(&"-".repeat(width + 2));
+            line.push('+');
+            line
+        });
+
+        let mut output = String::from(CLEAR_SCREEN);
+        let _ = writeln!(output, "{separator}");
+        output.push('|');
+        for (column, width) in self.columns.iter().zip(&widths) {
+            let _ = write!(output, " {column:<width$} |");
+        }
+        let _ = writeln!(output, "\n{separator}");
+
+        for (key, values) in &self.rows {
+            output.push('|');
+            for (column, (value, width)) in values.iter().zip(&widths).enumerate() {
+                let style = if self.added.contains(key) {
+                    Some(ADDED)
+                } else if self.changed.contains(&(key.clone(), column)) {
+                    Some(CHANGED)
+                } else {
+                    None
+                };
+                match style {
+                    Some(style) => {
+                        let _ = write!(output, " {style}{value:<width$}{RESET} |");
+                    }
+                    None => {
+                        let _ = write!(output, " {value:<width$} |");
+                    }
+                }
+            }
+            output.push('\n');
+        }
+        let _ = writeln!(output, "{separator}");
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    fn batch(keys: Vec<&str>, counts: Vec<i64>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(keys)),
+                Arc::new(Int64Array::from(counts)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn highlights_changed_cells() -> Result<()> {
+        let mut table = LiveTable::new(
+            vec!["sensor".to_string(), "count".to_string()],
+            &["sensor"],
+            10,
+        )?;
+        table.update(&batch(vec!["a", "b"], vec![1, 2]))?;
+        table.update(&batch(vec!["a", "b", "c"], vec![1, 5, 7]))?;
+
+        let rendered = table.render();
+        assert!(rendered.contains("| a      | 1     |"));
+        assert!(rendered.contains(&format!("| b      | {CHANGED}5    {RESET} |")));
+        assert!(rendered.contains(&format!("| {ADDED}c     {RESET} |")));
+        Ok(())
+    }
+
+    #[test]
+    fn keys_compare_by_value() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let mut table = LiveTable::new(vec!["id".to_string(), "name".to_string()], &["id"], 3)?;
+        table.update(&RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![Some(10), Some(2), None, Some(9)])),
+                Arc::new(StringArray::from(vec!["ten", "two", "none", "nine"])),
+            ],
+        )?)?;
+
+        // Keys are ordered numerically and the smallest is evicted, a NULL key sorts first
+        let names: Vec<&str> = table.rows.values().map(|row| row[1].as_str()).collect();
+        assert_eq!(names, vec!["two", "nine", "ten"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use datafusion::common::{DataFusionError, Result};
+use tracing::level_filters::LevelFilter;
+use tracing::Span;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use super::job::JobIdentity;
+
+static GLOBAL_LOG_LEVELS: OnceLock<LogLevels> = OnceLock::new();
+
+/// Span every event of an operator is logged in. `operator` is a short name such as
+/// `kafka_source`, which is what [`LogLevels::set_operator_level`] matches on. The `topic` and
+/// `partition` fields are recorded by operators they apply to.
+pub fn operator_span(operator: &'static str, job: Option<&JobIdentity>) -> Span {
+    tracing::info_span!(
+        "operator",
+        operator,
+        job = job.map(|job| job.job_id.as_str()),
+        run = job.map(|job| job.run_id.as_str()),
+        topic = tracing::field::Empty,
+        partition = tracing::field::Empty,
+    )
+}
+
+/// Log levels that can be changed while pipelines run, globally and per operator.
+///
+/// Installed with [`LogLevels::init`], which sets up the global `tracing` subscriber and
+/// forwards `log` records to it.
+#[derive(Clone)]
+pub struct LogLevels {
+    handle: reload::Handle<EnvFilter, Registry>,
+    inner: Arc<Mutex<Directives>>,
+}
+
+#[derive(Clone)]
+struct Directives {
+    base: String,
+    operators: BTreeMap<String, LevelFilter>,
+}
+
+impl Directives {
+    fn filter(&self) -> Result<EnvFilter> {
+        let mut directives = self.base.clone();
+        for (operator, level) in &self.operators {
+            directives.push_str(&format!(",[operator{{operator={operator}}}]={level}"));
+        }
+        EnvFilter::try_new(&directives)
+            .map_err(|e| DataFusionError::Configuration(format!("Invalid log directives: {e}")))
+    }
+}
+
+impl LogLevels {
+    /// `base` uses the `RUST_LOG` syntax, e.g. `info,rdkafka=warn`
+    pub fn init(base: &str) -> Result<Self> {
+        let directives = Directives {
+            base: base.to_string(),
+            operators: BTreeMap::new(),
+        };
+        let (filter, handle) = reload::Layer::new(directives.filter()?);
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(tracing_subscriber::fmt::layer())
+            .try_init()
+            .map_err(|e| DataFusionError::Internal(format!("Failed to set up logging: {e}")))?;
+
+        let levels = Self {
+            handle,
+            inner: Arc::new(Mutex::new(directives)),
+        };
+        let _ = GLOBAL_LOG_LEVELS.set(levels.clone());
+        Ok(levels)
+    }
+
+    /// The levels installed by [`LogLevels::init`], if it was called
+    pub fn global() -> Option<&'static Self> {
+        GLOBAL_LOG_LEVELS.get()
+    }
+
+    pub fn set_base(&self, base: &str) -> Result<()> {
+        self.update(|directives| directives.base = base.to_string())
+    }
+
+    /// Log events of `operator` down to `level`, regardless of the base directives
+    pub fn set_operator_level(&self, operator: &str, level: LevelFilter) -> Result<()> {
+        self.update(|directives| {
+            directives.operators.insert(operator.to_string(), level);
+        })
+    }
+
+    pub fn clear_operator_level(&self, operator: &str) -> Result<()> {
+        self.update(|directives| {
+            directives.operators.remove(operator);
+        })
+    }
+
+    /// Apply overrides in the `operator=level,...` form of the `operator_log_levels` option
+    pub fn apply_overrides(&self, overrides: &str) -> Result<()> {
+        for entry in overrides
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let Some((operator, level)) = entry.split_once('=') else {
+                return Err(DataFusionError::Configuration(format!(
+                    "Invalid operator log level '{entry}', expected operator=level"
+                )));
+            };
+            let level = level.trim().parse::<LevelFilter>().map_err(|e| {
+                DataFusionError::Configuration(format!("Invalid log level in '{entry}': {e}"))
+            })?;
+            self.set_operator_level(operator.trim(), level)?;
+        }
+        Ok(())
+    }
+
+    /// Apply `change` only if the resulting directives are valid and were reloaded, so a
+    /// rejected change leaves the levels in effect as they were
+    fn update(&self, change: impl FnOnce(&mut Directives)) -> Result<()> {
+        let mut directives = self.inner.lock().unwrap();
+        let mut updated = directives.clone();
+        change(&mut updated);
+        let filter = updated.filter()?;
+        self.handle
+            .reload(filter)
+            .map_err(|e| DataFusionError::Internal(format!("Failed to update log levels: {e}")))?;
+        *directives = updated;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_levels_when_a_change_is_invalid() -> Result<()> {
+        let directives = Directives {
+            base: "info".to_string(),
+            operators: BTreeMap::new(),
+        };
+        let (_layer, handle) = reload::Layer::new(directives.filter()?);
+        let levels = LogLevels {
+            handle,
+            inner: Arc::new(Mutex::new(directives)),
+        };
+
+        levels.set_operator_level("kafka_source", LevelFilter::DEBUG)?;
+        assert!(levels.set_base("info,rdkafka=loud").is_err());
+
+        let directives = levels.inner.lock().unwrap();
+        assert_eq!(directives.base, "info");
+        assert_eq!(
+            directives.operators.get("kafka_source"),
+            Some(&LevelFilter::DEBUG)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, Weak};
+
+use datafusion::physical_plan::metrics::{MetricValue, MetricsSet};
+use datafusion::physical_plan::ExecutionPlan;
+
+use super::job::JobIdentity;
+
+/// Prefix of the names of the exported metrics
+const METRIC_PREFIX: &str = "denormalized";
+
+/// The physical plans of the running pipelines, whose operator metrics are rendered in the
+/// Prometheus text format. Plans are held weakly and drop out once their pipeline finished.
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    job: Option<Arc<JobIdentity>>,
+    plans: Mutex<Vec<Weak<dyn ExecutionPlan>>>,
+}
+
+impl MetricsRegistry {
+    pub fn new(job: Arc<JobIdentity>) -> Self {
+        Self {
+            job: Some(job),
+            plans: Mutex::default(),
+        }
+    }
+
+    pub fn register(&self, plan: &Arc<dyn ExecutionPlan>) {
+        let mut plans = self.plans.lock().unwrap();
+        plans.retain(|plan| plan.strong_count() > 0);
+        plans.push(Arc::downgrade(plan));
+    }
+
+    /// The metrics of every operator of the running plans, labeled with the operator's name
+    /// and its position in a depth first walk of its plan
+    pub fn render(&self) -> String {
+        let plans: Vec<_> = self
+            .plans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        let mut families = BTreeMap::new();
+        for (plan_index, plan) in plans.iter().enumerate() {
+            let mut operator_index = 0;
+            let mut collect = |mut labels: Vec<(String, String)>, metrics: &MetricsSet| {
+                if let Some(job) = &self.job {
+                    labels.insert(0, ("job".to_string(), job.job_id.clone()));
+                }
+                collect_metrics(&mut families, &labels, metrics)
+            };
+            collect_plan(plan, plan_index, &mut operator_index, &mut collect);
+        }
+        render_families(&families)
+    }
+}
+
+fn collect_plan(
+    plan: &Arc<dyn ExecutionPlan>,
+    plan_index: usize,
+    operator_index: &mut usize,
+    collect: &mut impl FnMut(Vec<(String, String)>, &MetricsSet),
+) {
+    if let Some(metrics) = plan.metrics() {
+        let labels = vec![
+            ("plan".to_string(), plan_index.to_string()),
+            ("operator".to_string(), plan.name().to_string()),
+            ("operator_index".to_string(), operator_index.to_string()),
+        ];
+        collect(labels, &metrics);
+    }
+    *operator_index += 1;
+    for child in plan.children() {
+        collect_plan(child, plan_index, operator_index, collect);
+    }
+}
+
+/// Samples of one metric
+#[derive(Debug, Default)]
+struct Family {
+    gauge: bool,
+    samples: Vec<(String, f64)>,
+}
+
+fn collect_metrics(
+    families: &mut BTreeMap<String, Family>,
+    labels: &[(String, String)],
+    metrics: &MetricsSet,
+) {
+    for metric in metrics.iter() {
+        let value = metric.value();
+        let (suffix, gauge, sample) = match value {
+            MetricValue::StartTimestamp(_) | MetricValue::EndTimestamp(_) => continue,
+            MetricValue::ElapsedCompute(_) | MetricValue::Time { .. } => {
+                ("_seconds_total", false, value.as_usize() as f64 / 1e9)
+            }
+            MetricValue::Gauge { .. } | MetricValue::CurrentMemoryUsage(_) => {
+                ("", true, value.as_usize() as f64)
+            }
+            _ => ("_total", false, value.as_usize() as f64),
+        };
+        let name = format!("{METRIC_PREFIX}_{}{suffix}", sanitize(value.name()));
+
+        let mut sample_labels = labels.to_vec();
+        if let Some(partition) = metric.partition() {
+            sample_labels.push(("partition".to_string(), partition.to_string()));
+        }
+        for label in metric.labels() {
+            sample_labels.push((sanitize(label.name()), label.value().to_string()));
+        }
+        let labels = sample_labels
+            .iter()
+            .map(|(name, value)| format!("{name}=\"{}\"", escape(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let family = families.entry(name).or_default();
+        family.gauge = gauge;
+        family.samples.push((labels, sample));
+    }
+}
+
+fn render_families(families: &BTreeMap<String, Family>) -> String {
+    let mut text = String::new();
+    for (name, family) in families {
+        let kind = if family.gauge { "gauge" } else { "counter" };
+        let _ = writeln!(text, "# TYPE {name} {kind}");
+        for (labels, value) in &family.samples {
+            let _ = writeln!(text, "{name}{{{labels}}} {value}");
+        }
+    }
+    text
+}
+
+/// Replace the characters Prometheus doesn't allow in names
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves the metrics of a [`MetricsRegistry`] on `GET /metrics` for Prometheus to scrape,
+/// until dropped
+#[cfg(feature = "prometheus")]
+pub struct MetricsExporter {
+    address: std::net::SocketAddr,
+    _server: datafusion::common_runtime::SpawnedTask<()>,
+}
+
+#[cfg(feature = "prometheus")]
+impl MetricsExporter {
+    pub async fn serve(
+        registry: Arc<MetricsRegistry>,
+        address: &str,
+    ) -> datafusion::common::Result<Self> {
+        use axum::extract::State;
+        use axum::http::header;
+        use axum::routing::get;
+        use axum::Router;
+        use log::error;
+
+        let listener = tokio::net::TcpListener::bind(address).await?;
+        let address = listener.local_addr()?;
+        let router = Router::new()
+            .route(
+                "/metrics",
+                get(|State(registry): State<Arc<MetricsRegistry>>| async move {
+                    (
+                        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                        registry.render(),
+                    )
+                }),
+            )
+            .with_state(registry);
+        let server = datafusion::common_runtime::SpawnedTask::spawn(async move {
+            if let Err(err) = axum::serve(listener, router).await {
+                error!("Metrics exporter on {address} failed: {err}");
+            }
+        });
+        Ok(Self {
+            address,
+            _server: server,
+        })
+    }
+
+    /// The address the exporter listens on, telling the port picked when binding port 0
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricBuilder};
+
+    #[test]
+    fn render_metrics_grouped_by_name() {
+        let metrics = ExecutionPlanMetricsSet::new();
+        MetricBuilder::new(&metrics)
+            .counter("rows_written", 0)
+            .add(3);
+        MetricBuilder::new(&metrics)
+            .with_new_label("kafka_partition", "1")
+            .gauge("consumer_lag", 0)
+            .set(42);
+        MetricBuilder::new(&metrics)
+            .counter("rows_written", 1)
+            .add(2);
+
+        let mut families = BTreeMap::new();
+        let labels = [("operator".to_string(), "Kafka\"Sink".to_string())];
+        collect_metrics(&mut families, &labels, &metrics.clone_inner());
+
+        assert_eq!(
+            render_families(&families),
+            "# TYPE denormalized_consumer_lag gauge\n\
+             denormalized_consumer_lag{operator=\"Kafka\\\"Sink\",partition=\"0\",kafka_partition=\"1\"} 42\n\
+             # TYPE denormalized_rows_written_total counter\n\
+             denormalized_rows_written_total{operator=\"Kafka\\\"Sink\",partition=\"0\"} 3\n\
+             denormalized_rows_written_total{operator=\"Kafka\\\"Sink\",partition=\"1\"} 2\n"
+        );
+    }
+}
@@ -1,6 +1,23 @@
 #[allow(dead_code)]
 pub mod arrow_helpers;
+pub mod backfill;
 mod default_optimizer_rules;
+pub mod determinism;
+pub mod diagnostics;
+pub mod dry_run;
+pub mod fault_injection;
+pub mod job;
+pub mod json_format;
+pub mod json_parse;
+pub mod live_table;
+pub mod logging;
+pub mod metrics_export;
+pub mod preview;
+pub mod profiling;
+pub mod quota;
+pub mod repl;
 pub mod row_encoder;
+pub mod schema_drift;
+pub mod secrets;
 
 pub use default_optimizer_rules::get_default_optimizer_rules;
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use arrow::compute::concat_batches;
+use arrow_array::RecordBatch;
+use datafusion::common::Result;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use futures::StreamExt;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+/// Holds on to the most recent `capacity` rows of a stream
+struct RecentRows {
+    capacity: usize,
+    batches: VecDeque<RecordBatch>,
+    num_rows: usize,
+}
+
+impl RecentRows {
+    fn push(&mut self, batch: RecordBatch) {
+        self.num_rows += batch.num_rows();
+        self.batches.push_back(batch);
+        while let Some(oldest) = self.batches.front() {
+            if self.num_rows - oldest.num_rows() < self.capacity {
+                break;
+            }
+            self.num_rows -= oldest.num_rows();
+            self.batches.pop_front();
+        }
+    }
+
+    fn snapshot(&self, input: &SendableRecordBatchStream) -> Result<RecordBatch> {
+        let batch = concat_batches(&input.schema(), &self.batches)?;
+        let skip = batch.num_rows().saturating_sub(self.capacity);
+        Ok(batch.slice(skip, batch.num_rows() - skip))
+    }
+}
+
+struct PreviewState {
+    input: SendableRecordBatchStream,
+    recent: RecentRows,
+    ticker: Interval,
+    changed: bool,
+    done: bool,
+}
+
+/// Turn `input` into a stream of snapshots of its latest `n_rows` rows, at most one per
+/// `refresh` and only when new rows arrived since the previous snapshot.
+pub fn preview_stream(
+    input: SendableRecordBatchStream,
+    n_rows: usize,
+    refresh: Duration,
+) -> SendableRecordBatchStream {
+    let schema = input.schema();
+    let mut ticker = interval(refresh);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let state = PreviewState {
+        input,
+        recent: RecentRows {
+            capacity: n_rows,
+            batches: VecDeque::new(),
+            num_rows: 0,
+        },
+        ticker,
+        changed: false,
+        done: false,
+    };
+
+    let snapshots = futures::stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+        loop {
+            tokio::select! {
+                next = state.input.next() => match next {
+                    Some(Ok(batch)) if batch.num_rows() > 0 => {
+                        state.recent.push(batch);
+                        state.changed = true;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        state.done = true;
+                        return Some((Err(e), state));
+                    }
+                    None => {
+                        // Hand out whatever arrived after the last snapshot before finishing
+                        state.done = true;
+                        if !state.changed {
+                            return None;
+                        }
+                        let snapshot = state.recent.snapshot(&state.input);
+                        return Some((snapshot, state));
+                    }
+                },
+                _ = state.ticker.tick(), if state.changed => {
+                    state.changed = false;
+                    let snapshot = state.recent.snapshot(&state.input);
+                    return Some((snapshot, state));
+                }
+            }
+        }
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, snapshots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{Array, Int64Array};
+    use arrow_schema::{DataType, Field, Schema};
+
+    #[tokio::test]
+    async fn snapshots_hold_latest_rows() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)]));
+        let batches = [vec![1, 2], vec![3, 4, 5]].map(|values| -> Result<RecordBatch> {
+            Ok(RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int64Array::from(values))],
+            )?)
+        });
+        let input = Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            futures::stream::iter(batches),
+        ));
+
+        let snapshots: Vec<RecordBatch> = preview_stream(input, 3, Duration::from_secs(60))
+            .map(|snapshot| snapshot.unwrap())
+            .collect()
+            .await;
+        let last = snapshots.last().unwrap();
+        let values = last
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(values.values().to_vec(), vec![3, 4, 5]);
+        assert!(last.column(0).len() <= 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use datafusion::common::instant::Instant;
+use datafusion::execution::TaskContext;
+use log::{error, info, warn};
+
+use super::diagnostics::BackpressureReport;
+use super::job::JobIdentity;
+
+thread_local! {
+    static ALLOCATED_BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Global allocator that keeps a per thread count of allocated bytes so the profiler can
+/// attribute allocations to the operator being polled.
+///
+/// Allocation tracking is only available when installed by the application:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: denormalized::utils::profiling::ProfilingAllocator =
+///     denormalized::utils::profiling::ProfilingAllocator;
+/// ```
+pub struct ProfilingAllocator;
+
+unsafe impl GlobalAlloc for ProfilingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED_BYTES.try_with(|bytes| bytes.set(bytes.get() + layout.size() as u64));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Bytes allocated on the current thread so far. Always zero unless [`ProfilingAllocator`] is
+/// the global allocator.
+pub fn allocated_bytes() -> u64 {
+    ALLOCATED_BYTES.try_with(|bytes| bytes.get()).unwrap_or(0)
+}
+
+/// Counters collected for a single operator. Times and allocations are inclusive of the
+/// operator's children, [`Profiler::folded`] derives the self values from the stack paths.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OperatorProfile {
+    pub cpu_nanos: u64,
+    /// Time the operator's input had nothing for it
+    pub wait_nanos: u64,
+    /// Time a batch the operator produced waited until the consumer asked for the next one
+    pub blocked_nanos: u64,
+    pub allocated_bytes: u64,
+    pub output_rows: u64,
+}
+
+/// Collects per-operator profiles while a pipeline runs and reports them once after the
+/// configured duration.
+///
+/// Operators are keyed by their stack, a `;` separated path from the root of the plan, which
+/// makes the report directly usable with flame graph tooling.
+#[derive(Debug)]
+pub struct Profiler {
+    report_after: Option<Duration>,
+    output_path: Option<PathBuf>,
+    lag_alert: Option<LagAlert>,
+    job: Option<Arc<JobIdentity>>,
+    started: OnceLock<Instant>,
+    reported: AtomicBool,
+    profiles: Mutex<BTreeMap<String, OperatorProfile>>,
+}
+
+impl Profiler {
+    /// Without `report_after` profiles are only collected, e.g. for backpressure diagnostics
+    pub fn new(report_after: Option<Duration>, output_path: Option<PathBuf>) -> Self {
+        Self {
+            report_after,
+            output_path,
+            lag_alert: None,
+            job: None,
+            started: OnceLock::new(),
+            reported: AtomicBool::new(false),
+            profiles: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Name the job in the report, to tell apart reports of pipelines sharing a log
+    pub fn with_job(mut self, job: Arc<JobIdentity>) -> Self {
+        self.job = Some(job);
+        self
+    }
+
+    /// Log a backpressure report whenever [`Self::observe_lag`] sees a lag above `threshold`,
+    /// at most once every `interval`
+    pub fn with_lag_alert(mut self, threshold: Duration, interval: Duration) -> Self {
+        self.lag_alert = Some(LagAlert {
+            threshold,
+            interval,
+            last_report: Mutex::new(None),
+        });
+        self
+    }
+
+    /// The profiler registered with the session, if profiling is enabled
+    pub fn from_task_context(context: &TaskContext) -> Option<Arc<Self>> {
+        context.session_config().get_extension::<Self>()
+    }
+
+    pub fn record(&self, stack: &str, update: impl FnOnce(&mut OperatorProfile)) {
+        self.started.get_or_init(Instant::now);
+        let mut profiles = self.profiles.lock().unwrap();
+        update(profiles.entry(stack.to_string()).or_default());
+    }
+
+    /// Time `f` and attribute its CPU time and allocations to `stack`
+    pub fn measure<T>(&self, stack: &str, f: impl FnOnce() -> T) -> T {
+        let allocated_before = allocated_bytes();
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed().as_nanos() as u64;
+        let allocated = allocated_bytes().saturating_sub(allocated_before);
+        self.record(stack, |profile| {
+            profile.cpu_nanos += elapsed;
+            profile.allocated_bytes += allocated;
+        });
+        result
+    }
+
+    pub fn profiles(&self) -> BTreeMap<String, OperatorProfile> {
+        self.profiles.lock().unwrap().clone()
+    }
+
+    /// Self CPU time per stack in the folded format understood by flamegraph tools,
+    /// one `stack microseconds` line per operator.
+    pub fn folded(&self) -> String {
+        let profiles = self.profiles();
+        let mut output = String::new();
+        for (stack, profile) in &profiles {
+            let children_nanos: u64 = direct_children(&profiles, stack)
+                .map(|child| child.cpu_nanos)
+                .sum();
+            let self_micros = profile.cpu_nanos.saturating_sub(children_nanos) / 1_000;
+            let _ = writeln!(output, "{stack} {self_micros}");
+        }
+        output
+    }
+
+    /// Human readable table with wait time, allocations and row counts next to CPU time
+    pub fn summary(&self) -> String {
+        let profiles = self.profiles();
+        let mut output =
+            String::from("operator | self cpu ms | wait ms | self alloc bytes | rows\n");
+        for (stack, profile) in &profiles {
+            let (children_nanos, children_bytes) = direct_children(&profiles, stack)
+                .fold((0, 0), |(nanos, bytes), child| {
+                    (nanos + child.cpu_nanos, bytes + child.allocated_bytes)
+                });
+            let _ = writeln!(
+                output,
+                "{} | {} | {} | {} | {}",
+                stack,
+                profile.cpu_nanos.saturating_sub(children_nanos) / 1_000_000,
+                profile.wait_nanos / 1_000_000,
+                profile.allocated_bytes.saturating_sub(children_bytes),
+                profile.output_rows
+            );
+        }
+        output
+    }
+
+    /// Busy, idle and blocked time per operator since profiling started
+    pub fn backpressure_report(&self) -> BackpressureReport {
+        let elapsed = self
+            .started
+            .get()
+            .map_or(Duration::ZERO, |started| started.elapsed());
+        BackpressureReport::new(&self.profiles(), elapsed)
+    }
+
+    /// Called by sources with how far behind event time they are
+    pub fn observe_lag(&self, lag: Duration) {
+        let Some(alert) = &self.lag_alert else {
+            return;
+        };
+        if lag < alert.threshold {
+            return;
+        }
+        {
+            let mut last_report = alert.last_report.lock().unwrap();
+            if last_report.is_some_and(|last| last.elapsed() < alert.interval) {
+                return;
+            }
+            *last_report = Some(Instant::now());
+        }
+        warn!(
+            "Pipeline is {lag:?} behind event time\n{}",
+            self.backpressure_report()
+        );
+    }
+
+    /// Emit the report if the profiling window has elapsed. Only the first call after the
+    /// window elapses produces output.
+    pub fn maybe_report(&self) {
+        let (Some(started), Some(report_after)) = (self.started.get(), self.report_after) else {
+            return;
+        };
+        if started.elapsed() < report_after || self.reported.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        match &self.job {
+            Some(job) => info!(
+                "Profile of {job} after {:?}:\n{}",
+                report_after,
+                self.summary()
+            ),
+            None => info!("Profile after {:?}:\n{}", report_after, self.summary()),
+        }
+        match &self.output_path {
+            Some(path) => {
+                if let Err(err) = std::fs::write(path, self.folded()) {
+                    error!("Failed to write profile to {}: {err}", path.display());
+                }
+            }
+            None => println!("{}", self.folded()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LagAlert {
+    threshold: Duration,
+    interval: Duration,
+    last_report: Mutex<Option<Instant>>,
+}
+
+pub(crate) fn direct_children<'a>(
+    profiles: &'a BTreeMap<String, OperatorProfile>,
+    stack: &'a str,
+) -> impl Iterator<Item = &'a OperatorProfile> {
+    let prefix = format!("{stack};");
+    profiles
+        .range(prefix.clone()..)
+        .take_while(move |(key, _)| key.starts_with(&prefix))
+        .filter(move |(key, _)| !key[stack.len() + 1..].contains(';'))
+        .map(|(_, profile)| profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_self_time_once() {
+        let path = std::env::temp_dir().join(format!("profile_{}.folded", std::process::id()));
+        let profiler = Profiler::new(Some(Duration::ZERO), Some(path.clone()));
+        for (stack, cpu_nanos, rows) in [
+            ("KafkaSink[out]", 9_000_000, 10),
+            ("KafkaSink[out];Window", 7_000_000, 10),
+            ("KafkaSink[out];Window;KafkaSource[in]", 4_000_000, 100),
+            ("KafkaSink[out];Window;KafkaSource[in];decode", 1_000_000, 0),
+        ] {
+            profiler.record(stack, |profile| {
+                profile.cpu_nanos += cpu_nanos;
+                profile.output_rows += rows;
+            });
+        }
+
+        assert_eq!(
+            profiler.folded(),
+            "KafkaSink[out] 2000\n\
+             KafkaSink[out];Window 3000\n\
+             KafkaSink[out];Window;KafkaSource[in] 3000\n\
+             KafkaSink[out];Window;KafkaSource[in];decode 1000\n"
+        );
+        assert!(profiler
+            .summary()
+            .contains("KafkaSink[out];Window;KafkaSource[in] | 3 | 0 | 0 | 100"));
+
+        profiler.maybe_report();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), profiler.folded());
+        std::fs::remove_file(&path).unwrap();
+        profiler.maybe_report();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,133 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use datafusion::common::instant::Instant;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::execution::TaskContext;
+
+use crate::config_extensions::denormalized_config::DenormalizedConfig;
+
+/// Rows a sink may write ahead of its rate before the output quota counts as exceeded
+const OUTPUT_BURST: Duration = Duration::from_secs(1);
+
+/// What happens when a pipeline writes faster than its output quota allows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuotaPolicy {
+    /// Hold the sink back until the rate is within the quota again
+    #[default]
+    Backpressure,
+    /// Fail the pipeline with a resources exhausted error
+    Fail,
+}
+
+impl FromStr for QuotaPolicy {
+    type Err = DataFusionError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "backpressure" => Ok(Self::Backpressure),
+            "fail" => Ok(Self::Fail),
+            other => Err(DataFusionError::Configuration(format!(
+                "Unknown quota policy '{other}', expected backpressure or fail"
+            ))),
+        }
+    }
+}
+
+/// Limits on what a single pipeline may use when several share a process.
+///
+/// The memory budget is enforced by the memory pool of the session, see
+/// [`crate::context::Context::with_config`]. Exceeding it or the state quota always fails the
+/// pipeline, as waiting doesn't free either. The output rate is enforced by sinks calling
+/// [`ResourceQuotas::acquire_output`] and is subject to the [`QuotaPolicy`].
+#[derive(Debug, Default)]
+pub struct ResourceQuotas {
+    pub max_state_bytes: Option<u64>,
+    pub max_output_rows_per_sec: Option<u64>,
+    pub policy: QuotaPolicy,
+    /// When the rows admitted so far would have been written at exactly the quota
+    output_due: Mutex<Option<Instant>>,
+}
+
+impl ResourceQuotas {
+    pub fn from_config(config: &DenormalizedConfig) -> Result<Self> {
+        Ok(Self {
+            max_state_bytes: (config.max_state_bytes > 0).then_some(config.max_state_bytes),
+            max_output_rows_per_sec: (config.max_output_rows_per_sec > 0)
+                .then_some(config.max_output_rows_per_sec),
+            policy: config.quota_policy.parse()?,
+            output_due: Mutex::new(None),
+        })
+    }
+
+    pub fn from_task_context(context: &TaskContext) -> Option<Arc<Self>> {
+        context.session_config().get_extension::<Self>()
+    }
+
+    /// Fail once the state the pipeline keeps grows past its quota
+    pub fn check_state_size(&self, state_bytes: u64) -> Result<()> {
+        match self.max_state_bytes {
+            Some(max) if state_bytes > max => Err(DataFusionError::ResourcesExhausted(format!(
+                "State of {state_bytes} bytes exceeds the quota of {max} bytes"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Admit `rows` more output rows, waiting or failing when that would exceed the rate
+    /// quota by more than a second's worth of rows
+    pub async fn acquire_output(&self, rows: usize) -> Result<()> {
+        let Some(rate) = self.max_output_rows_per_sec else {
+            return Ok(());
+        };
+        let wait = {
+            let mut output_due = self.output_due.lock().unwrap();
+            let now = Instant::now();
+            let due = output_due.map_or(now, |due| due.max(now))
+                + Duration::from_secs_f64(rows as f64 / rate as f64);
+            let ahead = due.saturating_duration_since(now);
+            if ahead > OUTPUT_BURST && self.policy == QuotaPolicy::Fail {
+                return Err(DataFusionError::ResourcesExhausted(format!(
+                    "Output exceeds the quota of {rate} rows per second"
+                )));
+            }
+            *output_due = Some(due);
+            ahead.saturating_sub(OUTPUT_BURST)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn output_quota_allows_a_burst_then_fails() {
+        let quotas = ResourceQuotas {
+            max_output_rows_per_sec: Some(100),
+            policy: QuotaPolicy::Fail,
+            ..Default::default()
+        };
+        quotas.acquire_output(60).await.unwrap();
+        quotas.acquire_output(30).await.unwrap();
+        assert!(matches!(
+            quotas.acquire_output(50).await,
+            Err(DataFusionError::ResourcesExhausted(_))
+        ));
+    }
+
+    #[test]
+    fn state_quota() {
+        let quotas = ResourceQuotas {
+            max_state_bytes: Some(1024),
+            ..Default::default()
+        };
+        assert!(quotas.check_state_size(1024).is_ok());
+        assert!(quotas.check_state_size(1025).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::util::pretty::pretty_format_batches;
+use datafusion::common::Result;
+use futures::StreamExt;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::sync::Notify;
+
+use crate::context::Context;
+use crate::utils::live_table::CLEAR_SCREEN;
+
+const PROMPT: &str = "denormalized> ";
+const CONTINUATION: &str = "           -> ";
+
+/// Interactive SQL shell over the streams registered with a [`Context`].
+///
+/// A query starts printing its latest rows as soon as they arrive and redraws them at most
+/// once per refresh interval until the stream ends or the query is cancelled through
+/// [`Repl::cancel_handle`], which returns to the prompt instead of exiting. Statements end
+/// with `;`, besides SQL the shell understands:
+///
+/// - `\watch [seconds]` shows or sets the refresh interval
+/// - `\rows [n]` shows or sets how many of the latest rows are displayed
+/// - `\q` quits
+pub struct Repl {
+    context: Context,
+    refresh: Duration,
+    preview_rows: usize,
+    cancel: Arc<Notify>,
+}
+
+impl Repl {
+    pub fn new(context: Context) -> Self {
+        Self {
+            context,
+            refresh: Duration::from_secs(1),
+            preview_rows: 20,
+            cancel: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Notifying the handle cancels the running query, e.g. from a Ctrl-C handler. Use
+    /// `notify_waiters` so a notification while no query runs isn't kept for the next one.
+    pub fn cancel_handle(&self) -> Arc<Notify> {
+        self.cancel.clone()
+    }
+
+    /// Read statements from `input` until it ends or `\q`, writing results to `output`. A
+    /// failing statement is reported and the shell carries on.
+    pub async fn run<R, W>(&mut self, input: R, output: &mut W) -> Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: Write,
+    {
+        let mut lines = input.lines();
+        let mut statement = String::new();
+        loop {
+            write!(
+                output,
+                "{}",
+                if statement.is_empty() {
+                    PROMPT
+                } else {
+                    CONTINUATION
+                }
+            )?;
+            output.flush()?;
+            let Some(line) = lines.next_line().await? else {
+                return Ok(());
+            };
+            let line = line.trim();
+
+            if statement.is_empty() && line.starts_with('\\') {
+                let mut parts = line.split_whitespace();
+                let command = parts.next().unwrap_or_default();
+                let argument = parts.next();
+                match (command, argument) {
+                    ("\\q", _) => return Ok(()),
+                    ("\\watch", None) => writeln!(output, "Refreshing every {:?}", self.refresh)?,
+                    ("\\watch", Some(seconds)) => match seconds.parse::<f64>() {
+                        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                            self.refresh = Duration::from_secs_f64(seconds);
+                            writeln!(output, "Refreshing every {:?}", self.refresh)?;
+                        }
+                        _ => writeln!(output, "Invalid refresh interval {seconds}")?,
+                    },
+                    ("\\rows", None) => writeln!(output, "Showing {} rows", self.preview_rows)?,
+                    ("\\rows", Some(rows)) => match rows.parse::<usize>() {
+                        Ok(rows) if rows > 0 => {
+                            self.preview_rows = rows;
+                            writeln!(output, "Showing {} rows", self.preview_rows)?;
+                        }
+                        _ => writeln!(output, "Invalid number of rows {rows}")?,
+                    },
+                    _ => writeln!(output, "Unknown command {command}")?,
+                }
+                continue;
+            }
+
+            if !statement.is_empty() {
+                statement.push('\n');
+            }
+            statement.push_str(line);
+            if !statement.ends_with(';') {
+                continue;
+            }
+            let query = std::mem::take(&mut statement);
+            if let Err(err) = self.execute(query.trim_end_matches(';'), output).await {
+                writeln!(output, "Error: {err}")?;
+            }
+        }
+    }
+
+    async fn execute<W: Write>(&self, query: &str, output: &mut W) -> Result<()> {
+        let cancelled = self.cancel.notified();
+        tokio::pin!(cancelled);
+        let mut snapshots = self
+            .context
+            .sql(query)
+            .await?
+            .stream_preview(self.preview_rows, self.refresh)
+            .await?;
+        loop {
+            tokio::select! {
+                snapshot = snapshots.next() => match snapshot {
+                    Some(snapshot) => {
+                        let table = pretty_format_batches(&[snapshot?])?;
+                        writeln!(output, "{CLEAR_SCREEN}{table}")?;
+                        output.flush()?;
+                    }
+                    None => return Ok(()),
+                },
+                _ = &mut cancelled => {
+                    // Dropping the snapshots stops the query
+                    writeln!(output, "Query cancelled")?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::datasource::MemTable;
+
+    use crate::physical_plan::utils::metadata::{stream_metadata_array, stream_metadata_field};
+
+    #[tokio::test]
+    async fn query_registered_stream() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("reading", DataType::Int64, false),
+            stream_metadata_field(),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(Int64Array::from(vec![10, 20, 30])),
+                Arc::new(stream_metadata_array(
+                    vec![Some(1_000), Some(2_000), Some(3_000)].into(),
+                )),
+            ],
+        )?;
+        let context = Context::new()?;
+        context
+            .from_source(
+                "events",
+                Arc::new(MemTable::try_new(schema, vec![vec![batch]])?),
+            )
+            .await?;
+
+        let input = "\\watch 0.5\nSELECT id, reading\nFROM events WHERE reading > 10;\nSELECT nope FROM events;\n\\q\nSELECT 1;\n";
+        let mut output = vec![];
+        Repl::new(context)
+            .run(input.as_bytes(), &mut output)
+            .await?;
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("Refreshing every 500ms"));
+        assert!(output.contains(CONTINUATION));
+        assert!(output.contains("| 2  | 20      |"));
+        assert!(output.contains("| 3  | 30      |"));
+        assert!(!output.contains("| 1  | 10      |"));
+        assert!(output.contains("Error: "));
+        // Nothing runs after \q
+        assert!(!output.contains("Int64(1)"));
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
-use arrow::json::writer::{JsonFormat, Writer};
+use arrow::json::writer::{JsonFormat, WriterBuilder};
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::common::Result;
 
+use crate::utils::json_format::JsonFormatOptions;
+
 pub trait RowEncoder {
     fn encode(&self, batch: &RecordBatch) -> Result<Vec<Vec<u8>>>;
 }
@@ -10,15 +12,23 @@ pub trait RowEncoder {
 // Formats json without any characting separating items.
 pub struct NoDelimiter {}
 impl JsonFormat for NoDelimiter {}
-// writes rows as json without any character separating them
-type JsonWriter<W> = Writer<W, NoDelimiter>;
 
-pub struct JsonRowEncoder {}
+#[derive(Debug, Default)]
+pub struct JsonRowEncoder {
+    pub options: JsonFormatOptions,
+}
 
 impl JsonRowEncoder {
+    pub fn new(options: JsonFormatOptions) -> Self {
+        Self { options }
+    }
+
     pub fn batch_to_json(&self, batch: &RecordBatch) -> Result<Vec<u8>> {
         let buf = Vec::new();
-        let mut writer = JsonWriter::new(buf);
+        // writes rows as json without any character separating them
+        let mut writer = WriterBuilder::new()
+            .with_explicit_nulls(self.options.explicit_nulls())
+            .build::<_, NoDelimiter>(buf);
         writer.write(batch)?;
         writer.finish()?;
         let buf = writer.into_inner();
@@ -33,6 +43,7 @@ impl RowEncoder for JsonRowEncoder {
             return Ok(vec![]);
         }
 
+        let batch = self.options.encode_batch(batch)?;
         let mut buffer = Vec::with_capacity(batch.num_rows());
         for i in 0..batch.num_rows() {
             let row = batch.slice(i, 1);
@@ -46,9 +57,14 @@ impl RowEncoder for JsonRowEncoder {
 #[cfg(test)]
 mod tests {
     use super::{JsonRowEncoder, RowEncoder};
+    use crate::utils::json_format::{
+        DecimalFormat, JsonFormatOptions, NullFormat, TimestampFormat,
+    };
 
-    use datafusion::arrow::array::{Int32Array, StringArray};
-    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::array::{
+        Decimal128Array, Int32Array, StringArray, TimestampMillisecondArray,
+    };
+    use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
     use datafusion::arrow::record_batch::RecordBatch;
     use std::sync::Arc;
 
@@ -69,7 +85,7 @@ mod tests {
         )
         .unwrap();
 
-        let encoder = JsonRowEncoder {};
+        let encoder = JsonRowEncoder::default();
         let buf = encoder.encode(&batch).unwrap();
 
         let res: Vec<&[u8]> = [
@@ -84,4 +100,46 @@ mod tests {
 
         assert_eq!(buf, res);
     }
+
+    #[test]
+    fn serialize_with_format_options() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("price", DataType::Decimal128(10, 2), false),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(TimestampMillisecondArray::from(vec![1_700_000_000_000])),
+                Arc::new(
+                    Decimal128Array::from(vec![1234])
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ),
+                Arc::new(StringArray::from(vec![None::<&str>])),
+            ],
+        )
+        .unwrap();
+
+        let options = JsonFormatOptions::default()
+            .with_timestamps(TimestampFormat::EpochMillis)
+            .with_decimals(DecimalFormat::String)
+            .with_nulls(NullFormat::Explicit);
+        let buf = JsonRowEncoder::new(options).encode(&batch).unwrap();
+        assert_eq!(
+            String::from_utf8(buf[0].clone()).unwrap(),
+            "{\"ts\":1700000000000,\"price\":\"12.34\",\"note\":null}"
+        );
+
+        let buf = JsonRowEncoder::default().encode(&batch).unwrap();
+        assert_eq!(
+            String::from_utf8(buf[0].clone()).unwrap(),
+            "{\"ts\":\"2023-11-14T22:13:20\",\"price\":12.34}"
+        );
+    }
 }
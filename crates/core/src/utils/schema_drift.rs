@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::warn;
+use serde_json::Value;
+
+use crate::datasource::message::now_ms;
+
+/// A change in the fields a source's JSON records carry. Nested fields are named by their
+/// path, e.g. `device.firmware`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriftEvent {
+    FieldAdded {
+        source: String,
+        field: String,
+        json_type: &'static str,
+    },
+    /// No record carried the field for the monitor's absence timeout
+    FieldRemoved { source: String, field: String },
+    TypeChanged {
+        source: String,
+        field: String,
+        from: &'static str,
+        to: &'static str,
+    },
+}
+
+impl fmt::Display for DriftEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FieldAdded {
+                source,
+                field,
+                json_type,
+            } => write!(f, "{source}: new field {field} of type {json_type}"),
+            Self::FieldRemoved { source, field } => {
+                write!(f, "{source}: field {field} disappeared")
+            }
+            Self::TypeChanged {
+                source,
+                field,
+                from,
+                to,
+            } => write!(f, "{source}: field {field} changed from {from} to {to}"),
+        }
+    }
+}
+
+/// Where a [`SchemaDriftMonitor`] sends the drift it detects, e.g. a chat webhook or pager
+pub trait DriftNotifier: Debug + Send + Sync {
+    fn notify(&self, event: &DriftEvent);
+}
+
+/// Logs drift as warnings
+#[derive(Debug, Default)]
+pub struct LogNotifier;
+
+impl DriftNotifier for LogNotifier {
+    fn notify(&self, event: &DriftEvent) {
+        warn!("Schema drift detected, {event}");
+    }
+}
+
+/// Tracks the fields and JSON types the records of every source carry and notifies about
+/// fields appearing, disappearing or changing type. The first records of a source set its
+/// baseline without notifications. Nulls don't count as a type, a field only seen as null so
+/// far takes the type of its first value silently.
+#[derive(Debug)]
+pub struct SchemaDriftMonitor {
+    notifier: Arc<dyn DriftNotifier>,
+    absent_after: Duration,
+    sources: Mutex<HashMap<String, HashMap<String, ObservedField>>>,
+}
+
+#[derive(Debug)]
+struct ObservedField {
+    json_type: &'static str,
+    last_seen_ms: i64,
+}
+
+impl SchemaDriftMonitor {
+    pub fn new(notifier: Arc<dyn DriftNotifier>) -> Self {
+        Self {
+            notifier,
+            absent_after: Duration::from_secs(300),
+            sources: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Time no record may carry a field before it counts as removed, 5 minutes by default
+    pub fn with_absent_after(mut self, absent_after: Duration) -> Self {
+        self.absent_after = absent_after;
+        self
+    }
+
+    /// Check the records a source just read against the fields it carried so far
+    pub fn observe(&self, source: &str, records: &[Value]) {
+        self.observe_at(source, records, now_ms());
+    }
+
+    fn observe_at(&self, source: &str, records: &[Value], now_ms: i64) {
+        if records.is_empty() {
+            return;
+        }
+        let mut events = vec![];
+        {
+            let mut sources = self.sources.lock().unwrap();
+            let baseline = !sources.contains_key(source);
+            let fields = sources.entry(source.to_string()).or_default();
+
+            let mut observed = vec![];
+            for record in records {
+                flatten(record, "", &mut observed);
+            }
+            for (field, json_type) in observed.drain(..) {
+                let Some(known) = fields.get_mut(&field) else {
+                    if !baseline {
+                        events.push(DriftEvent::FieldAdded {
+                            source: source.to_string(),
+                            field: field.clone(),
+                            json_type,
+                        });
+                    }
+                    fields.insert(
+                        field,
+                        ObservedField {
+                            json_type,
+                            last_seen_ms: now_ms,
+                        },
+                    );
+                    continue;
+                };
+                known.last_seen_ms = now_ms;
+                if json_type == "null" || json_type == known.json_type {
+                    continue;
+                }
+                if known.json_type != "null" {
+                    events.push(DriftEvent::TypeChanged {
+                        source: source.to_string(),
+                        field,
+                        from: known.json_type,
+                        to: json_type,
+                    });
+                }
+                known.json_type = json_type;
+            }
+
+            let oldest = now_ms - self.absent_after.as_millis() as i64;
+            let mut removed = vec![];
+            fields.retain(|field, known| {
+                let present = known.last_seen_ms >= oldest;
+                if !present {
+                    removed.push(field.clone());
+                }
+                present
+            });
+            removed.sort();
+            events.extend(removed.into_iter().map(|field| DriftEvent::FieldRemoved {
+                source: source.to_string(),
+                field,
+            }));
+        }
+        for event in &events {
+            self.notifier.notify(event);
+        }
+    }
+}
+
+/// The fields of `value` with their JSON types, descending into objects but not arrays
+fn flatten(value: &Value, path: &str, fields: &mut Vec<(String, &'static str)>) {
+    let Value::Object(object) = value else {
+        return;
+    };
+    for (name, value) in object {
+        let field = match path {
+            "" => name.clone(),
+            path => format!("{path}.{name}"),
+        };
+        fields.push((field.clone(), json_type(value)));
+        flatten(value, &field, fields);
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[derive(Debug, Default)]
+    struct Collect(Mutex<Vec<String>>);
+
+    impl DriftNotifier for Collect {
+        fn notify(&self, event: &DriftEvent) {
+            self.0.lock().unwrap().push(event.to_string());
+        }
+    }
+
+    #[test]
+    fn detect_drift() {
+        let notifier = Arc::new(Collect::default());
+        let monitor =
+            SchemaDriftMonitor::new(notifier.clone()).with_absent_after(Duration::from_secs(60));
+
+        monitor.observe_at(
+            "orders",
+            &[json!({"id": 1, "note": null, "device": {"os": "ios"}})],
+            0,
+        );
+        monitor.observe_at(
+            "orders",
+            &[json!({"id": "1", "note": "gift", "device": {"os": "ios", "version": 17}})],
+            10_000,
+        );
+        let mut events = std::mem::take(&mut *notifier.0.lock().unwrap());
+        events.sort();
+        assert_eq!(
+            events,
+            [
+                "orders: field id changed from number to string",
+                "orders: new field device.version of type number",
+            ]
+        );
+
+        monitor.observe_at(
+            "orders",
+            &[json!({"id": "2", "device": {"os": "ios"}})],
+            71_000,
+        );
+        assert_eq!(
+            *notifier.0.lock().unwrap(),
+            [
+                "orders: field device.version disappeared",
+                "orders: field note disappeared",
+            ]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use std::fmt::Debug;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use datafusion::common::{DataFusionError, Result};
+
+/// Source of key material and credentials, so they don't have to be part of the job config.
+pub trait SecretsProvider: Debug + Send + Sync {
+    /// The secret stored under `name`, `None` when there is no such secret
+    fn get_secret(&self, name: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// Reads secrets from environment variables holding base64 encoded values
+#[derive(Debug, Default)]
+pub struct EnvSecretsProvider;
+
+impl SecretsProvider for EnvSecretsProvider {
+    fn get_secret(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let Ok(value) = std::env::var(name) else {
+            return Ok(None);
+        };
+        STANDARD.decode(value.trim()).map(Some).map_err(|e| {
+            DataFusionError::Configuration(format!("Secret {name} is not valid base64: {e}"))
+        })
+    }
+}
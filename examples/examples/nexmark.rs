@@ -0,0 +1,150 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::compute::max;
+use arrow::datatypes::TimestampMillisecondType;
+use datafusion::common::plan_err;
+use datafusion::error::Result;
+use futures::StreamExt;
+use tokio::time::{timeout_at, Instant};
+
+use denormalized::context::Context;
+use denormalized::datasource::message::now_ms;
+use denormalized::physical_plan::utils::metadata::{
+    CANONICAL_TIMESTAMP_FIELD, STREAMING_METADATA_COLUMN,
+};
+
+use denormalized_examples::nexmark::{nexmark_query, NexmarkConfig, QUERIES};
+
+/// Runs Nexmark queries over generated events and reports their throughput and latency.
+///
+/// `cargo run --release --example nexmark -- q5 --events-per-second 100000 --seconds 60`
+///
+/// Without a query all of them run one after the other. `--events-per-second 0` generates
+/// events as fast as the query takes them, `--events` stops the sources after that many
+/// events. Latency is the time from the event time of the newest row of an output batch to
+/// the batch arriving, for windows that includes the time the window was open.
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Warn)
+        .init();
+
+    let args = Args::parse()?;
+    let queries = match &args.query {
+        Some(query) => vec![query.as_str()],
+        None => QUERIES.to_vec(),
+    };
+    println!("query  events/s  rows/s   p50 ms   p99 ms   max ms");
+    for query in queries {
+        println!("{}", run(query, &args).await?);
+    }
+    Ok(())
+}
+
+struct Args {
+    query: Option<String>,
+    events_per_second: Option<u64>,
+    max_events: Option<u64>,
+    duration: Duration,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut args = Args {
+            query: None,
+            events_per_second: Some(100_000),
+            max_events: None,
+            duration: Duration::from_secs(30),
+        };
+        let mut argv = std::env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            let mut value = || -> Result<u64> {
+                match argv.next().and_then(|value| value.parse().ok()) {
+                    Some(value) => Ok(value),
+                    None => plan_err!("{arg} expects a number"),
+                }
+            };
+            match arg.as_str() {
+                "--events-per-second" => {
+                    args.events_per_second = Some(value()?).filter(|rate| *rate > 0)
+                }
+                "--events" => args.max_events = Some(value()?),
+                "--seconds" => args.duration = Duration::from_secs(value()?),
+                query if !query.starts_with("--") => args.query = Some(query.to_string()),
+                other => return plan_err!("Unknown option {other}"),
+            }
+        }
+        Ok(args)
+    }
+}
+
+struct Report {
+    query: String,
+    events: u64,
+    rows: usize,
+    elapsed: Duration,
+    /// Sorted
+    latencies_ms: Vec<i64>,
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        let percentile = |p: f64| {
+            let index = ((self.latencies_ms.len() as f64 * p) as usize)
+                .min(self.latencies_ms.len().saturating_sub(1));
+            self.latencies_ms.get(index).copied().unwrap_or_default()
+        };
+        write!(
+            f,
+            "{:<5} {:>9.0} {:>8.0} {:>8} {:>8} {:>8}",
+            self.query,
+            self.events as f64 / secs,
+            self.rows as f64 / secs,
+            percentile(0.5),
+            percentile(0.99),
+            self.latencies_ms.last().copied().unwrap_or_default(),
+        )
+    }
+}
+
+/// Run `query` on a context of its own until its sources end or the time is up
+async fn run(query: &str, args: &Args) -> Result<Report> {
+    let ctx = Context::new()?;
+    let config = NexmarkConfig {
+        events_per_second: args.events_per_second,
+        max_events: args.max_events,
+        ..Default::default()
+    };
+    let ds = nexmark_query(&ctx, query, &config).await?;
+
+    let mut stream = ds.df.as_ref().clone().execute_stream().await?;
+    let started = Instant::now();
+    let deadline = started + args.duration;
+    let mut rows = 0;
+    let mut latencies_ms = vec![];
+    while let Ok(Some(batch)) = timeout_at(deadline, stream.next()).await {
+        let batch = batch?;
+        rows += batch.num_rows();
+        if let Some(event_time) = newest_event_time(&batch) {
+            latencies_ms.push(now_ms() - event_time);
+        }
+    }
+    latencies_ms.sort_unstable();
+
+    Ok(Report {
+        query: query.to_string(),
+        events: config.generated.load(Ordering::Relaxed),
+        rows,
+        elapsed: started.elapsed(),
+        latencies_ms,
+    })
+}
+
+fn newest_event_time(batch: &RecordBatch) -> Option<i64> {
+    let metadata = batch.column_by_name(STREAMING_METADATA_COLUMN)?.as_struct();
+    let timestamps = metadata.column_by_name(CANONICAL_TIMESTAMP_FIELD)?;
+    max(timestamps.as_primitive::<TimestampMillisecondType>()).filter(|_| timestamps.len() > 0)
+}
@@ -0,0 +1,49 @@
+use datafusion::common_runtime::SpawnedTask;
+use datafusion::error::Result;
+
+use denormalized::context::Context;
+use denormalized::datasource::kafka::{ConnectionOpts, KafkaTopicBuilder};
+use denormalized::physical_plan::utils::time::TimestampUnit;
+use denormalized::utils::repl::Repl;
+
+use denormalized_examples::get_sample_json;
+
+/// Interactive SQL shell over the `temperature` topic written by the `emit_measurements.rs`
+/// example script. Ctrl-C cancels the running query, `\q` quits.
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Warn)
+        .init();
+
+    let bootstrap_servers = String::from("localhost:9092");
+
+    let ctx = Context::new()?;
+    let mut topic_builder = KafkaTopicBuilder::new(bootstrap_servers);
+    let source_topic = topic_builder
+        .with_timestamp(String::from("occurred_at_ms"), TimestampUnit::Int64Millis)
+        .with_encoding("json")?
+        .with_topic(String::from("temperature"))
+        .infer_schema_from_json(get_sample_json().as_str())?
+        .build_reader(ConnectionOpts::from([
+            ("auto.offset.reset".to_string(), "latest".to_string()),
+            ("group.id".to_string(), "repl".to_string()),
+        ]))
+        .await?;
+    ctx.from_topic(source_topic).await?;
+
+    let mut repl = Repl::new(ctx);
+    let cancel = repl.cancel_handle();
+    // Dropping the task aborts it, keep it around for as long as the shell runs
+    let _ctrl_c = SpawnedTask::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            cancel.notify_waiters();
+        }
+    });
+
+    repl.run(
+        tokio::io::BufReader::new(tokio::io::stdin()),
+        &mut std::io::stdout(),
+    )
+    .await
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+pub mod nexmark;
+
 #[derive(Serialize, Deserialize)]
 pub struct Measurment {
     pub occurred_at_ms: u64,
@@ -0,0 +1,378 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Int64Builder, StringBuilder};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use datafusion::error::Result;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+pub const FIRST_PERSON_ID: i64 = 1000;
+pub const FIRST_AUCTION_ID: i64 = 1000;
+pub const FIRST_CATEGORY_ID: i64 = 10;
+const NUM_CATEGORIES: i64 = 5;
+
+// Out of every 50 events one is a new person, three are new auctions and the rest are bids
+const PERSON_PROPORTION: u64 = 1;
+const AUCTION_PROPORTION: u64 = 3;
+const TOTAL_PROPORTION: u64 = 50;
+
+// One in `ratio` bids goes to a random recent auction or comes from a random recent person,
+// the others to the hot ones
+const HOT_AUCTION_RATIO: u64 = 2;
+const HOT_SELLER_RATIO: u64 = 4;
+const HOT_BIDDER_RATIO: u64 = 4;
+const NUM_ACTIVE_PEOPLE: i64 = 1000;
+const NUM_IN_FLIGHT_AUCTIONS: i64 = 100;
+
+const FIRST_NAMES: &[&str] = &[
+    "Peter", "Paul", "Luke", "John", "Saul", "Vicky", "Kate", "Julie", "Sarah", "Deiter", "Walter",
+];
+const LAST_NAMES: &[&str] = &[
+    "Shultz", "Abrams", "Spencer", "White", "Bartels", "Walton", "Smith", "Jones", "Noris",
+];
+const US_STATES: &[&str] = &["AZ", "CA", "ID", "OR", "WA", "WY"];
+const US_CITIES: &[&str] = &[
+    "Phoenix",
+    "Los Angeles",
+    "San Francisco",
+    "Boise",
+    "Portland",
+    "Bend",
+    "Redmond",
+    "Seattle",
+    "Kent",
+    "Cheyenne",
+];
+const CHANNELS: &[&str] = &["Google", "Facebook", "Baidu", "Apple"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Person,
+    Auction,
+    Bid,
+}
+
+impl EventKind {
+    /// The kind of the event numbered `event`, the same for every generator
+    pub fn of(event: u64) -> Self {
+        match event % TOTAL_PROPORTION {
+            offset if offset < PERSON_PROPORTION => Self::Person,
+            offset if offset < PERSON_PROPORTION + AUCTION_PROPORTION => Self::Auction,
+            _ => Self::Bid,
+        }
+    }
+
+    /// The table the events are read from in the queries
+    pub fn table(&self) -> &'static str {
+        match self {
+            Self::Person => "person",
+            Self::Auction => "auction",
+            Self::Bid => "bid",
+        }
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        let long = |name: &str| Field::new(name, DataType::Int64, false);
+        let string = |name: &str| Field::new(name, DataType::Utf8, false);
+        let fields = match self {
+            Self::Person => vec![
+                long("id"),
+                string("name"),
+                string("email_address"),
+                string("credit_card"),
+                string("city"),
+                string("state"),
+                long("date_time"),
+                string("extra"),
+            ],
+            Self::Auction => vec![
+                long("id"),
+                string("item_name"),
+                string("description"),
+                long("initial_bid"),
+                long("reserve"),
+                long("date_time"),
+                long("expires"),
+                long("seller"),
+                long("category"),
+                string("extra"),
+            ],
+            Self::Bid => vec![
+                long("auction"),
+                long("bidder"),
+                long("price"),
+                string("channel"),
+                string("url"),
+                long("date_time"),
+                string("extra"),
+            ],
+        };
+        Arc::new(Schema::new(fields))
+    }
+}
+
+/// Shape of the generated event stream
+#[derive(Debug, Clone)]
+pub struct NexmarkConfig {
+    /// Events of all kinds generated per second, as fast as possible without
+    pub events_per_second: Option<u64>,
+    /// Events of all kinds to generate before the sources end, endless without
+    pub max_events: Option<u64>,
+    /// How long auctions stay open, bids only win while it is
+    pub auction_length_ms: i64,
+    pub seed: u64,
+    /// Events generated so far by all sources, for the runner's throughput
+    pub generated: Arc<AtomicU64>,
+}
+
+impl Default for NexmarkConfig {
+    fn default() -> Self {
+        Self {
+            events_per_second: Some(10_000),
+            max_events: None,
+            auction_length_ms: 10_000,
+            seed: 0,
+            generated: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+/// Generates the events of one kind. Every field of an event is derived from the seed and
+/// the event number, so the person, auction and bid sources, and any number of copies of
+/// them read by self joins, agree on the ids without sharing state.
+#[derive(Debug, Clone)]
+pub struct NexmarkGenerator {
+    kind: EventKind,
+    config: NexmarkConfig,
+}
+
+impl NexmarkGenerator {
+    pub fn new(kind: EventKind, config: NexmarkConfig) -> Self {
+        Self { kind, config }
+    }
+
+    /// The events of this generator's kind among `events`, `date_time` giving the time of
+    /// each event number in milliseconds
+    pub fn generate(
+        &self,
+        events: Range<u64>,
+        date_time: impl Fn(u64) -> i64,
+    ) -> Result<RecordBatch> {
+        let events: Vec<u64> = events
+            .filter(|event| EventKind::of(*event) == self.kind)
+            .collect();
+        self.config
+            .generated
+            .fetch_add(events.len() as u64, Ordering::Relaxed);
+        let rng = |event: u64| {
+            StdRng::seed_from_u64(self.config.seed ^ event.wrapping_mul(0x9e3779b97f4a7c15))
+        };
+
+        let columns = match self.kind {
+            EventKind::Person => self.people(&events, rng, date_time),
+            EventKind::Auction => self.auctions(&events, rng, date_time),
+            EventKind::Bid => self.bids(&events, rng, date_time),
+        };
+        Ok(RecordBatch::try_new(self.kind.schema(), columns)?)
+    }
+
+    fn people(
+        &self,
+        events: &[u64],
+        rng: impl Fn(u64) -> StdRng,
+        date_time: impl Fn(u64) -> i64,
+    ) -> Vec<ArrayRef> {
+        let mut id = Int64Builder::new();
+        let mut name = StringBuilder::new();
+        let mut email_address = StringBuilder::new();
+        let mut credit_card = StringBuilder::new();
+        let mut city = StringBuilder::new();
+        let mut state = StringBuilder::new();
+        let mut time = Int64Builder::new();
+        let mut extra = StringBuilder::new();
+        for event in events {
+            let mut rng = rng(*event);
+            let full_name = format!(
+                "{} {}",
+                FIRST_NAMES.choose(&mut rng).unwrap(),
+                LAST_NAMES.choose(&mut rng).unwrap()
+            );
+            id.append_value(last_person_id(*event) + FIRST_PERSON_ID);
+            email_address.append_value(format!(
+                "{}@{}.com",
+                full_name.replace(' ', "."),
+                random_word(&mut rng)
+            ));
+            name.append_value(full_name);
+            credit_card.append_value(format!(
+                "{:04} {:04} {:04} {:04}",
+                rng.gen_range(0..10_000),
+                rng.gen_range(0..10_000),
+                rng.gen_range(0..10_000),
+                rng.gen_range(0..10_000)
+            ));
+            city.append_value(US_CITIES.choose(&mut rng).unwrap());
+            state.append_value(US_STATES.choose(&mut rng).unwrap());
+            time.append_value(date_time(*event));
+            extra.append_value(random_word(&mut rng));
+        }
+        vec![
+            Arc::new(id.finish()),
+            Arc::new(name.finish()),
+            Arc::new(email_address.finish()),
+            Arc::new(credit_card.finish()),
+            Arc::new(city.finish()),
+            Arc::new(state.finish()),
+            Arc::new(time.finish()),
+            Arc::new(extra.finish()),
+        ]
+    }
+
+    fn auctions(
+        &self,
+        events: &[u64],
+        rng: impl Fn(u64) -> StdRng,
+        date_time: impl Fn(u64) -> i64,
+    ) -> Vec<ArrayRef> {
+        let mut id = Int64Builder::new();
+        let mut item_name = StringBuilder::new();
+        let mut description = StringBuilder::new();
+        let mut initial_bid = Int64Builder::new();
+        let mut reserve = Int64Builder::new();
+        let mut time = Int64Builder::new();
+        let mut expires = Int64Builder::new();
+        let mut seller = Int64Builder::new();
+        let mut category = Int64Builder::new();
+        let mut extra = StringBuilder::new();
+        for event in events {
+            let mut rng = rng(*event);
+            let last_person = last_person_id(*event);
+            let bid = next_price(&mut rng);
+            let created = date_time(*event);
+            id.append_value(last_auction_id(*event) + FIRST_AUCTION_ID);
+            item_name.append_value(random_word(&mut rng));
+            description.append_value(random_word(&mut rng));
+            initial_bid.append_value(bid);
+            reserve.append_value(bid + next_price(&mut rng));
+            time.append_value(created);
+            expires.append_value(created + self.config.auction_length_ms);
+            seller.append_value(
+                hot_or_recent(&mut rng, last_person, HOT_SELLER_RATIO, NUM_ACTIVE_PEOPLE)
+                    + FIRST_PERSON_ID,
+            );
+            category.append_value(FIRST_CATEGORY_ID + rng.gen_range(0..NUM_CATEGORIES));
+            extra.append_value(random_word(&mut rng));
+        }
+        vec![
+            Arc::new(id.finish()),
+            Arc::new(item_name.finish()),
+            Arc::new(description.finish()),
+            Arc::new(initial_bid.finish()),
+            Arc::new(reserve.finish()),
+            Arc::new(time.finish()),
+            Arc::new(expires.finish()),
+            Arc::new(seller.finish()),
+            Arc::new(category.finish()),
+            Arc::new(extra.finish()),
+        ]
+    }
+
+    fn bids(
+        &self,
+        events: &[u64],
+        rng: impl Fn(u64) -> StdRng,
+        date_time: impl Fn(u64) -> i64,
+    ) -> Vec<ArrayRef> {
+        let mut auction = Int64Builder::new();
+        let mut bidder = Int64Builder::new();
+        let mut price = Int64Builder::new();
+        let mut channel = StringBuilder::new();
+        let mut url = StringBuilder::new();
+        let mut time = Int64Builder::new();
+        let mut extra = StringBuilder::new();
+        for event in events {
+            let mut rng = rng(*event);
+            auction.append_value(
+                hot_or_recent(
+                    &mut rng,
+                    last_auction_id(*event),
+                    HOT_AUCTION_RATIO,
+                    NUM_IN_FLIGHT_AUCTIONS,
+                ) + FIRST_AUCTION_ID,
+            );
+            bidder.append_value(
+                hot_or_recent(
+                    &mut rng,
+                    last_person_id(*event),
+                    HOT_BIDDER_RATIO,
+                    NUM_ACTIVE_PEOPLE,
+                ) + FIRST_PERSON_ID,
+            );
+            price.append_value(next_price(&mut rng));
+            channel.append_value(CHANNELS.choose(&mut rng).unwrap());
+            url.append_value(format!(
+                "https://www.nexmark.com/{}/item.htm?query=1",
+                random_word(&mut rng)
+            ));
+            time.append_value(date_time(*event));
+            extra.append_value(random_word(&mut rng));
+        }
+        vec![
+            Arc::new(auction.finish()),
+            Arc::new(bidder.finish()),
+            Arc::new(price.finish()),
+            Arc::new(channel.finish()),
+            Arc::new(url.finish()),
+            Arc::new(time.finish()),
+            Arc::new(extra.finish()),
+        ]
+    }
+}
+
+/// The zero based id of the latest person created at or before `event`
+fn last_person_id(event: u64) -> i64 {
+    let epoch = event / TOTAL_PROPORTION;
+    let offset = (event % TOTAL_PROPORTION).min(PERSON_PROPORTION - 1);
+    (epoch * PERSON_PROPORTION + offset) as i64
+}
+
+/// The zero based id of the latest auction created at or before `event`
+fn last_auction_id(event: u64) -> i64 {
+    let epoch = (event / TOTAL_PROPORTION) as i64;
+    let offset = event % TOTAL_PROPORTION;
+    let (epoch, offset) = if offset < PERSON_PROPORTION {
+        (epoch - 1, AUCTION_PROPORTION - 1)
+    } else {
+        (
+            epoch,
+            (offset - PERSON_PROPORTION).min(AUCTION_PROPORTION - 1),
+        )
+    };
+    (epoch * AUCTION_PROPORTION as i64 + offset as i64).max(0)
+}
+
+/// Mostly the hot id at or below `last`, which is the same for `ratio` ids in a row, else one
+/// of the `window` ids up to `last`
+fn hot_or_recent(rng: &mut StdRng, last: i64, ratio: u64, window: i64) -> i64 {
+    if rng.gen_range(0..ratio) > 0 {
+        (last / ratio as i64) * ratio as i64
+    } else {
+        (last - rng.gen_range(0..window)).max(0)
+    }
+}
+
+/// Prices in cents, spread over six orders of magnitude
+fn next_price(rng: &mut StdRng) -> i64 {
+    (10_f64.powf(rng.gen::<f64>() * 6.0) * 100.0).round() as i64
+}
+
+fn random_word(rng: &mut StdRng) -> String {
+    let len = rng.gen_range(3..10);
+    (0..len)
+        .map(|_| rng.gen_range(b'a'..=b'z') as char)
+        .collect()
+}
@@ -0,0 +1,11 @@
+//! The [Nexmark](https://datalab.cs.pdx.edu/niagara/NEXMark/) benchmark: an online auction
+//! where people sign up, open auctions and bid on them, with the standard queries over it.
+//! Run them with the `nexmark` example.
+
+pub mod generator;
+pub mod queries;
+pub mod source;
+
+pub use generator::{EventKind, NexmarkConfig, NexmarkGenerator};
+pub use queries::{nexmark_query, QUERIES};
+pub use source::NexmarkSource;
@@ -0,0 +1,329 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::{Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use datafusion::common::{plan_err, JoinType};
+use datafusion::error::Result;
+use datafusion::functions::datetime::expr_fn::{to_char, to_timestamp_millis};
+use datafusion::functions_aggregate::average::avg;
+use datafusion::functions_aggregate::count::count;
+use datafusion::functions_aggregate::expr_fn::{max, min};
+use datafusion::logical_expr::{col, lit};
+
+use denormalized::context::Context;
+use denormalized::datastream::DataStream;
+
+use super::generator::{EventKind, NexmarkConfig};
+use super::source::NexmarkSource;
+
+/// The queries [`nexmark_query`] knows
+pub const QUERIES: &[&str] = &[
+    "q0", "q1", "q2", "q3", "q4", "q5", "q6", "q7", "q8", "q9", "q10", "q11", "q12", "q13",
+];
+
+/// Rows of the static table bids are joined with in q13
+const SIDE_INPUT_ROWS: i64 = 10_000;
+
+/// Plan the Nexmark query `name` over sources generating events as `config` says.
+///
+/// The queries follow the Nexmark suite, with auction lifetimes, "last 10 auctions" and the
+/// like expressed with the windows denormalized has:
+///
+/// * q0: pass bids through
+/// * q1: convert bid prices to euros
+/// * q2: bids on every 123rd auction
+/// * q3: people in Oregon, Idaho or California selling in category 10
+/// * q4: average winning price per category
+/// * q5: the auctions with the most bids over a sliding window
+/// * q6: average winning price of every seller's last 10 auctions
+/// * q7: the highest bids of every window
+/// * q8: people who created an auction in the window they signed up in
+/// * q9: the winning bid of every auction
+/// * q10: bids with the date and time partitions a file system sink would use
+/// * q11: bids per bidder session
+/// * q12: bids per bidder in processing time windows
+/// * q13: bids joined with a static side input
+pub async fn nexmark_query(
+    ctx: &Context,
+    name: &str,
+    config: &NexmarkConfig,
+) -> Result<DataStream> {
+    let window = Duration::from_secs(10);
+    // Self joins read a second copy of a source, under a table name of its own
+    let source = |kind: EventKind, copy: &'static str| async move {
+        let source = NexmarkSource::try_new(kind, config.clone())?;
+        let table = format!("{}{copy}", kind.table());
+        ctx.from_source(&table, Arc::new(source)).await
+    };
+
+    match name {
+        "q0" => source(EventKind::Bid, "").await,
+        "q1" => source(EventKind::Bid, "").await?.select(vec![
+            col("auction"),
+            col("bidder"),
+            (lit(0.908) * col("price")).alias("price"),
+            col("date_time"),
+        ]),
+        "q2" => source(EventKind::Bid, "")
+            .await?
+            .filter((col("auction") % lit(123_i64)).eq(lit(0_i64)))?
+            .select(vec![col("auction"), col("price")]),
+        "q3" => {
+            let auctions = source(EventKind::Auction, "")
+                .await?
+                .filter(col("category").eq(lit(10_i64)))?
+                .select(vec![col("id").alias("auction_id"), col("seller")])?;
+            source(EventKind::Person, "")
+                .await?
+                .filter(col("state").in_list(vec![lit("OR"), lit("ID"), lit("CA")], false))?
+                .select(vec![
+                    col("id").alias("person_id"),
+                    col("name"),
+                    col("city"),
+                    col("state"),
+                ])?
+                .join(auctions, JoinType::Inner, &["person_id"], &["seller"], None)?
+                .select(vec![
+                    col("name"),
+                    col("city"),
+                    col("state"),
+                    col("auction_id"),
+                ])
+        }
+        "q4" => winning_bids(ctx, config).await?.window(
+            vec![col("category")],
+            vec![avg(col("final")).alias("average_price")],
+            window,
+            None,
+        ),
+        "q5" => {
+            let bids_per_auction = |copy| async move {
+                source(EventKind::Bid, copy)
+                    .await?
+                    .window(
+                        vec![col("auction")],
+                        vec![count(lit(1)).alias("num")],
+                        window,
+                        Some(Duration::from_secs(2)),
+                    )?
+                    .select(vec![
+                        col("auction"),
+                        col("num"),
+                        col("window_start_time").alias("starts"),
+                    ])
+            };
+            let hottest = bids_per_auction("_hottest")
+                .await?
+                .window(
+                    vec![col("starts")],
+                    vec![max(col("num")).alias("max_num")],
+                    Duration::from_secs(2),
+                    None,
+                )?
+                .select(vec![col("starts").alias("max_starts"), col("max_num")])?;
+            bids_per_auction("")
+                .await?
+                .join(
+                    hottest,
+                    JoinType::Inner,
+                    &["starts", "num"],
+                    &["max_starts", "max_num"],
+                    None,
+                )?
+                .select(vec![col("auction"), col("num"), col("starts")])
+        }
+        "q6" => winning_bids(ctx, config).await?.count_window(
+            vec![col("seller")],
+            vec![avg(col("final")).alias("average_price")],
+            10,
+            None,
+        ),
+        "q7" => {
+            let highest = source(EventKind::Bid, "_highest")
+                .await?
+                .window(
+                    vec![],
+                    vec![max(col("price")).alias("max_price")],
+                    window,
+                    None,
+                )?
+                .select(vec![
+                    col("max_price"),
+                    col("window_start_time").alias("max_starts"),
+                ])?;
+            source(EventKind::Bid, "")
+                .await?
+                .window(
+                    vec![col("auction"), col("bidder"), col("price")],
+                    vec![count(lit(1)).alias("bids")],
+                    window,
+                    None,
+                )?
+                .join(
+                    highest,
+                    JoinType::Inner,
+                    &["price", "window_start_time"],
+                    &["max_price", "max_starts"],
+                    None,
+                )?
+                .select(vec![
+                    col("auction"),
+                    col("bidder"),
+                    col("price"),
+                    col("window_start_time"),
+                ])
+        }
+        "q8" => {
+            let sellers = source(EventKind::Auction, "")
+                .await?
+                .window(
+                    vec![col("seller")],
+                    vec![count(lit(1)).alias("auctions")],
+                    window,
+                    None,
+                )?
+                .select(vec![
+                    col("seller"),
+                    col("window_start_time").alias("auction_window"),
+                ])?;
+            source(EventKind::Person, "")
+                .await?
+                .window(
+                    vec![col("id"), col("name")],
+                    vec![min(col("date_time")).alias("signed_up")],
+                    window,
+                    None,
+                )?
+                .join(
+                    sellers,
+                    JoinType::Inner,
+                    &["id", "window_start_time"],
+                    &["seller", "auction_window"],
+                    None,
+                )?
+                .select(vec![col("id"), col("name"), col("window_start_time")])
+        }
+        "q9" => winning_bids(ctx, config).await,
+        "q10" => {
+            let time = to_timestamp_millis(vec![col("date_time")]);
+            source(EventKind::Bid, "").await?.select(vec![
+                col("auction"),
+                col("bidder"),
+                col("price"),
+                col("date_time"),
+                col("extra"),
+                to_char(time.clone(), lit("%Y-%m-%d")).alias("dt"),
+                to_char(time, lit("%H:%M")).alias("hm"),
+            ])
+        }
+        "q11" => source(EventKind::Bid, "").await?.session_window(
+            vec![col("bidder")],
+            vec![count(lit(1)).alias("bid_count")],
+            lit(window.as_millis() as i64),
+        ),
+        "q12" => {
+            let bids = NexmarkSource::try_new(EventKind::Bid, config.clone())?.processing_time()?;
+            ctx.from_source("bid", Arc::new(bids)).await?.window(
+                vec![col("bidder")],
+                vec![count(lit(1)).alias("bid_count")],
+                window,
+                None,
+            )
+        }
+        "q13" => {
+            let side_input = ctx.session_conext.read().await.read_batch(side_input()?)?;
+            source(EventKind::Bid, "")
+                .await?
+                .select(vec![
+                    col("auction"),
+                    col("bidder"),
+                    col("price"),
+                    col("date_time"),
+                    (col("auction") % lit(SIDE_INPUT_ROWS)).alias("auction_key"),
+                ])?
+                .join(
+                    side_input,
+                    JoinType::Inner,
+                    &["auction_key"],
+                    &["key"],
+                    None,
+                )?
+                .select(vec![
+                    col("auction"),
+                    col("bidder"),
+                    col("price"),
+                    col("date_time"),
+                    col("value"),
+                ])
+        }
+        other => plan_err!(
+            "Unknown Nexmark query {other}, expected one of {}",
+            QUERIES.join(", ")
+        ),
+    }
+}
+
+/// The highest bid of every auction among the bids placed while it was open, with the
+/// auction's category and seller. Auctions are closed by a window the length of an auction.
+async fn winning_bids(ctx: &Context, config: &NexmarkConfig) -> Result<DataStream> {
+    let auctions = ctx
+        .from_source(
+            "auction",
+            Arc::new(NexmarkSource::try_new(EventKind::Auction, config.clone())?),
+        )
+        .await?
+        .select(vec![
+            col("id").alias("auction_id"),
+            col("category"),
+            col("seller"),
+            col("date_time").alias("opened"),
+            col("expires"),
+        ])?;
+    ctx.from_source(
+        "bid",
+        Arc::new(NexmarkSource::try_new(EventKind::Bid, config.clone())?),
+    )
+    .await?
+    .select(vec![
+        col("auction"),
+        col("price"),
+        col("date_time").alias("placed"),
+    ])?
+    .join(
+        auctions,
+        JoinType::Inner,
+        &["auction"],
+        &["auction_id"],
+        Some(col("placed").between(col("opened"), col("expires"))),
+    )?
+    .window(
+        vec![col("auction_id"), col("category"), col("seller")],
+        vec![max(col("price")).alias("final")],
+        Duration::from_millis(config.auction_length_ms as u64),
+        None,
+    )?
+    .select(vec![
+        col("auction_id"),
+        col("category"),
+        col("seller"),
+        col("final"),
+    ])
+}
+
+fn side_input() -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("key", DataType::Int64, false),
+        Field::new("value", DataType::Utf8, false),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int64Array::from_iter_values(0..SIDE_INPUT_ROWS)),
+            Arc::new(StringArray::from_iter_values(
+                (0..SIDE_INPUT_ROWS).map(|key| format!("value_{key}")),
+            )),
+        ],
+    )?)
+}
@@ -0,0 +1,143 @@
+use std::any::Any;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use datafusion::catalog::Session;
+use datafusion::datasource::TableProvider;
+use datafusion::error::Result;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::ExecutionPlan;
+
+use denormalized::datasource::message::{now_ms, JsonMessageDecoder, MAX_BATCH_MESSAGES};
+use denormalized::physical_plan::utils::time::TimestampUnit;
+use denormalized::utils::json_format::JsonFormatOptions;
+
+use super::generator::{EventKind, NexmarkConfig, NexmarkGenerator};
+
+/// Reads the generated events of one kind, registered with
+/// [`Context::from_source`](denormalized::context::Context::from_source).
+///
+/// Events are paced to the configured rate and their `date_time` is when they were due, which
+/// is also their event time. With [`Self::processing_time`] the arrival time is used instead.
+pub struct NexmarkSource {
+    generator: NexmarkGenerator,
+    config: NexmarkConfig,
+    decoder: JsonMessageDecoder,
+}
+
+impl NexmarkSource {
+    pub fn try_new(kind: EventKind, config: NexmarkConfig) -> Result<Self> {
+        let decoder = JsonMessageDecoder::try_new(
+            kind.schema(),
+            JsonFormatOptions::default(),
+            Some(("date_time".to_string(), TimestampUnit::Int64Millis)),
+        )?;
+        Ok(Self {
+            generator: NexmarkGenerator::new(kind, config.clone()),
+            config,
+            decoder,
+        })
+    }
+
+    /// Window on the time events were read rather than their `date_time`
+    pub fn processing_time(mut self) -> Result<Self> {
+        self.decoder = JsonMessageDecoder::try_new(
+            self.decoder.message_schema(),
+            JsonFormatOptions::default(),
+            None,
+        )?;
+        Ok(self)
+    }
+}
+
+#[async_trait]
+impl TableProvider for NexmarkSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.decoder.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let partition = NexmarkPartition {
+            generator: self.generator.clone(),
+            config: self.config.clone(),
+            decoder: self.decoder.clone(),
+            schema: self.decoder.schema(),
+        };
+        Ok(Arc::new(StreamingTableExec::try_new(
+            self.decoder.schema(),
+            vec![Arc::new(partition)],
+            projection,
+            vec![],
+            true,
+            None,
+        )?))
+    }
+}
+
+struct NexmarkPartition {
+    generator: NexmarkGenerator,
+    config: NexmarkConfig,
+    decoder: JsonMessageDecoder,
+    schema: SchemaRef,
+}
+
+impl PartitionStream for NexmarkPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStreamBuilder::new(self.schema.clone(), 1);
+        let tx = builder.tx();
+        let generator = self.generator.clone();
+        let decoder = self.decoder.clone();
+        let rate = self.config.events_per_second;
+        let max_events = self.config.max_events.unwrap_or(u64::MAX);
+
+        builder.spawn(async move {
+            let started_ms = now_ms();
+            let due_ms = |event: u64| match rate {
+                Some(rate) => started_ms + (event as f64 * 1000.0 / rate as f64) as i64,
+                None => now_ms(),
+            };
+            let mut next = 0;
+            while next < max_events {
+                let end = (next + MAX_BATCH_MESSAGES as u64).min(max_events);
+                let wait_ms = due_ms(end - 1) - now_ms();
+                if wait_ms > 0 {
+                    tokio::time::sleep(Duration::from_millis(wait_ms as u64)).await;
+                }
+                let events = generator.generate(next..end, &due_ms)?;
+                next = end;
+                if events.num_rows() == 0 {
+                    continue;
+                }
+                let batch = decoder.decode_batch(events, now_ms())?;
+                if tx.send(Ok(batch)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        builder.build()
+    }
+}